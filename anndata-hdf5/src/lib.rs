@@ -1,21 +1,24 @@
 use anndata::{
     backend::{
-        Backend, BackendData, DatasetOp, DynArrayView, FileOp, GroupOp, LocationOp, ScalarType,
-        WriteConfig,
+        cast_integer_array, cast_integer_scalar, Backend, BackendData, Compression, DatasetOp,
+        DynArrayView, FileOp, GroupOp, LocationOp, ScalarType, WriteConfig,
     },
     data::{ArrayOp, BoundedSelectInfo, DynArray, DynScalar, SelectInfoElem, Shape},
 };
 
-use anyhow::{bail, Result, Ok};
+use anyhow::{anyhow, bail, ensure, Result, Ok};
+use half::f16;
 use hdf5::{
     dataset::Dataset,
     types::IntSize::*,
-    types::{FloatSize, TypeDescriptor, VarLenUnicode},
+    types::{FloatSize, TypeDescriptor, VarLenAscii, VarLenUnicode},
     File, Group, H5Type, Location, Selection,
 };
+use log::warn;
 use ndarray::{Array, ArrayView, RemoveAxis, SliceInfo, ArrayBase};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 ///////////////////////////////////////////////////////////////////////////////
 /// Type definitions
@@ -53,6 +56,58 @@ impl Deref for H5Dataset {
     }
 }
 
+/// Size of the raw data chunk cache of each dataset in a file. HDF5 keeps
+/// only 1 MiB of decompressed chunks per dataset by default, so repeatedly
+/// reading nearby rows of a matrix with larger chunks decompresses the same
+/// chunks over and over.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkCache {
+    /// Total size of the cache in bytes.
+    pub nbytes: usize,
+    /// Number of slots in the hash table of the cache. A prime number about
+    /// 100 times the number of chunks that fit in the cache works best.
+    pub nslots: usize,
+}
+
+impl ChunkCache {
+    /// A cache of `nbytes` bytes, with the number of slots chosen for chunks
+    /// of about 1 MiB.
+    pub fn with_bytes(nbytes: usize) -> Self {
+        let mut nslots = ((nbytes >> 20).max(1) * 100) | 1;
+        while !is_prime(nslots) {
+            nslots += 2;
+        }
+        Self { nbytes, nslots }
+    }
+}
+
+fn is_prime(n: usize) -> bool {
+    n >= 2 && (2..).take_while(|i| i * i <= n).all(|i| n % i != 0)
+}
+
+impl H5 {
+    /// Creates a file in the latest format, which is required to open it in
+    /// SWMR mode later on, see [`Backend::open_swmr`]. Files in this format
+    /// cannot be read by HDF5 versions before 1.10.
+    pub fn create_swmr<P: AsRef<Path>>(path: P) -> Result<H5File> {
+        let file = File::with_options().with_fapl(|p| p.libver_latest()).create(path)?;
+        Ok(H5File(file))
+    }
+
+    /// Opens a file that must exist with the given chunk cache, which applies
+    /// to every dataset opened through the file.
+    pub fn open_with_chunk_cache<P: AsRef<Path>>(
+        path: P,
+        write: bool,
+        cache: ChunkCache,
+    ) -> Result<H5File> {
+        let mut builder = File::with_options();
+        builder.with_fapl(|p| p.chunk_cache(cache.nslots, cache.nbytes, 0.75));
+        let file = if write { builder.open_rw(path)? } else { builder.open(path)? };
+        Ok(H5File(file))
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// Backend implementation
 ///////////////////////////////////////////////////////////////////////////////
@@ -68,10 +123,12 @@ impl Backend for H5 {
     type Dataset = H5Dataset;
 
     fn create<P: AsRef<Path>>(path: P) -> Result<Self::File> {
-        Ok(H5File(File::create(path)?))
+        Ok(File::create(path).map(H5File)?)
     }
 
-    /// Opens a file as read-only, file must exist.
+    /// Opens a file as read-only, file must exist. Read-only handles only take
+    /// a shared lock on the file, so any number of processes can open the same
+    /// file concurrently as long as no process has it open for writing.
     fn open<P: AsRef<Path>>(path: P) -> Result<Self::File> {
         Ok(File::open(path).map(H5File)?)
     }
@@ -80,6 +137,35 @@ impl Backend for H5 {
     fn open_rw<P: AsRef<Path>>(path: P) -> Result<Self::File> {
         Ok(File::open_rw(path).map(H5File)?)
     }
+
+    /// The writer can modify and grow existing datasets, e.g., append rows to
+    /// X, but cannot create new groups or datasets. Readers see the changes
+    /// once the writer flushes them; datasets that are already open need to be
+    /// refreshed with [`H5Dataset::refresh`]. Only files created in the latest
+    /// format, such as those created by [`H5::create_swmr`], can be opened in
+    /// SWMR mode.
+    fn open_swmr<P: AsRef<Path>>(path: P, write: bool) -> Result<Self::File> {
+        use hdf5_sys::{h5f, h5p};
+        let path = path.as_ref();
+        if write {
+            let file = File::with_options().with_fapl(|p| p.libver_latest()).open_rw(path)?;
+            let status = unsafe { h5f::H5Fstart_swmr_write(file.id()) };
+            ensure!(status >= 0, "cannot start SWMR writing on '{}'", path.display());
+            Ok(H5File(file))
+        } else {
+            let name = std::ffi::CString::new(path.to_string_lossy().as_bytes())?;
+            let id = unsafe {
+                h5f::H5Fopen(name.as_ptr(), h5f::H5F_ACC_RDONLY | h5f::H5F_ACC_SWMR_READ, h5p::H5P_DEFAULT)
+            };
+            ensure!(id >= 0, "cannot open '{}' as a SWMR reader", path.display());
+            Ok(H5File(unsafe { hdf5::from_id(id)? }))
+        }
+    }
+
+    /// Hold the reentrant lock that the `hdf5` crate takes for every call.
+    fn locked<T, F: FnOnce() -> T>(f: F) -> T {
+        hdf5::sync(f)
+    }
 }
 
 impl FileOp for H5File {
@@ -89,9 +175,26 @@ impl FileOp for H5File {
         hdf5::Location::filename(&self).into()
     }
 
+    fn flush(&self) -> Result<()> {
+        Ok(self.0.flush()?)
+    }
+
     fn close(self) -> Result<()> {
         Ok(self.0.close()?)
     }
+
+    fn is_swmr(&self) -> bool {
+        use hdf5_sys::h5f;
+        let mut intent = 0;
+        let status = unsafe { h5f::H5Fget_intent(self.id(), &mut intent) };
+        status >= 0 && intent & (h5f::H5F_ACC_SWMR_WRITE | h5f::H5F_ACC_SWMR_READ) != 0
+    }
+
+    /// The identifier of the file, which `H5Iget_file_id` returns for all the
+    /// objects opened through it.
+    fn handle_id(&self) -> Option<u64> {
+        Some(self.id() as u64)
+    }
 }
 
 // Generic GroupOp implementations
@@ -114,6 +217,9 @@ fn new_dataset<T: BackendData>(
     shape: &Shape,
     config: WriteConfig,
 ) -> Result<H5Dataset> {
+    if T::DTYPE == ScalarType::F16 {
+        return new_f16_dataset(group, name, Some((shape, config)));
+    }
     let mut builder = match T::DTYPE {
         ScalarType::U8 => group.new_dataset::<u8>(),
         ScalarType::U16 => group.new_dataset::<u16>(),
@@ -124,17 +230,22 @@ fn new_dataset<T: BackendData>(
         ScalarType::I16 => group.new_dataset::<i16>(),
         ScalarType::I32 => group.new_dataset::<i32>(),
         ScalarType::I64 => group.new_dataset::<i64>(),
+        ScalarType::F16 => unreachable!(),
         ScalarType::F32 => group.new_dataset::<f32>(),  
         ScalarType::F64 => group.new_dataset::<f64>(),
         ScalarType::Bool => group.new_dataset::<bool>(),
         ScalarType::String => group.new_dataset::<VarLenUnicode>(),
     };
 
-    builder = if let Some(compression) = config.compression {
-        builder.deflate(compression)
-        //builder.blosc_blosclz(compression, hdf5::filters::BloscShuffle::Byte)
-    } else {
-        builder
+    builder = match config.compression.map(supported_compression) {
+        None => builder,
+        Some(Compression::Gzip(level)) => builder.deflate(level),
+        #[cfg(feature = "blosc")]
+        Some(Compression::Lz4(level)) => builder.blosc_lz4(level, hdf5::filters::BloscShuffle::Byte),
+        #[cfg(feature = "blosc")]
+        Some(Compression::Zstd(level)) => builder.blosc_zstd(level, hdf5::filters::BloscShuffle::Byte),
+        #[cfg(not(feature = "blosc"))]
+        Some(_) => unreachable!(),
     };
 
     builder = if let Some(s) = config.block_size {
@@ -152,8 +263,252 @@ fn new_dataset<T: BackendData>(
     Ok(H5Dataset(dataset))
 }
 
+/// The IEEE 754 half precision type, which has no predefined HDF5 type. It is
+/// derived from the single precision type in the same way as in h5py.
+fn f16_datatype() -> Result<hdf5_sys::h5i::hid_t> {
+    use hdf5_sys::h5t::*;
+    unsafe {
+        let id = H5Tcopy(*H5T_IEEE_F32LE);
+        ensure!(id >= 0, "cannot create the float16 type");
+        let status = [
+            H5Tset_fields(id, 15, 10, 5, 0, 10),
+            H5Tset_size(id, 2),
+            H5Tset_ebias(id, 15),
+        ];
+        if status.iter().any(|&x| x < 0) {
+            H5Tclose(id);
+            bail!("cannot create the float16 type");
+        }
+        Ok(id)
+    }
+}
+
+fn is_f16(dtype: &hdf5::Datatype) -> bool {
+    dtype.size() == 2 &&
+        unsafe { hdf5_sys::h5t::H5Tget_class(dtype.id()) } == hdf5_sys::h5t::H5T_class_t::H5T_FLOAT
+}
+
+/// Map an HDF5 datatype to the corresponding scalar type.
+fn to_scalar_type(dtype: &hdf5::Datatype) -> Result<ScalarType> {
+    if is_f16(dtype) {
+        return Ok(ScalarType::F16);
+    }
+    let ty = match dtype.to_descriptor()? {
+        TypeDescriptor::Unsigned(U1) => ScalarType::U8,
+        TypeDescriptor::Unsigned(U2) => ScalarType::U16,
+        TypeDescriptor::Unsigned(U4) => ScalarType::U32,
+        TypeDescriptor::Unsigned(U8) => ScalarType::U64,
+        TypeDescriptor::Integer(U1) => ScalarType::I8,
+        TypeDescriptor::Integer(U2) => ScalarType::I16,
+        TypeDescriptor::Integer(U4) => ScalarType::I32,
+        TypeDescriptor::Integer(U8) => ScalarType::I64,
+        TypeDescriptor::Float(FloatSize::U4) => ScalarType::F32,
+        TypeDescriptor::Float(FloatSize::U8) => ScalarType::F64,
+        TypeDescriptor::Boolean => ScalarType::Bool,
+        TypeDescriptor::VarLenAscii => ScalarType::String,
+        TypeDescriptor::VarLenUnicode => ScalarType::String,
+        TypeDescriptor::FixedAscii(_) | TypeDescriptor::FixedUnicode(_) => ScalarType::String,
+        ty => bail!("Unsupported type: {:?}", ty),
+    };
+    Ok(ty)
+}
+
+/// hdf5-rust cannot create datasets of types other than those implementing
+/// `H5Type`, so float16 datasets are created through the C API. Values are
+/// read and written as `f32`, and converted by HDF5 from and to the 16-bit
+/// type in the file. A scalar dataset is created if `shape` is `None`.
+fn new_f16_dataset(
+    group: &Group,
+    name: &str,
+    shape: Option<(&Shape, WriteConfig)>,
+) -> Result<H5Dataset> {
+    use hdf5_sys::{h5::hsize_t, h5d, h5i::hid_t, h5p, h5s, h5t};
+    let c_name = std::ffi::CString::new(name)?;
+    let dtype = f16_datatype()?;
+    // The ids are closed below whether or not the dataset is created.
+    let mut space: hid_t = -1;
+    let mut dcpl: hid_t = h5p::H5P_DEFAULT;
+    let result = (|| -> Result<hid_t> {
+        unsafe {
+            match shape {
+                None => space = h5s::H5Screate(h5s::H5S_class_t::H5S_SCALAR),
+                Some((shape, config)) => {
+                    let dims: Vec<hsize_t> = shape.as_ref().iter().map(|&x| x as hsize_t).collect();
+                    let max_dims = vec![h5s::H5S_UNLIMITED; dims.len()];
+                    let chunk: Vec<hsize_t> = match config.block_size {
+                        Some(s) if s.ndim() == dims.len() && s.as_ref().iter().all(|&x| x > 0) => {
+                            s.as_ref().iter().map(|&x| x as hsize_t).collect()
+                        }
+                        _ => default_chunk(shape),
+                    };
+                    dcpl = h5p::H5Pcreate(*h5p::H5P_CLS_DATASET_CREATE);
+                    ensure!(dcpl >= 0, "cannot create the properties of dataset '{}'", name);
+                    ensure!(
+                        h5p::H5Pset_chunk(dcpl, chunk.len() as _, chunk.as_ptr()) >= 0,
+                        "cannot set the chunk shape of dataset '{}'", name,
+                    );
+                    let status = match config.compression.map(supported_compression) {
+                        None => 0,
+                        Some(Compression::Gzip(level)) => h5p::H5Pset_deflate(dcpl, level as _),
+                        Some(compression) => {
+                            // The first four values are filled in by the filter
+                            // when the dataset is created.
+                            let compressor = match compression {
+                                Compression::Lz4(_) => 1,
+                                _ => 5, // zstd
+                            };
+                            let values = [0, 0, 0, 0, compression.level() as _, 1, compressor];
+                            h5p::H5Pset_filter(
+                                dcpl, BLOSC_FILTER_ID, hdf5_sys::h5z::H5Z_FLAG_OPTIONAL,
+                                values.len(), values.as_ptr(),
+                            )
+                        }
+                    };
+                    ensure!(status >= 0, "cannot set the compression of dataset '{}'", name);
+                    space = h5s::H5Screate_simple(dims.len() as _, dims.as_ptr(), max_dims.as_ptr());
+                }
+            }
+            ensure!(space >= 0, "cannot create the dataspace of dataset '{}'", name);
+            let id = h5d::H5Dcreate2(
+                group.id(), c_name.as_ptr(), dtype, space,
+                h5p::H5P_DEFAULT, dcpl, h5p::H5P_DEFAULT,
+            );
+            ensure!(id >= 0, "cannot create dataset '{}'", name);
+            Ok(id)
+        }
+    })();
+    unsafe {
+        if space >= 0 {
+            h5s::H5Sclose(space);
+        }
+        h5t::H5Tclose(dtype);
+        if dcpl >= 0 && dcpl != h5p::H5P_DEFAULT {
+            h5p::H5Pclose(dcpl);
+        }
+    }
+    let id = result?;
+    Ok(H5Dataset(unsafe { hdf5::from_id(id)? }))
+}
+
+/// Chunks of at most 1 MiB, obtained by halving the largest dimension.
+fn default_chunk(shape: &Shape) -> Vec<hdf5_sys::h5::hsize_t> {
+    let mut chunk: Vec<_> = shape.as_ref().iter().map(|&x| x.max(1) as hdf5_sys::h5::hsize_t).collect();
+    while chunk.iter().product::<hdf5_sys::h5::hsize_t>() * 2 > 1 << 20 {
+        let i = (0..chunk.len()).max_by_key(|&i| chunk[i]).unwrap();
+        chunk[i] = (chunk[i] + 1) / 2;
+    }
+    chunk
+}
+
 fn open_dataset(group: &Group, name: &str) -> Result<H5Dataset> {
-    Ok(H5Dataset(group.dataset(name)?))
+    let dataset = group.dataset(name)?;
+    warn_missing_filters(&dataset);
+    Ok(H5Dataset(dataset))
+}
+
+/// The id of the blosc filter registered with the HDF Group.
+const BLOSC_FILTER_ID: hdf5_sys::h5z::H5Z_filter_t = 32001;
+
+#[cfg(feature = "blosc")]
+fn blosc_available() -> bool {
+    hdf5::filters::blosc_available()
+}
+
+#[cfg(not(feature = "blosc"))]
+fn blosc_available() -> bool {
+    false
+}
+
+/// Replace the blosc based filters with gzip if blosc is not available.
+fn supported_compression(compression: Compression) -> Compression {
+    match compression {
+        Compression::Gzip(_) => compression,
+        _ if blosc_available() => compression,
+        _ => {
+            warn_once(BLOSC_FILTER_ID, || format!(
+                "{:?} compression needs the blosc filter, which is not available, using gzip instead",
+                compression,
+            ));
+            Compression::Gzip(compression.level())
+        }
+    }
+}
+
+/// The filters of the dataset that are not available. LZF is built in; HDF5
+/// looks for other filter plugins in the directories listed in
+/// `HDF5_PLUGIN_PATH`.
+fn missing_filters(dataset: &Dataset) -> Vec<hdf5_sys::h5z::H5Z_filter_t> {
+    use hdf5_sys::{h5d, h5p, h5z};
+    let mut missing = Vec::new();
+    unsafe {
+        let dcpl = h5d::H5Dget_create_plist(dataset.id());
+        if dcpl < 0 {
+            return missing;
+        }
+        for i in 0..h5p::H5Pget_nfilters(dcpl).max(0) {
+            let (mut flags, mut n, mut config) = (0, 0, 0);
+            let id = h5p::H5Pget_filter2(
+                dcpl, i as _, &mut flags, &mut n, std::ptr::null_mut(),
+                0, std::ptr::null_mut(), &mut config,
+            );
+            if id >= 0 && h5z::H5Zfilter_avail(id) <= 0 {
+                missing.push(id);
+            }
+        }
+        h5p::H5Pclose(dcpl);
+    }
+    missing
+}
+
+/// The id of a filter, followed by its name for the registered filters that
+/// are common in h5ad files.
+fn describe_filter(id: hdf5_sys::h5z::H5Z_filter_t) -> String {
+    let name = match id {
+        307 => "bzip2",
+        32000 => "lzf",
+        BLOSC_FILTER_ID => "blosc",
+        32004 => "lz4",
+        32008 => "bitshuffle",
+        32015 => "zstd",
+        _ => return id.to_string(),
+    };
+    format!("{} ({})", id, name)
+}
+
+/// Warn if the dataset is compressed with a filter that is not available,
+/// as reading it will fail.
+fn warn_missing_filters(dataset: &Dataset) {
+    for id in missing_filters(dataset) {
+        warn_once(id, || format!(
+            "'{}' is compressed with HDF5 filter {}, which is not available; \
+            install the filter plugin and set HDF5_PLUGIN_PATH to read it",
+            dataset.name(),
+            describe_filter(id),
+        ));
+    }
+}
+
+/// Name the missing filter in the error of a failed read, if the dataset is
+/// compressed with a filter that is not available.
+fn filter_error(dataset: &Dataset, err: anyhow::Error) -> anyhow::Error {
+    match missing_filters(dataset).first() {
+        Some(&id) => anyhow!(
+            "cannot read '{}', which is compressed with HDF5 filter {} that is not available",
+            dataset.name(),
+            describe_filter(id),
+        ),
+        None => err,
+    }
+}
+
+/// Log a warning about a filter only the first time it occurs.
+fn warn_once<F: FnOnce() -> String>(filter: hdf5_sys::h5z::H5Z_filter_t, message: F) {
+    static WARNED: Mutex<Vec<hdf5_sys::h5z::H5Z_filter_t>> = Mutex::new(Vec::new());
+    let mut warned = WARNED.lock().unwrap();
+    if !warned.contains(&filter) {
+        warned.push(filter);
+        warn!("{}", message());
+    }
 }
 
 fn delete(group: &Group, name: &str) -> Result<()> {
@@ -164,6 +519,33 @@ fn exists(group: &Group, name: &str) -> Result<bool> {
     Ok(group.link_exists(name))
 }
 
+fn copy_from(group: &Group, file: &Path, path: &str, name: &str) -> Result<()> {
+    let source = match hdf5::Location::file(group) {
+        std::result::Result::Ok(f) if Path::new(&f.filename()) == file => f,
+        _ => File::open(file)?,
+    };
+    let src_name = std::ffi::CString::new(path)?;
+    let dst_name = std::ffi::CString::new(name)?;
+    let status = unsafe {
+        hdf5_sys::h5o::H5Ocopy(
+            source.id(),
+            src_name.as_ptr(),
+            group.id(),
+            dst_name.as_ptr(),
+            hdf5_sys::h5p::H5P_DEFAULT,
+            hdf5_sys::h5p::H5P_DEFAULT,
+        )
+    };
+    if status < 0 {
+        bail!("cannot copy '{}' from '{}'", path, file.display());
+    }
+    Ok(())
+}
+
+fn rename(group: &Group, from: &str, to: &str) -> Result<()> {
+    Ok(group.relink(from, to)?)
+}
+
 fn create_scalar_data<D: BackendData>(group: &Group, name: &str, data: &D) -> Result<H5Dataset> {
     match data.into_dyn() {
         DynScalar::U8(x) => {
@@ -211,6 +593,11 @@ fn create_scalar_data<D: BackendData>(group: &Group, name: &str, data: &D) -> Re
             dataset.write_scalar(&x)?;
             Ok(dataset)
         }
+        DynScalar::F16(x) => {
+            let dataset = new_f16_dataset(group, name, None)?;
+            dataset.write_scalar(&x.to_f32())?;
+            return Ok(dataset);
+        }
         DynScalar::F32(x) => {
             let dataset = group.new_dataset::<f32>().create(name)?;
             dataset.write_scalar(&x)?;
@@ -235,27 +622,21 @@ fn create_scalar_data<D: BackendData>(group: &Group, name: &str, data: &D) -> Re
     .map(H5Dataset)
 }
 
+impl H5Dataset {
+    /// Reload the metadata of the dataset, so that a SWMR reader sees the
+    /// changes made by the writer, e.g., a new shape.
+    pub fn refresh(&self) -> Result<()> {
+        let status = unsafe { hdf5_sys::h5d::H5Drefresh(self.id()) };
+        ensure!(status >= 0, "cannot refresh dataset '{}'", self.name());
+        Ok(())
+    }
+}
+
 impl DatasetOp for H5Dataset {
     type Backend = H5;
 
     fn dtype(&self) -> Result<ScalarType> {
-        let ty = match hdf5::Container::dtype(self)?.to_descriptor()? {
-            TypeDescriptor::Unsigned(U1) => ScalarType::U8,
-            TypeDescriptor::Unsigned(U2) => ScalarType::U16,
-            TypeDescriptor::Unsigned(U4) => ScalarType::U32,
-            TypeDescriptor::Unsigned(U8) => ScalarType::U64,
-            TypeDescriptor::Integer(U1) => ScalarType::I8,
-            TypeDescriptor::Integer(U2) => ScalarType::I16,
-            TypeDescriptor::Integer(U4) => ScalarType::I32,
-            TypeDescriptor::Integer(U8) => ScalarType::I64,
-            TypeDescriptor::Float(FloatSize::U4) => ScalarType::F32,
-            TypeDescriptor::Float(FloatSize::U8) => ScalarType::F64,
-            TypeDescriptor::Boolean => ScalarType::Bool,
-            TypeDescriptor::VarLenAscii => ScalarType::String,
-            TypeDescriptor::VarLenUnicode => ScalarType::String,
-            ty => bail!("Unsupported type: {:?}", ty),
-        };
-        Ok(ty)
+        to_scalar_type(&hdf5::Container::dtype(self)?)
     }
 
     fn shape(&self) -> Shape {
@@ -266,6 +647,54 @@ impl DatasetOp for H5Dataset {
         Ok(Dataset::resize(self, shape.as_ref())?)
     }
 
+    fn storage_size(&self) -> Result<u64> {
+        Ok(unsafe { hdf5_sys::h5d::H5Dget_storage_size(self.id()) })
+    }
+
+    fn chunk_shape(&self) -> Option<Shape> {
+        self.deref().chunk().map(Shape::from)
+    }
+
+    fn chunk_cache(&self) -> Result<Option<usize>> {
+        use hdf5_sys::{h5d, h5p};
+        let mut nslots = 0;
+        let mut nbytes = 0;
+        let mut w0 = 0.0;
+        let status = unsafe {
+            let dapl = h5d::H5Dget_access_plist(self.id());
+            ensure!(dapl >= 0, "cannot get the access properties of '{}'", self.path().display());
+            let status = h5p::H5Pget_chunk_cache(dapl, &mut nslots, &mut nbytes, &mut w0);
+            h5p::H5Pclose(dapl);
+            status
+        };
+        ensure!(status >= 0, "cannot get the chunk cache of '{}'", self.path().display());
+        Ok(Some(nbytes))
+    }
+
+    /// HDF5 only applies cache settings when a dataset is opened while no
+    /// other handle to it is open, so this handle is closed first, after
+    /// every step that can fail before reopening it.
+    fn with_chunk_cache(self, nbytes: usize) -> Result<Self> {
+        use hdf5_sys::{h5d, h5p};
+        let cache = ChunkCache::with_bytes(nbytes);
+        let file = self.file()?;
+        let name = std::ffi::CString::new(self.name())?;
+        let dapl = unsafe { h5d::H5Dget_access_plist(self.id()) };
+        ensure!(dapl >= 0, "cannot get the access properties of '{}'", self.path().display());
+        if unsafe { h5p::H5Pset_chunk_cache(dapl, cache.nslots, cache.nbytes, 0.75) } < 0 {
+            unsafe { h5p::H5Pclose(dapl) };
+            bail!("cannot set the chunk cache of '{}'", self.path().display());
+        }
+        drop(self);
+        let id = unsafe {
+            let id = h5d::H5Dopen2(file.id(), name.as_ptr(), dapl);
+            h5p::H5Pclose(dapl);
+            id
+        };
+        ensure!(id >= 0, "cannot reopen '{}'", name.to_string_lossy());
+        Ok(H5Dataset(unsafe { hdf5::from_id(id)? }))
+    }
+
     fn read_scalar<T: BackendData>(&self) -> Result<T> {
         let val = match T::DTYPE {
             ScalarType::Bool => self.deref().read_scalar::<bool>()?.into_dyn(),
@@ -278,6 +707,7 @@ impl DatasetOp for H5Dataset {
             ScalarType::I16 => self.deref().read_scalar::<i16>()?.into_dyn(),
             ScalarType::I32 => self.deref().read_scalar::<i32>()?.into_dyn(),
             ScalarType::I64 => self.deref().read_scalar::<i64>()?.into_dyn(),
+            ScalarType::F16 => f16::from_f32(self.deref().read_scalar::<f32>()?).into_dyn(),
             ScalarType::F32 => self.deref().read_scalar::<f32>()?.into_dyn(),
             ScalarType::F64 => self.deref().read_scalar::<f64>()?.into_dyn(),
             ScalarType::String => {
@@ -317,49 +747,131 @@ impl DatasetOp for H5Dataset {
             }
         }
 
-        let array: DynArray = match T::DTYPE {
-            ScalarType::I8 => read_arr::<i8, _, D>(self, selection)?.into(),
-            ScalarType::I16 => read_arr::<i16, _, D>(self, selection)?.into(),
-            ScalarType::I32 => read_arr::<i32, _, D>(self, selection)?.into(),
-            ScalarType::I64 => read_arr::<i64, _, D>(self, selection)?.into(),
-            ScalarType::U8 => read_arr::<u8, _, D>(self, selection)?.into(),
-            ScalarType::U16 => read_arr::<u16, _, D>(self, selection)?.into(),
-            ScalarType::U32 => read_arr::<u32, _, D>(self, selection)?.into(),
-            ScalarType::U64 => read_arr::<u64, _, D>(self, selection)?.into(),
-            ScalarType::Usize => read_arr::<usize, _, D>(self, selection)?.into(),
-            ScalarType::F32 => read_arr::<f32, _, D>(self, selection)?.into(),
-            ScalarType::F64 => read_arr::<f64, _, D>(self, selection)?.into(),
-            ScalarType::Bool => read_arr::<bool, _, D>(self, selection)?.into(),
-            ScalarType::String => {
-                if selection.as_ref().iter().any(|x| x.as_ref().is_index()) {
-                    // fancy indexing is too slow, just read all
-                    let arr = self.deref().read::<VarLenUnicode, D>()?;
-                    let arr_ = arr.map(|s| s.to_string());
-                    let r: Result<_> = Ok(ArrayOp::select(&arr_, selection));
-                    r
-                } else {
-                    let (select, shape) = into_selection(selection, self.shape());
-                    let arr: Result<_> = if matches!(select, Selection::Points(_)) {
-                        let slice_1d = self.deref().read_slice_1d::<VarLenUnicode, _>(select)?;
-                        Ok(slice_1d
-                            .into_shape(shape.as_ref())?
-                            .into_dimensionality::<D>()?)
+        fn read_dyn<T, S, D>(dataset: &H5Dataset, selection: &[S]) -> Result<DynArray>
+        where
+            T: BackendData,
+            S: AsRef<SelectInfoElem>,
+            D: RemoveAxis,
+        {
+            let array: DynArray = match T::DTYPE {
+                ScalarType::I8 => read_arr::<i8, _, D>(dataset, selection)?.into(),
+                ScalarType::I16 => read_arr::<i16, _, D>(dataset, selection)?.into(),
+                ScalarType::I32 => read_arr::<i32, _, D>(dataset, selection)?.into(),
+                ScalarType::I64 => read_arr::<i64, _, D>(dataset, selection)?.into(),
+                ScalarType::U8 => read_arr::<u8, _, D>(dataset, selection)?.into(),
+                ScalarType::U16 => read_arr::<u16, _, D>(dataset, selection)?.into(),
+                ScalarType::U32 => read_arr::<u32, _, D>(dataset, selection)?.into(),
+                ScalarType::U64 => read_arr::<u64, _, D>(dataset, selection)?.into(),
+                ScalarType::Usize => read_arr::<usize, _, D>(dataset, selection)?.into(),
+                ScalarType::F16 => read_arr::<f32, _, D>(dataset, selection)?.mapv(f16::from_f32).into(),
+                ScalarType::F32 => read_arr::<f32, _, D>(dataset, selection)?.into(),
+                ScalarType::F64 => read_arr::<f64, _, D>(dataset, selection)?.into(),
+                ScalarType::Bool => read_arr::<bool, _, D>(dataset, selection)?.into(),
+                ScalarType::String => {
+                    if selection.as_ref().iter().any(|x| x.as_ref().is_index()) {
+                        // fancy indexing is too slow, just read all
+                        let arr = dataset.deref().read::<VarLenUnicode, D>()?;
+                        let arr_ = arr.map(|s| s.to_string());
+                        let r: Result<_> = Ok(ArrayOp::select(&arr_, selection));
+                        r
                     } else {
-                        Ok(self.deref().read_slice::<VarLenUnicode, _, D>(select)?)
-                    };
-                    Ok(arr?.map(|s| s.to_string()))
-                }?
-                .into()
-                /*
-                let arr = read_arr::<VarLenUnicode, _, _, D>(dataset, selection)?;
-                let arr = arr.map(|s| s.to_string());
-                arr.into()
-                */
-            }
-        };
+                        let (select, shape) = into_selection(selection, dataset.shape());
+                        let arr: Result<_> = if matches!(select, Selection::Points(_)) {
+                            let slice_1d = dataset.deref().read_slice_1d::<VarLenUnicode, _>(select)?;
+                            Ok(slice_1d
+                                .into_shape(shape.as_ref())?
+                                .into_dimensionality::<D>()?)
+                        } else {
+                            Ok(dataset.deref().read_slice::<VarLenUnicode, _, D>(select)?)
+                        };
+                        Ok(arr?.map(|s| s.to_string()))
+                    }?
+                    .into()
+                    /*
+                    let arr = read_arr::<VarLenUnicode, _, _, D>(dataset, selection)?;
+                    let arr = arr.map(|s| s.to_string());
+                    arr.into()
+                    */
+                }
+            };
+            Ok(array)
+        }
+
+        let array = read_dyn::<T, S, D>(self, selection).map_err(|e| filter_error(self, e))?;
         Ok(BackendData::from_dyn_arr(array)?.into_dimensionality::<D>()?)
     }
 
+    /// Numeric data selected by slices is read straight into `out`, letting
+    /// HDF5 convert between the stored and the requested type. Other data
+    /// goes through an intermediate array.
+    fn read_into<T: BackendData, S>(&self, selection: &[S], out: &mut [T]) -> Result<()>
+    where
+        S: AsRef<SelectInfoElem>,
+    {
+        use hdf5::{Dataspace, Datatype};
+        use hdf5_sys::{h5d, h5p};
+
+        let mem_type = match T::DTYPE {
+            ScalarType::I8 => Some(Datatype::from_type::<i8>()?),
+            ScalarType::I16 => Some(Datatype::from_type::<i16>()?),
+            ScalarType::I32 => Some(Datatype::from_type::<i32>()?),
+            ScalarType::I64 => Some(Datatype::from_type::<i64>()?),
+            ScalarType::U8 => Some(Datatype::from_type::<u8>()?),
+            ScalarType::U16 => Some(Datatype::from_type::<u16>()?),
+            ScalarType::U32 => Some(Datatype::from_type::<u32>()?),
+            ScalarType::U64 => Some(Datatype::from_type::<u64>()?),
+            ScalarType::Usize => Some(Datatype::from_type::<usize>()?),
+            ScalarType::F32 => Some(Datatype::from_type::<f32>()?),
+            ScalarType::F64 => Some(Datatype::from_type::<f64>()?),
+            _ => None,
+        };
+        let (select, shape) = into_selection(selection, self.shape());
+        let direct = !selection.iter().any(|x| x.as_ref().is_index())
+            && !matches!(select, Selection::Points(_));
+        match mem_type {
+            Some(mem_type) if direct => {
+                let n: usize = shape.as_ref().iter().product();
+                ensure!(
+                    n == out.len(),
+                    "cannot read {} entries into a buffer of length {}",
+                    n,
+                    out.len(),
+                );
+                if n == 0 {
+                    return Ok(());
+                }
+                let file_space = self.space()?.select(select)?;
+                let mem_space = Dataspace::try_new(n)?;
+                // `T::DTYPE` guarantees that `T` has the layout of `mem_type`.
+                let status = unsafe {
+                    h5d::H5Dread(
+                        self.id(),
+                        mem_type.id(),
+                        mem_space.id(),
+                        file_space.id(),
+                        h5p::H5P_DEFAULT,
+                        out.as_mut_ptr().cast(),
+                    )
+                };
+                if status < 0 {
+                    return Err(filter_error(self, anyhow!("cannot read '{}'", self.path().display())));
+                }
+                Ok(())
+            }
+            _ => {
+                let arr: ndarray::ArrayD<T> = self.read_array_slice(selection)?;
+                ensure!(
+                    arr.len() == out.len(),
+                    "cannot read {} entries into a buffer of length {}",
+                    arr.len(),
+                    out.len(),
+                );
+                out.iter_mut().zip(arr.iter()).for_each(|(o, x)| *o = x.clone());
+                Ok(())
+            }
+        }
+    }
+
     fn write_array_slice<'a, A, S, T, D>(&self, data: A, selection: &[S]) -> Result<()>
     where
         A: Into<ArrayView<'a, T, D>>,
@@ -392,6 +904,7 @@ impl DatasetOp for H5Dataset {
             DynArrayView::I16(x) => write_array_impl(self, x, selection),
             DynArrayView::I32(x) => write_array_impl(self, x, selection),
             DynArrayView::I64(x) => write_array_impl(self, x, selection),
+            DynArrayView::F16(x) => write_array_impl(self, x.mapv(f16::to_f32).view(), selection),
             DynArrayView::F32(x) => write_array_impl(self, x, selection),
             DynArrayView::F64(x) => write_array_impl(self, x, selection),
             DynArrayView::Bool(x) => write_array_impl(self, x, selection),
@@ -430,6 +943,7 @@ where
         DynArrayView::I16(x) => loc.new_attr_builder().with_data(x).create(name)?,
         DynArrayView::I32(x) => loc.new_attr_builder().with_data(x).create(name)?,
         DynArrayView::I64(x) => loc.new_attr_builder().with_data(x).create(name)?,
+        DynArrayView::F16(x) => loc.new_attr_builder().with_data(&x.mapv(f16::to_f32)).create(name)?,
         DynArrayView::F32(x) => loc.new_attr_builder().with_data(x).create(name)?,
         DynArrayView::F64(x) => loc.new_attr_builder().with_data(x).create(name)?,
         DynArrayView::Bool(x) => loc.new_attr_builder().with_data(x).create(name)?,
@@ -462,6 +976,7 @@ fn write_scalar_attr<D: BackendData>(loc: &Location, name: &str, value: D) -> Re
         DynScalar::I16(x) => loc.new_attr::<i16>().create(name)?.write_scalar(&x)?,
         DynScalar::I32(x) => loc.new_attr::<i32>().create(name)?.write_scalar(&x)?,
         DynScalar::I64(x) => loc.new_attr::<i64>().create(name)?.write_scalar(&x)?,
+        DynScalar::F16(x) => loc.new_attr::<f32>().create(name)?.write_scalar(&x.to_f32())?,
         DynScalar::F32(x) => loc.new_attr::<f32>().create(name)?.write_scalar(&x)?,
         DynScalar::F64(x) => loc.new_attr::<f64>().create(name)?.write_scalar(&x)?,
         DynScalar::Bool(x) => loc.new_attr::<bool>().create(name)?.write_scalar(&x)?,
@@ -475,7 +990,34 @@ fn write_scalar_attr<D: BackendData>(loc: &Location, name: &str, value: D) -> Re
 
 fn read_scalar_attr<T: BackendData>(loc: &Location, name: &str) -> Result<T> {
     let attr = loc.attr(name)?;
-    let val = match T::DTYPE {
+    match attr.dtype()?.to_descriptor()? {
+        TypeDescriptor::FixedAscii(_) | TypeDescriptor::FixedUnicode(_) if T::DTYPE == ScalarType::String => {
+            return T::from_dyn(read_fixed_str_attr(&attr, name)?.into_dyn());
+        }
+        TypeDescriptor::VarLenAscii if T::DTYPE == ScalarType::String => {
+            return T::from_dyn(attr.read_scalar::<VarLenAscii>()?.to_string().into_dyn());
+        }
+        _ => {}
+    }
+    match integer_conversion(&attr, T::DTYPE) {
+        Some(ty) => cast_integer_scalar(read_scalar_as(&attr, ty)?),
+        None => T::from_dyn(read_scalar_as(&attr, T::DTYPE)?),
+    }
+}
+
+/// Integer attributes, e.g., `shape`, are written with different widths by
+/// different tools, and flags such as `ordered` as integers or booleans.
+/// Return the stored type if it differs from the requested type `ty` and both
+/// are integer or boolean types, in which case the attribute is read as stored
+/// and converted.
+fn integer_conversion(attr: &hdf5::Attribute, ty: ScalarType) -> Option<ScalarType> {
+    attr.dtype().ok()
+        .and_then(|dtype| to_scalar_type(&dtype).ok())
+        .filter(|stored| *stored != ty && stored.is_integer_or_bool() && ty.is_integer_or_bool())
+}
+
+fn read_scalar_as(attr: &hdf5::Attribute, ty: ScalarType) -> Result<DynScalar> {
+    let val = match ty {
         ScalarType::I8 => attr.read_scalar::<i8>()?.into_dyn(),
         ScalarType::I16 => attr.read_scalar::<i16>()?.into_dyn(),
         ScalarType::I32 => attr.read_scalar::<i32>()?.into_dyn(),
@@ -485,12 +1027,46 @@ fn read_scalar_attr<T: BackendData>(loc: &Location, name: &str) -> Result<T> {
         ScalarType::U32 => attr.read_scalar::<u32>()?.into_dyn(),
         ScalarType::U64 => attr.read_scalar::<u64>()?.into_dyn(),
         ScalarType::Usize => attr.read_scalar::<usize>()?.into_dyn(),
+        ScalarType::F16 => f16::from_f32(attr.read_scalar::<f32>()?).into_dyn(),
         ScalarType::F32 => attr.read_scalar::<f32>()?.into_dyn(),
         ScalarType::F64 => attr.read_scalar::<f64>()?.into_dyn(),
         ScalarType::Bool => attr.read_scalar::<bool>()?.into_dyn(),
         ScalarType::String => attr.read_scalar::<VarLenUnicode>()?.to_string().into_dyn(),
     };
-    T::from_dyn(val)
+    Ok(val)
+}
+
+/// Read a fixed-length string attribute, e.g., an `encoding-version` written
+/// as `numpy.bytes_`. hdf5-rust can only read these if the length is known at
+/// compile time, so the raw bytes are read instead.
+fn read_fixed_str_attr(attr: &hdf5::Attribute, name: &str) -> Result<String> {
+    use hdf5_sys::{h5a, h5t};
+    let mut buf = unsafe {
+        let dtype = h5a::H5Aget_type(attr.id());
+        ensure!(dtype >= 0, "cannot get the type of attribute '{}'", name);
+        let mut buf = vec![0u8; h5t::H5Tget_size(dtype)];
+        let status = h5a::H5Aread(attr.id(), dtype, buf.as_mut_ptr() as *mut _);
+        h5t::H5Tclose(dtype);
+        ensure!(status >= 0, "cannot read attribute '{}'", name);
+        buf
+    };
+    // The string is padded with nulls or spaces.
+    if let Some(end) = buf.iter().position(|&x| x == 0) {
+        buf.truncate(end);
+    }
+    Ok(String::from_utf8(buf)?.trim_end().to_string())
+}
+
+fn attr_dtype(loc: &Location, name: &str) -> Result<Option<ScalarType>> {
+    if loc.attr_names()?.iter().any(|x| x == name) {
+        Ok(Some(to_scalar_type(&loc.attr(name)?.dtype()?)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn attr_names(loc: &Location) -> Result<Vec<String>> {
+    Ok(loc.attr_names()?)
 }
 
 fn read_array_attr<T: BackendData, D: RemoveAxis>(loc: &Location, name: &str) -> Result<Array<T, D>> {
@@ -499,7 +1075,8 @@ fn read_array_attr<T: BackendData, D: RemoveAxis>(loc: &Location, name: &str) ->
         let shape = D::zeros(D::NDIM.unwrap_or(0));
         ArrayBase::from_shape_vec(shape, vec![]).map_err(|e| e.into())
     } else {
-        let array: DynArray = match T::DTYPE {
+        let stored = integer_conversion(&attr, T::DTYPE);
+        let array: DynArray = match stored.unwrap_or(T::DTYPE) {
             ScalarType::I8 => attr.read::<i8, D>()?.into(),
             ScalarType::I16 => attr.read::<i16, D>()?.into(),
             ScalarType::I32 => attr.read::<i32, D>()?.into(),
@@ -509,6 +1086,7 @@ fn read_array_attr<T: BackendData, D: RemoveAxis>(loc: &Location, name: &str) ->
             ScalarType::U32 => attr.read::<u32, D>()?.into(),
             ScalarType::U64 => attr.read::<u64, D>()?.into(),
             ScalarType::Usize => attr.read::<usize, D>()?.into(),
+            ScalarType::F16 => attr.read::<f32, D>()?.mapv(f16::from_f32).into(),
             ScalarType::F32 => attr.read::<f32, D>()?.into(),
             ScalarType::F64 => attr.read::<f64, D>()?.into(),
             ScalarType::Bool => attr.read::<bool, D>()?.into(),
@@ -517,7 +1095,11 @@ fn read_array_attr<T: BackendData, D: RemoveAxis>(loc: &Location, name: &str) ->
                 s.map(|s| s.to_string()).into()
             }
         };
-        Ok(BackendData::from_dyn_arr(array)?.into_dimensionality::<D>()?)
+        let array = match stored {
+            Some(_) => cast_integer_array::<T>(array)?,
+            None => BackendData::from_dyn_arr(array)?,
+        };
+        Ok(array.into_dimensionality::<D>()?)
     }
 }
 
@@ -561,6 +1143,14 @@ impl GroupOp for H5File {
         exists(self, name)
     }
 
+    fn copy_from(&self, file: &Path, path: &str, name: &str) -> Result<()> {
+        copy_from(self, file, path, name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        rename(self, from, to)
+    }
+
     fn create_scalar_data<D: BackendData>(
         &self,
         name: &str,
@@ -606,6 +1196,14 @@ impl GroupOp for H5Group {
         exists(self, name)
     }
 
+    fn copy_from(&self, file: &Path, path: &str, name: &str) -> Result<()> {
+        copy_from(self, file, path, name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        rename(self, from, to)
+    }
+
     fn create_scalar_data<D: BackendData>(
         &self,
         name: &str,
@@ -650,6 +1248,14 @@ impl LocationOp for H5Group {
     fn read_array_attr<T: BackendData, D: RemoveAxis>(&self, name: &str) -> Result<Array<T, D>> {
         read_array_attr(self, name)
     }
+
+    fn attr_dtype(&self, name: &str) -> Result<Option<ScalarType>> {
+        attr_dtype(self, name)
+    }
+
+    fn attr_names(&self) -> Result<Vec<String>> {
+        attr_names(self)
+    }
 }
 
 impl LocationOp for H5Dataset {
@@ -687,6 +1293,14 @@ impl LocationOp for H5Dataset {
     fn read_array_attr<T: BackendData, D: RemoveAxis>(&self, name: &str) -> Result<Array<T, D>> {
         read_array_attr(self, name)
     }
+
+    fn attr_dtype(&self, name: &str) -> Result<Option<ScalarType>> {
+        attr_dtype(self, name)
+    }
+
+    fn attr_names(&self) -> Result<Vec<String>> {
+        attr_names(self)
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -725,10 +1339,11 @@ fn del_attr(loc: &Location, name: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use anndata::s;
+    use anndata::{s, AnnDataOp, ArrayElemOp, CreateMode, ElemCollectionOp};
+    use anndata::backend::{DataContainer, DataType};
     use ndarray_rand::rand_distr::Uniform;
     use ndarray_rand::RandomExt;
-    use ndarray::{Array1, Axis, concatenate, Ix1};
+    use ndarray::{Array1, Array2, Axis, concatenate, Ix1};
     use std::path::PathBuf;
     use tempfile::tempdir;
 
@@ -803,5 +1418,162 @@ mod tests {
             Ok(())
         })
     }
-}
 
+    #[test]
+    fn test_compression() -> Result<()> {
+        with_tmp_path(|path| -> Result<()> {
+            let file = H5::create(&path)?;
+            let arr: Array2<i32> = Array::random((200, 50), Uniform::new(0, 100));
+            let half = arr.mapv(|x| f16::from_f32(x as f32));
+            let filters = [Compression::Gzip(4), Compression::Lz4(5), Compression::Zstd(3)];
+            for (i, compression) in filters.into_iter().enumerate() {
+                let config = WriteConfig { compression: Some(compression), block_size: None, ..Default::default() };
+                let dataset = file.create_array_data(&format!("int{}", i), &arr, config.clone())?;
+                assert_eq!(arr, dataset.read_array::<i32, _>()?);
+                let dataset = file.create_array_data(&format!("half{}", i), &half, config)?;
+                assert_eq!(half, dataset.read_array::<f16, _>()?);
+            }
+            #[cfg(not(feature = "blosc"))]
+            assert_eq!(supported_compression(Compression::Zstd(3)), Compression::Gzip(3));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_lzf() -> Result<()> {
+        with_tmp_path(|path| -> Result<()> {
+            // Datasets written by h5py with `compression="lzf"`.
+            let arr: Array2<f32> = Array::random((200, 50), Uniform::new(0.0, 1.0));
+            let file = H5::create(&path)?;
+            file.new_dataset_builder().with_data(&arr).chunk((50, 50)).lzf().create("X")?;
+            file.close()?;
+
+            let file = H5::open(&path)?;
+            let dataset = file.open_dataset("X")?;
+            assert_eq!(arr, dataset.read_array::<f32, _>()?);
+            assert_eq!(
+                arr.slice(ndarray::s![10..20, ..]).to_owned(),
+                dataset.read_array_slice::<f32, _, _>(s![10..20, ..].as_ref())?,
+            );
+            assert_eq!(describe_filter(32000), "32000 (lzf)");
+            assert_eq!(describe_filter(12345), "12345");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_swmr() -> Result<()> {
+        with_tmp_path(|path| -> Result<()> {
+            let arr: Array2<i32> = Array::random((20, 50), Uniform::new(0, 100));
+            let file = H5::create(&path)?;
+            file.create_array_data("data", &arr, Default::default())?;
+            file.close()?;
+            // Files are created in the default format unless SWMR is requested.
+            assert!(H5::open_swmr(&path, true).is_err());
+
+            let file = H5::create_swmr(&path)?;
+            file.create_array_data("data", &arr, Default::default())?;
+            file.close()?;
+
+            let writer = H5::open_swmr(&path, true)?;
+            let reader = H5::open_swmr(&path, false)?;
+            let dataset = reader.open_dataset("data")?;
+            assert_eq!(arr, dataset.read_array::<i32, _>()?);
+
+            let written = writer.open_dataset("data")?;
+            written.reshape(&[40, 50].as_slice().into())?;
+            written.write_array_slice(&arr, s![20..40, ..].as_ref())?;
+            writer.flush()?;
+
+            dataset.refresh()?;
+            let merged = concatenate(Axis(0), &[arr.view(), arr.view()])?;
+            assert_eq!(merged, dataset.read_array::<i32, _>()?);
+            assert!(writer.is_swmr() && reader.is_swmr());
+            writer.close()?;
+            reader.close()?;
+
+            // Writes through an AnnData object opened as the SWMR writer do not
+            // update the modification time, which would modify an attribute.
+            let adata = anndata::AnnData::<H5>::new_with_create(&path, CreateMode::Truncate, |p| H5::create_swmr(p))?;
+            adata.uns().append_to_list("log", 1.0)?;
+            adata.uns().append_to_list("log", 2.0)?;
+            let modified = adata.last_modified();
+            adata.close()?;
+
+            let writer = anndata::AnnData::<H5>::open(H5::open_swmr(&path, true)?)?;
+            let reader = H5::open_swmr(&path, false)?;
+            writer.uns().append_to_list("log", 3.0)?;
+            writer.flush()?;
+            let log = reader.open_dataset("uns/log")?;
+            log.refresh()?;
+            assert_eq!(log.read_array::<f64, Ix1>()?.to_vec(), vec![1.0, 2.0, 3.0]);
+            assert_eq!(writer.last_modified(), modified);
+            writer.close()?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_lenient_attrs() -> Result<()> {
+        use anndata::{CsrMatrix, ReadData, WriteData};
+        with_tmp_path(|path| -> Result<()> {
+            let file = H5::create(&path)?;
+            let csr: CsrMatrix<i32> = CsrMatrix::try_from_csr_data(
+                2, 3, vec![0, 1, 2], vec![0, 2], vec![1, 2],
+            ).unwrap();
+            csr.write(&file, "X")?;
+
+            // Other writers store the shape with a narrower or unsigned type.
+            let group = file.open_group("X")?;
+            del_attr(&group, "shape");
+            group.new_attr_builder().with_data(&Array1::from(vec![2i32, 3])).create("shape")?;
+            assert_eq!(CsrMatrix::<i32>::read(&DataContainer::open(&file, "X")?)?, csr);
+            del_attr(&group, "shape");
+            group.new_attr_builder().with_data(&Array1::from(vec![2u64, 3])).create("shape")?;
+            assert_eq!(CsrMatrix::<i32>::read(&DataContainer::open(&file, "X")?)?, csr);
+            assert_eq!(group.read_array_attr::<usize, Ix1>("shape")?.to_vec(), vec![2, 3]);
+
+            // Fixed-length strings, and booleans stored as integers.
+            del_attr(&group, "encoding-version");
+            let version = hdf5::types::FixedAscii::<8>::from_ascii(b"0.1.0").unwrap();
+            group.new_attr::<hdf5::types::FixedAscii<8>>().create("encoding-version")?.write_scalar(&version)?;
+            assert_eq!(group.read_str_attr("encoding-version")?, "0.1.0");
+            group.new_attr::<i8>().create("ordered")?.write_scalar(&1i8)?;
+            assert!(group.read_scalar_attr::<bool>("ordered")?);
+            group.write_scalar_attr("flag", false)?;
+            assert_eq!(group.read_scalar_attr::<u8>("flag")?, 0);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_external_link() -> Result<()> {
+        with_tmp_dir(|dir| -> Result<()> {
+            let arr: Array2<i32> = Array::random((20, 50), Uniform::new(0, 100));
+            let external = H5::create(dir.join("matrix.h5"))?;
+            external.create_array_data("data", &arr, Default::default())?;
+            external.close()?;
+
+            let path = dir.join("test.h5ad");
+            let file = H5::create(&path)?;
+            file.link_external(dir.join("matrix.h5").to_str().unwrap(), "/data", "X")?;
+            file.close()?;
+
+            let file = H5::open(&path)?;
+            let container = DataContainer::<H5>::open(&file, "X")?;
+            assert_eq!(container.encoding_type()?, DataType::Array(ScalarType::I32));
+            assert_eq!(arr, container.as_dataset()?.read_array::<i32, _>()?);
+
+            let adata = anndata::AnnData::<H5>::open(file)?;
+            assert_eq!(adata.n_obs(), 20);
+            assert_eq!(arr, adata.x().get::<Array2<i32>>()?.unwrap());
+            adata.close()?;
+
+            // Links to missing files are reported when opening them.
+            let file = H5::create(dir.join("dangling.h5ad"))?;
+            file.link_external("missing.h5", "/data", "X")?;
+            assert!(DataContainer::<H5>::open(&file, "X").is_err());
+            Ok(())
+        })
+    }
+}
@@ -9,6 +9,7 @@ use anndata::data::slice::BoundedSlice;
 
 use std::str::FromStr;
 use anyhow::{bail, Result};
+use half::f16;
 use n5::{
     filesystem::N5Filesystem, ndarray::N5NdarrayWriter, DataType, DatasetAttributes, N5Lister,
     N5Reader, N5Writer, ReadableDataBlock, ReflectedType, SliceDataBlock,
@@ -71,6 +72,57 @@ impl Location {
     fn child_path(&self, name: &str) -> PathBuf {
         self.path.join(name)
     }
+
+    /// The directory storing the location on disk.
+    fn dir(&self) -> PathBuf {
+        self.filename.join(self.path.strip_prefix("/").unwrap_or(&self.path))
+    }
+
+    fn attributes(&self) -> Result<serde_json::Map<String, Value>> {
+        match self.root.get_attributes(&self.path.to_string_lossy())? {
+            Value::Object(map) => Ok(map),
+            _ => bail!("Attributes are not an object"),
+        }
+    }
+}
+
+/// The type of a JSON attribute. Arrays have the type of their first element.
+fn json_dtype(value: &Value) -> ScalarType {
+    match value {
+        Value::Bool(_) => ScalarType::Bool,
+        Value::Number(x) if x.is_i64() => ScalarType::I64,
+        Value::Number(x) if x.is_u64() => ScalarType::U64,
+        Value::Number(_) => ScalarType::F64,
+        Value::Array(x) => x.first().map_or(ScalarType::F64, json_dtype),
+        _ => ScalarType::String,
+    }
+}
+
+/// The total size of the files in the directory `dir` and its subdirectories.
+fn dir_size(dir: &Path) -> Result<u64> {
+    std::fs::read_dir(dir)?.try_fold(0, |acc, entry| {
+        let entry = entry?;
+        let size = if entry.file_type()?.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            entry.metadata()?.len()
+        };
+        Ok(acc + size)
+    })
+}
+
+/// Copy the directory `from` to `to`, including all files and subdirectories.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
 }
 
 impl Backend for N5 {
@@ -112,6 +164,12 @@ impl FileOp for Root {
         self.filename.clone()
     }
 
+    /// Blocks and attributes are written to disk directly, so there is
+    /// nothing to flush.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Close the file.
     fn close(self) -> Result<()> {
         todo!()
@@ -172,14 +230,18 @@ impl GroupOp for Group {
         let attr = DatasetAttributes::new(dimensions, block_size, data_type, Default::default());
         self.root.create_dataset(&path.to_string_lossy(), &attr)?;
 
-        Ok(Dataset {
+        let dataset = Dataset {
             attributes: attr,
             loc: Location {
                 path,
                 root: self.root.clone(),
                 filename: self.filename.clone(),
             },
-        })
+        };
+        if T::DTYPE == ScalarType::F16 {
+            dataset.write_str_attr("custom-data-type", "f16")?;
+        }
+        Ok(dataset)
     }
 
     fn open_dataset(&self, name: &str) -> Result<<Self::Backend as Backend>::Dataset> {
@@ -210,6 +272,21 @@ impl GroupOp for Group {
         Ok(self.root.exists(&self.child_path(name).to_string_lossy())?)
     }
 
+    /// Groups and datasets are directories, which are copied as they are.
+    fn copy_from(&self, file: &Path, path: &str, name: &str) -> Result<()> {
+        let source = file.join(path.trim_start_matches('/'));
+        if !source.is_dir() {
+            bail!("cannot copy '{}' from '{}'", path, file.display());
+        }
+        copy_dir(&source, &self.dir().join(name))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let dir = self.dir();
+        std::fs::rename(dir.join(from.trim_start_matches('/')), dir.join(to.trim_start_matches('/')))?;
+        Ok(())
+    }
+
     fn create_scalar_data<D: BackendData>(
         &self,
         name: &str,
@@ -256,6 +333,11 @@ impl GroupOp for Group {
             DynScalar::I16(x) => write_scalar!([x]),
             DynScalar::I32(x) => write_scalar!([x]),
             DynScalar::I64(x) => write_scalar!([x]),
+            DynScalar::F16(x) => {
+                let dataset = write_scalar!([x.to_f32()])?;
+                dataset.write_str_attr("custom-data-type", "f16")?;
+                Ok(dataset)
+            }
             DynScalar::F32(x) => write_scalar!([x]),
             DynScalar::F64(x) => write_scalar!([x]),
             DynScalar::Bool(x) => {
@@ -304,7 +386,14 @@ impl DatasetOp for Dataset {
             DataType::INT16 => Ok(ScalarType::I16),
             DataType::INT32 => Ok(ScalarType::I32),
             DataType::INT64 => Ok(ScalarType::I64),
-            DataType::FLOAT32 => Ok(ScalarType::F32),
+            DataType::FLOAT32 => match self
+                .read_str_attr("custom-data-type")
+                .as_ref()
+                .map(|x| x.as_str())
+            {
+                Ok("f16") => Ok(ScalarType::F16),
+                _ => Ok(ScalarType::F32),
+            },
             DataType::FLOAT64 => Ok(ScalarType::F64),
         }
     }
@@ -316,6 +405,11 @@ impl DatasetOp for Dataset {
         todo!()
     }
 
+    /// The total size of the files storing the blocks and attributes.
+    fn storage_size(&self) -> Result<u64> {
+        dir_size(&self.dir())
+    }
+
     fn read_scalar<T: BackendData>(&self) -> Result<T> {
         fn read<T>(dataset: &Dataset) -> Result<Vec<T>>
         where
@@ -342,6 +436,7 @@ impl DatasetOp for Dataset {
             ScalarType::I16 => read::<i16>(self)?[0].into_dyn(),
             ScalarType::I32 => read::<i32>(self)?[0].into_dyn(),
             ScalarType::I64 => read::<i64>(self)?[0].into_dyn(),
+            ScalarType::F16 => f16::from_f32(read::<f32>(self)?[0]).into_dyn(),
             ScalarType::F32 => read::<f32>(self)?[0].into_dyn(),
             ScalarType::F64 => read::<f64>(self)?[0].into_dyn(),
             ScalarType::Bool => (read::<u8>(self)?[0] != 0).into_dyn(),
@@ -393,6 +488,7 @@ impl DatasetOp for Dataset {
             ScalarType::U32 => impl_read!(u32).into(),
             ScalarType::U64 => impl_read!(u64).into(),
             ScalarType::Usize => impl_read!(u64).map(|x| *x as usize).into(),
+            ScalarType::F16 => impl_read!(f32).mapv(f16::from_f32).into(),
             ScalarType::F32 => impl_read!(f32).into(),
             ScalarType::F64 => impl_read!(f64).into(),
             _ => todo!(),
@@ -443,6 +539,7 @@ impl DatasetOp for Dataset {
             DynArrayView::I16(x) => impl_write!(x.into_dyn(), 0),
             DynArrayView::I32(x) => impl_write!(x.into_dyn(), 0),
             DynArrayView::I64(x) => impl_write!(x.into_dyn(), 0),
+            DynArrayView::F16(x) => impl_write!(&x.mapv(f16::to_f32).into_dyn(), 0.0),
             DynArrayView::F32(x) => impl_write!(x.into_dyn(), 0.0),
             DynArrayView::F64(x) => impl_write!(x.into_dyn(), 0.0),
             DynArrayView::String(x) => Ok(()),
@@ -483,6 +580,7 @@ impl LocationOp for Location {
             DynArrayView::I16(x) => self.root.set_attribute(&path, name.to_string(), x.into_dyn()),
             DynArrayView::I32(x) => self.root.set_attribute(&path, name.to_string(), x.into_dyn()),
             DynArrayView::I64(x) => self.root.set_attribute(&path, name.to_string(), x.into_dyn()),
+            DynArrayView::F16(x) => self.root.set_attribute(&path, name.to_string(), x.mapv(f16::to_f32).into_dyn()),
             DynArrayView::F32(x) => self.root.set_attribute(&path, name.to_string(), x.into_dyn()),
             DynArrayView::F64(x) => self.root.set_attribute(&path, name.to_string(), x.into_dyn()),
             DynArrayView::Bool(x) => self.root.set_attribute(&path, name.to_string(), x.into_dyn()),
@@ -509,6 +607,7 @@ impl LocationOp for Location {
             DynScalar::I16(x) => Value::Number(Number::from(x)),
             DynScalar::I32(x) => Value::Number(Number::from(x)),
             DynScalar::I64(x) => Value::Number(Number::from(x)),
+            DynScalar::F16(x) => Value::Number(Number::from_f64(x.to_f64()).unwrap()),
             DynScalar::F32(x) => Value::Number(Number::from_f64(x as f64).unwrap()),
             DynScalar::F64(x) => Value::Number(Number::from_f64(x).unwrap()),
             DynScalar::Bool(x) => Value::Bool(x),
@@ -520,7 +619,35 @@ impl LocationOp for Location {
     }
 
     fn read_scalar_attr<T: BackendData>(&self, name: &str) -> Result<T> {
-        todo!()
+        let val = match self.attributes()?.remove(name) {
+            Some(val) => val,
+            None => bail!("Attribute '{}' does not exist", name),
+        };
+        let scalar = match T::DTYPE {
+            ScalarType::U8 => serde_json::from_value::<u8>(val)?.into_dyn(),
+            ScalarType::U16 => serde_json::from_value::<u16>(val)?.into_dyn(),
+            ScalarType::U32 => serde_json::from_value::<u32>(val)?.into_dyn(),
+            ScalarType::U64 => serde_json::from_value::<u64>(val)?.into_dyn(),
+            ScalarType::Usize => serde_json::from_value::<usize>(val)?.into_dyn(),
+            ScalarType::I8 => serde_json::from_value::<i8>(val)?.into_dyn(),
+            ScalarType::I16 => serde_json::from_value::<i16>(val)?.into_dyn(),
+            ScalarType::I32 => serde_json::from_value::<i32>(val)?.into_dyn(),
+            ScalarType::I64 => serde_json::from_value::<i64>(val)?.into_dyn(),
+            ScalarType::F16 => f16::from_f32(serde_json::from_value::<f32>(val)?).into_dyn(),
+            ScalarType::F32 => serde_json::from_value::<f32>(val)?.into_dyn(),
+            ScalarType::F64 => serde_json::from_value::<f64>(val)?.into_dyn(),
+            ScalarType::Bool => serde_json::from_value::<bool>(val)?.into_dyn(),
+            ScalarType::String => serde_json::from_value::<String>(val)?.into_dyn(),
+        };
+        BackendData::from_dyn(scalar)
+    }
+
+    fn attr_dtype(&self, name: &str) -> Result<Option<ScalarType>> {
+        Ok(self.attributes()?.get(name).map(json_dtype))
+    }
+
+    fn attr_names(&self) -> Result<Vec<String>> {
+        Ok(self.attributes()?.keys().cloned().collect())
     }
 
     fn read_str_attr(&self, name: &str) -> Result<String> {
@@ -545,6 +672,7 @@ impl LocationOp for Location {
             ScalarType::I16 => serde_json::from_value::<Array<i16, IxDyn>>(val)?.into(),
             ScalarType::I32 => serde_json::from_value::<Array<i32, IxDyn>>(val)?.into(),
             ScalarType::I64 => serde_json::from_value::<Array<i64, IxDyn>>(val)?.into(),
+            ScalarType::F16 => serde_json::from_value::<Array<f32, IxDyn>>(val)?.mapv(f16::from_f32).into(),
             ScalarType::F32 => serde_json::from_value::<Array<f32, IxDyn>>(val)?.into(),
             ScalarType::F64 => serde_json::from_value::<Array<f64, IxDyn>>(val)?.into(),
             ScalarType::Bool => serde_json::from_value::<Array<bool, IxDyn>>(val)?.into(),
@@ -598,6 +726,14 @@ impl GroupOp for Root {
         self.deref().exists(name)
     }
 
+    fn copy_from(&self, file: &Path, path: &str, name: &str) -> Result<()> {
+        self.deref().copy_from(file, path, name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.deref().rename(from, to)
+    }
+
     fn create_scalar_data<D: BackendData>(
         &self,
         name: &str,
@@ -643,6 +779,14 @@ impl LocationOp for Group {
         self.deref().read_scalar_attr(name)
     }
 
+    fn attr_dtype(&self, name: &str) -> Result<Option<ScalarType>> {
+        self.deref().attr_dtype(name)
+    }
+
+    fn attr_names(&self) -> Result<Vec<String>> {
+        self.deref().attr_names()
+    }
+
     fn read_array_attr<T: BackendData, D: RemoveAxis>(&self, name: &str) -> Result<Array<T, D>> {
         self.deref().read_array_attr(name)
     }
@@ -684,6 +828,14 @@ impl LocationOp for Dataset {
         self.deref().read_scalar_attr(name)
     }
 
+    fn attr_dtype(&self, name: &str) -> Result<Option<ScalarType>> {
+        self.deref().attr_dtype(name)
+    }
+
+    fn attr_names(&self) -> Result<Vec<String>> {
+        self.deref().attr_names()
+    }
+
     fn read_array_attr<T: BackendData, D: RemoveAxis>(&self, name: &str) -> Result<Array<T, D>> {
         self.deref().read_array_attr(name)
     }
@@ -701,6 +853,7 @@ fn get_data_type<T: BackendData>() -> DataType {
         ScalarType::U32 => DataType::UINT32,
         ScalarType::U64 => DataType::UINT64,
         ScalarType::Usize => DataType::UINT64,
+        ScalarType::F16 => DataType::FLOAT32,
         ScalarType::F32 => DataType::FLOAT32,
         ScalarType::F64 => DataType::FLOAT64,
         ScalarType::Bool => DataType::UINT8,
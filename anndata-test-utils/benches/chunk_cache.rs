@@ -0,0 +1,37 @@
+use anndata::*;
+use anndata_hdf5::H5;
+use anndata_test_utils::with_tmp_dir;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ndarray::Array2;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// A sequential scan of a gzip-compressed dense `X` in chunks of 100 rows,
+/// with HDF5's default cache of 1 MiB and with the cache sized automatically
+/// by the chunked iterator to hold a full row of chunks.
+fn sequential_scan(c: &mut Criterion) {
+    with_tmp_dir(|dir| {
+        let file = dir.join("bench.h5ad");
+        let adata = AnnData::<H5>::new(&file).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        adata.set_x(Array2::from_shape_fn((10_000, 1_000), |_| rng.gen::<f64>())).unwrap();
+        adata.close().unwrap();
+
+        let mut group = c.benchmark_group("dense_sequential_scan");
+        group.sample_size(10);
+        for auto in [false, true] {
+            let id = BenchmarkId::from_parameter(if auto { "auto" } else { "1MiB" });
+            group.bench_function(id, |b| b.iter(|| {
+                let adata = AnnData::<H5>::open(H5::open(&file).unwrap()).unwrap();
+                if !auto {
+                    adata.get_x().inner().set_chunk_cache(1 << 20).unwrap();
+                }
+                adata.get_x().chunked::<ArrayData>(100).for_each(drop);
+                adata.close().unwrap();
+            }));
+        }
+        group.finish();
+    })
+}
+
+criterion_group!(benches, sequential_scan);
+criterion_main!(benches);
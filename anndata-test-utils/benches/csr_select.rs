@@ -0,0 +1,51 @@
+use anndata::*;
+use anndata_hdf5::H5;
+use anndata_test_utils::with_tmp_dir;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use nalgebra_sparse::CsrMatrix;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// A random csr matrix with `nnz_per_row` entries in every row.
+fn wide_csr(nrows: usize, ncols: usize, nnz_per_row: usize) -> CsrMatrix<f32> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let indptr = (0..=nrows).map(|i| i * nnz_per_row).collect();
+    let indices = (0..nrows)
+        .flat_map(|_| {
+            let mut row: Vec<usize> = rand::seq::index::sample(&mut rng, ncols, nnz_per_row).into_vec();
+            row.sort_unstable();
+            row
+        })
+        .collect();
+    let data = (0..nrows * nnz_per_row).map(|_| rng.gen()).collect();
+    CsrMatrix::try_from_csr_data(nrows, ncols, indptr, indices, data).unwrap()
+}
+
+/// 1000 random single-row reads of a backed csr matrix, with the `indptr`
+/// cached (the default) and re-read for every selection.
+fn random_rows(c: &mut Criterion) {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<H5>::new(dir.join("bench.h5ad")).unwrap();
+        adata.set_x(wide_csr(20_000, 30_000, 30)).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let rows: Vec<usize> = (0..1000).map(|_| rng.gen_range(0..20_000)).collect();
+
+        let mut group = c.benchmark_group("csr_random_rows");
+        group.sample_size(10);
+        for cached in [true, false] {
+            if !cached {
+                adata.get_x().inner().disable_cache();
+            }
+            let id = BenchmarkId::from_parameter(if cached { "cached" } else { "uncached" });
+            group.bench_with_input(id, &rows, |b, rows| b.iter(|| {
+                rows.iter().for_each(|&i| {
+                    let _: ArrayData = adata.get_x().slice(s![i, ..]).unwrap().unwrap();
+                })
+            }));
+        }
+        group.finish();
+        adata.close().unwrap();
+    })
+}
+
+criterion_group!(benches, random_rows);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+use anndata::*;
+use anndata_hdf5::H5;
+use anndata_test_utils::with_tmp_dir;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use polars::prelude::{DataFrame, NamedFrom, Series};
+
+/// A data frame with `n` distinct barcodes, where converting the strings
+/// dominates the cost of reading and writing.
+fn barcodes(n: usize) -> DataFrame {
+    let barcodes: Vec<String> = (0..n).map(|i| format!("{:016x}-1", i)).collect();
+    DataFrame::new(vec![Series::new("barcode", barcodes)]).unwrap()
+}
+
+/// Write obs with a large string column, and read it back from a freshly
+/// opened file so that nothing is served from the cache. This is the Rust
+/// half of the round trip of a polars data frame; the Python bindings pass
+/// the columns through the Arrow C data interface on top of it, which is
+/// measured against pandas by `python/benchmarks/obs_strings.py`.
+fn string_column(c: &mut Criterion) {
+    let mut group = c.benchmark_group("obs_string_column");
+    group.sample_size(10);
+    for n in [100_000, 1_000_000] {
+        let df = barcodes(n);
+        group.bench_with_input(BenchmarkId::new("write", n), &df, |b, df| b.iter(|| with_tmp_dir(|dir| {
+            let adata = AnnData::<H5>::new(dir.join("bench.h5ad")).unwrap();
+            adata.set_obs(df.clone()).unwrap();
+            adata.close().unwrap();
+        })));
+        with_tmp_dir(|dir| {
+            let file = dir.join("bench.h5ad");
+            let adata = AnnData::<H5>::new(&file).unwrap();
+            adata.set_obs(df.clone()).unwrap();
+            adata.close().unwrap();
+            group.bench_with_input(BenchmarkId::new("read", n), &file, |b, file| b.iter(|| {
+                let adata = AnnData::<H5>::open(H5::open(file).unwrap()).unwrap();
+                let obs = adata.read_obs().unwrap();
+                adata.close().unwrap();
+                obs
+            }));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, string_column);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+use anndata::*;
+use anndata::data::SelectInfoElem;
+use anndata_hdf5::H5;
+use anndata_test_utils::with_tmp_dir;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ndarray::Array2;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// 800 random row reads of 20 obsm elements, spread over 1 and 8 threads.
+/// With shared locks on the slots, the 8-thread time should drop with the
+/// number of cores instead of staying at the single-thread time. Elements are
+/// read with the cache disabled and with cached data.
+fn random_obsm_reads(c: &mut Criterion) {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<H5>::new(dir.join("bench.h5ad")).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let keys: Vec<_> = (0..20).map(|i| format!("X_{}", i)).collect();
+        for key in keys.iter() {
+            adata.obsm().add(key, Array2::from_shape_fn((10_000, 50), |_| rng.gen::<f32>())).unwrap();
+        }
+
+        let mut group = c.benchmark_group("obsm_random_reads");
+        group.sample_size(10);
+        for cached in [false, true] {
+            if cached {
+                for key in keys.iter() {
+                    if let Some(x) = adata.obsm().get(key).unwrap().lock().as_mut() {
+                        x.enable_cache();
+                    }
+                }
+            }
+            for n_threads in [1, 8] {
+                let id = BenchmarkId::new(if cached { "cached" } else { "uncached" }, n_threads);
+                group.bench_function(id, |b| b.iter(|| std::thread::scope(|s| {
+                    for t in 0..n_threads {
+                        let (adata, keys) = (&adata, &keys);
+                        s.spawn(move || {
+                            let mut rng = StdRng::seed_from_u64(t as u64);
+                            for _ in 0..800 / n_threads {
+                                let elem = adata.obsm().get(&keys[rng.gen_range(0..keys.len())]).unwrap();
+                                let row = SelectInfoElem::from(rng.gen_range(0..10_000usize));
+                                let _: Array2<f32> = elem.slice_axis(0, row).unwrap().unwrap();
+                            }
+                        });
+                    }
+                })));
+            }
+        }
+        group.finish();
+        adata.close().unwrap();
+    })
+}
+
+criterion_group!(benches, random_obsm_reads);
+criterion_main!(benches);
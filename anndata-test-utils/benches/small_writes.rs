@@ -0,0 +1,34 @@
+use anndata::*;
+use anndata_hdf5::H5;
+use anndata_test_utils::with_tmp_dir;
+use criterion::{criterion_group, criterion_main, Criterion};
+use ndarray::Array2;
+
+/// Create 100 small obsm elements in a new file, one write at a time and as a
+/// batched write. Set `TMPDIR` to a directory on a slow, e.g., network, file
+/// system to measure the overhead of small writes there.
+fn small_elements(c: &mut Criterion) {
+    fn create(adata: &AnnData<H5>) -> anyhow::Result<()> {
+        for i in 0..100 {
+            adata.obsm().add(&format!("X_{}", i), Array2::<f32>::zeros((100, 2)))?;
+        }
+        Ok(())
+    }
+
+    let mut group = c.benchmark_group("create_100_elements");
+    group.sample_size(10);
+    group.bench_function("unbatched", |b| b.iter(|| with_tmp_dir(|dir| {
+        let adata = AnnData::<H5>::new(dir.join("bench.h5ad")).unwrap();
+        create(&adata).unwrap();
+        adata.close().unwrap();
+    })));
+    group.bench_function("batched", |b| b.iter(|| with_tmp_dir(|dir| {
+        let adata = AnnData::<H5>::new(dir.join("bench.h5ad")).unwrap();
+        adata.batched(create).unwrap();
+        adata.close().unwrap();
+    })));
+    group.finish();
+}
+
+criterion_group!(benches, small_elements);
+criterion_main!(benches);
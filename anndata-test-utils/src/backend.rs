@@ -0,0 +1,212 @@
+//! A conformance suite for storage backends. Each function exercises one part
+//! of the backend traits, and [`test_backend`] runs all of them, so that a new
+//! backend can be validated against the same expectations as HDF5.
+
+use crate::with_tmp_dir;
+
+use anndata::backend::*;
+use anndata::data::SelectInfoElem;
+use anndata::s;
+use ndarray::{array, Array, Array1, Array2, Axis, Ix1, Ix2};
+use ndarray_rand::rand_distr::Uniform;
+use ndarray_rand::RandomExt;
+use std::path::PathBuf;
+
+/// Run all conformance tests for the backend.
+pub fn test_backend<B: Backend>() {
+    test_backend_groups::<B>();
+    test_backend_datasets::<B>();
+    test_backend_attributes::<B>();
+    test_backend_selection::<B>();
+}
+
+/// Groups can be created, listed, opened, deleted, and persist after reopening.
+pub fn test_backend_groups<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let path = dir.join("test");
+        let file = B::create(&path).unwrap();
+        assert_eq!(file.filename(), path);
+        assert!(file.list().unwrap().is_empty());
+
+        let a = file.create_group("a").unwrap();
+        let b = a.create_group("b").unwrap();
+        file.create_group("c").unwrap();
+        assert_eq!(a.path(), PathBuf::from("/a"));
+        assert_eq!(b.path(), PathBuf::from("/a/b"));
+        assert!(file.create_group("a").is_err());
+
+        let mut names = file.list().unwrap();
+        names.sort();
+        assert_eq!(names, vec!["a", "c"]);
+        assert_eq!(a.list().unwrap(), vec!["b"]);
+        assert!(file.exists("a").unwrap());
+        assert!(!file.exists("missing").unwrap());
+        assert!(file.open_group("missing").is_err());
+
+        file.delete("c").unwrap();
+        assert!(!file.exists("c").unwrap());
+        file.close().unwrap();
+
+        let file = B::open(&path).unwrap();
+        assert_eq!(file.list().unwrap(), vec!["a"]);
+        assert!(file.open_group("a").unwrap().exists("b").unwrap());
+        file.close().unwrap();
+    })
+}
+
+/// Arrays and scalars of every supported type round-trip, and datasets can
+/// be resized and written in parts.
+pub fn test_backend_datasets<B: Backend>() {
+    fn round_trip<B: Backend, T: BackendData + PartialEq + std::fmt::Debug>(
+        group: &B::Group,
+        name: &str,
+        data: Array2<T>,
+    ) {
+        let dataset = group.create_array_data(name, &data, WriteConfig::default()).unwrap();
+        assert_eq!(dataset.dtype().unwrap(), T::DTYPE);
+        assert_eq!(dataset.shape().as_ref(), data.shape());
+        assert_eq!(dataset.read_array::<T, Ix2>().unwrap(), data);
+        assert_eq!(group.open_dataset(name).unwrap().read_array::<T, Ix2>().unwrap(), data);
+    }
+
+    with_tmp_dir(|dir| {
+        let path = dir.join("test");
+        let file = B::create(&path).unwrap();
+        let group = file.create_group("data").unwrap();
+
+        round_trip::<B, _>(&group, "i8", array![[-1i8, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "i16", array![[-1i16, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "i32", array![[-1i32, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "i64", array![[-1i64, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "u8", array![[1u8, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "u16", array![[1u16, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "u32", array![[1u32, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "u64", array![[1u64, 2], [3, 4]]);
+        round_trip::<B, _>(&group, "f32", array![[1.5f32, 2.0], [3.0, 4.0]]);
+        round_trip::<B, _>(&group, "f64", array![[1.5f64, -2.0], [3.0, 4.0]]);
+        round_trip::<B, _>(&group, "bool", array![[true, false], [false, true]]);
+        round_trip::<B, _>(
+            &group, "string",
+            array![["a".to_string(), "".to_string()], ["ü".to_string(), "long string".to_string()]],
+        );
+        round_trip::<B, _>(&group, "large", Array::random((300, 40), Uniform::new(0, 100)));
+
+        let empty = Array1::<f64>::zeros(0);
+        let dataset = group.create_array_data("empty", &empty, WriteConfig::default()).unwrap();
+        assert_eq!(dataset.read_array::<f64, Ix1>().unwrap(), empty);
+
+        let dataset = group.create_scalar_data("scalar", &3i64).unwrap();
+        assert_eq!(dataset.shape().ndim(), 0);
+        assert_eq!(dataset.read_scalar::<i64>().unwrap(), 3);
+        let dataset = group.create_scalar_data("str", &"text".to_string()).unwrap();
+        assert_eq!(dataset.dtype().unwrap(), ScalarType::String);
+        assert_eq!(dataset.read_scalar::<String>().unwrap(), "text");
+
+        // Resize and write in parts.
+        let part: Array2<i32> = Array::random((5, 3), Uniform::new(0, 100));
+        let dataset = group.new_dataset::<i32>("parts", &[5, 3].as_slice().into(), WriteConfig::default()).unwrap();
+        dataset.write_array_slice(&part, s![0..5, ..].as_ref()).unwrap();
+        assert!(dataset.write_array_slice(&part, s![5..10, ..].as_ref()).is_err());
+        dataset.reshape(&[10, 3].as_slice().into()).unwrap();
+        dataset.write_array_slice(&part, s![5..10, ..].as_ref()).unwrap();
+        let expected = ndarray::concatenate(Axis(0), &[part.view(), part.view()]).unwrap();
+        assert_eq!(dataset.read_array::<i32, Ix2>().unwrap(), expected);
+        dataset.reshape(&[5, 3].as_slice().into()).unwrap();
+        assert_eq!(dataset.read_array::<i32, Ix2>().unwrap(), part);
+        assert!(dataset.storage_size().unwrap() > 0);
+
+        group.delete("parts").unwrap();
+        assert!(group.open_dataset("parts").is_err());
+        file.close().unwrap();
+
+        let file = B::open(&path).unwrap();
+        let group = file.open_group("data").unwrap();
+        assert_eq!(
+            group.open_dataset("i32").unwrap().read_array::<i32, Ix2>().unwrap(),
+            array![[-1, 2], [3, 4]],
+        );
+        assert_eq!(group.open_dataset("scalar").unwrap().read_scalar::<i64>().unwrap(), 3);
+        file.close().unwrap();
+    })
+}
+
+/// Scalar, string and array attributes round-trip on groups and datasets,
+/// and existing attributes can be overwritten.
+pub fn test_backend_attributes<B: Backend>() {
+    fn check<L: LocationOp>(location: &L) {
+        location.write_scalar_attr("int", 3i64).unwrap();
+        location.write_scalar_attr("float", 0.5f64).unwrap();
+        location.write_scalar_attr("bool", true).unwrap();
+        location.write_str_attr("str", "value").unwrap();
+        location.write_array_attr("ints", &array![1u32, 2, 3]).unwrap();
+        location.write_array_attr("strs", &array!["a".to_string(), "b".to_string()]).unwrap();
+
+        assert_eq!(location.read_scalar_attr::<i64>("int").unwrap(), 3);
+        assert_eq!(location.read_scalar_attr::<f64>("float").unwrap(), 0.5);
+        assert!(location.read_scalar_attr::<bool>("bool").unwrap());
+        assert_eq!(location.read_str_attr("str").unwrap(), "value");
+        assert_eq!(location.read_array_attr::<u32, Ix1>("ints").unwrap(), array![1, 2, 3]);
+        assert_eq!(
+            location.read_array_attr::<String, Ix1>("strs").unwrap(),
+            array!["a".to_string(), "b".to_string()],
+        );
+        assert!(location.read_str_attr("missing").is_err());
+
+        location.write_scalar_attr("int", 4i64).unwrap();
+        location.write_str_attr("str", "other").unwrap();
+        location.write_array_attr("ints", &array![5u32]).unwrap();
+        assert_eq!(location.read_scalar_attr::<i64>("int").unwrap(), 4);
+        assert_eq!(location.read_str_attr("str").unwrap(), "other");
+        assert_eq!(location.read_array_attr::<u32, Ix1>("ints").unwrap(), array![5]);
+    }
+
+    with_tmp_dir(|dir| {
+        let path = dir.join("test");
+        let file = B::create(&path).unwrap();
+        let group = file.create_group("group").unwrap();
+        let dataset = group.create_array_data("data", &array![1, 2, 3], WriteConfig::default()).unwrap();
+        check(&group);
+        check(&dataset);
+        file.close().unwrap();
+
+        let file = B::open(&path).unwrap();
+        let group = file.open_group("group").unwrap();
+        assert_eq!(group.read_str_attr("str").unwrap(), "other");
+        assert_eq!(group.open_dataset("data").unwrap().read_scalar_attr::<i64>("int").unwrap(), 4);
+        file.close().unwrap();
+    })
+}
+
+/// Reading and writing selections with ranges, steps and unsorted indices.
+pub fn test_backend_selection<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = B::create(dir.join("test")).unwrap();
+        let arr: Array2<i32> = Array::random((20, 10), Uniform::new(0, 100));
+        let dataset = file.create_array_data("data", &arr, WriteConfig::default()).unwrap();
+        let read = |select: &[SelectInfoElem]| dataset.read_array_slice::<i32, _, Ix2>(select).unwrap();
+
+        assert_eq!(read(s![2..5, ..].as_ref()), arr.slice(ndarray::s![2..5, ..]));
+        assert_eq!(read(s![.., 3..4].as_ref()), arr.slice(ndarray::s![.., 3..4]));
+
+        assert_eq!(read(s![1..15;3, ..].as_ref()), arr.slice(ndarray::s![1..15;3, ..]));
+
+        let rows = vec![7, 0, 3, 3, 19];
+        let cols = vec![9, 2];
+        assert_eq!(read(s![&rows, &cols].as_ref()), arr.select(Axis(0), &rows).select(Axis(1), &cols));
+
+        let strings = file.create_array_data(
+            "strings", &array!["a".to_string(), "b".to_string(), "c".to_string()], WriteConfig::default(),
+        ).unwrap();
+        assert_eq!(
+            strings.read_array_slice::<String, _, Ix1>(&[SelectInfoElem::from(vec![2, 0])]).unwrap(),
+            array!["c".to_string(), "a".to_string()],
+        );
+
+        let block = Array2::<i32>::zeros((2, 3));
+        dataset.write_array_slice(&block, s![4..6, 1..4].as_ref()).unwrap();
+        let mut expected = arr.clone();
+        expected.slice_mut(ndarray::s![4..6, 1..4]).fill(0);
+        assert_eq!(dataset.read_array::<i32, Ix2>().unwrap(), expected);
+        file.close().unwrap();
+    })
+}
@@ -1,7 +1,10 @@
+mod backend;
 mod common;
+pub use backend::*;
 pub use common::*;
 
-use anndata::{*, data::CsrNonCanonical};
+use anndata::{*, data::{ArrayFormat, CsrNonCanonical, SelectInfoElem}};
+use half::f16;
 use ndarray::{Array2, ArrayD};
 use nalgebra_sparse::{CooMatrix, CsrMatrix};
 use proptest::prelude::*;
@@ -143,4 +146,2427 @@ where
             prop_assert_eq!(adata.obsm().get_item::<ArrayData>("test2").unwrap().unwrap(), x);
         }
     });
-}
\ No newline at end of file
+}
+pub fn test_stacked_mixed_format<B: Backend>() {
+    fn to_dense(csr: &CsrMatrix<i32>) -> Array2<i32> {
+        let mut dense = Array2::zeros((csr.nrows(), csr.ncols()));
+        csr.triplet_iter().for_each(|(i, j, v)| dense[[i, j]] = *v);
+        dense
+    }
+
+    with_tmp_dir(|dir| {
+        let x1 = rand_csr::<i32>(10, 5, 20, 1, 100);
+        let x2 = rand_csr::<i32>(8, 5, 15, 1, 100);
+        let ann1 = AnnData::<B>::new(dir.join("test1.h5ad")).unwrap();
+        ann1.set_x(&x1).unwrap();
+        let ann2 = AnnData::<B>::new(dir.join("test2.h5ad")).unwrap();
+        ann2.set_x(to_dense(&x2)).unwrap();
+        let dataset = AnnDataSet::<B>::new(
+            [("ann1", ann1), ("ann2", ann2)],
+            dir.join("dataset.h5ads"),
+            "sample",
+        ).unwrap();
+
+        let full = ndarray::concatenate(
+            ndarray::Axis(0), &[to_dense(&x1).view(), to_dense(&x2).view()]
+        ).unwrap();
+        let rows = [12, 0, 17, 3, 3];
+        let expected = full.select(ndarray::Axis(0), &rows);
+        let adatas = dataset.adatas().inner();
+        let x = adatas.get_x();
+
+        let dense: Array2<i32> = x.read_rows(&rows, Some(ArrayFormat::Dense)).unwrap().unwrap();
+        assert_eq!(dense, expected);
+
+        let csr: ArrayData = x.read_rows(&rows, None).unwrap().unwrap();
+        assert_eq!(csr.format(), Some(ArrayFormat::Csr));
+        assert_eq!(csr.into_format(ArrayFormat::Dense).unwrap(), expected.clone().into());
+
+        let select = s![&rows[..], 1..4];
+        let dense: Array2<i32> = x.read_select(select.as_ref(), Some(ArrayFormat::Dense)).unwrap().unwrap();
+        assert_eq!(dense, expected.slice(ndarray::s![.., 1..4]));
+    })
+}
+
+pub fn test_stacked_var_alignment<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let new = |name: &str, x: Array2<i32>, vars: [&str; 3]| {
+            let adata = AnnData::<B>::new(dir.join(name)).unwrap();
+            adata.set_x(x).unwrap();
+            adata.set_var_names(vars.into_iter().map(String::from).collect()).unwrap();
+            adata
+        };
+        let x1 = Array2::from_shape_vec((2, 3), (0..6).collect()).unwrap();
+        let x2 = Array2::from_shape_vec((3, 3), (6..15).collect()).unwrap();
+        let dataset = AnnDataSet::<B>::new(
+            [
+                ("ann1", new("test1.h5ad", x1.clone(), ["a", "b", "c"])),
+                ("ann2", new("test2.h5ad", x2.clone(), ["c", "a", "b"])),
+            ],
+            dir.join("dataset.h5ads"),
+            "sample",
+        ).unwrap();
+
+        // The columns of the second object are reordered to a, b, c.
+        let expected = ndarray::concatenate(
+            ndarray::Axis(0), &[x1.view(), x2.select(ndarray::Axis(1), &[1, 2, 0]).view()]
+        ).unwrap();
+        let adatas = dataset.adatas().inner();
+        let x = adatas.get_x();
+        assert_eq!(x.data::<Array2<i32>>().unwrap().unwrap(), expected);
+        assert_eq!(x.par_data::<Array2<i32>>().unwrap().unwrap(), expected);
+
+        let select = s![&[4, 0, 3][..], &[2, 0][..]];
+        let expected_select = expected.select(ndarray::Axis(0), &[4, 0, 3]).select(ndarray::Axis(1), &[2, 0]);
+        assert_eq!(x.read_select::<Array2<i32>, _>(select.as_ref(), None).unwrap().unwrap(), expected_select);
+        let csr: ArrayData = x.par_read_select(select.as_ref(), Some(ArrayFormat::Csr)).unwrap().unwrap();
+        assert_eq!(csr.format(), Some(ArrayFormat::Csr));
+        assert_eq!(csr.into_format(ArrayFormat::Dense).unwrap(), expected_select.into());
+
+        let chunks: Vec<Array2<i32>> = x.chunked::<Array2<i32>>(2).map(|(x, _, _)| x).collect();
+        let views: Vec<_> = chunks.iter().map(|x| x.view()).collect();
+        assert_eq!(ndarray::concatenate(ndarray::Axis(0), &views).unwrap(), expected);
+        drop(adatas);
+        dataset.close().unwrap();
+
+        assert!(AnnDataSet::<B>::new(
+            [
+                ("ann1", new("test3.h5ad", x1.clone(), ["a", "b", "c"])),
+                ("ann2", new("test4.h5ad", x2.clone(), ["c", "a", "d"])),
+            ],
+            dir.join("dataset2.h5ads"),
+            "sample",
+        ).is_err());
+    })
+}
+
+pub fn test_dataset_open<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let new = |name: &str, n_obs: usize| {
+            let adata = AnnData::<B>::new(dir.join(name)).unwrap();
+            adata.set_x(Array2::<i32>::ones((n_obs, 3))).unwrap();
+            adata.set_var_names(["a", "b", "c"].into_iter().map(String::from).collect()).unwrap();
+            adata
+        };
+        let dataset = AnnDataSet::<B>::new(
+            [("ann1", new("test1.h5ad", 2)), ("ann2", new("test2.h5ad", 3))],
+            dir.join("dataset.h5ads"),
+            "sample",
+        ).unwrap();
+        assert_eq!((dataset.n_obs(), dataset.n_vars()), (5, 3));
+        dataset.close().unwrap();
+
+        let file = B::open_rw(dir.join("dataset.h5ads")).unwrap();
+        let dataset = AnnDataSet::<B>::open::<std::path::PathBuf>(file, None).unwrap();
+        assert_eq!((dataset.n_obs(), dataset.n_vars()), (5, 3));
+        dataset.close().unwrap();
+
+        let dataset = AnnDataSet::<B>::concat_lazy(
+            [("ann3", new("test3.h5ad", 1)), ("ann4", new("test4.h5ad", 4))],
+            "sample",
+        ).unwrap();
+        assert_eq!((dataset.n_obs(), dataset.n_vars()), (5, 3));
+        dataset.close().unwrap();
+    })
+}
+
+pub fn test_shuffled_chunks<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let x = rand_csr::<i32>(50, 10, 100, 1, 100);
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_x(&x).unwrap();
+        let full: ArrayData = x.into();
+
+        for buffer_size in [None, Some(8)] {
+            let chunks = match buffer_size {
+                None => adata.get_x().shuffled_chunks::<ArrayData>(16, 0),
+                Some(n) => adata.get_x().approx_shuffled_chunks::<ArrayData>(16, n, 0),
+            };
+            assert_eq!(chunks.len(), 4);
+            let mut seen = Vec::new();
+            for chunk in chunks {
+                let (data, indices) = chunk.unwrap();
+                assert!(indices.windows(2).all(|w| w[0] < w[1]));
+                assert_eq!(data, array_select(&full, &[(&indices).into(), SelectInfoElem::full()]));
+                seen.extend(indices);
+            }
+            seen.sort();
+            assert_eq!(seen, (0..50).collect::<Vec<_>>());
+        }
+    })
+}
+
+pub fn test_var_column_order<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let input = dir.join("input.h5ad");
+        let output = dir.join("output.h5ad");
+        let var = polars::df!(
+            "zeta" => [1, 2, 3],
+            "alpha" => ["a", "b", "c"],
+            "mid" => [0.1, 0.2, 0.3],
+        ).unwrap();
+
+        let adata = AnnData::<B>::new(&input).unwrap();
+        adata.set_var(var).unwrap();
+        adata.get_var().inner().set_column("beta", polars::prelude::Series::new("beta", [true, false, true])).unwrap();
+        adata.write::<B, _>(&output).unwrap();
+        adata.close().unwrap();
+
+        let expected = vec!["zeta", "alpha", "mid", "beta"];
+        for file in [&input, &output] {
+            let adata = AnnData::<B>::open(B::open(file).unwrap()).unwrap();
+            assert_eq!(adata.read_var().unwrap().get_column_names(), expected);
+            assert_eq!(
+                adata.get_var().inner().get_column_names().iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+                expected,
+            );
+            adata.close().unwrap();
+        }
+    })
+}
+
+pub fn test_flush<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_obs(polars::df!("a" => [1, 2, 3]).unwrap()).unwrap();
+        adata.flush().unwrap();
+
+        let reader = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert_eq!(reader.read_obs().unwrap().get_column_names(), vec!["a"]);
+        reader.close().unwrap();
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_neighbors<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let conn: ArrayData = rand_csr::<f64>(20, 20, 50, 0.0, 1.0).into();
+        let dist: ArrayData = rand_csr::<f64>(20, 20, 50, 0.0, 10.0).into();
+        let neighbors = Neighbors::new(conn, dist).with_params([
+            ("n_neighbors".to_string(), 15i64.into()),
+            ("metric".to_string(), "euclidean".to_string().into()),
+        ]);
+
+        assert!(adata.neighbors(None).unwrap().is_none());
+        adata.set_neighbors(None, neighbors.clone()).unwrap();
+        adata.set_neighbors(Some("umap"), neighbors.clone()).unwrap();
+        let mut obsp = adata.obsp().keys();
+        obsp.sort();
+        assert_eq!(obsp, vec!["connectivities", "distances", "umap_connectivities", "umap_distances"]);
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert_eq!(adata.neighbors(None).unwrap().unwrap(), neighbors);
+        assert_eq!(adata.neighbors(Some("umap")).unwrap().unwrap(), neighbors);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_spatial<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_x(Array2::<f32>::zeros((10, 3))).unwrap();
+        assert!(adata.spatial().unwrap().is_none());
+        assert!(adata.set_spatial(Spatial::new(Array2::<f64>::zeros((10, 3)).into())).is_err());
+
+        let coordinates: ArrayData = Array2::from_shape_fn((10, 2), |(i, j)| (i * 2 + j) as f64).into();
+        let library = SpatialLibrary {
+            images: [
+                ("hires".to_string(), ArrayD::<u8>::zeros(vec![20, 20, 3]).into()),
+                ("lowres".to_string(), ArrayD::<u8>::zeros(vec![5, 5, 3]).into()),
+            ].into_iter().collect(),
+            scalefactors: [
+                ("tissue_hires_scalef".to_string(), 0.5f64.into()),
+                ("spot_diameter_fullres".to_string(), 89.4f64.into()),
+            ].into_iter().collect::<std::collections::HashMap<String, Data>>().into(),
+        };
+        let spatial = Spatial::new(coordinates).with_library("slide1", library);
+        adata.set_spatial(spatial.clone()).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert_eq!(adata.spatial().unwrap().unwrap(), spatial);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_dirty<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        assert!(!adata.is_dirty());
+        assert!(adata.last_modified().is_none());
+
+        adata.set_x(Array2::<f32>::zeros((10, 3))).unwrap();
+        assert!(adata.is_dirty());
+        let t1 = adata.last_modified().unwrap();
+        adata.flush().unwrap();
+        assert!(!adata.is_dirty());
+
+        adata.obsm().add("X_pca", Array2::<f32>::zeros((10, 2))).unwrap();
+        assert!(adata.is_dirty());
+        adata.flush().unwrap();
+        adata.uns().add("a", 1i64).unwrap();
+        assert!(adata.is_dirty());
+        adata.flush().unwrap();
+        adata.set_obs_names((0..10).map(|x| x.to_string()).collect()).unwrap();
+        adata.flush().unwrap();
+        adata.get_obs().inner().set_column("a", polars::prelude::Series::new("a", vec![1; 10])).unwrap();
+        assert!(adata.is_dirty());
+        let t2 = adata.last_modified().unwrap();
+        assert!(t2 >= t1);
+
+        // Reading does not modify the file.
+        adata.flush().unwrap();
+        adata.obsm().get_item::<ArrayData>("X_pca").unwrap().unwrap();
+        adata.read_obs().unwrap();
+        assert!(!adata.is_dirty());
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert!(!adata.is_dirty());
+        assert_eq!(adata.last_modified(), Some(t2));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_sum<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i32>(1234, 57, 5000, 1, 100);
+        let mut dense = Array2::<i32>::zeros((csr.nrows(), csr.ncols()));
+        csr.triplet_iter().for_each(|(i, j, v)| dense[[i, j]] = *v);
+
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        assert!(adata.x().sum(0).unwrap().is_none());
+        adata.set_x(&csr).unwrap();
+        for axis in [0, 1] {
+            let expected = dense.sum_axis(ndarray::Axis(axis)).mapv(|x| x as f64);
+            assert_eq!(adata.x().sum(axis).unwrap().unwrap(), expected);
+            assert_eq!(ArrayData::from(dense.clone()).sum_axis(axis).unwrap(), expected);
+            let csc = nalgebra_sparse::CscMatrix::from(&csr);
+            assert_eq!(ArrayData::from(csc).sum_axis(axis).unwrap(), expected);
+        }
+        assert!(adata.x().sum(2).is_err());
+    })
+}
+
+pub fn test_var<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let csr = rand_csr::<i32>(1234, 57, 5000, 1, 100);
+        let mut dense = Array2::<f64>::zeros((csr.nrows(), csr.ncols()));
+        csr.triplet_iter().for_each(|(i, j, v)| dense[[i, j]] = *v as f64);
+        let close = |a: &ndarray::Array1<f64>, b: &ndarray::Array1<f64>| {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-8 * x.abs().max(1.0))
+        };
+
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        assert!(adata.x().var(0, 0).unwrap().is_none());
+        adata.set_x(&csr).unwrap();
+        for (axis, ddof) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let expected = dense.var_axis(ndarray::Axis(axis), ddof as f64);
+            assert!(close(&adata.x().var(axis, ddof).unwrap().unwrap(), &expected));
+            assert!(close(&ArrayData::from(dense.clone()).var_axis(axis, ddof).unwrap(), &expected));
+            let csc = nalgebra_sparse::CscMatrix::from(&csr);
+            assert!(close(&ArrayData::from(csc).var_axis(axis, ddof).unwrap(), &expected));
+        }
+
+        // Duplicate entries are summed.
+        let mut coo = nalgebra_sparse::CooMatrix::new(csr.nrows(), csr.ncols());
+        csr.triplet_iter().for_each(|(i, j, v)| {
+            coo.push(i, j, *v - 1);
+            coo.push(i, j, 1);
+        });
+        let noncanonical = ArrayData::from(CsrNonCanonical::from(&coo));
+        assert!(close(&noncanonical.var_axis(0, 1).unwrap(), &dense.var_axis(ndarray::Axis(0), 1.0)));
+
+        adata.set_x(dense.clone()).unwrap();
+        assert!(close(&adata.x().var(0, 1).unwrap().unwrap(), &dense.var_axis(ndarray::Axis(0), 1.0)));
+        assert!(adata.x().var(2, 0).is_err());
+    })
+}
+
+pub fn test_read_many<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let pca = Array2::<f32>::ones((10, 5));
+        let umap = Array2::<f64>::zeros((10, 2));
+        adata.obsm().add("X_pca", pca.clone()).unwrap();
+        adata.obsm().add("X_umap", umap.clone()).unwrap();
+        adata.uns().add("a", 1i64).unwrap();
+
+        let result = adata.read_many(&[
+            (ElemSlot::Obsm, "X_pca"),
+            (ElemSlot::Uns, "a"),
+            (ElemSlot::Obsm, "X_umap"),
+            (ElemSlot::Obsp, "missing"),
+        ]);
+        assert_eq!(result.len(), 4);
+        let get = |key: &str| result[key].as_ref().unwrap().clone();
+        assert_eq!(get("obsm/X_pca"), Data::from(ArrayData::from(pca)));
+        assert_eq!(get("obsm/X_umap"), Data::from(ArrayData::from(umap)));
+        assert_eq!(get("uns/a"), Data::from(1i64));
+        assert!(result["obsp/missing"].is_err());
+    })
+}
+
+pub fn test_categorical_codes_dtype<B: Backend>() {
+    use anndata::backend::{DatasetOp, GroupOp, ScalarType};
+    use polars::prelude::{CategoricalOrdering, DataFrame, DataType, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let cat = Series::new("cat", ["a", "b", "c", "a"])
+            .cast(&DataType::Categorical(None, CategoricalOrdering::Physical))
+            .unwrap();
+        adata.set_obs(DataFrame::new(vec![cat]).unwrap()).unwrap();
+        adata.close().unwrap();
+
+        let file = B::open(&file).unwrap();
+        let codes = file.open_group("obs").unwrap().open_group("cat").unwrap()
+            .open_dataset("codes").unwrap();
+        assert_eq!(codes.dtype().unwrap(), ScalarType::I8);
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        assert_eq!(
+            adata.read_obs().unwrap().column("cat").unwrap().categorical().unwrap()
+                .iter_str().map(|x| x.unwrap().to_string()).collect::<Vec<_>>(),
+            vec!["a", "b", "c", "a"],
+        );
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_column_encoding<B: Backend>() {
+    use anndata::backend::{DatasetOp, GroupOp, LocationOp};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let obs = polars::df!("name" => ["a", "b", "c", "a"], "n" => [1, 2, 3, 4]).unwrap();
+        adata.set_obs(obs.clone()).unwrap();
+        adata.get_obs().inner().as_categorical("name").unwrap();
+        assert!(adata.get_obs().inner().as_categorical("n").is_err());
+        assert_eq!(
+            adata.read_obs().unwrap().column("name").unwrap().categorical().unwrap()
+                .iter_str().map(|x| x.unwrap().to_string()).collect::<Vec<_>>(),
+            vec!["a", "b", "c", "a"],
+        );
+        adata.close().unwrap();
+
+        let file = B::open(&file).unwrap();
+        let name = file.open_group("obs").unwrap().open_group("name").unwrap();
+        assert_eq!(name.read_str_attr("encoding-type").unwrap(), "categorical");
+        let codes: Vec<i8> = name.open_dataset("codes").unwrap().read_array::<i8, ndarray::Ix1>().unwrap().to_vec();
+        assert_eq!(codes, vec![0, 1, 2, 0]);
+        let categories = name.open_dataset("categories").unwrap().read_array::<String, ndarray::Ix1>().unwrap();
+        assert_eq!(categories.to_vec(), vec!["a", "b", "c"]);
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        adata.get_obs().inner().as_string("name").unwrap();
+        assert_eq!(adata.read_obs().unwrap(), obs);
+        let file = adata.filename();
+        adata.close().unwrap();
+        let obs = B::open(&file).unwrap().open_group("obs").unwrap();
+        let name = obs.open_dataset("name").unwrap();
+        assert_eq!(name.read_str_attr("encoding-type").unwrap(), "string-array");
+        assert!(!obs.exists("name.rewrite").unwrap());
+    })
+}
+
+pub fn test_categorical_encoding<B: Backend>() {
+    use anndata::backend::{GroupOp, WriteConfig};
+    use polars::prelude::{DataFrame, DataType, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let obs = DataFrame::new(vec![
+            Series::new("tissue", ["cortex", "cortex", "liver", "cortex", "liver", "cortex"]),
+            Series::new("barcode", ["a", "b", "c", "d", "e", "f"]),
+        ]).unwrap();
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_obs(obs.clone()).unwrap();
+        adata.close().unwrap();
+
+        let file = B::open(&file).unwrap();
+        let group = file.open_group("obs").unwrap();
+        assert!(group.open_group("tissue").is_ok());
+        assert!(group.open_dataset("barcode").is_ok());
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        let result = adata.read_obs().unwrap();
+        assert!(matches!(result.column("tissue").unwrap().dtype(), DataType::Categorical(_, _)));
+        assert_eq!(result.column("barcode").unwrap().dtype(), &DataType::String);
+        assert_eq!(
+            adata.get_obs().inner().read_column_as::<String>("tissue").unwrap(),
+            vec!["cortex", "cortex", "liver", "cortex", "liver", "cortex"],
+        );
+
+        adata.close().unwrap();
+
+        // The threshold of a single write is set in its configuration.
+        let config = WriteConfig { categorical_threshold: None, ..Default::default() };
+        let adata = AnnDataBuilder::new(dir.join("strings.h5ad"))
+            .write_config(config)
+            .x(Array2::<f32>::zeros((6, 2)))
+            .obs(obs)
+            .build::<B>()
+            .unwrap();
+        assert_eq!(adata.read_obs().unwrap().column("tissue").unwrap().dtype(), &DataType::String);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_unsupported_uns<B: Backend>() {
+    use anndata::backend::{GroupOp, LocationOp};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let output = dir.join("output.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.uns().add("a", 1i64).unwrap();
+        adata.close().unwrap();
+
+        // Write an element with an encoding that this library does not know.
+        let file = B::open_rw(&file).unwrap();
+        let group = file.open_group("uns").unwrap().create_group("awkward").unwrap();
+        group.write_str_attr("encoding-type", "awkward-array").unwrap();
+        group.create_array_data("node0-data", &ndarray::arr1(&[1i64, 2, 3]), Default::default()).unwrap();
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        let unknown = adata.validate().unwrap();
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].path, "uns/awkward");
+        assert_eq!(unknown[0].message, "unsupported encoding 'awkward-array'");
+        assert!(matches!(adata.uns().get_item::<Data>("awkward").unwrap(), Some(Data::Unknown(_))));
+        assert!(adata.uns().get_item::<i64>("awkward").is_err());
+        assert_eq!(adata.uns().get_item::<i64>("a").unwrap(), Some(1));
+
+        adata.write::<B, _>(&output).unwrap();
+        adata.close().unwrap();
+        let adata = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+        assert_eq!(adata.validate().unwrap().len(), 1);
+        let group = B::open(&output).unwrap().open_group("uns/awkward").unwrap();
+        assert_eq!(group.read_str_attr("encoding-type").unwrap(), "awkward-array");
+        assert!(group.exists("node0-data").unwrap());
+    })
+}
+
+pub fn test_densify_x<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let csr = rand_csr::<i32>(25, 10, 60, 1, 100);
+        adata.set_x(&csr).unwrap();
+        adata.densify_x("dense", 7).unwrap();
+
+        let expected = ArrayData::from(csr.clone()).into_format(ArrayFormat::Dense).unwrap();
+        let dense = adata.layers().get_item::<ArrayData>("dense").unwrap().unwrap();
+        assert_eq!(dense, expected);
+        assert_eq!(adata.x().get::<CsrMatrix<i32>>().unwrap().unwrap(), csr);
+        assert!(adata.densify_x("dense", 0).is_err());
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::new(dir.join("empty.h5ad")).unwrap();
+        assert!(adata.densify_x("dense", 10).is_err());
+    })
+}
+
+pub fn test_raw<B: Backend>() {
+    use anndata::backend::GroupOp;
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let output = dir.join("output.h5ad");
+        let subset = dir.join("subset.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_x(Array2::<i32>::zeros((3, 2))).unwrap();
+        adata.close().unwrap();
+
+        let raw_x = Array2::from_shape_fn((3, 4), |(i, j)| (i * 4 + j) as i32);
+        let file = B::open_rw(&file).unwrap();
+        let group = file.create_group("raw").unwrap();
+        raw_x.write(&group, "X").unwrap();
+        DataFrame::new(vec![Series::new("n", [1i32, 2, 3, 4])]).unwrap().write(&group, "var").unwrap();
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        assert_eq!(adata.get_raw().inner().n_vars(), 4);
+        let x: Array2<i32> = adata.get_raw().inner().x().inner().data().unwrap();
+        assert_eq!(x, raw_x);
+        assert!(adata.validate().unwrap().iter().all(|x| !x.path.starts_with("raw")));
+
+        adata.write::<B, _>(&output).unwrap();
+        adata.write_select::<B, _, _>([[0, 2].as_slice().into(), SelectInfoElem::full()], &subset).unwrap();
+        let raw = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+        let x: Array2<i32> = raw.get_raw().inner().x().inner().data().unwrap();
+        assert_eq!(x, raw_x);
+        let raw = AnnData::<B>::open(B::open(&subset).unwrap()).unwrap();
+        let x: Array2<i32> = raw.get_raw().inner().x().inner().data().unwrap();
+        assert_eq!(x, raw_x.select(ndarray::Axis(0), &[0, 2]));
+        assert_eq!(raw.get_raw().inner().n_vars(), 4);
+        adata.close().unwrap();
+
+        // A raw group without var is skipped, but kept in the file.
+        let file = B::open_rw(dir.join("test.h5ad")).unwrap();
+        file.open_group("raw").unwrap().delete("var").unwrap();
+        let adata = AnnData::<B>::open(file).unwrap();
+        assert!(adata.get_raw().is_empty());
+        assert_eq!(adata.x().get::<Array2<i32>>().unwrap().unwrap(), Array2::zeros((3, 2)));
+        let violations = adata.validate().unwrap();
+        assert!(violations.iter().any(|x| x.path == "raw" && x.message == "missing 'var'"));
+        adata.write::<B, _>(&output).unwrap();
+        assert!(B::open(&output).unwrap().exists("raw/X").unwrap());
+
+        adata.del_raw().unwrap();
+        adata.close().unwrap();
+        assert!(!B::open(dir.join("test.h5ad")).unwrap().exists("raw").unwrap());
+    })
+}
+
+pub fn test_nested_uns<B: Backend>() {
+    use anndata::data::Mapping;
+    use std::collections::HashMap;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let names: HashMap<String, Data> = [
+            ("a".to_string(), Data::from(ndarray::arr1(&["x".to_string(), "y".to_string()]))),
+            ("b".to_string(), Data::from(1i64)),
+        ].into_iter().collect();
+        let rank: HashMap<String, Data> = [
+            ("names".to_string(), Data::from(Mapping::from(names))),
+            ("method".to_string(), Data::from("t-test".to_string())),
+        ].into_iter().collect();
+        adata.uns().add("rank_genes_groups", Mapping::from(rank)).unwrap();
+        adata.uns().add("x", 1i64).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open_rw(&file).unwrap()).unwrap();
+        let uns = adata.uns();
+        assert!(uns.get_collection("x").unwrap().is_none());
+        assert!(uns.get_collection("missing").unwrap().is_none());
+        let rank = uns.get_collection("rank_genes_groups").unwrap().unwrap();
+        let mut keys = (&rank).keys();
+        keys.sort();
+        assert_eq!(keys, vec!["method", "names"]);
+        let names = rank.get_collection("names").unwrap().unwrap();
+        assert_eq!((&names).get_item::<i64>("b").unwrap(), Some(1));
+
+        let elem = uns.get_path("rank_genes_groups/names/b").unwrap().unwrap();
+        assert_eq!(elem.inner().data::<i64>().unwrap(), 1);
+        assert!(uns.get_path("rank_genes_groups/missing/b").unwrap().is_none());
+        assert!(uns.get_path("x/b").unwrap().is_none());
+
+        // Changes made through a nested collection are visible from the parent.
+        (&names).add("c", 2i64).unwrap();
+        let rank = uns.get_item::<Mapping>("rank_genes_groups").unwrap().unwrap();
+        let names = Mapping::try_from(rank["names"].clone()).unwrap();
+        assert_eq!(names["c"], Data::from(2i64));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_embedding<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let umap: ArrayData = Array2::from_shape_fn((10, 2), |(i, j)| (i * 2 + j) as f64).into();
+        let pca: ArrayData = Array2::<f32>::zeros((10, 5)).into();
+        adata.obsm().add("X_umap", umap.clone()).unwrap();
+        adata.obsm().add("X_pca", pca.clone()).unwrap();
+        adata.obsm().add("spatial", Array2::<f64>::zeros((10, 2))).unwrap();
+
+        let mut embeddings = adata.obsm().embeddings();
+        embeddings.sort();
+        assert_eq!(embeddings, vec!["X_pca", "X_umap"]);
+
+        assert_eq!(adata.embedding("umap").unwrap().unwrap(), umap);
+        assert_eq!(adata.embedding("X_umap").unwrap().unwrap(), umap);
+        assert_eq!(adata.embedding("pca").unwrap().unwrap(), pca);
+        assert!(adata.embedding("spatial").unwrap().is_some());
+        assert!(adata.embedding("tsne").unwrap().is_none());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_dataframe_select<B: Backend>() {
+    use polars::prelude::{DataFrame, DataType, NamedFrom, Series};
+
+    // Categorical columns are compared by their values.
+    let as_str = |df: DataFrame| DataFrame::new(
+        df.get_columns().iter().map(|x| x.cast(&DataType::String).unwrap()).collect()
+    ).unwrap();
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let obs = DataFrame::new(vec![
+            Series::new("nullable", [Some(1), None, Some(3), None, Some(5), Some(6)]),
+            Series::new("float", [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            Series::new("tissue", ["cortex", "cortex", "liver", "cortex", "liver", "cortex"]),
+            Series::new("barcode", ["a", "b", "c", "d", "e", "f"]),
+        ]).unwrap();
+        adata.set_obs(obs.clone()).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let rows = SelectInfoElem::from(&[4usize, 1, 2][..]);
+        let expected = ArrayOp::select_axis(&obs, 0, &rows);
+        let result = adata.get_obs().inner().select_axis(0, &rows).unwrap();
+        assert_eq!(as_str(result), as_str(expected.clone()));
+
+        let cols = s![1..3, 2..4];
+        let result = adata.get_obs().inner().select(cols.as_ref()).unwrap();
+        assert_eq!(result.get_column_names(), vec!["tissue", "barcode"]);
+        assert_eq!(result.height(), 2);
+
+        adata.subset(s![rows, ..]).unwrap();
+        assert_eq!(as_str(adata.read_obs().unwrap()), as_str(expected));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_usage<B: Backend>() {
+    use anndata::backend::ScalarType;
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_x(Array2::<f64>::zeros((10, 5))).unwrap();
+        adata.obsm().add("csr", rand_csr::<i32>(10, 4, 12, 1, 100)).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("n", (0..10).collect::<Vec<i64>>()),
+        ]).unwrap()).unwrap();
+        adata.uns().add("a", 1i64).unwrap();
+
+        let report = adata.usage().unwrap();
+        let x = report.elements.iter().find(|x| x.path == "X").unwrap();
+        assert_eq!(x.slot, "X");
+        assert_eq!(x.encoding, "array");
+        assert_eq!(x.dtype, Some(ScalarType::F64));
+        assert_eq!(x.logical_size, 400);
+        assert!(x.storage_size > 0);
+
+        let csr = report.elements.iter().find(|x| x.path == "obsm/csr").unwrap();
+        assert_eq!(csr.slot, "obsm");
+        assert_eq!(csr.encoding, "csr_matrix");
+        assert_eq!(csr.dtype, Some(ScalarType::I32));
+        assert!(report.elements.iter().any(|x| x.path == "obs/n"));
+        assert!(report.elements.iter().any(|x| x.path == "uns/a"));
+
+        let slots = report.by_slot();
+        assert_eq!(slots["X"].n_elems, 1);
+        assert_eq!(slots["X"].logical_size, 400);
+        assert_eq!(
+            slots.values().map(|x| x.storage_size).sum::<u64>(),
+            report.storage_size(),
+        );
+        assert!(report.to_string().contains("obsm/csr"));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_create_mode<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let path = dir.join("test.h5ad");
+        assert_eq!("w-".parse::<CreateMode>().unwrap(), CreateMode::Exclusive);
+        assert!("r".parse::<CreateMode>().is_err());
+
+        let adata = AnnData::<B>::new_with_mode(&path, CreateMode::Exclusive).unwrap();
+        adata.set_x(Array2::<f64>::zeros((10, 5))).unwrap();
+        adata.close().unwrap();
+
+        let err = AnnData::<B>::new_with_mode(&path, CreateMode::Exclusive).err().unwrap();
+        assert!(err.to_string().contains("mode 'w'"));
+
+        let adata = AnnData::<B>::new_with_mode(&path, CreateMode::Append).unwrap();
+        assert_eq!(adata.n_obs(), 10);
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::new_with_mode(&path, CreateMode::Truncate).unwrap();
+        assert_eq!(adata.n_obs(), 0);
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::new_with_mode(dir.join("new.h5ad"), CreateMode::Append).unwrap();
+        assert!(adata.x().is_empty());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_coo<B: Backend>() {
+    use anndata::backend::{GroupOp, LocationOp};
+    use anndata::s;
+    use itertools::Itertools;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let csr = rand_csr::<i32>(10, 6, 20, 1, 100);
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_x(Array2::<f64>::zeros((10, 5))).unwrap();
+        adata.close().unwrap();
+
+        // Store the entries in reverse order, as exporters may not sort them.
+        let mut triplets: Vec<_> = csr.triplet_iter().map(|(i, j, v)| (i as i64, j as i64, *v)).collect();
+        triplets.reverse();
+        let (row, col, data): (Vec<_>, Vec<_>, Vec<_>) = triplets.into_iter().multiunzip();
+        let file = B::open_rw(&file).unwrap();
+        let group = file.open_group("obsm").unwrap().create_group("coo").unwrap();
+        group.write_str_attr("encoding-type", "coo_matrix").unwrap();
+        group.write_str_attr("encoding-version", "0.1.0").unwrap();
+        group.write_array_attr("shape", &ndarray::arr1(&[10i64, 6])).unwrap();
+        group.create_array_data("row", &ndarray::Array1::from(row), Default::default()).unwrap();
+        group.create_array_data("col", &ndarray::Array1::from(col), Default::default()).unwrap();
+        group.create_array_data("data", &ndarray::Array1::from(data), Default::default()).unwrap();
+
+        // A shape that is not 2-dimensional is an error rather than a panic.
+        let bad = file.open_group("uns").unwrap().create_group("bad_coo").unwrap();
+        bad.write_str_attr("encoding-type", "coo_matrix").unwrap();
+        bad.write_str_attr("encoding-version", "0.1.0").unwrap();
+        bad.write_array_attr("shape", &ndarray::arr1(&[10i64])).unwrap();
+        for name in ["row", "col"] {
+            bad.create_array_data(name, &ndarray::Array1::<i64>::zeros(1), Default::default()).unwrap();
+        }
+        bad.create_array_data("data", &ndarray::Array1::<i32>::ones(1), Default::default()).unwrap();
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        assert!(adata.uns().get_item::<Data>("bad_coo").is_err());
+        adata.uns().remove("bad_coo").unwrap();
+        assert!(adata.validate().unwrap().is_empty());
+        assert_eq!(
+            adata.obsm().get_item::<ArrayData>("coo").unwrap().unwrap(),
+            ArrayData::from(csr.clone()),
+        );
+        assert_eq!(
+            adata.obsm().get_item_slice::<ArrayData, _>("coo", s![2..5, ..]).unwrap().unwrap(),
+            ArrayData::from(csr).select(s![2..5, ..].as_ref()),
+        );
+    })
+}
+
+pub fn test_qc_metrics<B: Backend>() {
+    use anndata::data::DataFrameIndex;
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        assert!(adata.calculate_qc_metrics().unwrap().is_none());
+
+        let csr = rand_csr::<i32>(1234, 57, 5000, 1, 100);
+        let mut dense = Array2::<i32>::zeros((csr.nrows(), csr.ncols()));
+        csr.triplet_iter().for_each(|(i, j, v)| dense[[i, j]] = *v);
+        adata.set_x(&csr).unwrap();
+        adata.set_obs_names(DataFrameIndex::from((0..1234).map(|x| x.to_string()).collect::<Vec<_>>())).unwrap();
+        adata.set_var(DataFrame::new(vec![Series::new("a", vec![1i64; 57])]).unwrap()).unwrap();
+
+        let metrics = adata.calculate_qc_metrics().unwrap().unwrap();
+        let nonzero = dense.mapv(|x| (x != 0) as i64);
+        let total_counts = dense.sum_axis(ndarray::Axis(1)).mapv(|x| x as f64);
+        assert_eq!(metrics.total_counts, total_counts);
+        assert_eq!(metrics.n_genes_by_counts, nonzero.sum_axis(ndarray::Axis(1)));
+        assert_eq!(metrics.n_cells_by_counts, nonzero.sum_axis(ndarray::Axis(0)));
+        assert_eq!(
+            metrics.mean_counts,
+            dense.sum_axis(ndarray::Axis(0)).mapv(|x| x as f64 / 1234.0),
+        );
+
+        let obs = adata.read_obs().unwrap();
+        assert_eq!(
+            obs.column("total_counts").unwrap().f64().unwrap().into_no_null_iter().collect::<Vec<_>>(),
+            total_counts.to_vec(),
+        );
+        assert!(obs.column("n_genes_by_counts").is_ok());
+        assert_eq!(adata.obs_names().into_vec()[3], "3");
+        let var = adata.read_var().unwrap();
+        assert_eq!(var.get_column_names(), vec!["a", "n_cells_by_counts", "mean_counts"]);
+
+        // Computing the metrics again replaces the columns.
+        adata.calculate_qc_metrics().unwrap();
+        assert_eq!(adata.read_var().unwrap().get_column_names(), vec!["a", "n_cells_by_counts", "mean_counts"]);
+
+        // Explicitly stored zeros are not counted.
+        let adata = AnnData::<B>::new(dir.join("zeros.h5ad")).unwrap();
+        adata.set_x(CsrMatrix::try_from_csr_data(
+            2, 2, vec![0, 2, 3], vec![0, 1, 1], vec![0.0f64, 2.0, 3.0],
+        ).unwrap()).unwrap();
+        let metrics = adata.calculate_qc_metrics().unwrap().unwrap();
+        assert_eq!(metrics.n_genes_by_counts, ndarray::array![1, 1]);
+        assert_eq!(metrics.n_cells_by_counts, ndarray::array![0, 2]);
+        assert_eq!(metrics.mean_counts, ndarray::array![0.0, 2.5]);
+
+        // Duplicate entries are counted once.
+        let adata = AnnData::<B>::new(dir.join("duplicates.h5ad")).unwrap();
+        let mut coo = nalgebra_sparse::CooMatrix::new(2, 2);
+        coo.push(0, 1, 1i32);
+        coo.push(0, 1, 2);
+        coo.push(1, 0, 4);
+        adata.set_x(&CsrNonCanonical::from(&coo)).unwrap();
+        let metrics = adata.calculate_qc_metrics().unwrap().unwrap();
+        assert_eq!(metrics.total_counts, ndarray::array![3.0, 4.0]);
+        assert_eq!(metrics.n_genes_by_counts, ndarray::array![1, 1]);
+        assert_eq!(metrics.n_cells_by_counts, ndarray::array![1, 1]);
+    })
+}
+
+pub fn test_f16<B: Backend>() {
+    use anndata::backend::{DatasetOp, GroupOp, ScalarType, WriteConfig};
+
+    with_tmp_dir(|dir| {
+        let arr = Array2::from_shape_fn((30, 4), |(i, j)| f16::from_f32(i as f32 * 0.25 - j as f32));
+        let mut coo = CooMatrix::new(30, 4);
+        arr.indexed_iter().filter(|((i, j), _)| (i + j) % 3 == 0).for_each(|((i, j), &v)| coo.push(i, j, v));
+        let csr = CsrMatrix::from(&coo);
+
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.obsm().add("half", &arr).unwrap();
+        adata.set_x(&csr).unwrap();
+        adata.uns().add("scalar", f16::from_f32(1.5)).unwrap();
+        assert_eq!(adata.obsm().get_item::<Array2<f16>>("half").unwrap().unwrap(), arr);
+        assert_eq!(
+            adata.obsm().get("half").unwrap().slice::<Array2<f16>, _>(s![3..7, 1..3]).unwrap().unwrap(),
+            arr.slice(ndarray::s![3..7, 1..3]),
+        );
+        assert_eq!(adata.x().get::<CsrMatrix<f16>>().unwrap().unwrap(), csr);
+        assert_eq!(
+            adata.x().slice::<ArrayData, _>(s![vec![5, 1], ..]).unwrap().unwrap(),
+            ArrayData::from(csr.clone()).select(s![vec![5, 1], ..].as_ref()),
+        );
+        assert_eq!(adata.uns().get_item::<f16>("scalar").unwrap(), Some(f16::from_f32(1.5)));
+        let usage = adata.usage().unwrap();
+        let elem = usage.elements.iter().find(|x| x.path == "obsm/half").unwrap();
+        assert_eq!(elem.dtype, Some(ScalarType::F16));
+        assert_eq!(elem.logical_size, 30 * 4 * 2);
+        adata.close().unwrap();
+
+        // Values are stored with 16 bits.
+        let file = B::create(dir.join("test.h5")).unwrap();
+        let config = WriteConfig { compression: None, block_size: None, ..Default::default() };
+        let dataset = file.create_array_data("half", &arr, config).unwrap();
+        assert_eq!(dataset.dtype().unwrap(), ScalarType::F16);
+        assert_eq!(dataset.storage_size().unwrap(), 30 * 4 * 2);
+        assert_eq!(dataset.read_array::<f16, ndarray::Ix2>().unwrap(), arr);
+    })
+}
+
+pub fn test_transposed<B: Backend>() {
+    use anndata::backend::{DatasetOp, GroupOp, LocationOp};
+    use ndarray::Array;
+    use ndarray_rand::{rand_distr::Uniform, RandomExt};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let arr = Array::random((50, 10), Uniform::new(-1.0, 1.0));
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.obsm().add_transposed("X_pca", arr.clone()).unwrap();
+        let elem = adata.obsm().get("X_pca").unwrap();
+        assert_eq!(elem.shape().unwrap().as_ref(), &[50, 10]);
+        assert_eq!(adata.obsm().get_item::<Array2<f64>>("X_pca").unwrap().unwrap(), arr);
+        assert_eq!(
+            elem.slice_axis::<Array2<f64>, _>(1, SelectInfoElem::from(2..3)).unwrap().unwrap(),
+            arr.slice(ndarray::s![.., 2..3]),
+        );
+        assert_eq!(
+            elem.slice::<ArrayData, _>(s![vec![7, 3], 1..4]).unwrap().unwrap(),
+            ArrayData::from(arr.select(ndarray::Axis(0), &[7, 3]).slice(ndarray::s![.., 1..4]).to_owned()),
+        );
+        // Reading into a buffer would return the values in the stored order.
+        assert!(!elem.inner().read_dense_into(s![.., ..].as_ref(), &mut vec![0.0f64; 500]).unwrap());
+        assert!(adata.obsm().add_transposed("X_bad", Array2::<f64>::zeros((3, 2))).is_err());
+        adata.close().unwrap();
+
+        // A single component is a single row in the file.
+        let dataset = B::open(&file).unwrap().open_group("obsm").unwrap().open_dataset("X_pca").unwrap();
+        assert_eq!(dataset.shape().as_ref(), &[10, 50]);
+        assert_eq!(
+            dataset.read_array_slice::<f64, _, ndarray::Ix2>(s![2..3, ..].as_ref()).unwrap().row(0),
+            arr.column(2),
+        );
+
+        // Overwriting the key with `add` and subsetting keep the transposed layout.
+        let adata = AnnData::<B>::open(B::open_rw(&file).unwrap()).unwrap();
+        assert_eq!(adata.obsm().get_item::<Array2<f64>>("X_pca").unwrap().unwrap(), arr);
+        let square = Array::random((50, 50), Uniform::new(-1.0, 1.0));
+        adata.obsm().add_transposed("X_pca", square.clone()).unwrap();
+        adata.obsm().add("X_pca", square.clone()).unwrap();
+        assert_eq!(adata.obsm().get_item::<Array2<f64>>("X_pca").unwrap().unwrap(), square);
+        adata.subset(s![0..20, ..]).unwrap();
+        let expected = square.slice(ndarray::s![0..20, ..]).to_owned();
+        assert_eq!(adata.obsm().get_item::<Array2<f64>>("X_pca").unwrap().unwrap(), expected);
+        adata.close().unwrap();
+
+        let dataset = B::open(&file).unwrap().open_group("obsm").unwrap().open_dataset("X_pca").unwrap();
+        assert_eq!(dataset.shape().as_ref(), &[50, 20]);
+        assert!(dataset.read_scalar_attr::<bool>("transposed").unwrap());
+    })
+}
+
+pub fn test_bool_columns<B: Backend>() {
+    use anndata::backend::{GroupOp, LocationOp};
+    use polars::prelude::{DataFrame, DataType, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("flag", [1i8, 0, 0, 1]),
+            Series::new("hinted", [0i8, 1, 1, 1]),
+            Series::new("count", [0i64, 1, 2, 1]),
+        ]).unwrap()).unwrap();
+        adata.close().unwrap();
+
+        let file = B::open_rw(&file).unwrap();
+        let obs = file.open_group("obs").unwrap();
+        obs.open_dataset("hinted").unwrap().write_str_attr("dtype", "bool").unwrap();
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        let obs = adata.read_obs().unwrap();
+        assert_eq!(obs.column("flag").unwrap().dtype(), &DataType::Int8);
+        assert_eq!(obs.column("count").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(
+            obs.column("hinted").unwrap(),
+            &Series::new("hinted", [false, true, true, true]),
+        );
+
+        adata.set_coerce_bool_columns(true);
+        let obs = adata.read_obs().unwrap();
+        assert_eq!(obs.column("flag").unwrap(), &Series::new("flag", [true, false, false, true]));
+        assert_eq!(obs.column("count").unwrap().dtype(), &DataType::Int64);
+        let column = adata.get_obs().inner().read_column("flag").unwrap();
+        assert_eq!(column.dtype(), &DataType::Boolean);
+        let selected = adata.get_obs().inner().select_axis(0, SelectInfoElem::from(vec![3, 1])).unwrap();
+        assert_eq!(selected.column("flag").unwrap(), &Series::new("flag", [true, false]));
+        // Whether a column is boolean does not depend on the selected rows.
+        let selected = adata.get_obs().inner().select_axis(0, SelectInfoElem::from(vec![1, 3])).unwrap();
+        assert_eq!(selected.column("count").unwrap(), &Series::new("count", [1i64, 1]));
+
+        adata.set_coerce_bool_columns(false);
+        assert_eq!(adata.read_obs().unwrap().column("flag").unwrap().dtype(), &DataType::Int8);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_nan_as_null<B: Backend>() {
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("score", [1.5, f64::NAN, 3.0]),
+            Series::new("count", [1i32, 2, 3]),
+        ]).unwrap()).unwrap();
+
+        let score = adata.read_obs().unwrap().column("score").unwrap().clone();
+        assert_eq!(score.null_count(), 0);
+        assert!(score.f64().unwrap().get(1).unwrap().is_nan());
+
+        adata.set_nan_as_null(true);
+        let obs = adata.read_obs().unwrap();
+        assert_eq!(obs.column("score").unwrap(), &Series::new("score", [Some(1.5), None, Some(3.0)]));
+        assert_eq!(obs.column("count").unwrap(), &Series::new("count", [1i32, 2, 3]));
+        let column = adata.get_obs().inner().read_column("score").unwrap();
+        assert_eq!(column.null_count(), 1);
+
+        adata.set_nan_as_null(false);
+        assert_eq!(adata.read_obs().unwrap().column("score").unwrap().null_count(), 0);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_reorder_columns<B: Backend>() {
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("a", [1i32, 2]),
+            Series::new("b", [3i32, 4]),
+            Series::new("c", [5i32, 6]),
+            Series::new("d", [7i32, 8]),
+        ]).unwrap()).unwrap();
+
+        let order = vec!["c".to_string(), "a".to_string()];
+        adata.get_obs().inner().reorder_columns(&order).unwrap();
+        assert_eq!(
+            adata.get_obs().inner().get_column_names().iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+            vec!["c", "a", "b", "d"],
+        );
+        assert!(adata.get_obs().inner().reorder_columns(&["e".to_string()]).is_err());
+        assert!(adata.get_obs().inner().reorder_columns(&["a".to_string(), "a".to_string()]).is_err());
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let obs = adata.read_obs().unwrap();
+        assert_eq!(obs.get_column_names(), vec!["c", "a", "b", "d"]);
+        assert_eq!(obs.column("c").unwrap(), &Series::new("c", [5i32, 6]));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_query<B: Backend>() {
+    use polars::prelude::{DataFrame, DataType, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let cell_type = Series::new("cell_type", ["T", "B", "NK", "T", "B"])
+            .cast(&DataType::Categorical(None, Default::default())).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("n_counts", [100, 600, 700, 800, 900]),
+            cell_type,
+            Series::new("score", [Some(0.5), None, Some(1.5), Some(2.5), None]),
+            Series::new("doublet", [false, false, true, false, false]),
+        ]).unwrap()).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let query = |expr: &str| adata.get_obs().query(expr).unwrap();
+        assert_eq!(query("n_counts > 500 & cell_type in ['T','NK']"), vec![2, 3]);
+        assert_eq!(query("cell_type == \"B\" | isnull(score)"), vec![1, 4]);
+        assert_eq!(query("~doublet == True & score >= 0.5"), vec![0, 3]);
+        assert_eq!(query("n_counts < 0"), Vec::<usize>::new());
+        assert!(adata.get_obs().query("cell_type > 1").is_err());
+        assert!(adata.get_obs().query("missing == 1").is_err());
+        assert!(adata.get_obs().query("n_counts >").is_err());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_astype<B: Backend>() {
+    use anndata::backend::{DataType, GroupOp, ScalarType};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let x = Array2::from_shape_fn((20, 5), |(i, j)| i as f64 / 3.0 + j as f64);
+        adata.set_x(&x).unwrap();
+        adata.get_x().astype(ScalarType::F32).unwrap();
+        assert_eq!(adata.get_x().inner().dtype(), DataType::Array(ScalarType::F32));
+        let y = adata.x().get::<Array2<f32>>().unwrap().unwrap();
+        assert!(y.iter().zip(x.iter()).all(|(a, b)| (*a as f64 - b).abs() < 1e-6));
+        assert!(adata.get_x().astype(ScalarType::String).is_err());
+
+        let csr = rand_csr::<f64>(20, 5, 30, 1.0, 100.0);
+        adata.layers().add("counts", &csr).unwrap();
+        adata.layers().get("counts").unwrap().astype(ScalarType::I32).unwrap();
+        let counts: CsrMatrix<i32> = adata.layers().get_item("counts").unwrap().unwrap();
+        assert_eq!(counts.pattern(), csr.pattern());
+        assert_eq!(counts.values(), csr.values().iter().map(|x| *x as i32).collect::<Vec<_>>());
+
+        // A failed conversion leaves the element unchanged.
+        let signed = rand_csr::<i32>(20, 5, 30, -100, -1);
+        adata.layers().add("signed", &signed).unwrap();
+        assert!(adata.layers().get("signed").unwrap().astype(ScalarType::U32).is_err());
+        assert_eq!(adata.layers().get_item::<CsrMatrix<i32>>("signed").unwrap().unwrap(), signed);
+
+        let csc = rand_csc::<u16>(20, 5, 30, 1, 100);
+        adata.layers().add("csc", &csc).unwrap();
+        adata.layers().get("csc").unwrap().astype(ScalarType::F64).unwrap();
+        assert_eq!(adata.layers().get("csc").unwrap().inner().dtype(), DataType::CscMatrix(ScalarType::F64));
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert_eq!(adata.get_x().inner().dtype(), DataType::Array(ScalarType::F32));
+        adata.close().unwrap();
+        let mut layers = B::open(&file).unwrap().open_group("layers").unwrap().list().unwrap();
+        layers.sort();
+        assert_eq!(layers, vec!["counts", "csc", "signed"]);
+    })
+}
+
+pub fn test_map_chunks_in_place<B: Backend>() {
+    use anndata::backend::GroupOp;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let x = Array2::from_shape_fn((23, 4), |(i, j)| (i * 4 + j) as f64);
+        adata.set_x(&x).unwrap();
+        adata.get_x().map_chunks_in_place(5, |chunk| {
+            let chunk: Array2<f64> = chunk.try_into()?;
+            Ok(chunk.mapv(f64::ln_1p).into())
+        }).unwrap();
+        assert_eq!(adata.x().get::<Array2<f64>>().unwrap().unwrap(), x.mapv(f64::ln_1p));
+
+        let csr = rand_csr::<f64>(23, 4, 40, 1.0, 100.0);
+        adata.layers().add("counts", &csr).unwrap();
+        let counts = adata.layers().get("counts").unwrap();
+        counts.map_chunks_in_place(5, |chunk| {
+            let mut chunk: CsrMatrix<f64> = chunk.try_into()?;
+            chunk.values_mut().iter_mut().for_each(|x| *x = x.min(50.0));
+            Ok(chunk.into())
+        }).unwrap();
+        let clipped: CsrMatrix<f64> = adata.layers().get_item("counts").unwrap().unwrap();
+        assert_eq!(clipped.pattern(), csr.pattern());
+        assert_eq!(clipped.values(), csr.values().iter().map(|x| x.min(50.0)).collect::<Vec<_>>());
+
+        // A function changing the shape of the chunks leaves the element unchanged.
+        assert!(counts.map_chunks_in_place(5, |chunk| {
+            let chunk: CsrMatrix<f64> = chunk.try_into()?;
+            Ok(CsrMatrix::<f64>::zeros(chunk.nrows(), 2).into())
+        }).is_err());
+        assert_eq!(adata.layers().get_item::<CsrMatrix<f64>>("counts").unwrap().unwrap(), clipped);
+        adata.close().unwrap();
+        let layers = B::open(&file).unwrap().open_group("layers").unwrap().list().unwrap();
+        assert_eq!(layers, vec!["counts"]);
+    })
+}
+
+pub fn test_filter_to<B: Backend>() {
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let x = Array2::from_shape_fn((5, 3), |(i, j)| (i * 3 + j) as i32);
+        adata.set_x(&x).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("cell_type", ["T", "B", "T", "NK", "T"]),
+        ]).unwrap()).unwrap();
+        adata.set_obs_names((1..=5).map(|i| format!("c{}", i)).collect()).unwrap();
+        adata.set_var_names((1..=3).map(|i| format!("g{}", i)).collect()).unwrap();
+
+        let out = dir.join("out.h5ad");
+        adata.filter_to::<B, _>(
+            &out,
+            Some(AxisSelection::Query("cell_type == 'T'".into())),
+            Some(AxisSelection::Names(vec!["g3".into(), "g1".into()])),
+        ).unwrap();
+        let subset = AnnData::<B>::open(B::open(&out).unwrap()).unwrap();
+        assert_eq!(subset.obs_names().into_vec(), vec!["c1", "c3", "c5"]);
+        assert_eq!(subset.var_names().into_vec(), vec!["g3", "g1"]);
+        let expected = ndarray::array![[2, 0], [8, 6], [14, 12]];
+        assert_eq!(subset.x().get::<Array2<i32>>().unwrap().unwrap(), expected);
+        subset.close().unwrap();
+
+        let bad = dir.join("bad.h5ad");
+        let query = |expr: &str| Some(AxisSelection::Query(expr.into()));
+        assert!(adata.filter_to::<B, _>(&bad, query("missing == 1"), None).is_err());
+        assert!(adata.filter_to::<B, _>(&bad, None, Some(AxisSelection::Names(vec!["g4".into()]))).is_err());
+        assert!(!bad.exists());
+    })
+}
+
+pub fn test_minimal_file<B: Backend>() {
+    use anndata::backend::FileOp;
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        // Only X, opened read-only so that no empty groups can be created.
+        let path = dir.join("x_only.h5ad");
+        let file = B::create(&path).unwrap();
+        let x = Array2::from_shape_fn((4, 3), |(i, j)| (i * 3 + j) as i32);
+        x.write(&file, "X").unwrap();
+        file.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&path).unwrap()).unwrap();
+        assert_eq!((adata.n_obs(), adata.n_vars()), (4, 3));
+        assert_eq!(adata.obs_names().into_vec(), vec!["0", "1", "2", "3"]);
+        assert_eq!(adata.var_names().len(), 3);
+        assert_eq!(adata.obs_ix(["3", "1"]).unwrap(), vec![3, 1]);
+        assert!(adata.var_ix(["3"]).is_err());
+        assert!(adata.obs_ix(["cell1"]).is_err());
+        assert!(adata.read_obs().unwrap().is_empty());
+        assert!(adata.obsm().keys().is_empty());
+        assert!(adata.layers().keys().is_empty());
+
+        let out = dir.join("subset.h5ad");
+        adata.write_select::<B, _, _>(s![vec![3, 1], ..], &out).unwrap();
+        let subset = AnnData::<B>::open(B::open(&out).unwrap()).unwrap();
+        assert_eq!(subset.obs_names().into_vec(), vec!["3", "1"]);
+        assert_eq!(subset.var_names().into_vec(), vec!["0", "1", "2"]);
+        assert_eq!(subset.x().get::<Array2<i32>>().unwrap().unwrap(), x.select(ndarray::Axis(0), &[3, 1]));
+        subset.close().unwrap();
+        adata.close().unwrap();
+
+        // Metadata only: obs without X.
+        let path = dir.join("obs_only.h5ad");
+        let adata = AnnData::<B>::new(&path).unwrap();
+        adata.set_obs(DataFrame::new(vec![Series::new("a", [1, 2, 3])]).unwrap()).unwrap();
+        adata.close().unwrap();
+        let adata = AnnData::<B>::open(B::open(&path).unwrap()).unwrap();
+        assert_eq!((adata.n_obs(), adata.n_vars()), (3, 0));
+        assert!(adata.x().get::<ArrayData>().unwrap().is_none());
+        assert_eq!(adata.obs_names().len(), 3);
+        assert!(adata.var_names().is_empty());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_concat_lazy<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let new = |file: &str, key: &str, n: usize| {
+            let adata = AnnData::<B>::new(dir.join(file)).unwrap();
+            adata.set_x(Array2::from_shape_fn((n, 4), |(i, j)| (n * 10 + i * 4 + j) as i32)).unwrap();
+            adata.set_obs_names((0..n).map(|i| format!("{}_{}", key, i)).collect()).unwrap();
+            (key.to_string(), adata)
+        };
+        let eager = AnnDataSet::<B>::new(
+            [new("a1.h5ad", "a", 3), new("b1.h5ad", "b", 5)],
+            dir.join("dataset.h5ads"),
+            "sample",
+        ).unwrap().to_adata::<B, _>(dir.join("eager.h5ad"), true).unwrap();
+
+        let lazy = AnnDataSet::<B>::concat_lazy(
+            [new("a2.h5ad", "a", 3), new("b2.h5ad", "b", 5)],
+            "sample",
+        ).unwrap();
+        let annotation = lazy.get_anno().filename();
+        assert_eq!(lazy.n_obs(), eager.n_obs());
+        assert_eq!(lazy.obs_names(), eager.obs_names());
+        assert_eq!(lazy.read_obs().unwrap(), eager.read_obs().unwrap());
+        let x: Array2<i32> = lazy.adatas().inner().get_x().data().unwrap().unwrap();
+        assert_eq!(x, eager.x().get::<Array2<i32>>().unwrap().unwrap());
+        lazy.close().unwrap();
+        assert!(!annotation.exists());
+    })
+}
+
+pub fn test_concat<B: Backend>() {
+    use polars::prelude::DataType;
+
+    with_tmp_dir(|dir| {
+        let new = |file: &str, x: Array2<f64>, format: ArrayFormat, vars: &[&str]| {
+            let adata = AnnData::<B>::new(dir.join(file)).unwrap();
+            adata.set_x(ArrayData::from(x).into_format(format).unwrap()).unwrap();
+            adata.set_var_names(vars.iter().map(|x| x.to_string()).collect()).unwrap();
+            adata
+        };
+        let x1 = Array2::from_shape_vec((2, 2), vec![1.0, 2.0, 3.0, 4.0]).unwrap();
+        let x2 = Array2::from_shape_vec((1, 2), vec![5.0, 6.0]).unwrap();
+        let output = |name: &str| dir.join(format!("{}.h5ad", name));
+        let read_x = |adata: &AnnData<B>| -> ArrayData { adata.x().get().unwrap().unwrap() };
+        let dense = |x: ArrayData| -> Array2<f64> { x.into_format(ArrayFormat::Dense).unwrap().try_into().unwrap() };
+
+        let a = new("a.h5ad", x1.clone(), ArrayFormat::Dense, &["g1", "g2"]);
+        let b = new("b.h5ad", x2.clone(), ArrayFormat::Dense, &["g3", "g1"]);
+        let outer = concat::<B, _, _, _, _>([("a", &a), ("b", &b)], output("outer"), "sample", Join::Outer, 0.0).unwrap();
+        assert_eq!(outer.var_names().into_vec(), vec!["g1", "g2", "g3"]);
+        let expected = Array2::from_shape_vec((3, 3), vec![1.0, 2.0, 0.0, 3.0, 4.0, 0.0, 6.0, 0.0, 5.0]).unwrap();
+        assert_eq!(dense(read_x(&outer)), expected);
+        let sample = outer.read_obs().unwrap().column("sample").unwrap().cast(&DataType::String).unwrap();
+        assert_eq!(sample.str().unwrap().into_no_null_iter().collect::<Vec<_>>(), vec!["a", "a", "b"]);
+
+        let nan = concat::<B, _, _, _, _>([("a", &a), ("b", &b)], output("nan"), "sample", Join::Outer, f64::NAN).unwrap();
+        let x = dense(read_x(&nan));
+        assert!(x[[0, 2]].is_nan() && x[[1, 2]].is_nan() && x[[2, 1]].is_nan());
+        assert_eq!(x[[2, 0]], 6.0);
+
+        let inner = concat::<B, _, _, _, _>([("a", &a), ("b", &b)], output("inner"), "sample", Join::Inner, 0.0).unwrap();
+        assert_eq!(inner.var_names().into_vec(), vec!["g1"]);
+        assert_eq!(dense(read_x(&inner)), Array2::from_shape_vec((3, 1), vec![1.0, 3.0, 6.0]).unwrap());
+
+        // Missing entries of sparse matrices are structural zeros.
+        let c = new("c.h5ad", x1, ArrayFormat::Csr, &["g1", "g2"]);
+        let d = new("d.h5ad", x2, ArrayFormat::Csr, &["g3", "g1"]);
+        let sparse = concat::<B, _, _, _, _>([("c", &c), ("d", &d)], output("sparse"), "sample", Join::Outer, 0.0).unwrap();
+        let x = read_x(&sparse);
+        assert_eq!(x.format(), Some(ArrayFormat::Csr));
+        match &x {
+            ArrayData::CsrMatrix(DynCsrMatrix::F64(csr)) => assert_eq!(csr.nnz(), 6),
+            _ => panic!("expecting a f64 csr matrix"),
+        }
+        assert_eq!(dense(x), expected);
+
+        let filled = concat::<B, _, _, _, _>([("c", &c), ("d", &d)], output("filled"), "sample", Join::Outer, -1.0).unwrap();
+        let x = read_x(&filled);
+        assert_eq!(x.format(), Some(ArrayFormat::Dense));
+        assert_eq!(dense(x)[[0, 2]], -1.0);
+
+        // Duplicate variable names cannot be matched.
+        let e = new("e.h5ad", Array2::zeros((1, 2)), ArrayFormat::Dense, &["g1", "g1"]);
+        assert!(concat::<B, _, _, _, _>([("a", &a), ("e", &e)], output("dup"), "sample", Join::Outer, 0.0).is_err());
+    })
+}
+
+pub fn test_custom_attrs<B: Backend>() {
+    use anndata::data::DynScalar;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.obsm().add("X_umap", Array2::<f32>::zeros((5, 2))).unwrap();
+        adata.uns().add("n", 3i64).unwrap();
+        {
+            let umap = adata.obsm().get("X_umap").unwrap();
+            let mut umap = umap.inner();
+            assert_eq!(umap.get_attr("units").unwrap(), None);
+            umap.set_attr("units", DynScalar::String("um".into())).unwrap();
+            umap.set_attr("scale", DynScalar::F64(0.5)).unwrap();
+            assert!(umap.set_attr("encoding-type", DynScalar::String("csr_matrix".into())).is_err());
+            let n = adata.uns().inner().get("n").unwrap().clone();
+            n.inner().set_attr("verified", DynScalar::Bool(true)).unwrap();
+        }
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let umap = adata.obsm().get("X_umap").unwrap();
+        assert_eq!(umap.inner().get_attr("units").unwrap(), Some(DynScalar::String("um".into())));
+        assert_eq!(umap.inner().get_attr("scale").unwrap(), Some(DynScalar::F64(0.5)));
+        assert_eq!(
+            umap.inner().get_attr("encoding-type").unwrap(),
+            Some(DynScalar::String("array".into())),
+        );
+        let n = adata.uns().inner().get("n").unwrap().clone();
+        assert_eq!(n.inner().get_attr("verified").unwrap(), Some(DynScalar::Bool(true)));
+        adata.close().unwrap();
+    })
+}
+
+/// Custom attributes survive rewriting, subsetting and writing the element.
+pub fn test_custom_attrs_rewrite<B: Backend>() {
+    use anndata::backend::ScalarType;
+    use anndata::data::DynScalar;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let output = dir.join("output.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_x(&Array2::from_shape_fn((6, 3), |(i, j)| (i + j) as f64)).unwrap();
+        adata.set_obs(polars::df!("a" => [1, 2, 3, 4, 5, 6]).unwrap()).unwrap();
+        let units = DynScalar::String("um".into());
+        adata.get_x().inner().set_attr("units", units.clone()).unwrap();
+        adata.get_obs().inner().set_attr("source", units.clone()).unwrap();
+
+        adata.get_x().astype(ScalarType::F32).unwrap();
+        adata.get_x().map_chunks_in_place(2, Ok).unwrap();
+        adata.subset(s![vec![4, 1, 0], ..]).unwrap();
+        assert_eq!(adata.get_x().inner().get_attr("units").unwrap(), Some(units.clone()));
+        assert_eq!(adata.get_obs().inner().get_attr("source").unwrap(), Some(units.clone()));
+
+        adata.write::<B, _>(&output).unwrap();
+        adata.close().unwrap();
+        let adata = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+        assert_eq!(adata.get_x().inner().get_attr("units").unwrap(), Some(units.clone()));
+        assert_eq!(adata.get_obs().inner().get_attr("source").unwrap(), Some(units));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_no_x<B: Backend>() {
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let new = |file: &str, key: &str, n: usize| {
+            let adata = AnnData::<B>::new(dir.join(file)).unwrap();
+            adata.set_x(Array2::<f64>::zeros((n, 2))).unwrap();
+            adata.set_obs_names((0..n).map(|i| format!("{}_{}", key, i)).collect()).unwrap();
+            adata.set_obs(DataFrame::new(vec![Series::new("a", (0..n as i32).collect::<Vec<_>>())]).unwrap()).unwrap();
+            adata.set_var_names(["g1", "g2"].into_iter().map(String::from).collect()).unwrap();
+            adata.del_x().unwrap();
+            assert!(adata.x().is_empty());
+            adata.close().unwrap();
+            let adata = AnnData::<B>::open(B::open_rw(dir.join(file)).unwrap()).unwrap();
+            assert_eq!((adata.n_obs(), adata.n_vars()), (n, 2));
+            assert!(adata.x().is_empty());
+            (key.to_string(), adata)
+        };
+
+        let (_, adata) = new("a.h5ad", "a", 3);
+        adata.write_select::<B, _, _>(s![vec![2, 0], ..], dir.join("s.h5ad")).unwrap();
+        let subset = AnnData::<B>::open(B::open(dir.join("s.h5ad")).unwrap()).unwrap();
+        assert_eq!(subset.obs_names().into_vec(), vec!["a_2", "a_0"]);
+        assert_eq!(subset.n_vars(), 2);
+        assert!(subset.x().is_empty());
+        subset.close().unwrap();
+        adata.subset(s![vec![1], ..]).unwrap();
+        assert_eq!(adata.n_obs(), 1);
+        adata.close().unwrap();
+
+        let dataset = AnnDataSet::<B>::new(
+            [new("b.h5ad", "b", 3), new("c.h5ad", "c", 4)],
+            dir.join("dataset.h5ads"),
+            "sample",
+        ).unwrap();
+        assert_eq!((dataset.n_obs(), dataset.n_vars()), (7, 2));
+        assert!(dataset.x().is_empty());
+        let out = dataset.to_adata_select::<B, _, _>(
+            s![vec![6, 0], ..], dir.join("sel.h5ad"), true,
+        ).unwrap();
+        assert_eq!(out.obs_names().into_vec(), vec!["c_3", "b_0"]);
+        assert!(out.x().is_empty());
+        out.close().unwrap();
+        let out = dataset.to_adata::<B, _>(dir.join("all.h5ad"), true).unwrap();
+        assert_eq!(out.n_obs(), 7);
+        assert!(out.x().is_empty());
+        out.close().unwrap();
+        dataset.close().unwrap();
+    })
+}
+
+pub fn test_validate<B: Backend>() {
+    use anndata::backend::{GroupOp, LocationOp};
+    use anndata::data::Mapping;
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_x(rand_csr::<i32>(6, 4, 10, 1, 100)).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("nullable", [Some(1), None, Some(3), None, Some(5), Some(6)]),
+            Series::new("tissue", ["cortex", "cortex", "liver", "cortex", "liver", "cortex"]),
+        ]).unwrap()).unwrap();
+        adata.set_var_names(["a", "b", "c", "d"].into_iter().map(String::from).collect()).unwrap();
+        adata.obsm().add("X_pca", Array2::<f32>::zeros((6, 2))).unwrap();
+        adata.obsp().add("distances", rand_csr::<f64>(6, 6, 8, 1.0, 2.0)).unwrap();
+        adata.layers().add("counts", Array2::<i64>::ones((6, 4))).unwrap();
+        let method: std::collections::HashMap<String, Data> =
+            [("method".to_string(), Data::from("t-test".to_string()))].into_iter().collect();
+        adata.uns().add("rank_genes_groups", Mapping::from(method)).unwrap();
+        adata.uns().add("n", 1i64).unwrap();
+        assert!(adata.validate().unwrap().is_empty());
+        adata.close().unwrap();
+
+        let file = B::open_rw(&file).unwrap();
+        let uns = file.open_group("uns").unwrap();
+        let csr = uns.create_group("bad_csr").unwrap();
+        csr.write_str_attr("encoding-type", "csr_matrix").unwrap();
+        csr.write_str_attr("encoding-version", "0.1.0").unwrap();
+        csr.write_array_attr("shape", &ndarray::arr1(&[3i64, 2])).unwrap();
+        csr.create_array_data("indptr", &ndarray::arr1(&[0i64, 1, 3]), Default::default()).unwrap();
+        csr.create_array_data("indices", &ndarray::arr1(&[0i64, 1]), Default::default()).unwrap();
+        csr.create_array_data("data", &ndarray::arr1(&[1.0, 2.0, 3.0]), Default::default()).unwrap();
+        let df = uns.create_group("bad_df").unwrap();
+        df.write_str_attr("encoding-type", "dataframe").unwrap();
+        df.write_str_attr("encoding-version", "0.2.0").unwrap();
+        uns.create_array_data("no_encoding", &ndarray::arr1(&[1i64, 2]), Default::default()).unwrap();
+        let df = uns.create_group("scalar_df").unwrap();
+        df.write_str_attr("encoding-type", "dataframe").unwrap();
+        df.write_str_attr("encoding-version", "0.2.0").unwrap();
+        df.write_str_attr("_index", "idx").unwrap();
+        df.write_array_attr("column-order", &ndarray::arr1(&["c".to_string()])).unwrap();
+        for name in ["idx", "c"] {
+            let scalar = df.create_scalar_data(name, &1i64).unwrap();
+            scalar.write_str_attr("encoding-type", "array").unwrap();
+            scalar.write_str_attr("encoding-version", "0.2.0").unwrap();
+        }
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        let mut violations: Vec<_> = adata.validate().unwrap().iter().map(|x| x.to_string()).collect();
+        violations.sort();
+        assert_eq!(violations, vec![
+            "uns/bad_csr: 'data' has length 3, but 'indices' has length 2",
+            "uns/bad_csr: 'indptr' has length 3, expected 4 for shape [3, 2]",
+            "uns/bad_df: missing attribute '_index'",
+            "uns/bad_df: missing attribute 'column-order'",
+            "uns/no_encoding: missing attribute 'encoding-type'",
+            "uns/scalar_df/c: expected a 1-dimensional array, found a scalar",
+            "uns/scalar_df/idx: expected a 1-dimensional array, found a scalar",
+        ]);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_compound_index<B: Backend>() {
+    use anndata::backend::{GroupOp, LocationOp};
+    use ndarray::Array1;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_x(Array2::<i32>::zeros((3, 2))).unwrap();
+        adata.set_obs(polars::df!("n" => [1, 2, 3]).unwrap()).unwrap();
+        adata.close().unwrap();
+
+        // Replace the index by two levels, as written by some R packages.
+        let file = B::open_rw(&file).unwrap();
+        let obs = file.open_group("obs").unwrap();
+        obs.delete(&obs.read_str_attr("_index").unwrap()).unwrap();
+        let sample = Array1::from(vec!["s1".to_string(), "s1".to_string(), "s2".to_string()]);
+        let barcode = Array1::from(vec!["AAC".to_string(), "GTT".to_string(), "AAC".to_string()]);
+        for (name, level) in [("sample", &sample), ("barcode", &barcode)] {
+            let level = obs.create_array_data(name, level, Default::default()).unwrap();
+            level.write_str_attr("encoding-type", "string-array").unwrap();
+            level.write_str_attr("encoding-version", "0.2.0").unwrap();
+        }
+        obs.write_array_attr("_index", &Array1::from(vec!["sample".to_string(), "barcode".to_string()])).unwrap();
+
+        let adata = AnnData::<B>::open(file).unwrap();
+        assert_eq!(adata.obs_names().into_vec(), vec!["s1_AAC", "s1_GTT", "s2_AAC"]);
+        let levels = adata.get_obs().inner().index_levels().unwrap().unwrap();
+        assert_eq!(levels, polars::df!("sample" => ["s1", "s1", "s2"], "barcode" => ["AAC", "GTT", "AAC"]).unwrap());
+        assert_eq!(adata.read_obs().unwrap(), polars::df!("n" => [1, 2, 3]).unwrap());
+        let violations: Vec<_> = adata.validate().unwrap().iter().map(|x| x.to_string()).collect();
+        assert_eq!(violations, vec!["obs: the index has 2 levels, which other tools cannot read"]);
+
+        // Writing the index stores the composite names as a single level.
+        adata.subset(s![vec![2, 0], ..]).unwrap();
+        assert_eq!(adata.obs_names().into_vec(), vec!["s2_AAC", "s1_AAC"]);
+        assert!(adata.get_obs().inner().index_levels().unwrap().is_none());
+        assert!(adata.validate().unwrap().is_empty());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_write_csv<B: Backend>() {
+    use polars::prelude::{DataFrame, DataType, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_obs_names(["c1", "c2", "c3"].into_iter().map(|x| x.to_string()).collect()).unwrap();
+        let cell_type = Series::new("cell_type", ["T", "B", "T"])
+            .cast(&DataType::Categorical(None, Default::default())).unwrap();
+        adata.set_obs(DataFrame::new(vec![
+            Series::new("n_counts", [100, 600, 700]),
+            cell_type,
+            Series::new("score", [Some(0.5), None, Some(1.5)]),
+        ]).unwrap()).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let output = dir.join("obs.csv");
+        adata.get_obs().inner().write_csv(&output).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "index,n_counts,cell_type,score\nc1,100,T,0.5\nc2,600,B,\nc3,700,T,1.5\n",
+        );
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_export_ipc<B: Backend>() {
+    use polars::prelude::{DataFrame, DataType, IpcWriter, NamedFrom, SerWriter, Series};
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_obs_names(["c1", "c2", "c3"].into_iter().map(|x| x.to_string()).collect()).unwrap();
+        let cell_type = Series::new("cell_type", ["T", "B", "T"])
+            .cast(&DataType::Categorical(None, Default::default())).unwrap();
+        adata.set_obs(DataFrame::new(vec![Series::new("n_counts", [100, 600, 700]), cell_type]).unwrap()).unwrap();
+        let output = dir.join("obs.arrow");
+        adata.get_obs().inner().write_ipc(&output).unwrap();
+
+        let copy = AnnData::<B>::new(dir.join("copy.h5ad")).unwrap();
+        copy.set_obs_from_ipc(&output).unwrap();
+        assert_eq!(copy.obs_names().into_vec(), vec!["c1", "c2", "c3"]);
+        assert_eq!(copy.obs_names().index_name, "index");
+        let obs = copy.read_obs().unwrap();
+        assert_eq!(obs.get_column_names(), vec!["n_counts", "cell_type"]);
+        assert_eq!(obs.column("n_counts").unwrap().i32().unwrap().into_no_null_iter().collect::<Vec<_>>(), vec![100, 600, 700]);
+        assert_eq!(
+            obs.column("cell_type").unwrap().categorical().unwrap()
+                .iter_str().map(|x| x.unwrap().to_string()).collect::<Vec<_>>(),
+            vec!["T", "B", "T"],
+        );
+
+        // The number of rows must match the observations of the object.
+        let other = AnnData::<B>::new(dir.join("other.h5ad")).unwrap();
+        other.set_x(Array2::<i32>::zeros((2, 2))).unwrap();
+        assert!(other.set_obs_from_ipc(&output).is_err());
+        assert!(other.get_obs().is_empty());
+
+        // Unsupported columns are rejected before obs is touched.
+        let bad = dir.join("bad.arrow");
+        let mut df = DataFrame::new(vec![
+            Series::new("index", ["d1", "d2"]),
+            Series::new("label", [Some("a"), None]),
+        ]).unwrap();
+        IpcWriter::new(std::fs::File::create(&bad).unwrap()).finish(&mut df).unwrap();
+        assert!(copy.set_obs_from_ipc(&bad).is_err());
+        assert_eq!(copy.obs_names().into_vec(), vec!["c1", "c2", "c3"]);
+        assert_eq!(copy.read_obs().unwrap().get_column_names(), vec!["n_counts", "cell_type"]);
+        adata.close().unwrap();
+        copy.close().unwrap();
+        other.close().unwrap();
+    })
+}
+
+pub fn test_builder<B: Backend>() {
+    use anndata::backend::WriteConfig;
+
+    with_tmp_dir(|dir| {
+        // Mismatched dimensions are reported before the file is created.
+        let file = dir.join("conflict.h5ad");
+        let err = AnnDataBuilder::new(&file)
+            .n_obs(5)
+            .x(Array2::<f32>::zeros((4, 3)))
+            .build::<B>()
+            .err().unwrap();
+        assert_eq!(err.to_string(), "conflicting number of observations: n_obs has 5 but X has 4");
+        let err = AnnDataBuilder::new(&file)
+            .x(Array2::<f32>::zeros((4, 3)))
+            .varm("PCs", Array2::<f32>::zeros((2, 2)))
+            .build::<B>()
+            .err().unwrap();
+        assert_eq!(err.to_string(), "conflicting number of variables: X has 3 but varm['PCs'] has 2");
+        assert!(!file.exists());
+
+        // A failed write leaves neither the file nor its temporary file.
+        let err = AnnDataBuilder::new(&file)
+            .x(Array2::<f32>::zeros((3, 2)))
+            .obs(polars::df!("s" => [Some("a"), None, Some("c")]).unwrap())
+            .build::<B>();
+        assert!(err.is_err());
+        assert!(!file.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let file = dir.join("test.h5ad");
+        let x = Array2::from_shape_vec((3, 2), vec![1, 2, 3, 4, 5, 6]).unwrap();
+        let pca = Array2::<f64>::ones((3, 4));
+        let adata = AnnDataBuilder::new(&file)
+            .write_config(WriteConfig { compression: None, block_size: Some(vec![2, 2].into()), ..Default::default() })
+            .x(x.clone())
+            .obs_names(["a", "b", "c"].into_iter().map(|x| x.to_string()).collect())
+            .obs(polars::df!("n" => [1, 2, 3]).unwrap())
+            .obsm("X_pca", pca.clone())
+            .uns("n_pcs", 4)
+            .build::<B>()
+            .unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert_eq!(adata.n_obs(), 3);
+        assert_eq!(adata.n_vars(), 2);
+        assert_eq!(adata.x().get::<Array2<i32>>().unwrap().unwrap(), x);
+        assert_eq!(adata.obs_names().into_vec(), vec!["a", "b", "c"]);
+        assert_eq!(adata.read_obs().unwrap(), polars::df!("n" => [1, 2, 3]).unwrap());
+        assert_eq!(adata.obsm().get_item::<Array2<f64>>("X_pca").unwrap().unwrap(), pca);
+        assert_eq!(adata.uns().get_item::<i32>("n_pcs").unwrap(), Some(4));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_delete_slots<B: Backend>() {
+    use anndata::backend::{FileOp, GroupOp};
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_x(Array2::<f64>::zeros((4, 3))).unwrap();
+        adata.set_obs(DataFrame::new(vec![Series::new("a", [1, 2, 3, 4])]).unwrap()).unwrap();
+        adata.set_var_names(["g1", "g2", "g3"].into_iter().map(String::from).collect()).unwrap();
+        adata.obsm().add("pca", Array2::<f64>::zeros((4, 2))).unwrap();
+        adata.obsp().add("distances", Array2::<f64>::zeros((4, 4))).unwrap();
+        adata.varm().add("loadings", Array2::<f64>::zeros((3, 2))).unwrap();
+        adata.layers().add("counts", Array2::<f64>::zeros((4, 3))).unwrap();
+        adata.uns().add("n", 1i64).unwrap();
+
+        adata.del_obsp().unwrap();
+        adata.del_uns().unwrap();
+        adata.del_layers().unwrap();
+        let repr = adata.to_string();
+        assert!(["obsp:", "uns:", "layers:"].iter().all(|x| !repr.contains(x)));
+        adata.close().unwrap();
+        let h5 = B::open(&file).unwrap();
+        assert!(!h5.exists("obsp").unwrap() && !h5.exists("uns").unwrap() && !h5.exists("layers").unwrap());
+        h5.close().unwrap();
+
+        // obs can be deleted although X and obsm depend on `n_obs`.
+        let adata = AnnData::<B>::open(B::open_rw(&file).unwrap()).unwrap();
+        adata.del_obs().unwrap();
+        assert_eq!(adata.n_obs(), 4);
+        assert_eq!(adata.obs_names().into_vec(), vec!["0", "1", "2", "3"]);
+        adata.del_x().unwrap();
+        adata.del_obsm().unwrap();
+        assert_eq!((adata.n_obs(), adata.n_vars()), (0, 3));
+        assert!(adata.x().get::<ArrayData>().unwrap().is_none());
+        adata.close().unwrap();
+        let h5 = B::open(&file).unwrap();
+        assert!(!h5.exists("X").unwrap() && !h5.exists("obs").unwrap() && !h5.exists("obsm").unwrap());
+        assert!(h5.exists("var").unwrap() && h5.exists("varm").unwrap());
+
+        let adata = AnnData::<B>::open(h5).unwrap();
+        assert_eq!((adata.n_obs(), adata.n_vars()), (0, 3));
+        assert_eq!(adata.varm().keys(), vec!["loadings"]);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_save_as<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let old = dir.join("old.h5ad");
+        let new = dir.join("new.h5ad");
+        let mut adata = AnnData::<B>::new(&old).unwrap();
+        adata.set_x(Array2::<f64>::zeros((3, 2))).unwrap();
+        adata.set_obs_names(["a", "b", "c"].into_iter().map(String::from).collect()).unwrap();
+        assert!(adata.save_as(&old, true).is_err());
+
+        adata.save_as(&new, true).unwrap();
+        assert!(adata.filename().ends_with("new.h5ad"));
+        assert_eq!(adata.obs_names().into_vec(), vec!["a", "b", "c"]);
+        adata.uns().add("after", 1i64).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&new).unwrap()).unwrap();
+        assert_eq!(adata.x().shape().unwrap().as_ref(), &[3, 2]);
+        assert!(adata.uns().keys().contains(&"after".to_string()));
+        adata.close().unwrap();
+        let adata = AnnData::<B>::open(B::open(&old).unwrap()).unwrap();
+        assert!(!adata.uns().keys().contains(&"after".to_string()));
+        adata.close().unwrap();
+    })
+}
+
+/// A stress test rather than a model check: loom cannot check the locks, as
+/// they come from parking_lot and the backend calls into native code. Run it
+/// under ThreadSanitizer to detect data races, e.g., with
+/// `RUSTFLAGS=-Zsanitizer=thread cargo +nightly test -Zbuild-std
+/// --target x86_64-unknown-linux-gnu overwrite_while_reading`.
+pub fn test_overwrite_while_reading<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.obsm().add("x", Array2::<f64>::zeros((100, 10))).unwrap();
+        let elem = adata.obsm().get("x").unwrap();
+
+        // Readers must see either the old or the new array, never a mix.
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..50 {
+                        let x: Array2<f64> = elem.get().unwrap().unwrap();
+                        assert_eq!(x.shape(), &[100, 10]);
+                        assert!(x.iter().all(|v| *v == x[[0, 0]]));
+                        let row: Array2<f64> = elem.slice_axis(0, SelectInfoElem::from(vec![99])).unwrap().unwrap();
+                        assert_eq!(row.shape(), &[1, 10]);
+                    }
+                });
+            }
+            s.spawn(|| {
+                for i in 1..=50 {
+                    adata.obsm().add("x", Array2::<f64>::from_elem((100, 10), i as f64)).unwrap();
+                }
+            });
+        });
+        let x: Array2<f64> = adata.obsm().get_item("x").unwrap().unwrap();
+        assert!(x.iter().all(|v| *v == 50.0));
+    })
+}
+
+pub fn test_sample_obs<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_x(Array2::<i32>::from_shape_fn((100, 2), |(i, _)| i as i32)).unwrap();
+
+        let sample = adata.sample_obs(10, 42).unwrap();
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|x| x[0] < x[1]) && sample[9] < 100);
+        assert_eq!(sample, adata.sample_obs(10, 42).unwrap());
+        assert_ne!(sample, adata.sample_obs(10, 43).unwrap());
+        assert_eq!(adata.sample_obs(100, 0).unwrap(), (0..100).collect::<Vec<_>>());
+        assert!(adata.sample_obs(101, 0).is_err());
+
+        let file = dir.join("sample.h5ad");
+        adata.write_select::<B, _, _>(s![&sample, ..], &file).unwrap();
+        let subset = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let x: Array2<i32> = subset.x().get().unwrap().unwrap();
+        assert_eq!(x.column(0).to_vec(), sample.iter().map(|&i| i as i32).collect::<Vec<_>>());
+    })
+}
+
+pub fn test_string_array<B: Backend>() {
+    use anndata::backend::{DataType, ScalarType};
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let seq = Array2::from_shape_fn((6, 4), |(i, j)| ["A", "C", "G", "T"][(i + j) % 4].repeat(i + 1));
+        adata.obsm().add("seq", &seq).unwrap();
+        let elem = adata.obsm().get("seq").unwrap();
+        assert_eq!(elem.inner().dtype(), DataType::Array(ScalarType::String));
+        assert_eq!(elem.get::<Array2<String>>().unwrap().unwrap(), seq);
+
+        let select = s![vec![4, 1], 1..3];
+        let expected = ArrayOp::select(&seq, select.as_ref());
+        assert_eq!(elem.slice::<Array2<String>, _>(&select).unwrap().unwrap(), expected);
+        let select = s![2..5, ..];
+        let expected = ArrayOp::select(&seq, select.as_ref());
+        assert_eq!(elem.slice::<Array2<String>, _>(&select).unwrap().unwrap(), expected);
+
+        adata.set_x(Array2::<i32>::zeros((6, 2))).unwrap();
+        let file = dir.join("subset.h5ad");
+        adata.write_select::<B, _, _>(s![vec![5, 0], ..], &file).unwrap();
+        let subset = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let seq_subset: Array2<String> = subset.obsm().get_item("seq").unwrap().unwrap();
+        assert_eq!(seq_subset, ArrayOp::select(&seq, s![vec![5, 0], ..].as_ref()));
+        subset.close().unwrap();
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_zero_dim_dataset<B: Backend>() {
+    use anndata::backend::{FileOp, GroupOp, LocationOp};
+    use anndata::data::DynScalar;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        AnnData::<B>::new(&file).unwrap().close().unwrap();
+        {
+            let file = B::open_rw(&file).unwrap();
+            let uns = file.open_group("uns").unwrap();
+            uns.create_scalar_data("plain", &1.5f64).unwrap();
+            let tagged = uns.create_scalar_data("tagged", &2.5f64).unwrap();
+            tagged.write_str_attr("encoding-type", "array").unwrap();
+            tagged.write_str_attr("encoding-version", "0.2.0").unwrap();
+            file.close().unwrap();
+        }
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert_eq!(adata.uns().get_item::<f64>("plain").unwrap(), Some(1.5));
+        assert_eq!(adata.uns().get_item::<f64>("tagged").unwrap(), Some(2.5));
+        assert!(matches!(
+            adata.uns().get_item::<Data>("tagged").unwrap(),
+            Some(Data::Scalar(DynScalar::F64(x))) if x == 2.5
+        ));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_append_to_list<B: Backend>() {
+    use ndarray::Array1;
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        for i in 0..100 {
+            adata.uns().append_to_list("log", format!("step {}", i)).unwrap();
+        }
+        assert!(adata.uns().append_to_list("log", 1.0).is_err());
+        adata.uns().add("scalar", 1.0).unwrap();
+        assert!(adata.uns().append_to_list("scalar", 2.0).is_err());
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let log: Array1<String> = adata.uns().get_item("log").unwrap().unwrap();
+        assert_eq!(log.to_vec(), (0..100).map(|i| format!("step {}", i)).collect::<Vec<_>>());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_indptr_cache<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let csr = rand_csr::<i32>(200, 30, 1000, -100, 100);
+        let expected = ArrayData::from(csr.clone());
+        adata.set_x(&csr).unwrap();
+
+        let mask: Vec<bool> = (0..200).map(|i| i % 3 == 0).collect();
+        let selections = [
+            s![7, ..],
+            s![vec![150, 3, 3, 4, 5, 199], 2..9],
+            s![10..180; 4, ..],
+            s![..; -2, vec![0, 29]],
+            s![mask, ..],
+            s![Vec::<usize>::new(), ..],
+        ];
+        let x = adata.get_x();
+        for select in selections.iter() {
+            let result: ArrayData = x.slice(select).unwrap().unwrap();
+            assert_eq!(result, expected.select(select.as_ref()));
+        }
+
+        // The cached `indptr` is dropped when the matrix changes.
+        let csr = rand_csr::<i32>(200, 30, 500, -100, 100);
+        adata.set_x(&csr).unwrap();
+        let result: ArrayData = adata.get_x().slice(&selections[1]).unwrap().unwrap();
+        assert_eq!(result, ArrayData::from(csr.clone()).select(selections[1].as_ref()));
+        let append = rand_csr::<i32>(10, 30, 50, -100, 100);
+        adata.append(append.clone(), polars::prelude::DataFrame::empty(), None).unwrap();
+        let result: ArrayData = adata.get_x().slice(s![195..210, ..]).unwrap().unwrap();
+        let stacked = ArrayData::vstack([csr.into(), append.into()].into_iter()).unwrap();
+        assert_eq!(result, stacked.select(s![195..210, ..].as_ref()));
+
+        adata.get_x().inner().disable_cache();
+        let result: ArrayData = adata.get_x().slice(&selections[1]).unwrap().unwrap();
+        assert_eq!(result, stacked.select(selections[1].as_ref()));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_read_into<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let x = Array2::from_shape_fn((50, 4), |(i, j)| (i * 4 + j) as f64);
+        adata.set_x(&x).unwrap();
+        for select in [s![5..20, 1..3], s![vec![7, 3, 3], ..], s![0..0, ..]] {
+            let expected = ArrayOp::select(&x, select.as_ref());
+            let mut out = vec![0.0; expected.len()];
+            assert!(adata.get_x().inner().read_dense_into(select.as_ref(), &mut out).unwrap());
+            assert_eq!(out, expected.iter().copied().collect::<Vec<_>>());
+        }
+        let mut out = vec![0.0; 3];
+        assert!(adata.get_x().inner().read_dense_into(s![.., ..].as_ref(), &mut out).is_err());
+
+        let csr = rand_csr::<i32>(60, 20, 300, -100, 100);
+        adata.set_x(&csr).unwrap();
+        let x = adata.get_x().inner();
+        let nnz = x.nnz().unwrap().unwrap();
+        assert!(!x.read_dense_into(s![.., ..].as_ref(), &mut vec![0.0; 1200]).unwrap());
+        let (mut data, mut indices, mut indptr) = (vec![0i32; nnz], vec![0usize; nnz], vec![0usize; 61]);
+        assert!(x.read_sparse_into(&mut data, &mut indices, &mut indptr).unwrap());
+        assert_eq!((indptr, indices, data), csr.disassemble());
+    })
+}
+
+pub fn test_chunk_cache<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let x = Array2::from_shape_fn((3000, 200), |(i, j)| (i * 200 + j) as f64);
+        adata.set_x(&x).unwrap();
+
+        // The chunked iterator resizes the cache of a fresh element.
+        let chunks: Vec<ArrayData> = adata.get_x().chunked(700).map(|x| x.0).collect();
+        assert_eq!(ArrayData::vstack(chunks.into_iter()).unwrap(), ArrayData::from(x.clone()));
+
+        adata.get_x().inner().set_chunk_cache(8 << 20).unwrap();
+        let result: ArrayData = adata.get_x().slice(s![100..200, ..]).unwrap().unwrap();
+        assert_eq!(result, ArrayData::from(x.clone()).select(s![100..200, ..].as_ref()));
+        let chunks: Vec<ArrayData> = adata.get_x().chunked(700).map(|x| x.0).collect();
+        assert_eq!(ArrayData::vstack(chunks.into_iter()).unwrap(), ArrayData::from(x));
+
+        adata.set_x(rand_csr::<i32>(50, 20, 100, -100, 100)).unwrap();
+        assert!(adata.get_x().inner().set_chunk_cache(8 << 20).is_err());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_write_columns<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_x(Array2::<i32>::ones((3, 2))).unwrap();
+        adata.set_obs(polars::df!(
+            "a" => [1, 2, 3], "b" => ["x", "y", "z"], "c" => [0.5, 1.5, 2.5],
+            "d" => [true, false, true], "e" => [7, 8, 9],
+        ).unwrap()).unwrap();
+        adata.set_obs_names(vec!["u", "v", "w"].into_iter().map(String::from).collect()).unwrap();
+
+        let output = dir.join("slim.h5ad");
+        let columns = ["e".to_string(), "b".to_string()];
+        adata.write_columns::<B, _>(&output, Some(&columns), None, true).unwrap();
+        let slim = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+        let obs = slim.read_obs().unwrap();
+        assert_eq!(obs.get_column_names(), vec!["e", "b"]);
+        assert_eq!(obs, adata.read_obs().unwrap().select(["e", "b"]).unwrap());
+        assert_eq!(slim.obs_names().into_vec(), vec!["u", "v", "w"]);
+        assert_eq!(slim.x().get::<Array2<i32>>().unwrap(), adata.x().get::<Array2<i32>>().unwrap());
+        slim.close().unwrap();
+
+        let missing = dir.join("missing.h5ad");
+        let columns = ["a".to_string(), "f".to_string()];
+        assert!(adata.write_columns::<B, _>(&missing, Some(&columns), None, true).is_err());
+        assert!(adata.write_columns::<B, _>(&missing, None, Some(&columns), true).is_err());
+        assert!(!missing.exists());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_view<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        let x = Array2::from_shape_fn((10, 6), |(i, j)| (i * 6 + j) as i32);
+        adata.set_x(&x).unwrap();
+        adata.set_obs(polars::df!("a" => (0..10).collect::<Vec<i32>>()).unwrap()).unwrap();
+
+        let view = adata.view(2..9, vec![5, 1, 3]).unwrap();
+        let chained = view.view(vec![6, 0, 2], 1..3).unwrap();
+        let expected = ArrayOp::select(&ArrayOp::select(&x, s![2..9, vec![5, 1, 3]].as_ref()), s![vec![6, 0, 2], 1..3].as_ref());
+        assert_eq!((chained.n_obs().unwrap(), chained.n_vars().unwrap()), (3, 2));
+        assert_eq!(Array2::<i32>::try_from(chained.read_x().unwrap().unwrap()).unwrap(), expected);
+        assert_eq!(chained.read_obs().unwrap(), polars::df!("a" => [8, 2, 4]).unwrap());
+        assert!(view.view(7, SelectInfoElem::full()).is_err());
+
+        let output = dir.join("view.h5ad");
+        chained.write::<B, _>(&output).unwrap();
+        let subset = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+        assert_eq!(subset.x().get::<Array2<i32>>().unwrap().unwrap(), expected);
+        subset.close().unwrap();
+        assert_eq!(adata.x().get::<Array2<i32>>().unwrap().unwrap(), x);
+
+        // Views are invalidated by an in-place subset of the object.
+        adata.subset(s![0..5, ..]).unwrap();
+        assert!(chained.read_x().is_err());
+        assert!(view.n_obs().is_err());
+        assert_eq!(adata.view(1..3, SelectInfoElem::full()).unwrap().n_obs().unwrap(), 2);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_batched<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_x(Array2::<f64>::zeros((10, 3))).unwrap();
+        let before = adata.last_modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        // The modification time is written once the outermost batch is finished.
+        let n = adata.batched(|adata| {
+            for i in 0..20 {
+                adata.obsm().add(&format!("X_{}", i), Array2::<f32>::zeros((10, 2)))?;
+            }
+            adata.batched(|adata| adata.uns().add("key", "value".to_string()))?;
+            assert_eq!(adata.last_modified(), Some(before));
+            Ok(adata.obsm().keys().len())
+        }).unwrap();
+        assert_eq!(n, 20);
+        let after = adata.last_modified().unwrap();
+        assert!(after > before);
+
+        // Errors are returned and end the batch.
+        let result: anyhow::Result<()> = adata.batched(|adata| {
+            adata.uns().add("a", 1)?;
+            anyhow::bail!("failed")
+        });
+        assert!(result.is_err());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        adata.uns().add("b", 2).unwrap();
+        assert!(adata.last_modified().unwrap() > after);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_null_counts<B: Backend>() {
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let obs = DataFrame::new(vec![
+            Series::new("a", [Some(1), None, Some(3), None, Some(5)]),
+            Series::new("b", [Some(true), Some(false), None, Some(true), Some(true)]),
+            Series::new("c", ["x", "y", "z", "x", "y"]),
+        ]).unwrap();
+        adata.set_obs(obs.clone()).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let counts = adata.get_obs().inner().null_counts().unwrap();
+        assert_eq!(counts.into_iter().collect::<Vec<_>>(), vec![
+            ("a".to_string(), 2), ("b".to_string(), 1), ("c".to_string(), 0),
+        ]);
+        assert_eq!(adata.read_obs().unwrap(), obs);
+
+        let err = adata.set_obs(DataFrame::new(vec![
+            Series::new("c", [Some("x"), None, Some("z"), None, Some("y")]),
+        ]).unwrap());
+        assert!(err.is_err());
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_chunk_ranges<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        // All non-zero entries are in the first four rows.
+        let indptr = vec![0, 3, 6, 9, 12, 12, 12, 12, 12];
+        let indices = (0..4).flat_map(|_| 0..3).collect();
+        let csr = CsrMatrix::try_from_csr_data(8, 3, indptr, indices, vec![1; 12]).unwrap();
+        adata.set_x(&csr).unwrap();
+        assert_eq!(adata.chunk_ranges(2).unwrap(), vec![0..2, 2..8]);
+        assert_eq!(adata.chunk_ranges(1).unwrap(), vec![0..8]);
+        assert!(adata.chunk_ranges(0).is_err());
+
+        adata.set_x(Array2::<i32>::ones((8, 3))).unwrap();
+        assert_eq!(adata.chunk_ranges(4).unwrap(), vec![0..2, 2..4, 4..6, 6..8]);
+        assert_eq!(adata.chunk_ranges(3).unwrap(), vec![0..2, 2..5, 5..8]);
+        assert_eq!(adata.chunk_ranges(20).unwrap().len(), 8);
+        adata.close().unwrap();
+
+        // Read-only handles can be held concurrently.
+        let readers: Vec<_> = (0..3)
+            .map(|_| AnnData::<B>::open(B::open(&file).unwrap()).unwrap())
+            .collect();
+        for (reader, range) in readers.iter().zip(readers[0].chunk_ranges(3).unwrap()) {
+            let x: Array2<i32> = reader.get_x().slice_axis(0, SelectInfoElem::from(range.clone())).unwrap().unwrap();
+            assert_eq!(x, Array2::ones((range.len(), 3)));
+        }
+        readers.into_iter().for_each(|x| x.close().unwrap());
+    })
+}
+
+pub fn test_chunked_by_nnz<B: Backend>() {
+    use anndata::container::ChunkStrategy;
+
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        // Row i has i % 10 non-zero entries.
+        let nnz: Vec<usize> = (0..100).map(|i| i % 10).collect();
+        let mut indptr = vec![0];
+        nnz.iter().for_each(|n| indptr.push(indptr.last().unwrap() + n));
+        let indices = nnz.iter().flat_map(|&n| 0..n).collect();
+        let total = *indptr.last().unwrap();
+        let csr = CsrMatrix::try_from_csr_data(100, 10, indptr, indices, vec![1i32; total]).unwrap();
+        adata.set_x(&csr).unwrap();
+
+        let chunks = adata.get_x().chunked_by::<CsrMatrix<i32>>(ChunkStrategy::ByNnz(45)).unwrap();
+        assert_eq!(chunks.len(), 10);
+        let mut next_row = 0;
+        for (chunk, start, end) in chunks {
+            assert_eq!(start, next_row);
+            assert!(chunk.nnz() >= 45 || end == 100);
+            assert!(chunk.nnz() - chunk.row(chunk.nrows() - 1).nnz() < 45);
+            next_row = end;
+        }
+        assert_eq!(next_row, 100);
+
+        let chunks = adata.get_x().chunked_by::<CsrMatrix<i32>>(ChunkStrategy::ByNnz(1)).unwrap();
+        assert_eq!(chunks.map(|(x, _, _)| x.nrows()).sum::<usize>(), 100);
+        assert!(adata.get_x().chunked_by::<ArrayData>(ChunkStrategy::ByNnz(0)).is_err());
+
+        adata.set_x(Array2::<i32>::zeros((100, 10))).unwrap();
+        let chunks = adata.get_x().chunked_by::<Array2<i32>>(ChunkStrategy::ByNnz(45)).unwrap();
+        assert!(chunks.map(|(_, start, end)| (start, end)).eq((0..25).map(|i| (i * 4, i * 4 + 4))));
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_append<B: Backend>() {
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    with_tmp_dir(|dir| {
+        let file = dir.join("sparse.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let x = rand_csr::<i32>(1000, 20, 3000, 1, 100);
+        let batch = |i: usize| SelectInfoElem::from(i * 100..(i + 1) * 100);
+        let check = |adata: &AnnData<B>, n: usize| {
+            assert_eq!(adata.n_obs(), n);
+            assert_eq!(adata.read_obs().unwrap().height(), n);
+            assert_eq!(adata.obs_names().len(), n);
+            assert_eq!(adata.x().shape().unwrap()[0], n);
+        };
+        for i in 0..10 {
+            let obs = DataFrame::new(vec![Series::new("batch", vec![i as u32; 100])]).unwrap();
+            adata.append(x.select_axis(0, batch(i)), obs, None).unwrap();
+            check(&adata, (i + 1) * 100);
+        }
+
+        // Batches that do not match are rejected without modifying the data.
+        let obs = DataFrame::new(vec![Series::new("other", vec![0u32; 100])]).unwrap();
+        assert!(adata.append(x.select_axis(0, batch(0)), obs, None).is_err());
+        let obs = DataFrame::new(vec![Series::new("batch", vec![0u32; 99])]).unwrap();
+        assert!(adata.append(x.select_axis(0, batch(0)), obs, None).is_err());
+        assert!(adata.append(rand_csr::<i32>(100, 5, 10, 1, 100), DataFrame::empty(), None).is_err());
+        // A failure while writing obs also restores X.
+        let obs = DataFrame::new(vec![Series::new("batch", vec!["a"; 100])]).unwrap();
+        assert!(adata.append(x.select_axis(0, batch(0)), obs, None).is_err());
+        check(&adata, 1000);
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        check(&adata, 1000);
+        assert_eq!(adata.x().get::<CsrMatrix<i32>>().unwrap().unwrap(), x);
+        let batches: Vec<u32> = (0..1000).map(|i| i as u32 / 100).collect();
+        assert_eq!(adata.read_obs().unwrap().column("batch").unwrap(), &Series::new("batch", batches));
+        assert_eq!(adata.obs_names().into_vec(), (0..1000).map(|i| i.to_string()).collect::<Vec<_>>());
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::new(dir.join("dense.h5ad")).unwrap();
+        let x = Array2::from_shape_fn((1000, 3), |(i, j)| (i * 3 + j) as f64);
+        for i in 0..10 {
+            let names = (i * 100..(i + 1) * 100).map(|j| format!("cell{}", j)).collect();
+            adata.append(x.select_axis(0, batch(i)), DataFrame::empty(), Some(names)).unwrap();
+            assert_eq!(adata.n_obs(), (i + 1) * 100);
+            assert_eq!(adata.obs_names().len(), adata.n_obs());
+        }
+        assert!(adata.append(x.select_axis(0, batch(0)), DataFrame::empty(), None).is_err());
+        assert_eq!(adata.x().get::<Array2<f64>>().unwrap().unwrap(), x);
+        assert_eq!(adata.obs_ix(["cell999"]).unwrap(), vec![999]);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_overwrite_inplace<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        let arr = Array2::<f64>::zeros((1000, 50));
+        adata.obsm().add("X_embedding", arr.clone()).unwrap();
+        adata.flush().unwrap();
+        let size = std::fs::metadata(&file).unwrap().len();
+
+        for i in 1..=100 {
+            adata.obsm().add("X_embedding", arr.mapv(|x| x + i as f64)).unwrap();
+        }
+        adata.flush().unwrap();
+        assert_eq!(std::fs::metadata(&file).unwrap().len(), size);
+        assert_eq!(
+            adata.obsm().get_item::<Array2<f64>>("X_embedding").unwrap().unwrap(),
+            arr.mapv(|x| x + 100.0),
+        );
+
+        // Different shapes or data types are rewritten.
+        adata.obsm().add("X_embedding", Array2::<i32>::ones((1000, 10))).unwrap();
+        assert_eq!(
+            adata.obsm().get_item::<Array2<i32>>("X_embedding").unwrap().unwrap(),
+            Array2::<i32>::ones((1000, 10)),
+        );
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_remove_many<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        for key in ["a", "b", "c", "d"] {
+            adata.obsm().add(key, Array2::<i32>::zeros((10, 3))).unwrap();
+            adata.uns().add(key, 1i32).unwrap();
+        }
+        adata.obsm().remove_many(["b", "d"]).unwrap();
+        adata.uns().remove_many(["a", "c"]).unwrap();
+
+        let mut obsm = adata.obsm().keys();
+        obsm.sort();
+        assert_eq!(obsm, vec!["a", "c"]);
+        assert_eq!(adata.obsm().get_item::<Array2<i32>>("c").unwrap().unwrap(), Array2::zeros((10, 3)));
+        let mut uns = adata.uns().keys();
+        uns.sort();
+        assert_eq!(uns, vec!["b", "d"]);
+
+        // Repacking reclaims the space of the removed data.
+        let mut adata = adata;
+        adata.obsm().add("big", Array2::<f64>::ones((10, 10000))).unwrap();
+        adata.flush().unwrap();
+        let size = std::fs::metadata(adata.filename()).unwrap().len();
+        adata.obsm().remove_many(["big"]).unwrap();
+        adata.repack().unwrap();
+        assert!(std::fs::metadata(adata.filename()).unwrap().len() < size);
+        let mut obsm = adata.obsm().keys();
+        obsm.sort();
+        assert_eq!(obsm, vec!["a", "c"]);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_write_atomic<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("input.h5ad")).unwrap();
+        adata.set_x(Array2::<i32>::ones((10, 5))).unwrap();
+        let output = dir.join("output.h5ad");
+        adata.write::<B, _>(&output).unwrap();
+
+        // Writing again replaces the destination.
+        adata.set_x(Array2::<i32>::zeros((10, 5))).unwrap();
+        adata.write::<B, _>(&output).unwrap();
+        let adata_out = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+        assert_eq!(adata_out.x().get::<Array2<i32>>().unwrap().unwrap(), Array2::zeros((10, 5)));
+        adata_out.close().unwrap();
+
+        // `write_new` and `write_select_new` keep an existing destination.
+        adata.set_x(Array2::<i32>::ones((10, 5))).unwrap();
+        assert!(adata.write_new::<B, _>(&output).is_err());
+        assert!(adata.write_select_new::<B, _, _>([SelectInfoElem::full(), SelectInfoElem::full()], &output).is_err());
+        let adata_out = AnnData::<B>::open(B::open(&output).unwrap()).unwrap();
+        assert_eq!(adata_out.x().get::<Array2<i32>>().unwrap().unwrap(), Array2::zeros((10, 5)));
+        adata_out.close().unwrap();
+        adata.write_new::<B, _>(dir.join("new.h5ad")).unwrap();
+        std::fs::remove_file(dir.join("new.h5ad")).unwrap();
+        adata.write_select_new::<B, _, _>([SelectInfoElem::full(), SelectInfoElem::full()], dir.join("new.h5ad")).unwrap();
+        std::fs::remove_file(dir.join("new.h5ad")).unwrap();
+
+        // No temporary files are left behind, even when writing fails.
+        assert!(adata.write::<B, _>(dir.join("missing").join("output.h5ad")).is_err());
+        let mut files: Vec<_> = std::fs::read_dir(&dir).unwrap()
+            .map(|x| x.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["input.h5ad", "output.h5ad"]);
+    })
+}
+
+pub fn test_add_iter<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_x(Array2::<f64>::zeros((100, 20))).unwrap();
+        let chunks = |n: usize| (0..n).map(|i| Array2::<f64>::from_elem((10, 5), i as f64));
+
+        adata.obsm().add_iter("X_pca", chunks(10)).unwrap();
+        let expected = Array2::from_shape_fn((100, 5), |(i, _)| (i / 10) as f64);
+        assert_eq!(adata.obsm().get_item::<Array2<f64>>("X_pca").unwrap().unwrap(), expected);
+
+        // The length of the first axis is checked after the last chunk.
+        assert!(adata.obsm().add_iter("X_pca", chunks(9)).is_err());
+        assert!(adata.obsm().add_iter("X_bad", chunks(11)).is_err());
+        assert!(adata.obsm().keys().is_empty());
+        adata.obsm().add_iter("X_pca", chunks(10)).unwrap();
+        assert_eq!(adata.obsm().keys(), vec!["X_pca"]);
+    })
+}
+
+pub fn test_missing_x<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_obs(polars::df!("a" => [1, 2, 3]).unwrap()).unwrap();
+        adata.set_var(polars::df!("b" => ["x", "y"]).unwrap()).unwrap();
+        adata.obsm().add("X_pca", Array2::<f32>::zeros((3, 4))).unwrap();
+        adata.close().unwrap();
+
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        assert!(adata.get_x().is_empty());
+        assert!(adata.x().get::<ArrayData>().unwrap().is_none());
+        assert_eq!((adata.n_obs(), adata.n_vars()), (3, 2));
+        assert_eq!(adata.obsm().keys(), vec!["X_pca"]);
+        adata.close().unwrap();
+    })
+}
+
+pub fn test_infer_shape<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        assert_eq!((adata.n_obs(), adata.n_vars()), (0, 0));
+        adata.set_n_vars(4).unwrap();
+        adata.set_n_vars(4).unwrap();
+        adata.obsm().add("X_pca", Array2::<f32>::zeros((3, 2))).unwrap();
+        assert_eq!((adata.n_obs(), adata.n_vars()), (3, 4));
+
+        assert!(adata.set_n_obs(5).is_err());
+        assert!(adata.set_n_vars(5).is_err());
+        assert!(adata.set_obs(polars::df!("a" => [1, 2]).unwrap()).is_err());
+        assert!(adata.obsm().add("X_umap", Array2::<f32>::zeros((5, 2))).is_err());
+        assert!(adata.set_x(Array2::<f32>::zeros((3, 5))).is_err());
+        adata.set_x(Array2::<f32>::zeros((3, 4))).unwrap();
+
+        // A failed write does not fix any axis.
+        let adata = AnnData::<B>::new(dir.join("test2.h5ad")).unwrap();
+        adata.set_n_vars(4).unwrap();
+        assert!(adata.set_x(Array2::<f32>::zeros((3, 5))).is_err());
+        adata.set_n_obs(5).unwrap();
+    })
+}
+
+pub fn test_read_column_as<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let adata = AnnData::<B>::new(dir.join("test.h5ad")).unwrap();
+        adata.set_obs(polars::df!(
+            "count" => [1i64, 2, 3],
+            "score" => [0.5f64, 1.5, 2.5],
+            "small" => [1i32, 2, 3],
+            "name" => ["a", "b", "c"],
+        ).unwrap()).unwrap();
+
+        let obs = adata.get_obs().inner();
+        assert_eq!(obs.read_column_as::<i64>("count").unwrap(), vec![1, 2, 3]);
+        assert_eq!(obs.read_column_as::<f64>("score").unwrap(), vec![0.5, 1.5, 2.5]);
+        assert_eq!(obs.read_column_as::<String>("name").unwrap(), vec!["a", "b", "c"]);
+
+        // Lossless promotions are allowed, lossy conversions are not.
+        assert_eq!(obs.read_column_as::<i64>("small").unwrap(), vec![1, 2, 3]);
+        assert_eq!(obs.read_column_as::<f64>("small").unwrap(), vec![1.0, 2.0, 3.0]);
+        assert!(obs.read_column_as::<i32>("count").is_err());
+        assert!(obs.read_column_as::<i64>("score").is_err());
+        assert!(obs.read_column_as::<i64>("missing").is_err());
+    })
+}
+
+pub fn test_describe_backed<B: Backend>() {
+    with_tmp_dir(|dir| {
+        let file = dir.join("test.h5ad");
+        let adata = AnnData::<B>::new(&file).unwrap();
+        adata.set_obs(polars::df!(
+            "count" => [1i64, 2, 3],
+            "name" => ["a", "b", "c"],
+            "flag" => [true, false, true],
+        ).unwrap()).unwrap();
+        adata.close().unwrap();
+
+        // String columns are skipped without being read from the file.
+        let adata = AnnData::<B>::open(B::open(&file).unwrap()).unwrap();
+        let summary = adata.get_obs().inner().describe().unwrap();
+        assert_eq!(summary.numeric.get_column_names(), vec!["statistic", "count", "flag"]);
+        assert!(summary.categorical.is_empty());
+        adata.close().unwrap();
+    })
+}
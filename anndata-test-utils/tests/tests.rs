@@ -55,4 +55,358 @@ fn test_iterator_h5() {
         let adata_gen = || AnnData::<H5>::new(&file).unwrap();
         test_iterator(|| adata_gen());
     })
-}
\ No newline at end of file
+}
+#[test]
+fn test_stacked_mixed_format_h5() {
+    test_stacked_mixed_format::<H5>()
+}
+
+#[test]
+fn test_stacked_var_alignment_h5() {
+    test_stacked_var_alignment::<H5>()
+}
+
+#[test]
+fn test_dataset_open_h5() {
+    test_dataset_open::<H5>()
+}
+
+#[test]
+fn test_shuffled_chunks_h5() {
+    test_shuffled_chunks::<H5>()
+}
+
+#[test]
+fn test_var_column_order_h5() {
+    test_var_column_order::<H5>()
+}
+
+#[test]
+fn test_flush_h5() {
+    test_flush::<H5>()
+}
+
+#[test]
+fn test_neighbors_h5() {
+    test_neighbors::<H5>()
+}
+
+#[test]
+fn test_overwrite_inplace_h5() {
+    test_overwrite_inplace::<H5>()
+}
+
+#[test]
+fn test_remove_many_h5() {
+    test_remove_many::<H5>()
+}
+
+#[test]
+fn test_write_atomic_h5() {
+    test_write_atomic::<H5>()
+}
+
+#[test]
+fn test_add_iter_h5() {
+    test_add_iter::<H5>()
+}
+
+#[test]
+fn test_missing_x_h5() {
+    test_missing_x::<H5>()
+}
+
+#[test]
+fn test_read_column_as_h5() {
+    test_read_column_as::<H5>()
+}
+
+#[test]
+fn test_infer_shape_h5() {
+    test_infer_shape::<H5>()
+}
+
+#[test]
+fn test_spatial_h5() {
+    test_spatial::<H5>()
+}
+
+#[test]
+fn test_dirty_h5() {
+    test_dirty::<H5>()
+}
+
+#[test]
+fn test_sum_h5() {
+    test_sum::<H5>()
+}
+
+#[test]
+fn test_read_many_h5() {
+    test_read_many::<H5>()
+}
+
+#[test]
+fn test_categorical_codes_dtype_h5() {
+    test_categorical_codes_dtype::<H5>()
+}
+
+#[test]
+fn test_null_counts_h5() {
+    test_null_counts::<H5>()
+}
+
+#[test]
+fn test_chunk_ranges_h5() {
+    test_chunk_ranges::<H5>()
+}
+
+#[test]
+fn test_chunked_by_nnz_h5() {
+    test_chunked_by_nnz::<H5>()
+}
+
+#[test]
+fn test_append_h5() {
+    test_append::<H5>()
+}
+
+#[test]
+fn test_categorical_encoding_h5() {
+    test_categorical_encoding::<H5>()
+}
+
+#[test]
+fn test_unsupported_uns_h5() {
+    test_unsupported_uns::<H5>()
+}
+
+#[test]
+fn test_nested_uns_h5() {
+    test_nested_uns::<H5>()
+}
+
+#[test]
+fn test_embedding_h5() {
+    test_embedding::<H5>()
+}
+
+#[test]
+fn test_dataframe_select_h5() {
+    test_dataframe_select::<H5>()
+}
+
+#[test]
+fn test_usage_h5() {
+    test_usage::<H5>()
+}
+
+#[test]
+fn test_backend_h5() {
+    test_backend::<H5>()
+}
+
+#[test]
+fn test_create_mode_h5() {
+    test_create_mode::<H5>()
+}
+
+#[test]
+fn test_coo_h5() {
+    test_coo::<H5>()
+}
+
+#[test]
+fn test_qc_metrics_h5() {
+    test_qc_metrics::<H5>()
+}
+
+#[test]
+fn test_f16_h5() {
+    test_f16::<H5>()
+}
+
+#[test]
+fn test_transposed_h5() {
+    test_transposed::<H5>()
+}
+
+#[test]
+fn test_bool_columns_h5() {
+    test_bool_columns::<H5>()
+}
+
+#[test]
+fn test_query_h5() {
+    test_query::<H5>()
+}
+
+#[test]
+fn test_astype_h5() {
+    test_astype::<H5>()
+}
+
+#[test]
+fn test_filter_to_h5() {
+    test_filter_to::<H5>()
+}
+
+#[test]
+fn test_minimal_file_h5() {
+    test_minimal_file::<H5>()
+}
+
+#[test]
+fn test_concat_lazy_h5() {
+    test_concat_lazy::<H5>()
+}
+
+#[test]
+fn test_concat_h5() {
+    test_concat::<H5>()
+}
+
+#[test]
+fn test_custom_attrs_h5() {
+    test_custom_attrs::<H5>()
+}
+
+#[test]
+fn test_custom_attrs_rewrite_h5() {
+    test_custom_attrs_rewrite::<H5>()
+}
+
+#[test]
+fn test_no_x_h5() {
+    test_no_x::<H5>()
+}
+
+#[test]
+fn test_validate_h5() {
+    test_validate::<H5>()
+}
+
+#[test]
+fn test_delete_slots_h5() {
+    test_delete_slots::<H5>()
+}
+
+#[test]
+fn test_save_as_h5() {
+    test_save_as::<H5>()
+}
+
+#[test]
+fn test_overwrite_while_reading_h5() {
+    test_overwrite_while_reading::<H5>()
+}
+
+#[test]
+fn test_sample_obs_h5() {
+    test_sample_obs::<H5>()
+}
+
+#[test]
+fn test_zero_dim_dataset_h5() {
+    test_zero_dim_dataset::<H5>()
+}
+
+#[test]
+fn test_append_to_list_h5() {
+    test_append_to_list::<H5>()
+}
+
+#[test]
+fn test_indptr_cache_h5() {
+    test_indptr_cache::<H5>()
+}
+
+#[test]
+fn test_var_h5() {
+    test_var::<H5>()
+}
+
+#[test]
+fn test_batched_h5() {
+    test_batched::<H5>()
+}
+
+#[test]
+fn test_read_into_h5() {
+    test_read_into::<H5>()
+}
+
+#[test]
+fn test_chunk_cache_h5() {
+    test_chunk_cache::<H5>()
+}
+
+#[test]
+fn test_write_columns_h5() {
+    test_write_columns::<H5>()
+}
+
+#[test]
+fn test_view_h5() {
+    test_view::<H5>()
+}
+
+#[test]
+fn test_nan_as_null_h5() {
+    test_nan_as_null::<H5>()
+}
+
+#[test]
+fn test_reorder_columns_h5() {
+    test_reorder_columns::<H5>()
+}
+
+#[test]
+fn test_raw_h5() {
+    test_raw::<H5>()
+}
+
+#[test]
+fn test_densify_x_h5() {
+    test_densify_x::<H5>()
+}
+
+#[test]
+fn test_string_array_h5() {
+    test_string_array::<H5>()
+}
+
+#[test]
+fn test_compound_index_h5() {
+    test_compound_index::<H5>()
+}
+
+#[test]
+fn test_write_csv_h5() {
+    test_write_csv::<H5>()
+}
+
+#[test]
+fn test_builder_h5() {
+    test_builder::<H5>()
+}
+
+#[test]
+fn test_map_chunks_in_place_h5() {
+    test_map_chunks_in_place::<H5>()
+}
+
+#[test]
+fn test_column_encoding_h5() {
+    test_column_encoding::<H5>()
+}
+
+#[test]
+fn test_export_ipc_h5() {
+    test_export_ipc::<H5>()
+}
+
+#[test]
+fn test_describe_backed_h5() {
+    test_describe_backed::<H5>()
+}
@@ -1,22 +1,72 @@
+mod builder;
+mod concat;
 mod dataset;
+mod raw;
+mod usage;
+mod validate;
+mod view;
 
+pub use builder::AnnDataBuilder;
+pub use concat::{concat, Join};
 pub use dataset::{AnnDataSet, StackedAnnData};
+pub use raw::Raw;
+pub use usage::{ElemUsage, SlotUsage, UsageReport};
+pub use validate::Violation;
+pub use view::AnnDataView;
+#[cfg(feature = "trace")]
+pub(crate) use usage::sizes as data_sizes;
+pub(crate) use usage::item_size;
 use smallvec::SmallVec;
 
 use crate::{
-    backend::{Backend, DataContainer, FileOp, GroupOp},
+    backend::{Backend, DataContainer, DataType, FileOp, GroupOp},
     container::{
-        Dim, ArrayElem, Axis, AxisArrays, DataFrameElem, ElemCollection,
-        InnerDataFrameElem, Slot,
+        Dim, ArrayElem, Axis, AxisArrays, BatchedWrite, DataFrameElem, Elem, ElemCollection,
+        InnerDataFrameElem, Slot, base::{touch, last_modified, read_ipc_frame}, collection::DimLock,
     },
     data::*,
-    traits::AnnDataOp,
+    traits::{AnnDataOp, ArrayElemOp, AxisArraysOp},
 };
 
-use anyhow::{anyhow, ensure, Context, Result};
+#[cfg(feature = "parquet")]
+use crate::container::base::read_parquet_frame;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use itertools::Itertools;
-use polars::prelude::DataFrame;
+use log::warn;
+use polars::prelude::{DataFrame, Series};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// How [`AnnData::new_with_mode`] handles an existing file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateMode {
+    /// Truncate the existing file, "w".
+    Truncate,
+    /// Fail if the file exists, "w-" or "x".
+    Exclusive,
+    /// Open the existing file for reading and writing, "a".
+    Append,
+}
+
+impl FromStr for CreateMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "w" => Ok(CreateMode::Truncate),
+            "w-" | "x" => Ok(CreateMode::Exclusive),
+            "a" => Ok(CreateMode::Append),
+            _ => bail!("unknown mode '{}', expecting 'w', 'w-', 'x' or 'a'", s),
+        }
+    }
+}
 
 pub struct AnnData<B: Backend> {
     file: B::File,
@@ -34,6 +84,20 @@ pub struct AnnData<B: Backend> {
     varp: AxisArrays<B>,
     uns: ElemCollection<B>,
     layers: AxisArrays<B>,
+    raw: Slot<Raw<B>>,
+    // Set by operations that add or remove elements; modifications of
+    // existing elements are tracked by the elements themselves.
+    dirty: AtomicBool,
+    // See `AnnData::generation`.
+    generation: AtomicU64,
+}
+
+/// The source of [`AnnData::generation`], shared by all objects so that a
+/// generation is never repeated, even by a reopened file.
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed)
 }
 
 impl<B: Backend> std::fmt::Debug for AnnData<B> {
@@ -51,42 +115,42 @@ impl<B: Backend> std::fmt::Display for AnnData<B> {
             self.n_vars(),
             self.filename().to_str().unwrap().to_string(),
         )?;
-        if let Some(obs) = self.obs.lock().as_ref().map(|x| x.get_column_names()) {
+        if let Some(obs) = self.obs.read().as_ref().map(|x| x.get_column_names()) {
             if !obs.is_empty() {
                 write!(f, "\n    obs: '{}'", obs.into_iter().join("', '"))?;
             }
         }
-        if let Some(var) = self.var.lock().as_ref().map(|x| x.get_column_names()) {
+        if let Some(var) = self.var.read().as_ref().map(|x| x.get_column_names()) {
             if !var.is_empty() {
                 write!(f, "\n    var: '{}'", var.into_iter().join("', '"))?;
             }
         }
-        if let Some(keys) = self.uns.lock().as_ref().map(|x| x.keys().join("', '")) {
+        if let Some(keys) = self.uns.read().as_ref().map(|x| x.keys().join("', '")) {
             if !keys.is_empty() {
                 write!(f, "\n    uns: '{}'", keys)?;
             }
         }
-        if let Some(keys) = self.obsm.lock().as_ref().map(|x| x.keys().join("', '")) {
+        if let Some(keys) = self.obsm.read().as_ref().map(|x| x.keys().join("', '")) {
             if !keys.is_empty() {
                 write!(f, "\n    obsm: '{}'", keys)?;
             }
         }
-        if let Some(keys) = self.obsp.lock().as_ref().map(|x| x.keys().join("', '")) {
+        if let Some(keys) = self.obsp.read().as_ref().map(|x| x.keys().join("', '")) {
             if !keys.is_empty() {
                 write!(f, "\n    obsp: '{}'", keys)?;
             }
         }
-        if let Some(keys) = self.varm.lock().as_ref().map(|x| x.keys().join("', '")) {
+        if let Some(keys) = self.varm.read().as_ref().map(|x| x.keys().join("', '")) {
             if !keys.is_empty() {
                 write!(f, "\n    varm: '{}'", keys)?;
             }
         }
-        if let Some(keys) = self.varp.lock().as_ref().map(|x| x.keys().join("', '")) {
+        if let Some(keys) = self.varp.read().as_ref().map(|x| x.keys().join("', '")) {
             if !keys.is_empty() {
                 write!(f, "\n    varp: '{}'", keys)?;
             }
         }
-        if let Some(keys) = self.layers.lock().as_ref().map(|x| x.keys().join("', '")) {
+        if let Some(keys) = self.layers.read().as_ref().map(|x| x.keys().join("', '")) {
             if !keys.is_empty() {
                 write!(f, "\n    layers: '{}'", keys)?;
             }
@@ -115,6 +179,58 @@ fn new_layers<B: Backend>(group: B::Group, n_obs: &Dim, n_vars: &Dim) -> Result<
     AxisArrays::new(group, Axis::RowColumn, n_obs, Some(n_vars))
 }
 
+/// Write the default range index as the obs or var dataframe of an output
+/// file, so that files without one keep the names reported by
+/// `obs_names`/`var_names`. Nothing is written if the dimension is unknown.
+fn write_default_index<G: GroupOp>(
+    location: &G,
+    name: &str,
+    n: &DimLock<'_>,
+    select: &SelectInfoElem,
+) -> Result<()> {
+    if !n.is_empty() {
+        let index = DataFrameIndex::from(n.get()).select(select);
+        InnerDataFrameElem::new(location, name, index, &DataFrame::empty())?;
+    }
+    Ok(())
+}
+
+/// The slots of an AnnData object that hold elements by key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ElemSlot {
+    Obsm,
+    Obsp,
+    Varm,
+    Varp,
+    Layers,
+    Uns,
+}
+
+impl std::fmt::Display for ElemSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ElemSlot::Obsm => "obsm",
+            ElemSlot::Obsp => "obsp",
+            ElemSlot::Varm => "varm",
+            ElemSlot::Varp => "varp",
+            ElemSlot::Layers => "layers",
+            ElemSlot::Uns => "uns",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The observations or variables to keep in [`AnnData::filter_to`].
+#[derive(Debug, Clone)]
+pub enum AxisSelection {
+    /// A query on the columns of obs or var, see [`DataFrameElem::query`].
+    Query(String),
+    /// Observation or variable names.
+    Names(Vec<String>),
+    /// Indices, a slice or a boolean mask.
+    Index(SelectInfoElem),
+}
+
 impl<B: Backend> AnnData<B> {
     pub fn get_x(&self) -> &ArrayElem<B> {
         &self.x
@@ -122,12 +238,30 @@ impl<B: Backend> AnnData<B> {
     pub fn get_obs(&self) -> &DataFrameElem<B> {
         &self.obs
     }
+    /// The `raw` group, empty if the file has no raw group or it is
+    /// malformed. A malformed group is kept in the file, see
+    /// [`AnnData::del_raw`] to remove it.
+    pub fn get_raw(&self) -> &Slot<Raw<B>> {
+        &self.raw
+    }
+
+    /// Delete the `raw` group from the file, whether it is well-formed or not.
+    pub fn del_raw(&self) -> Result<()> {
+        self.raw.drop();
+        if self.file.exists("raw")? {
+            self.file.delete("raw")?;
+            self.mark_dirty()?;
+        }
+        Ok(())
+    }
+
     pub fn get_var(&self) -> &DataFrameElem<B> {
         &self.var
     }
 
     /// Open an existing AnnData.
     pub fn open(file: B::File) -> Result<Self> {
+        trace_span!("open", file = %file.filename().display());
         let n_obs = Dim::empty();
         let n_vars = Dim::empty();
 
@@ -159,36 +293,38 @@ impl<B: Backend> AnnData<B> {
             Slot::empty()
         };
 
-        let obsm = match file.open_group("obsm").or(file.create_group("obsm")) {
+        let obsm = match file.open_group("obsm").or_else(|_| file.create_group("obsm")) {
             Ok(group) => new_obsm(group, &n_obs)?,
             _ => AxisArrays::empty(),
         };
 
-        let obsp = match file.open_group("obsp").or(file.create_group("obsp")) {
+        let obsp = match file.open_group("obsp").or_else(|_| file.create_group("obsp")) {
             Ok(group) => new_obsp(group, &n_obs)?,
             _ => AxisArrays::empty(),
         };
 
-        let varm = match file.open_group("varm").or(file.create_group("varm")) {
+        let varm = match file.open_group("varm").or_else(|_| file.create_group("varm")) {
             Ok(group) => new_varm(group, &n_vars)?,
             _ => AxisArrays::empty(),
         };
 
-        let varp = match file.open_group("varp").or(file.create_group("varp")) {
+        let varp = match file.open_group("varp").or_else(|_| file.create_group("varp")) {
             Ok(group) => new_varp(group, &n_vars)?,
             _ => AxisArrays::empty(),
         };
 
-        let uns = match file.open_group("uns").or(file.create_group("uns")) {
+        let uns = match file.open_group("uns").or_else(|_| file.create_group("uns")) {
             Ok(group) => ElemCollection::new(group)?,
             _ => ElemCollection::empty(),
         };
 
-        let layers = match file.open_group("layers").or(file.create_group("layers")) {
+        let layers = match file.open_group("layers").or_else(|_| file.create_group("layers")) {
             Ok(group) => new_layers(group, &n_obs, &n_vars)?,
             _ => AxisArrays::empty(),
         };
 
+        let raw = raw::open_raw(&file, &n_obs)?;
+
         Ok(Self {
             file,
             n_obs,
@@ -202,11 +338,43 @@ impl<B: Backend> AnnData<B> {
             varp,
             uns,
             layers,
+            raw,
+            dirty: AtomicBool::new(false),
+            generation: AtomicU64::new(next_generation()),
         })
     }
 
+    /// Create a new AnnData file. An existing file at `filename` is truncated,
+    /// see [`AnnData::new_with_mode`] for the other options.
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<Self> {
-        let file = B::create(filename)?;
+        Self::new_with_mode(filename, CreateMode::Truncate)
+    }
+
+    /// Create a new AnnData file, handling an existing file at `filename`
+    /// according to `mode`.
+    pub fn new_with_mode<P: AsRef<Path>>(filename: P, mode: CreateMode) -> Result<Self> {
+        Self::new_with_create(filename, mode, |path| B::create(path))
+    }
+
+    /// Like [`Self::new_with_mode`], but the file is created by `create`, which
+    /// allows backend-specific options such as the file format.
+    pub fn new_with_create<P, F>(filename: P, mode: CreateMode, create: F) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&Path) -> Result<B::File>,
+    {
+        let path = filename.as_ref();
+        if path.exists() {
+            match mode {
+                CreateMode::Truncate => {}
+                CreateMode::Exclusive => bail!(
+                    "file '{}' already exists, use mode 'w' to truncate it", path.display()
+                ),
+                CreateMode::Append => return Self::open(B::open_rw(path)?),
+            }
+        }
+        trace_event!(file = %path.display(), "create");
+        let file = create(path)?;
         let n_obs = Dim::empty();
         let n_vars = Dim::empty();
         Ok(Self {
@@ -219,31 +387,93 @@ impl<B: Backend> AnnData<B> {
             varp: new_varp(file.create_group("varp")?, &n_vars)?,
             uns: ElemCollection::new(file.create_group("uns")?)?,
             layers: new_layers(file.create_group("layers")?, &n_obs, &n_vars)?,
+            raw: Slot::empty(),
             file,
             n_obs,
             n_vars,
+            dirty: AtomicBool::new(false),
+            generation: AtomicU64::new(next_generation()),
         })
     }
 
+    /// Write the AnnData object to a new file. The data is first written to a
+    /// temporary file in the same directory, which replaces `filename` only
+    /// after it has been written completely.
     pub fn write<O: Backend, P: AsRef<Path>>(&self, filename: P) -> Result<()> {
-        let file = O::create(filename)?;
-        let _obs_lock = self.n_obs.lock();
-        let _vars_lock = self.n_vars.lock();
+        self.write_columns::<O, _>(filename, None, None, true)
+    }
+
+    /// Like [`Self::write`], but it is an error if `filename` exists, including
+    /// when it is created by another process while the data is being written.
+    pub fn write_new<O: Backend, P: AsRef<Path>>(&self, filename: P) -> Result<()> {
+        self.write_columns::<O, _>(filename, None, None, false)
+    }
+
+    /// Like [`Self::write`], but only the listed columns of obs and var are
+    /// written, in the listed order. `None` keeps all columns of the axis.
+    /// Listing a column that does not exist is an error. If `overwrite` is
+    /// false, an existing `filename` is kept as in [`Self::write_new`].
+    pub fn write_columns<O, P>(
+        &self,
+        filename: P,
+        obs_columns: Option<&[String]>,
+        var_columns: Option<&[String]>,
+        overwrite: bool,
+    ) -> Result<()>
+    where
+        O: Backend,
+        P: AsRef<Path>,
+    {
+        trace_span!("write", file = %self.filename().display(), output = %filename.as_ref().display());
+        write_atomic(filename, overwrite, |tmp| self.write_to::<O>(O::create(tmp)?, [obs_columns, var_columns]))
+    }
+
+    /// Write the AnnData object to `filename` and make the object backed by
+    /// the new file, which is opened in read-write mode, so that subsequent
+    /// changes go to the new file. The old file is closed but not removed.
+    /// The object is unchanged if the writing fails. See [`Self::write_columns`]
+    /// for the meaning of `overwrite`.
+    pub fn save_as<P: AsRef<Path>>(&mut self, filename: P, overwrite: bool) -> Result<()> {
+        let filename = filename.as_ref();
+        if let (Ok(new), Ok(old)) = (filename.canonicalize(), self.filename().canonicalize()) {
+            ensure!(new != old, "'{}' is the file backing the object", filename.display());
+        }
+        self.write_columns::<B, _>(filename, None, None, overwrite)?;
+        let adata = Self::open(B::open_rw(filename)?)?;
+        std::mem::replace(self, adata).close()
+    }
+
+    /// Rewrite the backing file to reclaim the space of deleted elements, e.g.,
+    /// after [`AxisArraysOp::remove_many`], as HDF5 does not shrink files when
+    /// data is deleted. The file is replaced by a compacted copy, which the
+    /// object is then backed by.
+    pub fn repack(&mut self) -> Result<()> {
+        let filename = self.filename();
+        self.write_columns::<B, _>(&filename, None, None, true)?;
+        let adata = Self::open(B::open_rw(&filename)?)?;
+        std::mem::replace(self, adata).close()
+    }
+
+    fn write_to<O: Backend>(&self, file: O::File, columns: [Option<&[String]>; 2]) -> Result<()> {
+        let obs_lock = self.n_obs.lock();
+        let vars_lock = self.n_vars.lock();
         self.get_x()
             .lock()
             .as_mut()
             .map(|x| x.export::<O, _>(&file, "X"))
             .transpose()?;
-        self.get_obs()
-            .lock()
-            .as_mut()
-            .map(|x| x.export::<O, _>(&file, "obs"))
-            .transpose()?;
-        self.get_var()
-            .lock()
-            .as_mut()
-            .map(|x| x.export::<O, _>(&file, "var"))
-            .transpose()?;
+        match (self.get_obs().lock().as_mut(), columns[0]) {
+            (Some(x), None) => x.export::<O, _>(&file, "obs")?,
+            (Some(x), Some(columns)) => x.export_columns::<O, _>(columns, &file, "obs")?,
+            (None, Some([c, ..])) => bail!("no column named '{}' in 'obs'", c),
+            (None, _) => write_default_index(&file, "obs", &obs_lock, &SelectInfoElem::full())?,
+        }
+        match (self.get_var().lock().as_mut(), columns[1]) {
+            (Some(x), None) => x.export::<O, _>(&file, "var")?,
+            (Some(x), Some(columns)) => x.export_columns::<O, _>(columns, &file, "var")?,
+            (None, Some([c, ..])) => bail!("no column named '{}' in 'var'", c),
+            (None, _) => write_default_index(&file, "var", &vars_lock, &SelectInfoElem::full())?,
+        }
         self.obsm()
             .lock()
             .as_mut()
@@ -274,11 +504,32 @@ impl<B: Backend> AnnData<B> {
             .as_mut()
             .map(|x| x.export::<O, _>(&file, "layers"))
             .transpose()?;
+        raw::export_raw::<B, O>(&self.file, &self.raw, &SelectInfoElem::full(), &file)?;
         file.close()?;
         Ok(())
     }
 
     pub fn write_select<O, S, P>(&self, selection: S, filename: P) -> Result<()>
+    where
+        O: Backend,
+        S: AsRef<[SelectInfoElem]>,
+        P: AsRef<Path>,
+    {
+        self.write_select_impl::<O, _, _>(selection, filename, true)
+    }
+
+    /// Like [`Self::write_select`], but it is an error if `filename` exists,
+    /// as in [`Self::write_new`].
+    pub fn write_select_new<O, S, P>(&self, selection: S, filename: P) -> Result<()>
+    where
+        O: Backend,
+        S: AsRef<[SelectInfoElem]>,
+        P: AsRef<Path>,
+    {
+        self.write_select_impl::<O, _, _>(selection, filename, false)
+    }
+
+    fn write_select_impl<O, S, P>(&self, selection: S, filename: P, overwrite: bool) -> Result<()>
     where
         O: Backend,
         S: AsRef<[SelectInfoElem]>,
@@ -289,25 +540,68 @@ impl<B: Backend> AnnData<B> {
         selection.as_ref()[1].bound_check(self.n_vars())
             .map_err(|e| anyhow!("AnnData var {}", e))?;
         let slice: SmallVec<[_; 3]> = selection.as_ref().iter().collect();
-        let file = O::create(filename)?;
-        let _obs_lock = self.n_obs.lock();
-        let _vars_lock = self.n_vars.lock();
+        trace_span!(
+            "write_select",
+            file = %self.filename().display(),
+            output = %filename.as_ref().display(),
+            n_obs = slice[0].output_len(self.n_obs()),
+            n_vars = slice[1].output_len(self.n_vars()),
+        );
+        write_atomic(filename, overwrite, |tmp| self.write_select_to::<O>(&slice, O::create(tmp)?))
+    }
+
+    /// Write the observations and variables matching the selections to a new
+    /// file, without loading the subset into memory. `None` keeps all
+    /// observations or variables. The selections are resolved before the file
+    /// is created, so an invalid query or unknown name leaves no file behind.
+    pub fn filter_to<O, P>(
+        &self,
+        filename: P,
+        obs: Option<AxisSelection>,
+        var: Option<AxisSelection>,
+    ) -> Result<()>
+    where
+        O: Backend,
+        P: AsRef<Path>,
+    {
+        let obs = self.resolve_selection(0, obs).context("invalid obs selection")?;
+        let var = self.resolve_selection(1, var).context("invalid var selection")?;
+        self.write_select::<O, _, _>([obs, var], filename)
+    }
+
+    fn resolve_selection(&self, axis: usize, selection: Option<AxisSelection>) -> Result<SelectInfoElem> {
+        let elem = match selection {
+            None => SelectInfoElem::full(),
+            Some(AxisSelection::Query(expr)) => {
+                let df = if axis == 0 { &self.obs } else { &self.var };
+                df.query(&expr)?.into()
+            }
+            Some(AxisSelection::Names(names)) => {
+                let names = names.iter().map(String::as_str);
+                if axis == 0 { self.obs_ix(names)? } else { self.var_ix(names)? }.into()
+            }
+            Some(AxisSelection::Index(x)) => x,
+        };
+        Ok(elem)
+    }
+
+    fn write_select_to<O: Backend>(&self, slice: &[&SelectInfoElem], file: O::File) -> Result<()> {
+        let obs_lock = self.n_obs.lock();
+        let vars_lock = self.n_vars.lock();
         self.get_x()
             .lock()
             .as_mut()
-            .map(|x| x.export_select::<O, _>(slice.as_slice(), &file, "X"))
+            .map(|x| x.export_select::<O, _>(slice, &file, "X"))
             .transpose()?;
 
-        self.get_obs()
-            .lock()
-            .as_mut()
-            .map(|x| x.export_axis(0, slice[0], &file, "obs"))
-            .transpose()?;
-        self.get_var()
-            .lock()
-            .as_mut()
-            .map(|x| x.export_axis(0, slice[1], &file, "var"))
-            .transpose()?;
+        match self.get_obs().lock().as_mut() {
+            Some(x) => x.export_axis(0, slice[0], &file, "obs")?,
+            None => write_default_index(&file, "obs", &obs_lock, slice[0])?,
+        }
+        match self.get_var().lock().as_mut() {
+            Some(x) => x.export_axis(0, slice[1], &file, "var")?,
+            None => write_default_index(&file, "var", &vars_lock, slice[1])?,
+        }
         self.uns()
             .lock()
             .as_mut()
@@ -336,21 +630,263 @@ impl<B: Backend> AnnData<B> {
         self.layers()
             .lock()
             .as_mut()
-            .map(|x| x.export_select(slice.as_slice(), &file, "layers"))
+            .map(|x| x.export_select(slice, &file, "layers"))
             .transpose()?;
+        raw::export_raw::<B, O>(&self.file, &self.raw, slice[0], &file)?;
         file.close()?;
         Ok(())
     }
 
+    /// Fix the number of observations before any element is written along
+    /// this axis. The dimensions are otherwise inferred from the first element
+    /// written (X, obs, obsm, etc.), and all subsequent writes are validated
+    /// against it. Return an error if the number of observations has already
+    /// been set to a different value.
+    pub fn set_n_obs(&self, n: usize) -> Result<()> {
+        self.n_obs.try_set(n)
+    }
+
+    /// Fix the number of variables, see [`AnnData::set_n_obs`].
+    pub fn set_n_vars(&self, n: usize) -> Result<()> {
+        self.n_vars.try_set(n)
+    }
+
     pub fn filename(&self) -> PathBuf {
         self.file.filename()
     }
 
+    /// Check that the file complies with the AnnData on-disk specification
+    /// and return the violations found, which is empty for a valid file.
+    /// Every element must have the `encoding-type` and `encoding-version`
+    /// attributes, dataframes must have the `_index` and `column-order`
+    /// attributes, sparse matrices must have components whose lengths agree
+    /// with their `shape`, and the elements of axis arrays must agree with
+    /// `n_obs` and `n_vars`. Elements whose encoding is not supported by this
+    /// library, e.g., in `uns`, are reported as well. Only the metadata is read.
+    pub fn validate(&self) -> Result<Vec<Violation>> {
+        validate::validate::<B>(&self.file, self.n_obs(), self.n_vars())
+    }
+
+    /// Report the storage usage of the elements in the file, e.g., to find out
+    /// which elements take up the space. Only the metadata is read.
+    pub fn usage(&self) -> Result<UsageReport> {
+        usage::usage::<B>(&self.file)
+    }
+
+    /// Split the observations into at most `n_chunks` contiguous row ranges
+    /// that can be processed independently, e.g., by worker processes that
+    /// each open the file read-only. If X is a CSR matrix, the ranges contain
+    /// roughly the same number of non-zero entries, otherwise roughly the
+    /// same number of rows.
+    pub fn chunk_ranges(&self, n_chunks: usize) -> Result<Vec<Range<usize>>> {
+        ensure!(n_chunks > 0, "the number of chunks must be positive");
+        let x = self.x.read();
+        let indptr = x.as_ref().map(|x| x.csr_indptr()).transpose()?.flatten();
+        Ok(balanced_ranges(indptr.as_deref(), self.n_obs(), n_chunks))
+    }
+
+    /// Sample `n` observations without replacement by reservoir sampling. The
+    /// same `seed` gives the same sample. The indices are returned in
+    /// increasing order, so that the sampled rows can be read sequentially,
+    /// e.g., with [`AnnData::write_select`].
+    pub fn sample_obs(&self, n: usize, seed: u64) -> Result<Vec<usize>> {
+        let n_obs = self.n_obs();
+        ensure!(n <= n_obs, "cannot sample {} observations out of {}", n, n_obs);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut reservoir: Vec<usize> = (0..n).collect();
+        for i in n..n_obs {
+            let j = rng.gen_range(0..=i);
+            if j < n {
+                reservoir[j] = i;
+            }
+        }
+        reservoir.sort_unstable();
+        Ok(reservoir)
+    }
+
+    /// Read integer columns of `obs` and `var` containing only 0 and 1 as
+    /// boolean columns, e.g., for files written by tools that store booleans
+    /// as integers. Columns marked with a `dtype` attribute of "bool" are
+    /// always read as boolean, regardless of this setting.
+    pub fn set_coerce_bool_columns(&self, coerce: bool) {
+        for elem in [&self.obs, &self.var] {
+            if let Some(x) = elem.lock().as_mut() {
+                x.set_coerce_bool_columns(coerce);
+            }
+        }
+    }
+
+    /// Read NaN values of float columns of `obs` and `var` as nulls. NaN is
+    /// the conventional encoding of missing values in float columns. By
+    /// default, NaN values are kept.
+    pub fn set_nan_as_null(&self, nan_as_null: bool) {
+        for elem in [&self.obs, &self.var] {
+            if let Some(x) = elem.lock().as_mut() {
+                x.set_nan_as_null(nan_as_null);
+            }
+        }
+    }
+
+    /// Trust the column indices of the CSR matrices in X, layers, obsm, obsp,
+    /// varm and varp to be sorted and unique when reading them, which skips
+    /// sorting them and checking their order. This applies to the elements
+    /// present when it is called. See
+    /// [`crate::container::InnerArrayElem::set_assume_sorted_indices`].
+    pub fn set_assume_sorted_indices(&self, assume_sorted_indices: bool) {
+        if let Some(x) = self.x.lock().as_mut() {
+            x.set_assume_sorted_indices(assume_sorted_indices);
+        }
+        for elem in [&self.layers, &self.obsm, &self.obsp, &self.varm, &self.varp] {
+            if let Some(elem) = elem.lock().as_ref() {
+                elem.values().for_each(|x| if let Some(x) = x.lock().as_mut() {
+                    x.set_assume_sorted_indices(assume_sorted_indices);
+                });
+            }
+        }
+    }
+
+    /// A number that changes whenever the positions of the observations or
+    /// variables change in place, i.e., by [`AnnData::subset`],
+    /// [`AnnData::append`] or when a dimension is unset. Selections taken at a
+    /// different generation may no longer be valid.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    fn bump_generation(&self) {
+        self.generation.store(next_generation(), Ordering::Relaxed);
+    }
+
+    /// Whether the AnnData object has been modified since it was opened or
+    /// last flushed.
+    pub fn is_dirty(&self) -> bool {
+        macro_rules! is_dirty {
+            ($($name:ident),*) => {
+                false $(|| self.$name.read().as_ref().is_some_and(|x| x.is_dirty()))*
+            };
+        }
+        self.dirty.load(Ordering::Relaxed)
+            || is_dirty!(x, obs, var, obsm, obsp, varm, varp, uns, layers)
+    }
+
+    /// The time of the last modification of the file, in seconds since the
+    /// Unix epoch. This is stored in the `last_modified` attribute of the file
+    /// root, and is updated by every operation that modifies the file. Return
+    /// `None` if the file has never been modified by this library.
+    pub fn last_modified(&self) -> Option<f64> {
+        last_modified::<B>(&self.file)
+    }
+
+    fn mark_dirty(&self) -> Result<()> {
+        self.dirty.store(true, Ordering::Relaxed);
+        touch::<B>(&self.file)
+    }
+
+    /// Return the slots, other than obs or var, whose elements have the size
+    /// `n_obs` if `obs` is true, or `n_vars` otherwise.
+    fn dependents(&self, obs: bool) -> Vec<&'static str> {
+        let arrays = if obs {
+            [("obsm", &self.obsm), ("obsp", &self.obsp), ("layers", &self.layers)]
+        } else {
+            [("varm", &self.varm), ("varp", &self.varp), ("layers", &self.layers)]
+        };
+        let mut result: Vec<_> = arrays.into_iter()
+            .filter(|(_, x)| !x.keys().is_empty())
+            .map(|(name, _)| name)
+            .collect();
+        if !self.x.is_empty() {
+            result.insert(0, "X");
+        }
+        result
+    }
+
+    /// Unset `n_obs` and `n_vars` once no element depends on them, so that
+    /// they agree with the dimensions found when the file is reopened. The
+    /// dimension stays locked while the slots are checked, so that no element
+    /// can be added in between.
+    fn release_dims(&self) {
+        let mut n_obs = self.n_obs.lock();
+        if !n_obs.is_empty() && self.is_unused(true) == Some(true) {
+            n_obs.unset();
+            self.bump_generation();
+        }
+        drop(n_obs);
+        let mut n_vars = self.n_vars.lock();
+        if !n_vars.is_empty() && self.is_unused(false) == Some(true) {
+            n_vars.unset();
+            self.bump_generation();
+        }
+    }
+
+    /// Whether no element has the size `n_obs` if `obs` is true, or `n_vars`
+    /// otherwise. Slots being written are not waited for, as their writer may
+    /// be waiting for the dimension lock held by the caller; `None` is
+    /// returned instead, and the dimension is kept.
+    fn is_unused(&self, obs: bool) -> Option<bool> {
+        let (df, arrays) = if obs {
+            (&self.obs, [&self.obsm, &self.obsp, &self.layers])
+        } else {
+            (&self.var, [&self.varm, &self.varp, &self.layers])
+        };
+        if df.try_read()?.is_some() || self.x.try_read()?.is_some() {
+            return Some(false);
+        }
+        for x in arrays {
+            if x.try_read()?.as_ref().is_some_and(|x| !x.is_empty()) {
+                return Some(false);
+            }
+        }
+        Some(true)
+    }
+
+    /// Run `f` as a batched write. The modification time of the file is
+    /// written once when `f` returns, instead of after every write, and the
+    /// file is flushed at the end. This saves many small writes when creating
+    /// many elements, e.g., on network file systems. Batches can be nested.
+    ///
+    /// ```
+    /// # use anndata::*;
+    /// # use ndarray::Array2;
+    /// fn add_embeddings<B: Backend>(adata: &AnnData<B>) -> anyhow::Result<()> {
+    ///     adata.batched(|adata| {
+    ///         for i in 0..50 {
+    ///             adata.obsm().add(&format!("X_{}", i), Array2::<f32>::zeros((adata.n_obs(), 2)))?;
+    ///         }
+    ///         Ok(())
+    ///     })
+    /// }
+    /// ```
+    pub fn batched<T, F: FnOnce(&Self) -> Result<T>>(&self, f: F) -> Result<T> {
+        let batch = BatchedWrite::<B>::new(&self.file);
+        let result = f(self);
+        let finished = batch.finish();
+        let value = result?;
+        finished?;
+        Ok(value)
+    }
+
+    /// Flush the buffered data to disk without closing the file.
+    pub fn flush(&self) -> Result<()> {
+        macro_rules! mark_clean {
+            ($($name:ident),*) => {
+                $(
+                if let Some(x) = self.$name.lock().as_mut() {
+                    x.mark_clean();
+                }
+                )*
+            };
+        }
+        self.file.flush()?;
+        mark_clean!(x, obs, var, obsm, obsp, varm, varp, uns, layers);
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
     pub fn close(self) -> Result<()> {
         macro_rules! close {
             ($($name:ident),*) => {
                 $(
-                self.$name.lock().as_ref().map(|x| x.values().for_each(|x| x.drop()));
+                self.$name.read().as_ref().map(|x| x.values().for_each(|x| x.drop()));
                 self.$name.drop();
                 )*
             };
@@ -358,7 +894,9 @@ impl<B: Backend> AnnData<B> {
         self.x.drop();
         self.obs.drop();
         self.var.drop();
-        close!(obsm, obsp, varm, varp, uns);
+        self.raw.drop();
+        close!(obsm, obsp, varm, varp);
+        self.uns.close();
         self.file.close()
     }
 
@@ -375,6 +913,15 @@ impl<B: Backend> AnnData<B> {
         );
         let obs_ix = &slice[0];
         let var_ix = &slice[1];
+        let select = BoundedSelectInfo::try_new(&slice, &Shape::from((obs_lock.get(), vars_lock.get())))
+            .map_err(|e| anyhow!("cannot subset '{}': {}", self.filename().display(), e))?;
+        if select.is_identity() {
+            return Ok(());
+        }
+        // The positions change even if an element fails to be subset.
+        self.bump_generation();
+        let out_shape = select.out_shape();
+        trace_span!("subset", file = %self.filename().display(), shape = %out_shape);
 
         self.x
             .lock()
@@ -420,14 +967,250 @@ impl<B: Backend> AnnData<B> {
             .map(|layers| layers.subset(&[obs_ix, var_ix]))
             .transpose()?;
 
+        if !obs_ix.is_full() {
+            match self.raw.read().as_ref() {
+                Some(raw) => raw.subset_obs(obs_ix)?,
+                None if self.file.exists("raw")? => warn!(
+                    "the malformed raw group in '{}' is not subset", self.filename().display()
+                ),
+                None => {}
+            }
+        }
+
         if !obs_lock.is_empty() {
-            obs_lock.set(BoundedSelectInfoElem::new(obs_ix, obs_lock.get()).len());
+            obs_lock.set(out_shape[0]);
         }
         if !vars_lock.is_empty() {
-            vars_lock.set(BoundedSelectInfoElem::new(var_ix, vars_lock.get()).len());
+            vars_lock.set(out_shape[1]);
         }
 
-        Ok(())
+        self.mark_dirty()
+    }
+
+    /// Write a dense copy of X to the layer `name`, leaving X unchanged. X is
+    /// read and converted `chunk_size` rows at a time, so that only one chunk
+    /// is held in memory as a dense array.
+    pub fn densify_x(&self, name: &str, chunk_size: usize) -> Result<()> {
+        ensure!(chunk_size > 0, "chunk_size must be positive");
+        let dtype = self.x.try_inner().context("X is empty")?.dtype();
+        ensure!(
+            matches!(dtype, DataType::Array(_) | DataType::CsrMatrix(_) | DataType::CscMatrix(_)),
+            "cannot densify X of type {}",
+            dtype,
+        );
+        let mut error = None;
+        let chunks = self.x.chunked::<ArrayData>(chunk_size).map_while(|(chunk, _, _)| {
+            match chunk.into_format(ArrayFormat::Dense).and_then(DynArray::try_from) {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            }
+        });
+        let result = self.layers().add_iter(name, chunks);
+        // A failed conversion ends the stream early, which may also fail the
+        // write; report the conversion error as it is the root cause.
+        if let Some(e) = error {
+            if result.is_ok() {
+                self.layers().remove(name)?;
+            }
+            return Err(e.context("cannot densify X"));
+        }
+        result
+    }
+
+    /// Replace obs by an Arrow IPC file, e.g., one written by
+    /// [`InnerDataFrameElem::write_ipc`]. The first column of the file gives
+    /// the observation names.
+    pub fn set_obs_from_ipc<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let (index, df) = read_ipc_frame(path.as_ref())?;
+        self.replace_annotations(true, index, df)
+    }
+
+    /// Replace var by an Arrow IPC file, see [`AnnData::set_obs_from_ipc`].
+    pub fn set_var_from_ipc<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let (index, df) = read_ipc_frame(path.as_ref())?;
+        self.replace_annotations(false, index, df)
+    }
+
+    /// Replace obs by a Parquet file, e.g., one written by
+    /// [`InnerDataFrameElem::write_parquet`]. The first column of the file
+    /// gives the observation names.
+    #[cfg(feature = "parquet")]
+    pub fn set_obs_from_parquet<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let (index, df) = read_parquet_frame(path.as_ref())?;
+        self.replace_annotations(true, index, df)
+    }
+
+    /// Replace var by a Parquet file, see [`AnnData::set_obs_from_parquet`].
+    #[cfg(feature = "parquet")]
+    pub fn set_var_from_parquet<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let (index, df) = read_parquet_frame(path.as_ref())?;
+        self.replace_annotations(false, index, df)
+    }
+
+    /// Replace both the names and the columns of obs, or var if `is_obs` is false.
+    fn replace_annotations(&self, is_obs: bool, index: DataFrameIndex, df: DataFrame) -> Result<()> {
+        let (elem, dim, name) = if is_obs {
+            (&self.obs, &self.n_obs, "obs")
+        } else {
+            (&self.var, &self.n_vars, "var")
+        };
+        dim.try_set(index.len())?;
+        if elem.is_empty() {
+            elem.insert(InnerDataFrameElem::new(&self.file, name, index, &df)?);
+        } else {
+            elem.inner().replace(index, df)?;
+        }
+        self.mark_dirty()
+    }
+
+    /// Append a batch of observations, given as rows of X together with the
+    /// corresponding records of obs. X is grown in place, so that data can be
+    /// written in a streaming fashion. The batch is appended atomically: if
+    /// anything fails, X and obs are left as they were.
+    ///
+    /// `obs` may have no columns, otherwise its columns must match those of the
+    /// existing obs. `obs_names` can be omitted if obs has a range index, e.g.,
+    /// the default one. X must be a dense array or a csr matrix, and other
+    /// elements aligned with the observations (obsm, obsp and layers) must be
+    /// empty, and the file must have no raw group.
+    pub fn append<D: Into<ArrayData>>(
+        &self,
+        x: D,
+        obs: DataFrame,
+        obs_names: Option<Vec<String>>,
+    ) -> Result<()> {
+        let x: ArrayData = x.into();
+        let shape = x.shape();
+        let n = shape[0];
+        ensure!(
+            obs.width() == 0 || obs.height() == n,
+            "X and obs must have the same number of rows, but receive {} and {}",
+            n,
+            obs.height(),
+        );
+        let is_empty = |x: &AxisArrays<B>| x.read().as_ref().map_or(true, |x| x.is_empty());
+        ensure!(
+            is_empty(&self.obsm) && is_empty(&self.obsp) && is_empty(&self.layers),
+            "cannot append observations when obsm, obsp or layers are not empty"
+        );
+        ensure!(!self.file.exists("raw")?, "cannot append observations when raw is present");
+
+        let mut obs_lock = self.n_obs.lock();
+        let mut vars_lock = self.n_vars.lock();
+        let n_obs = obs_lock.get();
+        ensure!(
+            vars_lock.is_empty() || vars_lock.get() == shape[1],
+            "cannot append rows with {} columns to X with {} columns",
+            shape[1],
+            vars_lock.get(),
+        );
+
+        // Check the columns before X is touched; they are checked again when
+        // obs is written.
+        if let Some(elem) = self.obs.read().as_ref() {
+            ensure!(
+                (elem.width() == 0 && elem.height() == 0)
+                    || (obs.width() == elem.width()
+                        && obs.get_column_names().iter().all(|x| elem.get_column_names().contains(*x))),
+                "the columns of the new records differ from those of obs",
+            );
+        }
+
+        if self.x.is_empty() {
+            ensure!(n_obs == 0, "cannot append observations as X is empty");
+            let container = ArrayChunk::write_by_chunk(std::iter::once(x), &self.file, "X")?;
+            self.x.swap(&ArrayElem::try_from(container)?);
+        } else {
+            self.x.inner().append_rows(&x)?;
+        }
+
+        // Only the new rows are written to obs, which rolls itself back if this
+        // fails. X is then restored to its previous rows.
+        let result = if self.obs.is_empty() {
+            let mut index = DataFrameIndex::from(n_obs);
+            index.append(n, obs_names).and_then(|_| {
+                let elem = InnerDataFrameElem::new(&self.file, "obs", index, &obs);
+                if elem.is_err() && self.file.exists("obs")? {
+                    self.file.delete("obs")?;
+                }
+                self.obs.insert(elem?);
+                Ok(())
+            })
+        } else {
+            self.obs.inner().append_rows(n, obs_names, &obs)
+        };
+        if let Err(e) = result {
+            let restored = if n_obs == 0 {
+                self.x.clear()
+            } else {
+                self.x.inner().truncate_rows(n_obs)
+            };
+            if let Err(err) = restored {
+                warn!("cannot restore X after a failed append: {}", err);
+            }
+            return Err(e);
+        }
+
+        obs_lock.set(n_obs + n);
+        vars_lock.set(shape[1]);
+        self.bump_generation();
+        self.mark_dirty()
+    }
+
+    /// Read several elements at once. Each slot is locked only once to look
+    /// up the requested keys, and the elements are then read in parallel.
+    ///
+    /// The result is keyed by "{slot}/{key}", e.g., "obsm/X_pca". A failure to
+    /// read one element does not affect the others; it is reported in the
+    /// corresponding entry.
+    pub fn read_many(&self, requests: &[(ElemSlot, &str)]) -> HashMap<String, Result<Data>> {
+        enum Target<B: Backend> {
+            Array(ArrayElem<B>),
+            Elem(Elem<B>),
+        }
+
+        let axis_arrays = |slot| match slot {
+            ElemSlot::Obsm => Some(&self.obsm),
+            ElemSlot::Obsp => Some(&self.obsp),
+            ElemSlot::Varm => Some(&self.varm),
+            ElemSlot::Varp => Some(&self.varp),
+            ElemSlot::Layers => Some(&self.layers),
+            ElemSlot::Uns => None,
+        };
+        let mut targets = Vec::with_capacity(requests.len());
+        for (slot, keys) in requests.iter().into_group_map_by(|x| x.0) {
+            let found: Vec<_> = match axis_arrays(slot) {
+                Some(arrays) => {
+                    let arrays = arrays.read();
+                    keys.iter().map(|(_, k)| {
+                        (k, arrays.as_ref().and_then(|x| x.get(*k).cloned()).map(Target::Array))
+                    }).collect()
+                }
+                None => {
+                    let uns = self.uns.read();
+                    keys.iter().map(|(_, k)| {
+                        (k, uns.as_ref().and_then(|x| x.get(*k).cloned()).map(Target::Elem))
+                    }).collect()
+                }
+            };
+            targets.extend(found.into_iter().map(|(k, x)| (format!("{}/{}", slot, k), x)));
+        }
+
+        targets
+            .into_par_iter()
+            .map(|(name, target)| {
+                let data = match target {
+                    Some(Target::Array(x)) => x.get::<ArrayData>().map(|x| x.map(Into::into)),
+                    Some(Target::Elem(x)) => x.get_data::<Data>(),
+                    None => Err(anyhow!("'{}' does not exist", name)),
+                };
+                let data = data.and_then(|x| x.with_context(|| format!("'{}' has been closed", name)));
+                (name, data)
+            })
+            .collect()
     }
 }
 
@@ -456,7 +1239,7 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
         {
             Ok(_) => {
                 self.x.swap(&new_elem);
-                Ok(())
+                self.mark_dirty()
             }
             Err(e) => {
                 new_elem.clear()?;
@@ -471,8 +1254,7 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
             shape.ndim() >= 2,
             "X must be a N dimensional array, where N >= 2"
         );
-        self.n_obs.try_set(shape[0])?;
-        self.n_vars.try_set(shape[1])?;
+        self.n_obs.try_set_both(&self.n_vars, shape[0], shape[1])?;
 
         if !self.x.is_empty() {
             self.x.inner().save(data)?;
@@ -480,11 +1262,13 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
             let new_elem = ArrayElem::try_from(data.write(&self.file, "X")?)?;
             self.x.swap(&new_elem);
         }
-        Ok(())
+        self.mark_dirty()
     }
 
     fn del_x(&self) -> Result<()> {
-        self.x.clear()
+        self.x.clear()?;
+        self.release_dims();
+        self.mark_dirty()
     }
 
     fn n_obs(&self) -> usize {
@@ -494,18 +1278,16 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
         self.n_vars.get()
     }
 
+    // Files without an obs dataframe, e.g., those with only X, get a default
+    // range index so that the names always agree with `n_obs`.
     fn obs_names(&self) -> DataFrameIndex {
-        self.obs
-            .lock()
-            .as_ref()
-            .map_or(DataFrameIndex::empty(), |obs| obs.index.clone())
+        let index = self.obs.read().as_ref().map(|obs| obs.index.clone());
+        index.unwrap_or_else(|| DataFrameIndex::from(self.n_obs()))
     }
 
     fn var_names(&self) -> DataFrameIndex {
-        self.var
-            .lock()
-            .as_ref()
-            .map_or(DataFrameIndex::empty(), |var| var.index.clone())
+        let index = self.var.read().as_ref().map(|var| var.index.clone());
+        index.unwrap_or_else(|| DataFrameIndex::from(self.n_vars()))
     }
 
     fn set_obs_names(&self, index: DataFrameIndex) -> Result<()> {
@@ -516,7 +1298,7 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
         } else {
             self.obs.inner().set_index(index)?;
         }
-        Ok(())
+        self.mark_dirty()
     }
 
     fn set_var_names(&self, index: DataFrameIndex) -> Result<()> {
@@ -527,34 +1309,30 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
         } else {
             self.var.inner().set_index(index)?;
         }
-        Ok(())
+        self.mark_dirty()
     }
 
     fn obs_ix<'a, I: IntoIterator<Item = &'a str>>(&self, names: I) -> Result<Vec<usize>>
     {
-        let inner = self.obs.inner();
-        names
+        let lookup = |index: &DataFrameIndex| names
             .into_iter()
-            .map(|i| {
-                inner
-                    .index
-                    .get_index(i)
-                    .context(format!("'{}' does not exist in obs_names", i))
-            })
-            .collect()
+            .map(|i| index.get_index(i).context(format!("'{}' does not exist in obs_names", i)))
+            .collect();
+        if let Some(obs) = self.obs.read().as_ref() {
+            return lookup(&obs.index);
+        }
+        lookup(&DataFrameIndex::from(self.n_obs()))
     }
 
     fn var_ix<'a, I: IntoIterator<Item = &'a str>>(&self, names: I) -> Result<Vec<usize>> {
-        let inner = self.var.inner();
-        names
+        let lookup = |index: &DataFrameIndex| names
             .into_iter()
-            .map(|i| {
-                inner
-                    .index
-                    .get_index(i)
-                    .context(format!("'{}' does not exist in obs_names", i))
-            })
-            .collect()
+            .map(|i| index.get_index(i).context(format!("'{}' does not exist in var_names", i)))
+            .collect();
+        if let Some(var) = self.var.read().as_ref() {
+            return lookup(&var.index);
+        }
+        lookup(&DataFrameIndex::from(self.n_vars()))
     }
 
     fn read_obs(&self) -> Result<DataFrame> {
@@ -584,6 +1362,7 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
             } else {
                 self.obs.inner().save(obs)?;
             }
+            self.mark_dirty()?;
         }
         Ok(())
     }
@@ -602,16 +1381,55 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
             } else {
                 self.var.inner().save(var)?;
             }
+            self.mark_dirty()?;
         }
         Ok(())
     }
 
+    fn set_obs_column(&self, column: Series) -> Result<()> {
+        if self.obs.is_empty() {
+            return self.set_obs(DataFrame::new(vec![column])?);
+        }
+        let name = column.name().to_string();
+        self.obs.inner().set_column(&name, column)?;
+        self.mark_dirty()
+    }
+
+    fn set_var_column(&self, column: Series) -> Result<()> {
+        if self.var.is_empty() {
+            return self.set_var(DataFrame::new(vec![column])?);
+        }
+        let name = column.name().to_string();
+        self.var.inner().set_column(&name, column)?;
+        self.mark_dirty()
+    }
+
     fn del_obs(&self) -> Result<()> {
-        self.get_obs().clear()
+        let dependents = self.dependents(true);
+        if !self.obs.is_empty() && !dependents.is_empty() {
+            warn!(
+                "deleting obs although {} depend on the number of observations, \
+                the observation names are replaced by a range index",
+                dependents.join(", "),
+            );
+        }
+        self.get_obs().clear()?;
+        self.release_dims();
+        self.mark_dirty()
     }
 
     fn del_var(&self) -> Result<()> {
-        self.get_var().clear()
+        let dependents = self.dependents(false);
+        if !self.var.is_empty() && !dependents.is_empty() {
+            warn!(
+                "deleting var although {} depend on the number of variables, \
+                the variable names are replaced by a range index",
+                dependents.join(", "),
+            );
+        }
+        self.get_var().clear()?;
+        self.release_dims();
+        self.mark_dirty()
     }
 
     fn uns(&self) -> Self::ElemCollectionRef<'_> {
@@ -675,21 +1493,111 @@ impl<B: Backend> AnnDataOp for AnnData<B> {
     }
 
     fn del_uns(&self) -> Result<()> {
-        self.uns.clear()
+        self.uns.clear()?;
+        self.mark_dirty()
     }
     fn del_obsm(&self) -> Result<()> {
-        self.obsm.clear()
+        self.obsm.clear()?;
+        self.release_dims();
+        self.mark_dirty()
     }
     fn del_obsp(&self) -> Result<()> {
-        self.obsp.clear()
+        self.obsp.clear()?;
+        self.release_dims();
+        self.mark_dirty()
     }
     fn del_varm(&self) -> Result<()> {
-        self.varm.clear()
+        self.varm.clear()?;
+        self.release_dims();
+        self.mark_dirty()
     }
     fn del_varp(&self) -> Result<()> {
-        self.varp.clear()
+        self.varp.clear()?;
+        self.release_dims();
+        self.mark_dirty()
     }
     fn del_layers(&self) -> Result<()> {
-        self.layers.clear()
+        self.layers.clear()?;
+        self.release_dims();
+        self.mark_dirty()
+    }
+}
+
+/// Call `write` with a temporary path next to `filename`, then move the result
+/// to `filename`. The temporary file is removed if writing fails, so an
+/// interrupted write never leaves a partial file at `filename`. If `overwrite`
+/// is false, an existing `filename` is never replaced.
+fn write_atomic<P, F>(filename: P, overwrite: bool, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let filename = filename.as_ref();
+    ensure!(overwrite || !filename.exists(), "file already exists: {}", filename.display());
+    let name = filename.file_name()
+        .with_context(|| format!("invalid file name: {}", filename.display()))?;
+    let dir = match filename.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let tmp = dir.join(format!(".{}.{:08x}.tmp", name.to_string_lossy(), rand::random::<u32>()));
+    let result = write(&tmp)
+        .and_then(|_| Ok(std::fs::File::open(&tmp)?.sync_all()?))
+        .and_then(|_| if overwrite { rename(&tmp, filename) } else { rename_no_clobber(&tmp, filename) });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
     }
-}
\ No newline at end of file
+    result
+}
+
+/// Rename `from` to `to`, replacing `to` if it exists. On Windows the rename
+/// fails while another process holds `to` open, so we retry a few times.
+fn rename(from: &Path, to: &Path) -> Result<()> {
+    let mut retries = if cfg!(windows) { 10 } else { 0 };
+    loop {
+        match std::fs::rename(from, to) {
+            Err(e) if retries > 0 && e.kind() == std::io::ErrorKind::PermissionDenied => {
+                retries -= 1;
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            r => return r.with_context(|| format!("cannot move file to {}", to.display())),
+        }
+    }
+}
+
+/// Move `from` to `to`, failing if `to` exists. Unlike a rename, creating a
+/// hard link never replaces an existing file, so `to` cannot be clobbered even
+/// if it is created after it was checked.
+fn rename_no_clobber(from: &Path, to: &Path) -> Result<()> {
+    match std::fs::hard_link(from, to) {
+        Ok(()) => Ok(std::fs::remove_file(from)?),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => bail!("file already exists: {}", to.display()),
+        Err(e) => Err(e).with_context(|| format!("cannot move file to {}", to.display())),
+    }
+}
+
+/// Split `0..n` into at most `n_chunks` non-empty contiguous ranges of
+/// roughly equal weight, where `cumsum[i]` is the total weight of the first
+/// `i` rows. Rows are weighted equally if `cumsum` is `None` or all zeros.
+fn balanced_ranges(cumsum: Option<&[usize]>, n: usize, n_chunks: usize) -> Vec<Range<usize>> {
+    let cumsum = match cumsum {
+        Some(x) if x.last().is_some_and(|&total| total > 0) => Cow::Borrowed(x),
+        _ => Cow::Owned((0..=n).collect()),
+    };
+    let total = cumsum[n] as u128;
+    let mut ranges = Vec::with_capacity(n_chunks);
+    let mut start = 0;
+    for k in 1..=n_chunks {
+        let end = if k == n_chunks {
+            n
+        } else {
+            let target = (total * k as u128 / n_chunks as u128) as usize;
+            cumsum.partition_point(|&x| x < target).min(n)
+        };
+        if end > start {
+            ranges.push(start..end);
+            start = end;
+        }
+    }
+    ranges
+}
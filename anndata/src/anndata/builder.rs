@@ -0,0 +1,275 @@
+use crate::{
+    anndata::{write_atomic, AnnData, CreateMode},
+    backend::{with_write_config, Backend, WriteConfig},
+    data::{ArrayData, Data, DataFrameIndex, HasShape},
+    traits::{AnnDataOp, AxisArraysOp, ElemCollectionOp},
+};
+
+use anyhow::{bail, ensure, Result};
+use indexmap::IndexMap;
+use polars::prelude::DataFrame;
+use std::path::PathBuf;
+
+/// Assemble a new AnnData file in one pass.
+///
+/// The elements are collected in memory, and [`AnnDataBuilder::build`]
+/// checks that their dimensions agree before the file is created, so that a
+/// mistake does not leave a partially written file behind. All datasets are
+/// then written with the same [`WriteConfig`].
+///
+/// # Example
+///
+/// ```no_run
+/// use anndata::{AnnData, AnnDataBuilder, Backend};
+/// use ndarray::Array2;
+///
+/// fn create<B: Backend>() -> anyhow::Result<AnnData<B>> {
+///     AnnDataBuilder::new("test.h5ad")
+///         .x(Array2::<f32>::zeros((100, 50)))
+///         .obsm("X_pca", Array2::<f32>::zeros((100, 10)))
+///         .uns("n_pcs", 10)
+///         .build()
+/// }
+/// ```
+pub struct AnnDataBuilder {
+    filename: PathBuf,
+    mode: CreateMode,
+    config: WriteConfig,
+    n_obs: Option<usize>,
+    n_vars: Option<usize>,
+    x: Option<ArrayData>,
+    obs: Option<DataFrame>,
+    obs_names: Option<DataFrameIndex>,
+    var: Option<DataFrame>,
+    var_names: Option<DataFrameIndex>,
+    obsm: IndexMap<String, ArrayData>,
+    obsp: IndexMap<String, ArrayData>,
+    varm: IndexMap<String, ArrayData>,
+    varp: IndexMap<String, ArrayData>,
+    layers: IndexMap<String, ArrayData>,
+    uns: IndexMap<String, Data>,
+}
+
+impl AnnDataBuilder {
+    /// Start a new file at `filename`. An existing file is truncated unless
+    /// another mode is set with [`AnnDataBuilder::mode`].
+    pub fn new<P: Into<PathBuf>>(filename: P) -> Self {
+        Self {
+            filename: filename.into(),
+            mode: CreateMode::Truncate,
+            config: WriteConfig::default(),
+            n_obs: None,
+            n_vars: None,
+            x: None,
+            obs: None,
+            obs_names: None,
+            var: None,
+            var_names: None,
+            obsm: IndexMap::new(),
+            obsp: IndexMap::new(),
+            varm: IndexMap::new(),
+            varp: IndexMap::new(),
+            layers: IndexMap::new(),
+            uns: IndexMap::new(),
+        }
+    }
+
+    /// How an existing file is handled. `CreateMode::Append` is not
+    /// supported, as the builder always creates a new file.
+    pub fn mode(mut self, mode: CreateMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The compression and block size of the datasets. A block size is only
+    /// used for datasets with the same number of dimensions.
+    pub fn write_config(mut self, config: WriteConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn n_obs(mut self, n: usize) -> Self {
+        self.n_obs = Some(n);
+        self
+    }
+
+    pub fn n_vars(mut self, n: usize) -> Self {
+        self.n_vars = Some(n);
+        self
+    }
+
+    pub fn x<D: Into<ArrayData>>(mut self, data: D) -> Self {
+        self.x = Some(data.into());
+        self
+    }
+
+    pub fn obs(mut self, obs: DataFrame) -> Self {
+        self.obs = Some(obs);
+        self
+    }
+
+    pub fn obs_names(mut self, names: DataFrameIndex) -> Self {
+        self.obs_names = Some(names);
+        self
+    }
+
+    pub fn var(mut self, var: DataFrame) -> Self {
+        self.var = Some(var);
+        self
+    }
+
+    pub fn var_names(mut self, names: DataFrameIndex) -> Self {
+        self.var_names = Some(names);
+        self
+    }
+
+    /// Add an element to obsm, replacing any element previously added with
+    /// the same key. The same applies to the other collections.
+    pub fn obsm<D: Into<ArrayData>>(mut self, key: &str, data: D) -> Self {
+        self.obsm.insert(key.to_string(), data.into());
+        self
+    }
+
+    pub fn obsp<D: Into<ArrayData>>(mut self, key: &str, data: D) -> Self {
+        self.obsp.insert(key.to_string(), data.into());
+        self
+    }
+
+    pub fn varm<D: Into<ArrayData>>(mut self, key: &str, data: D) -> Self {
+        self.varm.insert(key.to_string(), data.into());
+        self
+    }
+
+    pub fn varp<D: Into<ArrayData>>(mut self, key: &str, data: D) -> Self {
+        self.varp.insert(key.to_string(), data.into());
+        self
+    }
+
+    pub fn layer<D: Into<ArrayData>>(mut self, key: &str, data: D) -> Self {
+        self.layers.insert(key.to_string(), data.into());
+        self
+    }
+
+    pub fn uns<D: Into<Data>>(mut self, key: &str, data: D) -> Self {
+        self.uns.insert(key.to_string(), data.into());
+        self
+    }
+
+    /// Check the dimensions of the elements, then create the file and write
+    /// them. No file is created if the dimensions disagree, and the elements
+    /// are written to a temporary file that is moved to the destination only
+    /// once all of them have been written, so that a failed write does not
+    /// leave a partial file behind.
+    pub fn build<B: Backend>(self) -> Result<AnnData<B>> {
+        ensure!(
+            self.mode != CreateMode::Append,
+            "cannot append to '{}' with a builder", self.filename.display()
+        );
+        let (n_obs, n_vars) = self.check_dims()?;
+        let filename = self.filename.clone();
+        if self.mode == CreateMode::Exclusive && filename.exists() {
+            bail!("file '{}' already exists, use mode 'w' to truncate it", filename.display());
+        }
+        let overwrite = self.mode == CreateMode::Truncate;
+        with_write_config(self.config.clone(), || {
+            write_atomic(&filename, overwrite, |tmp| {
+                let adata = AnnData::<B>::new(tmp)?;
+                adata.batched(|adata| self.write_to(adata, n_obs, n_vars))?;
+                adata.close()
+            })?;
+            AnnData::open(B::open_rw(&filename)?)
+        })
+    }
+
+    /// Infer the number of observations and variables from the elements,
+    /// and check that all elements agree.
+    fn check_dims(&self) -> Result<(Option<usize>, Option<usize>)> {
+        if let Some(x) = &self.x {
+            ensure!(x.shape().ndim() >= 2, "X must be a N dimensional array, where N >= 2");
+        }
+        let mut obs = vec![];
+        let mut vars = vec![];
+        obs.extend(self.n_obs.map(|n| ("n_obs".to_string(), n)));
+        vars.extend(self.n_vars.map(|n| ("n_vars".to_string(), n)));
+        if let Some(x) = &self.x {
+            obs.push(("X".to_string(), x.shape()[0]));
+            vars.push(("X".to_string(), x.shape()[1]));
+        }
+        obs.extend(self.obs_names.as_ref().map(|x| ("obs_names".to_string(), x.len())));
+        vars.extend(self.var_names.as_ref().map(|x| ("var_names".to_string(), x.len())));
+        // Empty dataframes are skipped when written, see `AnnDataOp::set_obs`.
+        obs.extend(self.obs.as_ref().filter(|x| x.height() != 0).map(|x| ("obs".to_string(), x.height())));
+        vars.extend(self.var.as_ref().filter(|x| x.height() != 0).map(|x| ("var".to_string(), x.height())));
+        let rows = |slot: &str, elems: &IndexMap<String, ArrayData>| -> Vec<(String, usize)> {
+            elems.iter().map(|(k, v)| (format!("{}['{}']", slot, k), v.shape()[0])).collect()
+        };
+        let cols = |slot: &str, elems: &IndexMap<String, ArrayData>| -> Vec<(String, usize)> {
+            elems.iter().map(|(k, v)| (format!("{}['{}']", slot, k), v.shape()[1])).collect()
+        };
+        for (slot, elems) in [("obsm", &self.obsm), ("varm", &self.varm)] {
+            for (k, v) in elems {
+                ensure!(v.shape().ndim() >= 1, "{}['{}'] must have at least 1 dimension", slot, k);
+            }
+        }
+        for (slot, elems) in [("obsp", &self.obsp), ("varp", &self.varp)] {
+            for (k, v) in elems {
+                ensure!(v.shape().ndim() == 2, "{}['{}'] must be 2-dimensional", slot, k);
+            }
+        }
+        for (k, v) in &self.layers {
+            ensure!(v.shape().ndim() >= 2, "layers['{}'] must have at least 2 dimensions", k);
+        }
+        obs.extend(rows("obsm", &self.obsm));
+        obs.extend(rows("obsp", &self.obsp));
+        obs.extend(cols("obsp", &self.obsp));
+        obs.extend(rows("layers", &self.layers));
+        vars.extend(rows("varm", &self.varm));
+        vars.extend(rows("varp", &self.varp));
+        vars.extend(cols("varp", &self.varp));
+        vars.extend(cols("layers", &self.layers));
+        Ok((agreed_dim("observations", &obs)?, agreed_dim("variables", &vars)?))
+    }
+
+    fn write_to<B: Backend>(self, adata: &AnnData<B>, n_obs: Option<usize>, n_vars: Option<usize>) -> Result<()> {
+        n_obs.map(|n| adata.set_n_obs(n)).transpose()?;
+        n_vars.map(|n| adata.set_n_vars(n)).transpose()?;
+        self.x.map(|x| adata.set_x(x)).transpose()?;
+        self.obs_names.map(|x| adata.set_obs_names(x)).transpose()?;
+        self.obs.map(|x| adata.set_obs(x)).transpose()?;
+        self.var_names.map(|x| adata.set_var_names(x)).transpose()?;
+        self.var.map(|x| adata.set_var(x)).transpose()?;
+        for (k, v) in self.obsm {
+            adata.obsm().add(&k, v)?;
+        }
+        for (k, v) in self.obsp {
+            adata.obsp().add(&k, v)?;
+        }
+        for (k, v) in self.varm {
+            adata.varm().add(&k, v)?;
+        }
+        for (k, v) in self.varp {
+            adata.varp().add(&k, v)?;
+        }
+        for (k, v) in self.layers {
+            adata.layers().add(&k, v)?;
+        }
+        for (k, v) in self.uns {
+            adata.uns().add(&k, v)?;
+        }
+        Ok(())
+    }
+}
+
+/// Return the size given by all `sources`, or an error naming the first two
+/// sources that disagree.
+fn agreed_dim(axis: &str, sources: &[(String, usize)]) -> Result<Option<usize>> {
+    let Some((first, n)) = sources.first() else {
+        return Ok(None);
+    };
+    for (name, m) in &sources[1..] {
+        if m != n {
+            bail!("conflicting number of {}: {} has {} but {} has {}", axis, first, n, name, m);
+        }
+    }
+    Ok(Some(*n))
+}
@@ -0,0 +1,210 @@
+use crate::{
+    anndata::{write_atomic, AnnData},
+    backend::Backend,
+    data::{ArrayData, ArrayFormat, ArrayOp, DataFrameIndex, SelectInfoElem, WriteData},
+    traits::{AnnDataOp, ArrayElemOp},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use indexmap::IndexSet;
+use itertools::Itertools;
+use polars::prelude::{concat as concat_frames, DataFrame, DataType, IntoLazy, NamedFrom, Series, UnionArgs};
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How [`concat`] combines the variables of the objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Join {
+    /// Keep the variables present in all objects, "inner".
+    Inner,
+    /// Keep the variables present in any object, "outer".
+    Outer,
+}
+
+impl FromStr for Join {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "inner" => Ok(Join::Inner),
+            "outer" => Ok(Join::Outer),
+            _ => bail!("unknown join '{}', expecting 'inner' or 'outer'", s),
+        }
+    }
+}
+
+/// Concatenate AnnData objects along the observations and write the result to
+/// a new file.
+///
+/// Variables are matched by name and ordered as they first appear. With
+/// [`Join::Outer`], the entries of X for variables absent from an object are
+/// set to `fill_value`. A sparse X stays sparse if `fill_value` is 0, as the
+/// missing entries are structural zeros; other fill values convert it to a
+/// dense array and a warning is logged.
+///
+/// The obs columns present in all objects are kept, and the keys are stored in
+/// the obs column `add_key`. Other elements are not concatenated.
+pub fn concat<'a, O, A, T, S, P>(
+    data: T,
+    filename: P,
+    add_key: &str,
+    join: Join,
+    fill_value: f64,
+) -> Result<AnnData<O>>
+where
+    O: Backend,
+    A: AnnDataOp + 'a,
+    T: IntoIterator<Item = (S, &'a A)>,
+    S: ToString,
+    P: AsRef<Path>,
+{
+    let adatas: Vec<(String, &A)> = data.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+    ensure!(!adatas.is_empty(), "no objects to concatenate");
+
+    let names = adatas.iter().map(|(k, adata)| {
+        let names = adata.var_names().into_vec();
+        ensure!(
+            names.len() == adata.n_vars(),
+            "'{}' has {} variables but {} variable names", k, adata.n_vars(), names.len(),
+        );
+        if let Some(name) = names.iter().duplicates().next() {
+            bail!("'{}' has duplicate variable name '{}'", k, name);
+        }
+        Ok(names)
+    }).collect::<Result<Vec<_>>>()?;
+    let var_names: IndexSet<String> = match join {
+        Join::Inner => {
+            let others: Vec<HashSet<&String>> = names[1..].iter().map(|x| x.iter().collect()).collect();
+            names[0].iter().filter(|x| others.iter().all(|o| o.contains(x))).cloned().collect()
+        }
+        Join::Outer => names.iter().flatten().cloned().collect(),
+    };
+
+    let obs = concat_obs(&adatas, add_key)?;
+    let obs_names: DataFrameIndex = adatas.iter().flat_map(|(_, x)| x.obs_names().into_iter()).collect();
+    let n_obs = obs.height();
+    ensure!(
+        obs_names.len() == n_obs,
+        "found {} observation names for {} observations",
+        obs_names.len(),
+        n_obs,
+    );
+
+    // The result is written to a temporary file, which replaces `filename`
+    // only once it is complete.
+    let filename = filename.as_ref();
+    write_atomic(filename, true, |tmp| {
+        let adata = AnnData::<O>::new(tmp)?;
+        if !concat_x(&adata, &adatas, &names, &var_names, fill_value)? {
+            adata.set_n_obs(n_obs)?;
+            adata.set_n_vars(var_names.len())?;
+        }
+        adata.set_obs_names(obs_names)?;
+        adata.set_obs(obs)?;
+        adata.set_var_names(var_names.into_iter().collect())?;
+        adata.close()
+    })?;
+    AnnData::open(O::open_rw(filename)?)
+}
+
+/// Align the columns of X of each object to `var_names` and stack them into X
+/// of `out`, reading and writing the data in chunks of rows. X is dense if any
+/// of them is dense, otherwise it has the format of the first. Return whether
+/// X was written, i.e., whether the objects have X.
+fn concat_x<A: AnnDataOp, O: Backend>(
+    out: &AnnData<O>,
+    adatas: &[(String, &A)],
+    names: &[Vec<String>],
+    var_names: &IndexSet<String>,
+    fill_value: f64,
+) -> Result<bool> {
+    const CHUNK_SIZE: usize = 500;
+
+    let columns: Vec<Vec<Option<usize>>> = names.iter()
+        .map(|names| names.iter().map(|x| var_names.get_index_of(x)).collect())
+        .collect();
+    let expand = |key: &str, x: ArrayData, columns: &[Option<usize>]| {
+        x.expand_columns(columns, var_names.len(), fill_value)
+            .with_context(|| format!("cannot concatenate X of '{}'", key))
+    };
+
+    // The type and format are taken from the first row, so that mismatches are
+    // found before anything is written.
+    let mut heads = Vec::with_capacity(adatas.len());
+    for ((key, adata), columns) in adatas.iter().zip(&columns) {
+        let x = adata.x();
+        match x.shape() {
+            Some(shape) => {
+                let head = x.slice_axis::<ArrayData, _>(0, SelectInfoElem::from(0..shape[0].min(1)))?.unwrap();
+                heads.push((key, expand(key.as_str(), head, columns)?));
+            }
+            None if heads.is_empty() => {}
+            None => bail!("'{}' has no X", key),
+        }
+    }
+    if heads.is_empty() {
+        return Ok(false);
+    }
+    ensure!(heads.len() == adatas.len(), "'{}' has no X", adatas[0].0);
+
+    let (first, dtype) = (heads[0].0, heads[0].1.data_type());
+    if let Some((key, x)) = heads.iter().find(|(_, x)| x.data_type() != dtype) {
+        bail!("X of '{}' is {} but X of '{}' is {}", first, dtype, key, x.data_type());
+    }
+    let format = if heads.iter().any(|(_, x)| x.format() == Some(ArrayFormat::Dense)) {
+        ArrayFormat::Dense
+    } else {
+        heads[0].1.format().unwrap()
+    };
+
+    let expand = &expand;
+    let mut error = None;
+    let chunks = adatas.iter().zip(&columns).flat_map(|((key, adata), columns)| {
+        adata.x().iter::<ArrayData>(CHUNK_SIZE)
+            .map(move |(x, _, _)| expand(key.as_str(), x, columns).and_then(|x| x.into_format(format)))
+    }).map_while(|x| x.map_err(|e| error = Some(e)).ok());
+    let result = out.set_x_from_iter(chunks);
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result?;
+    Ok(true)
+}
+
+/// Stack the obs columns present in all objects and add the keys as a column.
+fn concat_obs<A: AnnDataOp>(adatas: &[(String, &A)], add_key: &str) -> Result<DataFrame> {
+    let frames = adatas.iter().map(|(_, x)| x.read_obs()).collect::<Result<Vec<_>>>()?;
+    let columns: Vec<String> = frames[0].get_column_names().into_iter()
+        .filter(|c| frames[1..].iter().all(|x| x.get_column_names().contains(c)))
+        .map(|c| c.to_string())
+        .collect();
+    let mut obs = if columns.is_empty() {
+        DataFrame::empty()
+    } else {
+        // Categorical columns with different categories cannot be stacked
+        // directly, so they are stacked as strings.
+        let categorical: Vec<&String> = columns.iter()
+            .filter(|c| matches!(frames[0].column(c).unwrap().dtype(), DataType::Categorical(..)))
+            .collect();
+        let frames = frames.iter().map(|df| {
+            let mut df = df.select(&columns)?;
+            for c in &categorical {
+                let col = df.column(c)?.cast(&DataType::String)?;
+                df.with_column(col)?;
+            }
+            Ok(df.lazy())
+        }).collect::<Result<Vec<_>>>()?;
+        let mut obs = concat_frames(&frames, UnionArgs { to_supertypes: true, ..Default::default() })?.collect()?;
+        for c in categorical {
+            let col = obs.column(c)?.cast(&DataType::Categorical(None, Default::default()))?;
+            obs.with_column(col)?;
+        }
+        obs
+    };
+    let keys: Vec<&str> = adatas.iter()
+        .flat_map(|(k, x)| std::iter::repeat(k.as_str()).take(x.n_obs()))
+        .collect();
+    obs.with_column(Series::new(add_key, keys))?;
+    Ok(obs)
+}
@@ -1,6 +1,6 @@
 use crate::{
     traits::{AnnDataOp, ElemCollectionOp},
-    anndata::AnnData,
+    anndata::{write_atomic, AnnData},
     backend::Backend,
     container::{Slot, Dim, Axis, AxisArrays, StackedArrayElem, StackedAxisArrays, StackedDataFrame, ElemCollection},
     data::*,
@@ -11,12 +11,15 @@ use anyhow::{anyhow, bail, ensure, Context, Result};
 use indexmap::map::IndexMap;
 use itertools::Itertools;
 use polars::prelude::{DataFrame, NamedFrom, Series};
-use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use rayon::iter::ParallelIterator;
 use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
 
 pub struct AnnDataSet<B: Backend> {
     annotation: AnnData<B>,
     anndatas: Slot<StackedAnnData<B>>,
+    /// The temporary annotation file, which is removed when the dataset is
+    /// closed or dropped.
+    temporary: Option<tempfile::TempPath>,
 }
 
 impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
@@ -40,7 +43,7 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
         if let Some(obs) = self
             .annotation
             .obs
-            .lock()
+            .read()
             .as_ref()
             .map(|x| x.get_column_names())
         {
@@ -51,7 +54,7 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
         if let Some(var) = self
             .annotation
             .var
-            .lock()
+            .read()
             .as_ref()
             .map(|x| x.get_column_names())
         {
@@ -62,7 +65,7 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
         if let Some(keys) = self
             .annotation
             .uns
-            .lock()
+            .read()
             .as_ref()
             .map(|x| x.keys().join("', '"))
         {
@@ -73,7 +76,7 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
         if let Some(keys) = self
             .annotation
             .obsm
-            .lock()
+            .read()
             .as_ref()
             .map(|x| x.keys().join("', '"))
         {
@@ -84,7 +87,7 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
         if let Some(keys) = self
             .annotation
             .obsp
-            .lock()
+            .read()
             .as_ref()
             .map(|x| x.keys().join("', '"))
         {
@@ -95,7 +98,7 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
         if let Some(keys) = self
             .annotation
             .varm
-            .lock()
+            .read()
             .as_ref()
             .map(|x| x.keys().join("', '"))
         {
@@ -106,7 +109,7 @@ impl<B: Backend> std::fmt::Display for AnnDataSet<B> {
         if let Some(keys) = self
             .annotation
             .varp
-            .lock()
+            .read()
             .as_ref()
             .map(|x| x.keys().join("', '"))
         {
@@ -136,6 +139,7 @@ impl<B: Backend> AnnDataSet<B> {
         let anndatas = StackedAnnData::new(data)?;
         let n_obs = anndatas.n_obs;
         let n_vars = anndatas.n_vars;
+        trace_span!("concat", n = anndatas.len(), file = %filename.as_ref().display());
 
         let mut annotation = AnnData::new(filename)?;
         annotation.n_obs = Dim::new(n_obs);
@@ -188,9 +192,33 @@ impl<B: Backend> AnnDataSet<B> {
         Ok(Self {
             annotation,
             anndatas: Slot::new(anndatas),
+            temporary: None,
         })
     }
 
+    /// Concatenate AnnData objects along the observations without writing a
+    /// combined file. Like [`AnnDataSet::new`], the result is a virtual stacked
+    /// view that reads from the component files; its annotation (keys and
+    /// obs/var names) is kept in a temporary file that is removed when the
+    /// dataset is closed. This is intended for read-only iteration over the
+    /// concatenated data, as changes to the annotation are not persisted.
+    pub fn concat_lazy<T, S>(data: T, add_key: &str) -> Result<Self>
+    where
+        T: IntoIterator<Item = (S, AnnData<B>)>,
+        S: ToString,
+    {
+        // The file is created with a unique name and removed when `filename`
+        // is dropped, including when the dataset cannot be created.
+        let filename = tempfile::Builder::new()
+            .prefix("anndataset.")
+            .suffix(".tmp")
+            .tempfile()?
+            .into_temp_path();
+        let mut dataset = Self::new(data, &filename, add_key)?;
+        dataset.temporary = Some(filename);
+        Ok(dataset)
+    }
+
     pub fn open<P: AsRef<Path>>(
         file: B::File,
         adata_files_update: Option<Result<HashMap<String, P>, P>>
@@ -223,6 +251,7 @@ impl<B: Backend> AnnDataSet<B> {
         Ok(Self {
             annotation,
             anndatas: Slot::new(StackedAnnData::new(anndatas.into_iter())?),
+            temporary: None,
         })
     }
 
@@ -249,45 +278,50 @@ impl<B: Backend> AnnDataSet<B> {
             self.anndatas.inner()
                 .write_select::<O, _, _>(&selection, &anndata_dir, ".h5ad")?;
 
-        if let Some(order) = obs_idx_order.as_ref() {
-            let idx = BoundedSelectInfoElem::new(&selection.as_ref()[0], self.n_obs()).to_vec();
-            let new_idx = order.iter().map(|i| idx[*i]).collect::<SelectInfoElem>();
-            self.annotation
-                .write_select::<O, _, _>([new_idx, selection.as_ref()[1].clone()], &file)?;
-        } else {
-            self.annotation.write_select::<O, _, _>(selection, &file)?;
-        };
-
-        let adata: AnnData<O> = AnnData::open(O::open_rw(&file)?)?;
-
         let parent_dir = if anndata_dir.is_absolute() {
             anndata_dir
         } else {
             Path::new("anndatas").to_path_buf()
         };
-
         let (keys, filenames): (Vec<_>, Vec<_>) = files
             .into_iter()
-            .map(|(k, v)| (k, parent_dir.join(v.as_str()).to_str().unwrap().to_string()))
+            .map(|(k, v)| (k, parent_dir.join(v.as_str()).to_string_lossy().into_owned()))
             .unzip();
         let file_loc = DataFrame::new(vec![
             Series::new("keys", keys),
             Series::new("file_path", filenames),
         ])?;
-        adata.uns().add("AnnDataSet", file_loc)?;
-        adata.close()?;
+
+        // The annotation is completed in a temporary file, which replaces
+        // `file` only once it has been written.
+        write_atomic(&file, true, |tmp| {
+            if let Some(order) = obs_idx_order.as_ref() {
+                let idx = BoundedSelectInfoElem::new(&selection.as_ref()[0], self.n_obs()).to_vec();
+                let new_idx = order.iter().map(|i| idx[*i]).collect::<SelectInfoElem>();
+                self.annotation
+                    .write_select::<O, _, _>([new_idx, selection.as_ref()[1].clone()], tmp)?;
+            } else {
+                self.annotation.write_select::<O, _, _>(&selection, tmp)?;
+            };
+            let adata: AnnData<O> = AnnData::open(O::open_rw(tmp)?)?;
+            adata.uns().add("AnnDataSet", file_loc)?;
+            adata.close()
+        })?;
         Ok(obs_idx_order)
     }
 
     /// Convert AnnDataSet to AnnData object
     pub fn to_adata<O: Backend, P: AsRef<Path>>(&self, out: P, copy_x: bool) -> Result<AnnData<O>> {
-        self.annotation.write::<O, _>(&out)?;
-        let adata = AnnData::open(O::open_rw(&out)?)?;
-        if copy_x {
-            adata
-                .set_x_from_iter::<_, ArrayData>(self.anndatas.inner().x.chunked(500).map(|x| x.0))?;
-        }
-        Ok(adata)
+        write_atomic(out.as_ref(), true, |tmp| {
+            self.annotation.write::<O, _>(tmp)?;
+            let adata = AnnData::<O>::open(O::open_rw(tmp)?)?;
+            let x = &self.anndatas.inner().x;
+            if copy_x && !x.is_empty() {
+                adata.set_x_from_iter::<_, ArrayData>(x.chunked(500).map(|x| x.0))?;
+            }
+            adata.close()
+        })?;
+        AnnData::open(O::open_rw(&out)?)
     }
 
     pub fn to_adata_select<O, P, S>(&self, select: S, out: P, copy_x: bool) -> Result<AnnData<O>>
@@ -299,29 +333,44 @@ impl<B: Backend> AnnDataSet<B> {
         self.annotation.write_select::<O, _, _>(&select, &out)?;
         let adata = AnnData::open(O::open_rw(&out)?)?;
         if copy_x {
-            let x: ArrayData = self.anndatas.inner().x.select(select.as_ref())?.unwrap();
-            adata.set_x(x)?;
+            if let Some(x) = self.anndatas.inner().x.select::<ArrayData, _>(select.as_ref())? {
+                adata.set_x(x)?;
+            }
         }
         Ok(adata)
     }
 
     /// Convert AnnDataSet to AnnData object
     pub fn into_adata(self, copy_x: bool) -> Result<AnnData<B>> {
-        if copy_x {
-            self.annotation
-                .set_x_from_iter::<_, ArrayData>(self.anndatas.inner().x.chunked(500).map(|x| x.0))?;
+        let x = self.anndatas.inner().x.clone();
+        if copy_x && !x.is_empty() {
+            self.annotation.set_x_from_iter::<_, ArrayData>(x.chunked(500).map(|x| x.0))?;
         }
         for ann in self.anndatas.extract().unwrap().elems.into_values() {
             ann.close()?;
         }
+        // The returned object is backed by the annotation file, which must
+        // therefore outlive the dataset.
+        if let Some(filename) = self.temporary {
+            filename.keep()?;
+        }
         Ok(self.annotation)
     }
 
+    /// Flush the buffered data of the dataset and its components to disk.
+    pub fn flush(&self) -> Result<()> {
+        self.annotation.flush()?;
+        self.anndatas.inner().elems.values().try_for_each(|x| x.flush())
+    }
+
     pub fn close(self) -> Result<()> {
         self.annotation.close()?;
         for ann in self.anndatas.extract().unwrap().elems.into_values() {
             ann.close()?;
         }
+        if let Some(filename) = self.temporary {
+            filename.close()?;
+        }
         Ok(())
     }
 }
@@ -459,6 +508,12 @@ impl<B: Backend> AnnDataOp for AnnDataSet<B> {
     fn set_var(&self, var: DataFrame) -> Result<()> {
         self.annotation.set_var(var)
     }
+    fn set_obs_column(&self, column: Series) -> Result<()> {
+        self.annotation.set_obs_column(column)
+    }
+    fn set_var_column(&self, column: Series) -> Result<()> {
+        self.annotation.set_var_column(column)
+    }
     fn del_obs(&self) -> Result<()> {
         self.annotation.del_obs()
     }
@@ -554,19 +609,33 @@ impl<B: Backend> StackedAnnData<B> {
             iter.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
         ensure!(!adatas.is_empty(), "no AnnData objects to stack");
 
-        if let Some((_, first)) = adatas.first() {
-            let lock = first.var.lock();
+        // The variables of each object must be those of the first, possibly in
+        // a different order, in which case the columns of X are reordered.
+        let column_maps = {
+            let lock = adatas[0].var.read();
             let var_names = lock.as_ref().map(|x| &x.index);
-            if !adatas
-                .par_values()
-                .skip(1)
-                .all(|x| x.var.lock().as_ref().map(|x| &x.index).eq(&var_names))
-            {
-                bail!("var names mismatch");
-            }
-        }
+            adatas.par_values().map(|x| {
+                let lock = x.var.read();
+                let names = lock.as_ref().map(|x| &x.index);
+                match (var_names, names) {
+                    (Some(first), Some(names)) if first != names => {
+                        let map: Option<Vec<usize>> = first.clone().into_vec().iter()
+                            .map(|x| names.get_index(x))
+                            .collect();
+                        map.filter(|_| first.len() == names.len())
+                            .map(Some)
+                            .ok_or_else(|| anyhow!("var names mismatch"))
+                    }
+                    (first, names) if first == names => Ok(None),
+                    _ => bail!("var names mismatch"),
+                }
+            }).collect::<Result<Vec<_>>>()?
+        };
 
-        let x = StackedArrayElem::new(adatas.values().map(|x| x.get_x().clone()).collect())?;
+        let x = StackedArrayElem::with_column_maps(
+            adatas.values().map(|x| x.get_x().clone()).collect(),
+            column_maps,
+        )?;
 
         let obs = if adatas.values().any(|x| x.obs.is_empty()) {
             StackedDataFrame::new(Vec::new())
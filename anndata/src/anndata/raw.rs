@@ -0,0 +1,109 @@
+use crate::{
+    backend::{Backend, DataContainer, FileOp, GroupOp},
+    container::{ArrayElem, DataFrameElem, Dim, Slot},
+    data::SelectInfoElem,
+};
+
+use anyhow::{ensure, Context, Result};
+use log::warn;
+
+/// The `raw` group of a file, holding a copy of X and var made before the
+/// variables were filtered. Raw has the same observations as the object it
+/// belongs to, but its own variables.
+pub struct Raw<B: Backend> {
+    x: ArrayElem<B>,
+    var: DataFrameElem<B>,
+}
+
+impl<B: Backend> Raw<B> {
+    pub fn x(&self) -> &ArrayElem<B> {
+        &self.x
+    }
+
+    pub fn var(&self) -> &DataFrameElem<B> {
+        &self.var
+    }
+
+    pub fn n_vars(&self) -> usize {
+        self.var.inner().height()
+    }
+
+    pub(crate) fn subset_obs(&self, selection: &SelectInfoElem) -> Result<()> {
+        let full = SelectInfoElem::full();
+        self.x.lock().as_mut().map(|x| x.subset(&[selection, &full])).transpose()?;
+        Ok(())
+    }
+}
+
+/// Open the `raw` group of the file. A malformed group is skipped with a
+/// warning, so that the rest of the file can still be read; it is left in
+/// the file untouched.
+pub(crate) fn open_raw<B: Backend>(file: &B::File, n_obs: &Dim) -> Result<Slot<Raw<B>>> {
+    if !file.exists("raw")? {
+        return Ok(Slot::empty());
+    }
+    match read_raw(file, n_obs) {
+        Ok(raw) => Ok(Slot::new(raw)),
+        Err(e) => {
+            warn!("skipping the malformed raw group in '{}': {:#}", file.filename().display(), e);
+            Ok(Slot::empty())
+        }
+    }
+}
+
+fn read_raw<B: Backend>(file: &B::File, n_obs: &Dim) -> Result<Raw<B>> {
+    let group = file.open_group("raw")?;
+    ensure!(group.exists("X")?, "missing X");
+    ensure!(group.exists("var")?, "missing var");
+    let x = ArrayElem::try_from(DataContainer::open(&group, "X")?).context("cannot read X")?;
+    let var = DataFrameElem::try_from(DataContainer::open(&group, "var")?).context("cannot read var")?;
+    let shape = x.inner().shape().clone();
+    ensure!(shape.ndim() == 2, "X must be 2-dimensional, found {} dimensions", shape.ndim());
+    let n_obs = n_obs.lock();
+    ensure!(
+        n_obs.is_empty() || n_obs.get() == shape[0],
+        "X has {} rows but the object has {} observations",
+        shape[0],
+        n_obs.get(),
+    );
+    ensure!(
+        var.inner().height() == shape[1],
+        "X has {} columns but var has {} rows",
+        shape[1],
+        var.inner().height(),
+    );
+    Ok(Raw { x, var })
+}
+
+/// Write the `raw` group of `source`, if any, to `file`. If `obs` selects all
+/// observations the group is copied verbatim, otherwise the selected rows of
+/// X are written and the other members of the group are copied. A malformed
+/// group cannot be subset and is skipped with a warning in that case.
+pub(crate) fn export_raw<B: Backend, O: Backend>(
+    source: &B::File,
+    raw: &Slot<Raw<B>>,
+    obs: &SelectInfoElem,
+    file: &O::File,
+) -> Result<()> {
+    if !source.exists("raw")? {
+        return Ok(());
+    }
+    let filename = source.filename();
+    if obs.is_full() {
+        return file.copy_from(&filename, "raw", "raw");
+    }
+    let raw = raw.read();
+    let Some(raw) = raw.as_ref() else {
+        warn!("the malformed raw group in '{}' is not written to the subset", filename.display());
+        return Ok(());
+    };
+    let group = file.create_group("raw")?;
+    let full = SelectInfoElem::full();
+    raw.x.lock().as_mut().map(|x| x.export_select::<O, _>(&[obs, &full], &group, "X")).transpose()?;
+    for name in source.open_group("raw")?.list()? {
+        if name != "X" {
+            group.copy_from(&filename, &format!("raw/{}", name), &name)?;
+        }
+    }
+    Ok(())
+}
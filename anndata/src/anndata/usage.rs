@@ -0,0 +1,202 @@
+use crate::backend::{iter_containers, Backend, DataContainer, DatasetOp, GroupOp, ScalarType};
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use std::cmp::Reverse;
+use std::fmt::{Display, Formatter};
+
+/// Slots whose children are reported as separate elements.
+const COLLECTIONS: [&str; 8] = ["obs", "var", "obsm", "obsp", "varm", "varp", "layers", "uns"];
+
+/// The storage usage of an element, e.g., `obsm/X_pca`. Sizes are in bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElemUsage {
+    /// The top-level slot containing the element, e.g., "obsm".
+    pub slot: String,
+    /// The path of the element in the file, e.g., "obsm/X_pca".
+    pub path: String,
+    /// The encoding type, e.g., "csr_matrix".
+    pub encoding: String,
+    /// The type of the values, i.e., of the element itself if it is a dataset,
+    /// or of its "data" or "values" dataset otherwise.
+    pub dtype: Option<ScalarType>,
+    /// The number of bytes occupied in the file.
+    pub storage_size: u64,
+    /// The number of bytes of the uncompressed data. Strings are counted by
+    /// their storage size, as their length is unknown without reading them.
+    pub logical_size: u64,
+}
+
+impl ElemUsage {
+    /// The logical size divided by the storage size.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        ratio(self.logical_size, self.storage_size)
+    }
+}
+
+/// The storage usage of a slot, i.e., the sum over its elements.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlotUsage {
+    pub n_elems: usize,
+    pub storage_size: u64,
+    pub logical_size: u64,
+}
+
+impl SlotUsage {
+    /// The logical size divided by the storage size.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        ratio(self.logical_size, self.storage_size)
+    }
+}
+
+/// The storage usage of the elements in a file, see [`crate::AnnData::usage`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageReport {
+    pub elements: Vec<ElemUsage>,
+}
+
+impl UsageReport {
+    /// Aggregate the usage by slot, in the order the slots appear in the file.
+    pub fn by_slot(&self) -> IndexMap<String, SlotUsage> {
+        let mut slots: IndexMap<String, SlotUsage> = IndexMap::new();
+        for elem in &self.elements {
+            let slot = slots.entry(elem.slot.clone()).or_default();
+            slot.n_elems += 1;
+            slot.storage_size += elem.storage_size;
+            slot.logical_size += elem.logical_size;
+        }
+        slots
+    }
+
+    /// The number of bytes occupied by all elements in the file.
+    pub fn storage_size(&self) -> u64 {
+        self.elements.iter().map(|x| x.storage_size).sum()
+    }
+
+    /// The number of bytes of all elements when uncompressed.
+    pub fn logical_size(&self) -> u64 {
+        self.elements.iter().map(|x| x.logical_size).sum()
+    }
+}
+
+impl Display for UsageReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let show_ratio = |x: Option<f64>| x.map_or("-".to_string(), |r| format!("{:.2}", r));
+        writeln!(f, "{:<40} {:>12} {:>12} {:>8}", "element", "storage", "logical", "ratio")?;
+        let mut slots = self.by_slot().into_iter().collect::<Vec<_>>();
+        slots.sort_by_key(|x| Reverse(x.1.storage_size));
+        for (name, slot) in slots {
+            writeln!(
+                f, "{:<40} {:>12} {:>12} {:>8}",
+                name, format_size(slot.storage_size), format_size(slot.logical_size),
+                show_ratio(slot.compression_ratio()),
+            )?;
+            let mut elems = self.elements.iter()
+                .filter(|x| x.slot == name && x.path != name)
+                .collect::<Vec<_>>();
+            elems.sort_by_key(|x| Reverse(x.storage_size));
+            for elem in elems {
+                writeln!(
+                    f, "  {:<38} {:>12} {:>12} {:>8}",
+                    elem.path, format_size(elem.storage_size), format_size(elem.logical_size),
+                    show_ratio(elem.compression_ratio()),
+                )?;
+            }
+        }
+        write!(
+            f, "{:<40} {:>12} {:>12} {:>8}",
+            "total", format_size(self.storage_size()), format_size(self.logical_size()),
+            show_ratio(ratio(self.logical_size(), self.storage_size())),
+        )
+    }
+}
+
+/// Collect the usage of the elements under the root of a file. Only the
+/// metadata of the datasets is read.
+pub(crate) fn usage<B: Backend>(file: &B::File) -> Result<UsageReport> {
+    let mut elements = Vec::new();
+    for slot in file.list()? {
+        let container = DataContainer::<B>::open(file, &slot)?;
+        match container {
+            DataContainer::Group(group) if COLLECTIONS.contains(&slot.as_str()) => {
+                for (name, child) in iter_containers::<B>(&group) {
+                    let path = format!("{}/{}", slot, name);
+                    elements.push(elem_usage(&slot, path, &child)?);
+                }
+            }
+            _ => elements.push(elem_usage(&slot, slot.clone(), &container)?),
+        }
+    }
+    Ok(UsageReport { elements })
+}
+
+fn elem_usage<B: Backend>(slot: &str, path: String, container: &DataContainer<B>) -> Result<ElemUsage> {
+    let dtype = match container {
+        DataContainer::Dataset(dataset) => dataset.dtype().ok(),
+        DataContainer::Group(group) => ["data", "values"].into_iter()
+            .find_map(|name| group.open_dataset(name).ok())
+            .and_then(|dataset| dataset.dtype().ok()),
+    };
+    let (storage_size, logical_size) = sizes(container)?;
+    Ok(ElemUsage {
+        slot: slot.to_string(),
+        path,
+        encoding: container.encoding_name(),
+        dtype,
+        storage_size,
+        logical_size,
+    })
+}
+
+/// Return the storage and logical sizes of all datasets in the container.
+pub(crate) fn sizes<B: Backend>(container: &DataContainer<B>) -> Result<(u64, u64)> {
+    match container {
+        DataContainer::Dataset(dataset) => {
+            let storage = dataset.storage_size()?;
+            let logical = match dataset.dtype().ok().and_then(item_size) {
+                Some(n) => (dataset.shape().as_ref().iter().product::<usize>() * n) as u64,
+                None => storage,
+            };
+            Ok((storage, logical))
+        }
+        DataContainer::Group(group) => iter_containers::<B>(group).try_fold((0, 0), |acc, (_, x)| {
+            let (storage, logical) = sizes(&x)?;
+            Ok((acc.0 + storage, acc.1 + logical))
+        }),
+    }
+}
+
+/// The number of bytes of a value, or None for variable-length strings.
+pub(crate) fn item_size(ty: ScalarType) -> Option<usize> {
+    match ty {
+        ScalarType::I8 | ScalarType::U8 | ScalarType::Bool => Some(1),
+        ScalarType::I16 | ScalarType::U16 | ScalarType::F16 => Some(2),
+        ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => Some(4),
+        ScalarType::I64 | ScalarType::U64 | ScalarType::F64 => Some(8),
+        ScalarType::Usize => Some(std::mem::size_of::<usize>()),
+        ScalarType::String => None,
+    }
+}
+
+fn ratio(logical: u64, storage: u64) -> Option<f64> {
+    if storage == 0 {
+        None
+    } else {
+        Some(logical as f64 / storage as f64)
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
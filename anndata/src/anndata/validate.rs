@@ -0,0 +1,363 @@
+use crate::backend::{Backend, DataContainer, DatasetOp, GroupOp, LocationOp};
+use crate::data::dataframe::read_index_names;
+
+use anyhow::Result;
+use ndarray::Ix1;
+use std::fmt::{Display, Formatter};
+
+/// A part of a file that does not comply with the AnnData on-disk
+/// specification, see [`crate::AnnData::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// The path of the offending element in the file, e.g., "obsm/X_pca".
+    pub path: String,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// The expected size of each dimension of an element, `None` if any size is
+/// allowed.
+type Dims = [Option<usize>; 2];
+
+/// Walk the elements under the root of a file and collect the violations.
+/// Only the metadata of the datasets is read.
+pub(crate) fn validate<B: Backend>(file: &B::File, n_obs: usize, n_vars: usize) -> Result<Vec<Violation>> {
+    let mut validator = Validator::<B>::default();
+    for slot in file.list()? {
+        let container = match validator.open(file, &slot, &slot) {
+            Some(x) => x,
+            None => continue,
+        };
+        let dims = match slot.as_str() {
+            "obsm" => [Some(n_obs), None],
+            "varm" => [Some(n_vars), None],
+            "obsp" => [Some(n_obs), Some(n_obs)],
+            "varp" => [Some(n_vars), Some(n_vars)],
+            "layers" => [Some(n_obs), Some(n_vars)],
+            "uns" => [None, None],
+            "raw" => {
+                validator.check_raw(&container, n_obs);
+                continue;
+            }
+            _ => {
+                let dims = match slot.as_str() {
+                    "X" => [Some(n_obs), Some(n_vars)],
+                    "obs" => [Some(n_obs), None],
+                    "var" => [Some(n_vars), None],
+                    _ => [None, None],
+                };
+                let shape = validator.check_elem(&slot, &container);
+                validator.check_dims(&slot, shape, dims);
+                continue;
+            }
+        };
+        // The slots themselves are not required to carry encoding attributes.
+        match container {
+            DataContainer::Group(group) => {
+                for name in group.list()? {
+                    let path = format!("{}/{}", slot, name);
+                    if let Some(child) = validator.open(&group, &name, &path) {
+                        let shape = validator.check_elem(&path, &child);
+                        validator.check_dims(&path, shape, dims);
+                    }
+                }
+            }
+            DataContainer::Dataset(_) => validator.push(&slot, "expected a group"),
+        }
+    }
+    Ok(validator.violations)
+}
+
+struct Validator<B> {
+    violations: Vec<Violation>,
+    backend: std::marker::PhantomData<B>,
+}
+
+impl<B> Default for Validator<B> {
+    fn default() -> Self {
+        Self { violations: Vec::new(), backend: std::marker::PhantomData }
+    }
+}
+
+impl<B: Backend> Validator<B> {
+    fn push<S: Into<String>>(&mut self, path: &str, message: S) {
+        self.violations.push(Violation { path: path.to_string(), message: message.into() });
+    }
+
+    fn open<G: GroupOp<Backend = B>>(&mut self, group: &G, name: &str, path: &str) -> Option<DataContainer<B>> {
+        match DataContainer::open(group, name) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                self.push(path, format!("{:#}", e));
+                None
+            }
+        }
+    }
+
+    /// Check the element and return its shape if it is an array.
+    fn check_elem(&mut self, path: &str, container: &DataContainer<B>) -> Option<Vec<usize>> {
+        // The specification of unsupported encodings is unknown.
+        if let Some(encoding) = container.unsupported_encoding() {
+            self.push(path, format!("unsupported encoding '{}'", encoding));
+            return None;
+        }
+        self.check_encoding(path, container);
+        let encoding = container.encoding_name();
+        match encoding.as_str() {
+            "array" | "string-array" => self.dataset(path, container).map(|x| x.shape().as_ref().to_vec()),
+            "string" | "numeric-scalar" => {
+                if let Some(dataset) = self.dataset(path, container) {
+                    if dataset.shape().ndim() != 0 {
+                        self.push(path, format!("the {} encoding requires a 0-dimensional dataset", encoding));
+                    }
+                }
+                None
+            }
+            "categorical" => {
+                let group = self.group(path, container)?;
+                if group.attr_dtype("ordered").ok().flatten().is_none() {
+                    self.push(path, "missing attribute 'ordered'");
+                }
+                self.child_len(path, group, "categories");
+                self.child_len(path, group, "codes").map(|n| vec![n])
+            }
+            "csr_matrix" | "csc_matrix" => {
+                let group = self.group(path, container)?;
+                let shape = self.shape_attr(path, group);
+                let indptr = self.child_len(path, group, "indptr");
+                let indices = self.child_len(path, group, "indices");
+                let data = self.child_len(path, group, "data");
+                let major = if encoding == "csr_matrix" { 0 } else { 1 };
+                if let (Some(shape), Some(indptr)) = (&shape, indptr) {
+                    if indptr != shape[major] + 1 {
+                        self.push(path, format!(
+                            "'indptr' has length {}, expected {} for shape {:?}", indptr, shape[major] + 1, shape,
+                        ));
+                    }
+                }
+                self.check_same_len(path, &[("indices", indices), ("data", data)]);
+                shape
+            }
+            "coo_matrix" => {
+                let group = self.group(path, container)?;
+                let shape = self.shape_attr(path, group);
+                let lens = ["row", "col", "data"].map(|name| (name, self.child_len(path, group, name)));
+                self.check_same_len(path, &lens);
+                shape
+            }
+            "dataframe" => self.check_dataframe(path, container),
+            "mapping" | "dict" => {
+                let group = self.group(path, container)?;
+                for name in group.list().unwrap_or_default() {
+                    let child_path = format!("{}/{}", path, name);
+                    if let Some(child) = self.open(group, &name, &child_path) {
+                        self.check_elem(&child_path, &child);
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Check the raw group: X must have the observations of the object, and
+    /// var and the elements of varm the variables of X.
+    fn check_raw(&mut self, container: &DataContainer<B>, n_obs: usize) {
+        let group = match container.as_group() {
+            Ok(x) => x,
+            Err(_) => return self.push("raw", "expected a group"),
+        };
+        let mut n_vars = None;
+        for name in ["X", "var"] {
+            let path = format!("raw/{}", name);
+            if !group.exists(name).unwrap_or(false) {
+                self.push("raw", format!("missing '{}'", name));
+            } else if let Some(child) = self.open(group, name, &path) {
+                let shape = self.check_elem(&path, &child);
+                if name == "X" {
+                    n_vars = shape.as_ref().and_then(|x| x.get(1).copied());
+                    self.check_dims(&path, shape, [Some(n_obs), None]);
+                } else {
+                    self.check_dims(&path, shape, [n_vars, None]);
+                }
+            }
+        }
+        if !group.exists("varm").unwrap_or(false) {
+            return;
+        }
+        if let Some(DataContainer::Group(varm)) = self.open(group, "varm", "raw/varm") {
+            for name in varm.list().unwrap_or_default() {
+                let path = format!("raw/varm/{}", name);
+                if let Some(child) = self.open(&varm, &name, &path) {
+                    let shape = self.check_elem(&path, &child);
+                    self.check_dims(&path, shape, [n_vars, None]);
+                }
+            }
+        }
+    }
+
+    /// Check a dataframe column, which may also be a nullable array.
+    fn check_column(&mut self, path: &str, container: &DataContainer<B>) -> Option<Vec<usize>> {
+        if !container.encoding_name().starts_with("nullable-") {
+            return self.check_elem(path, container);
+        }
+        self.check_encoding(path, container);
+        let group = self.group(path, container)?;
+        let values = self.child_len(path, group, "values");
+        let mask = self.child_len(path, group, "mask");
+        self.check_same_len(path, &[("values", values), ("mask", mask)]);
+        values.map(|n| vec![n])
+    }
+
+    fn check_dataframe(&mut self, path: &str, container: &DataContainer<B>) -> Option<Vec<usize>> {
+        let group = self.group(path, container)?;
+        let columns = match group.attr_dtype("column-order").ok().flatten() {
+            // An empty array of floats is written by some tools.
+            Some(_) => group.read_array_attr::<String, Ix1>("column-order")
+                .map(|x| x.into_raw_vec())
+                .unwrap_or_default(),
+            None => {
+                self.push(path, "missing attribute 'column-order'");
+                Vec::new()
+            }
+        };
+        let n_rows = match read_index_names(container) {
+            Ok(index) => {
+                if index.len() > 1 {
+                    self.push(path, format!("the index has {} levels, which other tools cannot read", index.len()));
+                }
+                let lens: Vec<_> = index.iter()
+                    .map(|name| {
+                        let level_path = format!("{}/{}", path, name);
+                        let level = self.open(group, name, &level_path)?;
+                        let shape = self.check_elem(&level_path, &level);
+                        self.first_dim(&level_path, shape)
+                    })
+                    .collect();
+                let named: Vec<_> = index.iter().map(String::as_str).zip(lens.iter().copied()).collect();
+                self.check_same_len(path, &named);
+                lens[0]
+            }
+            Err(e) => {
+                self.push(path, e.to_string());
+                None
+            }
+        };
+        for name in &columns {
+            let column_path = format!("{}/{}", path, name);
+            let column = match self.open(group, name, &column_path) {
+                Some(x) => x,
+                None => continue,
+            };
+            let shape = self.check_column(&column_path, &column);
+            let len = self.first_dim(&column_path, shape);
+            if let (Some(len), Some(n)) = (len, n_rows) {
+                if len != n {
+                    self.push(&column_path, format!("column has length {}, expected {}", len, n));
+                }
+            }
+        }
+        n_rows.map(|n| vec![n, columns.len()])
+    }
+
+    /// The length of a one-dimensional element such as an index level or a
+    /// column. A scalar has no length and is reported.
+    fn first_dim(&mut self, path: &str, shape: Option<Vec<usize>>) -> Option<usize> {
+        let len = shape.as_ref().and_then(|x| x.first().copied());
+        if shape.is_some() && len.is_none() {
+            self.push(path, "expected a 1-dimensional array, found a scalar");
+        }
+        len
+    }
+
+    fn check_encoding(&mut self, path: &str, container: &DataContainer<B>) {
+        match container.attr_dtype("encoding-type").ok().flatten() {
+            None => self.push(path, "missing attribute 'encoding-type'"),
+            Some(_) if container.attr_dtype("encoding-version").ok().flatten().is_none() => {
+                self.push(path, "missing attribute 'encoding-version'")
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn check_dims(&mut self, path: &str, shape: Option<Vec<usize>>, dims: Dims) {
+        let shape = match shape {
+            Some(x) => x,
+            None => return,
+        };
+        for (axis, expected) in dims.into_iter().enumerate() {
+            match (shape.get(axis), expected) {
+                (Some(n), Some(expected)) if *n != expected => self.push(path, format!(
+                    "dimension {} has size {}, expected {}", axis, n, expected,
+                )),
+                (None, Some(_)) => self.push(path, format!("expected at least {} dimensions", axis + 1)),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_same_len(&mut self, path: &str, lens: &[(&str, Option<usize>)]) {
+        if let [(first, Some(n)), rest @ ..] = lens {
+            for (name, len) in rest {
+                if let Some(len) = len {
+                    if len != n {
+                        self.push(path, format!("'{}' has length {}, but '{}' has length {}", name, len, first, n));
+                    }
+                }
+            }
+        }
+    }
+
+    fn shape_attr(&mut self, path: &str, group: &B::Group) -> Option<Vec<usize>> {
+        match group.read_array_attr::<usize, Ix1>("shape") {
+            Ok(shape) if shape.len() == 2 => Some(shape.to_vec()),
+            Ok(_) => {
+                self.push(path, "attribute 'shape' must have two elements");
+                None
+            }
+            Err(_) => {
+                self.push(path, "missing attribute 'shape'");
+                None
+            }
+        }
+    }
+
+    /// Return the length of the 1-dimensional dataset `name` in the group.
+    fn child_len(&mut self, path: &str, group: &B::Group, name: &str) -> Option<usize> {
+        match group.open_dataset(name) {
+            Ok(dataset) => match dataset.shape().as_ref() {
+                [n] => Some(*n),
+                _ => {
+                    self.push(path, format!("'{}' must be 1-dimensional", name));
+                    None
+                }
+            },
+            Err(_) => {
+                self.push(path, format!("missing dataset '{}'", name));
+                None
+            }
+        }
+    }
+
+    fn group<'a>(&mut self, path: &str, container: &'a DataContainer<B>) -> Option<&'a B::Group> {
+        let group = container.as_group().ok();
+        if group.is_none() {
+            self.push(path, format!("the {} encoding requires a group", container.encoding_name()));
+        }
+        group
+    }
+
+    fn dataset<'a>(&mut self, path: &str, container: &'a DataContainer<B>) -> Option<&'a B::Dataset> {
+        let dataset = container.as_dataset().ok();
+        if dataset.is_none() {
+            self.push(path, format!("the {} encoding requires a dataset", container.encoding_name()));
+        }
+        dataset
+    }
+}
@@ -0,0 +1,128 @@
+use super::AnnData;
+use crate::{
+    backend::Backend,
+    data::{DataFrameIndex, SelectInfoElem},
+    traits::{AnnDataOp, ArrayElemOp},
+    ArrayData,
+};
+
+use anyhow::{anyhow, ensure, Result};
+use polars::prelude::DataFrame;
+use std::path::Path;
+
+/// A subset of an [`AnnData`] object, given by selections of observations and
+/// variables. Creating a view does not read or copy any data; the selections
+/// are applied when elements are read, and reads of a view of a view go
+/// through the composed selection. Unlike [`AnnData::subset`], the underlying
+/// object is left unchanged. A view is invalidated by changes of the
+/// positions of the underlying object, see [`AnnData::generation`], after which
+/// its methods return an error.
+pub struct AnnDataView<'a, B: Backend> {
+    adata: &'a AnnData<B>,
+    obs: SelectInfoElem,
+    var: SelectInfoElem,
+    generation: u64,
+}
+
+impl<B: Backend> AnnData<B> {
+    /// Return a view of the observations and variables in `obs` and `var`.
+    pub fn view<O, V>(&self, obs: O, var: V) -> Result<AnnDataView<'_, B>>
+    where
+        O: Into<SelectInfoElem>,
+        V: Into<SelectInfoElem>,
+    {
+        AnnDataView {
+            adata: self,
+            obs: SelectInfoElem::full(),
+            var: SelectInfoElem::full(),
+            generation: self.generation(),
+        }
+        .view(obs, var)
+    }
+}
+
+impl<'a, B: Backend> AnnDataView<'a, B> {
+    /// Select from the observations and variables of this view. The positions
+    /// in `obs` and `var` are relative to the view, not to the underlying
+    /// object.
+    pub fn view<O, V>(&self, obs: O, var: V) -> Result<Self>
+    where
+        O: Into<SelectInfoElem>,
+        V: Into<SelectInfoElem>,
+    {
+        self.check()?;
+        let obs = self.obs.compose(self.adata.n_obs(), &obs.into())
+            .map_err(|e| anyhow!("AnnData view obs {}", e))?;
+        let var = self.var.compose(self.adata.n_vars(), &var.into())
+            .map_err(|e| anyhow!("AnnData view var {}", e))?;
+        Ok(Self { adata: self.adata, obs, var, generation: self.generation })
+    }
+
+    /// The object this view selects from.
+    pub fn parent(&self) -> &'a AnnData<B> {
+        self.adata
+    }
+
+    /// The selections of observations and variables in the underlying object.
+    pub fn selection(&self) -> [&SelectInfoElem; 2] {
+        [&self.obs, &self.var]
+    }
+
+    /// Return an error if the underlying object has been subset, appended to
+    /// or resized since the view was created.
+    pub fn check(&self) -> Result<()> {
+        ensure!(
+            self.adata.generation() == self.generation,
+            "the view is no longer valid as '{}' has been subset or resized",
+            self.adata.filename().display(),
+        );
+        Ok(())
+    }
+
+    pub fn n_obs(&self) -> Result<usize> {
+        self.check()?;
+        Ok(self.obs.output_len(self.adata.n_obs()))
+    }
+
+    pub fn n_vars(&self) -> Result<usize> {
+        self.check()?;
+        Ok(self.var.output_len(self.adata.n_vars()))
+    }
+
+    pub fn obs_names(&self) -> Result<DataFrameIndex> {
+        self.check()?;
+        Ok(self.adata.obs_names().select(&self.obs))
+    }
+
+    pub fn var_names(&self) -> Result<DataFrameIndex> {
+        self.check()?;
+        Ok(self.adata.var_names().select(&self.var))
+    }
+
+    /// Read the selected part of X, `None` if X is empty.
+    pub fn read_x(&self) -> Result<Option<ArrayData>> {
+        self.check()?;
+        self.adata.x().slice([self.obs.clone(), self.var.clone()])
+    }
+
+    /// Read the selected rows of obs. Only these rows are read from the
+    /// storage if obs is not cached.
+    pub fn read_obs(&self) -> Result<DataFrame> {
+        self.check()?;
+        self.adata.get_obs().lock().as_mut()
+            .map_or(Ok(DataFrame::empty()), |x| x.select_axis(0, &self.obs))
+    }
+
+    /// Read the selected rows of var. See [`AnnDataView::read_obs`].
+    pub fn read_var(&self) -> Result<DataFrame> {
+        self.check()?;
+        self.adata.get_var().lock().as_mut()
+            .map_or(Ok(DataFrame::empty()), |x| x.select_axis(0, &self.var))
+    }
+
+    /// Write the view to a new file, see [`AnnData::write_select`].
+    pub fn write<O: Backend, P: AsRef<Path>>(&self, filename: P) -> Result<()> {
+        self.check()?;
+        self.adata.write_select::<O, _, _>([self.obs.clone(), self.var.clone()], filename)
+    }
+}
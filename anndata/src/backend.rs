@@ -1,24 +1,76 @@
-use crate::data::{DynArray, DynScalar, SelectInfo, SelectInfoElem, Shape};
+use crate::data::{categorical_threshold, utils::cast_values, DynArray, DynScalar, HasShape, SelectInfo, SelectInfoElem, Shape, WriteData};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Context, Result};
+use half::f16;
 use core::fmt::{Display, Formatter, Debug};
 use ndarray::{Array, ArrayD, ArrayView, RemoveAxis};
+use std::cell::RefCell;
 use std::path::{Path, PathBuf};
 
+/// The filter used to compress the chunks of a dataset. Levels range from 0
+/// to 9. Backends fall back to `Gzip` with a warning if a filter is not
+/// available.
+///
+/// In HDF5 files, `Lz4` and `Zstd` use the blosc filter (id 32001), which
+/// is not built into HDF5. Other readers, e.g., h5py, need the filter plugin
+/// to decompress such datasets, for instance by running `import hdf5plugin`
+/// before opening the file. Use `Gzip` for files that must be readable
+/// everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip(u8),
+    Lz4(u8),
+    Zstd(u8),
+}
+
+impl Compression {
+    pub fn level(&self) -> u8 {
+        match self {
+            Compression::Gzip(x) | Compression::Lz4(x) | Compression::Zstd(x) => *x,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WriteConfig {
-    pub compression: Option<u8>,
+    pub compression: Option<Compression>,
     pub block_size: Option<Shape>,
+    /// Threshold below which string columns of dataframes are written as
+    /// categorical arrays, see [`crate::data::set_categorical_threshold`].
+    pub categorical_threshold: Option<f64>,
+}
+
+thread_local! {
+    /// The configuration returned by `WriteConfig::default`, if overridden by
+    /// [`with_write_config`].
+    static WRITE_CONFIG: RefCell<Option<WriteConfig>> = const { RefCell::new(None) };
 }
 
 impl Default for WriteConfig {
     fn default() -> Self {
-        Self {
-            compression: Some(1),
+        WRITE_CONFIG.with(|x| x.borrow().clone()).unwrap_or(Self {
+            compression: Some(Compression::Gzip(1)),
             //compression: None,
             block_size: None,
+            categorical_threshold: categorical_threshold(),
+        })
+    }
+}
+
+/// Run `f` with `config` as the default configuration of the datasets
+/// written by the current thread. A block size is only used for datasets
+/// with the same number of dimensions.
+pub(crate) fn with_write_config<T>(config: WriteConfig, f: impl FnOnce() -> T) -> T {
+    struct Reset(Option<WriteConfig>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            WRITE_CONFIG.with(|x| *x.borrow_mut() = self.0.take());
         }
     }
+
+    let _reset = Reset(WRITE_CONFIG.with(|x| x.borrow_mut().replace(config)));
+    f()
 }
 
 pub trait Backend: 'static {
@@ -41,6 +93,21 @@ pub trait Backend: 'static {
 
     /// Opens a file as read/write, file must exist.
     fn open_rw<P: AsRef<Path>>(path: P) -> Result<Self::File>;
+
+    /// Opens a file for single-writer-multiple-reader (SWMR) access, file must
+    /// exist. If `write` is true, the file is opened as the writer, otherwise
+    /// as a reader that observes the changes made by the writer.
+    fn open_swmr<P: AsRef<Path>>(_path: P, _write: bool) -> Result<Self::File> {
+        bail!("the {} backend does not support SWMR", Self::NAME)
+    }
+
+    /// Run `f` in one locked region of the library, if the backend serializes
+    /// its calls with a lock, so that an element is created and its encoding
+    /// attributes are written without acquiring the lock for every call. This
+    /// blocks all other threads, so it is only used for short writes.
+    fn locked<T, F: FnOnce() -> T>(f: F) -> T {
+        f()
+    }
 }
 
 pub trait FileOp {
@@ -49,8 +116,23 @@ pub trait FileOp {
     /// Returns the file path.
     fn filename(&self) -> PathBuf;
 
+    /// Flush the buffered data to disk.
+    fn flush(&self) -> Result<()>;
+
     /// Close the file.
     fn close(self) -> Result<()>;
+
+    /// Whether the file is opened for SWMR access, see [`Backend::open_swmr`].
+    fn is_swmr(&self) -> bool {
+        false
+    }
+
+    /// Returns an identifier of this opening of the file, shared by the handles
+    /// returned by [`LocationOp::file`] but not by other openings of the same
+    /// path. Backends without open handles return `None`.
+    fn handle_id(&self) -> Option<u64> {
+        None
+    }
 }
 
 pub trait GroupOp {
@@ -80,6 +162,13 @@ pub trait GroupOp {
     /// Check if a group or dataset exists.
     fn exists(&self, name: &str) -> Result<bool>;
 
+    /// Copy the group or dataset at `path` in the file `file` into this group
+    /// as `name`, including all attributes and children, without interpreting it.
+    fn copy_from(&self, file: &Path, path: &str, name: &str) -> Result<()>;
+
+    /// Move the group or dataset `from` to `to`, without copying the data.
+    fn rename(&self, from: &str, to: &str) -> Result<()>;
+
     fn create_scalar_data<D: BackendData>(
         &self,
         name: &str,
@@ -99,7 +188,7 @@ pub trait GroupOp {
     {
         let arr_view = arr.into();
         let shape = arr_view.shape();
-        let block_size = config.block_size.unwrap_or_else(|| if shape.len() == 1 {
+        let block_size = config.block_size.filter(|x| x.ndim() == shape.len()).unwrap_or_else(|| if shape.len() == 1 {
             shape[0].min(10000).into()
         } else {
             shape.iter().map(|&x| x.min(100)).collect()
@@ -112,6 +201,7 @@ pub trait GroupOp {
         let new_config = WriteConfig {
             compression: compression,
             block_size: Some(block_size),
+            ..config
         };
         let dataset = self.new_dataset::<D>(name, &shape.into(), new_config)?;
         dataset.write_array(arr_view)?;
@@ -140,12 +230,70 @@ pub trait LocationOp {
     fn write_str_attr(&self, name: &str, value: &str) -> Result<()> {
         self.write_scalar_attr(name, value.to_string())
     }
+    /// Write a string attribute, unless it already has the given value, so
+    /// that overwriting an element does not rewrite unchanged metadata.
+    fn update_str_attr(&self, name: &str, value: &str) -> Result<()> {
+        if self.read_str_attr(name).ok().as_deref() == Some(value) {
+            Ok(())
+        } else {
+            self.write_str_attr(name, value)
+        }
+    }
 
     fn read_scalar_attr<T: BackendData>(&self, name: &str) -> Result<T>;
     fn read_array_attr<T: BackendData, D: RemoveAxis>(&self, name: &str) -> Result<Array<T, D>>;
     fn read_str_attr(&self, name: &str) -> Result<String> {
         self.read_scalar_attr(name)
     }
+
+    /// Returns the data type of the attribute `name`, or `None` if there is no
+    /// such attribute.
+    fn attr_dtype(&self, name: &str) -> Result<Option<ScalarType>>;
+
+    /// Returns the names of all attributes at the location.
+    fn attr_names(&self) -> Result<Vec<String>>;
+
+    /// Read a scalar attribute whose type is not known in advance. Returns
+    /// `None` if there is no such attribute.
+    fn read_dyn_scalar_attr(&self, name: &str) -> Result<Option<DynScalar>> {
+        let value = match self.attr_dtype(name)? {
+            None => return Ok(None),
+            Some(ScalarType::I8) => DynScalar::I8(self.read_scalar_attr(name)?),
+            Some(ScalarType::I16) => DynScalar::I16(self.read_scalar_attr(name)?),
+            Some(ScalarType::I32) => DynScalar::I32(self.read_scalar_attr(name)?),
+            Some(ScalarType::I64) => DynScalar::I64(self.read_scalar_attr(name)?),
+            Some(ScalarType::U8) => DynScalar::U8(self.read_scalar_attr(name)?),
+            Some(ScalarType::U16) => DynScalar::U16(self.read_scalar_attr(name)?),
+            Some(ScalarType::U32) => DynScalar::U32(self.read_scalar_attr(name)?),
+            Some(ScalarType::U64) => DynScalar::U64(self.read_scalar_attr(name)?),
+            Some(ScalarType::Usize) => DynScalar::Usize(self.read_scalar_attr(name)?),
+            Some(ScalarType::F16) => DynScalar::F16(self.read_scalar_attr(name)?),
+            Some(ScalarType::F32) => DynScalar::F32(self.read_scalar_attr(name)?),
+            Some(ScalarType::F64) => DynScalar::F64(self.read_scalar_attr(name)?),
+            Some(ScalarType::Bool) => DynScalar::Bool(self.read_scalar_attr(name)?),
+            Some(ScalarType::String) => DynScalar::String(self.read_scalar_attr(name)?),
+        };
+        Ok(Some(value))
+    }
+
+    fn write_dyn_scalar_attr(&self, name: &str, value: DynScalar) -> Result<()> {
+        match value {
+            DynScalar::I8(x) => self.write_scalar_attr(name, x),
+            DynScalar::I16(x) => self.write_scalar_attr(name, x),
+            DynScalar::I32(x) => self.write_scalar_attr(name, x),
+            DynScalar::I64(x) => self.write_scalar_attr(name, x),
+            DynScalar::U8(x) => self.write_scalar_attr(name, x),
+            DynScalar::U16(x) => self.write_scalar_attr(name, x),
+            DynScalar::U32(x) => self.write_scalar_attr(name, x),
+            DynScalar::U64(x) => self.write_scalar_attr(name, x),
+            DynScalar::Usize(x) => self.write_scalar_attr(name, x),
+            DynScalar::F16(x) => self.write_scalar_attr(name, x),
+            DynScalar::F32(x) => self.write_scalar_attr(name, x),
+            DynScalar::F64(x) => self.write_scalar_attr(name, x),
+            DynScalar::Bool(x) => self.write_scalar_attr(name, x),
+            DynScalar::String(x) => self.write_scalar_attr(name, x),
+        }
+    }
 }
 
 pub trait DatasetOp {
@@ -155,6 +303,34 @@ pub trait DatasetOp {
     fn shape(&self) -> Shape;
     fn reshape(&self, shape: &Shape) -> Result<()>;
 
+    /// Return the number of bytes the dataset occupies in the file, which
+    /// differs from the size of the data if it is compressed.
+    fn storage_size(&self) -> Result<u64>;
+
+    /// The shape of the chunks the dataset is stored in, or `None` if it is
+    /// not stored in chunks.
+    fn chunk_shape(&self) -> Option<Shape> {
+        None
+    }
+
+    /// The size in bytes of the cache of decompressed chunks, or `None` if
+    /// the backend does not cache chunks.
+    fn chunk_cache(&self) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Reopen the dataset with a cache of `nbytes` bytes for decompressed
+    /// chunks. Backends that do not cache chunks return the dataset as is.
+    /// If the dataset is closed before it is reopened and reopening fails,
+    /// the error is returned and the caller needs to open the dataset again.
+    fn with_chunk_cache(self, nbytes: usize) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let _ = nbytes;
+        Ok(self)
+    }
+
     fn read_scalar<T: BackendData>(&self) -> Result<T>;
 
     fn read_array<T: BackendData, D>(&self) -> Result<Array<T, D>>
@@ -169,6 +345,26 @@ pub trait DatasetOp {
         S: AsRef<SelectInfoElem>,
         D: RemoveAxis;
 
+    /// Read the selection into `out` in row-major order, which must have
+    /// exactly as many entries as the selection. This lets callers read into
+    /// buffers they own, e.g., numpy arrays. The default implementation goes
+    /// through an intermediate array; backends can override it to read into
+    /// the buffer directly.
+    fn read_into<T: BackendData, S>(&self, selection: &[S], out: &mut [T]) -> Result<()>
+    where
+        S: AsRef<SelectInfoElem>,
+    {
+        let arr: ArrayD<T> = self.read_array_slice(selection)?;
+        ensure!(
+            arr.len() == out.len(),
+            "cannot read {} entries into a buffer of length {}",
+            arr.len(),
+            out.len(),
+        );
+        out.iter_mut().zip(arr.iter()).for_each(|(o, x)| *o = x.clone());
+        Ok(())
+    }
+
     fn write_array<'a, A, D, Dim>(
         &self,
         data: A,
@@ -202,9 +398,13 @@ pub enum DataType {
     Categorical,
     CsrMatrix(ScalarType),
     CscMatrix(ScalarType),
+    /// A matrix in coordinate format. It is read as a csr matrix.
+    CooMatrix(ScalarType),
     DataFrame,
     Scalar(ScalarType),
     Mapping,
+    /// An element with an encoding that is not supported. It is kept as is.
+    Unknown,
 }
 
 impl Display for DataType {
@@ -214,9 +414,11 @@ impl Display for DataType {
             DataType::Categorical => write!(f, "Categorical"),
             DataType::CsrMatrix(t) => write!(f, "CsrMatrix({})", t),
             DataType::CscMatrix(t) => write!(f, "CscMatrix({})", t),
+            DataType::CooMatrix(t) => write!(f, "CooMatrix({})", t),
             DataType::DataFrame => write!(f, "DataFrame"),
             DataType::Scalar(t) => write!(f, "Scalar({})", t),
             DataType::Mapping => write!(f, "Mapping"),
+            DataType::Unknown => write!(f, "Unknown"),
         }
     }
 }
@@ -233,6 +435,7 @@ pub enum ScalarType {
     U32,
     U64,
     Usize,
+    F16,
     F32,
     F64,
     Bool,
@@ -251,6 +454,7 @@ impl Display for ScalarType {
             ScalarType::U32 => write!(f, "u32"),
             ScalarType::U64 => write!(f, "u64"),
             ScalarType::Usize => write!(f, "usize"),
+            ScalarType::F16 => write!(f, "f16"),
             ScalarType::F32 => write!(f, "f32"),
             ScalarType::F64 => write!(f, "f64"),
             ScalarType::Bool => write!(f, "bool"),
@@ -259,6 +463,57 @@ impl Display for ScalarType {
     }
 }
 
+impl ScalarType {
+    /// Whether the type is an integer or boolean type. Values of these types
+    /// can be converted to each other, see [`cast_integer_array`].
+    pub fn is_integer_or_bool(&self) -> bool {
+        !matches!(self, ScalarType::F16 | ScalarType::F32 | ScalarType::F64 | ScalarType::String)
+    }
+}
+
+/// Convert an array of integers or booleans to `T`, which must be an integer
+/// or boolean type as well. Backends use this to read attributes written with
+/// a different width than the one requested, e.g., a `shape` stored as `i32`
+/// or `u64` and read as `usize`. Return an error if a value does not fit in
+/// `T`. Booleans become 0 and 1, and non-zero integers become `true`.
+pub fn cast_integer_array<T: BackendData>(arr: DynArray) -> Result<ArrayD<T>> {
+    ensure!(T::DTYPE.is_integer_or_bool(), "cannot convert integers to {}", T::DTYPE);
+    let shape = arr.shape().as_ref().to_vec();
+    macro_rules! cast {
+        ($x:expr) => {
+            cast_values($x.iter().copied().collect(), T::DTYPE)?
+        };
+    }
+    let values = match &arr {
+        DynArray::I8(x) => cast!(x),
+        DynArray::I16(x) => cast!(x),
+        DynArray::I32(x) => cast!(x),
+        DynArray::I64(x) => cast!(x),
+        DynArray::U8(x) => cast!(x),
+        DynArray::U16(x) => cast!(x),
+        DynArray::U32(x) => cast!(x),
+        DynArray::U64(x) => cast!(x),
+        DynArray::Usize(x) => cast!(x),
+        DynArray::Bool(x) => cast_values(x.iter().map(|&x| x as u8).collect(), T::DTYPE)?,
+        _ => bail!("cannot convert {} array to {}", arr.data_type(), T::DTYPE),
+    };
+    Ok(T::from_dyn_arr(values)?.into_shape(shape)?)
+}
+
+/// Convert an integer or boolean scalar to `T`, see [`cast_integer_array`].
+pub fn cast_integer_scalar<T: BackendData>(x: DynScalar) -> Result<T> {
+    macro_rules! arr0 {
+        ($($variant:ident),*) => {
+            match x {
+                $(DynScalar::$variant(x) => ndarray::arr0(x).into_dyn().into(),)*
+                _ => bail!("cannot convert {:?} to {}", x, T::DTYPE),
+            }
+        };
+    }
+    let arr: DynArray = arr0!(I8, I16, I32, I64, U8, U16, U32, U64, Usize, Bool);
+    Ok(cast_integer_array::<T>(arr)?.into_iter().next().unwrap())
+}
+
 pub enum DataContainer<B: Backend> {
     Group(B::Group),
     Dataset(B::Dataset),
@@ -319,15 +574,32 @@ impl<B: Backend> LocationOp for DataContainer<B> {
             DataContainer::Dataset(d) => d.read_array_attr(name),
         }
     }
+
+    fn attr_dtype(&self, name: &str) -> Result<Option<ScalarType>> {
+        match self {
+            DataContainer::Group(g) => g.attr_dtype(name),
+            DataContainer::Dataset(d) => d.attr_dtype(name),
+        }
+    }
+
+    fn attr_names(&self) -> Result<Vec<String>> {
+        match self {
+            DataContainer::Group(g) => g.attr_names(),
+            DataContainer::Dataset(d) => d.attr_names(),
+        }
+    }
 }
 
 impl<B: Backend> DataContainer<B> {
+    /// Open the group or dataset named `name`. Links, including links to
+    /// objects in other files, are followed transparently.
     pub fn open<G: GroupOp<Backend = B>>(group: &G, name: &str) -> Result<Self> {
         if group.exists(name)? {
             group
                 .open_dataset(name)
                 .map(DataContainer::Dataset)
-                .or(group.open_group(name).map(DataContainer::Group))
+                .or_else(|_| group.open_group(name).map(DataContainer::Group))
+                .with_context(|| format!("cannot open '{}', it may be a link to a missing object", name))
         } else {
             bail!("No group or dataset named '{}' in group", name);
         }
@@ -337,19 +609,34 @@ impl<B: Backend> DataContainer<B> {
         container.file()?.delete(&container.path().to_string_lossy())
     }
 
-    pub fn encoding_type(&self) -> Result<DataType> {
-        let enc = match self {
+    /// Return the value of the `encoding-type` attribute. Groups without the
+    /// attribute are mappings, and datasets without it are inferred from their shape.
+    pub(crate) fn encoding_name(&self) -> String {
+        match self {
             DataContainer::Group(group) => group
                 .read_str_attr("encoding-type")
                 .unwrap_or("mapping".to_string()),
             DataContainer::Dataset(dataset) => dataset
                 .read_str_attr("encoding-type")
-                .unwrap_or("numeric-scalar".to_string()),
-        };
+                .unwrap_or_else(|_| match (dataset.shape().ndim(), dataset.dtype()) {
+                    (0, Ok(ScalarType::String)) => "string",
+                    (0, _) => "numeric-scalar",
+                    (_, Ok(ScalarType::String)) => "string-array",
+                    _ => "array",
+                }.to_string()),
+        }
+    }
+
+    pub fn encoding_type(&self) -> Result<DataType> {
+        let enc = self.encoding_name();
         let ty = match enc.as_str() {
             "string" => DataType::Scalar(ScalarType::String),
             "numeric-scalar" => DataType::Scalar(self.as_dataset()?.dtype()?),
             "categorical" => DataType::Categorical,
+            // Some writers tag 0-dimensional datasets as arrays, but they hold a single value.
+            "string-array" | "array" if self.as_dataset()?.shape().ndim() == 0 => {
+                DataType::Scalar(self.as_dataset()?.dtype()?)
+            },
             "string-array" => DataType::Array(ScalarType::String),
             "array" => DataType::Array(self.as_dataset()?.dtype()?),
             "csc_matrix" => {
@@ -360,13 +647,27 @@ impl<B: Backend> DataContainer<B> {
                 let ty = self.as_group()?.open_dataset("data")?.dtype()?;
                 DataType::CsrMatrix(ty)
             },
+            "coo_matrix" => {
+                let ty = self.as_group()?.open_dataset("data")?.dtype()?;
+                DataType::CooMatrix(ty)
+            },
             "dataframe" => DataType::DataFrame,
             "mapping" | "dict" => DataType::Mapping,
-            ty => bail!("Unsupported type '{}'", ty),
+            ty => bail!("unsupported encoding '{}'", ty),
         };
         Ok(ty)
     }
 
+    /// Return the encoding of the container if it is not supported by this library.
+    pub fn unsupported_encoding(&self) -> Option<String> {
+        let enc = self.encoding_name();
+        match enc.as_str() {
+            "string" | "numeric-scalar" | "categorical" | "string-array" | "array"
+            | "csc_matrix" | "csr_matrix" | "coo_matrix" | "dataframe" | "mapping" | "dict" => None,
+            _ => Some(enc),
+        }
+    }
+
     pub fn as_group(&self) -> Result<&B::Group> {
         match self {
             Self::Group(x) => Ok(&x),
@@ -642,6 +943,34 @@ impl BackendData for usize {
     }
 }
 
+impl BackendData for f16 {
+    const DTYPE: ScalarType = ScalarType::F16;
+
+    fn into_dyn(&self) -> DynScalar {
+        DynScalar::F16(*self)
+    }
+
+    fn into_dyn_arr<'a, D>(arr: ArrayView<'a, Self, D>) -> DynArrayView<'a, D> {
+        DynArrayView::F16(arr)
+    }
+
+    fn from_dyn(x: DynScalar) -> Result<Self> {
+        if let DynScalar::F16(x) = x {
+            Ok(x)
+        } else {
+            bail!("Expecting f16")
+        }
+    }
+
+    fn from_dyn_arr(x: DynArray) -> Result<ArrayD<Self>> {
+        if let DynArray::F16(x) = x {
+            Ok(x)
+        } else {
+            bail!("Expecting f16 array")
+        }
+    }
+}
+
 impl BackendData for f32 {
     const DTYPE: ScalarType = ScalarType::F32;
 
@@ -764,6 +1093,7 @@ pub enum DynArrayView<'a, D> {
     U32(ArrayView<'a, u32, D>),
     U64(ArrayView<'a, u64, D>),
     Usize(ArrayView<'a, usize, D>),
+    F16(ArrayView<'a, f16, D>),
     F32(ArrayView<'a, f32, D>),
     F64(ArrayView<'a, f64, D>),
     String(ArrayView<'a, String, D>),
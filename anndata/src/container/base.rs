@@ -1,29 +1,227 @@
 use crate::{
     traits::ArrayElemOp,
-    backend::{Backend, DataContainer, DataType, GroupOp, LocationOp},
+    backend::{Backend, BackendData, DataContainer, DataType, DatasetOp, FileOp, GroupOp, LocationOp, ScalarType},
     data::*,
     data::index::VecVecIndex,
+    data::dataframe::{check_writable, read_column_order, read_index_levels, read_null_count, series_to_array, Query},
+    anndata::item_size,
 };
 
-use anyhow::{bail, ensure, Result};
-use indexmap::set::IndexSet;
+use anyhow::{bail, ensure, Context, Result};
+use indexmap::{IndexMap, set::IndexSet};
 use itertools::Itertools;
-use ndarray::Ix1;
 use num::integer::div_rem;
-use parking_lot::{Mutex, MutexGuard};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use polars::{
     frame::DataFrame,
-    prelude::{concat, IntoLazy, UnionArgs},
+    prelude::{concat, CsvWriter, IntoLazy, IpcReader, IpcWriter, NamedFrom, SerReader, SerWriter, UnionArgs},
     series::{Series, IntoSeries},
 };
+#[cfg(feature = "parquet")]
+use polars::prelude::{ParquetReader, ParquetWriter};
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use smallvec::SmallVec;
-use std::{ops::{Deref, DerefMut}, sync::Arc};
+use std::{borrow::Cow, collections::{BTreeMap, HashMap}, ops::{Deref, DerefMut}, path::{Path, PathBuf}, sync::{Arc, OnceLock}};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::warn;
+
+/// The attribute of the file root recording the time of the last modification,
+/// in seconds since the Unix epoch.
+pub(crate) const LAST_MODIFIED: &str = "last_modified";
+
+/// Identifies the file handle of a batched write, by [`FileOp::handle_id`] or
+/// by the file path if the backend has no open handles.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum BatchKey {
+    Handle(u64),
+    Path(PathBuf),
+}
+
+impl BatchKey {
+    fn new<F: FileOp>(file: &F) -> Self {
+        file.handle_id().map_or_else(|| BatchKey::Path(file.filename()), BatchKey::Handle)
+    }
+}
+
+/// The file handles in a batched write, with the number of nested batches and
+/// whether the file has been modified since the outermost batch started.
+static BATCHES: Mutex<BTreeMap<BatchKey, (usize, bool)>> = Mutex::new(BTreeMap::new());
+
+/// Write the current time to the `last_modified` attribute of the file root.
+/// In a batched write, this is deferred until the batch is finished. Files
+/// opened for SWMR access are not touched, as the SWMR writer cannot modify
+/// attributes while readers have the file open.
+pub(crate) fn touch<B: Backend>(file: &B::File) -> Result<()> {
+    if file.is_swmr() {
+        return Ok(());
+    }
+    {
+        let mut batches = BATCHES.lock();
+        if !batches.is_empty() {
+            if let Some((_, modified)) = batches.get_mut(&BatchKey::new(file)) {
+                *modified = true;
+                return Ok(());
+            }
+        }
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    file.open_group("/")?.write_scalar_attr(LAST_MODIFIED, now)
+}
+
+/// A guard deferring the updates of the modification time and the flush of a
+/// file. When the outermost guard of the file is finished or dropped, the
+/// modification time is written once if the file has been modified, and the
+/// file is flushed. See [`crate::AnnData::batched`].
+pub struct BatchedWrite<'a, B: Backend> {
+    file: &'a B::File,
+    key: BatchKey,
+    finished: bool,
+}
+
+impl<'a, B: Backend> BatchedWrite<'a, B> {
+    pub(crate) fn new(file: &'a B::File) -> Self {
+        let key = BatchKey::new(file);
+        BATCHES.lock().entry(key.clone()).or_insert((0, false)).0 += 1;
+        Self { file, key, finished: false }
+    }
+
+    /// End the batch, returning the errors of the deferred writes.
+    pub fn finish(mut self) -> Result<()> {
+        self.finished = true;
+        self.end()
+    }
 
-/// Slot stores an optional object wrapped by Arc and Mutex.
+    fn end(&self) -> Result<()> {
+        let modified = {
+            let mut batches = BATCHES.lock();
+            let (depth, modified) = batches.get_mut(&self.key).unwrap();
+            *depth -= 1;
+            if *depth > 0 {
+                return Ok(());
+            }
+            let modified = *modified;
+            batches.remove(&self.key);
+            modified
+        };
+        if modified {
+            touch::<B>(self.file)?;
+        }
+        self.file.flush()
+    }
+}
+
+impl<B: Backend> Drop for BatchedWrite<'_, B> {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.end() {
+                warn!("failed to finish the batched write of '{}': {}", self.file.filename().display(), e);
+            }
+        }
+    }
+}
+
+/// Read the `last_modified` attribute of the file root, if any.
+pub(crate) fn last_modified<B: Backend>(file: &B::File) -> Option<f64> {
+    file.open_group("/").and_then(|g| g.read_scalar_attr(LAST_MODIFIED)).ok()
+}
+
+/// Attributes used by the on-disk format, which cannot be set through
+/// `set_attr`.
+const RESERVED_ATTRS: [&str; 9] = [
+    "encoding-type", "encoding-version", "_index", "column-order", "shape", "h5sparse_format",
+    "ordered", "transposed", "dtype",
+];
+
+/// Write a custom attribute on the container of an element.
+fn write_custom_attr<B: Backend>(container: &DataContainer<B>, name: &str, value: DynScalar) -> Result<()> {
+    ensure!(
+        !RESERVED_ATTRS.contains(&name),
+        "'{}' is a reserved attribute and cannot be set", name
+    );
+    container.write_dyn_scalar_attr(name, value)?;
+    touch::<B>(&container.file()?)
+}
+
+/// Replace the object `name` in `group` by the object `tmp`. The original is
+/// renamed to a backup that is only deleted once `tmp` has been moved into
+/// place, so that it is restored if the move fails.
+pub(crate) fn replace_by_tmp<G: GroupOp>(group: &G, tmp: &str, name: &str) -> Result<()> {
+    let backup = format!("{}.backup", name);
+    group.rename(name, &backup)?;
+    if let Err(e) = group.rename(tmp, name) {
+        group.rename(&backup, name)?;
+        group.delete(tmp)?;
+        return Err(e);
+    }
+    group.delete(&backup)
+}
+
+/// Whether the container is a dense array stored transposed, see [`is_transposed`].
+fn container_is_transposed<B: Backend>(container: &DataContainer<B>) -> Result<bool> {
+    match container {
+        DataContainer::Dataset(dataset) => is_transposed::<B>(dataset),
+        DataContainer::Group(_) => Ok(false),
+    }
+}
+
+/// Read the custom attributes of a container, so that they can be restored
+/// after the element is rewritten. Attributes that cannot be read as scalars,
+/// e.g., arrays written by other tools, are left out.
+fn custom_attrs<B: Backend>(container: &DataContainer<B>) -> Result<Vec<(String, DynScalar)>> {
+    Ok(container
+        .attr_names()?
+        .into_iter()
+        .filter(|name| !RESERVED_ATTRS.contains(&name.as_str()))
+        .filter_map(|name| {
+            let value = container.read_dyn_scalar_attr(&name).ok().flatten()?;
+            Some((name, value))
+        })
+        .collect())
+}
+
+fn restore_custom_attrs<B: Backend>(container: &DataContainer<B>, attrs: Vec<(String, DynScalar)>) -> Result<()> {
+    attrs
+        .into_iter()
+        .try_for_each(|(name, value)| container.write_dyn_scalar_attr(&name, value))
+}
+
+/// Copy the custom attributes of an element to the container it was written to.
+fn copy_custom_attrs<B: Backend, O: Backend>(from: &DataContainer<B>, to: &DataContainer<O>) -> Result<()> {
+    restore_custom_attrs(to, custom_attrs(from)?)
+}
+
+/// Read the data in the container, logging a cache miss.
+fn read_traced<B: Backend, D: ReadData>(container: &DataContainer<B>) -> Result<D> {
+    read_traced_assume_sorted(container, false)
+}
+
+/// Like [`read_traced`], see [`ReadData::read_assume_sorted`].
+fn read_traced_assume_sorted<B: Backend, D: ReadData>(
+    container: &DataContainer<B>,
+    assume_sorted_indices: bool,
+) -> Result<D> {
+    let data = D::read_assume_sorted(container, assume_sorted_indices)?;
+    trace_event!(
+        path = %container.path().display(),
+        bytes = crate::trace::nbytes(container),
+        "read: cache miss",
+    );
+    Ok(data)
+}
+
+/// Log a write to the container.
+#[cfg_attr(not(feature = "trace"), allow(unused_variables))]
+fn trace_write<B: Backend>(container: &DataContainer<B>) {
+    trace_event!(path = %container.path().display(), bytes = crate::trace::nbytes(container), "write");
+}
+
+/// Slot stores an optional object wrapped by Arc and RwLock.
 /// Encapsulating an object inside a slot allows us to drop the object from all references.
+/// Readers can share the lock through [`Slot::read`], while [`Slot::lock`] and
+/// [`Slot::inner`] give exclusive access.
 #[derive(Debug)]
-pub struct Slot<T>(pub(crate) Arc<Mutex<Option<T>>>);
+pub struct Slot<T>(pub(crate) Arc<RwLock<Option<T>>>);
 
 impl<T> Clone for Slot<T> {
     fn clone(&self) -> Self {
@@ -36,10 +234,9 @@ where
     T: std::fmt::Display,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.is_empty() {
-            write!(f, "Empty or closed slot")
-        } else {
-            write!(f, "{}", self.inner().deref())
+        match self.read().as_ref() {
+            None => write!(f, "Empty or closed slot"),
+            Some(x) => write!(f, "{}", x),
         }
     }
 }
@@ -47,34 +244,54 @@ where
 impl<T> Slot<T> {
     /// Create a slot from data.
     pub fn new(x: T) -> Self {
-        Slot(Arc::new(Mutex::new(Some(x))))
+        Slot(Arc::new(RwLock::new(Some(x))))
     }
 
     /// Create an empty slot.
     pub fn empty() -> Self {
-        Slot(Arc::new(Mutex::new(None)))
+        Slot(Arc::new(RwLock::new(None)))
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.lock().is_none()
+        self.0.read().is_none()
+    }
+
+    /// Lock the slot for shared read access.
+    pub fn read(&self) -> RwLockReadGuard<'_, Option<T>> {
+        self.0.read()
     }
 
-    pub fn lock(&self) -> MutexGuard<'_, Option<T>> {
-        self.0.lock()
+    /// Same as [`Slot::read`], but return `None` instead of blocking if the
+    /// slot is locked for writing.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, Option<T>>> {
+        self.0.try_read()
+    }
+
+    /// Lock the slot for exclusive access.
+    pub fn lock(&self) -> RwLockWriteGuard<'_, Option<T>> {
+        self.0.write()
     }
 
     pub fn inner(&self) -> Inner<'_, T> {
-        Inner(self.0.lock())
+        Inner(self.0.write())
+    }
+
+    /// Same as [`Slot::inner`], but return an error instead of panicking if
+    /// the slot is empty, e.g., because the object has been closed.
+    pub fn try_inner(&self) -> Result<Inner<'_, T>> {
+        let inner = Inner(self.0.write());
+        ensure!(inner.0.is_some(), "accessing an empty or closed slot");
+        Ok(inner)
     }
 
     /// Insert data to the slot, and return the old data.
     pub fn insert(&self, data: T) -> Option<T> {
-        std::mem::replace(self.0.lock().deref_mut(), Some(data))
+        self.0.write().replace(data)
     }
 
     /// Extract the data from the slot. The slot becomes empty after this operation.
     pub fn extract(&self) -> Option<T> {
-        std::mem::replace(self.0.lock().deref_mut(), None)
+        self.0.write().take()
     }
 
     /// Remove the data from the slot.
@@ -83,13 +300,13 @@ impl<T> Slot<T> {
     }
 
     pub fn swap(&self, other: &Self) {
-        let mut self_lock = self.0.lock();
-        let mut other_lock = other.0.lock();
+        let mut self_lock = self.0.write();
+        let mut other_lock = other.0.write();
         std::mem::swap(self_lock.deref_mut(), other_lock.deref_mut());
     }
 }
 
-pub struct Inner<'a, T>(pub MutexGuard<'a, Option<T>>);
+pub struct Inner<'a, T>(pub RwLockWriteGuard<'a, Option<T>>);
 
 impl<T> Deref for Inner<'_, T> {
     type Target = T;
@@ -111,12 +328,73 @@ impl<T> DerefMut for Inner<'_, T> {
     }
 }
 
+/// Create the file at `path` and write a dataframe to it with `write`.
+fn write_frame<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut std::fs::File) -> polars::error::PolarsResult<()>,
+{
+    let mut file = std::fs::File::create(path)
+        .with_context(|| format!("cannot create '{}'", path.display()))?;
+    write(&mut file).with_context(|| format!("cannot write '{}'", path.display()))
+}
+
+/// Read an Arrow IPC file written by [`InnerDataFrameElem::write_ipc`], see
+/// [`split_index`].
+pub(crate) fn read_ipc_frame(path: &Path) -> Result<(DataFrameIndex, DataFrame)> {
+    let file = std::fs::File::open(path).with_context(|| format!("cannot open '{}'", path.display()))?;
+    let df = IpcReader::new(file).finish().with_context(|| format!("cannot read '{}'", path.display()))?;
+    split_index(df).with_context(|| format!("cannot read '{}'", path.display()))
+}
+
+/// Read a Parquet file written by [`InnerDataFrameElem::write_parquet`], see
+/// [`split_index`].
+#[cfg(feature = "parquet")]
+pub(crate) fn read_parquet_frame(path: &Path) -> Result<(DataFrameIndex, DataFrame)> {
+    let file = std::fs::File::open(path).with_context(|| format!("cannot open '{}'", path.display()))?;
+    let df = ParquetReader::new(file).finish().with_context(|| format!("cannot read '{}'", path.display()))?;
+    split_index(df).with_context(|| format!("cannot read '{}'", path.display()))
+}
+
+/// Split an exported dataframe into the index, taken from the first column,
+/// and the other columns. The columns are checked to be writable, so that an
+/// unsupported column is reported before the annotations are replaced.
+fn split_index(mut df: DataFrame) -> Result<(DataFrameIndex, DataFrame)> {
+    ensure!(df.width() > 0, "no columns found, expecting the index as the first column");
+    let name = df.get_column_names()[0].to_string();
+    let column = df.drop_in_place(&name)?.cast(&polars::datatypes::DataType::String)?;
+    ensure!(column.null_count() == 0, "the index column '{}' contains nulls", name);
+    let mut index: DataFrameIndex = column.str()?.into_iter().map(|x| x.unwrap().to_string()).collect();
+    index.index_name = name;
+    df.get_columns().iter().try_for_each(|x| check_writable(x, x.name()))?;
+    Ok((index, df))
+}
+
+/// Whether the column is stored as a plain array, i.e., not as a categorical
+/// or nullable array.
+fn is_plain_column(series: &Series) -> bool {
+    let ty = series.dtype();
+    ty.is_numeric() || matches!(ty, polars::datatypes::DataType::Boolean | polars::datatypes::DataType::String)
+}
+
+/// How to undo the append of a column, see [`InnerDataFrameElem::append_rows`].
+enum AppendUndo {
+    /// Shrink the column back to its previous length.
+    Truncate(String),
+    /// Write back the previous data of the column.
+    Rewrite(String, Series),
+    /// Delete the column, which did not exist before.
+    Delete(String),
+}
+
 #[derive(Debug)]
 pub struct InnerDataFrameElem<B: Backend> {
     element: Option<DataFrame>,
     container: DataContainer<B>,
     column_names: IndexSet<String>,
     pub index: DataFrameIndex,
+    dirty: bool,
+    coerce_bool_columns: bool,
+    nan_as_null: bool,
 }
 
 impl<B: Backend> InnerDataFrameElem<B> {
@@ -137,6 +415,9 @@ impl<B: Backend> InnerDataFrameElem<B> {
             container,
             column_names,
             index,
+            dirty: false,
+            coerce_bool_columns: false,
+            nan_as_null: false,
         })
     }
 }
@@ -156,6 +437,89 @@ impl<B: Backend> InnerDataFrameElem<B> {
         self.index.len()
     }
 
+    /// Whether the element has been modified since it was opened or last flushed.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Read the attribute `name` of the element, e.g., a custom `units`
+    /// attribute. Return `None` if there is no such attribute.
+    pub fn get_attr(&self, name: &str) -> Result<Option<DynScalar>> {
+        self.container.read_dyn_scalar_attr(name)
+    }
+
+    /// Set a custom attribute of the element. The attribute is stored in the
+    /// file directly. Attributes describing the encoding cannot be changed.
+    pub fn set_attr(&mut self, name: &str, value: DynScalar) -> Result<()> {
+        write_custom_attr(&self.container, name, value)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self) -> Result<()> {
+        self.dirty = true;
+        trace_write(&self.container);
+        touch::<B>(&self.container.file()?)
+    }
+
+    /// Convert integer columns containing only 0 and 1 to boolean when reading,
+    /// see [`crate::data::coerce_bool_column`]. The data in the storage is not
+    /// modified.
+    pub fn set_coerce_bool_columns(&mut self, coerce: bool) {
+        if self.coerce_bool_columns != coerce {
+            self.coerce_bool_columns = coerce;
+            self.element = None;
+        }
+    }
+
+    /// Read NaN values of float columns as nulls, see
+    /// [`crate::data::nan_as_null_column`]. The data in the storage is not
+    /// modified.
+    pub fn set_nan_as_null(&mut self, nan_as_null: bool) {
+        if self.nan_as_null != nan_as_null {
+            self.nan_as_null = nan_as_null;
+            self.element = None;
+        }
+    }
+
+    /// Apply the conversions set for reading. `all_rows` tells whether the
+    /// columns hold all the rows; otherwise whether an integer column is read
+    /// as boolean is decided from the whole column in the storage, so that it
+    /// does not depend on the selected rows.
+    fn coerce(&self, df: DataFrame, all_rows: bool) -> Result<DataFrame> {
+        if self.coerce_bool_columns || self.nan_as_null {
+            df.get_columns().iter().map(|x| self.coerce_column(x.clone(), all_rows)).collect()
+        } else {
+            Ok(df)
+        }
+    }
+
+    fn coerce_column(&self, mut series: Series, all_rows: bool) -> Result<Series> {
+        if self.coerce_bool_columns && series.dtype().is_integer() {
+            if all_rows {
+                series = coerce_bool_column(series)?;
+            } else if self.is_bool_column(series.name())? {
+                series = series.cast(&polars::datatypes::DataType::Boolean)?;
+            }
+        }
+        if self.nan_as_null {
+            series = nan_as_null_column(series)?;
+        }
+        Ok(series)
+    }
+
+    /// Whether the column stored as `name` only contains 0 and 1, see
+    /// [`coerce_bool_column`].
+    fn is_bool_column(&self, name: &str) -> Result<bool> {
+        let container = DataContainer::<B>::open(self.container.as_group()?, name)?;
+        let series = coerce_bool_column(Series::read(&container)?)?;
+        Ok(series.dtype() == &polars::datatypes::DataType::Boolean)
+    }
+
     pub fn column(&mut self, name: &str) -> Result<&Series> {
         self.data().and_then(|x| Ok(x.column(name)?))
     }
@@ -164,12 +528,229 @@ impl<B: Backend> InnerDataFrameElem<B> {
         &self.column_names
     }
 
-    /// Set a column with a Series.
-    //TODO: this is not efficient. We should be able to replace a column without reading the whole dataframe.
+    /// The levels of a compound index, which some tools, e.g., R packages,
+    /// store as separate datasets. The names of the rows join the levels with
+    /// [`INDEX_LEVEL_SEPARATOR`]. Return `None` if the index has a single
+    /// level, which is also the case once the index has been rewritten.
+    pub fn index_levels(&self) -> Result<Option<DataFrame>> {
+        read_index_levels(&self.container)
+    }
+
+    /// Read a single column. If the dataframe has not been cached, only the
+    /// requested column is read from the storage.
+    pub fn read_column(&self, name: &str) -> Result<Series> {
+        ensure!(
+            self.column_names.contains(name),
+            "column '{}' does not exist",
+            name
+        );
+        match self.element {
+            Some(ref df) => Ok(df.column(name)?.clone()),
+            None => {
+                let container = DataContainer::<B>::open(self.container.as_group()?, name)?;
+                let mut series = Series::read(&container)?;
+                series.rename(name);
+                self.coerce_column(series, true)
+            }
+        }
+    }
+
+    /// Read a single column from the storage as a vector of `T`, without going
+    /// through polars. The column is promoted to `T` if this does not lose
+    /// precision, otherwise an error is returned.
+    pub fn read_column_as<T: FromDynArray>(&self, name: &str) -> Result<Vec<T>> {
+        ensure!(
+            self.column_names.contains(name),
+            "column '{}' does not exist",
+            name
+        );
+        let container = DataContainer::<B>::open(self.container.as_group()?, name)?;
+        let arr = T::from_dyn_array(DynArray::read(&container)?)
+            .with_context(|| format!("cannot read column '{}'", name))?;
+        Ok(arr.into_raw_vec())
+    }
+
+    /// Compute summary statistics of the columns. If the dataframe has not been
+    /// cached, the columns are read one at a time.
+    pub fn describe(&self) -> Result<DataFrameSummary> {
+        let columns = self.column_names.iter().filter_map(|name| match self.is_string_column(name) {
+            Ok(true) => None,
+            Ok(false) => Some(self.read_column(name)),
+            Err(e) => Some(Err(e)),
+        });
+        DataFrameSummary::from_columns(columns)
+    }
+
+    /// Whether the column `name` holds strings, which are not summarized by
+    /// [`InnerDataFrameElem::describe`]. Only the dtype is read from the storage.
+    pub(crate) fn is_string_column(&self, name: &str) -> Result<bool> {
+        match self.element {
+            Some(ref df) => Ok(df.column(name)?.dtype() == &polars::datatypes::DataType::String),
+            None => match DataContainer::<B>::open(self.container.as_group()?, name)? {
+                DataContainer::Dataset(dataset) => Ok(dataset.dtype()? == ScalarType::String),
+                DataContainer::Group(_) => Ok(false),
+            },
+        }
+    }
+
+    /// Count the nulls in each column. If the dataframe has not been cached,
+    /// only the null masks of nullable columns are read from the storage.
+    pub fn null_counts(&self) -> Result<IndexMap<String, usize>> {
+        self.column_names.iter().map(|name| {
+            let n = match self.element {
+                Some(ref df) => df.column(name)?.null_count(),
+                None => read_null_count(&DataContainer::<B>::open(self.container.as_group()?, name)?)?,
+            };
+            Ok((name.clone(), n))
+        }).collect()
+    }
+
+    /// Write the index and the columns to a CSV file, see
+    /// [`InnerDataFrameElem::write_ipc`]. Categorical columns are written as
+    /// their labels.
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut df = self.export_frame(true)?;
+        write_frame(path.as_ref(), |file| CsvWriter::new(file).finish(&mut df))
+    }
+
+    /// Write the index and the columns to an Arrow IPC file. The index is the
+    /// first column, named by the `_index` attribute. If the dataframe has not
+    /// been cached, the columns are read one at a time. Categorical columns
+    /// are dictionary-encoded.
+    pub fn write_ipc<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut df = self.export_frame(false)?;
+        write_frame(path.as_ref(), |file| IpcWriter::new(file).finish(&mut df))
+    }
+
+    /// Write the index and the columns to a Parquet file, see
+    /// [`InnerDataFrameElem::write_ipc`].
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut df = self.export_frame(false)?;
+        write_frame(path.as_ref(), |file| ParquetWriter::new(file).finish(&mut df).map(|_| ()))
+    }
+
+    /// The index followed by the columns, read one at a time. Categorical
+    /// columns are cast to strings if `cast_categorical` is set.
+    fn export_frame(&self, cast_categorical: bool) -> Result<DataFrame> {
+        let mut columns = vec![Series::new(&self.index.index_name, self.index.clone().into_vec())];
+        for name in &self.column_names {
+            let series = self.read_column(name)?;
+            let series = match series.dtype() {
+                polars::datatypes::DataType::Categorical(..) | polars::datatypes::DataType::Enum(..)
+                    if cast_categorical => series.cast(&polars::datatypes::DataType::String)?,
+                _ => series,
+            };
+            columns.push(series);
+        }
+        Ok(DataFrame::new(columns)?)
+    }
+
+    /// Set a column with a Series, adding it after the other columns if it
+    /// does not exist. Only this column is written to the storage.
     pub fn set_column<S: IntoSeries>(&mut self, name: &str, new_col: S) -> Result<()> {
-        let mut df = self.data()?.clone();
-        df.replace_or_add(name, new_col)?;
-        self.save(df)
+        let mut series = new_col.into_series();
+        series.rename(name);
+        ensure!(
+            series.len() == self.index.len(),
+            "cannot set column '{}' of length {} in a dataframe with {} rows",
+            name,
+            series.len(),
+            self.index.len(),
+        );
+        ensure!(name != self.index.index_name, "cannot set column '{}' as it is the index", name);
+        if self.column_names.contains(name) {
+            self.rewrite_column(name, series.clone())?;
+        } else {
+            let group = self.container.as_group()?;
+            if let Err(e) = series.write(group, name) {
+                if group.exists(name)? {
+                    group.delete(name)?;
+                }
+                return Err(e.context(format!("cannot write column '{}'", name)));
+            }
+            let columns: ndarray::Array1<String> = self.column_names.iter().cloned()
+                .chain(std::iter::once(name.to_string()))
+                .collect();
+            self.container.write_array_attr("column-order", &columns)?;
+            self.column_names.insert(name.to_string());
+        }
+        if self.element.is_some() {
+            let series = self.coerce_column(series, true)?;
+            self.element.as_mut().unwrap().replace_or_add(name, series)?;
+        }
+        self.mark_dirty()
+    }
+
+    /// Change the order of the columns by rewriting the `column-order`
+    /// attribute; the data of the columns is not moved. Columns not in
+    /// `order` are placed after those in it, keeping their relative order.
+    pub fn reorder_columns(&mut self, order: &[String]) -> Result<()> {
+        let mut column_names = IndexSet::with_capacity(self.column_names.len());
+        for name in order {
+            ensure!(self.column_names.contains(name), "column '{}' does not exist", name);
+            ensure!(column_names.insert(name.clone()), "column '{}' appears more than once", name);
+        }
+        column_names.extend(self.column_names.iter().cloned());
+        let columns: ndarray::Array1<String> = column_names.iter().cloned().collect();
+        self.container.write_array_attr("column-order", &columns)?;
+        if let Some(df) = self.element.take() {
+            self.element = Some(df.select(column_names.iter())?);
+        }
+        self.column_names = column_names;
+        self.mark_dirty()
+    }
+
+    /// Store the string column `name` as a categorical array. Only this
+    /// column is rewritten; categorical columns are left unchanged.
+    pub fn as_categorical(&mut self, name: &str) -> Result<()> {
+        let series = self.read_column(name)?;
+        let values = match series.dtype() {
+            polars::datatypes::DataType::Categorical(..) => return Ok(()),
+            polars::datatypes::DataType::String => series.str()?,
+            ty => bail!("cannot convert column '{}' of type {} to categorical", name, ty),
+        };
+        ensure!(values.null_count() == 0, "cannot convert column '{}' with nulls to categorical", name);
+        let array: CategoricalArray = values.into_iter().map(|x| x.unwrap()).collect();
+        self.rewrite_column(name, array)?;
+        if let Some(df) = self.element.as_mut() {
+            let cat = polars::datatypes::DataType::Categorical(None, Default::default());
+            df.replace(name, series.cast(&cat)?)?;
+        }
+        self.mark_dirty()
+    }
+
+    /// Store the categorical column `name` as a string array, the reverse of
+    /// [`InnerDataFrameElem::as_categorical`].
+    pub fn as_string(&mut self, name: &str) -> Result<()> {
+        let series = self.read_column(name)?;
+        let values = match series.dtype() {
+            polars::datatypes::DataType::Categorical(..) => series.categorical()?,
+            polars::datatypes::DataType::String => return Ok(()),
+            ty => bail!("cannot convert column '{}' of type {} to string", name, ty),
+        };
+        ensure!(values.null_count() == 0, "cannot convert column '{}' with nulls to string", name);
+        let array: ndarray::Array1<String> = values.iter_str().map(|x| x.unwrap().to_string()).collect();
+        self.rewrite_column(name, array)?;
+        if let Some(df) = self.element.as_mut() {
+            df.replace(name, series.cast(&polars::datatypes::DataType::String)?)?;
+        }
+        self.mark_dirty()
+    }
+
+    /// Replace the data of a column in the storage, leaving the other
+    /// columns untouched. The new data is written next to the old one, so that
+    /// the column is kept if writing fails.
+    fn rewrite_column<D: WriteData>(&mut self, name: &str, data: D) -> Result<()> {
+        let group = self.container.as_group()?;
+        let tmp = format!("{}.rewrite", name);
+        if let Err(e) = data.write(group, &tmp) {
+            if group.exists(&tmp)? {
+                group.delete(&tmp)?;
+            }
+            return Err(e.context(format!("cannot rewrite column '{}'", name)));
+        }
+        replace_by_tmp(group, &tmp, name)
     }
 
     pub fn set_index(&mut self, index: DataFrameIndex) -> Result<()> {
@@ -177,10 +758,121 @@ impl<B: Backend> InnerDataFrameElem<B> {
             self.index.len() == index.len(),
             "cannot change the index as the lengths differ"
         );
+        index.write_into(&self.container)?;
         self.index = index;
-        replace_with::replace_with_or_abort(&mut self.container, |x| {
-            self.index.overwrite(x).unwrap()
-        });
+        self.mark_dirty()
+    }
+
+    /// Replace both the index and the data.
+    pub(crate) fn replace(&mut self, index: DataFrameIndex, data: DataFrame) -> Result<()> {
+        ensure!(
+            data.height() == 0 || index.len() == data.height(),
+            "cannot update dataframe as lengths of index and dataframe differ"
+        );
+        index.write_into(&self.container)?;
+        self.index = index;
+        self.save(data)
+    }
+
+    /// Append `n` rows, given by the records in `data` and the names of the
+    /// new rows, which may be omitted for a range index. `data` must have the
+    /// same columns as the dataframe, or no columns at all if the dataframe has
+    /// none. Columns stored as resizable datasets are grown in place and the
+    /// other columns are rewritten. If anything fails, the rows already
+    /// written are removed.
+    pub(crate) fn append_rows(&mut self, n: usize, names: Option<Vec<String>>, data: &DataFrame) -> Result<()> {
+        let height = self.height();
+        if self.width() > 0 || height > 0 {
+            ensure!(
+                data.width() == self.width()
+                    && data.get_column_names().iter().all(|x| self.column_names.contains(*x)),
+                "the columns of the new rows ('{}') differ from those of the dataframe ('{}')",
+                data.get_column_names().iter().join("', '"),
+                self.column_names.iter().join("', '"),
+            );
+        }
+        ensure!(
+            data.width() == 0 || data.height() == n,
+            "expect {} rows, but receive {}",
+            n,
+            data.height(),
+        );
+        let mut index = self.index.clone();
+        index.append(n, names)?;
+
+        let mut undo = Vec::with_capacity(data.width());
+        let result = self.append_columns(height, data, &mut undo)
+            .and_then(|_| index.write_into(&self.container));
+        if let Err(e) = result {
+            for step in undo.into_iter().rev() {
+                let restored = match step {
+                    AppendUndo::Truncate(name) => self.container.as_group()
+                        .and_then(|x| DataContainer::<B>::open(x, &name))
+                        .and_then(|x| truncate_rows(&x, height)),
+                    AppendUndo::Rewrite(name, old) => self.rewrite_column(&name, old),
+                    AppendUndo::Delete(name) => self.container.as_group().and_then(|x| x.delete(&name)),
+                };
+                if let Err(err) = restored {
+                    warn!("cannot restore the dataframe after a failed append: {}", err);
+                }
+            }
+            if let Err(err) = self.index.write_into(&self.container) {
+                warn!("cannot restore the index after a failed append: {}", err);
+            }
+            return Err(e.context("cannot append rows to the dataframe"));
+        }
+        if self.width() == 0 && data.width() > 0 {
+            self.column_names = data.get_column_names().into_iter().map(|x| x.to_string()).collect();
+            let columns: ndarray::Array1<String> = self.column_names.iter().cloned().collect();
+            self.container.write_array_attr("column-order", &columns)?;
+        }
+        self.index = index;
+        self.element = None;
+        self.mark_dirty()
+    }
+
+    /// Append the columns of `data` to those in the storage, recording how to
+    /// undo each step in `undo`.
+    fn append_columns(&mut self, height: usize, data: &DataFrame, undo: &mut Vec<AppendUndo>) -> Result<()> {
+        for new in data.get_columns() {
+            let name = new.name().to_string();
+            let group = self.container.as_group()?;
+            if !group.exists(&name)? {
+                new.write(group, &name)?;
+                undo.push(AppendUndo::Delete(name));
+                continue;
+            }
+            let container = DataContainer::<B>::open(group, &name)?;
+            let array = match container.as_dataset() {
+                Ok(dataset) if dataset.chunk_shape().is_some() && new.null_count() == 0 && is_plain_column(new) =>
+                    Some(ArrayData::from(series_to_array(new, None)?)),
+                _ => None,
+            };
+            match array {
+                Some(array) if array.data_type() == container.encoding_type()? => {
+                    append_rows(&container, &array)?;
+                    undo.push(AppendUndo::Truncate(name));
+                }
+                _ => {
+                    // Categorical, nullable and non-resizable columns are rewritten.
+                    let mut old = Series::read(&container)?;
+                    old.rename(&name);
+                    ensure!(old.len() == height, "column '{}' has {} rows, expect {}", name, old.len(), height);
+                    let series = if let polars::datatypes::DataType::Categorical(..) = old.dtype() {
+                        let ty = polars::datatypes::DataType::String;
+                        let mut series = old.cast(&ty)?;
+                        series.append(&new.cast(&ty)?)?;
+                        series.cast(&polars::datatypes::DataType::Categorical(None, Default::default()))?
+                    } else {
+                        let mut series = old.clone();
+                        series.append(&new.strict_cast(old.dtype())?)?;
+                        series
+                    };
+                    self.rewrite_column(&name, series)?;
+                    undo.push(AppendUndo::Rewrite(name, old));
+                }
+            }
+        }
         Ok(())
     }
 
@@ -188,7 +880,7 @@ impl<B: Backend> InnerDataFrameElem<B> {
         match self.element {
             Some(ref df) => Ok(df),
             None => {
-                let df = DataFrame::read(&self.container)?;
+                let df = self.coerce(DataFrame::read(&self.container)?, true)?;
                 self.element = Some(df);
                 Ok(&self.element.as_ref().unwrap())
             }
@@ -204,8 +896,8 @@ impl<B: Backend> InnerDataFrameElem<B> {
             Some(ref df) => df.clone(),
             None => DataFrame::read(&self.container)?,
         };
-        df.overwrite(self.index.write(location, name)?)?;
-        Ok(())
+        let container = df.overwrite(self.index.write(location, name)?)?;
+        copy_custom_attrs(&self.container, &container)
     }
 
     pub fn export_select<O, G>(
@@ -221,13 +913,31 @@ impl<B: Backend> InnerDataFrameElem<B> {
         if selection.as_ref().into_iter().all(|x| x.is_full()) {
             self.export::<O, _>(location, name)
         } else {
-            self.select(selection)?.overwrite(
+            let container = self.select(selection)?.overwrite(
                 self.index.select(&selection[0]).write(location, name)?
             )?;
-            Ok(())
+            copy_custom_attrs(&self.container, &container)
         }
     }
 
+    /// Write the element with only the given columns, in the given order.
+    pub fn export_columns<O, G>(&mut self, columns: &[String], location: &G, name: &str) -> Result<()>
+    where
+        O: Backend,
+        G: GroupOp<Backend = O>,
+    {
+        let indices = columns
+            .iter()
+            .map(|c| {
+                self.column_names
+                    .get_index_of(c)
+                    .with_context(|| format!("no column named '{}' in '{}'", c, name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let full = SelectInfoElem::full();
+        self.export_select::<O, _>(&[&full, &SelectInfoElem::from(indices)], location, name)
+    }
+
     pub fn export_axis<O, S, G>(
         &mut self,
         axis: usize,
@@ -245,18 +955,28 @@ impl<B: Backend> InnerDataFrameElem<B> {
         self.export_select(slice.as_slice(), location, name)
     }
 
+    /// Select rows and columns. If the dataframe is not cached, only the
+    /// selected rows of the selected columns are read from the storage.
     pub fn select<S>(&mut self, selection: &[S]) -> Result<DataFrame>
     where
         S: AsRef<SelectInfoElem>,
     {
-        Ok(ArrayOp::select(self.data()?, selection))
+        match self.element {
+            Some(ref df) => Ok(ArrayOp::select(df, selection)),
+            None => {
+                let all_rows = selection.first().map_or(true, |x| x.as_ref().is_full());
+                self.coerce(DataFrame::read_select(&self.container, selection)?, all_rows)
+            }
+        }
     }
 
     pub fn select_axis<S>(&mut self, axis: usize, selection: S) -> Result<DataFrame>
     where
         S: AsRef<SelectInfoElem>,
     {
-        Ok(ArrayOp::select_axis(self.data()?, axis, selection))
+        let full = SelectInfoElem::full();
+        let slice = selection.as_ref().set_axis(axis, 2, &full);
+        self.select(slice.as_slice())
     }
 
     pub fn save(&mut self, data: DataFrame) -> Result<()> {
@@ -268,20 +988,19 @@ impl<B: Backend> InnerDataFrameElem<B> {
         replace_with::replace_with_or_abort(&mut self.container, |x| data.overwrite(x).unwrap());
         self.column_names = data.get_column_names().into_iter().map(|x| x.to_string()).collect();
         if self.element.is_some() {
-            self.element = Some(data);
+            self.element = Some(self.coerce(data, true)?);
         }
-        Ok(())
+        self.mark_dirty()
     }
 
     pub fn subset<S>(&mut self, selection: &[S]) -> Result<()>
     where
         S: AsRef<SelectInfoElem>,
     {
-        self.index = self.index.select(selection[0].as_ref());
-        replace_with::replace_with_or_abort(&mut self.container, |x| {
-            self.index.overwrite(x).unwrap()
-        });
         let df = self.select(selection)?;
+        let index = self.index.select(selection[0].as_ref());
+        index.write_into(&self.container)?;
+        self.index = index;
         self.save(df)
     }
 
@@ -305,16 +1024,15 @@ impl<B: Backend> TryFrom<DataContainer<B>> for DataFrameElem<B> {
             DataType::DataFrame => {
                 //let grp = container.as_group()?;
                 let index = DataFrameIndex::read(&container)?;
-                let column_names = container
-                    .read_array_attr::<String, Ix1>("column-order")?
-                    .into_raw_vec()
-                    .into_iter()
-                    .collect();
+                let column_names = read_column_order(&container)?.into_iter().collect();
                 let df = InnerDataFrameElem {
                     element: None,
                     container,
                     column_names,
                     index,
+                    dirty: false,
+                    coerce_bool_columns: false,
+                    nan_as_null: false,
                 };
                 Ok(Slot::new(df))
             }
@@ -331,6 +1049,19 @@ impl<B: Backend> DataFrameElem<B> {
         }
         Ok(())
     }
+
+    /// Return the indices of the rows matching the query, e.g.,
+    /// `n_counts > 500 & cell_type in ['T', 'NK']`. Only the columns referenced
+    /// in the query are read. See [`crate::data::dataframe::Query`] for the syntax.
+    pub fn query(&self, expr: &str) -> Result<Vec<usize>> {
+        let query = Query::parse(expr)?;
+        let inner = self.try_inner()?;
+        let columns = query.columns().into_iter()
+            .map(|name| Ok((name, inner.read_column(name)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let mask = query.eval(&columns)?;
+        Ok(mask.into_iter().enumerate().filter_map(|(i, x)| x.then_some(i)).collect())
+    }
 }
 
 /// Container holding general data types.
@@ -340,6 +1071,7 @@ pub struct InnerElem<B: Backend, T> {
     cache_enabled: bool,
     container: DataContainer<B>,
     element: Option<T>,
+    dirty: bool,
 }
 
 impl<B: Backend, T> std::fmt::Display for InnerElem<B, T> {
@@ -370,33 +1102,72 @@ impl<B: Backend, T> InnerElem<B, T> {
         self.cache_enabled = false;
     }
 
+    /// Whether the element has been modified since it was opened or last flushed.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    /// See [`InnerDataFrameElem::get_attr`].
+    pub fn get_attr(&self, name: &str) -> Result<Option<DynScalar>> {
+        self.container.read_dyn_scalar_attr(name)
+    }
+
+    /// See [`InnerDataFrameElem::set_attr`].
+    pub fn set_attr(&mut self, name: &str, value: DynScalar) -> Result<()> {
+        write_custom_attr(&self.container, name, value)?;
+        self.dirty = true;
+        Ok(())
+    }
+
     pub(crate) fn save<D: WriteData + Into<T>>(&mut self, data: D) -> Result<()> {
+        let attrs = custom_attrs(&self.container)?;
         replace_with::replace_with_or_abort(&mut self.container, |x| data.overwrite(x).unwrap());
+        restore_custom_attrs(&self.container, attrs)?;
         self.dtype = data.data_type();
         if self.element.is_some() {
             self.element = Some(data.into());
         }
-        Ok(())
+        self.dirty = true;
+        trace_write(&self.container);
+        touch::<B>(&self.container.file()?)
     }
 }
 
 impl<B: Backend, T: Clone> InnerElem<B, T> {
-    pub fn data<D>(&mut self) -> Result<D>
+    /// Read the data through a shared reference, so that readers do not need
+    /// exclusive access to the slot. Return `None` if the data is going to be
+    /// cached, which requires [`Self::data`] instead.
+    pub(crate) fn data_shared<D>(&self) -> Option<Result<D>>
     where
         D: Into<T> + ReadData + Clone + TryFrom<T>,
         <D as TryFrom<T>>::Error: Into<anyhow::Error>,
     {
         match self.element.as_ref() {
-            Some(data) => Ok(data.clone().try_into().map_err(Into::into)?),
-            None => {
-                let data = D::read(&self.container)?;
-                if self.cache_enabled {
-                    self.element = Some(data.clone().into());
-                }
-                Ok(data)
+            Some(data) => {
+                trace_event!(path = %self.container.path().display(), "read: cache hit");
+                Some(data.clone().try_into().map_err(Into::into))
             }
+            None if self.cache_enabled => None,
+            None => Some(read_traced(&self.container)),
         }
     }
+
+    pub fn data<D>(&mut self) -> Result<D>
+    where
+        D: Into<T> + ReadData + Clone + TryFrom<T>,
+        <D as TryFrom<T>>::Error: Into<anyhow::Error>,
+    {
+        if let Some(data) = self.data_shared() {
+            return data;
+        }
+        let data: D = read_traced(&self.container)?;
+        self.element = Some(data.clone().into());
+        Ok(data)
+    }
 }
 
 impl<B: Backend, T: ReadData + WriteData + Clone> InnerElem<B, T> {
@@ -405,11 +1176,43 @@ impl<B: Backend, T: ReadData + WriteData + Clone> InnerElem<B, T> {
         location: &G,
         name: &str,
     ) -> Result<()> {
-        match self.element.as_ref() {
+        let container = match self.element.as_ref() {
             Some(data) => data.write(location, name)?,
             None => T::read(&self.container)?.write(location, name)?,
         };
-        Ok(())
+        copy_custom_attrs(&self.container, &container)
+    }
+}
+
+impl<B: Backend> InnerElem<B, Data> {
+    /// Append a value to the 1-dimensional array, growing the dataset in place
+    /// instead of rewriting it.
+    pub(crate) fn append_to_list(&mut self, value: DynScalar) -> Result<()> {
+        let value = ArrayData::from(DynArray::from(value));
+        let is_list = matches!(self.dtype, DataType::Array(_))
+            && self.container.as_dataset().is_ok_and(|x| x.shape().ndim() == 1);
+        ensure!(
+            is_list,
+            "cannot append to '{}', which is not a 1-dimensional array",
+            self.container.path().display(),
+        );
+        ensure!(
+            self.dtype == value.data_type(),
+            "cannot append {} to {}",
+            value.data_type(),
+            self.dtype,
+        );
+        if self.container.as_dataset()?.chunk_shape().is_none() {
+            // Datasets written by other tools may not be resizable. They are
+            // rewritten once, after which they can grow in place.
+            let data = ArrayData::read(&self.container)?;
+            return self.save(Data::from(ArrayData::vstack([data, value].into_iter())?));
+        }
+        append_rows(&self.container, &value)?;
+        self.element = None;
+        self.dirty = true;
+        trace_write(&self.container);
+        touch::<B>(&self.container.file()?)
     }
 }
 
@@ -419,12 +1222,18 @@ impl<B: Backend> TryFrom<DataContainer<B>> for Elem<B> {
     type Error = anyhow::Error;
 
     fn try_from(container: DataContainer<B>) -> Result<Self> {
-        let dtype = container.encoding_type()?;
+        // Elements with unsupported encodings are kept, so that they are not
+        // lost when the file is copied. Reading them returns `Data::Unknown`.
+        let dtype = match container.unsupported_encoding() {
+            Some(_) => DataType::Unknown,
+            None => container.encoding_type()?,
+        };
         let elem = InnerElem {
             dtype,
             cache_enabled: false,
             element: None,
             container,
+            dirty: false,
         };
         Ok(Slot::new(elem))
     }
@@ -438,8 +1247,29 @@ impl<B: Backend> Elem<B> {
         }
         Ok(())
     }
+
+    /// Read the data of the element, or `None` if the slot is empty. The slot
+    /// is only locked exclusively if the data is going to be cached.
+    pub fn get_data<D>(&self) -> Result<Option<D>>
+    where
+        D: Into<Data> + ReadData + Clone + TryFrom<Data>,
+        <D as TryFrom<Data>>::Error: Into<anyhow::Error>,
+    {
+        // The read lock must be released before taking the write lock.
+        let shared = self.read().as_ref().map(|x| x.data_shared());
+        match shared {
+            None => Ok(None),
+            Some(Some(data)) => data.map(Some),
+            Some(None) => self.lock().as_mut().map(|x| x.data()).transpose(),
+        }
+    }
 }
 
+/// CSR matrices with at most this many rows keep their `indptr` in memory
+/// after its first use, unless the cache is disabled. Larger matrices only do
+/// so when the cache is enabled.
+pub const INDPTR_CACHE_MAX_ROWS: usize = 1 << 22;
+
 #[derive(Debug)]
 pub struct InnerArrayElem<B: Backend, T> {
     dtype: DataType,
@@ -447,6 +1277,17 @@ pub struct InnerArrayElem<B: Backend, T> {
     cache_enabled: bool,
     container: DataContainer<B>,
     element: Option<T>,
+    /// The `indptr` of a CSR matrix, if it has been read and `cache_indptr` is set.
+    indptr: OnceLock<Vec<usize>>,
+    cache_indptr: bool,
+    /// The chunk cache size set with [`InnerArrayElem::set_chunk_cache`].
+    chunk_cache: Option<usize>,
+    /// See [`InnerArrayElem::set_assume_sorted_indices`].
+    assume_sorted_indices: bool,
+    /// Whether the dense array is stored with its axes reversed, see
+    /// [`DynArray::write_transposed`].
+    transposed: bool,
+    dirty: bool,
 }
 
 impl<B: Backend, T> std::fmt::Display for InnerArrayElem<B, T> {
@@ -470,44 +1311,295 @@ impl<B: Backend, T> InnerArrayElem<B, T> {
         &self.shape
     }
 
+    /// Cache the data after it is first read. The `indptr` of a CSR matrix
+    /// is then cached regardless of [`INDPTR_CACHE_MAX_ROWS`].
     pub fn enable_cache(&mut self) {
         self.cache_enabled = true;
+        self.cache_indptr = true;
     }
 
     pub fn disable_cache(&mut self) {
         if self.element.is_some() {
             self.element = None;
         }
-        self.cache_enabled = false;
+        self.cache_enabled = false;
+        self.cache_indptr = false;
+        self.indptr.take();
+    }
+
+    /// Whether the element has been modified since it was opened or last flushed.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    /// See [`InnerDataFrameElem::get_attr`].
+    pub fn get_attr(&self, name: &str) -> Result<Option<DynScalar>> {
+        self.container.read_dyn_scalar_attr(name)
+    }
+
+    /// See [`InnerDataFrameElem::set_attr`].
+    pub fn set_attr(&mut self, name: &str, value: DynScalar) -> Result<()> {
+        write_custom_attr(&self.container, name, value)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// The `indptr` of the element if it is a CSR matrix, i.e., the cumulative
+    /// number of stored entries per row. Return `None` for other types. The
+    /// `indptr` is kept in memory for later calls if `cache_indptr` is set.
+    pub(crate) fn csr_indptr(&self) -> Result<Option<Cow<'_, [usize]>>> {
+        match self.dtype {
+            DataType::CsrMatrix(_) => {
+                if let Some(indptr) = self.indptr.get() {
+                    return Ok(Some(Cow::Borrowed(indptr)));
+                }
+                let group = self.container.as_group()?;
+                let indptr = group.open_dataset("indptr")?.read_array::<usize, ndarray::Ix1>()?.into_raw_vec();
+                if self.cache_indptr {
+                    Ok(Some(Cow::Borrowed(self.indptr.get_or_init(|| indptr))))
+                } else {
+                    Ok(Some(Cow::Owned(indptr)))
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Read the selection of a dense array directly into `out` in row-major
+    /// order, see [`DatasetOp::read_into`]. Return `false` without reading if
+    /// the element is not a dense array, is stored transposed, or caching is
+    /// enabled, in which case [`Self::select`] should be used.
+    pub fn read_dense_into<U, S>(&self, selection: &[S], out: &mut [U]) -> Result<bool>
+    where
+        U: BackendData,
+        S: AsRef<SelectInfoElem>,
+    {
+        if !matches!(self.dtype, DataType::Array(_)) || self.cache_enabled {
+            return Ok(false);
+        }
+        if self.transposed {
+            return Ok(false);
+        }
+        let dataset = self.container.as_dataset()?;
+        trace_event!(path = %self.container.path().display(), "read: into buffer");
+        dataset.read_into(selection, out)?;
+        Ok(true)
+    }
+
+    /// The number of stored entries of a CSR or CSC matrix, or `None` for
+    /// other types.
+    pub fn nnz(&self) -> Result<Option<usize>> {
+        match self.dtype {
+            DataType::CsrMatrix(_) | DataType::CscMatrix(_) => {
+                let data = self.container.as_group()?.open_dataset("data")?;
+                Ok(Some(data.shape()[0]))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Read a whole CSR or CSC matrix directly into its components, which
+    /// must have [`Self::nnz`], [`Self::nnz`] and `n + 1` entries, where `n`
+    /// is the number of rows of a CSR matrix or columns of a CSC matrix. See
+    /// [`Self::read_dense_into`] for the return value.
+    pub fn read_sparse_into<U, I>(&self, data: &mut [U], indices: &mut [I], indptr: &mut [I]) -> Result<bool>
+    where
+        U: BackendData,
+        I: BackendData,
+    {
+        if !matches!(self.dtype, DataType::CsrMatrix(_) | DataType::CscMatrix(_)) || self.cache_enabled {
+            return Ok(false);
+        }
+        trace_event!(path = %self.container.path().display(), "read: into buffer");
+        let group = self.container.as_group()?;
+        let full = [SelectInfoElem::full()];
+        group.open_dataset("data")?.read_into(&full, data)?;
+        group.open_dataset("indices")?.read_into(&full, indices)?;
+        group.open_dataset("indptr")?.read_into(&full, indptr)?;
+        Ok(true)
+    }
+
+    /// Trust the column indices of a CSR matrix to be sorted and unique when
+    /// reading it, so that they are neither checked for order nor sorted, see
+    /// [`ReadData::read_assume_sorted`]. The offsets and the bounds of the
+    /// indices are still checked. Unsorted indices then give a matrix whose
+    /// lookups are wrong.
+    pub fn set_assume_sorted_indices(&mut self, assume_sorted_indices: bool) {
+        self.assume_sorted_indices = assume_sorted_indices;
+    }
+
+    /// Set the size in bytes of the cache of decompressed chunks of a dense
+    /// array, see [`DatasetOp::with_chunk_cache`]. The size is kept until the
+    /// array is replaced, and the chunked iterators do not change it.
+    pub fn set_chunk_cache(&mut self, nbytes: usize) -> Result<()> {
+        ensure!(
+            matches!(self.container, DataContainer::Dataset(_)),
+            "the chunk cache can only be set for dense arrays, not {}",
+            self.dtype,
+        );
+        self.reopen_with_chunk_cache(nbytes)?;
+        self.chunk_cache = Some(nbytes);
+        Ok(())
+    }
+
+    /// Grow the chunk cache of a dense array to hold a full row of chunks, so
+    /// that reading the rows in order decompresses every chunk only once.
+    fn cache_chunk_row(&mut self) -> Result<()> {
+        let DataContainer::Dataset(dataset) = &self.container else { return Ok(()) };
+        let (DataType::Array(ty), Some(chunk)) = (self.dtype, dataset.chunk_shape()) else { return Ok(()) };
+        if self.chunk_cache.is_some() || self.cache_enabled {
+            return Ok(());
+        }
+        let row_chunks: usize = self.shape.as_ref().iter().zip(chunk.as_ref()).skip(1)
+            .map(|(&n, &c)| (n + c - 1) / c.max(1))
+            .product();
+        let nbytes = row_chunks * chunk.as_ref().iter().product::<usize>() * item_size(ty).unwrap_or(16);
+        if nbytes <= MAX_AUTO_CHUNK_CACHE && dataset.chunk_cache()?.is_some_and(|n| n < nbytes) {
+            trace_event!(path = %self.container.path().display(), bytes = nbytes, "read: resize chunk cache");
+            // The cache only speeds up reading, so the array is read with its
+            // current cache if it cannot be resized.
+            if let Err(e) = self.reopen_with_chunk_cache(nbytes) {
+                warn!("cannot resize the chunk cache of '{}': {}", self.container.path().display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reopen a dense array with a chunk cache of `nbytes` bytes. If this
+    /// fails, the array is reopened with its previous settings.
+    fn reopen_with_chunk_cache(&mut self, nbytes: usize) -> Result<()> {
+        let DataContainer::Dataset(dataset) = &self.container else { return Ok(()) };
+        let file = dataset.file()?;
+        let path = dataset.path().to_string_lossy().into_owned();
+        // Make sure the dataset can be opened by its path before it is closed,
+        // so that it can be reopened if setting the cache fails.
+        drop(file.open_dataset(&path)?);
+        // The root group stands in for the dataset while it is closed.
+        let placeholder = DataContainer::Group(file.open_group("/")?);
+        let DataContainer::Dataset(dataset) = std::mem::replace(&mut self.container, placeholder) else {
+            unreachable!()
+        };
+        let (dataset, result) = match dataset.with_chunk_cache(nbytes) {
+            Ok(dataset) => (Ok(dataset), Ok(())),
+            Err(e) => (file.open_dataset(&path), Err(e)),
+        };
+        match dataset {
+            Ok(dataset) => {
+                self.container = DataContainer::Dataset(dataset);
+                result
+            }
+            Err(e) => Err(e.context(format!("cannot reopen '{}' after setting its chunk cache", path))),
+        }
+    }
+
+    /// Write `data` in place of the stored data, keeping the custom attributes.
+    /// Dense arrays stored transposed are written transposed again, into a
+    /// temporary container that replaces the old one once it is complete.
+    fn overwrite_data(&mut self, data: &T) -> Result<()>
+    where
+        T: WriteData + Clone + TryInto<DynArray>,
+        <T as TryInto<DynArray>>::Error: Into<anyhow::Error>,
+    {
+        let attrs = custom_attrs(&self.container)?;
+        if self.transposed && matches!(data.data_type(), DataType::Array(_)) {
+            let array: DynArray = data.clone().try_into().map_err(Into::into)?;
+            let file = self.container.file()?;
+            let path = self.container.path().to_string_lossy().into_owned();
+            let tmp = format!("{}.transposed", path);
+            if let Err(e) = array.write_transposed(&file, &tmp) {
+                if file.exists(&tmp)? {
+                    file.delete(&tmp)?;
+                }
+                return Err(e);
+            }
+            replace_by_tmp(&file, &tmp, &path)?;
+            self.container = DataContainer::open(&file, &path)?;
+        } else {
+            replace_with::replace_with_or_abort(&mut self.container, |x| data.overwrite(x).unwrap());
+            self.transposed = false;
+        }
+        restore_custom_attrs(&self.container, attrs)
     }
 
-    pub(crate) fn save<D: HasShape + WriteArrayData + Into<T>>(&mut self, data: D) -> Result<()> {
-        replace_with::replace_with_or_abort(&mut self.container, |x| data.overwrite(x).unwrap());
+    /// Append rows to the element in place. See [`append_rows`].
+    pub(crate) fn append_rows(&mut self, data: &ArrayData) -> Result<()> {
+        ensure!(
+            self.dtype == data.data_type(),
+            "cannot append {} to {}",
+            data.data_type(),
+            self.dtype,
+        );
+        append_rows(&self.container, data)?;
+        self.shape[0] += data.shape()[0];
+        self.element = None;
+        self.indptr.take();
+        self.dirty = true;
+        trace_write(&self.container);
+        touch::<B>(&self.container.file()?)
+    }
+
+    /// Keep only the first `n` rows of the element, undoing [`Self::append_rows`].
+    pub(crate) fn truncate_rows(&mut self, n: usize) -> Result<()> {
+        truncate_rows(&self.container, n)?;
+        self.shape[0] = n;
+        self.element = None;
+        self.indptr.take();
+        self.dirty = true;
+        touch::<B>(&self.container.file()?)
+    }
+
+    pub(crate) fn save<D: HasShape + WriteArrayData + Into<T>>(&mut self, data: D) -> Result<()>
+    where
+        T: WriteData + Clone + TryInto<DynArray>,
+        <T as TryInto<DynArray>>::Error: Into<anyhow::Error>,
+    {
         self.dtype = data.data_type();
         self.shape = data.shape();
+        let data: T = data.into();
+        self.overwrite_data(&data)?;
         if self.element.is_some() {
-            self.element = Some(data.into());
+            self.element = Some(data);
         }
-        Ok(())
+        self.indptr.take();
+        self.chunk_cache = None;
+        self.dirty = true;
+        trace_write(&self.container);
+        touch::<B>(&self.container.file()?)
     }
 }
 
 impl<B: Backend, T: Clone> InnerArrayElem<B, T> {
-    pub fn data<D>(&mut self) -> Result<D>
+    /// See [`InnerElem::data_shared`].
+    pub(crate) fn data_shared<D>(&self) -> Option<Result<D>>
     where
         D: Into<T> + ReadData + Clone + TryFrom<T>,
         <D as TryFrom<T>>::Error: Into<anyhow::Error>,
     {
         match self.element.as_ref() {
-            Some(data) => Ok(data.clone().try_into().map_err(Into::into)?),
-            None => {
-                let data = D::read(&self.container)?;
-                if self.cache_enabled {
-                    self.element = Some(data.clone().into());
-                }
-                Ok(data)
+            Some(data) => {
+                trace_event!(path = %self.container.path().display(), "read: cache hit");
+                Some(data.clone().try_into().map_err(Into::into))
             }
+            None if self.cache_enabled => None,
+            None => Some(read_traced_assume_sorted(&self.container, self.assume_sorted_indices)),
+        }
+    }
+
+    pub fn data<D>(&mut self) -> Result<D>
+    where
+        D: Into<T> + ReadData + Clone + TryFrom<T>,
+        <D as TryFrom<T>>::Error: Into<anyhow::Error>,
+    {
+        if let Some(data) = self.data_shared() {
+            return data;
         }
+        let data: D = read_traced_assume_sorted(&self.container, self.assume_sorted_indices)?;
+        self.element = Some(data.clone().into());
+        Ok(data)
     }
 }
 
@@ -517,28 +1609,56 @@ impl<B: Backend, T: ReadArrayData + WriteArrayData + Clone> InnerArrayElem<B, T>
         location: &G,
         name: &str,
     ) -> Result<()> {
-        match self.element.as_ref() {
+        let container = match self.element.as_ref() {
             Some(data) => data.write(location, name)?,
-            None => T::read(&self.container)?.write(location, name)?,
+            None => T::read_assume_sorted(&self.container, self.assume_sorted_indices)?.write(location, name)?,
         };
-        Ok(())
+        copy_custom_attrs(&self.container, &container)
     }
 }
 
-impl<B: Backend, T: ArrayOp + Clone> InnerArrayElem<B, T> {
+impl<B: Backend, T: ArrayOp + Clone + From<ArrayData>> InnerArrayElem<B, T> {
     pub fn select<D, S>(&mut self, selection: &[S]) -> Result<D>
+    where
+        D: Into<T> + TryFrom<T> + ReadArrayData + Clone,
+        S: AsRef<SelectInfoElem>,
+        <D as TryFrom<T>>::Error: Into<anyhow::Error>,
+    {
+        match self.select_shared(selection) {
+            Some(data) => data,
+            None => self.data(),
+        }
+    }
+
+    /// Like [`Self::select`], but return `None` if the data is going to be
+    /// cached. See [`InnerElem::data_shared`].
+    pub(crate) fn select_shared<D, S>(&self, selection: &[S]) -> Option<Result<D>>
     where
         D: Into<T> + TryFrom<T> + ReadArrayData + Clone,
         S: AsRef<SelectInfoElem>,
         <D as TryFrom<T>>::Error: Into<anyhow::Error>,
     {
         if selection.as_ref().iter().all(|x| x.as_ref().is_full()) {
-            self.data()
+            self.data_shared()
         } else {
-            match self.element.as_ref() {
-                Some(data) => Ok(data.select(selection).try_into().map_err(Into::into)?),
-                None => D::read_select(&self.container, selection),
-            }
+            trace_event!(
+                path = %self.container.path().display(),
+                shape = ?selection.iter().zip(self.shape.as_ref())
+                    .map(|(s, &n)| s.as_ref().output_len(n))
+                    .collect::<Vec<_>>(),
+                "read: selection",
+            );
+            Some(match self.element.as_ref() {
+                Some(data) => data.select(selection).try_into().map_err(Into::into),
+                // Rows of CSR matrices are located with the cached `indptr`.
+                None if self.cache_indptr && selection.first().is_some_and(|x| !x.as_ref().is_full()) => match self.csr_indptr() {
+                    Ok(Some(indptr)) => read_csr_rows(&self.container, &indptr, selection, self.assume_sorted_indices)
+                        .and_then(|x| T::from(x).try_into().map_err(Into::into)),
+                    Ok(None) => D::read_select_assume_sorted(&self.container, selection, self.assume_sorted_indices),
+                    Err(e) => Err(e),
+                },
+                None => D::read_select_assume_sorted(&self.container, selection, self.assume_sorted_indices),
+            })
         }
     }
 
@@ -556,7 +1676,7 @@ impl<B: Backend, T: ArrayOp + Clone> InnerArrayElem<B, T> {
     }
 }
 
-impl<B: Backend, T: ReadArrayData + WriteArrayData + ArrayOp + Clone> InnerArrayElem<B, T> {
+impl<B: Backend, T: ReadArrayData + WriteArrayData + ArrayOp + Clone + From<ArrayData>> InnerArrayElem<B, T> {
     pub fn export_select<O, G>(
         &mut self,
         selection: &[&SelectInfoElem],
@@ -570,8 +1690,8 @@ impl<B: Backend, T: ReadArrayData + WriteArrayData + ArrayOp + Clone> InnerArray
         if selection.as_ref().into_iter().all(|x| x.is_full()) {
             self.export::<O, _>(location, name)
         } else {
-            self.select::<T, _>(selection)?.write(location, name)?;
-            Ok(())
+            let container = self.select::<T, _>(selection)?.write(location, name)?;
+            copy_custom_attrs(&self.container, &container)
         }
     }
 
@@ -596,23 +1716,30 @@ impl<B: Backend, T: ReadArrayData + WriteArrayData + ArrayOp + Clone> InnerArray
     pub(crate) fn subset<S>(&mut self, selection: &[S]) -> Result<()>
     where
         S: AsRef<SelectInfoElem>,
+        T: TryInto<DynArray>,
+        <T as TryInto<DynArray>>::Error: Into<anyhow::Error>,
     {
         let data = match self.element.as_ref() {
             Some(data) => data.select(selection),
-            None => T::read_select(&self.container, selection)?,
+            None => T::read_select_assume_sorted(&self.container, selection, self.assume_sorted_indices)?,
         };
 
         self.shape = data.shape();
-        replace_with::replace_with_or_abort(&mut self.container, |x| data.overwrite(x).unwrap());
+        self.overwrite_data(&data)?;
         if self.element.is_some() {
             self.element = Some(data);
         }
-        Ok(())
+        self.indptr.take();
+        self.dirty = true;
+        trace_write(&self.container);
+        touch::<B>(&self.container.file()?)
     }
 
     pub(crate) fn subset_axis<S>(&mut self, axis: usize, selection: S) -> Result<()>
     where
         S: AsRef<SelectInfoElem>,
+        T: TryInto<DynArray>,
+        <T as TryInto<DynArray>>::Error: Into<anyhow::Error>,
     {
         let full = SelectInfoElem::full();
         let slice = selection
@@ -624,18 +1751,32 @@ impl<B: Backend, T: ReadArrayData + WriteArrayData + ArrayOp + Clone> InnerArray
 
 pub type ArrayElem<B> = Slot<InnerArrayElem<B, ArrayData>>;
 
+/// The number of entries converted at a time by [`ArrayElem::astype`].
+const ASTYPE_CHUNK_NNZ: usize = 1 << 24;
+
+/// The largest chunk cache the chunked iterators set automatically.
+const MAX_AUTO_CHUNK_CACHE: usize = 1 << 30;
+
 /// Container holding matrix data types.
 impl<B: Backend> TryFrom<DataContainer<B>> for ArrayElem<B> {
     type Error = anyhow::Error;
 
     fn try_from(container: DataContainer<B>) -> Result<Self> {
         let dtype = container.encoding_type()?;
+        let shape = ArrayData::get_shape(&container)?;
+        let transposed = container_is_transposed(&container)?;
         let elem = InnerArrayElem {
             dtype,
-            shape: ArrayData::get_shape(&container)?,
+            cache_indptr: shape.ndim() > 0 && shape[0] <= INDPTR_CACHE_MAX_ROWS,
+            shape,
             cache_enabled: false,
             element: None,
+            indptr: OnceLock::new(),
+            chunk_cache: None,
+            assume_sorted_indices: false,
+            transposed,
             container,
+            dirty: false,
         };
         Ok(Slot::new(elem))
     }
@@ -648,7 +1789,7 @@ impl<B: Backend> ArrayElemOp for ArrayElem<B> {
             <T as TryFrom<ArrayData>>::Error: Into<anyhow::Error>;
     
     fn shape(&self) -> Option<Shape> {
-        self.lock().as_ref().map(|x| x.shape().clone())
+        self.read().as_ref().map(|x| x.shape().clone())
     }
 
     fn get<D>(&self) -> Result<Option<D>>
@@ -656,7 +1797,13 @@ impl<B: Backend> ArrayElemOp for ArrayElem<B> {
             D: ReadData + Into<ArrayData> + TryFrom<ArrayData> + Clone,
             <D as TryFrom<ArrayData>>::Error: Into<anyhow::Error>
     {
-        self.lock().as_mut().map(|x| x.data()).transpose()
+        // The read lock must be released before taking the write lock.
+        let shared = self.read().as_ref().map(|x| x.data_shared());
+        match shared {
+            None => Ok(None),
+            Some(Some(data)) => data.map(Some),
+            Some(None) => self.lock().as_mut().map(|x| x.data()).transpose(),
+        }
     }
 
     fn slice<D, S>(&self, slice: S) -> Result<Option<D>>
@@ -664,7 +1811,12 @@ impl<B: Backend> ArrayElemOp for ArrayElem<B> {
             D: ReadArrayData + Into<ArrayData> + TryFrom<ArrayData> + ArrayOp + Clone,
             S: AsRef<[SelectInfoElem]>,
             <D as TryFrom<ArrayData>>::Error: Into<anyhow::Error> {
-        self.lock().as_mut().map(|x| x.select(slice.as_ref())).transpose()
+        let shared = self.read().as_ref().map(|x| x.select_shared(slice.as_ref()));
+        match shared {
+            None => Ok(None),
+            Some(Some(data)) => data.map(Some),
+            Some(None) => self.lock().as_mut().map(|x| x.select(slice.as_ref())).transpose(),
+        }
     }
 
     fn iter<T>(&self, chunk_size: usize) -> Self::ArrayIter<T>
@@ -685,27 +1837,147 @@ impl<B: Backend> ArrayElem<B> {
         Ok(())
     }
 
+    /// Convert the values of the element to `ty` in place, see
+    /// [`ArrayData::astype`]. The element is converted chunk by chunk into a
+    /// new container in the file, which then replaces the old one, so that the
+    /// element is left untouched if a value cannot be converted. CSC matrices
+    /// cannot be written chunk by chunk and are converted in memory.
+    pub fn astype(&self, ty: ScalarType) -> Result<()> {
+        let path = {
+            let mut inner = self.try_inner()?;
+            if !check_astype(&inner.dtype, ty)? {
+                return Ok(());
+            }
+            if matches!(inner.dtype, DataType::CscMatrix(_)) || inner.shape[0] == 0 {
+                let data = inner.data::<ArrayData>()?.cast(ty)?;
+                return inner.save(data);
+            }
+            inner.container.path()
+        };
+        self.rewrite_by_chunk(ChunkStrategy::ByNnz(ASTYPE_CHUNK_NNZ), |chunk| chunk.cast(ty))
+            .with_context(|| format!("cannot convert '{}' to {}", path.display(), ty))
+    }
+
+    /// Replace each chunk of `chunk_size` rows of the element by the result of
+    /// `f`, which must have the same shape. Like [`ArrayElem::astype`], the
+    /// chunks are written into a new container, so that the element is left
+    /// untouched if `f` fails. CSC matrices cannot be written chunk by chunk.
+    pub fn map_chunks_in_place<F>(&self, chunk_size: usize, mut f: F) -> Result<()>
+    where
+        F: FnMut(ArrayData) -> Result<ArrayData>,
+    {
+        let path = {
+            let inner = self.try_inner()?;
+            ensure!(
+                !matches!(inner.dtype, DataType::CscMatrix(_)),
+                "cannot map the chunks of a CSC matrix in place"
+            );
+            if inner.shape[0] == 0 {
+                return Ok(());
+            }
+            inner.container.path()
+        };
+        self.rewrite_by_chunk(ChunkStrategy::ByRows(chunk_size), |chunk| {
+            let shape = chunk.shape();
+            let result = f(chunk)?;
+            ensure!(
+                result.shape().as_ref() == shape.as_ref(),
+                "the function changed the shape of a chunk from {} to {}",
+                shape,
+                result.shape(),
+            );
+            Ok(result)
+        }).with_context(|| format!("cannot map the chunks of '{}'", path.display()))
+    }
+
+    /// Write the chunks of the element transformed by `f` into a temporary
+    /// container, and replace the element by it once all chunks have been
+    /// written. The temporary container is deleted if an error occurs.
+    fn rewrite_by_chunk<F>(&self, strategy: ChunkStrategy, mut f: F) -> Result<()>
+    where
+        F: FnMut(ArrayData) -> Result<ArrayData>,
+    {
+        let (file, path) = {
+            let inner = self.try_inner()?;
+            (inner.container.file()?, inner.container.path().to_string_lossy().into_owned())
+        };
+        let tmp = format!("{}.rewrite", path);
+        let mut error = None;
+        let chunks = self.chunked_by::<ArrayData>(strategy)?
+            .map_while(|(chunk, _, _)| match f(chunk) {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            });
+        let result = ArrayChunk::write_by_chunk(chunks, &file, &tmp);
+        if let Some(e) = error.or(result.err()) {
+            if file.exists(&tmp)? {
+                file.delete(&tmp)?;
+            }
+            return Err(e);
+        }
+
+        let mut inner = self.inner();
+        if let Err(e) = DataContainer::open(&file, &tmp).and_then(|x| copy_custom_attrs(&inner.container, &x)) {
+            file.delete(&tmp)?;
+            return Err(e);
+        }
+        replace_by_tmp(&file, &tmp, &path)?;
+        inner.container = DataContainer::open(&file, &path)?;
+        inner.dtype = inner.container.encoding_type()?;
+        inner.transposed = container_is_transposed(&inner.container)?;
+        inner.element = None;
+        inner.indptr.take();
+        inner.chunk_cache = None;
+        inner.dirty = true;
+        trace_write(&inner.container);
+        touch::<B>(&file)
+    }
+
     pub fn chunked<T>(&self, chunk_size: usize) -> ChunkedArrayElem<B, T>
     where
         T: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
     {
         ChunkedArrayElem::new(self.clone(), chunk_size)
     }
+
+    /// Like [`Self::chunked`], but the chunk boundaries are determined by `strategy`.
+    pub fn chunked_by<T>(&self, strategy: ChunkStrategy) -> Result<ChunkedArrayElem<B, T>>
+    where
+        T: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
+    {
+        ChunkedArrayElem::with_strategy(self.clone(), strategy)
+    }
+
+    /// Iterate over minibatches of `batch_size` rows in a random order
+    /// determined by `seed`.
+    pub fn shuffled_chunks<T>(&self, batch_size: usize, seed: u64) -> ShuffledChunks<Self, T> {
+        ShuffledChunks::new(self.clone(), batch_size, None, seed)
+    }
+
+    /// Like [`Self::shuffled_chunks`], but rows are only shuffled within a
+    /// sliding buffer of `buffer_size` rows, which keeps reads more sequential.
+    pub fn approx_shuffled_chunks<T>(&self, batch_size: usize, buffer_size: usize, seed: u64) -> ShuffledChunks<Self, T> {
+        ShuffledChunks::new(self.clone(), batch_size, Some(buffer_size), seed)
+    }
 }
 
 /// Horizontal concatenated dataframe elements.
 pub struct StackedDataFrame<B: Backend> {
-    column_names: IndexSet<String>,
     elems: Arc<Vec<DataFrameElem<B>>>,
     index: VecVecIndex,
+    /// The columns shared by all the components, see [`StackedDataFrame::column_names`].
+    column_names: Arc<RwLock<IndexSet<String>>>,
 }
 
 impl<B: Backend> Clone for StackedDataFrame<B> {
     fn clone(&self) -> Self {
         Self {
-            column_names: self.column_names.clone(),
             elems: self.elems.clone(),
             index: self.index.clone(),
+            column_names: self.column_names.clone(),
         }
     }
 }
@@ -715,7 +1987,7 @@ impl<B: Backend> std::fmt::Display for StackedDataFrame<B> {
         write!(
             f,
             "stacked dataframe with columns: '{}'",
-            self.column_names.iter().join("', '")
+            self.column_names().iter().join("', '")
         )
     }
 }
@@ -726,7 +1998,7 @@ impl<B: Backend> StackedDataFrame<B> {
     }
 
     pub fn width(&self) -> usize {
-        self.column_names.len()
+        self.column_names().len()
     }
 
     pub fn height(&self) -> usize {
@@ -736,50 +2008,74 @@ impl<B: Backend> StackedDataFrame<B> {
     pub fn new(elems: Vec<DataFrameElem<B>>) -> Result<Self> {
         let index = elems
             .iter()
-            .map(|x| x.lock().as_ref().map(|x| x.height()).unwrap_or(0))
+            .map(|x| x.read().as_ref().map(|x| x.height()).unwrap_or(0))
             .collect();
         if elems.iter().all(|x| x.is_empty()) {
             Ok(Self {
-                column_names: IndexSet::new(),
                 elems: Arc::new(elems),
                 index,
+                column_names: Default::default(),
             })
         } else if elems.iter().all(|x| !x.is_empty()) {
-            let column_names = elems
+            let shared = elems
                 .iter()
-                .map(|x| x.inner().get_column_names().clone())
+                .flat_map(|x| x.read().as_ref().map(|x| x.get_column_names().clone()))
                 .reduce(|shared_keys, next_keys| {
                     shared_keys
                         .intersection(&next_keys)
                         .map(|x| x.to_owned())
                         .collect()
                 })
-                .unwrap_or(IndexSet::new());
-            Ok(Self {
-                column_names,
+                .unwrap_or_default();
+            let df = Self {
                 elems: Arc::new(elems),
                 index,
-            })
+                column_names: Arc::new(RwLock::new(shared.clone())),
+            };
+            let ignored: IndexSet<String> = df
+                .elems
+                .iter()
+                .flat_map(|x| x.inner().get_column_names().clone())
+                .filter(|x| !shared.contains(x))
+                .collect();
+            if !ignored.is_empty() {
+                warn!(
+                    "these columns are not present in all dataframes and will be ignored: '{}'",
+                    ignored.iter().join("', '")
+                );
+            }
+            Ok(df)
         } else {
             bail!("slots must be either all empty or all full");
         }
     }
 
-    pub fn get_column_names(&self) -> &IndexSet<String> {
-        &self.column_names
+    /// Names of the columns shared by all the component dataframes. They are
+    /// computed when the stacked dataframe is created and updated by
+    /// [`StackedDataFrame::set_column`]; columns added to or removed from the
+    /// components directly are not reflected.
+    pub fn column_names(&self) -> IndexSet<String> {
+        self.column_names.read().clone()
+    }
+
+    /// Borrow the shared column names without copying them. The guard must be
+    /// dropped before calling [`StackedDataFrame::set_column`].
+    pub fn get_column_names(&self) -> RwLockReadGuard<'_, IndexSet<String>> {
+        self.column_names.read()
     }
 
     pub fn data(&self) -> Result<DataFrame> {
-        let df = if self.column_names.is_empty() || self.elems.is_empty() {
+        let column_names = self.column_names();
+        let df = if column_names.is_empty() || self.elems.is_empty() {
             DataFrame::empty()
         } else {
             let _sc = polars::datatypes::string_cache::StringCacheHolder::hold();
             let mut elems = self.elems.iter();
             let mut columns = elems.next().unwrap().inner().data()?
-                .columns(self.column_names.iter())?.into_iter().cloned().collect::<Vec<_>>();
+                .columns(column_names.iter())?.into_iter().cloned().collect::<Vec<_>>();
             elems.try_for_each(|el| {
                 let mut inner = el.inner();
-                let col = inner.data()?.columns(self.column_names.iter())?;
+                let col = inner.data()?.columns(column_names.iter())?;
                 columns.iter_mut().zip(col.into_iter()).try_for_each(|(a, b)| {
                     a.append(b)?;
                     Ok::<_, anyhow::Error>(())
@@ -794,6 +2090,9 @@ impl<B: Backend> StackedDataFrame<B> {
     where
         S: AsRef<SelectInfoElem>,
     {
+        if BoundedSelectInfo::try_new(&selection, &Shape::from((self.height(), self.width())))?.is_identity() {
+            return self.data();
+        }
         let (indices, mapping) = self.index.split_select(selection.as_ref()[0].as_ref());
         let dfs = self
             .elems
@@ -824,13 +2123,106 @@ impl<B: Backend> StackedDataFrame<B> {
         }
     }
 
-    // TODO: this is not efficient, we should use the index to select the columns
+    /// Read a column by concatenating the corresponding columns of the component
+    /// dataframes. Only the requested column is read from each component.
     pub fn column(&self, name: &str) -> Result<Series> {
-        if self.column_names.contains(name) {
-            Ok(self.data()?.column(name)?.clone())
-        } else {
-            bail!("key is not present");
+        ensure!(
+            self.column_names().contains(name),
+            "column '{}' is not present in all dataframes",
+            name
+        );
+        let _sc = polars::datatypes::string_cache::StringCacheHolder::hold();
+        let mut elems = self.elems.iter();
+        let mut series = elems.next().unwrap().inner().read_column(name)?;
+        elems.try_for_each(|el| {
+            series.append(&el.inner().read_column(name)?)?;
+            Ok::<_, anyhow::Error>(())
+        })?;
+        Ok(series)
+    }
+
+    /// Compute summary statistics of the shared columns.
+    pub fn describe(&self) -> Result<DataFrameSummary> {
+        let first = match self.elems.first() {
+            Some(el) => el,
+            None => return DataFrameSummary::from_columns(std::iter::empty()),
+        };
+        let columns = self.column_names().into_iter().filter_map(|name| {
+            match first.inner().is_string_column(&name) {
+                Ok(true) => None,
+                Ok(false) => Some(self.column(&name)),
+                Err(e) => Some(Err(e)),
+            }
+        });
+        DataFrameSummary::from_columns(columns)
+    }
+
+    /// Count the nulls in each of the shared columns.
+    pub fn null_counts(&self) -> Result<IndexMap<String, usize>> {
+        let mut counts: IndexMap<_, _> = self.column_names().into_iter().map(|x| (x, 0)).collect();
+        self.elems.iter().try_for_each(|el| {
+            for (name, n) in el.inner().null_counts()? {
+                if let Some(count) = counts.get_mut(&name) {
+                    *count += n;
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+        Ok(counts)
+    }
+
+    /// Add or replace a column. The values are split according to the row ranges
+    /// of the component dataframes and written back to each component. The
+    /// values are checked before any component is written, and if writing a
+    /// component fails, the components already written are restored.
+    pub fn set_column<S: IntoSeries>(&self, name: &str, values: S) -> Result<()> {
+        let mut values = values.into_series();
+        values.rename(name);
+        let height = self.height();
+        ensure!(
+            values.len() == height,
+            "cannot set column '{}' as the length ({}) differs from the number of rows ({})",
+            name,
+            values.len(),
+            height,
+        );
+        check_writable(&values, name)?;
+
+        // The previous column of each component written so far, or `None` if
+        // the component did not have it.
+        let mut written: Vec<(&DataFrameElem<B>, Option<Series>)> = Vec::with_capacity(self.elems.len());
+        let mut offset = 0;
+        let result = self.elems.iter().try_for_each(|el| {
+            let mut inner = el.inner();
+            let n = inner.height();
+            let old = if inner.get_column_names().contains(name) {
+                Some(inner.read_column(name)?)
+            } else {
+                None
+            };
+            inner.set_column(name, values.slice(offset as i64, n))?;
+            written.push((el, old));
+            offset += n;
+            Ok::<_, anyhow::Error>(())
+        });
+        if let Err(e) = result {
+            for (el, old) in written {
+                let mut inner = el.inner();
+                let restored = match old {
+                    Some(old) => inner.set_column(name, old),
+                    None => inner.data().cloned().and_then(|mut df| {
+                        let _ = df.drop_in_place(name)?;
+                        inner.save(df)
+                    }),
+                };
+                if let Err(err) = restored {
+                    warn!("cannot restore column '{}' after a failed write: {}", name, err);
+                }
+            }
+            return Err(e.context(format!("cannot set column '{}'", name)));
         }
+        self.column_names.write().insert(name.to_string());
+        Ok(())
     }
 }
 
@@ -838,6 +2230,11 @@ pub struct InnerStackedArrayElem<B: Backend> {
     shape: Option<Shape>,
     elems: SmallVec<[ArrayElem<B>; 96]>,
     index: VecVecIndex,
+    /// The common format used when the elements are stored in different formats.
+    mixed_format: Option<ArrayFormat>,
+    /// For each element, the column of the element holding each column of the
+    /// stacked array, or `None` if the columns are in the same order.
+    column_maps: Arc<Vec<Option<Vec<usize>>>>,
 }
 
 impl<B: Backend> std::fmt::Display for InnerStackedArrayElem<B> {
@@ -881,12 +2278,7 @@ impl<B: Backend> InnerStackedArrayElem<B> {
         let data = if self.is_empty() {
             None
         } else {
-            let array = self
-                .elems
-                .iter()
-                .flat_map(|x| x.lock().as_mut().map(|i| i.data::<ArrayData>()))
-                .process_results(|x| ArrayOp::vstack(x).unwrap())?;
-            Some(array.try_into().map_err(Into::into)?)
+            Some(self.stack_all(None)?.try_into().map_err(Into::into)?)
         };
         Ok(data)
     }
@@ -899,14 +2291,7 @@ impl<B: Backend> InnerStackedArrayElem<B> {
         let data = if self.is_empty() {
             None
         } else {
-            let array = self
-                .elems
-                .par_iter()
-                .flat_map(|x| x.lock().as_mut().map(|i| i.data::<ArrayData>()))
-                .collect::<Vec<_>>()
-                .into_iter()
-                .process_results(|x| ArrayOp::vstack(x).unwrap())?;
-            Some(array.try_into().map_err(Into::into)?)
+            Some(self.par_stack_all(None)?.try_into().map_err(Into::into)?)
         };
         Ok(data)
     }
@@ -928,6 +2313,22 @@ impl<B: Backend> InnerStackedArrayElem<B> {
     }
  
     pub fn select<D, S>(&self, selection: &[S]) -> Result<Option<D>>
+    where
+        D: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
+        S: AsRef<SelectInfoElem>,
+        <D as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
+    {
+        self.read_select(selection, None)
+    }
+
+    /// Read a selection of the stacked array. The selection on the first axis
+    /// is split across the elements and the pieces are concatenated in the
+    /// requested order. The selection on the second axis is translated to the
+    /// columns of each element if their columns are in a different order.
+    /// Pieces are converted to the `prefer` format before being stacked. If
+    /// `prefer` is `None`, pieces keep their own format unless the elements are
+    /// stored in different formats, in which case csr is used.
+    pub fn read_select<D, S>(&self, selection: &[S], prefer: Option<ArrayFormat>) -> Result<Option<D>>
     where
         D: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
         S: AsRef<SelectInfoElem>,
@@ -935,21 +2336,18 @@ impl<B: Backend> InnerStackedArrayElem<B> {
     {
         let data = if self.is_empty() {
             None
+        } else if self.check_select(selection)?.is_identity() {
+            Some(self.stack_all(prefer)?.try_into().map_err(Into::into)?)
         } else {
-            let (indices, mapping) = self.index.split_select(selection.as_ref()[0].as_ref());
-            let array: ArrayData = self
-                .elems
-                .iter()
-                .enumerate()
-                .flat_map(|(i, el)| {
+            let (indices, mapping) = self.index.split_select(selection[0].as_ref());
+            let array = self.stack(
+                self.elems.iter().enumerate().flat_map(|(i, el)| {
                     indices.get(&i).map(|idx| {
-                        let select: SmallVec<[_; 3]> = std::iter::once(idx)
-                            .chain(selection.as_ref()[1..].iter().map(|x| x.as_ref()))
-                            .collect();
-                        el.inner().select(select.as_slice())
+                        el.inner().select(self.element_select(i, idx, selection).as_slice())
                     })
-                })
-                .process_results(|x| ArrayOp::vstack(x).unwrap())?;
+                }),
+                prefer,
+            )?;
             if let Some(m) = mapping {
                 Some(array
                     .select_axis(0, SelectInfoElem::from(reverse_mapping(m)))
@@ -962,6 +2360,16 @@ impl<B: Backend> InnerStackedArrayElem<B> {
     }
 
     pub fn par_select<D, S>(&self, selection: &[S]) -> Result<Option<D>>
+    where
+        D: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
+        S: AsRef<SelectInfoElem> + Sync,
+        <D as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
+    {
+        self.par_read_select(selection, None)
+    }
+
+    /// Like [`Self::read_select`], but the elements are read in parallel.
+    pub fn par_read_select<D, S>(&self, selection: &[S], prefer: Option<ArrayFormat>) -> Result<Option<D>>
     where
         D: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
         S: AsRef<SelectInfoElem> + Sync,
@@ -969,23 +2377,22 @@ impl<B: Backend> InnerStackedArrayElem<B> {
     {
         let data = if self.is_empty() {
             None
+        } else if self.check_select(selection)?.is_identity() {
+            Some(self.par_stack_all(prefer)?.try_into().map_err(Into::into)?)
         } else {
-            let (indices, mapping) = self.index.split_select(selection.as_ref()[0].as_ref());
-            let array: ArrayData = self
-                .elems
-                .par_iter()
-                .enumerate()
-                .flat_map(|(i, el)| {
-                    indices.get(&i).map(|idx| {
-                        let select: SmallVec<[_; 3]> = std::iter::once(idx)
-                            .chain(selection.as_ref()[1..].iter().map(|x| x.as_ref()))
-                            .collect();
-                        el.inner().select(select.as_slice())
+            let (indices, mapping) = self.index.split_select(selection[0].as_ref());
+            let array = self.stack(
+                self.elems
+                    .par_iter()
+                    .enumerate()
+                    .flat_map(|(i, el)| {
+                        indices.get(&i).map(|idx| {
+                            el.inner().select(self.element_select(i, idx, selection).as_slice())
+                        })
                     })
-                })
-                .collect::<Vec<_>>()
-                .into_iter()
-                .process_results(|x| ArrayOp::vstack(x).unwrap())?;
+                    .collect::<Vec<_>>(),
+                prefer,
+            )?;
             if let Some(m) = mapping {
                 Some(array
                     .select_axis(0, SelectInfoElem::from(reverse_mapping(m)))
@@ -998,6 +2405,94 @@ impl<B: Backend> InnerStackedArrayElem<B> {
         Ok(data)
     }
 
+    /// Read the given rows, in the given order, across the stacked elements.
+    /// See [`Self::read_select`] for the meaning of `prefer`.
+    pub fn read_rows<D>(&self, rows: &[usize], prefer: Option<ArrayFormat>) -> Result<Option<D>>
+    where
+        D: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
+        <D as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
+    {
+        let ndim = match self.shape.as_ref() {
+            Some(s) => s.ndim(),
+            None => return Ok(None),
+        };
+        let full = SelectInfoElem::full();
+        let slice = SelectInfoElem::from(rows).set_axis(0, ndim, &full);
+        self.read_select(slice.as_slice(), prefer)
+    }
+
+    /// Check the selection against the shape of the stacked array.
+    fn check_select<'a, S: AsRef<SelectInfoElem>>(&self, selection: &'a [S]) -> Result<BoundedSelectInfo<'a>> {
+        let shape = self.shape.as_ref().context("the stacked array is empty")?;
+        BoundedSelectInfo::try_new(selection, shape)
+    }
+
+    /// The selection to read from the `i`-th element, given the rows of the
+    /// element to read and the selection of the stacked array.
+    fn element_select<'a, S: AsRef<SelectInfoElem>>(
+        &self,
+        i: usize,
+        rows: &'a SelectInfoElem,
+        selection: &'a [S],
+    ) -> SmallVec<[Cow<'a, SelectInfoElem>; 3]> {
+        let mut select: SmallVec<[_; 3]> = std::iter::once(Cow::Borrowed(rows))
+            .chain(selection[1..].iter().map(|x| Cow::Borrowed(x.as_ref())))
+            .collect();
+        if let (Some(map), Some(columns)) = (&self.column_maps[i], select.get_mut(1)) {
+            let mapped: Vec<usize> = BoundedSelectInfoElem::new(columns.as_ref(), map.len())
+                .iter()
+                .map(|j| map[j])
+                .collect();
+            *columns = Cow::Owned(mapped.into());
+        }
+        select
+    }
+
+    /// Reorder the columns of a full element to those of the stacked array.
+    fn align_columns(&self, i: usize, data: ArrayData) -> ArrayData {
+        match &self.column_maps[i] {
+            Some(map) => data.select_axis(1, SelectInfoElem::from(map.as_slice())),
+            None => data,
+        }
+    }
+
+    /// Read and stack all the elements.
+    fn stack_all(&self, prefer: Option<ArrayFormat>) -> Result<ArrayData> {
+        self.stack(
+            self.elems.iter().enumerate().flat_map(|(i, x)| {
+                x.lock().as_mut().map(|el| el.data::<ArrayData>().map(|d| self.align_columns(i, d)))
+            }),
+            prefer,
+        )
+    }
+
+    /// Like [`Self::stack_all`], but the elements are read in parallel.
+    fn par_stack_all(&self, prefer: Option<ArrayFormat>) -> Result<ArrayData> {
+        self.stack(
+            self.elems
+                .par_iter()
+                .enumerate()
+                .flat_map(|(i, x)| {
+                    x.lock().as_mut().map(|el| el.data::<ArrayData>().map(|d| self.align_columns(i, d)))
+                })
+                .collect::<Vec<_>>(),
+            prefer,
+        )
+    }
+
+    fn stack<I>(&self, pieces: I, prefer: Option<ArrayFormat>) -> Result<ArrayData>
+    where
+        I: IntoIterator<Item = Result<ArrayData>>,
+    {
+        match prefer.or(self.mixed_format) {
+            None => pieces.into_iter().process_results(|x| ArrayOp::vstack(x))?,
+            Some(format) => pieces
+                .into_iter()
+                .map(|x| x?.into_format(format))
+                .process_results(|x| ArrayOp::vstack(x))?,
+        }
+    }
+
     /// Activate the cache for all elements.
     pub fn enable_cache(&self) {
         for el in self.elems.iter() {
@@ -1015,6 +2510,11 @@ impl<B: Backend> InnerStackedArrayElem<B> {
             }
         }
     }
+
+    /// Set the chunk cache of all elements. See [`InnerArrayElem::set_chunk_cache`].
+    pub fn set_chunk_cache(&self, nbytes: usize) -> Result<()> {
+        self.elems.iter().try_for_each(|el| el.try_inner()?.set_chunk_cache(nbytes))
+    }
 }
 
 pub struct StackedArrayElem<B: Backend>(Arc<InnerStackedArrayElem<B>>);
@@ -1080,21 +2580,47 @@ impl<B: Backend> StackedArrayElem<B> {
             shape: None,
             elems: SmallVec::new(),
             index: std::iter::empty().collect(),
+            mixed_format: None,
+            column_maps: Default::default(),
         }))
     }
 
+    /// Elements must have the same dtype, except that dense arrays and sparse
+    /// matrices of the same scalar type can be mixed. Mixed elements are
+    /// converted to csr when read, unless another format is requested.
     pub(crate) fn new(elems: SmallVec<[ArrayElem<B>; 96]>) -> Result<Self> {
-        ensure!(
-            elems
-                .iter()
-                .map(|x| x.lock().as_ref().map(|x| x.dtype()))
-                .all_equal(),
-            "all elements must have the same dtype"
-        );
+        let column_maps = vec![None; elems.len()];
+        Self::with_column_maps(elems, column_maps)
+    }
+
+    /// Like [`Self::new`], but the columns of each element are reordered when
+    /// read: the `j`-th column of the stacked array is the `column_maps[i][j]`-th
+    /// column of the `i`-th element, or its `j`-th column if `column_maps[i]` is
+    /// `None`.
+    pub(crate) fn with_column_maps(
+        elems: SmallVec<[ArrayElem<B>; 96]>,
+        column_maps: Vec<Option<Vec<usize>>>,
+    ) -> Result<Self> {
+        ensure!(column_maps.len() == elems.len(), "expecting a column map for each element");
+        let dtypes: Vec<_> = elems.iter().map(|x| x.read().as_ref().map(|x| x.dtype())).collect();
+        let mixed_format = if dtypes.iter().all_equal() {
+            None
+        } else {
+            let scalar_types: Vec<_> = dtypes.iter().map(|ty| match ty {
+                Some(DataType::Array(t)) | Some(DataType::CsrMatrix(t)) | Some(DataType::CscMatrix(t))
+                | Some(DataType::CooMatrix(t)) => Some(*t),
+                _ => None,
+            }).collect();
+            ensure!(
+                scalar_types.iter().all(|t| t.is_some()) && scalar_types.iter().all_equal(),
+                "all elements must have the same dtype"
+            );
+            Some(ArrayFormat::Csr)
+        };
 
         let shapes: Vec<_> = elems
             .iter()
-            .map(|x| x.lock().as_ref().map(|x| x.shape().clone()))
+            .map(|x| x.read().as_ref().map(|x| x.shape().clone()))
             .collect();
         ensure!(
             shapes.iter().map(|x| x.as_ref().map(|s| &s.as_ref()[1..])).all_equal(),
@@ -1106,15 +2632,61 @@ impl<B: Backend> StackedArrayElem<B> {
             ss[0] = index.len();
             ss
         }));
-        Ok(Self(Arc::new(InnerStackedArrayElem { shape, elems, index })))
+        if let Some(ncols) = shape.as_ref().filter(|s| s.ndim() > 1).map(|s| s[1]) {
+            ensure!(
+                column_maps.iter().flatten().all(|m| m.len() == ncols && m.iter().all(|&j| j < ncols)),
+                "column maps must map each of the {} columns to a column of the element",
+                ncols,
+            );
+        } else {
+            ensure!(
+                column_maps.iter().all(|m| m.is_none()),
+                "column maps require two-dimensional elements",
+            );
+        }
+        let column_maps = Arc::new(column_maps);
+        Ok(Self(Arc::new(InnerStackedArrayElem { shape, elems, index, mixed_format, column_maps })))
     }
 
     pub fn chunked<T>(&self, chunk_size: usize) -> StackedChunkedArrayElem<B, T>
     where
         T: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
     {
-        StackedChunkedArrayElem::new(self.elems.iter().map(|x| x.clone()), chunk_size)
+        StackedChunkedArrayElem::new(self.elems.iter().cloned(), chunk_size, self.column_maps.clone())
+    }
+
+    /// Like [`Self::chunked`], but the chunk boundaries are determined by
+    /// `strategy`. Chunks never span more than one element.
+    pub fn chunked_by<T>(&self, strategy: ChunkStrategy) -> Result<StackedChunkedArrayElem<B, T>>
+    where
+        T: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
+    {
+        StackedChunkedArrayElem::with_strategy(self.elems.iter().cloned(), strategy, self.column_maps.clone())
+    }
+
+    /// Iterate over minibatches of `batch_size` rows in a random order
+    /// determined by `seed`. Rows are drawn across all stacked elements.
+    pub fn shuffled_chunks<T>(&self, batch_size: usize, seed: u64) -> ShuffledChunks<Self, T> {
+        ShuffledChunks::new(self.clone(), batch_size, None, seed)
     }
+
+    /// Like [`Self::shuffled_chunks`], but rows are only shuffled within a
+    /// sliding buffer of `buffer_size` rows, which keeps reads more sequential.
+    pub fn approx_shuffled_chunks<T>(&self, batch_size: usize, buffer_size: usize, seed: u64) -> ShuffledChunks<Self, T> {
+        ShuffledChunks::new(self.clone(), batch_size, Some(buffer_size), seed)
+    }
+}
+
+/// How the rows of an array are split into chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStrategy {
+    /// Chunks of a fixed number of rows.
+    ByRows(usize),
+    /// Chunks of approximately the given number of non-zero entries. For CSR
+    /// matrices the boundaries are computed from the `indptr`, so that rows
+    /// with many entries end up in smaller chunks. For other arrays every
+    /// entry counts. A chunk always contains at least one row.
+    ByNnz(usize),
 }
 
 /// Chunked Arrays
@@ -1123,22 +2695,73 @@ pub struct ChunkedArrayElem<B: Backend, T> {
     elem: ArrayElem<B>,
     /// The chunk size.
     chunk_size: usize,
+    /// The end of every chunk, if the chunks have variable sizes.
+    chunk_ends: Option<Vec<usize>>,
     num_items: usize,
     current_position: usize,
     type_marker: std::marker::PhantomData<T>,
 }
 
 impl<B: Backend, T> ChunkedArrayElem<B, T> {
+    /// The chunk cache of a dense array is grown to hold a full row of chunks
+    /// unless it has been set with [`InnerArrayElem::set_chunk_cache`].
     pub fn new(elem: ArrayElem<B>, chunk_size: usize) -> Self {
-        let num_items = elem.inner().shape()[0];
+        let num_items = {
+            let mut inner = elem.inner();
+            if let Err(e) = inner.cache_chunk_row() {
+                warn!("cannot resize the chunk cache: {}", e);
+            }
+            inner.shape()[0]
+        };
         Self {
             elem,
             chunk_size,
+            chunk_ends: None,
             num_items,
             current_position: 0,
             type_marker: std::marker::PhantomData,
         }
     }
+
+    /// Split the rows into chunks according to `strategy`.
+    pub fn with_strategy(elem: ArrayElem<B>, strategy: ChunkStrategy) -> Result<Self> {
+        let (chunk_size, chunk_ends) = match strategy {
+            ChunkStrategy::ByRows(n) => {
+                ensure!(n > 0, "chunk size must be positive");
+                (n, None)
+            }
+            ChunkStrategy::ByNnz(nnz) => {
+                ensure!(nnz > 0, "target number of non-zero entries must be positive");
+                let inner = elem.inner();
+                match inner.csr_indptr()? {
+                    Some(indptr) => (nnz, Some(nnz_chunk_ends(&indptr, nnz))),
+                    None => {
+                        let row_size: usize = inner.shape().as_ref()[1..].iter().product();
+                        ((nnz / row_size.max(1)).max(1), None)
+                    }
+                }
+            }
+        };
+        let mut chunked = Self::new(elem, chunk_size);
+        chunked.chunk_ends = chunk_ends;
+        Ok(chunked)
+    }
+}
+
+/// Cut the rows at approximately every `target` stored entries, given the
+/// `indptr` of a CSR matrix. Return the end of every chunk.
+fn nnz_chunk_ends(indptr: &[usize], target: usize) -> Vec<usize> {
+    let n = indptr.len().saturating_sub(1);
+    let mut ends = Vec::new();
+    let mut start = 0;
+    while start < n {
+        let end = indptr
+            .partition_point(|&x| x < indptr[start] + target)
+            .clamp(start + 1, n);
+        ends.push(end);
+        start = end;
+    }
+    ends
 }
 
 impl<B, T> Iterator for ChunkedArrayElem<B, T>
@@ -1154,7 +2777,10 @@ where
             None
         } else {
             let i = self.current_position;
-            let j = std::cmp::min(self.num_items, self.current_position + self.chunk_size);
+            let j = match &self.chunk_ends {
+                Some(ends) => ends[ends.partition_point(|&x| x <= i)],
+                None => std::cmp::min(self.num_items, i + self.chunk_size),
+            };
             self.current_position = j;
             let data = self.elem.inner().select_axis(0, SelectInfoElem::from(i..j)).unwrap();
             Some((data, i, j))
@@ -1169,6 +2795,9 @@ where
     <T as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
 {
     fn len(&self) -> usize {
+        if let Some(ends) = &self.chunk_ends {
+            return ends.len();
+        }
         let (n, remain) = div_rem(self.num_items, self.chunk_size);
         if remain == 0 {
             n
@@ -1180,20 +2809,45 @@ where
 
 pub struct StackedChunkedArrayElem<B: Backend, T> {
     arrays: SmallVec<[ChunkedArrayElem<B, T>; 96]>,
+    /// See [`StackedArrayElem::with_column_maps`].
+    column_maps: Arc<Vec<Option<Vec<usize>>>>,
     current_position: usize,
     current_array: usize,
 }
 
 impl<B: Backend, T> StackedChunkedArrayElem<B, T> {
-    pub(crate) fn new<I: Iterator<Item = ArrayElem<B>>>(elems: I, chunk_size: usize) -> Self {
+    pub(crate) fn new<I: Iterator<Item = ArrayElem<B>>>(
+        elems: I,
+        chunk_size: usize,
+        column_maps: Arc<Vec<Option<Vec<usize>>>>,
+    ) -> Self {
         Self {
             arrays: elems
                 .map(|x| ChunkedArrayElem::new(x, chunk_size))
                 .collect(),
+            column_maps,
             current_position: 0,
             current_array: 0,
         }
     }
+
+    pub(crate) fn with_strategy<I>(
+        elems: I,
+        strategy: ChunkStrategy,
+        column_maps: Arc<Vec<Option<Vec<usize>>>>,
+    ) -> Result<Self>
+    where
+        I: Iterator<Item = ArrayElem<B>>,
+    {
+        Ok(Self {
+            arrays: elems
+                .map(|x| ChunkedArrayElem::with_strategy(x, strategy))
+                .collect::<Result<_>>()?,
+            column_maps,
+            current_position: 0,
+            current_array: 0,
+        })
+    }
 }
 
 impl<B, T> Iterator for StackedChunkedArrayElem<B, T>
@@ -1210,6 +2864,13 @@ where
                 let new_start = self.current_position;
                 let new_stop = new_start + stop - start;
                 self.current_position = new_stop;
+                let data = match &self.column_maps[self.current_array] {
+                    Some(map) => data.into().select_axis(1, SelectInfoElem::from(map.as_slice()))
+                        .try_into()
+                        .map_err(Into::into)
+                        .expect("reordering columns does not change the type"),
+                    None => data,
+                };
                 Some((data, new_start, new_stop))
             } else {
                 self.current_array += 1;
@@ -1232,6 +2893,97 @@ where
     }
 }
 
+/// Minibatches of rows drawn in a shuffled order. Each item is the batch
+/// together with the global indices of its rows, or the error raised when
+/// reading the batch. Rows within a batch are sorted so that they can be
+/// fetched in a single pass.
+#[derive(Clone)]
+pub struct ShuffledChunks<A, T> {
+    elem: A,
+    ndim: usize,
+    batches: std::vec::IntoIter<Vec<usize>>,
+    type_marker: std::marker::PhantomData<T>,
+}
+
+impl<A: ArrayElemOp, T> ShuffledChunks<A, T> {
+    /// If `buffer_size` is given, rows are shuffled approximately: they are read
+    /// sequentially into a buffer of that size and then drawn from the buffer at
+    /// random, which keeps the rows of each batch close to each other on disk.
+    pub(crate) fn new(elem: A, batch_size: usize, buffer_size: Option<usize>, seed: u64) -> Self {
+        let (n, ndim) = elem.shape().map_or((0, 0), |s| (s[0], s.ndim()));
+        let mut rng = StdRng::seed_from_u64(seed);
+        let order = match buffer_size {
+            Some(buffer_size) if buffer_size < n => {
+                let mut order = Vec::with_capacity(n);
+                let mut buffer = Vec::with_capacity(buffer_size.max(1));
+                for i in 0..n {
+                    if buffer.len() >= buffer_size.max(1) {
+                        let k = rng.gen_range(0..buffer.len());
+                        order.push(buffer.swap_remove(k));
+                    }
+                    buffer.push(i);
+                }
+                buffer.shuffle(&mut rng);
+                order.extend(buffer);
+                order
+            }
+            _ => {
+                let mut order: Vec<usize> = (0..n).collect();
+                order.shuffle(&mut rng);
+                order
+            }
+        };
+        let batches: Vec<_> = order
+            .chunks(batch_size.max(1))
+            .map(|x| {
+                let mut batch = x.to_vec();
+                batch.sort_unstable();
+                batch
+            })
+            .collect();
+        Self {
+            elem,
+            ndim,
+            batches: batches.into_iter(),
+            type_marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<A, T> Iterator for ShuffledChunks<A, T>
+where
+    A: ArrayElemOp,
+    T: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + ArrayOp + Clone,
+    <T as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
+{
+    type Item = Result<(T, Vec<usize>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.batches.next().map(|batch| {
+            let full = SelectInfoElem::full();
+            let rows = SelectInfoElem::from(&batch);
+            let select: SmallVec<[SelectInfoElem; 3]> = rows
+                .set_axis(0, self.ndim, &full)
+                .into_iter()
+                .cloned()
+                .collect();
+            let data = self.elem.slice(select)?.context("cannot read a batch from an empty array")?;
+            Ok((data, batch))
+        })
+    }
+}
+
+impl<A, T> ExactSizeIterator for ShuffledChunks<A, T>
+where
+    A: ArrayElemOp,
+    T: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + ArrayOp + Clone,
+    <T as TryFrom<ArrayData>>::Error: Into<anyhow::Error>,
+{
+    fn len(&self) -> usize {
+        self.batches.len()
+    }
+}
+
 fn reverse_mapping(mapping: Vec<usize>) -> Vec<usize> {
     let mut res = vec![0; mapping.len()];
     for (i, x) in mapping.into_iter().enumerate() {
@@ -1,7 +1,7 @@
 use crate::{
-    backend::{iter_containers, Backend, GroupOp, LocationOp},
+    backend::{iter_containers, Backend, DataContainer, DataType, GroupOp, LocationOp},
     container::base::*,
-    data::*,
+    data::{*, data_traits::write_locked},
     AxisArraysOp, ElemCollectionOp,
 };
 
@@ -20,6 +20,9 @@ use log::warn;
 pub struct InnerElemCollection<B: Backend> {
     container: B::Group,
     data: HashMap<String, Elem<B>>,
+    /// Nested collections that have been opened, see [`ElemCollection::get_collection`].
+    children: HashMap<String, ElemCollection<B>>,
+    dirty: bool,
 }
 
 impl<B: Backend> std::fmt::Debug for InnerElemCollection<B> {
@@ -54,19 +57,59 @@ impl<B: Backend> std::fmt::Display for InnerElemCollection<B> {
 }
 
 impl<B: Backend> InnerElemCollection<B> {
+    /// Whether any element has been added, removed or modified since the
+    /// collection was opened or last flushed.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+            || self.values().any(|x| x.read().as_ref().is_some_and(|x| x.is_dirty()))
+            || self.children.values().any(|x| x.read().as_ref().is_some_and(|x| x.is_dirty()))
+    }
+
+    pub(crate) fn mark_clean(&mut self) {
+        self.dirty = false;
+        self.values().for_each(|x| if let Some(x) = x.lock().as_mut() { x.mark_clean() });
+        self.children.values().for_each(|x| if let Some(x) = x.lock().as_mut() { x.mark_clean() });
+    }
+
+    fn mark_dirty(&mut self) -> Result<()> {
+        self.dirty = true;
+        touch::<B>(&self.container.file()?)
+    }
+
     pub fn add_data<D: WriteData + Into<Data>>(&mut self, key: &str, data: D) -> Result<()> {
+        if let Some(child) = self.children.remove(key) {
+            child.close();
+        }
         match self.get_mut(key) {
             None => {
-                let container = data.write(&self.container, key)?;
+                let container = write_locked(&data, &self.container, key)?;
                 self.insert(key.to_string(), container.try_into()?);
             }
             Some(elem) => elem.inner().save(data)?,
         }
-        Ok(())
+        self.mark_dirty()
+    }
+
+    /// Append a value to the 1-dimensional array under `key`, or create the
+    /// array if `key` does not exist. See [`ElemCollectionOp::append_to_list`].
+    pub fn append_to_list(&mut self, key: &str, value: DynScalar) -> Result<()> {
+        match self.get(key) {
+            None => self.add_data(key, ArrayData::from(DynArray::from(value))),
+            Some(elem) => {
+                elem.inner().append_to_list(value)?;
+                self.mark_dirty()
+            }
+        }
     }
 
     pub fn remove_data(&mut self, key: &str) -> Result<()> {
-        self.remove(key).map(|x| x.clear()).transpose()?;
+        if let Some(child) = self.children.remove(key) {
+            child.close();
+        }
+        if let Some(elem) = self.remove(key) {
+            elem.clear()?;
+            self.mark_dirty()?;
+        }
         Ok(())
     }
 
@@ -114,7 +157,7 @@ impl<B: Backend> DerefMut for ElemCollection<B> {
 
 impl<B: Backend> ElemCollectionOp for &ElemCollection<B> {
     fn keys(&self) -> Vec<String> {
-        self.inner().keys().cloned().collect()
+        self.read().as_ref().map_or(Vec::new(), |x| x.keys().cloned().collect())
     }
 
     fn get_item<D>(&self, key: &str) -> Result<Option<D>>
@@ -122,17 +165,19 @@ impl<B: Backend> ElemCollectionOp for &ElemCollection<B> {
         D: ReadData + Into<Data> + TryFrom<Data> + Clone,
         <D as TryFrom<Data>>::Error: Into<anyhow::Error>,
     {
-        self.lock()
-            .as_mut()
-            .and_then(|x| x.get_mut(key))
-            .map(|x| x.inner().data())
-            .transpose()
+        // Release the lock on the collection before reading the element.
+        let elem = self.read().as_ref().and_then(|x| x.get(key).cloned());
+        elem.map(|x| x.get_data()).transpose().map(Option::flatten)
     }
 
     fn add<D: WriteData + Into<Data>>(&self, key: &str, data: D) -> Result<()> {
         self.inner().add_data(key, data)
     }
 
+    fn append_to_list<D: Into<DynScalar>>(&self, key: &str, value: D) -> Result<()> {
+        self.inner().append_to_list(key, value.into())
+    }
+
     fn remove(&self, key: &str) -> Result<()> {
         self.inner().remove_data(key)
     }
@@ -150,20 +195,71 @@ impl<B: Backend> ElemCollection<B> {
         let collection = InnerElemCollection {
             container,
             data: data?,
+            children: HashMap::new(),
+            dirty: false,
         };
         Ok(Self(Slot::new(collection)))
     }
 
+    /// Return the collection nested under `key` if the element is a mapping,
+    /// e.g., `uns/rank_genes_groups`. The collection is opened on first access
+    /// and shared afterwards, and it reads and writes the same storage as the
+    /// parent.
+    pub fn get_collection(&self, key: &str) -> Result<Option<ElemCollection<B>>> {
+        let mut guard = self.lock();
+        let inner = match guard.as_mut() {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        if let Some(child) = inner.children.get(key) {
+            return Ok(Some(child.clone()));
+        }
+        match inner.data.get(key) {
+            Some(elem) if elem.inner().dtype() == DataType::Mapping => {
+                // The mapping may be modified through the child collection.
+                elem.inner().disable_cache();
+                let child = ElemCollection::new(inner.container.open_group(key)?)?;
+                inner.children.insert(key.to_string(), child.clone());
+                Ok(Some(child))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Return the element at a `/`-separated path, e.g., `rank_genes_groups/names`,
+    /// where all components but the last are nested collections.
+    pub fn get_path(&self, path: &str) -> Result<Option<Elem<B>>> {
+        let mut components = path.split('/').filter(|x| !x.is_empty()).collect::<Vec<_>>();
+        let key = components.pop().ok_or_else(|| anyhow::anyhow!("empty path"))?;
+        let mut collection = self.clone();
+        for component in components {
+            match collection.get_collection(component)? {
+                Some(x) => collection = x,
+                None => return Ok(None),
+            }
+        }
+        let elem = collection.read().as_ref().and_then(|x| x.get(key).cloned());
+        Ok(elem)
+    }
+
+    /// Close the collection and all nested collections.
+    pub(crate) fn close(&self) {
+        if let Some(inner) = self.extract() {
+            inner.values().for_each(|x| x.drop());
+            inner.children.values().for_each(|x| x.close());
+        }
+    }
+
     pub fn clear(&self) -> Result<()> {
         self.0
-            .lock()
+            .read()
             .as_ref()
             .map(|x| {
                 let g = &x.container;
                 g.file()?.delete(&g.path().to_string_lossy())
             })
             .transpose()?;
-        self.0.drop();
+        self.close();
         Ok(())
     }
 }
@@ -204,6 +300,24 @@ impl Dim {
     pub fn try_set(&self, n: usize) -> Result<()> {
         self.lock().try_set(n)
     }
+
+    /// Set two dimensions at once. Neither dimension is changed if either of
+    /// them fails to be set.
+    pub fn try_set_both(&self, other: &Dim, n: usize, m: usize) -> Result<()> {
+        let mut lock1 = self.lock();
+        let mut lock2 = other.lock();
+        for (lock, n) in [(&lock1, n), (&lock2, m)] {
+            ensure!(
+                lock.is_empty() || lock.get() == n,
+                "dimension cannot be changed from {} to {}",
+                lock.get(),
+                n
+            );
+        }
+        lock1.set(n);
+        lock2.set(m);
+        Ok(())
+    }
 }
 
 impl Display for Dim {
@@ -245,6 +359,11 @@ impl DimLock<'_> {
     pub(crate) fn set(&mut self, n: usize) {
         *self.0 = Some(n);
     }
+
+    /// Unset the dimension, so that it can be set to any value again.
+    pub(crate) fn unset(&mut self) {
+        *self.0 = None;
+    }
 }
 
 pub struct InnerAxisArrays<B: Backend> {
@@ -253,6 +372,7 @@ pub struct InnerAxisArrays<B: Backend> {
     pub(crate) dim1: Dim,
     pub(crate) dim2: Option<Dim>,
     data: HashMap<String, ArrayElem<B>>,
+    dirty: bool,
 }
 
 impl<B: Backend> std::fmt::Debug for InnerAxisArrays<B> {
@@ -296,20 +416,80 @@ impl<B: Backend> InnerAxisArrays<B> {
         self.dim1.get()
     }
 
+    /// Whether any array has been added, removed or modified since the
+    /// collection was opened or last flushed.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.values().any(|x| x.read().as_ref().is_some_and(|x| x.is_dirty()))
+    }
+
+    pub(crate) fn mark_clean(&mut self) {
+        self.dirty = false;
+        self.values().for_each(|x| if let Some(x) = x.lock().as_mut() { x.mark_clean() });
+    }
+
+    fn mark_dirty(&mut self) -> Result<()> {
+        self.dirty = true;
+        touch::<B>(&self.container.file()?)
+    }
+
     pub fn add_data<D: WriteArrayData + HasShape + Into<ArrayData>>(
         &mut self,
         key: &str,
         data: D,
     ) -> Result<()> {
-        // Check if the data is compatible with the current size
-        let shape = data.shape();
+        self.check_shape(&data.shape())?;
+        match self.get_mut(key) {
+            None => {
+                let container = write_locked(&data, &self.container, key)?;
+                let elem = container.try_into()?;
+                self.insert(key.to_string(), elem);
+            }
+            Some(elem) => elem.inner().save(data)?,
+        }
+        self.mark_dirty()
+    }
+
+    /// Add a dense array stored with its axes reversed, so that a single
+    /// column, e.g., one component of an embedding, is a contiguous read.
+    /// The array is transposed back when it is read. It is written to a
+    /// temporary container first, so that an existing element is only
+    /// replaced once the new one is complete.
+    pub fn add_data_transposed(&mut self, key: &str, data: DynArray) -> Result<()> {
+        self.check_shape(&data.shape())?;
+        let tmp = format!("{}.transposed", key);
+        if let Err(e) = data.write_transposed(&self.container, &tmp) {
+            if self.container.exists(&tmp)? {
+                self.container.delete(&tmp)?;
+            }
+            return Err(e);
+        }
+        if self.contains_key(key) {
+            replace_by_tmp(&self.container, &tmp, key)?;
+            self.remove(key);
+        } else {
+            self.container.rename(&tmp, key)?;
+        }
+        let elem = DataContainer::open(&self.container, key)?.try_into()?;
+        self.insert(key.to_string(), elem);
+        self.mark_dirty()
+    }
+
+    /// Check if an array of the given shape is compatible with the current size.
+    fn check_shape(&self, shape: &Shape) -> Result<()> {
+        // Only the leading axes are constrained; trailing axes can be of any size.
+        let min_ndim = if let Axis::Row = self.axis { 1 } else { 2 };
+        ensure!(
+            shape.ndim() >= min_ndim,
+            "expecting an array with at least {} dimensions, but receive a {:?} array",
+            min_ndim,
+            shape
+        );
         match self.axis {
             Axis::Row => {
                 self.dim1.try_set(shape[0])?;
             }
             Axis::RowColumn => {
-                self.dim1.try_set(shape[0])?;
-                self.dim2.as_ref().unwrap().try_set(shape[1])?;
+                self.dim1.try_set_both(self.dim2.as_ref().unwrap(), shape[0], shape[1])?;
             }
             Axis::Pairwise => {
                 ensure!(
@@ -320,15 +500,6 @@ impl<B: Backend> InnerAxisArrays<B> {
                 self.dim1.try_set(shape[0])?;
             }
         }
-
-        match self.get_mut(key) {
-            None => {
-                let container = data.write(&self.container, key)?;
-                let elem = container.try_into()?;
-                self.insert(key.to_string(), elem);
-            }
-            Some(elem) => elem.inner().save(data)?,
-        }
         Ok(())
     }
 
@@ -337,10 +508,20 @@ impl<B: Backend> InnerAxisArrays<B> {
         I: Iterator<Item = D>,
         D: ArrayChunk,
     {
-        if let Some(elem) = self.get(key) {
+        if let Some(elem) = self.remove(key) {
             elem.clear()?;
+            self.mark_dirty()?;
         }
-        let elem = ArrayElem::try_from(ArrayChunk::write_by_chunk(data, &self.container, key)?)?;
+        let elem = match ArrayChunk::write_by_chunk(data, &self.container, key) {
+            Ok(container) => ArrayElem::try_from(container)?,
+            Err(e) => {
+                // Do not leave partially written data in the file.
+                if self.container.exists(key)? {
+                    self.container.delete(key)?;
+                }
+                return Err(e);
+            }
+        };
 
         let shape = { elem.inner().shape().clone() };
         match self.axis {
@@ -350,20 +531,19 @@ impl<B: Backend> InnerAxisArrays<B> {
                     bail!(e)
                 } else {
                     self.insert(key.to_string(), elem);
-                    Ok(())
+                    self.mark_dirty()
                 }
             }
             Axis::RowColumn => {
                 if let Err(e) = self
                     .dim1
-                    .try_set(shape[0])
-                    .and(self.dim2.as_ref().unwrap().try_set(shape[1]))
+                    .try_set_both(self.dim2.as_ref().unwrap(), shape[0], shape[1])
                 {
                     elem.clear()?;
                     bail!(e)
                 } else {
                     self.insert(key.to_string(), elem);
-                    Ok(())
+                    self.mark_dirty()
                 }
             }
             Axis::Pairwise => {
@@ -375,14 +555,17 @@ impl<B: Backend> InnerAxisArrays<B> {
                     bail!(e)
                 } else {
                     self.insert(key.to_string(), elem);
-                    Ok(())
+                    self.mark_dirty()
                 }
             }
         }
     }
 
     pub fn remove_data(&mut self, key: &str) -> Result<()> {
-        self.remove(key).map(|x| x.clear()).transpose()?;
+        if let Some(elem) = self.remove(key) {
+            elem.clear()?;
+            self.mark_dirty()?;
+        }
         Ok(())
     }
 
@@ -451,7 +634,7 @@ impl<B: Backend> InnerAxisArrays<B> {
                 self.values()
                     .try_for_each(|x| x.inner().subset_axis(0, selection[0]))?;
                 if let Some(mut lock) = self.dim1.try_lock() {
-                    lock.set(BoundedSelectInfoElem::new(selection[0], lock.get()).len());
+                    lock.set(selection[0].output_len(lock.get()));
                 }
             }
             Axis::RowColumn => {
@@ -461,10 +644,10 @@ impl<B: Backend> InnerAxisArrays<B> {
                 self.values()
                     .try_for_each(|x| x.inner().subset(selection))?;
                 if let Some(mut lock) = self.dim1.try_lock() {
-                    lock.set(BoundedSelectInfoElem::new(selection[0], lock.get()).len());
+                    lock.set(selection[0].output_len(lock.get()));
                 }
                 if let Some(mut lock) = self.dim2.as_ref().unwrap().try_lock() {
-                    lock.set(BoundedSelectInfoElem::new(selection[1], lock.get()).len());
+                    lock.set(selection[1].output_len(lock.get()));
                 }
             }
             Axis::Pairwise => {
@@ -479,7 +662,7 @@ impl<B: Backend> InnerAxisArrays<B> {
                     x.inner().subset(slice.as_slice())
                 })?;
                 if let Some(mut lock) = self.dim1.try_lock() {
-                    lock.set(BoundedSelectInfoElem::new(selection[0], lock.get()).len());
+                    lock.set(selection[0].output_len(lock.get()));
                 }
             }
         }
@@ -554,13 +737,14 @@ impl<B: Backend> AxisArrays<B> {
             dim2: dim2.cloned(),
             axis,
             data,
+            dirty: false,
         };
         Ok(Self(Slot::new(arrays)))
     }
 
     pub fn clear(&self) -> Result<()> {
         self.0
-            .lock()
+            .read()
             .as_ref()
             .map(|x| {
                 let g = &x.container;
@@ -570,17 +754,22 @@ impl<B: Backend> AxisArrays<B> {
         self.0.drop();
         Ok(())
     }
+
+    /// Add a dense array stored transposed. See [`InnerAxisArrays::add_data_transposed`].
+    pub fn add_transposed<D: Into<DynArray>>(&self, key: &str, data: D) -> Result<()> {
+        self.inner().add_data_transposed(key, data.into())
+    }
 }
 
 impl<B: Backend> AxisArraysOp for &AxisArrays<B> {
     type ArrayElem = ArrayElem<B>;
 
     fn keys(&self) -> Vec<String> {
-        self.inner().keys().cloned().collect()
+        self.read().as_ref().map_or(Vec::new(), |x| x.keys().cloned().collect())
     }
 
     fn get(&self, key: &str) -> Option<Self::ArrayElem> {
-        self.lock().as_ref().and_then(|x| x.get(key).cloned())
+        self.read().as_ref().and_then(|x| x.get(key).cloned())
     }
 
     fn add<D: WriteArrayData + HasShape + Into<ArrayData>>(
@@ -2,7 +2,9 @@ pub(crate) mod base;
 pub(crate) mod collection;
 
 pub use base::{
-    InnerDataFrameElem, DataFrameElem, Elem, Inner, ArrayElem, Slot,
-    StackedDataFrame, StackedArrayElem, ChunkedArrayElem, StackedChunkedArrayElem,
+    InnerArrayElem, InnerDataFrameElem, DataFrameElem, Elem, Inner, ArrayElem, Slot,
+    StackedDataFrame, StackedArrayElem, ChunkedArrayElem, StackedChunkedArrayElem, ChunkStrategy, ShuffledChunks,
+    BatchedWrite,
+    INDPTR_CACHE_MAX_ROWS,
 };
 pub use collection::{Dim, Axis, AxisArrays, ElemCollection, StackedAxisArrays};
\ No newline at end of file
@@ -9,19 +9,66 @@ pub use data_traits::*;
 pub use mapping::*;
 pub use scalar::*;
 
-use crate::backend::{Backend, DataContainer, DataType, GroupOp};
+use crate::backend::{Backend, DataContainer, DataType, FileOp, GroupOp, LocationOp};
+use crate::data::data_traits::delete_and_write;
 
 use ::ndarray::{Array, RemoveAxis};
 use anyhow::{bail, Ok, Result};
+use half::f16;
 use nalgebra_sparse::csr::CsrMatrix;
 use nalgebra_sparse::csc::CscMatrix;
 use polars::frame::DataFrame;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Data {
     ArrayData(ArrayData),
     Scalar(DynScalar),
     Mapping(Mapping),
+    /// An element with an unsupported encoding, see [`ContainerRef`].
+    Unknown(ContainerRef),
+}
+
+/// Reference to an element whose encoding is not supported, e.g., awkward
+/// arrays written by Python anndata. The element cannot be read, but writing
+/// it copies the original container as is, so that it is not lost when files
+/// are copied or rewritten.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerRef {
+    /// The file containing the element.
+    pub file: PathBuf,
+    /// The path of the element within the file.
+    pub path: PathBuf,
+    /// The value of the `encoding-type` attribute.
+    pub encoding: String,
+}
+
+impl std::fmt::Display for ContainerRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unsupported encoding '{}' of '{}'", self.encoding, self.path.display())
+    }
+}
+
+impl ContainerRef {
+    pub(crate) fn new<B: Backend>(container: &DataContainer<B>, encoding: String) -> Result<Self> {
+        Ok(Self {
+            file: container.file()?.filename(),
+            path: container.path(),
+            encoding,
+        })
+    }
+}
+
+impl Data {
+    /// Return the first element with an unsupported encoding, including
+    /// those nested in mappings.
+    pub fn find_unsupported(&self) -> Option<&ContainerRef> {
+        match self {
+            Data::Unknown(x) => Some(x),
+            Data::Mapping(m) => m.values().find_map(|x| x.find_unsupported()),
+            _ => None,
+        }
+    }
 }
 
 /// Types that can be converted to Data
@@ -70,6 +117,7 @@ impl_into_data!(u8, U8);
 impl_into_data!(u16, U16);
 impl_into_data!(u32, U32);
 impl_into_data!(u64, U64);
+impl_into_data!(f16, F16);
 impl_into_data!(f32, F32);
 impl_into_data!(f64, F64);
 impl_into_data!(bool, Bool);
@@ -116,7 +164,7 @@ macro_rules! impl_try_from_for_scalar {
 }
 
 impl_try_from_for_scalar!(
-    I8, i8, I16, i16, I32, i32, I64, i64, U8, u8, U16, u16, U32, u32, U64, u64, F32, f32, F64, f64,
+    I8, i8, I16, i16, I32, i32, I64, i64, U8, u8, U16, u16, U32, u32, U64, u64, F16, f16, F32, f32, F64, f64,
     Bool, bool, String, String
 );
 
@@ -148,6 +196,7 @@ impl WriteData for Data {
             Data::ArrayData(data) => data.data_type(),
             Data::Scalar(data) => data.data_type(),
             Data::Mapping(data) => data.data_type(),
+            Data::Unknown(_) => DataType::Unknown,
         }
     }
     fn write<B: Backend, G: GroupOp<Backend = B>>(
@@ -159,12 +208,30 @@ impl WriteData for Data {
             Data::ArrayData(data) => data.write(location, name),
             Data::Scalar(data) => data.write(location, name),
             Data::Mapping(data) => data.write(location, name),
+            Data::Unknown(data) => {
+                location.copy_from(&data.file, &data.path.to_string_lossy(), name)?;
+                DataContainer::open(location, name)
+            }
+        }
+    }
+    fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
+        match self {
+            Data::ArrayData(data) => data.overwrite(container),
+            Data::Scalar(data) => data.overwrite(container),
+            Data::Mapping(data) => data.overwrite(container),
+            // Writing an element back to where it was read from is a no-op.
+            Data::Unknown(data) if data.path == container.path()
+                && data.file == container.file()?.filename() => Ok(container),
+            Data::Unknown(_) => delete_and_write(self, container),
         }
     }
 }
 
 impl ReadData for Data {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        if let Some(encoding) = container.unsupported_encoding() {
+            return Ok(Data::Unknown(ContainerRef::new(container, encoding)?));
+        }
         match container.encoding_type()? {
             DataType::Categorical | DataType::Array(_) => {
                 DynArray::read(container).map(|x| ArrayData::from(x).into())
@@ -175,9 +242,11 @@ impl ReadData for Data {
             DataType::CscMatrix(_) => {
                 DynCscMatrix::read(container).map(|x| ArrayData::from(x).into())
             },
+            DataType::CooMatrix(_) => read_coo(container).map(|x| x.into()),
             DataType::DataFrame => DataFrame::read(container).map(|x| ArrayData::from(x).into()),
             DataType::Scalar(_) => DynScalar::read(container).map(|x| x.into()),
             DataType::Mapping => Mapping::read(container).map(|x| x.into()),
+            DataType::Unknown => unreachable!(),
         }
     }
 }
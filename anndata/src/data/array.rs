@@ -5,19 +5,26 @@ pub mod utils;
 mod sparse;
 mod chunks;
 
-pub use self::ndarray::{CategoricalArray, DynArray};
-pub use slice::{BoundedSelectInfo, BoundedSelectInfoElem, SelectInfo, SelectInfoElem, Shape};
+pub use self::ndarray::{CategoricalArray, DynArray, FromDynArray};
+pub(crate) use self::ndarray::is_transposed;
+pub use slice::{AxisArg, BoundedSelectInfo, BoundedSelectInfoElem, SelectArg, SelectInfo, SelectInfoElem, Shape};
 pub use sparse::{DynCsrMatrix, DynCscMatrix, DynCsrNonCanonical, CsrNonCanonical};
-pub use dataframe::DataFrameIndex;
+pub use dataframe::{
+    DataFrameIndex, DataFrameSummary, describe, categorical_threshold, set_categorical_threshold,
+    coerce_bool_column, coerce_bool_columns, nan_as_null_column, INDEX_LEVEL_SEPARATOR,
+};
 pub use chunks::ArrayChunk;
+pub(crate) use chunks::{append_rows, truncate_rows};
 
 use crate::backend::*;
-use crate::data::utils::from_csr_data;
+use crate::data::utils::from_csr_data_assume_sorted;
 use crate::data::{data_traits::*, scalar::DynScalar, DataType};
 
 use polars::prelude::DataFrame;
-use ::ndarray::{Array, RemoveAxis, Ix1};
-use anyhow::{bail, Result};
+use ::ndarray::{Array, Array1, RemoveAxis, Ix1};
+use anyhow::{bail, ensure, Result};
+use half::f16;
+use log::warn;
 use nalgebra_sparse::csr::CsrMatrix;
 use nalgebra_sparse::csc::CscMatrix;
 
@@ -31,6 +38,333 @@ pub enum ArrayData {
     DataFrame(DataFrame),
 }
 
+/// Storage layout of two-dimensional array data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArrayFormat {
+    Dense,
+    Csr,
+    Csc,
+}
+
+/// Map every scalar variant of one dynamic container type to the same variant of
+/// another. Extra match arms can be supplied for variants that exist only in the
+/// source type.
+macro_rules! map_dyn {
+    ($data:expr, $from:ident => $to:ident, $x:ident => $body:expr $(, $pat:pat => $res:expr)*) => {
+        match $data {
+            $from::I8($x) => $to::I8($body),
+            $from::I16($x) => $to::I16($body),
+            $from::I32($x) => $to::I32($body),
+            $from::I64($x) => $to::I64($body),
+            $from::U8($x) => $to::U8($body),
+            $from::U16($x) => $to::U16($body),
+            $from::U32($x) => $to::U32($body),
+            $from::U64($x) => $to::U64($body),
+            $from::Usize($x) => $to::Usize($body),
+            $from::F16($x) => $to::F16($body),
+            $from::F32($x) => $to::F32($body),
+            $from::F64($x) => $to::F64($body),
+            $from::Bool($x) => $to::Bool($body),
+            $from::String($x) => $to::String($body),
+            $($pat => $res,)*
+        }
+    };
+}
+
+/// Evaluate `$body` with `$x` bound to the data of a numeric variant of
+/// `$ty`, or return an error "cannot `$what` ..." for other variants.
+macro_rules! numeric {
+    ($data:expr, $ty:ident, $x:ident => $body:expr, $what:literal) => {
+        match $data {
+            $ty::I8($x) => $body,
+            $ty::I16($x) => $body,
+            $ty::I32($x) => $body,
+            $ty::I64($x) => $body,
+            $ty::U8($x) => $body,
+            $ty::U16($x) => $body,
+            $ty::U32($x) => $body,
+            $ty::U64($x) => $body,
+            $ty::Usize($x) => $body,
+            $ty::F16($x) => $body,
+            $ty::F32($x) => $body,
+            $ty::F64($x) => $body,
+            x => bail!(concat!("cannot ", $what, " {}"), x.data_type()),
+        }
+    };
+}
+
+impl ArrayData {
+    /// The storage layout of the data, or `None` for data frames.
+    pub fn format(&self) -> Option<ArrayFormat> {
+        match self {
+            ArrayData::Array(_) => Some(ArrayFormat::Dense),
+            ArrayData::CsrMatrix(_) | ArrayData::CsrNonCanonical(_) => Some(ArrayFormat::Csr),
+            ArrayData::CscMatrix(_) => Some(ArrayFormat::Csc),
+            ArrayData::DataFrame(_) => None,
+        }
+    }
+
+    /// Convert the data to the given storage layout. Only two-dimensional arrays
+    /// and sparse matrices can be converted. Duplicate entries in a non-canonical
+    /// csr matrix are merged first.
+    pub fn into_format(self, format: ArrayFormat) -> Result<Self> {
+        let data = match (self, format) {
+            (ArrayData::CsrNonCanonical(data), _) => {
+                return ArrayData::CsrMatrix(data.sum_duplicates()?).into_format(format)
+            }
+            (ArrayData::DataFrame(_), _) => bail!("cannot convert a data frame to {:?} format", format),
+            (data @ ArrayData::Array(_), ArrayFormat::Dense) => data,
+            (data @ ArrayData::CsrMatrix(_), ArrayFormat::Csr) => data,
+            (data @ ArrayData::CscMatrix(_), ArrayFormat::Csc) => data,
+            (ArrayData::Array(data), ArrayFormat::Csr) => map_dyn!(
+                data, DynArray => DynCsrMatrix, x => utils::dense_to_csr(x)?,
+                DynArray::Categorical(_) => bail!("cannot convert a categorical array to a sparse matrix")
+            ).into(),
+            (ArrayData::Array(data), ArrayFormat::Csc) => map_dyn!(
+                data, DynArray => DynCscMatrix, x => CscMatrix::from(&utils::dense_to_csr(x)?),
+                DynArray::Categorical(_) => bail!("cannot convert a categorical array to a sparse matrix")
+            ).into(),
+            (ArrayData::CsrMatrix(data), ArrayFormat::Dense) =>
+                map_dyn!(data, DynCsrMatrix => DynArray, x => utils::csr_to_dense(x)).into(),
+            (ArrayData::CsrMatrix(data), ArrayFormat::Csc) =>
+                map_dyn!(data, DynCsrMatrix => DynCscMatrix, x => CscMatrix::from(&x)).into(),
+            (ArrayData::CscMatrix(data), ArrayFormat::Dense) =>
+                map_dyn!(data, DynCscMatrix => DynArray, x => utils::csr_to_dense(CsrMatrix::from(&x))).into(),
+            (ArrayData::CscMatrix(data), ArrayFormat::Csr) =>
+                map_dyn!(data, DynCscMatrix => DynCsrMatrix, x => CsrMatrix::from(&x)).into(),
+        };
+        Ok(data)
+    }
+
+    /// Convert the values to the scalar type `ty`. Sparse matrices keep their
+    /// structure and only the stored values are converted. A warning is logged
+    /// if the conversion may lose precision, e.g., from f64 to f32, and an error
+    /// is returned if a value cannot be represented, e.g., -1 converted to u32.
+    pub fn astype(self, ty: ScalarType) -> Result<Self> {
+        if check_astype(&self.data_type(), ty)? {
+            self.cast(ty)
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Like [`ArrayData::astype`], but without checking the types first, so
+    /// that chunks of an element can be converted after a single check.
+    pub(crate) fn cast(self, ty: ScalarType) -> Result<Self> {
+        // Split the data into its structure and values, convert the values,
+        // and rebuild the data from the structure and the converted values.
+        macro_rules! cast {
+            ($data:expr, $from:ident, $x:ident => $split:expr) => {
+                match $data {
+                    $from::I8($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::I16($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::I32($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::I64($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::U8($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::U16($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::U32($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::U64($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::Usize($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::F16($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::F32($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::F64($x) => { let (s, v) = $split; (s, utils::cast_values(v, ty)?) }
+                    $from::Bool($x) => {
+                        let (s, v) = $split;
+                        (s, utils::cast_values(v.into_iter().map(u8::from).collect(), ty)?)
+                    }
+                    _ => unreachable!(),
+                }
+            };
+        }
+        let data = match self {
+            ArrayData::Array(data) => {
+                let (shape, values) = cast!(data, DynArray, x => (x.raw_dim(), x.iter().copied().collect::<Vec<_>>()));
+                map_dyn!(
+                    values, DynArray => DynArray, x => x.into_shape(shape.clone())?,
+                    DynArray::Categorical(_) => unreachable!()
+                ).into()
+            }
+            ArrayData::CsrMatrix(data) => {
+                let (pattern, values) = cast!(data, DynCsrMatrix, x => x.into_pattern_and_values());
+                map_dyn!(
+                    values, DynArray => DynCsrMatrix,
+                    x => CsrMatrix::try_from_pattern_and_values(pattern.clone(), x.into_raw_vec()).unwrap(),
+                    DynArray::Categorical(_) => unreachable!()
+                ).into()
+            }
+            ArrayData::CsrNonCanonical(data) => {
+                let ((nrows, ncols, offsets, indices), values) = cast!(data, DynCsrNonCanonical, x => {
+                    let (nrows, ncols) = (x.nrows(), x.ncols());
+                    let (offsets, indices, values) = x.disassemble();
+                    ((nrows, ncols, offsets, indices), values)
+                });
+                map_dyn!(
+                    values, DynArray => DynCsrNonCanonical,
+                    x => CsrNonCanonical::from_csr_data(nrows, ncols, offsets.clone(), indices.clone(), x.into_raw_vec()),
+                    DynArray::Categorical(_) => unreachable!()
+                ).into()
+            }
+            ArrayData::CscMatrix(data) => {
+                let (pattern, values) = cast!(data, DynCscMatrix, x => x.into_pattern_and_values());
+                map_dyn!(
+                    values, DynArray => DynCscMatrix,
+                    x => CscMatrix::try_from_pattern_and_values(pattern.clone(), x.into_raw_vec()).unwrap(),
+                    DynArray::Categorical(_) => unreachable!()
+                ).into()
+            }
+            ArrayData::DataFrame(_) => unreachable!(),
+        };
+        Ok(data)
+    }
+
+    /// Move column `j` of a two-dimensional array or sparse matrix to column
+    /// `columns[j]` of a matrix with `ncols` columns, or drop it if `columns[j]`
+    /// is `None`. The columns that are not filled this way are set to
+    /// `fill_value`. Sparse matrices stay sparse if `fill_value` is 0, as the
+    /// missing entries are structural zeros, and are converted to dense arrays
+    /// otherwise.
+    pub(crate) fn expand_columns(self, columns: &[Option<usize>], ncols: usize, fill_value: f64) -> Result<Self> {
+        let shape = self.shape();
+        ensure!(shape.ndim() == 2, "expecting a 2-dimensional array, but got {} dimensions", shape.ndim());
+        ensure!(columns.len() == shape[1], "expecting {} columns, but got {}", columns.len(), shape[1]);
+        let mut filled = vec![false; ncols];
+        columns.iter().flatten().for_each(|c| filled[*c] = true);
+        let needs_fill = filled.iter().any(|x| !x);
+
+        let data = match self {
+            ArrayData::CsrNonCanonical(data) => {
+                return ArrayData::CsrMatrix(data.sum_duplicates()?).expand_columns(columns, ncols, fill_value)
+            }
+            ArrayData::DataFrame(_) => bail!("cannot expand the columns of a data frame"),
+            data @ (ArrayData::CsrMatrix(_) | ArrayData::CscMatrix(_)) if needs_fill && fill_value != 0.0 => {
+                warn!("converting a sparse matrix to a dense array to fill missing values with {}", fill_value);
+                return data.into_format(ArrayFormat::Dense)?.expand_columns(columns, ncols, fill_value)
+            }
+            ArrayData::CsrMatrix(data) => map_dyn!(
+                data, DynCsrMatrix => DynCsrMatrix, x => utils::csr_expand_columns(x, columns, ncols)?
+            ).into(),
+            ArrayData::CscMatrix(data) => map_dyn!(
+                data, DynCscMatrix => DynCscMatrix, x => utils::csc_expand_columns(x, columns, ncols)?
+            ).into(),
+            ArrayData::Array(data) => {
+                macro_rules! fill {
+                    ($x:expr, $ty:ty) => {{
+                        let fill = <$ty as num::NumCast>::from(fill_value).ok_or_else(||
+                            anyhow::anyhow!("cannot represent the fill value {} as {}", fill_value, stringify!($ty))
+                        )?;
+                        utils::dense_expand_columns($x, columns, ncols, fill)?
+                    }};
+                }
+                match data {
+                    DynArray::I8(x) => DynArray::I8(fill!(x, i8)),
+                    DynArray::I16(x) => DynArray::I16(fill!(x, i16)),
+                    DynArray::I32(x) => DynArray::I32(fill!(x, i32)),
+                    DynArray::I64(x) => DynArray::I64(fill!(x, i64)),
+                    DynArray::U8(x) => DynArray::U8(fill!(x, u8)),
+                    DynArray::U16(x) => DynArray::U16(fill!(x, u16)),
+                    DynArray::U32(x) => DynArray::U32(fill!(x, u32)),
+                    DynArray::U64(x) => DynArray::U64(fill!(x, u64)),
+                    DynArray::Usize(x) => DynArray::Usize(fill!(x, usize)),
+                    DynArray::F16(x) => DynArray::F16(fill!(x, f16)),
+                    DynArray::F32(x) => DynArray::F32(fill!(x, f32)),
+                    DynArray::F64(x) => DynArray::F64(fill!(x, f64)),
+                    DynArray::Bool(x) => DynArray::Bool(utils::dense_expand_columns(x, columns, ncols, fill_value != 0.0)?),
+                    DynArray::String(x) if !needs_fill => DynArray::String(utils::dense_expand_columns(x, columns, ncols, String::new())?),
+                    data => bail!("cannot fill missing values of {}", data.data_type()),
+                }.into()
+            }
+        };
+        Ok(data)
+    }
+
+    /// Sum a two-dimensional array or sparse matrix along `axis`. The result
+    /// has one value per column if `axis` is 0, and one value per row if `axis` is 1.
+    pub fn sum_axis(&self, axis: usize) -> Result<Array1<f64>> {
+        self.fold_axis(axis, false)
+    }
+
+    /// Count the nonzero values of a two-dimensional array or sparse matrix
+    /// along `axis`, see [`ArrayData::sum_axis`]. Zeros explicitly stored in
+    /// sparse matrices are not counted.
+    pub fn count_nonzero_axis(&self, axis: usize) -> Result<Array1<f64>> {
+        self.fold_axis(axis, true)
+    }
+
+    fn fold_axis(&self, axis: usize, count_nonzero: bool) -> Result<Array1<f64>> {
+        let shape = self.shape();
+        ensure!(shape.ndim() == 2, "sum is only supported for 2D arrays, but got {:?}", shape);
+        ensure!(axis < 2, "axis must be 0 or 1, but got {}", axis);
+        let sum = match self {
+            // Duplicate entries would be counted more than once.
+            ArrayData::CsrNonCanonical(data) if count_nonzero => {
+                return ArrayData::CsrMatrix(data.clone().sum_duplicates()?).fold_axis(axis, true)
+            }
+            ArrayData::Array(data) => numeric!(data, DynArray, x => utils::dense_sum(x, axis, count_nonzero), "sum over"),
+            ArrayData::CsrMatrix(data) => numeric!(data, DynCsrMatrix, x =>
+                utils::cs_sum(x.row_offsets(), x.col_indices(), x.values(), shape[1], axis == 1, count_nonzero),
+                "sum over"
+            ),
+            ArrayData::CsrNonCanonical(data) => numeric!(data, DynCsrNonCanonical, x =>
+                utils::cs_sum(x.row_offsets(), x.col_indices(), x.values(), shape[1], axis == 1, count_nonzero),
+                "sum over"
+            ),
+            ArrayData::CscMatrix(data) => numeric!(data, DynCscMatrix, x =>
+                utils::cs_sum(x.col_offsets(), x.row_indices(), x.values(), shape[0], axis == 0, count_nonzero),
+                "sum over"
+            ),
+            ArrayData::DataFrame(_) => bail!("cannot sum over a data frame"),
+        };
+        Ok(sum)
+    }
+
+    /// Compute the variance of a two-dimensional array or sparse matrix along
+    /// `axis`, see [`ArrayData::sum_axis`]. The sum of squared deviations is
+    /// divided by `n - ddof`, where `n` is the number of values in a lane,
+    /// including the zeros that are not stored in sparse matrices.
+    pub fn var_axis(&self, axis: usize, ddof: usize) -> Result<Array1<f64>> {
+        Ok(self.moments_axis(axis)?.var(ddof))
+    }
+
+    pub(crate) fn moments_axis(&self, axis: usize) -> Result<utils::Moments> {
+        let shape = self.shape();
+        ensure!(shape.ndim() == 2, "variance is only supported for 2D arrays, but got {:?}", shape);
+        ensure!(axis < 2, "axis must be 0 or 1, but got {}", axis);
+        let moments = match self {
+            ArrayData::Array(data) => numeric!(data, DynArray, x => utils::dense_moments(x, axis), "compute the variance of"),
+            ArrayData::CsrMatrix(data) => numeric!(data, DynCsrMatrix, x =>
+                utils::cs_moments(x.row_offsets(), x.col_indices(), x.values(), shape[1], axis == 1),
+                "compute the variance of"
+            ),
+            ArrayData::CscMatrix(data) => numeric!(data, DynCscMatrix, x =>
+                utils::cs_moments(x.col_offsets(), x.row_indices(), x.values(), shape[0], axis == 0),
+                "compute the variance of"
+            ),
+            // Duplicate entries are summed, as they are when the matrix is read as a csr matrix.
+            ArrayData::CsrNonCanonical(data) => return ArrayData::CsrMatrix(data.clone().sum_duplicates()?).moments_axis(axis),
+            ArrayData::DataFrame(_) => bail!("cannot compute the variance of a data frame"),
+        };
+        Ok(moments)
+    }
+}
+
+/// Check that data of type `dtype` can be converted to `ty`, logging a warning
+/// if the conversion may lose precision. Return whether a conversion is needed.
+pub(crate) fn check_astype(dtype: &DataType, ty: ScalarType) -> Result<bool> {
+    let from = match dtype {
+        DataType::Array(t) | DataType::CsrMatrix(t) | DataType::CscMatrix(t) => *t,
+        dtype => bail!("cannot convert {} to {}", dtype, ty),
+    };
+    if from == ty {
+        return Ok(false);
+    }
+    ensure!(from != ScalarType::String, "cannot convert strings to {}", ty);
+    if !utils::is_lossless(from, ty) {
+        warn!("converting {} to {} may lose precision", from, ty);
+    }
+    Ok(true)
+}
+
+
 impl<T: Clone + Into<ArrayData>> From<&T> for ArrayData {
     fn from(data: &T) -> Self {
         data.clone().into()
@@ -181,7 +515,7 @@ macro_rules! impl_into_array_data {
     };
 }
 
-impl_into_array_data!(i8, i16, i32, i64, u8, u16, u32, u64, usize, f32, f64, bool, String);
+impl_into_array_data!(i8, i16, i32, i64, u8, u16, u32, u64, usize, f16, f32, f64, bool, String);
 
 impl WriteData for ArrayData {
     fn data_type(&self) -> DataType {
@@ -206,16 +540,30 @@ impl WriteData for ArrayData {
             ArrayData::DataFrame(data) => data.write(location, name),
         }
     }
+    fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
+        match self {
+            ArrayData::Array(data) => data.overwrite(container),
+            ArrayData::CsrMatrix(data) => data.overwrite(container),
+            ArrayData::CsrNonCanonical(data) => data.overwrite(container),
+            ArrayData::CscMatrix(data) => data.overwrite(container),
+            ArrayData::DataFrame(data) => data.overwrite(container),
+        }
+    }
 }
 
 impl ReadData for ArrayData {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        Self::read_assume_sorted(container, false)
+    }
+
+    fn read_assume_sorted<B: Backend>(container: &DataContainer<B>, assume_sorted_indices: bool) -> Result<Self> {
         match container.encoding_type()? {
             DataType::Categorical | DataType::Array(_) => {
                 DynArray::read(container).map(ArrayData::Array)
             }
-            DataType::CsrMatrix(_) => read_csr(container),
+            DataType::CsrMatrix(_) => read_csr(container, assume_sorted_indices),
             DataType::CscMatrix(_) => DynCscMatrix::read(container).map(ArrayData::CscMatrix),
+            DataType::CooMatrix(_) => read_coo(container),
             DataType::DataFrame => DataFrame::read(container).map(ArrayData::DataFrame),
             ty => bail!("Cannot read type '{:?}' as matrix data", ty),
         }
@@ -280,12 +628,29 @@ impl ReadArrayData for ArrayData {
             DataType::Categorical | DataType::Array(_) => DynArray::get_shape(container),
             DataType::CsrMatrix(_) => DynCsrMatrix::get_shape(container),
             DataType::CscMatrix(_) => DynCscMatrix::get_shape(container),
+            DataType::CooMatrix(_) => {
+                let shape: Vec<usize> = container.as_group()?.read_array_attr("shape")?.to_vec();
+                ensure!(shape.len() == 2, "coo matrix must have a 2-dimensional shape, but found {:?}", shape);
+                Ok(shape.into())
+            }
             DataType::DataFrame => DataFrame::get_shape(container),
             ty => bail!("Cannot read shape information from type '{}'", ty),
         }
     }
 
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+    {
+        Self::read_select_assume_sorted(container, info, false)
+    }
+
+    fn read_select_assume_sorted<B, S>(
+        container: &DataContainer<B>,
+        info: &[S],
+        assume_sorted_indices: bool,
+    ) -> Result<Self>
     where
         B: Backend,
         S: AsRef<SelectInfoElem>,
@@ -293,9 +658,10 @@ impl ReadArrayData for ArrayData {
         match container.encoding_type()? {
             DataType::Categorical | DataType::Array(_) =>
                 DynArray::read_select(container, info).map(ArrayData::Array),
-            DataType::CsrMatrix(_) => read_csr_select(container, info),
+            DataType::CsrMatrix(_) => read_csr_select(container, info, assume_sorted_indices),
             DataType::CscMatrix(_) =>
                 DynCscMatrix::read_select(container, info).map(ArrayData::CscMatrix),
+            DataType::CooMatrix(_) => read_coo(container).map(|x| x.select(info)),
             DataType::DataFrame =>
                 DataFrame::read_select(container, info).map(ArrayData::DataFrame),
             ty => bail!("Cannot read type '{:?}' as matrix data", ty),
@@ -309,8 +675,11 @@ impl WriteArrayData for &ArrayData {}
 
 // Helper
 
-fn read_csr<B: Backend>(container: &DataContainer<B>) -> Result<ArrayData> {
-    fn _read_csr<B: Backend, T: BackendData>(container: &DataContainer<B>) -> Result<ArrayData>
+/// Read a csr matrix from the container. If `assume_sorted_indices` is true,
+/// the column indices within each row are trusted to be sorted and the per-row
+/// sort is skipped.
+pub fn read_csr<B: Backend>(container: &DataContainer<B>, assume_sorted_indices: bool) -> Result<ArrayData> {
+    fn _read_csr<B: Backend, T: BackendData>(container: &DataContainer<B>, assume_sorted_indices: bool) -> Result<ArrayData>
     where
         CsrMatrix<T>: Into<ArrayData>,
         CsrNonCanonical<T>: Into<ArrayData>,
@@ -320,35 +689,98 @@ fn read_csr<B: Backend>(container: &DataContainer<B>) -> Result<ArrayData> {
         let data = group.open_dataset("data")?.read_array::<_, Ix1>()?.into_raw_vec();
         let indptr: Vec<usize> = group.open_dataset("indptr")?.read_array::<_, Ix1>()?.into_raw_vec();
         let indices: Vec<usize> = group.open_dataset("indices")?.read_array::<_, Ix1>()?.into_raw_vec();
-        from_csr_data::<T>(shape[0], shape[1], indptr, indices, data)
+        from_csr_data_assume_sorted::<T>(shape[0], shape[1], indptr, indices, data, assume_sorted_indices)
     }
 
     match container {
         DataContainer::Group(group) => match group.open_dataset("data")?.dtype()? {
-            ScalarType::I8 => _read_csr::<B, i8>(container),
-            ScalarType::I16 => _read_csr::<B, i16>(container),
-            ScalarType::I32 => _read_csr::<B, i32>(container),
-            ScalarType::I64 => _read_csr::<B, i64>(container),
-            ScalarType::U8 => _read_csr::<B, u8>(container),
-            ScalarType::U16 => _read_csr::<B, u16>(container),
-            ScalarType::U32 => _read_csr::<B, u32>(container),
-            ScalarType::U64 => _read_csr::<B, u64>(container),
-            ScalarType::Usize => _read_csr::<B, usize>(container),
-            ScalarType::F32 => _read_csr::<B, f32>(container),
-            ScalarType::F64 => _read_csr::<B, f64>(container),
-            ScalarType::Bool => _read_csr::<B, bool>(container),
-            ScalarType::String => _read_csr::<B, String>(container),
+            ScalarType::I8 => _read_csr::<B, i8>(container, assume_sorted_indices),
+            ScalarType::I16 => _read_csr::<B, i16>(container, assume_sorted_indices),
+            ScalarType::I32 => _read_csr::<B, i32>(container, assume_sorted_indices),
+            ScalarType::I64 => _read_csr::<B, i64>(container, assume_sorted_indices),
+            ScalarType::U8 => _read_csr::<B, u8>(container, assume_sorted_indices),
+            ScalarType::U16 => _read_csr::<B, u16>(container, assume_sorted_indices),
+            ScalarType::U32 => _read_csr::<B, u32>(container, assume_sorted_indices),
+            ScalarType::U64 => _read_csr::<B, u64>(container, assume_sorted_indices),
+            ScalarType::Usize => _read_csr::<B, usize>(container, assume_sorted_indices),
+            ScalarType::F16 => _read_csr::<B, f16>(container, assume_sorted_indices),
+            ScalarType::F32 => _read_csr::<B, f32>(container, assume_sorted_indices),
+            ScalarType::F64 => _read_csr::<B, f64>(container, assume_sorted_indices),
+            ScalarType::Bool => _read_csr::<B, bool>(container, assume_sorted_indices),
+            ScalarType::String => _read_csr::<B, String>(container, assume_sorted_indices),
         },
         _ => bail!("cannot read csr matrix from non-group container"),
     }
 }
 
-fn read_csr_select<B: Backend, S>(container: &DataContainer<B>, info: &[S]) -> Result<ArrayData>
+/// Read a coo matrix from the container as a csr matrix. Duplicated entries
+/// are kept, in which case a non-canonical csr matrix is returned.
+pub fn read_coo<B: Backend>(container: &DataContainer<B>) -> Result<ArrayData> {
+    fn _read_coo<B: Backend, T: BackendData>(container: &DataContainer<B>) -> Result<ArrayData>
+    where
+        CsrMatrix<T>: Into<ArrayData>,
+        CsrNonCanonical<T>: Into<ArrayData>,
+    {
+        let group = container.as_group()?;
+        let shape: Vec<usize> = group.read_array_attr("shape")?.to_vec();
+        ensure!(shape.len() == 2, "coo matrix must have a 2-dimensional shape, but found {:?}", shape);
+        let data: Vec<T> = group.open_dataset("data")?.read_array::<_, Ix1>()?.into_raw_vec();
+        let row: Vec<usize> = group.open_dataset("row")?.read_array::<_, Ix1>()?.into_raw_vec();
+        let col: Vec<usize> = group.open_dataset("col")?.read_array::<_, Ix1>()?.into_raw_vec();
+        ensure!(
+            row.len() == data.len() && col.len() == data.len(),
+            "coo matrix must have the same number of row indices, column indices and values",
+        );
+        ensure!(row.iter().all(|&i| i < shape[0]), "row index out of bounds in coo matrix");
+        ensure!(col.iter().all(|&j| j < shape[1]), "column index out of bounds in coo matrix");
+
+        let mut indptr = vec![0; shape[0] + 1];
+        row.iter().for_each(|&i| indptr[i + 1] += 1);
+        (0..shape[0]).for_each(|i| indptr[i + 1] += indptr[i]);
+        let mut order: Vec<usize> = (0..data.len()).collect();
+        order.sort_by_key(|&i| row[i]);
+        let indices = order.iter().map(|&i| col[i]).collect();
+        let data = order.iter().map(|&i| data[i].clone()).collect();
+        from_csr_data_assume_sorted::<T>(shape[0], shape[1], indptr, indices, data, false)
+    }
+
+    match container {
+        DataContainer::Group(group) => match group.open_dataset("data")?.dtype()? {
+            ScalarType::I8 => _read_coo::<B, i8>(container),
+            ScalarType::I16 => _read_coo::<B, i16>(container),
+            ScalarType::I32 => _read_coo::<B, i32>(container),
+            ScalarType::I64 => _read_coo::<B, i64>(container),
+            ScalarType::U8 => _read_coo::<B, u8>(container),
+            ScalarType::U16 => _read_coo::<B, u16>(container),
+            ScalarType::U32 => _read_coo::<B, u32>(container),
+            ScalarType::U64 => _read_coo::<B, u64>(container),
+            ScalarType::Usize => _read_coo::<B, usize>(container),
+            ScalarType::F16 => _read_coo::<B, f16>(container),
+            ScalarType::F32 => _read_coo::<B, f32>(container),
+            ScalarType::F64 => _read_coo::<B, f64>(container),
+            ScalarType::Bool => _read_coo::<B, bool>(container),
+            ScalarType::String => _read_coo::<B, String>(container),
+        },
+        _ => bail!("cannot read coo matrix from non-group container"),
+    }
+}
+
+/// Read a selection of a csr matrix from the container. See [`read_csr`] for
+/// the meaning of `assume_sorted_indices`.
+pub fn read_csr_select<B, S>(
+    container: &DataContainer<B>,
+    info: &[S],
+    assume_sorted_indices: bool,
+) -> Result<ArrayData>
 where
     B: Backend,
     S: AsRef<SelectInfoElem>,
 {
-    fn _read_csr<B: Backend, T: BackendData, S>(container: &DataContainer<B>, info: &[S]) -> Result<ArrayData>
+    fn _read_csr<B: Backend, T: BackendData, S>(
+        container: &DataContainer<B>,
+        info: &[S],
+        assume_sorted_indices: bool,
+    ) -> Result<ArrayData>
     where
         CsrMatrix<T>: Into<ArrayData>,
         CsrNonCanonical<T>: Into<ArrayData>,
@@ -359,7 +791,7 @@ where
         }
 
         if info.iter().all(|s| s.as_ref().is_full()) {
-            return read_csr(container);
+            return read_csr(container, assume_sorted_indices);
         }
 
         let data = if let SelectInfoElem::Slice(s) = info[0].as_ref()  {
@@ -380,35 +812,155 @@ where
             let indices: Vec<usize> = group.open_dataset("indices")?.read_array_slice(&[&slice])?.to_vec();
             indptr.iter_mut().for_each(|x| *x -= lo);
 
-            from_csr_data::<T>(
+            from_csr_data_assume_sorted::<T>(
                 indptr.len() - 1,
                 shape[1],
                 indptr,
                 indices,
                 data,
-            ).unwrap().select_axis(1, info[1].as_ref())
+                assume_sorted_indices,
+            )?.select_axis(1, info[1].as_ref())
         } else {
-            read_csr(container)?.select(info)
+            read_csr(container, assume_sorted_indices)?.select(info)
         };
         Ok(data)
     }
 
     match container {
         DataContainer::Group(group) => match group.open_dataset("data")?.dtype()? {
-            ScalarType::I8 => _read_csr::<B, i8, _>(container, info),
-            ScalarType::I16 => _read_csr::<B, i16, _>(container, info),
-            ScalarType::I32 => _read_csr::<B, i32, _>(container, info),
-            ScalarType::I64 => _read_csr::<B, i64, _>(container, info),
-            ScalarType::U8 => _read_csr::<B, u8, _>(container, info),
-            ScalarType::U16 => _read_csr::<B, u16, _>(container, info),
-            ScalarType::U32 => _read_csr::<B, u32, _>(container, info),
-            ScalarType::U64 => _read_csr::<B, u64, _>(container, info),
-            ScalarType::Usize => _read_csr::<B, usize, _>(container, info),
-            ScalarType::F32 => _read_csr::<B, f32, _>(container, info),
-            ScalarType::F64 => _read_csr::<B, f64, _>(container, info),
-            ScalarType::Bool => _read_csr::<B, bool, _>(container, info),
-            ScalarType::String => _read_csr::<B, String, _>(container, info),
+            ScalarType::I8 => _read_csr::<B, i8, _>(container, info, assume_sorted_indices),
+            ScalarType::I16 => _read_csr::<B, i16, _>(container, info, assume_sorted_indices),
+            ScalarType::I32 => _read_csr::<B, i32, _>(container, info, assume_sorted_indices),
+            ScalarType::I64 => _read_csr::<B, i64, _>(container, info, assume_sorted_indices),
+            ScalarType::U8 => _read_csr::<B, u8, _>(container, info, assume_sorted_indices),
+            ScalarType::U16 => _read_csr::<B, u16, _>(container, info, assume_sorted_indices),
+            ScalarType::U32 => _read_csr::<B, u32, _>(container, info, assume_sorted_indices),
+            ScalarType::U64 => _read_csr::<B, u64, _>(container, info, assume_sorted_indices),
+            ScalarType::Usize => _read_csr::<B, usize, _>(container, info, assume_sorted_indices),
+            ScalarType::F16 => _read_csr::<B, f16, _>(container, info, assume_sorted_indices),
+            ScalarType::F32 => _read_csr::<B, f32, _>(container, info, assume_sorted_indices),
+            ScalarType::F64 => _read_csr::<B, f64, _>(container, info, assume_sorted_indices),
+            ScalarType::Bool => _read_csr::<B, bool, _>(container, info, assume_sorted_indices),
+            ScalarType::String => _read_csr::<B, String, _>(container, info, assume_sorted_indices),
         },
         _ => bail!("cannot read csr matrix from non-group container"),
     }
-}
\ No newline at end of file
+}
+
+/// Above this number of separately stored row ranges, [`read_csr_rows`] reads
+/// the span covering all selected rows at once instead of one range at a time,
+/// unless the span holds more than [`MAX_SPAN_RATIO`] times the stored entries
+/// of the selected rows.
+const MAX_ROW_RANGE_READS: usize = 64;
+
+/// See [`MAX_ROW_RANGE_READS`].
+const MAX_SPAN_RATIO: usize = 4;
+
+/// Read a selection of a csr matrix from the container, given its full
+/// `indptr`. Only the stored entries of the selected rows are read, and
+/// consecutive rows are read together. See [`read_csr`] for the meaning of
+/// `assume_sorted_indices`.
+pub(crate) fn read_csr_rows<B, S>(
+    container: &DataContainer<B>,
+    indptr: &[usize],
+    info: &[S],
+    assume_sorted_indices: bool,
+) -> Result<ArrayData>
+where
+    B: Backend,
+    S: AsRef<SelectInfoElem>,
+{
+    fn _read_csr<B: Backend, T: BackendData>(
+        container: &DataContainer<B>,
+        indptr: &[usize],
+        rows: &[std::ops::Range<usize>],
+        assume_sorted_indices: bool,
+    ) -> Result<ArrayData>
+    where
+        CsrMatrix<T>: Into<ArrayData>,
+        CsrNonCanonical<T>: Into<ArrayData>,
+    {
+        let group = container.as_group()?;
+        let shape: Vec<usize> = group.read_array_attr("shape")?.to_vec();
+        let data_dataset = group.open_dataset("data")?;
+        let indices_dataset = group.open_dataset("indices")?;
+        let read_range = |lo: usize, hi: usize| -> Result<(Vec<T>, Vec<usize>)> {
+            let slice = SelectInfoElem::from(lo..hi);
+            Ok((
+                data_dataset.read_array_slice::<T, _, Ix1>(&[&slice])?.into_raw_vec(),
+                indices_dataset.read_array_slice::<usize, _, Ix1>(&[&slice])?.into_raw_vec(),
+            ))
+        };
+
+        let span = if rows.len() > MAX_ROW_RANGE_READS {
+            let lo = rows.iter().map(|r| indptr[r.start]).min().unwrap();
+            let hi = rows.iter().map(|r| indptr[r.end]).max().unwrap();
+            let nnz: usize = rows.iter().map(|r| indptr[r.end] - indptr[r.start]).sum();
+            if hi - lo <= nnz.saturating_mul(MAX_SPAN_RATIO) {
+                Some((lo, read_range(lo, hi)?))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let mut new_indptr = Vec::with_capacity(rows.iter().map(|r| r.len()).sum::<usize>() + 1);
+        new_indptr.push(0);
+        let mut data: Vec<T> = Vec::new();
+        let mut indices: Vec<usize> = Vec::new();
+        for r in rows {
+            let (lo, hi) = (indptr[r.start], indptr[r.end]);
+            let offset = data.len();
+            match &span {
+                Some((base, (d, i))) => {
+                    data.extend_from_slice(&d[lo - base..hi - base]);
+                    indices.extend_from_slice(&i[lo - base..hi - base]);
+                }
+                None if lo < hi => {
+                    let (d, i) = read_range(lo, hi)?;
+                    data.extend(d);
+                    indices.extend(i);
+                }
+                None => {}
+            }
+            new_indptr.extend(indptr[r.start + 1..=r.end].iter().map(|&x| x - lo + offset));
+        }
+        from_csr_data_assume_sorted::<T>(new_indptr.len() - 1, shape[1], new_indptr, indices, data, assume_sorted_indices)
+    }
+
+    ensure!(info.len() == 2, "index must have length 2");
+    let nrows = indptr.len().saturating_sub(1);
+    let mut rows: Vec<std::ops::Range<usize>> = Vec::new();
+    for i in BoundedSelectInfoElem::new(info[0].as_ref(), nrows).iter() {
+        match rows.last_mut() {
+            Some(r) if r.end == i => r.end += 1,
+            _ => rows.push(i..i + 1),
+        }
+    }
+
+    let data = match container {
+        DataContainer::Group(group) => match group.open_dataset("data")?.dtype()? {
+            ScalarType::I8 => _read_csr::<B, i8>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::I16 => _read_csr::<B, i16>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::I32 => _read_csr::<B, i32>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::I64 => _read_csr::<B, i64>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::U8 => _read_csr::<B, u8>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::U16 => _read_csr::<B, u16>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::U32 => _read_csr::<B, u32>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::U64 => _read_csr::<B, u64>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::Usize => _read_csr::<B, usize>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::F16 => _read_csr::<B, f16>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::F32 => _read_csr::<B, f32>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::F64 => _read_csr::<B, f64>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::Bool => _read_csr::<B, bool>(container, indptr, &rows, assume_sorted_indices),
+            ScalarType::String => _read_csr::<B, String>(container, indptr, &rows, assume_sorted_indices),
+        },
+        _ => bail!("cannot read csr matrix from non-group container"),
+    }?;
+    if info[1].as_ref().is_full() {
+        Ok(data)
+    } else {
+        Ok(data.select_axis(1, info[1].as_ref()))
+    }
+}
@@ -1,14 +1,17 @@
-use crate::backend::{Backend, DataContainer, GroupOp, LocationOp, BackendData, ScalarType};
+use crate::backend::{Backend, DataContainer, DatasetOp, GroupOp, LocationOp, BackendData, ScalarType};
 use crate::ArrayOp;
 use crate::data::{
     ArrayData,
     array::utils::ExtendableDataset,
+    array::slice::SelectInfoElem,
 };
 
-use anyhow::{bail, Result, Context};
-use ndarray::{Array, ArrayView1, ArrayD, RemoveAxis};
+use anyhow::{bail, ensure, Result, Context};
+use half::f16;
+use ndarray::{Array, ArrayView, ArrayView1, ArrayD, Ix1, IxDyn, RemoveAxis};
 use nalgebra_sparse::na::Scalar;
 use nalgebra_sparse::{CsrMatrix, CscMatrix};
+use super::ndarray::is_transposed;
 use super::{DynCsrMatrix, DynCscMatrix, DynArray, DynCsrNonCanonical, CsrNonCanonical};
 
 pub trait ArrayChunk: ArrayOp {
@@ -55,6 +58,7 @@ impl ArrayChunk for DynArray {
             DynArray::I16(_) => ArrayD::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::I32(_) => ArrayD::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::I64(_) => ArrayD::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynArray::F16(_) => ArrayD::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::F32(_) => ArrayD::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::F64(_) => ArrayD::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynArray::Bool(_) => ArrayD::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -114,6 +118,7 @@ impl ArrayChunk for DynCsrMatrix {
             DynCsrMatrix::I16(_) => CsrMatrix::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::I32(_) => CsrMatrix::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::I64(_) => CsrMatrix::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCsrMatrix::F16(_) => CsrMatrix::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::F32(_) => CsrMatrix::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::F64(_) => CsrMatrix::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrMatrix::Bool(_) => CsrMatrix::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -192,6 +197,7 @@ impl ArrayChunk for DynCsrNonCanonical {
             DynCsrNonCanonical::I16(_) => CsrNonCanonical::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::I32(_) => CsrNonCanonical::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::I64(_) => CsrNonCanonical::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCsrNonCanonical::F16(_) => CsrNonCanonical::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::F32(_) => CsrNonCanonical::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::F64(_) => CsrNonCanonical::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCsrNonCanonical::Bool(_) => CsrNonCanonical::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -270,6 +276,7 @@ impl ArrayChunk for DynCscMatrix {
             DynCscMatrix::I16(_) => CscMatrix::<i16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::I32(_) => CscMatrix::<i32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::I64(_) => CscMatrix::<i64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
+            DynCscMatrix::F16(_) => CscMatrix::<f16>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::F32(_) => CscMatrix::<f32>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::F64(_) => CscMatrix::<f64>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
             DynCscMatrix::Bool(_) => CscMatrix::<bool>::write_by_chunk(iter.map(|x| x.try_into().unwrap()), location, name),
@@ -342,9 +349,123 @@ impl<T: BackendData+Scalar> ArrayChunk for CscMatrix<T> {
     }
 }
 
+/// Append rows to the array stored in `container`, growing the underlying
+/// datasets in place. Only dense arrays and CSR matrices are supported.
+pub(crate) fn append_rows<B: Backend>(container: &DataContainer<B>, data: &ArrayData) -> Result<()> {
+    macro_rules! append {
+        ($data:expr, $ty:ident, $f:ident, $c:expr) => {
+            match $data {
+                $ty::U8(x) => $f::<B, _>($c, x),
+                $ty::U16(x) => $f::<B, _>($c, x),
+                $ty::U32(x) => $f::<B, _>($c, x),
+                $ty::U64(x) => $f::<B, _>($c, x),
+                $ty::Usize(x) => $f::<B, _>($c, x),
+                $ty::I8(x) => $f::<B, _>($c, x),
+                $ty::I16(x) => $f::<B, _>($c, x),
+                $ty::I32(x) => $f::<B, _>($c, x),
+                $ty::I64(x) => $f::<B, _>($c, x),
+                $ty::F16(x) => $f::<B, _>($c, x),
+                $ty::F32(x) => $f::<B, _>($c, x),
+                $ty::F64(x) => $f::<B, _>($c, x),
+                $ty::Bool(x) => $f::<B, _>($c, x),
+                $ty::String(x) => $f::<B, _>($c, x),
+                #[allow(unreachable_patterns)]
+                _ => bail!("cannot append categorical arrays"),
+            }
+        };
+    }
 
+    match data {
+        ArrayData::Array(x) => {
+            let dataset = container.as_dataset()?;
+            ensure!(!is_transposed::<B>(dataset)?, "cannot append rows to an array stored transposed");
+            append!(x, DynArray, append_array, dataset)
+        }
+        ArrayData::CsrMatrix(x) => append!(x, DynCsrMatrix, append_csr, container.as_group()?),
+        _ => bail!("appending rows is only supported for dense arrays and csr matrices"),
+    }
+}
 
+/// Shrink the array stored in `container` to its first `n` rows, which
+/// undoes [`append_rows`].
+pub(crate) fn truncate_rows<B: Backend>(container: &DataContainer<B>, n: usize) -> Result<()> {
+    match container {
+        DataContainer::Dataset(dataset) => {
+            let mut shape = dataset.shape();
+            shape[0] = n;
+            dataset.reshape(&shape)
+        }
+        DataContainer::Group(group) => {
+            let indptr = group.open_dataset("indptr")?;
+            let nnz = indptr.read_array_slice::<usize, _, Ix1>(&[SelectInfoElem::from(n..n + 1)])?[0];
+            group.open_dataset("data")?.reshape(&nnz.into())?;
+            group.open_dataset("indices")?.reshape(&nnz.into())?;
+            indptr.reshape(&(n + 1).into())?;
+            let mut shape: Vec<usize> = group.read_array_attr::<usize, Ix1>("shape")?.to_vec();
+            shape[0] = n;
+            group.write_array_attr("shape", &shape)
+        }
+    }
+}
 
+fn append_array<'a, B: Backend, T: BackendData>(
+    dataset: &B::Dataset,
+    data: impl Into<ArrayView<'a, T, IxDyn>>,
+) -> Result<()> {
+    let data = data.into();
+    let mut shape = dataset.shape();
+    ensure!(
+        shape.ndim() == data.ndim() && shape.as_ref()[1..] == data.shape()[1..],
+        "cannot append an array of shape {:?} to an array of shape {}",
+        data.shape(),
+        shape,
+    );
+    let start = shape[0];
+    shape[0] += data.shape()[0];
+    dataset.reshape(&shape)?;
+    let rows = SelectInfoElem::from(start..shape[0]);
+    let full = SelectInfoElem::full();
+    let slice = rows.set_axis(0, shape.ndim(), &full);
+    dataset.write_array_slice(data, slice.as_slice())
+}
 
+fn append_csr<B: Backend, T: BackendData>(group: &B::Group, csr: &CsrMatrix<T>) -> Result<()> {
+    let mut shape: Vec<usize> = group.read_array_attr::<usize, Ix1>("shape")?.to_vec();
+    ensure!(
+        shape[1] == csr.ncols(),
+        "cannot append a csr matrix with {} columns to a csr matrix with {} columns",
+        csr.ncols(),
+        shape[1],
+    );
+    let (indptr, indices, data) = csr.csr_data();
+    let data_set = group.open_dataset("data")?;
+    let nnz = data_set.shape()[0];
+    append_array::<B, T>(&data_set, ArrayView1::from(data).into_dyn())?;
+    append_indices::<B>(&group.open_dataset("indices")?, indices.to_vec())?;
+    append_indices::<B>(&group.open_dataset("indptr")?, indptr[1..].iter().map(|x| x + nnz).collect())?;
+    shape[0] += csr.nrows();
+    group.write_array_attr("shape", &shape)
+}
 
+/// Append indices, converted to the integer type of the dataset.
+fn append_indices<B: Backend>(dataset: &B::Dataset, values: Vec<usize>) -> Result<()> {
+    macro_rules! append {
+        ($ty:ty) => {{
+            let values: Vec<$ty> = values
+                .into_iter()
+                .map(<$ty>::try_from)
+                .collect::<std::result::Result<_, _>>()
+                .context(concat!("indices do not fit in ", stringify!($ty)))?;
+            append_array::<B, $ty>(dataset, ArrayView1::from(&values).into_dyn())
+        }};
+    }
 
+    match dataset.dtype()? {
+        ScalarType::I32 => append!(i32),
+        ScalarType::I64 => append!(i64),
+        ScalarType::U32 => append!(u32),
+        ScalarType::U64 => append!(u64),
+        ScalarType::Usize => append!(usize),
+        ty => bail!("unsupported index type: {}", ty),
+    }
+}
@@ -1,6 +1,8 @@
+use std::collections::HashSet;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::backend::{Backend, DataContainer, DatasetOp, GroupOp, LocationOp};
+use crate::backend::{with_write_config, Backend, DataContainer, DatasetOp, GroupOp, LocationOp, ScalarType, WriteConfig};
 use crate::data::array::slice::{SelectInfoElem, Shape};
 use crate::data::array::{CategoricalArray, DynArray};
 use crate::data::data_traits::*;
@@ -8,15 +10,19 @@ use crate::data::index::{Index, Interval};
 use crate::data::scalar::DynScalar;
 
 use log::warn;
-use anyhow::{bail, Result};
-use ndarray::{Array1, Array2};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use indexmap::IndexMap;
+use ndarray::{Array1, Array2, Ix1};
 use polars::chunked_array::ChunkedArray;
-use polars::datatypes::{CategoricalChunkedBuilder, DataType};
-use polars::prelude::IntoSeries;
-use polars::prelude::{DataFrame, Series};
+use polars::datatypes::{BooleanChunked, CategoricalChunkedBuilder, DataType};
+use polars::prelude::{ChunkFillNullValue, FillNullStrategy, IntoSeries};
+use polars::prelude::{DataFrame, NamedFrom, Series};
 
 use super::{BoundedSelectInfo, BoundedSelectInfoElem};
 
+mod query;
+pub use query::{CmpOp, Query, Value};
+
 impl WriteData for DataFrame {
     fn data_type(&self) -> crate::backend::DataType {
         crate::backend::DataType::DataFrame
@@ -40,8 +46,9 @@ impl WriteData for DataFrame {
             .map(|x| x.to_owned())
             .collect();
         group.write_array_attr("column-order", &columns)?;
+        let config = WriteConfig::default();
         self.iter()
-            .try_for_each(|x| x.write(&group, x.name()).map(|_| ()))?;
+            .try_for_each(|x| write_series(x, &group, x.name(), &config).map(|_| ()))?;
 
         let container = DataContainer::Group(group);
 
@@ -50,14 +57,14 @@ impl WriteData for DataFrame {
     }
 
     fn overwrite<B: Backend>(&self, mut container: DataContainer<B>) -> Result<DataContainer<B>> {
-        if let Ok(index_name) = container.read_str_attr("_index") {
+        if let Ok(index_names) = read_index_names(&container) {
             for obj in container.as_group()?.list()? {
-                if obj != index_name {
+                if !index_names.contains(&obj) {
                     container.as_group()?.delete(&obj)?;
                 }
             }
             let n = self.height();
-            if n != 0 && n != container.as_group()?.open_dataset(&index_name)?.shape()[0] {
+            if n != 0 && n != container.as_group()?.open_dataset(&index_names[0])?.shape()[0] {
                 container = DataFrameIndex::from(self.height()).overwrite(container)?;
             }
         } else {
@@ -72,20 +79,76 @@ impl WriteData for DataFrame {
             .into_iter()
             .map(|x| x.to_owned())
             .collect();
-        container.write_array_attr("column-order", &columns)?;
+        if container.read_array_attr::<String, Ix1>("column-order").ok().as_ref() != Some(&columns) {
+            container.write_array_attr("column-order", &columns)?;
+        }
         self.iter()
             .try_for_each(|x| x.write(container.as_group()?, x.name()).map(|_| ()))?;
-        container.write_str_attr("encoding-type", "dataframe")?;
-        container.write_str_attr("encoding-version", "0.2.0")?;
+        container.update_str_attr("encoding-type", "dataframe")?;
+        container.update_str_attr("encoding-version", "0.2.0")?;
 
         Ok(container)
     }
 }
 
+/// The separator joining the levels of a compound index into the names of the
+/// rows, e.g., `sample1_AAACCTG`.
+pub const INDEX_LEVEL_SEPARATOR: &str = "_";
+
+/// Read the names of the datasets holding the index from the `_index`
+/// attribute. Some tools, e.g., R packages, store a compound index with
+/// multiple levels as an array of dataset names.
+pub(crate) fn read_index_names<B: Backend>(container: &DataContainer<B>) -> Result<Vec<String>> {
+    if let Ok(name) = container.read_str_attr("_index") {
+        return Ok(vec![name]);
+    }
+    let names = container.read_array_attr::<String, Ix1>("_index")
+        .map_err(|_| anyhow!("missing attribute '_index'"))?
+        .into_raw_vec();
+    ensure!(!names.is_empty(), "the '_index' attribute is empty");
+    Ok(names)
+}
+
+/// Read the levels of a compound index as the columns of a data frame.
+/// Return `None` if the index has a single level.
+pub(crate) fn read_index_levels<B: Backend>(container: &DataContainer<B>) -> Result<Option<DataFrame>> {
+    let names = read_index_names(container)?;
+    if names.len() == 1 {
+        return Ok(None);
+    }
+    let levels = names.iter().map(|name| {
+        let mut level = Series::read::<B>(&DataContainer::open(container.as_group()?, name)?)
+            .with_context(|| format!("cannot read the index level '{}'", name))?;
+        level.rename(name);
+        Ok(level)
+    }).collect::<Result<Vec<_>>>()?;
+    Ok(Some(DataFrame::new(levels)?))
+}
+
+/// Read the column names in the order given by the `column-order` attribute.
+/// If the attribute is missing or unreadable, e.g. an empty array of floats
+/// written by other tools, the columns stored in the group are returned in
+/// storage order.
+pub(crate) fn read_column_order<B: Backend>(container: &DataContainer<B>) -> Result<Vec<String>> {
+    let group = container.as_group()?;
+    // Some writers store an empty "column-order" as a float array, so only a
+    // string attribute is read, and errors in reading it are propagated.
+    match group.attr_dtype("column-order")? {
+        Some(ScalarType::String) => Ok(group.read_array_attr::<String, Ix1>("column-order")?.into_raw_vec()),
+        _ => {
+            let index_names = read_index_names(container).unwrap_or_default();
+            Ok(group
+                .list()?
+                .into_iter()
+                .filter(|x| !index_names.contains(x) && !x.starts_with("__"))
+                .collect())
+        }
+    }
+}
+
 impl ReadData for DataFrame {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
-        let columns: Array1<String> = container.read_array_attr("column-order")?;
-        columns
+        read_column_order(container)?
             .into_iter()
             .map(|x| {
                 let name = x.as_str();
@@ -138,9 +201,9 @@ impl ArrayOp for DataFrame {
 impl ReadArrayData for DataFrame {
     fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
         let group = container.as_group()?;
-        let index = group.read_str_attr("_index")?;
-        let nrows = group.open_dataset(&index)?.shape()[0];
-        let columns: Array1<String> = container.read_array_attr("column-order")?;
+        let index = read_index_names(container)?;
+        let nrows = group.open_dataset(&index[0])?.shape()[0];
+        let columns = read_column_order(container)?;
         Ok((nrows, columns.len()).into())
     }
 
@@ -149,15 +212,12 @@ impl ReadArrayData for DataFrame {
         B: Backend,
         S: AsRef<SelectInfoElem>,
     {
-        let columns: Vec<String> = container.read_array_attr("column-order")?.to_vec();
+        let columns = read_column_order(container)?;
         BoundedSelectInfoElem::new(&info.as_ref()[1], columns.len())
             .iter()
             .map(|i| {
                 let name = &columns[i];
-                let mut series = container
-                    .as_group()?
-                    .open_dataset(name)
-                    .map(DataContainer::Dataset)
+                let mut series = DataContainer::<B>::open(container.as_group()?, name)
                     .and_then(|x| Series::read_select::<B, _>(&x, &info[..1]))?;
                 series.rename(name);
                 Ok(series)
@@ -177,131 +237,317 @@ impl WriteData for Series {
         location: &G,
         name: &str,
     ) -> Result<DataContainer<B>> {
-        let array: DynArray = match self.dtype() {
-            DataType::UInt8 => self
-                .u8()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::UInt16 => self
-                .u16()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::UInt32 => self
-                .u32()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::UInt64 => self
-                .u64()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Int8 => self
-                .i8()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Int16 => self
-                .i16()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Int32 => self
-                .i32()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Int64 => self
-                .i64()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Float32 => self
-                .f32()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Float64 => self
-                .f64()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Boolean => self
-                .bool()?
-                .into_iter()
-                .map(|x| x.unwrap())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::String => self
-                .str()?
-                .into_iter()
-                .map(|x| x.unwrap().to_string())
-                .collect::<Array1<_>>()
-                .into_dyn()
-                .into(),
-            DataType::Categorical(_,_) => self
-                .categorical()?
-                .iter_str()
-                .map(|x| x.unwrap())
-                .collect::<CategoricalArray>()
-                .into(),
-            other => bail!("Unsupported series data type: {:?}", other),
-        };
-        array.write(location, name)
+        write_series(self, location, name, &WriteConfig::default())
+    }
+}
+
+/// Write a column with the given configuration, which also decides whether a
+/// string column is written as a categorical array.
+pub(crate) fn write_series<B: Backend, G: GroupOp<Backend = B>>(
+    series: &Series,
+    location: &G,
+    name: &str,
+    config: &WriteConfig,
+) -> Result<DataContainer<B>> {
+    check_writable(series, name)?;
+    if series.null_count() > 0 && (series.dtype().is_integer() || series.dtype() == &DataType::Boolean) {
+        return with_write_config(config.clone(), || write_nullable(series, location, name));
+    }
+    let array = series_to_array(series, config.categorical_threshold)?;
+    with_write_config(config.clone(), || array.write(location, name))
+}
+
+/// Convert a column without nulls in integer or boolean columns to the array
+/// it is stored as. String columns become categorical arrays according to
+/// `categorical_threshold`, see [`WriteConfig::categorical_threshold`].
+pub(crate) fn series_to_array(series: &Series, categorical_threshold: Option<f64>) -> Result<DynArray> {
+    let array: DynArray = match series.dtype() {
+        DataType::UInt8 => series
+            .u8()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::UInt16 => series
+            .u16()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::UInt32 => series
+            .u32()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::UInt64 => series
+            .u64()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Int8 => series
+            .i8()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Int16 => series
+            .i16()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Int32 => series
+            .i32()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Int64 => series
+            .i64()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Float32 => series
+            .f32()?
+            .into_iter()
+            .map(|x| x.unwrap_or(f32::NAN))
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Float64 => series
+            .f64()?
+            .into_iter()
+            .map(|x| x.unwrap_or(f64::NAN))
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Boolean => series
+            .bool()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::String if is_categorical_like(series, categorical_threshold)? => series
+            .str()?
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect::<CategoricalArray>()
+            .into(),
+        DataType::String => series
+            .str()?
+            .into_iter()
+            .map(|x| x.unwrap().to_string())
+            .collect::<Array1<_>>()
+            .into_dyn()
+            .into(),
+        DataType::Categorical(_,_) => series
+            .categorical()?
+            .iter_str()
+            .map(|x| x.unwrap())
+            .collect::<CategoricalArray>()
+            .into(),
+        other => bail!("Unsupported series data type: {:?}", other),
+    };
+    Ok(array)
+}
+
+/// Bits of the current categorical threshold, 0.5 by default. `NaN` means disabled.
+static CATEGORICAL_THRESHOLD: AtomicU64 = AtomicU64::new(0x3FE0_0000_0000_0000);
+
+/// The threshold below which string columns are written as categorical arrays,
+/// as the number of unique values divided by the length of the column.
+/// `None` if the conversion is disabled.
+pub fn categorical_threshold() -> Option<f64> {
+    let threshold = f64::from_bits(CATEGORICAL_THRESHOLD.load(Ordering::Relaxed));
+    if threshold.is_nan() {
+        None
+    } else {
+        Some(threshold)
+    }
+}
+
+/// Set the threshold below which string columns of dataframes are written as
+/// categorical arrays, like Python anndata does. Columns with the polars
+/// `Categorical` type are always written as categorical arrays. Use `None` to
+/// write all string columns as string arrays. Default: 0.5.
+///
+/// This sets the default of [`WriteConfig::categorical_threshold`] for the
+/// whole process. To change the threshold of a single write, set it in the
+/// [`WriteConfig`] of the write instead.
+pub fn set_categorical_threshold(threshold: Option<f64>) {
+    let threshold = threshold.unwrap_or(f64::NAN);
+    CATEGORICAL_THRESHOLD.store(threshold.to_bits(), Ordering::Relaxed);
+}
+
+/// Check that the column can be written, i.e., that its type is supported and
+/// that nulls, if any, can be stored.
+pub(crate) fn check_writable(series: &Series, name: &str) -> Result<()> {
+    let ty = series.dtype();
+    ensure!(
+        ty.is_integer() || ty.is_float() || matches!(ty, DataType::Boolean | DataType::String | DataType::Categorical(..)),
+        "cannot write column '{}' of unsupported type {}", name, ty,
+    );
+    ensure!(
+        series.null_count() == 0 || ty.is_integer() || ty.is_float() || ty == &DataType::Boolean,
+        "cannot write column '{}' as nulls are not supported in {} columns", name, ty,
+    );
+    Ok(())
+}
+
+/// Whether a string column should be written as a categorical array.
+fn is_categorical_like(series: &Series, threshold: Option<f64>) -> Result<bool> {
+    match threshold {
+        Some(threshold) if !series.is_empty() => {
+            Ok((series.n_unique()? as f64) < threshold * series.len() as f64)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Write a series containing nulls using the nullable encodings of anndata,
+/// i.e., a group storing the values and a boolean mask that is true for nulls.
+fn write_nullable<B: Backend, G: GroupOp<Backend = B>>(
+    series: &Series,
+    location: &G,
+    name: &str,
+) -> Result<DataContainer<B>> {
+    let (encoding, values) = if series.dtype() == &DataType::Boolean {
+        ("nullable-boolean", series.bool()?.fill_null_with_values(false)?.into_series())
+    } else {
+        ("nullable-integer", series.fill_null(FillNullStrategy::Zero)?)
+    };
+    let group = location.create_group(name)?;
+    values.write(&group, "values")?;
+    series.is_null().into_series().write(&group, "mask")?;
+    group.write_str_attr("encoding-type", encoding)?;
+    group.write_str_attr("encoding-version", "0.1.0")?;
+    Ok(DataContainer::Group(group))
+}
+
+/// Return the group if the container stores a nullable array.
+fn as_nullable<B: Backend>(container: &DataContainer<B>) -> Option<&B::Group> {
+    match container {
+        DataContainer::Group(group) => match group.read_str_attr("encoding-type") {
+            Ok(ty) if ty == "nullable-integer" || ty == "nullable-boolean" => Some(group),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn read_null_mask<B: Backend>(group: &B::Group) -> Result<Array1<bool>> {
+    group.open_dataset("mask")?.read_array::<bool, Ix1>()
+}
+
+/// Count the nulls of a column. Only the mask of nullable arrays is read, as
+/// other arrays cannot contain nulls.
+pub(crate) fn read_null_count<B: Backend>(container: &DataContainer<B>) -> Result<usize> {
+    match as_nullable(container) {
+        Some(group) => Ok(read_null_mask::<B>(group)?.iter().filter(|x| **x).count()),
+        None => Ok(0),
     }
 }
 
 impl ReadData for Series {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
-        match DynArray::read(container)? {
-            DynArray::I8(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::I16(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::I32(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::I64(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::U8(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::U16(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::U32(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::U64(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::Usize(x) => Ok(x.iter().map(|x| *x as u64).collect::<Series>()),
-            DynArray::F32(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::F64(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::Bool(x) => Ok(x.iter().collect::<Series>()),
-            DynArray::String(x) => Ok(x.iter().map(|x| x.as_str()).collect::<Series>()),
-            DynArray::Categorical(arr) => {
-                let result = CategoricalChunkedBuilder::new(
-                    "", arr.codes.len(), polars::datatypes::CategoricalOrdering::Lexical
-                ).drain_iter_and_finish(
-                    arr.codes
-                        .into_iter()
-                        .map(|i| Some(arr.categories[i as usize].as_str())),
-                ).into_series();
-                Ok(result)
+        let series = if let Some(group) = as_nullable(container) {
+            let values = Series::read(&DataContainer::<B>::open(group, "values")?)?;
+            with_null_mask(values, read_null_mask::<B>(group)?)?
+        } else {
+            series_from_dyn_array(DynArray::read(container)?)
+        };
+        with_bool_hint(container, series)
+    }
+}
+
+/// Some writers store boolean columns as 0/1 integers, and mark them with a
+/// `dtype` attribute of "bool" or "boolean". These columns are converted to
+/// boolean, while other integer columns are kept as is.
+fn with_bool_hint<B: Backend>(container: &DataContainer<B>, series: Series) -> Result<Series> {
+    let hint = container.read_str_attr("dtype");
+    if series.dtype().is_integer() && matches!(hint.as_deref(), Ok("bool") | Ok("boolean")) {
+        Ok(series.cast(&DataType::Boolean)?)
+    } else {
+        Ok(series)
+    }
+}
+
+/// Convert an integer column to boolean if all its values are 0 or 1. Other
+/// columns, including empty or all-null integer columns, are returned
+/// unchanged. As integer columns that happen to only contain 0 and 1 are
+/// converted as well, this is never done implicitly.
+pub fn coerce_bool_column(series: Series) -> Result<Series> {
+    if series.dtype().is_integer() {
+        if let (Some(min), Some(max)) = (series.min::<f64>()?, series.max::<f64>()?) {
+            if min >= 0.0 && max <= 1.0 {
+                return Ok(series.cast(&DataType::Boolean)?);
             }
         }
     }
+    Ok(series)
+}
+
+/// Apply [`coerce_bool_column`] to all columns of the dataframe.
+pub fn coerce_bool_columns(df: DataFrame) -> Result<DataFrame> {
+    df.get_columns().iter().map(|x| coerce_bool_column(x.clone())).collect()
+}
+
+/// Replace NaN values of a float column with nulls, the conventional
+/// encoding of missing values in float columns. Other columns are returned
+/// unchanged.
+pub fn nan_as_null_column(series: Series) -> Result<Series> {
+    if series.dtype().is_float() {
+        let mask = Array1::from_iter(series.is_nan()?.into_iter().map(|x| x.unwrap_or(false)));
+        with_null_mask(series, mask)
+    } else {
+        Ok(series)
+    }
+}
+
+/// Set the elements of `values` to null where `mask` is true.
+fn with_null_mask(values: Series, mask: Array1<bool>) -> Result<Series> {
+    let not_null: BooleanChunked = mask.iter().map(|x| !x).collect();
+    let nulls = Series::full_null("", values.len(), values.dtype());
+    Ok(values.zip_with(&not_null, &nulls)?)
+}
+
+fn series_from_dyn_array(arr: DynArray) -> Series {
+    match arr {
+        DynArray::I8(x) => x.iter().collect::<Series>(),
+        DynArray::I16(x) => x.iter().collect::<Series>(),
+        DynArray::I32(x) => x.iter().collect::<Series>(),
+        DynArray::I64(x) => x.iter().collect::<Series>(),
+        DynArray::U8(x) => x.iter().collect::<Series>(),
+        DynArray::U16(x) => x.iter().collect::<Series>(),
+        DynArray::U32(x) => x.iter().collect::<Series>(),
+        DynArray::U64(x) => x.iter().collect::<Series>(),
+        DynArray::Usize(x) => x.iter().map(|x| *x as u64).collect::<Series>(),
+        // polars has no 16-bit float type.
+        DynArray::F16(x) => x.iter().map(|x| x.to_f32()).collect::<Series>(),
+        DynArray::F32(x) => x.iter().collect::<Series>(),
+        DynArray::F64(x) => x.iter().collect::<Series>(),
+        DynArray::Bool(x) => x.iter().collect::<Series>(),
+        DynArray::String(x) => x.iter().map(|x| x.as_str()).collect::<Series>(),
+        DynArray::Categorical(arr) => CategoricalChunkedBuilder::new(
+            "", arr.codes.len(), polars::datatypes::CategoricalOrdering::Lexical
+        ).drain_iter_and_finish(
+            arr.codes
+                .into_iter()
+                .map(|i| Some(arr.categories[i as usize].as_str())),
+        ).into_series(),
+    }
 }
 
 impl HasShape for Series {
@@ -331,18 +577,84 @@ impl ArrayOp for Series {
 
 impl ReadArrayData for Series {
     fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
-        Ok(container.as_dataset()?.shape().into())
+        match as_nullable(container) {
+            Some(group) => Ok(group.open_dataset("mask")?.shape()),
+            None => Ok(container.as_dataset()?.shape()),
+        }
     }
 
+    /// Only the selected elements are read from the storage. For categorical
+    /// columns only the selected codes are read, and for nullable columns
+    /// the selected elements of both the values and the mask.
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
     where
         B: Backend,
         S: AsRef<SelectInfoElem>,
     {
-        Ok(Self::read(container)?.select(info))
+        let series = if let Some(group) = as_nullable(container) {
+            let values = Series::read_select(&DataContainer::<B>::open(group, "values")?, info)?;
+            let mask = group.open_dataset("mask")?.read_array_slice::<bool, _, Ix1>(info)?;
+            with_null_mask(values, mask)?
+        } else {
+            series_from_dyn_array(DynArray::read_select(container, info)?)
+        };
+        with_bool_hint(container, series)
     }
 }
 
+/// Summary statistics of the columns in a dataframe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataFrameSummary {
+    /// Statistics of the numeric columns. The first column, "statistic", contains
+    /// the names of the statistics: "count", "mean", "std", "min" and "max".
+    pub numeric: DataFrame,
+    /// The number of occurrences of each category in the categorical columns.
+    pub categorical: IndexMap<String, DataFrame>,
+}
+
+impl DataFrameSummary {
+    /// Compute the summary from a sequence of columns. Columns that are neither
+    /// numeric nor categorical are ignored.
+    pub fn from_columns<I>(columns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = Result<Series>>,
+    {
+        let mut numeric = vec![Series::new("statistic", ["count", "mean", "std", "min", "max"])];
+        let mut categorical = IndexMap::new();
+        for series in columns {
+            let series = series?;
+            match series.dtype() {
+                DataType::Categorical(_, _) => {
+                    let counts = series.categorical()?.value_counts()?;
+                    categorical.insert(series.name().to_string(), counts);
+                }
+                ty if ty.is_numeric() || ty == &DataType::Boolean => {
+                    let series = series.cast(&DataType::Float64)?;
+                    let stats: [Option<f64>; 5] = [
+                        Some((series.len() - series.null_count()) as f64),
+                        series.mean(),
+                        series.std(1),
+                        series.min()?,
+                        series.max()?,
+                    ];
+                    numeric.push(Series::new(series.name(), stats));
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            numeric: DataFrame::new(numeric)?,
+            categorical,
+        })
+    }
+}
+
+/// Compute summary statistics of the columns, similar to `pandas.DataFrame.describe`.
+/// See [`DataFrameSummary`].
+pub fn describe(df: &DataFrame) -> Result<DataFrameSummary> {
+    DataFrameSummary::from_columns(df.iter().map(|x| Ok(x.clone())))
+}
+
 #[derive(Debug, Clone)]
 pub struct DataFrameIndex {
     pub index_name: String,
@@ -375,10 +687,36 @@ impl DataFrameIndex {
         self.index.get_index(k)
     }
 
+    /// Return the position of each name, or `None` if it is not in the index.
+    pub fn get_indexer<I, S>(&self, names: I) -> Vec<Option<usize>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        names.into_iter().map(|x| self.get_index(x.as_ref())).collect()
+    }
+
     pub fn into_vec(self) -> Vec<String> {
         self.index.into_vec()
     }
 
+    /// Append `n` entries to the index. The names of the new entries may be
+    /// omitted for a range index, which is then extended with the next integers.
+    pub fn append(&mut self, n: usize, names: Option<Vec<String>>) -> Result<()> {
+        self.index = match names {
+            Some(names) => {
+                ensure!(names.len() == n, "expect {} names, but receive {}", n, names.len());
+                self.index.iter().chain(names).collect()
+            }
+            None => match &self.index {
+                Index::Range(r) => Index::Range(r.start..r.end + n),
+                x if x.is_empty() => Index::Range(0..n),
+                _ => bail!("names of the new entries must be given as the index is not a range"),
+            },
+        };
+        Ok(())
+    }
+
     pub fn select(&self, select: &SelectInfoElem) -> Self {
         let index = self.index.select(select);
         Self {
@@ -415,13 +753,33 @@ impl WriteData for DataFrameIndex {
     }
 
     fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
-        if let Ok(index_name) = container.read_str_attr("_index") {
-            container.as_group()?.delete(&index_name)?;
+        self.write_into(&container)?;
+        Ok(container)
+    }
+}
+
+impl DataFrameIndex {
+    /// Replace the index stored in the dataframe `container`. Unlike
+    /// [`WriteData::overwrite`], the container is borrowed, so that it is kept
+    /// by the caller if writing fails.
+    pub(crate) fn write_into<B: Backend>(&self, container: &DataContainer<B>) -> Result<()> {
+        // The levels of a compound index are replaced by a single dataset.
+        if let Ok(index_names) = read_index_names(&container) {
+            for name in &index_names {
+                if container.as_group()?.exists(name)? {
+                    container.as_group()?.delete(name)?;
+                }
+            }
+            if index_names.len() > 1 {
+                container.write_scalar_attr("_index", self.index_name.clone())?;
+            }
         }
-        container.write_str_attr("_index", &self.index_name)?;
+        container.update_str_attr("_index", &self.index_name)?;
         let group = container.as_group()?;
         let arr: Array1<String> = self.clone().into_iter().collect();
         let data = group.create_array_data(&self.index_name, &arr, Default::default())?;
+        data.write_str_attr("encoding-type", "string-array")?;
+        data.write_str_attr("encoding-version", "0.2.0")?;
         match &self.index {
             Index::List(_) => { data.write_str_attr("index_type", "list")?; },
             Index::Intervals(intervals) => {
@@ -441,12 +799,15 @@ impl WriteData for DataFrameIndex {
                 data.write_scalar_attr("end", range.end)?;
             },
         }
-        Ok(container)
+        Ok(())
     }
 }
 
 impl ReadData for DataFrameIndex {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        if let Some(levels) = read_index_levels(container)? {
+            return compound_index(&levels);
+        }
         let index_name = container.read_str_attr("_index")?;
         let dataset = container.as_group()?.open_dataset(&index_name)?;
         match dataset.read_str_attr("index_type").as_ref().map_or("list", |x| x.as_str()) {
@@ -473,6 +834,29 @@ impl ReadData for DataFrameIndex {
     }
 }
 
+/// Join the levels of a compound index with [`INDEX_LEVEL_SEPARATOR`]. The
+/// levels must have no missing values, and the joined names must be unique,
+/// which fails if a level contains the separator, e.g., `("a", "b_c")` and
+/// `("a_b", "c")` both give `"a_b_c"`.
+fn compound_index(levels: &DataFrame) -> Result<DataFrameIndex> {
+    let levels = levels.get_columns().iter().map(|level| {
+        let values = level.cast(&DataType::String)?;
+        values.str()?.into_iter().enumerate().map(|(i, x)| x.map(str::to_string).ok_or_else(||
+            anyhow!("the level '{}' of the compound index has a missing value in row {}", level.name(), i)
+        )).collect::<Result<Vec<_>>>()
+    }).collect::<Result<Vec<_>>>()?;
+    let names: Vec<String> = (0..levels[0].len())
+        .map(|i| levels.iter().map(|x| x[i].as_str()).collect::<Vec<_>>().join(INDEX_LEVEL_SEPARATOR))
+        .collect();
+    let mut seen = HashSet::with_capacity(names.len());
+    if let Some(name) = names.iter().find(|x| !seen.insert(x.as_str())) {
+        bail!("the levels of the compound index give the duplicate name '{}'", name);
+    }
+    let mut index: DataFrameIndex = names.into();
+    index.index_name = "_index".to_string();
+    Ok(index)
+}
+
 impl<D> From<D> for DataFrameIndex
 where
     Index: From<D>,
@@ -498,4 +882,65 @@ where
             index: iter.into_iter().collect(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::CategoricalOrdering;
+
+    #[test]
+    fn test_describe() {
+        let cell_type = Series::new("cell_type", ["B", "T", "T", "NK", "T"])
+            .cast(&DataType::Categorical(None, CategoricalOrdering::Physical))
+            .unwrap();
+        let df = DataFrame::new(vec![
+            Series::new("n_genes", [1u32, 2, 3, 4, 5]),
+            cell_type,
+            Series::new("barcode", ["a", "b", "c", "d", "e"]),
+        ]).unwrap();
+        let summary = describe(&df).unwrap();
+
+        assert_eq!(summary.numeric.get_column_names(), vec!["statistic", "n_genes"]);
+        let stats = summary.numeric.column("n_genes").unwrap().f64().unwrap()
+            .into_no_null_iter().collect::<Vec<_>>();
+        assert_eq!(stats[0], 5.0);
+        assert_eq!(stats[1], 3.0);
+        assert!((stats[2] - 2.5f64.sqrt()).abs() < 1e-12);
+        assert_eq!(stats[3], 1.0);
+        assert_eq!(stats[4], 5.0);
+
+        assert_eq!(summary.categorical.len(), 1);
+        let counts = &summary.categorical["cell_type"];
+        let categories = counts.column("cell_type").unwrap().cast(&DataType::String).unwrap();
+        assert_eq!(categories.str().unwrap().get(0), Some("T"));
+        assert_eq!(counts.column("counts").unwrap().u32().unwrap().get(0), Some(3));
+        assert_eq!(counts.column("counts").unwrap().u32().unwrap().into_no_null_iter().sum::<u32>(), 5);
+    }
+
+    #[test]
+    fn test_nan_as_null() {
+        let series = nan_as_null_column(Series::new("x", [f32::NAN, 1.0, f32::NAN])).unwrap();
+        assert_eq!(series, Series::new("x", [None, Some(1.0f32), None]));
+        let series = Series::new("y", [1, 2]);
+        assert_eq!(nan_as_null_column(series.clone()).unwrap(), series);
+    }
+
+    #[test]
+    fn test_compound_index() {
+        let levels = polars::df!("sample" => ["s1", "s1"], "barcode" => ["AAC", "GTT"]).unwrap();
+        assert_eq!(compound_index(&levels).unwrap().into_vec(), vec!["s1_AAC", "s1_GTT"]);
+        let levels = polars::df!("sample" => ["a", "a_b"], "barcode" => ["b_c", "c"]).unwrap();
+        assert!(compound_index(&levels).is_err());
+        let levels = polars::df!("sample" => [Some("s1"), None], "barcode" => ["AAC", "GTT"]).unwrap();
+        assert!(compound_index(&levels).is_err());
+    }
+
+    #[test]
+    fn test_get_indexer() {
+        let index: DataFrameIndex = ["a", "b", "c"].into_iter().map(|x| x.to_string()).collect();
+        assert_eq!(index.get_indexer(["c", "x", "a", "a"]), vec![Some(2), None, Some(0), Some(0)]);
+
+        let index = DataFrameIndex::from(3);
+        assert_eq!(index.get_indexer(["2", "3", "a"]), vec![Some(2), None, None]);
+    }
+}
@@ -0,0 +1,408 @@
+//! A small expression language to select the rows of a dataframe, e.g.,
+//! `n_counts > 500 & cell_type in ['T', 'NK']`.
+//!
+//! An expression is made of:
+//!
+//! - comparisons of a column with a literal: `==`, `!=`, `<`, `<=`, `>`, `>=`,
+//! - membership tests: `column in [value, ...]`,
+//! - null tests: `isnull(column)`,
+//! - `&`, `|` and `~` to combine them, and parentheses for grouping.
+//!
+//! Literals are numbers, strings in single or double quotes, and `True` or
+//! `False`. Column names containing other characters than letters, digits,
+//! `_` and `.` can be quoted with backticks. Categorical columns are compared
+//! against their labels. Null values never match a comparison or membership
+//! test.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use anyhow::{bail, ensure, Context, Result};
+use polars::datatypes::DataType;
+use polars::prelude::Series;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn matches(&self, ordering: Option<Ordering>) -> bool {
+        match ordering {
+            None => false,
+            Some(ordering) => match self {
+                CmpOp::Eq => ordering.is_eq(),
+                CmpOp::Ne => ordering.is_ne(),
+                CmpOp::Lt => ordering.is_lt(),
+                CmpOp::Le => ordering.is_le(),
+                CmpOp::Gt => ordering.is_gt(),
+                CmpOp::Ge => ordering.is_ge(),
+            },
+        }
+    }
+}
+
+impl Display for CmpOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// A literal value in a query.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Value {
+    Num(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Num(_) => "number",
+            Value::Str(_) => "string",
+            Value::Bool(_) => "boolean",
+        }
+    }
+}
+
+/// A parsed query, see the module documentation for the syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Compare(String, CmpOp, Value),
+    In(String, Vec<Value>),
+    IsNull(String),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let tokens = tokenize(expr).with_context(|| format!("invalid query '{}'", expr))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.parse_or()
+            .and_then(|query| match parser.peek() {
+                None => Ok(query),
+                Some(token) => bail!("unexpected {}", token),
+            })
+            .with_context(|| format!("invalid query '{}'", expr))?;
+        Ok(query)
+    }
+
+    /// The names of the columns referenced by the query, in order of first
+    /// appearance.
+    pub fn columns(&self) -> Vec<&str> {
+        fn collect<'a>(query: &'a Query, result: &mut Vec<&'a str>) {
+            match query {
+                Query::Compare(name, _, _) | Query::In(name, _) | Query::IsNull(name) => {
+                    if !result.contains(&name.as_str()) {
+                        result.push(name);
+                    }
+                }
+                Query::Not(x) => collect(x, result),
+                Query::And(a, b) | Query::Or(a, b) => {
+                    collect(a, result);
+                    collect(b, result);
+                }
+            }
+        }
+        let mut result = Vec::new();
+        collect(self, &mut result);
+        result
+    }
+
+    /// Evaluate the query, returning for each row whether it matches. All
+    /// columns returned by [`Query::columns`] must be present in `columns`.
+    pub fn eval(&self, columns: &HashMap<&str, Series>) -> Result<Vec<bool>> {
+        let column = |name: &str| columns.get(name)
+            .with_context(|| format!("column '{}' does not exist", name));
+        match self {
+            Query::Compare(name, op, value) => Ok(values(column(name)?, value)?
+                .into_iter()
+                .map(|x| op.matches(x.and_then(|x| x.partial_cmp(value))))
+                .collect()),
+            Query::In(name, list) => {
+                ensure!(
+                    list.iter().all(|x| x.kind() == list[0].kind()),
+                    "values in the list of column '{}' must have the same type", name
+                );
+                Ok(values(column(name)?, &list[0])?
+                    .into_iter()
+                    .map(|x| x.is_some_and(|x| list.contains(&x)))
+                    .collect())
+            }
+            Query::IsNull(name) => Ok(column(name)?.is_null().into_iter().map(|x| x.unwrap_or(false)).collect()),
+            Query::Not(x) => Ok(x.eval(columns)?.into_iter().map(|x| !x).collect()),
+            Query::And(a, b) => Ok(a.eval(columns)?.into_iter().zip(b.eval(columns)?).map(|(a, b)| a && b).collect()),
+            Query::Or(a, b) => Ok(a.eval(columns)?.into_iter().zip(b.eval(columns)?).map(|(a, b)| a || b).collect()),
+        }
+    }
+}
+
+/// Convert the column to values comparable with `value`.
+fn values(series: &Series, value: &Value) -> Result<Vec<Option<Value>>> {
+    let name = series.name();
+    let dtype = series.dtype();
+    let result = match value {
+        Value::Num(_) if dtype.is_numeric() => series.cast(&DataType::Float64)?
+            .f64()?.into_iter().map(|x| x.map(Value::Num)).collect(),
+        Value::Str(_) if matches!(dtype, DataType::String | DataType::Categorical(_, _)) => series
+            .cast(&DataType::String)?
+            .str()?.into_iter().map(|x| x.map(|x| Value::Str(x.to_string()))).collect(),
+        Value::Bool(_) if dtype == &DataType::Boolean => series
+            .bool()?.into_iter().map(|x| x.map(Value::Bool)).collect(),
+        _ => bail!("cannot compare column '{}' of type {} with a {}", name, dtype, value.kind()),
+    };
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Value(Value),
+    Op(CmpOp),
+    In,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(x) => write!(f, "'{}'", x),
+            Token::Value(Value::Num(x)) => write!(f, "{}", x),
+            Token::Value(Value::Str(x)) => write!(f, "'{}'", x),
+            Token::Value(Value::Bool(x)) => write!(f, "{}", x),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::In => write!(f, "'in'"),
+            Token::And => write!(f, "'&'"),
+            Token::Or => write!(f, "'|'"),
+            Token::Not => write!(f, "'~'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::LBracket => write!(f, "'['"),
+            Token::RBracket => write!(f, "']'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, width) = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '[' => (Token::LBracket, 1),
+            ']' => (Token::RBracket, 1),
+            ',' => (Token::Comma, 1),
+            '&' => (Token::And, 1),
+            '|' => (Token::Or, 1),
+            '~' => (Token::Not, 1),
+            '=' if next == Some('=') => (Token::Op(CmpOp::Eq), 2),
+            '!' if next == Some('=') => (Token::Op(CmpOp::Ne), 2),
+            '<' if next == Some('=') => (Token::Op(CmpOp::Le), 2),
+            '>' if next == Some('=') => (Token::Op(CmpOp::Ge), 2),
+            '<' => (Token::Op(CmpOp::Lt), 1),
+            '>' => (Token::Op(CmpOp::Gt), 1),
+            '\'' | '"' | '`' => {
+                let end = chars[i + 1..].iter().position(|x| *x == c)
+                    .with_context(|| format!("unterminated quote at position {}", i))?;
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                i += end + 2;
+                tokens.push(if c == '`' { Token::Ident(text) } else { Token::Value(Value::Str(text)) });
+                continue;
+            }
+            _ if c.is_ascii_digit() || (matches!(c, '-' | '.') && next.is_some_and(|x| x.is_ascii_digit() || x == '.')) => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.'
+                    || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+                {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let num = text.parse::<f64>().with_context(|| format!("invalid number '{}'", text))?;
+                tokens.push(Token::Value(Value::Num(num)));
+                continue;
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.')) {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push(match text.as_str() {
+                    "in" => Token::In,
+                    "True" | "true" => Token::Value(Value::Bool(true)),
+                    "False" | "false" => Token::Value(Value::Bool(false)),
+                    _ => Token::Ident(text),
+                });
+                continue;
+            }
+            _ => bail!("unexpected character '{}' at position {}", c, i),
+        };
+        i += width;
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.pos).cloned().context("unexpected end of query")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        let token = self.next()?;
+        ensure!(token == expected, "expected {} but found {}", expected, token);
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Query> {
+        let mut query = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            query = Query::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Query> {
+        let mut query = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            query = Query::And(Box::new(query), Box::new(self.parse_unary()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query> {
+        match self.next()? {
+            Token::Not => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Token::LParen => {
+                let query = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(query)
+            }
+            Token::Ident(name) if name == "isnull" && self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                let column = self.parse_column()?;
+                self.expect(Token::RParen)?;
+                Ok(Query::IsNull(column))
+            }
+            Token::Ident(name) => match self.next()? {
+                Token::Op(op) => Ok(Query::Compare(name, op, self.parse_value()?)),
+                Token::In => {
+                    self.expect(Token::LBracket)?;
+                    let mut values = vec![self.parse_value()?];
+                    while self.peek() == Some(&Token::Comma) {
+                        self.pos += 1;
+                        values.push(self.parse_value()?);
+                    }
+                    self.expect(Token::RBracket)?;
+                    Ok(Query::In(name, values))
+                }
+                token => bail!("expected a comparison after column '{}' but found {}", name, token),
+            },
+            token => bail!("unexpected {}", token),
+        }
+    }
+
+    fn parse_column(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            token => bail!("expected a column name but found {}", token),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.next()? {
+            Token::Value(value) => Ok(value),
+            token => bail!("expected a value but found {}", token),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::NamedFrom;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Query::parse("n_counts > 5e2 & `cell type` in ['T', \"NK\"]").unwrap(),
+            Query::And(
+                Box::new(Query::Compare("n_counts".to_string(), CmpOp::Gt, Value::Num(500.0))),
+                Box::new(Query::In(
+                    "cell type".to_string(),
+                    vec![Value::Str("T".to_string()), Value::Str("NK".to_string())],
+                )),
+            ),
+        );
+        assert_eq!(
+            Query::parse("~isnull(a) | b<=-1.5 & c == True").unwrap().columns(),
+            vec!["a", "b", "c"],
+        );
+        assert!(Query::parse("a >").is_err());
+        assert!(Query::parse("a > 1 b").is_err());
+        assert!(Query::parse("(a > 1").is_err());
+        assert!(Query::parse("a == 'x").is_err());
+    }
+
+    #[test]
+    fn test_eval() {
+        let columns = HashMap::from([
+            ("a", Series::new("a", [Some(1), None, Some(3), Some(4)])),
+            ("b", Series::new("b", ["x", "y", "z", "x"])),
+        ]);
+        let eval = |expr: &str| Query::parse(expr).unwrap().eval(&columns).unwrap();
+        assert_eq!(eval("a >= 3"), vec![false, false, true, true]);
+        assert_eq!(eval("a != 3"), vec![true, false, false, true]);
+        assert_eq!(eval("isnull(a) | b == 'z'"), vec![false, true, true, false]);
+        assert_eq!(eval("~(b in ['x', 'y']) & a < 10"), vec![false, false, true, false]);
+        assert!(Query::parse("b > 1").unwrap().eval(&columns).is_err());
+        assert!(Query::parse("c > 1").unwrap().eval(&columns).is_err());
+    }
+}
@@ -8,7 +8,9 @@ use crate::{
 };
 
 use anyhow::{bail, ensure, anyhow, Result};
+use half::f16;
 use ndarray::{ArrayView, Array, Array1, ArrayD, RemoveAxis, SliceInfoElem, Dimension, Axis};
+use smallvec::SmallVec;
 use std::collections::HashMap;
 use std::ops::Index;
 
@@ -24,6 +26,7 @@ pub enum DynArray {
     U32(ArrayD<u32>),
     U64(ArrayD<u64>),
     Usize(ArrayD<usize>),
+    F16(ArrayD<f16>),
     F32(ArrayD<f32>),
     F64(ArrayD<f64>),
     Bool(ArrayD<bool>),
@@ -37,6 +40,28 @@ impl From<CategoricalArray> for DynArray {
     }
 }
 
+/// A 1-dimensional array with a single element.
+impl From<DynScalar> for DynArray {
+    fn from(x: DynScalar) -> Self {
+        match x {
+            DynScalar::I8(x) => Self::I8(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::I16(x) => Self::I16(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::I32(x) => Self::I32(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::I64(x) => Self::I64(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::U8(x) => Self::U8(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::U16(x) => Self::U16(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::U32(x) => Self::U32(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::U64(x) => Self::U64(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::Usize(x) => Self::Usize(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::F16(x) => Self::F16(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::F32(x) => Self::F32(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::F64(x) => Self::F64(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::Bool(x) => Self::Bool(ndarray::arr1(&[x]).into_dyn()),
+            DynScalar::String(x) => Self::String(ndarray::arr1(&[x]).into_dyn()),
+        }
+    }
+}
+
 impl TryFrom<DynArray> for CategoricalArray {
     type Error = anyhow::Error;
 
@@ -83,11 +108,58 @@ impl_dyn_array_convert!(u16, U16);
 impl_dyn_array_convert!(u32, U32);
 impl_dyn_array_convert!(u64, U64);
 impl_dyn_array_convert!(usize, Usize);
+impl_dyn_array_convert!(f16, F16);
 impl_dyn_array_convert!(f32, F32);
 impl_dyn_array_convert!(f64, F64);
 impl_dyn_array_convert!(bool, Bool);
 impl_dyn_array_convert!(String, String);
 
+/// Scalar types that a `DynArray` can be converted to without loss of precision.
+pub trait FromDynArray: Sized {
+    /// Convert the array, promoting the element type where it is lossless
+    /// (e.g., `i32` to `i64` or `f32` to `f64`).
+    fn from_dyn_array(arr: DynArray) -> Result<ArrayD<Self>>;
+}
+
+macro_rules! impl_from_dyn_array {
+    ($to_type:ty, $same:ident $(, $from:ident)*) => {
+        impl FromDynArray for $to_type {
+            fn from_dyn_array(arr: DynArray) -> Result<ArrayD<Self>> {
+                match arr {
+                    DynArray::$same(x) => Ok(x),
+                    $(DynArray::$from(x) => Ok(x.mapv(<$to_type>::from)),)*
+                    _ => bail!("cannot convert {} array to {}", arr.data_type(), stringify!($to_type)),
+                }
+            }
+        }
+    };
+}
+
+impl_from_dyn_array!(i8, I8, Bool);
+impl_from_dyn_array!(i16, I16, I8, U8, Bool);
+impl_from_dyn_array!(i32, I32, I8, I16, U8, U16, Bool);
+impl_from_dyn_array!(i64, I64, I8, I16, I32, U8, U16, U32, Bool);
+impl_from_dyn_array!(u8, U8, Bool);
+impl_from_dyn_array!(u16, U16, U8, Bool);
+impl_from_dyn_array!(u32, U32, U8, U16, Bool);
+impl_from_dyn_array!(u64, U64, U8, U16, U32, Bool);
+impl_from_dyn_array!(usize, Usize, U8, U16, Bool);
+impl_from_dyn_array!(f16, F16, I8, U8);
+impl_from_dyn_array!(f32, F32, F16, I8, I16, U8, U16);
+impl_from_dyn_array!(f64, F64, F32, F16, I8, I16, I32, U8, U16, U32);
+impl_from_dyn_array!(bool, Bool);
+
+/// Categorical arrays are decoded into their values.
+impl FromDynArray for String {
+    fn from_dyn_array(arr: DynArray) -> Result<ArrayD<Self>> {
+        match arr {
+            DynArray::String(x) => Ok(x),
+            DynArray::Categorical(x) => Ok(x.codes.mapv(|i| x.categories[i as usize].clone())),
+            _ => bail!("cannot convert {} array to String", arr.data_type()),
+        }
+    }
+}
+
 impl WriteData for DynArray {
     fn data_type(&self) -> DataType {
         match self {
@@ -100,6 +172,7 @@ impl WriteData for DynArray {
             Self::U32(arr) => arr.data_type(),
             Self::U64(arr) => arr.data_type(),
             Self::Usize(arr) => arr.data_type(),
+            Self::F16(arr) => arr.data_type(),
             Self::F32(arr) => arr.data_type(),
             Self::F64(arr) => arr.data_type(),
             Self::Bool(arr) => arr.data_type(),
@@ -122,6 +195,7 @@ impl WriteData for DynArray {
             Self::U32(array) => array.write(location, name),
             Self::U64(array) => array.write(location, name),
             Self::Usize(array) => array.write(location, name),
+            Self::F16(array) => array.write(location, name),
             Self::F32(array) => array.write(location, name),
             Self::F64(array) => array.write(location, name),
             Self::Bool(array) => array.write(location, name),
@@ -129,25 +203,76 @@ impl WriteData for DynArray {
             Self::Categorical(array) => array.write(location, name),
         }
     }
+    fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
+        match self {
+            Self::I8(array) => array.overwrite(container),
+            Self::I16(array) => array.overwrite(container),
+            Self::I32(array) => array.overwrite(container),
+            Self::I64(array) => array.overwrite(container),
+            Self::U8(array) => array.overwrite(container),
+            Self::U16(array) => array.overwrite(container),
+            Self::U32(array) => array.overwrite(container),
+            Self::U64(array) => array.overwrite(container),
+            Self::Usize(array) => array.overwrite(container),
+            Self::F16(array) => array.overwrite(container),
+            Self::F32(array) => array.overwrite(container),
+            Self::F64(array) => array.overwrite(container),
+            Self::Bool(array) => array.overwrite(container),
+            Self::String(array) => array.overwrite(container),
+            Self::Categorical(array) => array.overwrite(container),
+        }
+    }
+}
+
+impl DynArray {
+    /// Write the array with its axes reversed, so that each column of a 2D
+    /// array is contiguous. The dataset is marked as transposed and the
+    /// original array is returned when it is read.
+    pub(crate) fn write_transposed<B: Backend, G: GroupOp<Backend = B>>(
+        &self,
+        location: &G,
+        name: &str,
+    ) -> Result<DataContainer<B>> {
+        let container = match self {
+            Self::I8(array) => array.view().reversed_axes().write(location, name)?,
+            Self::I16(array) => array.view().reversed_axes().write(location, name)?,
+            Self::I32(array) => array.view().reversed_axes().write(location, name)?,
+            Self::I64(array) => array.view().reversed_axes().write(location, name)?,
+            Self::U8(array) => array.view().reversed_axes().write(location, name)?,
+            Self::U16(array) => array.view().reversed_axes().write(location, name)?,
+            Self::U32(array) => array.view().reversed_axes().write(location, name)?,
+            Self::U64(array) => array.view().reversed_axes().write(location, name)?,
+            Self::Usize(array) => array.view().reversed_axes().write(location, name)?,
+            Self::F16(array) => array.view().reversed_axes().write(location, name)?,
+            Self::F32(array) => array.view().reversed_axes().write(location, name)?,
+            Self::F64(array) => array.view().reversed_axes().write(location, name)?,
+            Self::Bool(array) => array.view().reversed_axes().write(location, name)?,
+            Self::String(array) => array.view().reversed_axes().write(location, name)?,
+            Self::Categorical(_) => bail!("categorical arrays cannot be stored transposed"),
+        };
+        container.write_scalar_attr("transposed", true)?;
+        Ok(container)
+    }
 }
 
 impl ReadData for DynArray {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
         match container {
             DataContainer::Dataset(dataset) => match dataset.dtype()? {
-                ScalarType::I8 => Ok(Self::I8(dataset.read_array()?)),
-                ScalarType::I16 => Ok(Self::I16(dataset.read_array()?)),
-                ScalarType::I32 => Ok(Self::I32(dataset.read_array()?)),
-                ScalarType::I64 => Ok(Self::I64(dataset.read_array()?)),
-                ScalarType::U8 => Ok(Self::U8(dataset.read_array()?)),
-                ScalarType::U16 => Ok(Self::U16(dataset.read_array()?)),
-                ScalarType::U32 => Ok(Self::U32(dataset.read_array()?)),
-                ScalarType::U64 => Ok(Self::U64(dataset.read_array()?)),
-                ScalarType::Usize => Ok(Self::Usize(dataset.read_array()?)),
-                ScalarType::F32 => Ok(Self::F32(dataset.read_array()?)),
-                ScalarType::F64 => Ok(Self::F64(dataset.read_array()?)),
-                ScalarType::Bool => Ok(Self::Bool(dataset.read_array()?)),
-                ScalarType::String => Ok(Self::String(dataset.read_array()?)),
+                ScalarType::I8 => Ok(Self::I8(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::I16 => Ok(Self::I16(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::I32 => Ok(Self::I32(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::I64 => Ok(Self::I64(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::U8 => Ok(Self::U8(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::U16 => Ok(Self::U16(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::U32 => Ok(Self::U32(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::U64 => Ok(Self::U64(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::Usize => Ok(Self::Usize(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::F16 => Ok(Self::F16(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::F32 => Ok(Self::F32(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::F64 => Ok(Self::F64(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::Bool => Ok(Self::Bool(read_dataset::<B, _, _>(dataset)?)),
+                ScalarType::String => Ok(Self::String(read_dataset::<B, _, _>(dataset)?)),
             },
             DataContainer::Group(_) =>
                 Ok(Self::Categorical(CategoricalArray::read(container)?)),
@@ -167,6 +292,7 @@ impl HasShape for DynArray {
             DynArray::U32(array) => array.shape().to_vec(),
             DynArray::U64(array) => array.shape().to_vec(),
             DynArray::Usize(array) => array.shape().to_vec(),
+            DynArray::F16(array) => array.shape().to_vec(),
             DynArray::F32(array) => array.shape().to_vec(),
             DynArray::F64(array) => array.shape().to_vec(),
             DynArray::Bool(array) => array.shape().to_vec(),
@@ -189,6 +315,7 @@ impl ArrayOp for DynArray {
             DynArray::U32(array) => array.get(index).map(|x| (*x).into()),
             DynArray::U64(array) => array.get(index).map(|x| (*x).into()),
             DynArray::Usize(array) => array.get(index).map(|x| (*x).into()),
+            DynArray::F16(array) => array.get(index).map(|x| (*x).into()),
             DynArray::F32(array) => array.get(index).map(|x| (*x).into()),
             DynArray::F64(array) => array.get(index).map(|x| (*x).into()),
             DynArray::Bool(array) => array.get(index).map(|x| (*x).into()),
@@ -214,6 +341,7 @@ impl ArrayOp for DynArray {
             DynArray::U32(array) => ArrayOp::select(array, info).into(),
             DynArray::U64(array) => ArrayOp::select(array, info).into(),
             DynArray::Usize(array) => ArrayOp::select(array, info).into(),
+            DynArray::F16(array) => ArrayOp::select(array, info).into(),
             DynArray::F32(array) => ArrayOp::select(array, info).into(),
             DynArray::F64(array) => ArrayOp::select(array, info).into(),
             DynArray::Bool(array) => ArrayOp::select(array, info).into(),
@@ -238,6 +366,7 @@ impl ArrayOp for DynArray {
             DynArray::I16(_) => ArrayD::<i16>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into()),
             DynArray::I32(_) => ArrayD::<i32>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into()),
             DynArray::I64(_) => ArrayD::<i64>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into()),
+            DynArray::F16(_) => ArrayD::<f16>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into()),
             DynArray::F32(_) => ArrayD::<f32>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into()),
             DynArray::F64(_) => ArrayD::<f64>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into()),
             DynArray::Bool(_) => ArrayD::<bool>::vstack(iter.map(|x| x.try_into().unwrap())).map(|x| x.into()),
@@ -250,7 +379,7 @@ impl ArrayOp for DynArray {
 impl WriteArrayData for DynArray {}
 impl ReadArrayData for DynArray {
     fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
-        Ok(container.as_dataset()?.shape().into())
+        dataset_shape::<B>(container.as_dataset()?)
     }
 
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
@@ -260,19 +389,20 @@ impl ReadArrayData for DynArray {
     {
         match container {
             DataContainer::Dataset(dataset) => match dataset.dtype()? {
-                ScalarType::I8 => Ok(Self::I8(dataset.read_array_slice(info)?)),
-                ScalarType::I16 => Ok(Self::I16(dataset.read_array_slice(info)?)),
-                ScalarType::I32 => Ok(Self::I32(dataset.read_array_slice(info)?)),
-                ScalarType::I64 => Ok(Self::I64(dataset.read_array_slice(info)?)),
-                ScalarType::U8 => Ok(Self::U8(dataset.read_array_slice(info)?)),
-                ScalarType::U16 => Ok(Self::U16(dataset.read_array_slice(info)?)),
-                ScalarType::U32 => Ok(Self::U32(dataset.read_array_slice(info)?)),
-                ScalarType::U64 => Ok(Self::U64(dataset.read_array_slice(info)?)),
-                ScalarType::Usize => Ok(Self::Usize(dataset.read_array_slice(info)?)),
-                ScalarType::F32 => Ok(Self::F32(dataset.read_array_slice(info)?)),
-                ScalarType::F64 => Ok(Self::F64(dataset.read_array_slice(info)?)),
-                ScalarType::Bool => Ok(Self::Bool(dataset.read_array_slice(info)?)),
-                ScalarType::String => Ok(Self::String(dataset.read_array_slice(info)?)),
+                ScalarType::I8 => Ok(Self::I8(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::I16 => Ok(Self::I16(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::I32 => Ok(Self::I32(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::I64 => Ok(Self::I64(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::U8 => Ok(Self::U8(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::U16 => Ok(Self::U16(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::U32 => Ok(Self::U32(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::U64 => Ok(Self::U64(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::Usize => Ok(Self::Usize(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::F16 => Ok(Self::F16(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::F32 => Ok(Self::F32(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::F64 => Ok(Self::F64(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::Bool => Ok(Self::Bool(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
+                ScalarType::String => Ok(Self::String(read_dataset_slice::<B, _, _, _>(dataset, info)?)),
             },
             DataContainer::Group(_) =>
                 Ok(Self::Categorical(CategoricalArray::read_select(container, info)?)),
@@ -301,6 +431,23 @@ impl<'a, T: BackendData, D: RemoveAxis> WriteData for ArrayView<'a, T, D> {
         container.write_str_attr("encoding-version", "0.2.0")?;
         Ok(container)
     }
+
+    /// Write into the existing dataset when it has the same shape and data type,
+    /// so that repeated updates do not grow the backing file. Strings are
+    /// variable-length and are always rewritten.
+    fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
+        if let DataContainer::Dataset(dataset) = &container {
+            if T::DTYPE != ScalarType::String
+                && container.encoding_type().ok() == Some(self.data_type())
+                && dataset.shape().as_ref() == self.shape()
+                && !is_transposed::<B>(dataset)?
+            {
+                dataset.write_array(self.view())?;
+                return Ok(container);
+            }
+        }
+        delete_and_write(self, container)
+    }
 }
 
 impl<T: BackendData, D: RemoveAxis> WriteData for Array<T, D> {
@@ -314,6 +461,9 @@ impl<T: BackendData, D: RemoveAxis> WriteData for Array<T, D> {
     ) -> Result<DataContainer<B>> {
         self.view().write(location, name)
     }
+    fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
+        self.view().overwrite(container)
+    }
 }
 
 impl<T: BackendData, D: RemoveAxis> HasShape for Array<T, D> {
@@ -338,7 +488,12 @@ impl<T: BackendData, D: RemoveAxis> ArrayOp for Array<T, D> {
         S: AsRef<SelectInfoElem>,
     {
         let arr = self.view().into_dyn();
-        let slices = info.as_ref().into_iter().map(|x| match x.as_ref() {
+        let full = SelectInfoElem::full();
+        // Axes not covered by the selection are fully selected.
+        let info: SmallVec<[&SelectInfoElem; 3]> = (0..arr.ndim())
+            .map(|i| info.get(i).map_or(&full, |x| x.as_ref()))
+            .collect();
+        let slices = info.iter().map(|x| match x {
             SelectInfoElem::Slice(slice) => Some(SliceInfoElem::from(slice.clone())),
             _ => None,
         }).collect::<Option<Vec<_>>>();
@@ -346,8 +501,8 @@ impl<T: BackendData, D: RemoveAxis> ArrayOp for Array<T, D> {
             arr.slice(slices.as_slice()).into_owned()
         } else {
             let shape = self.shape();
-            let select: Vec<_> = info.as_ref().into_iter().zip(shape)
-                .map(|(x, n)| BoundedSelectInfoElem::new(x.as_ref(), *n)).collect();
+            let select: Vec<_> = info.iter().zip(shape)
+                .map(|(x, n)| BoundedSelectInfoElem::new(x, *n)).collect();
             let new_shape = select.iter().map(|x| x.len()).collect::<Vec<_>>();
             ArrayD::from_shape_fn(new_shape, |idx| {
                 let new_idx: Vec<_> = (0..idx.ndim()).into_iter().map(|i| select[i].index(idx[i])).collect();
@@ -366,13 +521,13 @@ impl<T: BackendData, D: RemoveAxis> ArrayOp for Array<T, D> {
 
 impl<T: BackendData, D: RemoveAxis> ReadData for Array<T, D> {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
-        Ok(container.as_dataset()?.read_array::<T, D>()?)
+        read_dataset::<B, T, D>(container.as_dataset()?)
     }
 }
 
 impl<T: BackendData, D: RemoveAxis> ReadArrayData for Array<T, D> {
     fn get_shape<B: Backend>(container: &DataContainer<B>) -> Result<Shape> {
-        Ok(container.as_dataset()?.shape().into())
+        dataset_shape::<B>(container.as_dataset()?)
     }
 
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
@@ -380,7 +535,50 @@ impl<T: BackendData, D: RemoveAxis> ReadArrayData for Array<T, D> {
             B: Backend,
             S: AsRef<SelectInfoElem>,
     {
-        container.as_dataset()?.read_array_slice(info)
+        read_dataset_slice::<B, T, S, D>(container.as_dataset()?, info)
+    }
+}
+
+/// Whether the dataset stores its array with the axes reversed, see
+/// [`DynArray::write_transposed`].
+pub(crate) fn is_transposed<B: Backend>(dataset: &B::Dataset) -> Result<bool> {
+    match dataset.attr_dtype("transposed")? {
+        Some(_) => dataset.read_scalar_attr::<bool>("transposed"),
+        None => Ok(false),
+    }
+}
+
+fn dataset_shape<B: Backend>(dataset: &B::Dataset) -> Result<Shape> {
+    let mut shape = dataset.shape();
+    if is_transposed::<B>(dataset)? {
+        shape = shape.as_ref().iter().rev().copied().collect::<Vec<_>>().into();
+    }
+    Ok(shape)
+}
+
+fn read_dataset<B: Backend, T: BackendData, D: RemoveAxis>(dataset: &B::Dataset) -> Result<Array<T, D>> {
+    let arr = dataset.read_array::<T, D>()?;
+    Ok(if is_transposed::<B>(dataset)? { arr.reversed_axes() } else { arr })
+}
+
+/// Read a selection of the array. For transposed datasets, the selection is
+/// reversed as well, so that selecting a column of the array reads a single
+/// row of the dataset.
+fn read_dataset_slice<B, T, S, D>(dataset: &B::Dataset, info: &[S]) -> Result<Array<T, D>>
+where
+    B: Backend,
+    T: BackendData,
+    S: AsRef<SelectInfoElem>,
+    D: RemoveAxis,
+{
+    if is_transposed::<B>(dataset)? {
+        let full = SelectInfoElem::full();
+        let info: SmallVec<[&SelectInfoElem; 3]> = (0..dataset.shape().ndim()).rev()
+            .map(|i| info.get(i).map_or(&full, |x| x.as_ref()))
+            .collect();
+        Ok(dataset.read_array_slice::<T, _, D>(info.as_slice())?.reversed_axes())
+    } else {
+        dataset.read_array_slice(info)
     }
 }
 
@@ -440,7 +638,18 @@ impl WriteData for CategoricalArray {
         group.write_str_attr("encoding-version", "0.2.0")?;
         group.write_scalar_attr("ordered", false)?;
 
-        group.create_array_data("codes", &self.codes, Default::default())?;
+        // Store the codes using the smallest signed integer type that can hold
+        // all categories, as anndata does.
+        let n = self.categories.len();
+        if n <= i8::MAX as usize + 1 {
+            group.create_array_data("codes", &self.codes.mapv(|x| x as i8), Default::default())?;
+        } else if n <= i16::MAX as usize + 1 {
+            group.create_array_data("codes", &self.codes.mapv(|x| x as i16), Default::default())?;
+        } else if n <= i32::MAX as usize + 1 {
+            group.create_array_data("codes", &self.codes.mapv(|x| x as i32), Default::default())?;
+        } else {
+            group.create_array_data("codes", &self.codes.mapv(i64::from), Default::default())?;
+        }
         group.create_array_data("categories", &self.categories, Default::default())?;
 
         Ok(DataContainer::Group(group))
@@ -1,5 +1,5 @@
 use ndarray::{Array1, Array2, Slice, SliceInfo, SliceInfoElem, IxDyn};
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use itertools::Itertools;
 use std::ops::{RangeFull, Range, Index, IndexMut, RangeFrom, RangeTo};
 use smallvec::{SmallVec, smallvec};
@@ -76,8 +76,10 @@ impl FromIterator<usize> for Shape {
 }
 
 /// A multi-dimensional selection used for reading and writing to a Container.
+/// The second field records the axes selected by boolean masks in [`s!`],
+/// with the lengths of the masks.
 #[derive(Debug, PartialEq, Eq)]
-pub struct SelectInfo(pub Vec<SelectInfoElem>);
+pub struct SelectInfo(pub Vec<SelectInfoElem>, Vec<(usize, usize)>);
 
 impl AsRef<[SelectInfoElem]> for SelectInfo {
     fn as_ref(&self) -> &[SelectInfoElem] {
@@ -87,19 +89,19 @@ impl AsRef<[SelectInfoElem]> for SelectInfo {
 
 impl FromIterator<SelectInfoElem> for SelectInfo {
     fn from_iter<T: IntoIterator<Item = SelectInfoElem>>(iter: T) -> Self {
-        Self(iter.into_iter().collect())
+        Self(iter.into_iter().collect(), Vec::new())
     }
 }
 
 impl FromIterator<Slice> for SelectInfo {
     fn from_iter<T: IntoIterator<Item = Slice>>(iter: T) -> Self {
-        Self(iter.into_iter().map(SelectInfoElem::Slice).collect())
+        Self(iter.into_iter().map(SelectInfoElem::Slice).collect(), Vec::new())
     }
 }
 
 impl<'a> FromIterator<&'a Slice> for SelectInfo {
     fn from_iter<T: IntoIterator<Item = &'a Slice>>(iter: T) -> Self {
-        Self(iter.into_iter().map(|x| SelectInfoElem::Slice(x.clone())).collect())
+        Self(iter.into_iter().map(|x| SelectInfoElem::Slice(x.clone())).collect(), Vec::new())
     }
 }
 
@@ -119,7 +121,37 @@ impl TryInto<SliceInfo<Vec<SliceInfoElem>, IxDyn, IxDyn>> for SelectInfo {
 
 impl SelectInfo {
     pub fn all(n: usize) -> Self {
-        Self(vec![SelectInfoElem::Slice(SLICE_FULL); n])
+        Self(vec![SelectInfoElem::Slice(SLICE_FULL); n], Vec::new())
+    }
+
+    /// Build a selection from the arguments of [`s!`].
+    pub fn from_args(args: Vec<AxisArg>) -> Self {
+        let masks = args.iter().enumerate().filter_map(|(i, x)| x.1.map(|n| (i, n))).collect();
+        Self(args.into_iter().map(|x| x.0).collect(), masks)
+    }
+
+    /// Check that the selection has one element per axis of `shape`, that it
+    /// is within the bounds of each axis, and that the boolean masks have the
+    /// lengths of their axes.
+    pub fn check_shape(&self, shape: &Shape) -> Result<()> {
+        ensure!(
+            self.0.len() == shape.ndim(),
+            "the selection has {} axes, but the shape {} has {}",
+            self.0.len(),
+            shape,
+            shape.ndim(),
+        );
+        self.1.iter().try_for_each(|(axis, n)| {
+            ensure!(
+                shape.as_ref().get(*axis) == Some(n),
+                "the boolean mask of axis {} has length {}, but the shape is {}",
+                axis,
+                n,
+                shape,
+            );
+            Ok(())
+        })?;
+        self.0.iter().zip(shape.as_ref()).try_for_each(|(x, n)| x.bound_check(*n))
     }
 }
 
@@ -240,6 +272,74 @@ impl From<RangeTo<i32>> for SelectInfoElem {
     }
 }
 
+/// A value that selects an axis in [`s!`]: anything that converts into a
+/// [`SelectInfoElem`], or a boolean mask selecting the positions that are
+/// `true`. A mask is kept as an [`AxisArg`] rather than converted into a
+/// [`SelectInfoElem`], so that its length can be checked against the axis.
+pub trait SelectArg {
+    fn into_select_arg(self) -> AxisArg;
+}
+
+/// The selection of an axis built by [`s!`], with the length of the boolean
+/// mask it was built from, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AxisArg(SelectInfoElem, Option<usize>);
+
+impl AxisArg {
+    /// See [`SelectInfoElem::with_step`].
+    pub fn with_step(self, step: isize) -> Self {
+        Self(self.0.with_step(step), self.1)
+    }
+}
+
+macro_rules! impl_select_arg {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl SelectArg for $ty {
+                fn into_select_arg(self) -> AxisArg {
+                    AxisArg(self.into(), None)
+                }
+            }
+        )*
+    };
+}
+
+impl_select_arg!(
+    SelectInfoElem, Slice, usize, &[usize], Vec<usize>, &Vec<usize>, Array1<usize>, &Array1<usize>,
+    Range<usize>, Range<isize>, Range<i32>, RangeFull, RangeFrom<usize>, RangeFrom<isize>,
+    RangeFrom<i32>, RangeTo<usize>, RangeTo<isize>, RangeTo<i32>,
+);
+
+impl SelectArg for &[bool] {
+    fn into_select_arg(self) -> AxisArg {
+        AxisArg(self.iter().positions(|x| *x).collect(), Some(self.len()))
+    }
+}
+
+impl SelectArg for Vec<bool> {
+    fn into_select_arg(self) -> AxisArg {
+        self.as_slice().into_select_arg()
+    }
+}
+
+impl SelectArg for &Vec<bool> {
+    fn into_select_arg(self) -> AxisArg {
+        self.as_slice().into_select_arg()
+    }
+}
+
+impl SelectArg for &Array1<bool> {
+    fn into_select_arg(self) -> AxisArg {
+        AxisArg(self.iter().positions(|x| *x).collect(), Some(self.len()))
+    }
+}
+
+impl SelectArg for Array1<bool> {
+    fn into_select_arg(self) -> AxisArg {
+        (&self).into_select_arg()
+    }
+}
+
 impl AsRef<SelectInfoElem> for SelectInfoElem {
     fn as_ref(&self) -> &SelectInfoElem {
         self
@@ -258,16 +358,99 @@ impl SelectInfoElem {
                 }
             ),
             SelectInfoElem::Slice(slice) => {
-                if let Some(end) = slice.end {
-                    if end > bound as isize {
-                        bail!("slice end out of bounds: {} >= {}", end, bound)
-                    } else {
-                        Ok(())
-                    }
-                } else {
-                    Ok(())
+                let n = bound as isize;
+                if slice.start < -n {
+                    bail!("slice start out of bounds: {} < -{}", slice.start, bound)
+                }
+                match slice.end {
+                    Some(end) if end > n => bail!("slice end out of bounds: {} >= {}", end, bound),
+                    Some(end) if end < -n => bail!("slice end out of bounds: {} < -{}", end, bound),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// The number of elements selected on an axis of length `axis_len`. The
+    /// selection must be within bounds, see [`Self::bound_check`].
+    pub fn output_len(&self, axis_len: usize) -> usize {
+        BoundedSelectInfoElem::new(self, axis_len).len()
+    }
+
+    /// Whether selecting on an axis of length `axis_len` returns the axis
+    /// unchanged. Unlike [`Self::is_full`], this recognizes any selection of
+    /// all elements in order, e.g., `0..axis_len` or `vec![0, 1, 2]`.
+    pub fn is_identity(&self, axis_len: usize) -> bool {
+        self.bound_check(axis_len).is_ok() && BoundedSelectInfoElem::new(self, axis_len).is_full(axis_len)
+    }
+
+    /// The elements of an axis of length `axis_len` that are not selected, in
+    /// increasing order. The result is a slice if the selection is a range
+    /// at either end of the axis, and a list of indices otherwise.
+    pub fn invert(&self, axis_len: usize) -> Result<SelectInfoElem> {
+        self.bound_check(axis_len)?;
+        let select = BoundedSelectInfoElem::new(self, axis_len);
+        if select.len() == 0 {
+            return Ok(SelectInfoElem::full());
+        }
+        if let BoundedSelectInfoElem::Slice(slice) = &select {
+            if slice.step.unsigned_abs() == 1 || slice.len() == 1 {
+                let lo = select.iter().min().unwrap();
+                let hi = select.iter().max().unwrap() + 1;
+                if lo == 0 {
+                    return Ok(Slice::new(hi as isize, Some(axis_len as isize), 1).into());
+                } else if hi == axis_len {
+                    return Ok(Slice::new(0, Some(lo as isize), 1).into());
+                }
+            }
+        }
+        let mut mask = vec![true; axis_len];
+        select.iter().for_each(|i| mask[i] = false);
+        Ok(mask.into_iter().positions(|x| x).collect())
+    }
+
+    /// Take every `step`-th element of the selection. A negative step starts
+    /// from the back, as in [`ndarray::Slice`].
+    ///
+    /// # Panics
+    ///
+    /// If `step` is zero.
+    pub fn with_step(self, step: isize) -> Self {
+        assert!(step != 0, "step must not be zero");
+        match self {
+            SelectInfoElem::Slice(slice) => SelectInfoElem::Slice(slice.step_by(step)),
+            SelectInfoElem::Index(mut index) => {
+                if step < 0 {
+                    index.reverse();
                 }
+                SelectInfoElem::Index(index.into_iter().step_by(step.unsigned_abs()).collect())
+            }
+        }
+    }
+
+    /// Select `other` from the elements selected by `self` on an axis of
+    /// length `bound`, and return the equivalent selection of the whole axis.
+    /// `other` is bound checked against the length of `self`.
+    ///
+    /// The kind of the selection is kept where possible: composing two slices
+    /// gives a slice, and selecting with a full slice returns the other side
+    /// unchanged. Otherwise the result is a list of indices.
+    pub fn compose(&self, bound: usize, other: &SelectInfoElem) -> Result<SelectInfoElem> {
+        let outer = BoundedSelectInfoElem::new(self, bound);
+        other.bound_check(outer.len())?;
+        if outer.is_full(bound) {
+            return Ok(other.clone());
+        }
+        let inner = BoundedSelectInfoElem::new(other, outer.len());
+        if inner.is_full(outer.len()) {
+            return Ok(self.clone());
+        }
+        match (&outer, &inner) {
+            (BoundedSelectInfoElem::Slice(a), BoundedSelectInfoElem::Slice(b)) => {
+                Ok(SelectInfoElem::Slice(a.compose(b)))
             }
+            (BoundedSelectInfoElem::Index(idx), _) => Ok(inner.iter().map(|i| idx[i]).collect()),
+            _ => Ok(inner.iter().map(|i| outer.index(i)).collect()),
         }
     }
 
@@ -330,13 +513,18 @@ impl<'a> TryInto<SliceInfo<Vec<SliceInfoElem>, IxDyn, IxDyn>> for BoundedSelectI
 
 
 impl<'a> BoundedSelectInfo<'a> {
+    /// Axes not covered by `select` are fully selected.
     pub fn new<S, E>(select: &'a S, shape: &Shape) -> Self
     where
-        S: AsRef<[E]>,
+        S: AsRef<[E]> + ?Sized,
         E: AsRef<SelectInfoElem> + 'a,
     {
-        let res: Vec<_> = select.as_ref().iter().zip(shape.as_ref()).map(|(sel, dim)|
-            BoundedSelectInfoElem::new(sel.as_ref(), *dim)
+        let full = Slice { start: 0, end: None, step: 1 };
+        let res: Vec<_> = shape.as_ref().iter().enumerate().map(|(i, dim)|
+            match select.as_ref().get(i) {
+                Some(sel) => BoundedSelectInfoElem::new(sel.as_ref(), *dim),
+                None => BoundedSelectInfoElem::Slice(BoundedSlice::new(&full, *dim)),
+            }
         ).collect();
         Self {
             input_shape: shape.clone(),
@@ -344,6 +532,32 @@ impl<'a> BoundedSelectInfo<'a> {
         }
     }
 
+    /// Like [`Self::new`], but check that `select` has at most one element
+    /// per axis of `shape`, and that it is within the bounds of each axis.
+    pub fn try_new<S, E>(select: &'a S, shape: &Shape) -> Result<Self>
+    where
+        S: AsRef<[E]> + ?Sized,
+        E: AsRef<SelectInfoElem> + 'a,
+    {
+        let n = select.as_ref().len();
+        ensure!(
+            n <= shape.ndim(),
+            "the selection has {} axes, but the shape {} has {}",
+            n,
+            shape,
+            shape.ndim(),
+        );
+        select.as_ref().iter().zip(shape.as_ref()).enumerate().try_for_each(|(i, (x, dim))|
+            x.as_ref().bound_check(*dim).map_err(|e| anyhow!("axis {}: {}", i, e))
+        )?;
+        Ok(Self::new(select, shape))
+    }
+
+    /// Whether the selection returns the input unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.select.iter().zip(self.input_shape.as_ref()).all(|(x, dim)| x.is_full(*dim))
+    }
+
     pub fn in_shape(&self) -> Shape {
         self.input_shape.clone()
     }
@@ -446,7 +660,7 @@ impl<'a> BoundedSelectInfoElem<'a> {
 
     pub fn is_full(&self, bound: usize) -> bool {
         match self {
-            Self::Slice(slice) => slice.start == 0 && slice.end == bound && slice.step == 1,
+            Self::Slice(slice) => slice.len() == bound && slice.end <= bound && (bound <= 1 || slice.step == 1),
             Self::Index(indices) => indices.len() == bound && indices.iter().enumerate().all(|(i, &x)| x == i),
         }
     }
@@ -457,7 +671,7 @@ impl<'a> BoundedSelectInfoElem<'a> {
             Self::Slice(slice) => if slice.step > 0 {
                 (slice.start..slice.end).step_by(slice.step as usize).collect()
             } else {
-                (slice.start..slice.end).step_by(slice.step.abs() as usize).rev().collect()
+                (slice.start..slice.end).rev().step_by(slice.step.unsigned_abs()).collect()
             },
         }
     }
@@ -468,7 +682,7 @@ impl<'a> BoundedSelectInfoElem<'a> {
             Self::Slice(slice) => if slice.step > 0 {
                 Box::new((slice.start..slice.end).step_by(slice.step as usize))
             } else {
-                Box::new((slice.start..slice.end).step_by(slice.step.abs() as usize).rev())
+                Box::new((slice.start..slice.end).rev().step_by(slice.step.unsigned_abs()))
             },
         }
     }
@@ -511,7 +725,7 @@ impl BoundedSlice {
     }
 
     pub(crate) fn len(&self) -> usize {
-        (self.end - self.start).checked_div(self.step.unsigned_abs()).unwrap()
+        num::integer::div_ceil(self.end.saturating_sub(self.start), self.step.unsigned_abs())
     }
 
     pub(crate) fn index(&self, i: usize) -> usize {
@@ -521,6 +735,23 @@ impl BoundedSlice {
             self.end.checked_sub(1 + i * (-self.step) as usize).unwrap()
         }
     }
+
+    /// The slice selecting `other` from the elements selected by `self`.
+    fn compose(&self, other: &BoundedSlice) -> Slice {
+        let n = other.len();
+        if n == 0 {
+            return Slice::new(0, Some(0), 1);
+        }
+        let first = self.index(other.index(0)) as isize;
+        let last = self.index(other.index(n - 1)) as isize;
+        let step = self.step * other.step;
+        if step > 0 {
+            Slice::new(first, Some(last + 1), step)
+        } else {
+            // A negative step walks from the back of `start..end`.
+            Slice::new(last, Some(first + 1), step)
+        }
+    }
 }
 
 pub const SLICE_FULL: Slice = Slice {
@@ -560,10 +791,13 @@ fn _unique_indices_sorted(indices: &[usize], upper_bound: usize) -> (Vec<usize>,
 
 /// Slice argument constructor.
 ///
-/// `s![]` takes a list of ranges/slices/indices/new-axes, separated by comma,
-/// with optional step sizes that are separated from the range by a semicolon.
+/// `s![]` takes a list of ranges, indices and boolean masks, one per axis and
+/// separated by commas, and builds a [`SelectInfo`]. `..` selects a whole axis.
+/// A range or a list of indices can be followed by a step size, separated by
+/// a semicolon. See [`SelectInfoElem::with_step`].
 ///
-/// # Example
+/// The number of axes and the bounds are only known at runtime, see
+/// [`SelectInfo::check_shape`].
 ///
 /// # Negative *step*
 ///
@@ -584,23 +818,21 @@ fn _unique_indices_sorted(indices: &[usize], upper_bound: usize) -> (Vec<usize>,
 ///
 /// ```
 /// # use anndata::s;
+/// # use anndata::data::SelectInfoElem;
 /// #
 /// # fn main() {
-/// println!("{:?}", s![1..3 , ..]);
-/// println!("{:?}", s![vec![1, 10, 3], ..]);
+/// assert_eq!(s![1..3, ..].as_ref(), &[SelectInfoElem::from(1..3), SelectInfoElem::full()]);
+/// assert_eq!(s![vec![1, 10, 3], 0..10;2].as_ref()[0], SelectInfoElem::from(vec![1, 10, 3]));
+/// assert_eq!(s![vec![true, false, true]].as_ref()[0], SelectInfoElem::from(vec![0, 2]));
+/// assert_eq!(s![0..5;-2].as_ref()[0], SelectInfoElem::from(ndarray::Slice::new(0, Some(5), -2)));
 /// # }
 /// ```
 #[macro_export]
-macro_rules! s{
-    ( $( $x:expr ),* ) => {
-        {
-            let mut temp_vec = Vec::new();
-            $(
-                temp_vec.push($crate::data::SelectInfoElem::from($x));
-            )*
-            $crate::data::SelectInfo(temp_vec)
-        }
-
+macro_rules! s {
+    ( $( $x:expr $( ; $step:expr )? ),* $(,)? ) => {
+        $crate::data::SelectInfo::from_args(vec![
+            $( $crate::data::SelectArg::into_select_arg($x) $( .with_step($step) )? ),*
+        ])
     };
 }
 
@@ -621,6 +853,124 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_select_macro() {
+        let mask = vec![true, false, false, true];
+        let select = crate::s![0..10;3, &mask, vec![5, 4, 3, 2];-2];
+        assert_eq!(select.as_ref()[0], SelectInfoElem::from(Slice::new(0, Some(10), 3)));
+        assert_eq!(select.as_ref()[1], SelectInfoElem::from(vec![0, 3]));
+        assert_eq!(select.as_ref()[2], SelectInfoElem::from(vec![2, 4]));
+
+        assert!(select.check_shape(&vec![10, 4, 6].into()).is_ok());
+        assert!(select.check_shape(&vec![10, 4].into()).is_err());
+        assert!(select.check_shape(&vec![10, 3, 6].into()).is_err());
+        assert!(select.check_shape(&vec![10, 5, 6].into()).is_err());
+    }
+
+    #[test]
+    fn test_bounded_slice() {
+        let slice = |start, end, step| BoundedSlice::new(&Slice { start, end, step }, 10);
+        assert_eq!(slice(0, None, 3).len(), 4);
+        assert_eq!(slice(0, None, -3).len(), 4);
+        assert_eq!(slice(5, Some(5), 1).len(), 0);
+        assert_eq!(slice(1, Some(8), 2).len(), 4);
+        let s = slice(0, Some(9), -3);
+        assert_eq!((0..s.len()).map(|i| s.index(i)).collect::<Vec<_>>(), vec![8, 5, 2]);
+    }
+
+    /// A slice or a list of indices on an axis of length `n`.
+    fn select_strategy(n: usize) -> BoxedStrategy<SelectInfoElem> {
+        let slice = (0..=n, 0..=n, 1..4isize, any::<bool>(), any::<bool>()).prop_map(
+            |(a, b, step, reverse, open)| {
+                let end = if open { None } else { Some(a.max(b) as isize) };
+                let step = if reverse { -step } else { step };
+                SelectInfoElem::Slice(Slice::new(a.min(b) as isize, end, step))
+            }
+        );
+        if n == 0 {
+            slice.boxed()
+        } else {
+            let index = prop::collection::vec(0..n, 0..20).prop_map(SelectInfoElem::Index);
+            prop_oneof![slice, index].boxed()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_compose_materialized(
+            (n, outer, inner) in (0..40usize)
+                .prop_flat_map(|n| (Just(n), select_strategy(n)))
+                .prop_flat_map(|(n, outer)| {
+                    let len = BoundedSelectInfoElem::new(&outer, n).len();
+                    (Just(n), Just(outer), select_strategy(len))
+                })
+        ) {
+            let selected = BoundedSelectInfoElem::new(&outer, n).to_vec();
+            let expected: Vec<_> = BoundedSelectInfoElem::new(&inner, selected.len())
+                .iter().map(|i| selected[i]).collect();
+            let composed = outer.compose(n, &inner).unwrap();
+            prop_assert_eq!(BoundedSelectInfoElem::new(&composed, n).to_vec(), expected);
+            if outer.is_slice() && inner.is_slice() {
+                prop_assert!(composed.is_slice());
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_select_materialized(
+            (n, select) in (0..40usize).prop_flat_map(|n| (Just(n), select_strategy(n)))
+        ) {
+            let selected = BoundedSelectInfoElem::new(&select, n).to_vec();
+            prop_assert_eq!(select.output_len(n), selected.len());
+            prop_assert_eq!(select.is_identity(n), selected == (0..n).collect::<Vec<_>>());
+
+            let expected: Vec<_> = (0..n).filter(|i| !selected.contains(i)).collect();
+            let inverted = select.invert(n).unwrap();
+            prop_assert_eq!(BoundedSelectInfoElem::new(&inverted, n).to_vec(), expected);
+
+            let shape = Shape::from((n, 3));
+            let info = [select.clone()];
+            let bounded = BoundedSelectInfo::try_new(&info, &shape).unwrap();
+            prop_assert_eq!(bounded.out_shape().as_ref().to_vec(), vec![selected.len(), 3]);
+            prop_assert_eq!(bounded.is_identity(), select.is_identity(n));
+        }
+    }
+
+    #[test]
+    fn test_select_utils() {
+        assert_eq!(SelectInfoElem::from(2..8).invert(10).unwrap(), SelectInfoElem::from(vec![0, 1, 8, 9]));
+        assert_eq!(SelectInfoElem::from(0..8).invert(10).unwrap(), SelectInfoElem::from(8..10));
+        assert_eq!(SelectInfoElem::from(vec![9]).invert(10).unwrap(), SelectInfoElem::from(vec![0, 1, 2, 3, 4, 5, 6, 7, 8]));
+        assert!(SelectInfoElem::from(vec![10]).invert(10).is_err());
+
+        assert!(SelectInfoElem::from(vec![0, 1, 2]).is_identity(3));
+        assert!(SelectInfoElem::from(0..3).is_identity(3));
+        assert!(!SelectInfoElem::from(0..3).is_identity(4));
+        assert!(!SelectInfoElem::from(0..5).is_identity(4));
+
+        let shape = Shape::from((10, 4));
+        let select = crate::s![vec![1, 3], ..];
+        assert_eq!(BoundedSelectInfo::try_new(&select, &shape).unwrap().out_shape().as_ref(), &[2, 4]);
+        assert!(BoundedSelectInfo::try_new(&crate::s![vec![1, 10], ..], &shape).is_err());
+        assert!(BoundedSelectInfo::try_new(&crate::s![.., -5..], &shape).is_err());
+        assert!(BoundedSelectInfo::try_new(&crate::s![.., .., ..], &shape).is_err());
+    }
+
+    #[test]
+    fn test_compose() {
+        let outer = SelectInfoElem::from(2..8);
+        let inner = SelectInfoElem::from(vec![5, 0, 3]);
+        assert_eq!(outer.compose(10, &inner).unwrap(), SelectInfoElem::from(vec![7, 2, 5]));
+        assert_eq!(inner.compose(10, &SelectInfoElem::from(1..3)).unwrap(), SelectInfoElem::from(vec![0, 3]));
+        assert_eq!(outer.compose(10, &SelectInfoElem::full()).unwrap(), outer);
+        assert_eq!(
+            SelectInfoElem::from(10..20).compose(30, &SelectInfoElem::from(2..8)).unwrap(),
+            SelectInfoElem::from(12..18),
+        );
+        assert!(outer.compose(10, &SelectInfoElem::from(vec![6])).is_err());
+    }
+
     #[test]
     fn test_basic() {
         assert_eq!(
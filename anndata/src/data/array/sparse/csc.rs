@@ -8,6 +8,7 @@ use crate::data::{
 };
 
 use anyhow::{bail, Context, Result};
+use half::f16;
 use nalgebra_sparse::csc::CscMatrix;
 use nalgebra_sparse::pattern::SparsityPattern;
 use ndarray::Ix1;
@@ -26,6 +27,7 @@ pub enum DynCscMatrix {
     U32(CscMatrix<u32>),
     U64(CscMatrix<u64>),
     Usize(CscMatrix<usize>),
+    F16(CscMatrix<f16>),
     F32(CscMatrix<f32>),
     F64(CscMatrix<f64>),
     Bool(CscMatrix<bool>),
@@ -70,6 +72,7 @@ impl TryFrom<DynCscMatrix> for CscMatrix<u32> {
             DynCscMatrix::U16(data) => Ok(cast_csc(data)?),
             DynCscMatrix::U64(data) => Ok(cast_csc(data)?),
             DynCscMatrix::Usize(data) => Ok(cast_csc(data)?),
+            DynCscMatrix::F16(_) => bail!("Cannot convert f16 to u32"),
             DynCscMatrix::F32(_) => bail!("Cannot convert f32 to u32"),
             DynCscMatrix::F64(_) => bail!("Cannot convert f64 to u32"),
             DynCscMatrix::Bool(_) => bail!("Cannot convert bool to f64"),
@@ -99,6 +102,7 @@ impl TryFrom<DynCscMatrix> for CscMatrix<f64> {
             DynCscMatrix::U32(data) => Ok(cast_csc(data)?),
             DynCscMatrix::U64(_) => bail!("Cannot convert u64 to f64"),
             DynCscMatrix::Usize(_) => bail!("Cannot convert usize to f64"),
+            DynCscMatrix::F16(data) => Ok(cast_csc(data)?),
             DynCscMatrix::F32(data) => Ok(cast_csc(data)?),
             DynCscMatrix::Bool(_) => bail!("Cannot convert bool to f64"),
             DynCscMatrix::String(_) => bail!("Cannot convert string to f64"),
@@ -114,6 +118,7 @@ impl_into_dyn_csc!(u8, U8);
 impl_into_dyn_csc!(u16, U16);
 impl_into_dyn_csc!(u64, U64);
 impl_into_dyn_csc!(usize, Usize);
+impl_into_dyn_csc!(f16, F16);
 impl_into_dyn_csc!(f32, F32);
 impl_into_dyn_csc!(bool, Bool);
 impl_into_dyn_csc!(String, String);
@@ -130,6 +135,7 @@ macro_rules! impl_dyn_csc_matrix {
             DynCscMatrix::U32(data) => $fun!(data),
             DynCscMatrix::U64(data) => $fun!(data),
             DynCscMatrix::Usize(data) => $fun!(data),
+            DynCscMatrix::F16(data) => $fun!(data),
             DynCscMatrix::F32(data) => $fun!(data),
             DynCscMatrix::F64(data) => $fun!(data),
             DynCscMatrix::Bool(data) => $fun!(data),
@@ -150,6 +156,7 @@ impl WriteData for DynCscMatrix {
             DynCscMatrix::U32(csc) => csc.data_type(),
             DynCscMatrix::U64(csc) => csc.data_type(),
             DynCscMatrix::Usize(csc) => csc.data_type(),
+            DynCscMatrix::F16(csc) => csc.data_type(),
             DynCscMatrix::F32(csc) => csc.data_type(),
             DynCscMatrix::F64(csc) => csc.data_type(),
             DynCscMatrix::Bool(csc) => csc.data_type(),
@@ -183,6 +190,7 @@ impl ReadData for DynCscMatrix {
                 ScalarType::U32 => CscMatrix::<u32>::read(container).map(DynCscMatrix::U32),
                 ScalarType::U64 => CscMatrix::<u64>::read(container).map(DynCscMatrix::U64),
                 ScalarType::Usize => CscMatrix::<usize>::read(container).map(DynCscMatrix::Usize),
+                ScalarType::F16 => CscMatrix::<f16>::read(container).map(DynCscMatrix::F16),
                 ScalarType::F32 => CscMatrix::<f32>::read(container).map(DynCscMatrix::F32),
                 ScalarType::F64 => CscMatrix::<f64>::read(container).map(DynCscMatrix::F64),
                 ScalarType::Bool => CscMatrix::<bool>::read(container).map(DynCscMatrix::Bool),
@@ -240,6 +248,7 @@ impl ArrayOp for DynCscMatrix {
             DynCscMatrix::I16(_) => Ok(DynCscMatrix::I16(CscMatrix::<i16>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCscMatrix::I32(_) => Ok(DynCscMatrix::I32(CscMatrix::<i32>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCscMatrix::I64(_) => Ok(DynCscMatrix::I64(CscMatrix::<i64>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
+            DynCscMatrix::F16(_) => Ok(DynCscMatrix::F16(CscMatrix::<f16>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCscMatrix::F32(_) => Ok(DynCscMatrix::F32(CscMatrix::<f32>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCscMatrix::F64(_) => Ok(DynCscMatrix::F64(CscMatrix::<f64>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCscMatrix::Bool(_) => Ok(DynCscMatrix::Bool(CscMatrix::<bool>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
@@ -283,6 +292,8 @@ impl ReadArrayData for DynCscMatrix {
                     .map(Into::into),
                 ScalarType::Usize => CscMatrix::<usize>::read_select(container, info)
                     .map(Into::into),
+                ScalarType::F16 => CscMatrix::<f16>::read_select(container, info)
+                    .map(Into::into),
                 ScalarType::F32 => CscMatrix::<f32>::read_select(container, info)
                     .map(Into::into),
                 ScalarType::F64 => CscMatrix::<f64>::read_select(container, info)
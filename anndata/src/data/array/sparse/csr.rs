@@ -1,13 +1,14 @@
 use crate::backend::*;
 use crate::data::{
-    array::utils::{cs_major_minor_index, cs_major_index, cs_major_slice},
+    array::utils::{cs_major_minor_index, cs_major_index, cs_major_slice, csr_from_data},
     data_traits::*,
     scalar::DynScalar,
     slice::{SelectInfoElem, Shape},
     BoundedSelectInfo, BoundedSelectInfoElem,
 };
 
-use anyhow::{bail, anyhow, Context, Result};
+use anyhow::{bail, Context, Result};
+use half::f16;
 use nalgebra_sparse::csr::CsrMatrix;
 use nalgebra_sparse::pattern::SparsityPattern;
 use ndarray::Ix1;
@@ -26,6 +27,7 @@ pub enum DynCsrMatrix {
     U32(CsrMatrix<u32>),
     U64(CsrMatrix<u64>),
     Usize(CsrMatrix<usize>),
+    F16(CsrMatrix<f16>),
     F32(CsrMatrix<f32>),
     F64(CsrMatrix<f64>),
     Bool(CsrMatrix<bool>),
@@ -70,6 +72,7 @@ impl TryFrom<DynCsrMatrix> for CsrMatrix<u32> {
             DynCsrMatrix::U16(data) => Ok(cast_csr(data)?),
             DynCsrMatrix::U64(data) => Ok(cast_csr(data)?),
             DynCsrMatrix::Usize(data) => Ok(cast_csr(data)?),
+            DynCsrMatrix::F16(_) => bail!("Cannot convert f16 to u32"),
             DynCsrMatrix::F32(_) => bail!("Cannot convert f32 to u32"),
             DynCsrMatrix::F64(_) => bail!("Cannot convert f64 to u32"),
             DynCsrMatrix::Bool(_) => bail!("Cannot convert bool to f64"),
@@ -99,6 +102,7 @@ impl TryFrom<DynCsrMatrix> for CsrMatrix<f64> {
             DynCsrMatrix::U32(data) => Ok(cast_csr(data)?),
             DynCsrMatrix::U64(_) => bail!("Cannot convert u64 to f64"),
             DynCsrMatrix::Usize(_) => bail!("Cannot convert usize to f64"),
+            DynCsrMatrix::F16(data) => Ok(cast_csr(data)?),
             DynCsrMatrix::F32(data) => Ok(cast_csr(data)?),
             DynCsrMatrix::Bool(_) => bail!("Cannot convert bool to f64"),
             DynCsrMatrix::String(_) => bail!("Cannot convert string to f64"),
@@ -114,6 +118,7 @@ impl_into_dyn_csr!(u8, U8);
 impl_into_dyn_csr!(u16, U16);
 impl_into_dyn_csr!(u64, U64);
 impl_into_dyn_csr!(usize, Usize);
+impl_into_dyn_csr!(f16, F16);
 impl_into_dyn_csr!(f32, F32);
 impl_into_dyn_csr!(bool, Bool);
 impl_into_dyn_csr!(String, String);
@@ -130,6 +135,7 @@ macro_rules! impl_dyn_csr_matrix {
             DynCsrMatrix::U32(data) => $fun!(data),
             DynCsrMatrix::U64(data) => $fun!(data),
             DynCsrMatrix::Usize(data) => $fun!(data),
+            DynCsrMatrix::F16(data) => $fun!(data),
             DynCsrMatrix::F32(data) => $fun!(data),
             DynCsrMatrix::F64(data) => $fun!(data),
             DynCsrMatrix::Bool(data) => $fun!(data),
@@ -163,22 +169,27 @@ impl WriteData for DynCsrMatrix {
 
 impl ReadData for DynCsrMatrix {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        Self::read_assume_sorted(container, false)
+    }
+
+    fn read_assume_sorted<B: Backend>(container: &DataContainer<B>, assume_sorted_indices: bool) -> Result<Self> {
         match container {
             DataContainer::Group(group) => match group.open_dataset("data")?.dtype()? {
-                ScalarType::I8 => CsrMatrix::<i8>::read(container).map(DynCsrMatrix::I8),
-                ScalarType::I16 => CsrMatrix::<i16>::read(container).map(DynCsrMatrix::I16),
-                ScalarType::I32 => CsrMatrix::<i32>::read(container).map(DynCsrMatrix::I32),
-                ScalarType::I64 => CsrMatrix::<i64>::read(container).map(DynCsrMatrix::I64),
-                ScalarType::U8 => CsrMatrix::<u8>::read(container).map(DynCsrMatrix::U8),
-                ScalarType::U16 => CsrMatrix::<u16>::read(container).map(DynCsrMatrix::U16),
-                ScalarType::U32 => CsrMatrix::<u32>::read(container).map(DynCsrMatrix::U32),
-                ScalarType::U64 => CsrMatrix::<u64>::read(container).map(DynCsrMatrix::U64),
-                ScalarType::Usize => CsrMatrix::<usize>::read(container).map(DynCsrMatrix::Usize),
-                ScalarType::F32 => CsrMatrix::<f32>::read(container).map(DynCsrMatrix::F32),
-                ScalarType::F64 => CsrMatrix::<f64>::read(container).map(DynCsrMatrix::F64),
-                ScalarType::Bool => CsrMatrix::<bool>::read(container).map(DynCsrMatrix::Bool),
+                ScalarType::I8 => CsrMatrix::<i8>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::I8),
+                ScalarType::I16 => CsrMatrix::<i16>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::I16),
+                ScalarType::I32 => CsrMatrix::<i32>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::I32),
+                ScalarType::I64 => CsrMatrix::<i64>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::I64),
+                ScalarType::U8 => CsrMatrix::<u8>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::U8),
+                ScalarType::U16 => CsrMatrix::<u16>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::U16),
+                ScalarType::U32 => CsrMatrix::<u32>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::U32),
+                ScalarType::U64 => CsrMatrix::<u64>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::U64),
+                ScalarType::Usize => CsrMatrix::<usize>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::Usize),
+                ScalarType::F16 => CsrMatrix::<f16>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::F16),
+                ScalarType::F32 => CsrMatrix::<f32>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::F32),
+                ScalarType::F64 => CsrMatrix::<f64>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::F64),
+                ScalarType::Bool => CsrMatrix::<bool>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::Bool),
                 ScalarType::String => {
-                    CsrMatrix::<String>::read(container).map(DynCsrMatrix::String)
+                    CsrMatrix::<String>::read_assume_sorted(container, assume_sorted_indices).map(DynCsrMatrix::String)
                 }
             },
             _ => bail!("cannot read csr matrix from non-group container"),
@@ -231,6 +242,7 @@ impl ArrayOp for DynCsrMatrix {
             DynCsrMatrix::I16(_) => Ok(DynCsrMatrix::I16(CsrMatrix::<i16>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrMatrix::I32(_) => Ok(DynCsrMatrix::I32(CsrMatrix::<i32>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrMatrix::I64(_) => Ok(DynCsrMatrix::I64(CsrMatrix::<i64>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
+            DynCsrMatrix::F16(_) => Ok(DynCsrMatrix::F16(CsrMatrix::<f16>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrMatrix::F32(_) => Ok(DynCsrMatrix::F32(CsrMatrix::<f32>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrMatrix::F64(_) => Ok(DynCsrMatrix::F64(CsrMatrix::<f64>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrMatrix::Bool(_) => Ok(DynCsrMatrix::Bool(CsrMatrix::<bool>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
@@ -250,37 +262,47 @@ impl ReadArrayData for DynCsrMatrix {
     }
 
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+    {
+        Self::read_select_assume_sorted(container, info, false)
+    }
+
+    fn read_select_assume_sorted<B, S>(container: &DataContainer<B>, info: &[S], assume_sorted_indices: bool) -> Result<Self>
     where
         B: Backend,
         S: AsRef<SelectInfoElem>,
     {
         if let DataType::CsrMatrix(ty) = container.encoding_type()? {
             match ty {
-                ScalarType::I8 => CsrMatrix::<i8>::read_select(container, info)
+                ScalarType::I8 => CsrMatrix::<i8>::read_select_assume_sorted(container, info, assume_sorted_indices)
+                    .map(Into::into),
+                ScalarType::I16 => CsrMatrix::<i16>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::I16 => CsrMatrix::<i16>::read_select(container, info)
+                ScalarType::I32 => CsrMatrix::<i32>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::I32 => CsrMatrix::<i32>::read_select(container, info)
+                ScalarType::I64 => CsrMatrix::<i64>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::I64 => CsrMatrix::<i64>::read_select(container, info)
+                ScalarType::U8 => CsrMatrix::<u8>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::U8 => CsrMatrix::<u8>::read_select(container, info)
+                ScalarType::U16 => CsrMatrix::<u16>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::U16 => CsrMatrix::<u16>::read_select(container, info)
+                ScalarType::U32 => CsrMatrix::<u32>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::U32 => CsrMatrix::<u32>::read_select(container, info)
+                ScalarType::U64 => CsrMatrix::<u64>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::U64 => CsrMatrix::<u64>::read_select(container, info) 
+                ScalarType::Usize => CsrMatrix::<usize>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::Usize => CsrMatrix::<usize>::read_select(container, info)
+                ScalarType::F16 => CsrMatrix::<f16>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::F32 => CsrMatrix::<f32>::read_select(container, info)
+                ScalarType::F32 => CsrMatrix::<f32>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::F64 => CsrMatrix::<f64>::read_select(container, info)
+                ScalarType::F64 => CsrMatrix::<f64>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::Bool => CsrMatrix::<bool>::read_select(container, info)
+                ScalarType::Bool => CsrMatrix::<bool>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
-                ScalarType::String => CsrMatrix::<String>::read_select(container, info)
+                ScalarType::String => CsrMatrix::<String>::read_select_assume_sorted(container, info, assume_sorted_indices)
                     .map(Into::into),
             }
         } else {
@@ -571,6 +593,10 @@ impl<T: BackendData> WriteData for CsrMatrix<T> {
 
 impl<T: BackendData> ReadData for CsrMatrix<T> {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self> {
+        Self::read_assume_sorted(container, false)
+    }
+
+    fn read_assume_sorted<B: Backend>(container: &DataContainer<B>, assume_sorted_indices: bool) -> Result<Self> {
         let data_type = container.encoding_type()?;
         if let DataType::CsrMatrix(_) = data_type {
             let group = container.as_group()?;
@@ -578,9 +604,7 @@ impl<T: BackendData> ReadData for CsrMatrix<T> {
             let data = group.open_dataset("data")?.read_array::<_, Ix1>()?.into_raw_vec();
             let indptr: Vec<usize> = group.open_dataset("indptr")?.read_array::<_, Ix1>()?.into_raw_vec();
             let indices: Vec<usize> = group.open_dataset("indices")?.read_array::<_, Ix1>()?.into_raw_vec();
-            CsrMatrix::try_from_csr_data(
-                shape[0], shape[1], indptr, indices, data
-            ).map_err(|e| anyhow!("cannot read csr matrix: {}", e))
+            csr_from_data(shape[0], shape[1], indptr, indices, data, assume_sorted_indices)
         } else {
             bail!("cannot read csr matrix from container with data type {:?}", data_type)
         }
@@ -596,8 +620,16 @@ impl<T: BackendData> ReadArrayData for CsrMatrix<T> {
             .into())
     }
 
-    // TODO: efficient implementation for slice
     fn read_select<B, S>(container: &DataContainer<B>, info: &[S]) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+    {
+        Self::read_select_assume_sorted(container, info, false)
+    }
+
+    // TODO: efficient implementation for slice
+    fn read_select_assume_sorted<B, S>(container: &DataContainer<B>, info: &[S], assume_sorted_indices: bool) -> Result<Self>
     where
         B: Backend,
         S: AsRef<SelectInfoElem>,
@@ -609,7 +641,7 @@ impl<T: BackendData> ReadArrayData for CsrMatrix<T> {
             }
 
             if info.iter().all(|s| s.as_ref().is_full()) {
-                return Self::read(container);
+                return Self::read_assume_sorted(container, assume_sorted_indices);
             }
 
             let data = if let SelectInfoElem::Slice(s) = info[0].as_ref()  {
@@ -628,15 +660,16 @@ impl<T: BackendData> ReadArrayData for CsrMatrix<T> {
                 let data: Vec<T> = group.open_dataset("data")?.read_array_slice(&[&slice])?.to_vec();
                 let indices: Vec<usize> = group.open_dataset("indices")?.read_array_slice(&[&slice])?.to_vec();
                 indptr.iter_mut().for_each(|x| *x -= lo);
-                CsrMatrix::try_from_csr_data(
+                csr_from_data(
                     indptr.len() - 1,
                     Self::get_shape(container)?[1],
                     indptr,
                     indices,
                     data,
-                ).unwrap().select_axis(1, info[1].as_ref())
+                    assume_sorted_indices,
+                )?.select_axis(1, info[1].as_ref())
             } else {
-                Self::read(container)?.select(info)
+                Self::read_assume_sorted(container, assume_sorted_indices)?.select(info)
             };
             Ok(data)
         } else {
@@ -820,4 +853,18 @@ mod csr_matrix_index_tests {
             csr_select(&csr_matrix, ridx.iter().cloned(), cidx.iter().cloned()),
         );
     }
+
+    #[test]
+    fn test_csr_from_data() {
+        let unsorted = csr_from_data(2, 3, vec![0, 2, 3], vec![2, 0, 1], vec![1, 2, 3], false).unwrap();
+        assert_eq!(unsorted.col_indices(), &[0, 2, 1]);
+        assert_eq!(unsorted.values(), &[2, 1, 3]);
+
+        // The order of the indices is trusted, but not their bounds or the offsets.
+        let trusted = csr_from_data(2, 3, vec![0, 2, 3], vec![2, 0, 1], vec![1, 2, 3], true).unwrap();
+        assert_eq!(trusted.col_indices(), &[2, 0, 1]);
+        assert!(csr_from_data(2, 3, vec![0, 2, 3], vec![0, 3, 1], vec![1, 2, 3], true).is_err());
+        assert!(csr_from_data(2, 3, vec![0, 3, 2], vec![0, 1, 2], vec![1, 2, 3], true).is_err());
+        assert!(csr_from_data(2, 3, vec![0, 2], vec![0, 1], vec![1, 2], true).is_err());
+    }
 }
@@ -8,6 +8,7 @@ use crate::data::{
 };
 
 use anyhow::{bail, Result};
+use half::f16;
 use nalgebra_sparse::pattern::SparsityPattern;
 use nalgebra_sparse::{coo::CooMatrix, csr::CsrMatrix};
 use ndarray::Ix1;
@@ -26,6 +27,7 @@ pub enum DynCsrNonCanonical {
     U32(CsrNonCanonical<u32>),
     U64(CsrNonCanonical<u64>),
     Usize(CsrNonCanonical<usize>),
+    F16(CsrNonCanonical<f16>),
     F32(CsrNonCanonical<f32>),
     F64(CsrNonCanonical<f64>),
     Bool(CsrNonCanonical<bool>),
@@ -33,6 +35,9 @@ pub enum DynCsrNonCanonical {
 }
 
 impl DynCsrNonCanonical {
+    /// Convert to a csr matrix if the matrix is already in canonical form,
+    /// i.e., the column indices are sorted and contain no duplicates.
+    /// Otherwise the matrix is returned unchanged, see [`Self::sum_duplicates`].
     pub fn canonicalize(self) -> Result<DynCsrMatrix, Self> {
         match self {
             DynCsrNonCanonical::I8(data) => data.canonicalize().map(DynCsrMatrix::I8).map_err(Into::into),
@@ -44,12 +49,39 @@ impl DynCsrNonCanonical {
             DynCsrNonCanonical::U32(data) => data.canonicalize().map(DynCsrMatrix::U32).map_err(Into::into),
             DynCsrNonCanonical::U64(data) => data.canonicalize().map(DynCsrMatrix::U64).map_err(Into::into),
             DynCsrNonCanonical::Usize(data) => data.canonicalize().map(DynCsrMatrix::Usize).map_err(Into::into),
+            DynCsrNonCanonical::F16(data) => data.canonicalize().map(DynCsrMatrix::F16).map_err(Into::into),
             DynCsrNonCanonical::F32(data) => data.canonicalize().map(DynCsrMatrix::F32).map_err(Into::into),
             DynCsrNonCanonical::F64(data) => data.canonicalize().map(DynCsrMatrix::F64).map_err(Into::into),
             DynCsrNonCanonical::Bool(data) => data.canonicalize().map(DynCsrMatrix::Bool).map_err(Into::into),
             DynCsrNonCanonical::String(data) => data.canonicalize().map(DynCsrMatrix::String).map_err(Into::into),
         }
     }
+
+    /// Sort the column indices and sum the values of duplicate entries.
+    /// Duplicate boolean entries are combined with logical OR. Duplicate
+    /// string entries cannot be combined and result in an error.
+    pub fn sum_duplicates(self) -> Result<DynCsrMatrix> {
+        let csr = match self {
+            DynCsrNonCanonical::I8(data) => DynCsrMatrix::I8(data.sum_duplicates()),
+            DynCsrNonCanonical::I16(data) => DynCsrMatrix::I16(data.sum_duplicates()),
+            DynCsrNonCanonical::I32(data) => DynCsrMatrix::I32(data.sum_duplicates()),
+            DynCsrNonCanonical::I64(data) => DynCsrMatrix::I64(data.sum_duplicates()),
+            DynCsrNonCanonical::U8(data) => DynCsrMatrix::U8(data.sum_duplicates()),
+            DynCsrNonCanonical::U16(data) => DynCsrMatrix::U16(data.sum_duplicates()),
+            DynCsrNonCanonical::U32(data) => DynCsrMatrix::U32(data.sum_duplicates()),
+            DynCsrNonCanonical::U64(data) => DynCsrMatrix::U64(data.sum_duplicates()),
+            DynCsrNonCanonical::Usize(data) => DynCsrMatrix::Usize(data.sum_duplicates()),
+            DynCsrNonCanonical::F16(data) => DynCsrMatrix::F16(data.sum_duplicates()),
+            DynCsrNonCanonical::F32(data) => DynCsrMatrix::F32(data.sum_duplicates()),
+            DynCsrNonCanonical::F64(data) => DynCsrMatrix::F64(data.sum_duplicates()),
+            DynCsrNonCanonical::Bool(data) =>
+                DynCsrMatrix::Bool(data.sum_duplicates_with(|a, b| Ok(a || b))?),
+            DynCsrNonCanonical::String(data) => DynCsrMatrix::String(
+                data.sum_duplicates_with(|_, _| bail!("cannot merge duplicate entries of string type"))?
+            ),
+        };
+        Ok(csr)
+    }
 }
 
 macro_rules! impl_into_dyn_csr {
@@ -80,6 +112,7 @@ impl_into_dyn_csr!(u16, U16);
 impl_into_dyn_csr!(u32, U32);
 impl_into_dyn_csr!(u64, U64);
 impl_into_dyn_csr!(usize, Usize);
+impl_into_dyn_csr!(f16, F16);
 impl_into_dyn_csr!(f32, F32);
 impl_into_dyn_csr!(f64, F64);
 impl_into_dyn_csr!(bool, Bool);
@@ -97,6 +130,7 @@ macro_rules! impl_dyn_csr_matrix {
             DynCsrNonCanonical::U32(data) => $fun!(data),
             DynCsrNonCanonical::U64(data) => $fun!(data),
             DynCsrNonCanonical::Usize(data) => $fun!(data),
+            DynCsrNonCanonical::F16(data) => $fun!(data),
             DynCsrNonCanonical::F32(data) => $fun!(data),
             DynCsrNonCanonical::F64(data) => $fun!(data),
             DynCsrNonCanonical::Bool(data) => $fun!(data),
@@ -117,6 +151,7 @@ impl From<DynCsrMatrix> for DynCsrNonCanonical {
             DynCsrMatrix::U32(data) => DynCsrNonCanonical::U32(data.into()),
             DynCsrMatrix::U64(data) => DynCsrNonCanonical::U64(data.into()),
             DynCsrMatrix::Usize(data) => DynCsrNonCanonical::Usize(data.into()),
+            DynCsrMatrix::F16(data) => DynCsrNonCanonical::F16(data.into()),
             DynCsrMatrix::F32(data) => DynCsrNonCanonical::F32(data.into()),
             DynCsrMatrix::F64(data) => DynCsrNonCanonical::F64(data.into()),
             DynCsrMatrix::Bool(data) => DynCsrNonCanonical::Bool(data.into()),
@@ -161,6 +196,7 @@ impl ReadData for DynCsrNonCanonical {
                 ScalarType::U32 => CsrNonCanonical::<u32>::read(container).map(DynCsrNonCanonical::U32),
                 ScalarType::U64 => CsrNonCanonical::<u64>::read(container).map(DynCsrNonCanonical::U64),
                 ScalarType::Usize => CsrNonCanonical::<usize>::read(container).map(DynCsrNonCanonical::Usize),
+                ScalarType::F16 => CsrNonCanonical::<f16>::read(container).map(DynCsrNonCanonical::F16),
                 ScalarType::F32 => CsrNonCanonical::<f32>::read(container).map(DynCsrNonCanonical::F32),
                 ScalarType::F64 => CsrNonCanonical::<f64>::read(container).map(DynCsrNonCanonical::F64),
                 ScalarType::Bool => CsrNonCanonical::<bool>::read(container).map(DynCsrNonCanonical::Bool),
@@ -218,6 +254,7 @@ impl ArrayOp for DynCsrNonCanonical {
             DynCsrNonCanonical::I16(_) => Ok(DynCsrNonCanonical::I16(CsrNonCanonical::<i16>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrNonCanonical::I32(_) => Ok(DynCsrNonCanonical::I32(CsrNonCanonical::<i32>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrNonCanonical::I64(_) => Ok(DynCsrNonCanonical::I64(CsrNonCanonical::<i64>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
+            DynCsrNonCanonical::F16(_) => Ok(DynCsrNonCanonical::F16(CsrNonCanonical::<f16>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrNonCanonical::F32(_) => Ok(DynCsrNonCanonical::F32(CsrNonCanonical::<f32>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrNonCanonical::F64(_) => Ok(DynCsrNonCanonical::F64(CsrNonCanonical::<f64>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
             DynCsrNonCanonical::Bool(_) => Ok(DynCsrNonCanonical::Bool(CsrNonCanonical::<bool>::vstack(iter.map(|x| x.try_into().unwrap()))?)),
@@ -261,6 +298,8 @@ impl ReadArrayData for DynCsrNonCanonical {
                     .map(Into::into),
                 ScalarType::Usize => CsrNonCanonical::<usize>::read_select(container, info)
                     .map(Into::into),
+                ScalarType::F16 => CsrNonCanonical::<f16>::read_select(container, info)
+                    .map(Into::into),
                 ScalarType::F32 => CsrNonCanonical::<f32>::read_select(container, info)
                     .map(Into::into),
                 ScalarType::F64 => CsrNonCanonical::<f64>::read_select(container, info)
@@ -336,6 +375,9 @@ impl<T> CsrNonCanonical<T> {
         }
     }
 
+    /// Convert to a csr matrix if the matrix is already in canonical form,
+    /// i.e., the column indices are sorted and contain no duplicates.
+    /// Otherwise the matrix is returned unchanged, see [`Self::sum_duplicates`].
     pub fn canonicalize(self) -> Result<CsrMatrix<T>, Self> {
         let nrows = self.nrows();
         let ncols = self.ncols();
@@ -350,6 +392,49 @@ impl<T> CsrNonCanonical<T> {
     }
 }
 
+impl<T: Clone> CsrNonCanonical<T> {
+    /// Sort the column indices within each row and sum the values of duplicate entries.
+    pub fn sum_duplicates(self) -> CsrMatrix<T>
+    where
+        T: std::ops::Add<Output = T>,
+    {
+        self.sum_duplicates_with(|a, b| Ok(a + b)).unwrap()
+    }
+
+    /// Sort the column indices within each row and merge the values of duplicate
+    /// entries using `merge`.
+    pub fn sum_duplicates_with<F>(self, mut merge: F) -> Result<CsrMatrix<T>>
+    where
+        F: FnMut(T, T) -> Result<T>,
+    {
+        let nrows = self.nrows();
+        let ncols = self.ncols();
+        let (offsets, mut indices, mut values) = self.disassemble();
+        crate::data::utils::sort_csr_indices(&offsets, &mut indices, &mut values);
+
+        let mut new_offsets = Vec::with_capacity(offsets.len());
+        let mut new_indices: Vec<usize> = Vec::with_capacity(indices.len());
+        let mut new_values: Vec<T> = Vec::with_capacity(values.len());
+        new_offsets.push(0);
+        let mut entries = indices.into_iter().zip(values);
+        for (begin, end) in offsets.iter().zip(offsets.iter().skip(1)) {
+            let lane_start = new_indices.len();
+            for (i, v) in entries.by_ref().take(end - begin) {
+                if new_indices.len() > lane_start && *new_indices.last().unwrap() == i {
+                    let prev = new_values.pop().unwrap();
+                    new_values.push(merge(prev, v)?);
+                } else {
+                    new_indices.push(i);
+                    new_values.push(v);
+                }
+            }
+            new_offsets.push(new_indices.len());
+        }
+        CsrMatrix::try_from_csr_data(nrows, ncols, new_offsets, new_indices, new_values)
+            .map_err(|e| anyhow::anyhow!("cannot canonicalize csr matrix: {}", e))
+    }
+}
+
 impl<T> From<CsrMatrix<T>> for CsrNonCanonical<T> {
     fn from(csr: CsrMatrix<T>) -> Self {
         let num_rows = csr.nrows();
@@ -796,6 +881,30 @@ mod csr_noncanonical_index_tests {
         assert_eq!(&CooMatrix::from(a), b);
     }
 
+    #[test]
+    fn test_canonicalize() {
+        // Row 0 has unsorted indices, row 2 has unsorted duplicates.
+        let indptr = vec![0, 3, 3, 7];
+        let indices = vec![2, 0, 1, 3, 1, 3, 0];
+        let data = vec![1, 2, 3, 4, 5, 6, 7];
+
+        // The order is not checked when the indices are assumed to be sorted.
+        assert!(crate::data::utils::from_csr_data_assume_sorted::<i32>(
+            3, 4, indptr.clone(), indices.clone(), data.clone(), true
+        ).is_ok());
+
+        let csr: CsrNonCanonical<i32> = crate::data::utils::from_csr_data_assume_sorted(
+            3, 4, indptr, indices, data, false
+        ).unwrap().try_into().unwrap();
+        assert_eq!(csr.row_offsets(), &[0, 3, 3, 7]);
+        assert_eq!(csr.col_indices(), &[0, 1, 2, 0, 1, 3, 3]);
+
+        let csr = csr.sum_duplicates();
+        assert_eq!(csr.row_offsets(), &[0, 3, 3, 6]);
+        assert_eq!(csr.col_indices(), &[0, 1, 2, 0, 1, 3]);
+        assert_eq!(csr.values(), &[2, 3, 1, 7, 5, 10]);
+    }
+
     #[test]
     fn test_csr_noncanonical() {
         let coo = CooMatrix::try_from_triplets(
@@ -1,12 +1,14 @@
-use crate::backend::{Backend, BackendData, DatasetOp, GroupOp, WriteConfig};
-use crate::data::{SelectInfoElem, Shape};
+use crate::backend::{Backend, BackendData, DatasetOp, GroupOp, ScalarType, WriteConfig};
+use crate::data::{DynArray, SelectInfoElem, Shape};
 use crate::ArrayData;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use half::f16;
 use itertools::Itertools;
-use ndarray::{ArrayView, RemoveAxis};
+use ndarray::{Array1, Array2, ArrayD, ArrayView, Axis, Ix1, Ix2, RemoveAxis};
+use num::{NumCast, ToPrimitive};
 use smallvec::SmallVec;
-use nalgebra_sparse::{CsrMatrix, pattern::{ SparsityPattern, SparsityPatternFormatError}};
+use nalgebra_sparse::{CscMatrix, CsrMatrix, pattern::{ SparsityPattern, SparsityPatternFormatError}};
 
 use super::CsrNonCanonical;
 
@@ -318,6 +320,40 @@ pub(crate) fn compute_sort_permutation(permutation: &mut [usize], indices: &[usi
     permutation.sort_unstable_by_key(|idx| indices[*idx]);
 }
 
+/// Sort the column indices within each row of a compressed sparse matrix,
+/// permuting the values accordingly. Rows that are already sorted are left untouched.
+pub(crate) fn sort_csr_indices<T: Clone>(indptr: &[usize], indices: &mut [usize], data: &mut [T]) {
+    let mut idx_workspace = Vec::new();
+    let mut perm_workspace = Vec::new();
+    let mut values_workspace = Vec::new();
+    for (&begin, &end) in indptr.iter().tuple_windows() {
+        // Malformed offsets are left for the format check to report.
+        if begin >= end || end > indices.len() || end > data.len() {
+            continue;
+        }
+        let lane_indices = &mut indices[begin..end];
+        if lane_indices.windows(2).all(|w| w[0] <= w[1]) {
+            continue;
+        }
+        let count = end - begin;
+        idx_workspace.clear();
+        idx_workspace.extend_from_slice(lane_indices);
+        values_workspace.clear();
+        values_workspace.extend_from_slice(&data[begin..end]);
+        perm_workspace.resize(count, 0);
+        sort_lane(
+            lane_indices,
+            &mut data[begin..end],
+            &idx_workspace,
+            &values_workspace,
+            &mut perm_workspace,
+        );
+    }
+}
+
+/// Create a csr matrix from the raw data. A `CsrNonCanonical` is returned if
+/// the matrix contains duplicate entries. Unsorted column indices are reported
+/// as an error, see [`from_csr_data_assume_sorted`] to sort them instead.
 pub fn from_csr_data<T>(
     nrows: usize,
     ncols: usize,
@@ -337,36 +373,103 @@ where
             let csr = CsrMatrix::try_from_pattern_and_values(pattern, data).unwrap();
             Ok(csr.into())
         },
-        Err(e) => {
-            match e {
-                SparsityPatternFormatError::DuplicateEntry => {
-                    let csr = CsrNonCanonical::from_csr_data(
-                        nrows, ncols, indptr, indices, data
-                    );
-                    Ok(csr.into())
-                },
-                _ => Err(anyhow!("cannot read csr matrix: {}", e))
-            }
-        }
+        Err(SparsityPatternFormatError::DuplicateEntry) => {
+            Ok(CsrNonCanonical::from_csr_data(nrows, ncols, indptr, indices, data).into())
+        },
+        Err(e) => Err(anyhow!("cannot read csr matrix: {}", e)),
     }
 }
 
-pub(crate) fn check_format(nrows: usize, ncols: usize, indptr: &[usize], indices: &[usize]) -> std::result::Result<(), SparsityPatternFormatError>
+/// Like [`from_csr_data`], but the column indices within each row are sorted
+/// if needed. If `assume_sorted_indices` is true, the column indices are
+/// trusted to be sorted and unique, so that they are neither sorted nor
+/// checked for order; see [`csr_from_sorted_data`].
+pub fn from_csr_data_assume_sorted<T>(
+    nrows: usize,
+    ncols: usize,
+    indptr: Vec<usize>,
+    mut indices: Vec<usize>,
+    mut data: Vec<T>,
+    assume_sorted_indices: bool,
+) -> anyhow::Result<ArrayData>
+where
+    T: Clone,
+    CsrMatrix<T>: Into<ArrayData>,
+    CsrNonCanonical<T>: Into<ArrayData>,
 {
+    if assume_sorted_indices {
+        return csr_from_sorted_data(nrows, ncols, indptr, indices, data).map(Into::into);
+    }
+    if let Err(SparsityPatternFormatError::NonmonotonicMinorIndices) =
+        check_format(nrows, ncols, &indptr, &indices)
+    {
+        sort_csr_indices(&indptr, &mut indices, &mut data);
+    }
+    from_csr_data(nrows, ncols, indptr, indices, data)
+}
+
+/// Like [`from_csr_data`], but fail if the matrix contains duplicate entries.
+pub(crate) fn csr_from_data<T: Clone>(
+    nrows: usize,
+    ncols: usize,
+    indptr: Vec<usize>,
+    mut indices: Vec<usize>,
+    mut data: Vec<T>,
+    assume_sorted_indices: bool,
+) -> Result<CsrMatrix<T>> {
+    if assume_sorted_indices {
+        csr_from_sorted_data(nrows, ncols, indptr, indices, data)
+    } else {
+        sort_csr_indices(&indptr, &mut indices, &mut data);
+        CsrMatrix::try_from_csr_data(nrows, ncols, indptr, indices, data)
+            .map_err(|e| anyhow!("cannot read csr matrix: {}", e))
+    }
+}
+
+/// Create a csr matrix whose column indices are trusted to be sorted and
+/// unique within each row. Only the offsets and the bounds of the indices are
+/// checked, which is much cheaper than checking the order of every row.
+pub(crate) fn csr_from_sorted_data<T>(
+    nrows: usize,
+    ncols: usize,
+    indptr: Vec<usize>,
+    indices: Vec<usize>,
+    data: Vec<T>,
+) -> Result<CsrMatrix<T>> {
+    check_offsets(nrows, &indptr, indices.len())
+        .map_err(|e| anyhow!("cannot read csr matrix: {}", e))?;
+    if indices.iter().any(|&i| i >= ncols) {
+        bail!("cannot read csr matrix: {}", SparsityPatternFormatError::MinorIndexOutOfBounds);
+    }
+    let pattern = unsafe {
+        SparsityPattern::from_offset_and_indices_unchecked(nrows, ncols, indptr, indices)
+    };
+    CsrMatrix::try_from_pattern_and_values(pattern, data)
+        .map_err(|e| anyhow!("cannot read csr matrix: {}", e))
+}
+
+/// Check the offsets of a compressed sparse matrix with `nrows` rows and
+/// `nnz` stored entries.
+fn check_offsets(nrows: usize, indptr: &[usize], nnz: usize) -> std::result::Result<(), SparsityPatternFormatError> {
     use SparsityPatternFormatError::*;
 
     if indptr.len() != nrows + 1 {
         return Err(InvalidOffsetArrayLength);
     }
-
-    // Check that the first and last offsets conform to the specification
-    {
-        let first_offset_ok = *indptr.first().unwrap() == 0;
-        let last_offset_ok = *indptr.last().unwrap() == indices.len();
-        if !first_offset_ok || !last_offset_ok {
-            return Err(InvalidOffsetFirstLast);
-        }
+    if indptr[0] != 0 || indptr[nrows] != nnz {
+        return Err(InvalidOffsetFirstLast);
+    }
+    if indptr.windows(2).any(|w| w[0] > w[1]) {
+        return Err(NonmonotonicOffsets);
     }
+    Ok(())
+}
+
+pub(crate) fn check_format(nrows: usize, ncols: usize, indptr: &[usize], indices: &[usize]) -> std::result::Result<(), SparsityPatternFormatError>
+{
+    use SparsityPatternFormatError::*;
+
+    check_offsets(nrows, indptr, indices.len())?;
 
     // Test that each lane has strictly monotonically increasing minor indices, i.e.
     // minor indices within a lane are sorted, unique. In addition, each minor index
@@ -376,12 +479,6 @@ pub(crate) fn check_format(nrows: usize, ncols: usize, indptr: &[usize], indices
         for lane_idx in 0..nrows {
             let range_start = indptr[lane_idx];
             let range_end = indptr[lane_idx + 1];
-
-            // Test that major offsets are monotonically increasing
-            if range_start > range_end {
-                return Err(NonmonotonicOffsets);
-            }
-
             let indices = &indices[range_start..range_end];
 
             // We test for in-bounds, uniqueness and monotonicity at the same time
@@ -435,4 +532,334 @@ where
     indptr.push(nnz);
 
     (num_rows, num_cols, indptr, indices, data)
-}
\ No newline at end of file
+}
+
+/// Convert a two-dimensional dense array to a csr matrix. Elements equal to
+/// `T::default()` are treated as zeros and are not stored.
+pub(crate) fn dense_to_csr<T>(array: ArrayD<T>) -> Result<CsrMatrix<T>>
+where
+    T: Clone + Default + PartialEq,
+{
+    let array = array.into_dimensionality::<Ix2>()?;
+    let (nrows, ncols) = array.dim();
+    let zero = T::default();
+    let mut indptr = Vec::with_capacity(nrows + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    for row in array.rows() {
+        row.iter().enumerate().filter(|(_, v)| **v != zero).for_each(|(j, v)| {
+            indices.push(j);
+            data.push(v.clone());
+        });
+        indptr.push(indices.len());
+    }
+    CsrMatrix::try_from_csr_data(nrows, ncols, indptr, indices, data)
+        .map_err(|e| anyhow!("cannot build a csr matrix: {}", e))
+}
+
+/// Convert a csr matrix to a two-dimensional dense array, filling the missing
+/// entries with `T::default()`.
+pub(crate) fn csr_to_dense<T>(csr: CsrMatrix<T>) -> ArrayD<T>
+where
+    T: Clone + Default,
+{
+    let mut array = Array2::from_elem((csr.nrows(), csr.ncols()), T::default());
+    csr.triplet_iter().for_each(|(i, j, v)| array[[i, j]] = v.clone());
+    array.into_dyn()
+}
+
+/// Move column `j` of a two-dimensional dense array to column `columns[j]` of
+/// an array with `ncols` columns, dropping it if `columns[j]` is `None`. The
+/// other columns are set to `fill`.
+pub(crate) fn dense_expand_columns<T: Clone>(
+    array: ArrayD<T>,
+    columns: &[Option<usize>],
+    ncols: usize,
+    fill: T,
+) -> Result<ArrayD<T>> {
+    let array = array.into_dimensionality::<Ix2>()?;
+    let mut result = Array2::from_elem((array.nrows(), ncols), fill);
+    columns.iter().enumerate().filter_map(|(j, c)| c.map(|c| (j, c))).for_each(|(j, c)|
+        result.column_mut(c).assign(&array.column(j))
+    );
+    Ok(result.into_dyn())
+}
+
+/// See [`dense_expand_columns`]. The missing entries are structural zeros.
+pub(crate) fn csr_expand_columns<T: Clone>(
+    csr: CsrMatrix<T>,
+    columns: &[Option<usize>],
+    ncols: usize,
+) -> Result<CsrMatrix<T>> {
+    let mut indptr = Vec::with_capacity(csr.nrows() + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    let mut row = Vec::new();
+    indptr.push(0);
+    for lane in csr.row_iter() {
+        row.extend(lane.col_indices().iter().zip(lane.values())
+            .filter_map(|(j, v)| columns[*j].map(|c| (c, v.clone()))));
+        // The columns are not necessarily moved in order.
+        row.sort_unstable_by_key(|(c, _)| *c);
+        row.drain(..).for_each(|(c, v)| {
+            indices.push(c);
+            data.push(v);
+        });
+        indptr.push(indices.len());
+    }
+    CsrMatrix::try_from_csr_data(csr.nrows(), ncols, indptr, indices, data)
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// See [`dense_expand_columns`]. The missing entries are structural zeros.
+pub(crate) fn csc_expand_columns<T: Clone>(
+    csc: CscMatrix<T>,
+    columns: &[Option<usize>],
+    ncols: usize,
+) -> Result<CscMatrix<T>> {
+    let mut source = vec![None; ncols];
+    columns.iter().enumerate().for_each(|(j, c)| if let Some(c) = c {
+        source[*c] = Some(j);
+    });
+    let mut indptr = Vec::with_capacity(ncols + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    for j in source {
+        if let Some(j) = j {
+            let lane = csc.col(j);
+            indices.extend_from_slice(lane.row_indices());
+            data.extend_from_slice(lane.values());
+        }
+        indptr.push(indices.len());
+    }
+    CscMatrix::try_from_csc_data(csc.nrows(), ncols, indptr, indices, data)
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// The value to sum, i.e., the value itself, or 1 if it is nonzero and 0
+/// otherwise if `count_nonzero` is true.
+fn summand<T: ToPrimitive>(x: &T, count_nonzero: bool) -> f64 {
+    let x = x.to_f64().unwrap_or(f64::NAN);
+    if count_nonzero {
+        if x == 0.0 { 0.0 } else { 1.0 }
+    } else {
+        x
+    }
+}
+
+/// Sum a two-dimensional dense array along `axis`. See [`summand`] for the
+/// meaning of `count_nonzero`.
+pub(crate) fn dense_sum<T: ToPrimitive>(array: &ArrayD<T>, axis: usize, count_nonzero: bool) -> Array1<f64> {
+    array
+        .fold_axis(Axis(axis), 0.0, |acc, x| acc + summand(x, count_nonzero))
+        .into_dimensionality::<Ix1>()
+        .unwrap()
+}
+
+/// Sum a compressed sparse matrix. If `per_major` is true, return one sum per
+/// major lane (e.g., row sums of a CSR matrix), otherwise one sum per minor index.
+/// See [`summand`] for the meaning of `count_nonzero`.
+pub(crate) fn cs_sum<T: ToPrimitive>(
+    offsets: &[usize],
+    indices: &[usize],
+    values: &[T],
+    minor_len: usize,
+    per_major: bool,
+    count_nonzero: bool,
+) -> Array1<f64> {
+    let to_f64 = |x: &T| summand(x, count_nonzero);
+    if per_major {
+        offsets
+            .windows(2)
+            .map(|w| values[w[0]..w[1]].iter().map(to_f64).sum())
+            .collect()
+    } else {
+        let mut sum = Array1::zeros(minor_len);
+        indices
+            .iter()
+            .zip(values)
+            .for_each(|(i, x)| sum[*i] += to_f64(x));
+        sum
+    }
+}
+
+/// The number of values, the mean and the sum of squared deviations from the
+/// mean of every lane of a two-dimensional array. Moments of consecutive
+/// chunks of rows can be merged, so that the variance is computed in a single
+/// pass using Welford's algorithm.
+#[derive(Debug, Clone)]
+pub(crate) struct Moments {
+    pub count: f64,
+    pub mean: Array1<f64>,
+    pub m2: Array1<f64>,
+}
+
+impl Moments {
+    pub fn empty(len: usize) -> Self {
+        Self { count: 0.0, mean: Array1::zeros(len), m2: Array1::zeros(len) }
+    }
+
+    /// Merge with the moments of other rows of the same columns, using the
+    /// update of Chan et al.
+    pub fn merge(&mut self, other: Moments) {
+        if other.count == 0.0 {
+            return;
+        }
+        if self.count == 0.0 {
+            *self = other;
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = &other.mean - &self.mean;
+        self.mean.scaled_add(other.count / count, &delta);
+        self.m2 += &other.m2;
+        self.m2.scaled_add(self.count * other.count / count, &delta.mapv(|x| x * x));
+        self.count = count;
+    }
+
+    /// The variance, i.e., the sum of squared deviations divided by `count - ddof`.
+    /// It is NaN if `count <= ddof`.
+    pub fn var(&self, ddof: usize) -> Array1<f64> {
+        let n = self.count - ddof as f64;
+        if n > 0.0 {
+            self.m2.mapv(|x| x / n)
+        } else {
+            Array1::from_elem(self.m2.len(), f64::NAN)
+        }
+    }
+}
+
+/// Compute the moments of a two-dimensional dense array along `axis`.
+pub(crate) fn dense_moments<T: ToPrimitive>(array: &ArrayD<T>, axis: usize) -> Moments {
+    let n = array.len_of(Axis(axis));
+    let len = array.len_of(Axis(1 - axis));
+    if n == 0 {
+        return Moments::empty(len);
+    }
+    let mean = dense_sum(array, axis, false) / n as f64;
+    let mut m2 = Array1::zeros(len);
+    array.axis_iter(Axis(axis)).for_each(|lane| {
+        lane.iter().zip(m2.iter_mut()).zip(mean.iter()).for_each(|((x, m2), mean)| {
+            *m2 += (summand(x, false) - mean).powi(2)
+        })
+    });
+    Moments { count: n as f64, mean, m2 }
+}
+
+/// Compute the moments of a compressed sparse matrix, counting the entries that
+/// are not stored as zeros. See [`cs_sum`] for the meaning of `per_major`.
+pub(crate) fn cs_moments<T: ToPrimitive>(
+    offsets: &[usize],
+    indices: &[usize],
+    values: &[T],
+    minor_len: usize,
+    per_major: bool,
+) -> Moments {
+    let to_f64 = |x: &T| summand(x, false);
+    let n = if per_major { minor_len } else { offsets.len() - 1 };
+    if n == 0 {
+        return Moments::empty(if per_major { offsets.len() - 1 } else { minor_len });
+    }
+    let count = n as f64;
+    let mean = cs_sum(offsets, indices, values, minor_len, per_major, false) / count;
+    let mut m2 = Array1::zeros(mean.len());
+    let mut stored = vec![0usize; mean.len()];
+    if per_major {
+        offsets.windows(2).enumerate().for_each(|(i, w)| {
+            values[w[0]..w[1]].iter().for_each(|x| m2[i] += (to_f64(x) - mean[i]).powi(2));
+            stored[i] = w[1] - w[0];
+        });
+    } else {
+        indices.iter().zip(values).for_each(|(&j, x)| {
+            m2[j] += (to_f64(x) - mean[j]).powi(2);
+            stored[j] += 1;
+        });
+    }
+    m2.iter_mut().zip(mean.iter()).zip(stored).for_each(|((m2, mean), k)| {
+        *m2 += (count - k as f64) * mean * mean
+    });
+    Moments { count, mean, m2 }
+}
+
+/// Convert values to `ty`, returning a one-dimensional array. Return an error
+/// if a value cannot be represented in `ty`, e.g., a negative value converted
+/// to an unsigned type or NaN converted to an integer. Non-zero values are
+/// converted to `true`.
+pub(crate) fn cast_values<T: ToPrimitive>(values: Vec<T>, ty: ScalarType) -> Result<DynArray> {
+    macro_rules! cast {
+        ($t:ty) => {
+            values.into_iter()
+                .map(<$t as NumCast>::from)
+                .collect::<Option<Array1<$t>>>()
+                .ok_or_else(|| anyhow!("cannot convert some values to {}", ty))?
+                .into_dyn()
+                .into()
+        };
+    }
+    let arr = match ty {
+        ScalarType::I8 => cast!(i8),
+        ScalarType::I16 => cast!(i16),
+        ScalarType::I32 => cast!(i32),
+        ScalarType::I64 => cast!(i64),
+        ScalarType::U8 => cast!(u8),
+        ScalarType::U16 => cast!(u16),
+        ScalarType::U32 => cast!(u32),
+        ScalarType::U64 => cast!(u64),
+        ScalarType::Usize => cast!(usize),
+        ScalarType::F16 => cast!(f16),
+        ScalarType::F32 => cast!(f32),
+        ScalarType::F64 => cast!(f64),
+        ScalarType::Bool => values.into_iter()
+            .map(|x| x.to_f64().map(|x| x != 0.0))
+            .collect::<Option<Array1<bool>>>()
+            .ok_or_else(|| anyhow!("cannot convert some values to {}", ty))?
+            .into_dyn()
+            .into(),
+        ScalarType::String => bail!("cannot convert numbers to strings"),
+    };
+    Ok(arr)
+}
+
+/// Whether every value of type `from` can be represented exactly in `to`.
+pub(crate) fn is_lossless(from: ScalarType, to: ScalarType) -> bool {
+    // Whether the type is signed and its number of bits, for booleans and integers.
+    fn int_bits(ty: ScalarType) -> Option<(bool, usize)> {
+        match ty {
+            ScalarType::Bool => Some((false, 1)),
+            ScalarType::I8 => Some((true, 8)),
+            ScalarType::I16 => Some((true, 16)),
+            ScalarType::I32 => Some((true, 32)),
+            ScalarType::I64 => Some((true, 64)),
+            ScalarType::U8 => Some((false, 8)),
+            ScalarType::U16 => Some((false, 16)),
+            ScalarType::U32 => Some((false, 32)),
+            ScalarType::U64 => Some((false, 64)),
+            ScalarType::Usize => Some((false, 8 * std::mem::size_of::<usize>())),
+            _ => None,
+        }
+    }
+    // The number of significand bits of floating point types.
+    fn float_digits(ty: ScalarType) -> Option<usize> {
+        match ty {
+            ScalarType::F16 => Some(11),
+            ScalarType::F32 => Some(24),
+            ScalarType::F64 => Some(53),
+            _ => None,
+        }
+    }
+    if from == to {
+        return true;
+    }
+    match (int_bits(from), float_digits(from)) {
+        (Some((signed, bits)), _) => match (int_bits(to), float_digits(to)) {
+            (Some((to_signed, to_bits)), _) if signed == to_signed => to_bits >= bits,
+            (Some((to_signed, to_bits)), _) => !signed && to_signed && to_bits > bits,
+            (_, Some(digits)) => bits - signed as usize <= digits,
+            _ => false,
+        },
+        (_, Some(digits)) => float_digits(to).is_some_and(|x| x >= digits),
+        _ => false,
+    }
+}
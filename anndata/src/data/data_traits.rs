@@ -11,6 +11,17 @@ pub trait ReadData {
     fn read<B: Backend>(container: &DataContainer<B>) -> Result<Self>
     where
         Self: Sized;
+
+    /// Like [`ReadData::read`], but if `assume_sorted_indices` is true, the
+    /// column indices of csr matrices are trusted to be sorted and unique, so
+    /// that they are neither checked for order nor sorted. Other types ignore
+    /// the flag.
+    fn read_assume_sorted<B: Backend>(container: &DataContainer<B>, _assume_sorted_indices: bool) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::read(container)
+    }
 }
 
 /// Write data to a backend
@@ -22,12 +33,37 @@ pub trait WriteData {
         name: &str,
     ) -> Result<DataContainer<B>>;
     fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
-        let file = container.file()?;
-        let path = container.path();
-        let group = file.open_group(path.parent().unwrap().to_str().unwrap())?;
-        let name = path.file_name().unwrap().to_str().unwrap();
-        group.delete(name)?;
-        self.write(&group, name)
+        delete_and_write(self, container)
+    }
+}
+
+/// Replace the container by deleting it and writing the data to the same location.
+pub(crate) fn delete_and_write<B, D>(data: &D, container: DataContainer<B>) -> Result<DataContainer<B>>
+where
+    B: Backend,
+    D: WriteData + ?Sized,
+{
+    let file = container.file()?;
+    let path = container.path();
+    let group = file.open_group(path.parent().unwrap().to_str().unwrap())?;
+    let name = path.file_name().unwrap().to_str().unwrap();
+    group.delete(name)?;
+    write_locked(data, &group, name)
+}
+
+/// Write the data, holding the lock of the backend for the whole write if the
+/// data is a scalar. Other elements may take long to write and must not block
+/// the other threads, so the lock is then acquired for every call.
+pub(crate) fn write_locked<B, G, D>(data: &D, location: &G, name: &str) -> Result<DataContainer<B>>
+where
+    B: Backend,
+    G: GroupOp<Backend = B>,
+    D: WriteData + ?Sized,
+{
+    if matches!(data.data_type(), DataType::Scalar(_)) {
+        B::locked(|| data.write(location, name))
+    } else {
+        data.write(location, name)
     }
 }
 
@@ -45,6 +81,9 @@ where
     ) -> Result<DataContainer<B>> {
             (*self).write(location, name)
     }
+    fn overwrite<B: Backend>(&self, container: DataContainer<B>) -> Result<DataContainer<B>> {
+        (*self).overwrite(container)
+    }
 }
 
 /// Anything that has a shape.
@@ -90,6 +129,20 @@ pub trait ReadArrayData: ReadData {
         S: AsRef<SelectInfoElem>,
         Self: Sized;
 
+    /// Like [`ReadArrayData::read_select`], see [`ReadData::read_assume_sorted`].
+    fn read_select_assume_sorted<B, S>(
+        container: &DataContainer<B>,
+        info: &[S],
+        _assume_sorted_indices: bool,
+    ) -> Result<Self>
+    where
+        B: Backend,
+        S: AsRef<SelectInfoElem>,
+        Self: Sized,
+    {
+        Self::read_select(container, info)
+    }
+
     fn read_axis<B, S>(container: &DataContainer<B>, axis: usize, slice: S) -> Result<Self>
     where
         B: Backend,
@@ -51,17 +51,17 @@ impl Index {
         match self {
             Index::Intervals(map) => {
                 let query: SmallVec<[&str; 3]> = key.split(&['-', ':']).collect();
-                map.get_index(query[0], (query[1].parse().unwrap(), query[2].parse().unwrap()))
+                match query.as_slice() {
+                    [name, start, end] => map.get_index(name, (start.parse().ok()?, end.parse().ok()?)),
+                    _ => None,
+                }
             }
             Index::List(list) => list.get_index(key),
-            Index::Range(range) => {
-                let i: usize = key.parse().unwrap();
-                if i >= range.start && i < range.end {
-                    Some(i - range.start)
-                } else {
-                    None
-                }
-            },
+            Index::Range(range) => key
+                .parse()
+                .ok()
+                .filter(|i| range.contains(i))
+                .map(|i| i - range.start),
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::backend::{Backend, GroupOp, DataContainer, iter_containers, DataType};
+use crate::backend::{Backend, GroupOp, LocationOp, DataContainer, iter_containers, DataType};
 use crate::data::{Data, ReadData, WriteData};
 
 use std::collections::HashMap;
@@ -34,6 +34,8 @@ impl WriteData for Mapping {
     }
     fn write<B: Backend, G: GroupOp<Backend = B>>(&self, location: &G, name: &str) -> Result<DataContainer<B>> {
         let group = location.create_group(name)?;
+        group.write_str_attr("encoding-type", "dict")?;
+        group.write_str_attr("encoding-version", "0.1.0")?;
         self.0
             .iter()
             .try_for_each(|(k, v)| v.write(&group, k).map(|_| ()))?;
@@ -2,6 +2,7 @@ use crate::backend::*;
 use crate::data::data_traits::*;
 
 use anyhow::{Result, bail};
+use half::f16;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DynScalar {
@@ -14,6 +15,7 @@ pub enum DynScalar {
     U32(u32),
     U64(u64),
     Usize(usize),
+    F16(f16),
     F32(f32),
     F64(f64),
     Bool(bool),
@@ -71,6 +73,7 @@ impl_from_dynscalar!(
     u32, U32,
     u64, U64,
     usize, Usize,
+    f16, F16,
     f32, F32,
     f64, F64,
     bool, Bool,
@@ -89,6 +92,7 @@ impl WriteData for DynScalar {
             DynScalar::U32(_) => DataType::Scalar(ScalarType::U32),
             DynScalar::U64(_) => DataType::Scalar(ScalarType::U64),
             DynScalar::Usize(_) => DataType::Scalar(ScalarType::Usize),
+            DynScalar::F16(_) => DataType::Scalar(ScalarType::F16),
             DynScalar::F32(_) => DataType::Scalar(ScalarType::F32),
             DynScalar::F64(_) => DataType::Scalar(ScalarType::F64),
             DynScalar::Bool(_) => DataType::Scalar(ScalarType::Bool),
@@ -107,6 +111,7 @@ impl WriteData for DynScalar {
             DynScalar::U32(data) => data.write(location, name),
             DynScalar::U64(data) => data.write(location, name),
             DynScalar::Usize(data) => data.write(location, name),
+            DynScalar::F16(data) => data.write(location, name),
             DynScalar::F32(data) => data.write(location, name),
             DynScalar::F64(data) => data.write(location, name),
             DynScalar::Bool(data) => data.write(location, name),
@@ -128,6 +133,7 @@ impl ReadData for DynScalar {
             ScalarType::U32 => Ok(DynScalar::U32(dataset.read_scalar()?)),
             ScalarType::U64 => Ok(DynScalar::U64(dataset.read_scalar()?)),
             ScalarType::Usize => Ok(DynScalar::Usize(dataset.read_scalar()?)),
+            ScalarType::F16 => Ok(DynScalar::F16(dataset.read_scalar()?)),
             ScalarType::F32 => Ok(DynScalar::F32(dataset.read_scalar()?)),
             ScalarType::F64 => Ok(DynScalar::F64(dataset.read_scalar()?)),
             ScalarType::Bool => Ok(DynScalar::Bool(dataset.read_scalar()?)),
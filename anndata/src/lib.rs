@@ -1,14 +1,30 @@
+#[macro_use]
+mod trace;
 mod anndata;
 pub mod traits;
 pub mod backend;
 pub mod data;
 pub mod container;
 pub mod reader;
+pub mod neighbors;
+pub mod spatial;
+pub mod qc;
+pub mod prelude;
 
 pub use traits::{AnnDataOp, AxisArraysOp, ElemCollectionOp, ArrayElemOp};
-pub use crate::anndata::{AnnData, AnnDataSet, StackedAnnData};
+pub use crate::anndata::{
+    concat, AnnData, AnnDataSet, AnnDataView, AxisSelection, CreateMode, ElemSlot, Join,
+    StackedAnnData, ElemUsage, SlotUsage, UsageReport, Violation, Raw, AnnDataBuilder,
+};
 pub use backend::Backend;
-pub use data::{HasShape, Data, ReadData, WriteData, ArrayData, WriteArrayData, ReadArrayData, ArrayOp};
+pub use neighbors::Neighbors;
+pub use spatial::{Spatial, SpatialLibrary};
+pub use qc::QcMetrics;
+pub use data::{
+    HasShape, Data, ReadData, WriteData, ArrayData, WriteArrayData, ReadArrayData, ArrayOp,
+    DynArray, DynScalar, DynCsrMatrix, DynCscMatrix, SelectInfo, SelectInfoElem, Shape,
+};
+pub use nalgebra_sparse::{CsrMatrix, CscMatrix};
 pub use container::{
     AxisArrays, DataFrameElem, Elem, ElemCollection, ArrayElem, 
     StackedAxisArrays, StackedDataFrame, StackedArrayElem,
@@ -0,0 +1,87 @@
+use crate::data::{ArrayData, Data, Mapping};
+use crate::traits::{AnnDataOp, AxisArraysOp, ElemCollectionOp};
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// Nearest neighbor graphs stored following the scanpy convention.
+///
+/// The connectivity and distance matrices are stored in `obsp`, and
+/// `uns[key]` holds a mapping with the obsp keys of both matrices
+/// (`connectivities_key`, `distances_key`) and the parameters used to
+/// compute the graph (`params`). The default key is "neighbors", in which
+/// case the matrices are stored in `obsp["connectivities"]` and
+/// `obsp["distances"]`. Otherwise they are stored in `obsp["{key}_connectivities"]`
+/// and `obsp["{key}_distances"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbors {
+    pub connectivities: ArrayData,
+    pub distances: ArrayData,
+    pub params: Mapping,
+}
+
+impl Neighbors {
+    pub const DEFAULT_KEY: &'static str = "neighbors";
+
+    pub fn new(connectivities: ArrayData, distances: ArrayData) -> Self {
+        Self {
+            connectivities,
+            distances,
+            params: HashMap::new().into(),
+        }
+    }
+
+    pub fn with_params<I: IntoIterator<Item = (String, Data)>>(mut self, params: I) -> Self {
+        let mut map: HashMap<_, _> = self.params.into();
+        map.extend(params);
+        self.params = map.into();
+        self
+    }
+
+    /// Read the neighbors stored under `uns[key]`. Return `None` if `uns[key]`
+    /// does not exist.
+    pub fn read<A: AnnDataOp>(adata: &A, key: Option<&str>) -> Result<Option<Self>> {
+        let key = key.unwrap_or(Self::DEFAULT_KEY);
+        let uns: HashMap<String, Data> = match adata.uns().get_item::<Mapping>(key)? {
+            Some(x) => x.into(),
+            None => return Ok(None),
+        };
+        let get_key = |name: &str| -> Result<String> {
+            match uns.get(name) {
+                Some(x) => x.clone().try_into(),
+                None => bail!("'{}' not found in uns['{}']", name, key),
+            }
+        };
+        let read_obsp = |obsp_key: String| -> Result<ArrayData> {
+            adata.obsp().get_item(&obsp_key)?
+                .with_context(|| format!("'{}' not found in obsp", obsp_key))
+        };
+        let params = match uns.get("params") {
+            Some(x) => x.clone().try_into()?,
+            None => HashMap::new().into(),
+        };
+        Ok(Some(Self {
+            connectivities: read_obsp(get_key("connectivities_key")?)?,
+            distances: read_obsp(get_key("distances_key")?)?,
+            params,
+        }))
+    }
+
+    /// Write the neighbors to `obsp` and `uns[key]`, replacing existing ones.
+    pub fn write<A: AnnDataOp>(self, adata: &A, key: Option<&str>) -> Result<()> {
+        let key = key.unwrap_or(Self::DEFAULT_KEY);
+        let (conn_key, dist_key) = if key == Self::DEFAULT_KEY {
+            ("connectivities".to_string(), "distances".to_string())
+        } else {
+            (format!("{}_connectivities", key), format!("{}_distances", key))
+        };
+        adata.obsp().add(&conn_key, self.connectivities)?;
+        adata.obsp().add(&dist_key, self.distances)?;
+        let uns: HashMap<String, Data> = [
+            ("connectivities_key".to_string(), conn_key.into()),
+            ("distances_key".to_string(), dist_key.into()),
+            ("params".to_string(), self.params.into()),
+        ].into_iter().collect();
+        adata.uns().add(key, Mapping::from(uns))
+    }
+}
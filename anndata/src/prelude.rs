@@ -0,0 +1,27 @@
+//! The commonly used traits and types, imported with `use anndata::prelude::*`.
+//!
+//! Open a file, read a slice of `X`, and write the same selection to a new file:
+//!
+//! ```
+//! use anndata::prelude::*;
+//! use std::path::Path;
+//!
+//! fn head<B: Backend>(input: &Path, output: &Path) -> anyhow::Result<ArrayData> {
+//!     let adata = AnnData::<B>::open(B::open(input)?)?;
+//!     let selection = s![0..10, ..];
+//!     let x: ArrayData = adata.x().slice(&selection)?.expect("X is empty");
+//!     adata.write_select::<B, _, _>(&selection, output)?;
+//!     adata.close()?;
+//!     Ok(x)
+//! }
+//! ```
+
+pub use crate::backend::{Backend, DatasetOp, FileOp, GroupOp, LocationOp};
+pub use crate::data::{
+    ArrayData, ArrayOp, Data, DynArray, DynCscMatrix, DynCsrMatrix, DynScalar, HasShape,
+    ReadArrayData, ReadData, SelectInfo, SelectInfoElem, Shape, WriteArrayData, WriteData,
+};
+pub use crate::s;
+pub use crate::traits::{AnnDataOp, ArrayElemOp, AxisArraysOp, ElemCollectionOp};
+pub use crate::{AnnData, AnnDataSet};
+pub use nalgebra_sparse::{CscMatrix, CsrMatrix};
@@ -0,0 +1,57 @@
+use crate::data::ArrayData;
+use crate::traits::{AnnDataOp, ArrayElemOp};
+
+use anyhow::{ensure, Result};
+use ndarray::Array1;
+use polars::prelude::{NamedFrom, Series};
+
+/// Quality control metrics following `scanpy.pp.calculate_qc_metrics`.
+///
+/// For each observation, `total_counts` is the sum of its counts and
+/// `n_genes_by_counts` the number of variables with nonzero counts. For each
+/// variable, `n_cells_by_counts` is the number of observations with nonzero
+/// counts and `mean_counts` its mean count over all observations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QcMetrics {
+    pub total_counts: Array1<f64>,
+    pub n_genes_by_counts: Array1<i64>,
+    pub n_cells_by_counts: Array1<i64>,
+    pub mean_counts: Array1<f64>,
+}
+
+impl QcMetrics {
+    /// Compute the metrics of a two-dimensional array in a single pass over
+    /// chunks of rows. Return `None` if the element is empty.
+    pub fn compute<X: ArrayElemOp>(x: &X) -> Result<Option<Self>> {
+        let shape = match x.shape() {
+            Some(shape) => shape,
+            None => return Ok(None),
+        };
+        ensure!(shape.ndim() == 2, "QC metrics are only supported for 2D arrays, but got {:?}", shape);
+        let mut total_counts = Vec::with_capacity(shape[0]);
+        let mut n_genes_by_counts = Vec::with_capacity(shape[0]);
+        let mut sum_counts = Array1::zeros(shape[1]);
+        let mut n_cells_by_counts = Array1::zeros(shape[1]);
+        for (chunk, _, _) in x.iter::<ArrayData>(500) {
+            total_counts.extend(chunk.sum_axis(1)?);
+            n_genes_by_counts.extend(chunk.count_nonzero_axis(1)?.into_iter().map(|x| x as i64));
+            sum_counts += &chunk.sum_axis(0)?;
+            n_cells_by_counts += &chunk.count_nonzero_axis(0)?;
+        }
+        Ok(Some(Self {
+            total_counts: total_counts.into(),
+            n_genes_by_counts: n_genes_by_counts.into(),
+            n_cells_by_counts: n_cells_by_counts.mapv(|x| x as i64),
+            mean_counts: sum_counts / shape[0] as f64,
+        }))
+    }
+
+    /// Add the metrics as columns of `obs` and `var`, replacing existing
+    /// columns of the same names. The other columns are left untouched.
+    pub fn write<A: AnnDataOp>(&self, adata: &A) -> Result<()> {
+        adata.set_obs_column(Series::new("total_counts", self.total_counts.to_vec()))?;
+        adata.set_obs_column(Series::new("n_genes_by_counts", self.n_genes_by_counts.to_vec()))?;
+        adata.set_var_column(Series::new("n_cells_by_counts", self.n_cells_by_counts.to_vec()))?;
+        adata.set_var_column(Series::new("mean_counts", self.mean_counts.to_vec()))
+    }
+}
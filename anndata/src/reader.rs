@@ -1,7 +1,7 @@
 use crate::data::utils::to_csr_data;
 use crate::{data::array::DataFrameIndex, AnnDataOp, ArrayData};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use flate2::read::MultiGzDecoder;
 use itertools::Itertools;
 use nalgebra_sparse::{coo::CooMatrix, csr::CsrMatrix};
@@ -55,6 +55,7 @@ impl MMReader {
     }
 
     pub fn finish<O: AnnDataOp>(mut self, output: &O) -> Result<()> {
+        trace_span!("import_mtx");
         if self.sorted {
             let (_, cols, iter) = read_sorted_mm_body_from_bufread::<_, f64>(&mut self.reader);
             output.set_x_from_iter(
@@ -95,6 +96,36 @@ fn open_file<P: AsRef<Path>>(file: P) -> Result<Box<dyn BufRead>> {
     Ok(reader)
 }
 
+/// Extract the first `.h5ad` file in a gzip-compressed tarball, e.g., a
+/// `.tar.gz` dataset bundle, and write it to `output`. The file is extracted
+/// to a temporary file next to `output`, so that a failed extraction does not
+/// leave a partial file behind.
+pub fn extract_h5ad<P: AsRef<Path>, Q: AsRef<Path>>(tarball: P, output: Q) -> Result<()> {
+    let output = output.as_ref();
+    let mut archive = tar::Archive::new(MultiGzDecoder::new(BufReader::new(File::open(tarball)?)));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() && is_h5ad(&entry.path()?.to_string_lossy()) {
+            let dir = match output.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p,
+                _ => Path::new("."),
+            };
+            let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+            io::copy(&mut entry, &mut tmp).context("cannot extract the .h5ad file from the tarball")?;
+            tmp.persist(output)?;
+            return Ok(());
+        }
+    }
+    bail!("no .h5ad file found in the tarball")
+}
+
+/// Whether the entry is an h5ad file, ignoring the "._" metadata files
+/// created by macOS.
+fn is_h5ad(name: &str) -> bool {
+    let basename = Path::new(name).file_name().and_then(|x| x.to_str()).unwrap_or("");
+    basename.ends_with(".h5ad") && !basename.starts_with("._")
+}
+
 /*
 // TODO: fix dataframe index
 pub fn import_csv<P>(
@@ -417,3 +448,44 @@ fn parse_header(header: &str) -> Result<(SymmetryMode, DataType), IoError> {
     };
     Ok((sym_mode, data_type))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    fn tar_entry(name: &str, typeflag: u8, data: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[156] = typeflag;
+        let mut entry = header.to_vec();
+        entry.extend_from_slice(data);
+        entry.resize(entry.len() + tar_padding(data.len()), 0);
+        entry
+    }
+
+    #[test]
+    fn test_extract_h5ad() {
+        let dir = tempfile::tempdir().unwrap();
+        let long_name = format!("{}/data.h5ad", "x".repeat(120));
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar_entry("README", b'0', b"a dataset")).unwrap();
+        encoder.write_all(&tar_entry("._data.h5ad", b'0', b"metadata")).unwrap();
+        encoder.write_all(&tar_entry("././@LongLink", b'L', long_name.as_bytes())).unwrap();
+        encoder.write_all(&tar_entry(&long_name[..100], b'0', &[7; 1000])).unwrap();
+        encoder.write_all(&[0; 1024]).unwrap();
+        let tarball = dir.path().join("bundle.tar.gz");
+        std::fs::write(&tarball, encoder.finish().unwrap()).unwrap();
+
+        let output = dir.path().join("data.h5ad");
+        extract_h5ad(&tarball, &output).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), vec![7; 1000]);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar_entry("README", b'0', b"a dataset")).unwrap();
+        std::fs::write(&tarball, encoder.finish().unwrap()).unwrap();
+        assert!(extract_h5ad(&tarball, &output).is_err());
+    }
+}
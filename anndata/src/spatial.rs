@@ -0,0 +1,119 @@
+use crate::data::{ArrayData, Data, HasShape, Mapping};
+use crate::traits::{AnnDataOp, AxisArraysOp, ElemCollectionOp};
+
+use anyhow::{bail, ensure, Context, Result};
+use std::collections::HashMap;
+
+/// Spatial coordinates and images stored following the scanpy/squidpy convention.
+///
+/// The coordinates of the observations are stored in `obsm["spatial"]` as a
+/// `n_obs x 2` array. `uns["spatial"]` holds one mapping per library (e.g., a
+/// Visium slide), each containing the tissue images (`images`, typically
+/// "hires" and "lowres") and the scale factors mapping pixel coordinates to
+/// the images (`scalefactors`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spatial {
+    pub coordinates: ArrayData,
+    pub libraries: HashMap<String, SpatialLibrary>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpatialLibrary {
+    pub images: HashMap<String, ArrayData>,
+    pub scalefactors: Mapping,
+}
+
+impl TryFrom<Mapping> for SpatialLibrary {
+    type Error = anyhow::Error;
+
+    fn try_from(mapping: Mapping) -> Result<Self> {
+        let mut mapping: HashMap<_, _> = mapping.into();
+        let images = match mapping.remove("images") {
+            Some(Data::Mapping(images)) => {
+                let images: HashMap<_, _> = images.into();
+                images.into_iter().map(|(k, v)| match v {
+                    Data::ArrayData(x) => Ok((k, x)),
+                    _ => bail!("image '{}' is not an array", k),
+                }).collect::<Result<_>>()?
+            }
+            Some(_) => bail!("'images' is not a mapping"),
+            None => HashMap::new(),
+        };
+        let scalefactors = match mapping.remove("scalefactors") {
+            Some(x) => x.try_into()?,
+            None => HashMap::new().into(),
+        };
+        Ok(Self { images, scalefactors })
+    }
+}
+
+impl From<SpatialLibrary> for Mapping {
+    fn from(library: SpatialLibrary) -> Self {
+        let images: HashMap<_, _> = library.images.into_iter().map(|(k, v)| (k, v.into())).collect();
+        let mapping: HashMap<String, Data> = [
+            ("images".to_string(), Mapping::from(images).into()),
+            ("scalefactors".to_string(), library.scalefactors.into()),
+        ].into_iter().collect();
+        mapping.into()
+    }
+}
+
+impl Spatial {
+    pub const KEY: &'static str = "spatial";
+
+    pub fn new(coordinates: ArrayData) -> Self {
+        Self {
+            coordinates,
+            libraries: HashMap::new(),
+        }
+    }
+
+    pub fn with_library(mut self, library_id: &str, library: SpatialLibrary) -> Self {
+        self.libraries.insert(library_id.to_string(), library);
+        self
+    }
+
+    /// Read the spatial coordinates in `obsm["spatial"]` and the libraries in
+    /// `uns["spatial"]`. Return `None` if `obsm["spatial"]` does not exist.
+    pub fn read<A: AnnDataOp>(adata: &A) -> Result<Option<Self>> {
+        let coordinates = match adata.obsm().get_item::<ArrayData>(Self::KEY)? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        Self::check_coordinates(&coordinates, adata.n_obs())?;
+        let libraries = match adata.uns().get_item::<Mapping>(Self::KEY)? {
+            Some(uns) => {
+                let uns: HashMap<_, _> = uns.into();
+                uns.into_iter().map(|(k, v)| {
+                    let library = Mapping::try_from(v)?.try_into()
+                        .with_context(|| format!("invalid library '{}' in uns['spatial']", k))?;
+                    Ok((k, library))
+                }).collect::<Result<_>>()?
+            }
+            None => HashMap::new(),
+        };
+        Ok(Some(Self { coordinates, libraries }))
+    }
+
+    /// Write the spatial data to `obsm["spatial"]` and `uns["spatial"]`,
+    /// replacing existing ones.
+    pub fn write<A: AnnDataOp>(self, adata: &A) -> Result<()> {
+        Self::check_coordinates(&self.coordinates, adata.n_obs())?;
+        adata.obsm().add(Self::KEY, self.coordinates)?;
+        let uns: HashMap<_, _> = self.libraries.into_iter()
+            .map(|(k, v)| (k, Mapping::from(v).into()))
+            .collect();
+        adata.uns().add(Self::KEY, Mapping::from(uns))
+    }
+
+    fn check_coordinates(coordinates: &ArrayData, n_obs: usize) -> Result<()> {
+        let shape = coordinates.shape();
+        ensure!(
+            shape.ndim() == 2 && shape[1] == 2 && (n_obs == 0 || shape[0] == n_obs),
+            "spatial coordinates must be a {} x 2 array, but receive a {:?} array",
+            n_obs,
+            shape,
+        );
+        Ok(())
+    }
+}
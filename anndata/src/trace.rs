@@ -0,0 +1,30 @@
+//! Tracing of I/O and long-running operations, compiled in only with the
+//! `trace` feature. Events and spans are emitted through `tracing` at the
+//! debug level, e.g., they are shown by `tracing_subscriber` with
+//! `RUST_LOG=anndata=debug`, and the duration of a span is reported when it is
+//! closed. Without a subscriber, they are forwarded to the `log` facade. The
+//! fields are not evaluated unless the feature is enabled and the subscriber
+//! accepts debug events of the module.
+
+/// Emit an event at the debug level, see [`tracing::debug!`].
+macro_rules! trace_event {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "trace")]
+        tracing::debug!($($arg)+);
+    }};
+}
+
+/// Enter a span at the debug level until the end of the enclosing scope, see
+/// [`tracing::debug_span!`].
+macro_rules! trace_span {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::debug_span!($($arg)+).entered();
+    };
+}
+
+/// The uncompressed size of the data in the container.
+#[cfg(feature = "trace")]
+pub(crate) fn nbytes<B: crate::backend::Backend>(container: &crate::backend::DataContainer<B>) -> u64 {
+    crate::anndata::data_sizes(container).map_or(0, |x| x.1)
+}
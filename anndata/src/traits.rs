@@ -1,7 +1,13 @@
+use crate::backend::DataType;
 use crate::data::*;
-
-use anyhow::Result;
-use polars::prelude::DataFrame;
+use crate::data::utils::Moments;
+use crate::neighbors::Neighbors;
+use crate::qc::QcMetrics;
+use crate::spatial::Spatial;
+
+use anyhow::{bail, ensure, Result};
+use ndarray::Array1;
+use polars::prelude::{DataFrame, Series};
 use smallvec::SmallVec;
 
 /// AnnData container operations.
@@ -51,6 +57,22 @@ pub trait AnnDataOp {
     /// Change the variable annotations.
     fn set_var(&self, var: DataFrame) -> Result<()>;
 
+    /// Add or replace a column of the observation annotations. The default
+    /// implementation rewrites the whole dataframe.
+    fn set_obs_column(&self, column: Series) -> Result<()> {
+        let mut obs = self.read_obs()?;
+        obs.with_column(column)?;
+        self.set_obs(obs)
+    }
+
+    /// Add or replace a column of the variable annotations, see
+    /// [`AnnDataOp::set_obs_column`].
+    fn set_var_column(&self, column: Series) -> Result<()> {
+        let mut var = self.read_var()?;
+        var.with_column(column)?;
+        self.set_var(var)
+    }
+
     /// Delete the observation annotations.
     fn del_obs(&self) -> Result<()>;
 
@@ -95,6 +117,52 @@ pub trait AnnDataOp {
         data.try_for_each(|(k, v)| layers.add(&k, v))
     }
 
+    /// Return the nearest neighbor graphs stored under `uns[key]`, see [`Neighbors`].
+    fn neighbors(&self, key: Option<&str>) -> Result<Option<Neighbors>> where Self: Sized {
+        Neighbors::read(self, key)
+    }
+
+    /// Store the nearest neighbor graphs in `obsp` and `uns[key]`, see [`Neighbors`].
+    fn set_neighbors(&self, key: Option<&str>, neighbors: Neighbors) -> Result<()> where Self: Sized {
+        neighbors.write(self, key)
+    }
+
+    /// Return the spatial coordinates and images, see [`Spatial`].
+    fn spatial(&self) -> Result<Option<Spatial>> where Self: Sized {
+        Spatial::read(self)
+    }
+
+    /// Store the spatial coordinates in `obsm` and images in `uns`, see [`Spatial`].
+    fn set_spatial(&self, spatial: Spatial) -> Result<()> where Self: Sized {
+        spatial.write(self)
+    }
+
+    /// Compute the QC metrics of `X` and add them as columns of `obs` and
+    /// `var`, see [`QcMetrics`]. Return `None` if `X` is empty.
+    fn calculate_qc_metrics(&self) -> Result<Option<QcMetrics>> where Self: Sized {
+        let metrics = QcMetrics::compute(&self.x())?;
+        if let Some(metrics) = &metrics {
+            metrics.write(self)?;
+        }
+        Ok(metrics)
+    }
+
+    /// Return the embedding of `basis` stored in `obsm`. As in scanpy, `basis`
+    /// is either a key of `obsm`, or a basis name such that `obsm["X_<basis>"]`
+    /// exists, e.g. "umap" for "X_umap".
+    fn embedding(&self, basis: &str) -> Result<Option<ArrayData>> {
+        let obsm = self.obsm();
+        let keys = obsm.keys();
+        let prefixed = format!("X_{}", basis);
+        if keys.iter().any(|k| k == basis) {
+            obsm.get_item(basis)
+        } else if keys.contains(&prefixed) {
+            obsm.get_item(&prefixed)
+        } else {
+            Ok(None)
+        }
+    }
+
     fn del_uns(&self) -> Result<()>;
     fn del_obsm(&self) -> Result<()>;
     fn del_obsp(&self) -> Result<()>;
@@ -117,7 +185,35 @@ pub trait ElemCollectionOp {
         data: D,
     ) -> Result<()>;
 
+    /// Append a value to the 1-dimensional array stored under `key`, e.g., a
+    /// log of processing steps, or create the array if `key` does not exist.
+    /// Backed collections grow the array in place.
+    fn append_to_list<D: Into<DynScalar>>(&self, key: &str, value: D) -> Result<()> {
+        let value = ArrayData::from(DynArray::from(value.into()));
+        let data = match self.get_item::<Data>(key)? {
+            Some(Data::ArrayData(data)) => {
+                let is_list = matches!(data.data_type(), DataType::Array(_)) && data.shape().ndim() == 1;
+                ensure!(is_list, "cannot append to '{}', which is not a 1-dimensional array", key);
+                ensure!(
+                    data.data_type() == value.data_type(),
+                    "cannot append {} to {}",
+                    value.data_type(),
+                    data.data_type(),
+                );
+                ArrayData::vstack([data, value].into_iter())?
+            }
+            Some(_) => bail!("cannot append to '{}', which is not a 1-dimensional array", key),
+            None => value,
+        };
+        self.add(key, data)
+    }
+
     fn remove(&self, key: &str) -> Result<()>;
+
+    /// Remove multiple items by key.
+    fn remove_many<'a, I: IntoIterator<Item = &'a str>>(&self, keys: I) -> Result<()> {
+        keys.into_iter().try_for_each(|k| self.remove(k))
+    }
 }
 
 /// A trait for accessing arrays with multiple axes.
@@ -130,6 +226,12 @@ pub trait AxisArraysOp {
     /// Return the ArrayElem object by key, but do not read the data.
     fn get(&self, key: &str) -> Option<Self::ArrayElem>;
 
+    /// Return the keys of embeddings, i.e., keys following the `X_<basis>`
+    /// naming convention, e.g. "X_umap" or "X_pca".
+    fn embeddings(&self) -> Vec<String> {
+        self.keys().into_iter().filter(|k| k.starts_with("X_")).collect()
+    }
+
     /// Return the array data by key.
     fn get_item<D>(&self, key: &str) -> Result<Option<D>>
     where
@@ -172,6 +274,12 @@ pub trait AxisArraysOp {
 
     /// Remove data by key.
     fn remove(&self, key: &str) -> Result<()>;
+
+    /// Remove data by multiple keys. The space of the removed data can be
+    /// reclaimed with [`crate::AnnData::repack`].
+    fn remove_many<'a, I: IntoIterator<Item = &'a str>>(&self, keys: I) -> Result<()> {
+        keys.into_iter().try_for_each(|k| self.remove(k))
+    }
 }
 
 pub trait ArrayElemOp {
@@ -220,4 +328,103 @@ pub trait ArrayElemOp {
     where
         T: Into<ArrayData> + TryFrom<ArrayData> + ReadArrayData + Clone,
         <T as TryFrom<ArrayData>>::Error: Into<anyhow::Error>;
-}
\ No newline at end of file
+
+    /// Sum a two-dimensional array along `axis`, see [`ArrayData::sum_axis`].
+    /// The data is read in chunks of rows, so it is never loaded into memory
+    /// at once. Return `None` if the element is empty.
+    fn sum(&self, axis: usize) -> Result<Option<Array1<f64>>> {
+        let shape = match self.shape() {
+            Some(shape) => shape,
+            None => return Ok(None),
+        };
+        ensure!(shape.ndim() == 2, "sum is only supported for 2D arrays, but got {:?}", shape);
+        ensure!(axis < 2, "axis must be 0 or 1, but got {}", axis);
+        let mut chunks = self.iter::<ArrayData>(500).map(|(chunk, _, _)| chunk.sum_axis(axis));
+        let sum = if axis == 0 {
+            chunks.try_fold(Array1::zeros(shape[1]), |acc, x| x.map(|x| acc + x))?
+        } else {
+            let sums = chunks.collect::<Result<Vec<_>>>()?;
+            sums.into_iter().flatten().collect()
+        };
+        Ok(Some(sum))
+    }
+
+    /// Compute the variance of a two-dimensional array along `axis`, see
+    /// [`ArrayData::var_axis`]. Like [`Self::sum`], the data is read in chunks
+    /// of rows, and the moments of the chunks are merged in a single pass.
+    fn var(&self, axis: usize, ddof: usize) -> Result<Option<Array1<f64>>> {
+        let shape = match self.shape() {
+            Some(shape) => shape,
+            None => return Ok(None),
+        };
+        ensure!(shape.ndim() == 2, "variance is only supported for 2D arrays, but got {:?}", shape);
+        ensure!(axis < 2, "axis must be 0 or 1, but got {}", axis);
+        let var = if axis == 0 {
+            let mut moments = Moments::empty(shape[1]);
+            for (chunk, _, _) in self.iter::<ArrayData>(500) {
+                moments.merge(chunk.moments_axis(0)?);
+            }
+            moments.var(ddof)
+        } else {
+            let vars = self.iter::<ArrayData>(500)
+                .map(|(chunk, _, _)| chunk.var_axis(1, ddof))
+                .collect::<Result<Vec<_>>>()?;
+            vars.into_iter().flatten().collect()
+        };
+        Ok(Some(var))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_sparse::CsrMatrix;
+    use ndarray::Array2;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    /// An in-memory collection that relies on the default `append_to_list`.
+    #[derive(Default)]
+    struct Collection(RefCell<HashMap<String, Data>>);
+
+    impl ElemCollectionOp for Collection {
+        fn keys(&self) -> Vec<String> {
+            self.0.borrow().keys().cloned().collect()
+        }
+
+        fn get_item<D>(&self, key: &str) -> Result<Option<D>>
+        where
+            D: ReadData + Into<Data> + TryFrom<Data> + Clone,
+            <D as TryFrom<Data>>::Error: Into<anyhow::Error>,
+        {
+            self.0.borrow().get(key).cloned().map(|x| D::try_from(x).map_err(Into::into)).transpose()
+        }
+
+        fn add<D: WriteData + Into<Data>>(&self, key: &str, data: D) -> Result<()> {
+            self.0.borrow_mut().insert(key.to_string(), data.into());
+            Ok(())
+        }
+
+        fn remove(&self, key: &str) -> Result<()> {
+            self.0.borrow_mut().remove(key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_append_to_list() {
+        let uns = Collection::default();
+        for i in 0..3 {
+            uns.append_to_list("log", format!("step {}", i)).unwrap();
+        }
+        let log: Array1<String> = uns.get_item("log").unwrap().unwrap();
+        assert_eq!(log.to_vec(), vec!["step 0", "step 1", "step 2"]);
+        assert!(uns.append_to_list("log", 1.0).is_err());
+
+        uns.add("scalar", 1.0).unwrap();
+        assert!(uns.append_to_list("scalar", 2.0).is_err());
+        uns.add("matrix", Array2::<f64>::zeros((2, 2))).unwrap();
+        assert!(uns.append_to_list("matrix", 2.0).is_err());
+        uns.add("sparse", CsrMatrix::<f64>::zeros(2, 2)).unwrap();
+        assert!(uns.append_to_list("sparse", 2.0).is_err());
+    }
+}
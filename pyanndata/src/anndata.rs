@@ -1,17 +1,19 @@
 mod backed;
 pub mod memory;
 mod dataset;
+mod view;
 
 pub use backed::AnnData;
 pub use memory::PyAnnData;
 pub use dataset::AnnDataSet;
+pub use view::AnnDataView;
 
 use anndata;
 use anndata::Backend;
 use anndata_hdf5::H5;
 use pyo3::prelude::*;
 use std::{path::PathBuf, collections::HashMap};
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 /// Read `.h5ad`-formatted hdf5 file.
 ///
@@ -20,20 +22,67 @@ use anyhow::Result;
 ///
 /// filename: Path
 ///     File name of data file.
-/// backed: Literal['r', 'r+'] | None
+/// backed: Literal['r', 'r+', 'swmr', 'swmr+'] | None
 ///     Default is `r+`.
 ///     If `'r'`, the file is opened in read-only mode.
 ///     If `'r+'`, the file is opened in read/write mode.
+///     If `'swmr+'`, the file is opened as the writer in single-writer-multiple-reader
+///     (SWMR) mode. The writer can modify existing arrays in place, but cannot
+///     add new elements.
+///     If `'swmr'`, the file is opened as a reader in SWMR mode, which sees the
+///     changes flushed by the writer after being reopened.
+///     Only files created with `AnnData(..., swmr=True)` can be opened in SWMR mode.
 ///     If `None`, the AnnData object is read into memory.
 /// backend: Literal['hdf5'] | None
+/// coerce_bool_columns: bool
+///     If True, integer columns of obs and var containing only 0 and 1 are
+///     read as boolean columns. Columns with a `dtype` attribute of "bool" are
+///     always read as boolean. Only supported in backed mode.
+/// nan_as_null: bool
+///     If True, NaN values of float columns of obs and var, the conventional
+///     encoding of missing values, are read as nulls. Otherwise they are kept
+///     as NaN. Only supported in backed mode.
+/// assume_sorted_indices: bool
+///     If True, the column indices of CSR matrices are trusted to be sorted
+///     within each row, which skips sorting them and checking their order when
+///     reading. Only use it for files written by tools that sort the indices,
+///     e.g., this library or scipy after `sort_indices()`, as unsorted indices
+///     then give wrong results. Only supported in backed mode.
+/// chunk_cache_bytes: int | None
+///     Size in bytes of the cache of decompressed chunks kept for each dataset.
+///     HDF5 keeps 1 MiB by default; a larger cache speeds up repeated reads of
+///     nearby rows of chunked, compressed matrices. Only supported in the
+///     `'r'` and `'r+'` modes.
 #[pyfunction]
 #[pyo3(
-    signature = (filename, backed="r+", backend=None),
-    text_signature = "(filename, backed='r+', backend=None)",
+    signature = (filename, backed="r+", backend=None, coerce_bool_columns=false, nan_as_null=false, assume_sorted_indices=false, chunk_cache_bytes=None),
+    text_signature = "(filename, backed='r+', backend=None, coerce_bool_columns=False, nan_as_null=False, assume_sorted_indices=False, chunk_cache_bytes=None)",
 )]
-pub fn read<'py>(py: Python<'py>, filename: PathBuf, backed: Option<&str>, backend: Option<&str>) -> Result<PyObject> {
+pub fn read<'py>(
+    py: Python<'py>,
+    filename: PathBuf,
+    backed: Option<&str>,
+    backend: Option<&str>,
+    coerce_bool_columns: bool,
+    nan_as_null: bool,
+    assume_sorted_indices: bool,
+    chunk_cache_bytes: Option<usize>,
+) -> Result<PyObject> {
     let adata = match backed {
-        Some(m) => AnnData::new_from(filename, m, backend).unwrap().into_py(py),
+        Some(m) => {
+            let adata = match chunk_cache_bytes {
+                Some(n) => AnnData::new_with_chunk_cache(filename, m, backend, n)?,
+                None => AnnData::new_from(filename, m, backend).unwrap(),
+            };
+            adata.set_coerce_bool_columns(coerce_bool_columns)?;
+            adata.set_nan_as_null(nan_as_null)?;
+            adata.set_assume_sorted_indices(assume_sorted_indices)?;
+            adata.into_py(py)
+        }
+        None if coerce_bool_columns => bail!("coerce_bool_columns is only supported in backed mode"),
+        None if nan_as_null => bail!("nan_as_null is only supported in backed mode"),
+        None if assume_sorted_indices => bail!("assume_sorted_indices is only supported in backed mode"),
+        None if chunk_cache_bytes.is_some() => bail!("chunk_cache_bytes is only supported in backed mode"),
         None => PyModule::import_bound(py, "anndata")?
             .getattr("read_h5ad")?
             .call1((filename,))?
@@ -42,6 +91,40 @@ pub fn read<'py>(py: Python<'py>, filename: PathBuf, backed: Option<&str>, backe
     Ok(adata)
 }
 
+/// Read an `.h5ad` file bundled in a gzip-compressed tarball.
+///
+/// The first `.h5ad` file in the tarball is extracted to a temporary location
+/// and opened in read-only mode. The extracted file is deleted when the
+/// AnnData object is closed.
+///
+/// Parameters
+/// ----------
+///
+/// filename: Path
+///     File name of the `.tar.gz` bundle.
+/// backend: Literal['hdf5'] | None
+///
+/// Returns
+/// -------
+/// AnnData
+#[pyfunction]
+#[pyo3(
+    signature = (filename, backend=None),
+    text_signature = "(filename, backend=None)",
+)]
+pub fn read_h5ad_bundle(filename: PathBuf, backend: Option<&str>) -> Result<AnnData> {
+    // The guard removes the extracted file unless it is successfully opened.
+    let output = tempfile::Builder::new()
+        .prefix("anndata_")
+        .suffix(".h5ad")
+        .tempfile()?
+        .into_temp_path();
+    anndata::reader::extract_h5ad(filename, &output)?;
+    let adata = AnnData::open_temporary(output.to_path_buf(), "r", backend)?;
+    output.keep()?;
+    Ok(adata)
+}
+
 /// Read Matrix Market file.
 ///
 /// Parameters
@@ -156,4 +239,24 @@ pub fn read_dataset(
 pub enum LocationUpdate {
     Map(HashMap<String, PathBuf>),
     Dir(PathBuf),
-}
\ No newline at end of file
+}
+/// Set the threshold below which string columns of dataframes are written as
+/// categorical arrays.
+///
+/// A string column is written as a categorical array if the number of its
+/// unique values divided by its length is below the threshold. Categorical
+/// columns are always written as categorical arrays.
+///
+/// Parameters
+/// ----------
+///
+/// threshold: float | None
+///     Default is `0.5`. If `None`, string columns are always written as string arrays.
+#[pyfunction]
+#[pyo3(
+    signature = (threshold),
+    text_signature = "(threshold)",
+)]
+pub fn set_categorical_threshold(threshold: Option<f64>) {
+    anndata::data::set_categorical_threshold(threshold)
+}
@@ -1,19 +1,26 @@
-use crate::container::{PyArrayElem, PyAxisArrays, PyDataFrameElem, PyElemCollection, PyChunkedArray};
-use crate::data::{isinstance_of_pandas, to_select_elem, PyArrayData, PyData};
+use crate::container::{MAX_DENSE_SIZE, PyArrayElem, PyAxisArrays, PyDataFrameElem, PyElemCollection, PyChunkedArray};
+use crate::data::{isinstance_of_csc, isinstance_of_csr, isinstance_of_pandas, to_select_elem, PyArrayData, PyArrayDict, PyData, PyIndex};
 use crate::anndata::PyAnnData;
+use super::dataset::{AnnDataFile, AnnDataSet};
+use super::view::AnnDataView;
 
-use anndata::{self, ArrayElemOp, ArrayOp, AxisArraysOp, Data, ElemCollectionOp};
+use anndata::{self, ArrayElemOp, AxisArraysOp, Data, ElemCollectionOp};
 use anndata::container::Slot;
-use anndata::data::{DataFrameIndex, SelectInfoElem, BoundedSelectInfoElem};
-use anndata::{AnnDataOp, ArrayData, Backend};
-use anndata_hdf5::H5;
-use anyhow::{bail, Result};
+use anndata::data::{DataFrameIndex, Mapping, SelectInfoElem, BoundedSelectInfoElem};
+use anndata::{AnnDataOp, ArrayData, AxisSelection, Backend, CreateMode, ElemSlot, Neighbors, Spatial, SpatialLibrary, UsageReport};
+use anndata_hdf5::{ChunkCache, H5};
+use anyhow::{bail, ensure, Context, Result};
 use downcast_rs::{impl_downcast, Downcast};
+use parking_lot::Mutex;
 use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+use polars::prelude::{DataFrame, NamedFrom, Series};
 use pyo3_polars::PyDataFrame;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /** An annotated data matrix.
 
@@ -43,6 +50,13 @@ use std::ops::Deref;
         If passing a :class:`~numpy.ndarray`, it needs to have a structured datatype.
     filename
         Name of backing file.
+    mode
+        How to handle an existing `filename`: "w" truncates it, "w-" or "x"
+        raises an error, and "a" opens it for reading and writing.
+    swmr
+        If True, the file is created in the latest HDF5 format, which is
+        required to open it later with `backed="swmr"` or `backed="swmr+"`.
+        Such files cannot be read by HDF5 versions before 1.10.
 
     Note
     ----
@@ -59,7 +73,7 @@ use std::ops::Deref;
 */
 #[pyclass]
 #[repr(transparent)]
-pub struct AnnData(Box<dyn AnnDataTrait>);
+pub struct AnnData(pub(super) Box<dyn AnnDataTrait>);
 
 impl Clone for AnnData {
     fn clone(&self) -> Self {
@@ -83,6 +97,8 @@ impl AnnData {
                 let file = match mode {
                     "r" => H5::open(filename)?,
                     "r+" => H5::open_rw(filename)?,
+                    "swmr" => H5::open_swmr(filename, false)?,
+                    "swmr+" => H5::open_swmr(filename, true)?,
                     _ => bail!("Unknown mode: {}", mode),
                 };
                 anndata::AnnData::<H5>::open(file).map(|adata| adata.into())
@@ -91,6 +107,63 @@ impl AnnData {
         }
     }
 
+    /// Open a file with a chunk cache of `chunk_cache_bytes` bytes per dataset.
+    pub(crate) fn new_with_chunk_cache(
+        filename: PathBuf,
+        mode: &str,
+        backend: Option<&str>,
+        chunk_cache_bytes: usize,
+    ) -> Result<Self> {
+        match backend.unwrap_or(H5::NAME) {
+            H5::NAME => {
+                let cache = ChunkCache::with_bytes(chunk_cache_bytes);
+                let file = match mode {
+                    "r" => H5::open_with_chunk_cache(filename, false, cache)?,
+                    "r+" => H5::open_with_chunk_cache(filename, true, cache)?,
+                    _ => bail!("chunk_cache_bytes is not supported in mode: {}", mode),
+                };
+                anndata::AnnData::<H5>::open(file).map(|adata| adata.into())
+            }
+            x => bail!("Unknown backend: {}", x),
+        }
+    }
+
+    /// Read integer columns of obs and var containing only 0 and 1 as booleans.
+    pub(crate) fn set_coerce_bool_columns(&self, coerce: bool) -> Result<()> {
+        self.0.set_coerce_bool_columns(coerce)
+    }
+
+    /// Read NaN values of float columns of obs and var as nulls.
+    pub(crate) fn set_nan_as_null(&self, nan_as_null: bool) -> Result<()> {
+        self.0.set_nan_as_null(nan_as_null)
+    }
+
+    /// Trust the column indices of CSR matrices to be sorted when reading.
+    pub(crate) fn set_assume_sorted_indices(&self, assume_sorted_indices: bool) -> Result<()> {
+        self.0.set_assume_sorted_indices(assume_sorted_indices)
+    }
+
+    /// Open a temporary file in mode "r" or "r+". The file is deleted when the
+    /// object is closed or dropped, unless it is saved with `save_as`.
+    pub(crate) fn open_temporary(filename: PathBuf, mode: &str, backend: Option<&str>) -> Result<Self> {
+        match backend.unwrap_or(H5::NAME) {
+            H5::NAME => {
+                let file = match mode {
+                    "r" => H5::open(&filename)?,
+                    "r+" => H5::open_rw(&filename)?,
+                    _ => bail!("temporary files cannot be opened in mode: {}", mode),
+                };
+                let inner = InnerAnnData {
+                    adata: Slot::new(anndata::AnnData::<H5>::open(file)?),
+                    filename: Arc::new(Mutex::new(filename)),
+                    temporary: Arc::new(AtomicBool::new(true)),
+                };
+                Ok(AnnData(Box::new(inner)))
+            }
+            x => bail!("Unknown backend: {}", x),
+        }
+    }
+
     fn select_obs(&self, ix: &Bound<'_, PyAny>) -> PyResult<SelectInfoElem> {
         let from_iter = ix.iter().and_then(|iter| 
             iter.map(|x| x.unwrap().extract::<String>()).collect::<PyResult<Vec<_>>>()
@@ -131,8 +204,9 @@ impl AnnData {
 impl<B: Backend> From<anndata::AnnData<B>> for AnnData {
     fn from(adata: anndata::AnnData<B>) -> Self {
         let inner = InnerAnnData {
-            filename: adata.filename(),
+            filename: Arc::new(Mutex::new(adata.filename())),
             adata: Slot::new(adata),
+            temporary: Arc::new(AtomicBool::new(false)),
         };
         AnnData(Box::new(inner))
     }
@@ -141,19 +215,23 @@ impl<B: Backend> From<anndata::AnnData<B>> for AnnData {
 #[pymethods]
 impl AnnData {
     #[new]
-    #[pyo3(signature = (*, filename, X=None, obs=None, var=None, obsm=None, varm=None, uns=None, backend=None))]
+    #[pyo3(signature = (*, filename, X=None, obs=None, var=None, obsm=None, varm=None, uns=None, mode="w-", backend=None, swmr=false))]
     pub fn new(
         filename: PathBuf,
         X: Option<PyArrayData>,
         obs: Option<Bound<'_, PyAny>>,
         var: Option<Bound<'_, PyAny>>,
-        obsm: Option<HashMap<String, PyArrayData>>,
-        varm: Option<HashMap<String, PyArrayData>>,
+        obsm: Option<PyArrayDict>,
+        varm: Option<PyArrayDict>,
         uns: Option<HashMap<String, PyData>>,
+        mode: &str,
         backend: Option<&str>,
+        swmr: bool,
     ) -> Result<Self> {
+        let mode: CreateMode = mode.parse()?;
         let adata: AnnData = match backend.unwrap_or(H5::NAME) {
-            H5::NAME => anndata::AnnData::<H5>::new(filename)?.into(),
+            H5::NAME if swmr => anndata::AnnData::<H5>::new_with_create(filename, mode, |p| H5::create_swmr(p))?.into(),
+            H5::NAME => anndata::AnnData::<H5>::new_with_mode(filename, mode)?.into(),
             backend => bail!("Unknown backend: {}", backend),
         };
 
@@ -178,6 +256,134 @@ impl AnnData {
         Ok(adata)
     }
 
+    /// Create a backed AnnData object from a Python `anndata.AnnData` object.
+    ///
+    /// `X`, `obs`, `var`, `obsm`, `obsp`, `varm`, `varp`, `layers` and `uns`
+    /// are written to `filename`.
+    ///
+    /// Parameters
+    /// ----------
+    /// adata: anndata.AnnData
+    ///     The in-memory AnnData object.
+    /// filename: Path
+    ///     File name of the output file.
+    /// backend: Literal['hdf5'] | None
+    ///
+    /// Returns
+    /// -------
+    /// AnnData
+    #[staticmethod]
+    #[pyo3(
+        signature = (adata, filename, backend=None),
+        text_signature = "(adata, filename, backend=None)",
+    )]
+    pub fn from_anndata(adata: PyAnnData<'_>, filename: PathBuf, backend: Option<&str>) -> Result<Self> {
+        match backend.unwrap_or(H5::NAME) {
+            H5::NAME => Ok(adata.to_anndata::<H5, _>(filename)?.into()),
+            backend => bail!("Unknown backend: {}", backend),
+        }
+    }
+
+    /// Concatenate AnnData objects along the observations into a new file.
+    ///
+    /// Variables are matched by name. With an outer join, the entries of `X`
+    /// for variables absent from an object are set to `fill_value`. A sparse
+    /// `X` stays sparse if `fill_value` is 0, as the missing entries are
+    /// structural zeros; other fill values convert it to a dense array and a
+    /// warning is logged. The obs columns present in all objects are kept.
+    ///
+    /// Parameters
+    /// ----------
+    /// adatas: list[(str, Path)] | list[(str, AnnData)]
+    ///     List of key and file name (or backed AnnData object) pairs. The
+    ///     objects are left open.
+    /// filename: Path
+    ///     File name of the output file.
+    /// join: Literal['inner', 'outer']
+    ///     Keep the variables present in all objects, or in any object.
+    /// fill_value: float
+    ///     The value of missing entries in an outer join. Default: 0.
+    /// add_key: str
+    ///     The column name in obs to store the keys.
+    /// backend: Literal['hdf5'] | None
+    ///
+    /// Returns
+    /// -------
+    /// AnnData
+    #[staticmethod]
+    #[pyo3(
+        signature = (adatas, *, filename, join="inner", fill_value=0.0, add_key="sample", backend=None),
+        text_signature = "(adatas, *, filename, join='inner', fill_value=0.0, add_key='sample', backend=None)",
+    )]
+    pub fn concat(
+        adatas: Vec<(String, AnnDataFile)>,
+        filename: PathBuf,
+        join: &str,
+        fill_value: f64,
+        add_key: &str,
+        backend: Option<&str>,
+    ) -> Result<Self> {
+        let join = join.parse()?;
+        match backend.unwrap_or(H5::NAME) {
+            H5::NAME => {
+                // Backed objects are read through their own handles, so that
+                // they stay usable; files are opened read-only.
+                let opened = adatas.iter().map(|(key, data_file)| match data_file {
+                    AnnDataFile::Data(data) => {
+                        let data = data.borrow();
+                        ensure!(!data.is_closed(), "AnnData '{}' is closed", key);
+                        Ok((key.as_str(), Some(data), None))
+                    }
+                    AnnDataFile::Path(path) => {
+                        Ok((key.as_str(), None, Some(anndata::AnnData::<H5>::open(H5::open(path)?)?)))
+                    }
+                }).collect::<Result<Vec<_>>>()?;
+                let guards: Vec<_> = opened.iter().map(|(_, data, _)| data.as_ref().map(|x| x.inner_ref::<H5>())).collect();
+                let members = opened.iter().zip(&guards).map(|((key, _, file), guard)| match guard {
+                    Some(guard) => (*key, guard.deref()),
+                    None => (*key, file.as_ref().unwrap()),
+                });
+                Ok(anndata::concat::<H5, _, _, _, _>(members, filename, add_key, join, fill_value)?.into())
+            }
+            x => bail!("Unsupported backend: {}", x),
+        }
+    }
+
+    /// Concatenate AnnData objects lazily along the observations.
+    ///
+    /// This is the lazy counterpart of writing a combined file: no data is
+    /// copied, and the returned `AnnDataSet` reads from the component files on
+    /// demand. Its annotation is kept in a temporary file that is removed when
+    /// the dataset is closed, so the result is meant for read-only iteration.
+    ///
+    /// Parameters
+    /// ----------
+    /// adatas: list[(str, Path)] | list[(str, AnnData)]
+    ///     List of key and file name (or backed AnnData object) pairs.
+    /// add_key: str
+    ///     The column name in obs to store the keys.
+    /// backend: Literal['hdf5'] | None
+    ///
+    /// Returns
+    /// -------
+    /// AnnDataSet
+    ///
+    /// See Also
+    /// --------
+    /// AnnDataSet
+    #[staticmethod]
+    #[pyo3(
+        signature = (adatas, *, add_key="sample", backend=None),
+        text_signature = "(adatas, *, add_key='sample', backend=None)",
+    )]
+    pub fn concat_lazy(
+        adatas: Vec<(String, AnnDataFile)>,
+        add_key: &str,
+        backend: Option<&str>,
+    ) -> Result<AnnDataSet> {
+        AnnDataSet::concat_lazy(adatas, add_key, backend)
+    }
+
     /// Shape of data matrix (`n_obs`, `n_vars`).
     ///
     /// Returns
@@ -190,6 +396,9 @@ impl AnnData {
 
     /// Number of observations.
     ///
+    /// It is inferred from the first element written along this axis, and can
+    /// only be set explicitly before that.
+    ///
     /// Returns
     /// -------
     /// int
@@ -198,8 +407,16 @@ impl AnnData {
         self.shape().0
     }
 
+    #[setter(n_obs)]
+    pub fn set_n_obs(&self, n: usize) -> Result<()> {
+        self.0.set_n_obs(n)
+    }
+
     /// Number of variables/features.
     ///
+    /// It is inferred from the first element written along this axis, and can
+    /// only be set explicitly before that.
+    ///
     /// Returns
     /// -------
     /// int
@@ -208,14 +425,19 @@ impl AnnData {
         self.shape().1
     }
 
+    #[setter(n_vars)]
+    pub fn set_n_vars(&self, n: usize) -> Result<()> {
+        self.0.set_n_vars(n)
+    }
+
     /// Names of observations.
     ///
     /// Returns
     /// -------
-    /// list[str]
+    /// Index
     #[getter]
-    pub fn obs_names(&self) -> Vec<String> {
-        self.0.obs_names().into_vec()
+    pub fn obs_names(&self) -> PyIndex {
+        self.0.obs_names().into()
     }
     #[setter(obs_names)]
     pub fn set_obs_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
@@ -225,14 +447,35 @@ impl AnnData {
     #[pyo3(text_signature = "($self, names)")]
     fn obs_ix(&self, names: Bound<'_, PyAny>) -> Result<Vec<usize>> { self.0.obs_ix(names) }
 
+    /// Select observations with a query on the columns of obs.
+    ///
+    /// Only the columns referenced in the query are read from disk. The query
+    /// supports comparisons with numbers, strings and booleans (`==`, `!=`, `<`,
+    /// `<=`, `>`, `>=`), `column in [...]`, `isnull(column)`, and `&`, `|`, `~`
+    /// with parentheses. Categorical columns are compared against their labels.
+    /// Column names can be quoted with backticks.
+    ///
+    /// Parameters
+    /// ----------
+    /// expr : str
+    ///     The query, e.g., `"n_counts > 500 & cell_type in ['T', 'NK']"`.
+    ///
+    /// Returns
+    /// -------
+    /// list[int]
+    ///     The indices of the matching observations, which can be passed to
+    ///     `subset` or used to index the object.
+    #[pyo3(text_signature = "($self, expr)")]
+    fn obs_query(&self, expr: &str) -> Result<Vec<usize>> { self.0.obs_query(expr) }
+
     /// Names of variables.
     ///
     /// Returns
     /// -------
-    /// list[str]
+    /// Index
     #[getter]
-    pub fn var_names(&self) -> Vec<String> {
-        self.0.var_names().into_vec()
+    pub fn var_names(&self) -> PyIndex {
+        self.0.var_names().into()
     }
     #[setter(var_names)]
     pub fn set_var_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
@@ -256,6 +499,13 @@ impl AnnData {
         self.0.set_x(data)
     }
 
+    /// Delete the data matrix `X`, leaving a metadata-only AnnData object.
+    ///
+    /// This is equivalent to `adata.X = None` and `del adata.X`.
+    pub fn del_x(&self) -> Result<()> {
+        self.0.set_x(None)
+    }
+
     /// Observation annotations.
     ///
     /// Returns
@@ -270,6 +520,14 @@ impl AnnData {
         self.0.set_obs(obs)
     }
 
+    /// Delete `obs` from the file, equivalent to `adata.obs = None`.
+    ///
+    /// The observation names are replaced by a range index. A warning is
+    /// logged if other elements still depend on the number of observations.
+    pub fn del_obs(&self) -> Result<()> {
+        self.0.set_obs(None)
+    }
+
     /// Variable annotations.
     ///
     /// Returns
@@ -284,6 +542,39 @@ impl AnnData {
         self.0.set_var(var)
     }
 
+    /// Delete `var` from the file, equivalent to `adata.var = None`.
+    ///
+    /// The variable names are replaced by a range index. A warning is logged
+    /// if other elements still depend on the number of variables.
+    pub fn del_var(&self) -> Result<()> {
+        self.0.set_var(None)
+    }
+
+    /// Replace obs by a Parquet file, e.g., one written by `obs.to_parquet`.
+    ///
+    /// The first column of the file is used as the observation names, and its
+    /// name is kept as the name of the index.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: Path
+    ///     File name of the Parquet file.
+    #[pyo3(text_signature = "($self, path)")]
+    fn set_obs_from_parquet(&self, path: PathBuf) -> Result<()> {
+        self.0.set_obs_from_parquet(&path)
+    }
+
+    /// Replace var by a Parquet file, see `set_obs_from_parquet`.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: Path
+    ///     File name of the Parquet file.
+    #[pyo3(text_signature = "($self, path)")]
+    fn set_var_from_parquet(&self, path: PathBuf) -> Result<()> {
+        self.0.set_var_from_parquet(&path)
+    }
+
     /// Unstructured annotation (ordered dictionary).
     ///
     /// Returns
@@ -298,13 +589,23 @@ impl AnnData {
         self.0.set_uns(uns)
     }
 
+    /// Delete `uns` from the file, equivalent to `adata.uns = None`.
+    pub fn del_uns(&self) -> Result<()> {
+        self.0.set_uns(None)
+    }
+
     #[getter(obsm)]
     pub fn get_obsm(&self) -> Option<PyAxisArrays> {
         self.0.get_obsm()
     }
     #[setter(obsm)]
-    pub fn set_obsm(&self, obsm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_obsm(obsm)
+    pub fn set_obsm(&self, obsm: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_obsm(obsm.map(Into::into))
+    }
+
+    /// Delete `obsm` from the file, equivalent to `adata.obsm = None`.
+    pub fn del_obsm(&self) -> Result<()> {
+        self.0.set_obsm(None)
     }
 
     #[getter(obsp)]
@@ -312,8 +613,13 @@ impl AnnData {
         self.0.get_obsp()
     }
     #[setter(obsp)]
-    pub fn set_obsp(&self, obsp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_obsp(obsp)
+    pub fn set_obsp(&self, obsp: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_obsp(obsp.map(Into::into))
+    }
+
+    /// Delete `obsp` from the file, equivalent to `adata.obsp = None`.
+    pub fn del_obsp(&self) -> Result<()> {
+        self.0.set_obsp(None)
     }
 
     #[getter(varm)]
@@ -321,8 +627,13 @@ impl AnnData {
         self.0.get_varm()
     }
     #[setter(varm)]
-    pub fn set_varm(&self, varm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_varm(varm)
+    pub fn set_varm(&self, varm: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_varm(varm.map(Into::into))
+    }
+
+    /// Delete `varm` from the file, equivalent to `adata.varm = None`.
+    pub fn del_varm(&self) -> Result<()> {
+        self.0.set_varm(None)
     }
 
     #[getter(varp)]
@@ -330,8 +641,13 @@ impl AnnData {
         self.0.get_varp()
     }
     #[setter(varp)]
-    pub fn set_varp(&self, varp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_varp(varp)
+    pub fn set_varp(&self, varp: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_varp(varp.map(Into::into))
+    }
+
+    /// Delete `varp` from the file, equivalent to `adata.varp = None`.
+    pub fn del_varp(&self) -> Result<()> {
+        self.0.set_varp(None)
     }
 
     #[getter(layers)]
@@ -339,8 +655,244 @@ impl AnnData {
         self.0.get_layers()
     }
     #[setter(layers)]
-    pub fn set_layers(&self, layers: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_layers(layers)
+    pub fn set_layers(&self, layers: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_layers(layers.map(Into::into))
+    }
+
+    /// Delete `layers` from the file, equivalent to `adata.layers = None`.
+    pub fn del_layers(&self) -> Result<()> {
+        self.0.set_layers(None)
+    }
+
+    /// Delete the `raw` group from the file. Malformed raw groups, which are
+    /// otherwise skipped and kept in the file as they are, are deleted too.
+    pub fn del_raw(&self) -> Result<()> {
+        self.0.del_raw()
+    }
+
+    /// Return the nearest neighbor graphs stored under `uns[key]`.
+    ///
+    /// This follows the scanpy convention: `uns[key]` contains the obsp keys of
+    /// the connectivity and distance matrices as well as the parameters used
+    /// to compute them.
+    ///
+    /// Parameters
+    /// ----------
+    /// key: str | None
+    ///     The key of the neighbors in `uns`. Default: "neighbors".
+    ///
+    /// Returns
+    /// -------
+    /// dict | None
+    ///     A dictionary with keys "connectivities", "distances" and "params",
+    ///     or None if the neighbors do not exist.
+    #[pyo3(
+        signature = (key=None),
+        text_signature = "($self, key=None)",
+    )]
+    pub fn neighbors(&self, py: Python<'_>, key: Option<&str>) -> Result<Option<PyObject>> {
+        self.0.neighbors(key)?.map(|n| {
+            let dict = pyo3::types::PyDict::new_bound(py);
+            dict.set_item("connectivities", PyArrayData::from(n.connectivities).into_py(py))?;
+            dict.set_item("distances", PyArrayData::from(n.distances).into_py(py))?;
+            dict.set_item("params", PyData::from(Data::Mapping(n.params)).into_py(py))?;
+            Ok(dict.to_object(py))
+        }).transpose()
+    }
+
+    /// Store the nearest neighbor graphs and their parameters.
+    ///
+    /// The matrices are stored in `obsp["connectivities"]` and `obsp["distances"]`
+    /// if `key` is None, and in `obsp["{key}_connectivities"]` and
+    /// `obsp["{key}_distances"]` otherwise.
+    ///
+    /// Parameters
+    /// ----------
+    /// connectivities
+    ///     The weighted adjacency matrix of the neighbor graph.
+    /// distances
+    ///     The distances to the nearest neighbors.
+    /// params: dict | None
+    ///     Parameters used to compute the graph, e.g., `n_neighbors` and `metric`.
+    /// key: str | None
+    ///     The key of the neighbors in `uns`. Default: "neighbors".
+    #[pyo3(
+        signature = (connectivities, distances, params=None, key=None),
+        text_signature = "($self, connectivities, distances, params=None, key=None)",
+    )]
+    pub fn set_neighbors(
+        &self,
+        connectivities: PyArrayData,
+        distances: PyArrayData,
+        params: Option<HashMap<String, PyData>>,
+        key: Option<&str>,
+    ) -> Result<()> {
+        let neighbors = Neighbors::new(connectivities.into(), distances.into())
+            .with_params(params.into_iter().flatten().map(|(k, v)| (k, v.into())));
+        self.0.set_neighbors(key, neighbors)
+    }
+
+    /// Return the spatial coordinates and the associated images.
+    ///
+    /// This follows the scanpy/squidpy convention: coordinates are stored in
+    /// `obsm["spatial"]`, and `uns["spatial"]` contains the images and scale
+    /// factors of each library.
+    ///
+    /// Returns
+    /// -------
+    /// dict | None
+    ///     A dictionary with keys "coordinates" and "libraries", or None if
+    ///     `obsm["spatial"]` does not exist. "libraries" maps library ids to
+    ///     dictionaries with keys "images" and "scalefactors".
+    #[pyo3(text_signature = "($self)")]
+    pub fn spatial(&self, py: Python<'_>) -> Result<Option<PyObject>> {
+        self.0.spatial()?.map(|s| {
+            let libraries = pyo3::types::PyDict::new_bound(py);
+            for (k, v) in s.libraries {
+                libraries.set_item(k, PyData::from(Data::Mapping(v.into())).into_py(py))?;
+            }
+            let dict = pyo3::types::PyDict::new_bound(py);
+            dict.set_item("coordinates", PyArrayData::from(s.coordinates).into_py(py))?;
+            dict.set_item("libraries", libraries)?;
+            Ok(dict.to_object(py))
+        }).transpose()
+    }
+
+    /// Store the spatial coordinates and the associated images.
+    ///
+    /// Parameters
+    /// ----------
+    /// coordinates
+    ///     A `n_obs x 2` array of spatial coordinates.
+    /// libraries: dict[str, dict] | None
+    ///     A dictionary mapping library ids to dictionaries with keys "images"
+    ///     and "scalefactors", e.g.,
+    ///     `{"slide1": {"images": {"hires": img}, "scalefactors": {"tissue_hires_scalef": 0.1}}}`.
+    #[pyo3(
+        signature = (coordinates, libraries=None),
+        text_signature = "($self, coordinates, libraries=None)",
+    )]
+    pub fn set_spatial(
+        &self,
+        coordinates: PyArrayData,
+        libraries: Option<HashMap<String, PyData>>,
+    ) -> Result<()> {
+        let mut spatial = Spatial::new(coordinates.into());
+        for (k, v) in libraries.into_iter().flatten() {
+            let data: Data = v.into();
+            let library: SpatialLibrary = Mapping::try_from(data)?.try_into()?;
+            spatial = spatial.with_library(&k, library);
+        }
+        self.0.set_spatial(spatial)
+    }
+
+    /// Return the embedding of a basis stored in `obsm`.
+    ///
+    /// This mirrors the lookup used by scanpy plotting functions: `basis` is
+    /// either a key of `obsm`, or a basis name such that `obsm["X_{basis}"]`
+    /// exists.
+    ///
+    /// Parameters
+    /// ----------
+    /// basis: str
+    ///     The basis name, e.g., "umap" or "X_umap".
+    ///
+    /// Returns
+    /// -------
+    /// np.ndarray | scipy.sparse.csr_matrix | None
+    ///     The embedding, or None if it does not exist.
+    #[pyo3(text_signature = "($self, basis)")]
+    pub fn embedding(&self, basis: &str) -> Result<Option<PyArrayData>> {
+        Ok(self.0.embedding(basis)?.map(Into::into))
+    }
+
+    /// Calculate quality control metrics and add them to `obs` and `var`.
+    ///
+    /// This computes the same metrics as `scanpy.pp.calculate_qc_metrics`
+    /// in a single pass over `X`, which is read in chunks: `total_counts`
+    /// and `n_genes_by_counts` are added to `obs`, and `n_cells_by_counts`
+    /// and `mean_counts` to `var`. Existing columns with these names are
+    /// replaced. Nothing is done if `X` is empty.
+    #[pyo3(text_signature = "($self)")]
+    pub fn calculate_qc_metrics(&self) -> Result<()> {
+        self.0.calculate_qc_metrics()
+    }
+
+    /// Read several elements at once.
+    ///
+    /// The requested elements are looked up in a single pass and read in
+    /// parallel. Elements that cannot be read are omitted from the result,
+    /// and a warning listing them is issued.
+    ///
+    /// Parameters
+    /// ----------
+    /// obsm: list[str] | None
+    ///     Keys of `obsm` to read.
+    /// obsp: list[str] | None
+    ///     Keys of `obsp` to read.
+    /// varm: list[str] | None
+    ///     Keys of `varm` to read.
+    /// varp: list[str] | None
+    ///     Keys of `varp` to read.
+    /// layers: list[str] | None
+    ///     Keys of `layers` to read.
+    /// uns: list[str] | None
+    ///     Keys of `uns` to read.
+    ///
+    /// Returns
+    /// -------
+    /// dict[str, dict[str, Any]]
+    ///     A dictionary keyed by slot names, e.g., `result["obsm"]["X_pca"]`.
+    #[pyo3(
+        signature = (*, obsm=None, obsp=None, varm=None, varp=None, layers=None, uns=None),
+        text_signature = "($self, *, obsm=None, obsp=None, varm=None, varp=None, layers=None, uns=None)",
+    )]
+    pub fn fetch(
+        &self,
+        py: Python<'_>,
+        obsm: Option<Vec<String>>,
+        obsp: Option<Vec<String>>,
+        varm: Option<Vec<String>>,
+        varp: Option<Vec<String>>,
+        layers: Option<Vec<String>>,
+        uns: Option<Vec<String>>,
+    ) -> Result<PyObject> {
+        let requests: Vec<_> = [
+            (ElemSlot::Obsm, &obsm),
+            (ElemSlot::Obsp, &obsp),
+            (ElemSlot::Varm, &varm),
+            (ElemSlot::Varp, &varp),
+            (ElemSlot::Layers, &layers),
+            (ElemSlot::Uns, &uns),
+        ].into_iter()
+            .flat_map(|(slot, keys)| keys.iter().flatten().map(move |k| (slot, k.as_str())))
+            .collect();
+
+        let result = pyo3::types::PyDict::new_bound(py);
+        let mut failed = Vec::new();
+        for (name, data) in self.0.read_many(&requests) {
+            let (slot, key) = name.split_once('/').unwrap();
+            match data {
+                Ok(data) => {
+                    let dict = match result.get_item(slot)? {
+                        Some(dict) => dict.downcast_into::<pyo3::types::PyDict>().unwrap(),
+                        None => {
+                            let dict = pyo3::types::PyDict::new_bound(py);
+                            result.set_item(slot, &dict)?;
+                            dict
+                        }
+                    };
+                    dict.set_item(key, PyData::from(data).into_py(py))?;
+                }
+                Err(e) => failed.push(format!("{}: {}", name, e)),
+            }
+        }
+        if !failed.is_empty() {
+            failed.sort();
+            let warning = py.get_type_bound::<pyo3::exceptions::PyUserWarning>();
+            PyErr::warn_bound(py, &warning, &format!("failed to read: {}", failed.join("; ")), 1)?;
+        }
+        Ok(result.to_object(py))
     }
 
     /// Subsetting the AnnData object.
@@ -385,6 +937,61 @@ impl AnnData {
         self.0.subset(py, &[i, j], out, inplace, backend)
     }
 
+    /// Return a view of the observations and variables selected by
+    /// `adata[obs, var]` or `adata[obs]`, without reading any data.
+    ///
+    /// Returns
+    /// -------
+    /// AnnDataView
+    fn __getitem__(&self, subscript: &Bound<'_, PyAny>) -> PyResult<AnnDataView> {
+        AnnDataView::new(self.clone())?.select(subscript)
+    }
+
+    /// Randomly sample observations without replacement.
+    ///
+    /// Only the sampled rows are read from the backing file, which makes this
+    /// suitable for quick previews of large datasets.
+    ///
+    /// Parameters
+    /// ----------
+    /// n: int
+    ///     Number of observations to sample.
+    /// seed: int
+    ///     Random seed. The same seed gives the same sample.
+    /// out: Path | None
+    ///     File name of the output `.h5ad` file. If `None`, an in-memory AnnData
+    ///     is returned.
+    /// return_indices: bool
+    ///     If True, return the indices of the sampled observations in increasing
+    ///     order instead of an AnnData object.
+    /// backend: str | None
+    ///     The backend to use. Currently "hdf5" is the only supported backend.
+    ///
+    /// Returns
+    /// -------
+    /// AnnData | list[int]
+    #[pyo3(
+        signature = (n, seed=0, *, out=None, return_indices=false, backend=None),
+        text_signature = "($self, n, seed=0, *, out=None, return_indices=False, backend=None)",
+    )]
+    pub fn sample_obs(
+        &self,
+        py: Python<'_>,
+        n: usize,
+        seed: u64,
+        out: Option<PathBuf>,
+        return_indices: bool,
+        backend: Option<&str>,
+    ) -> Result<PyObject> {
+        let indices = self.0.sample_obs(n, seed)?;
+        if return_indices {
+            return Ok(indices.into_py(py));
+        }
+        let slice = [SelectInfoElem::from(indices), SelectInfoElem::full()];
+        let adata = self.0.subset(py, &slice, out, false, backend)?;
+        Ok(adata.expect("subset returns a new object when not in place"))
+    }
+
     /// Return an iterator over the rows of the data matrix X.
     ///
     /// Parameters
@@ -404,8 +1011,79 @@ impl AnnData {
         self.0.chunked_x(chunk_size)
     }
 
+    /// Write a dense copy of X to a layer, leaving X unchanged.
+    ///
+    /// X is converted `chunk_size` rows at a time, so that the memory used is
+    /// bounded by the size of a dense chunk.
+    ///
+    /// Parameters
+    /// ----------
+    /// layer : str
+    ///     Name of the layer to write. An existing layer is replaced.
+    /// chunk_size : int
+    ///     Row size of a single chunk. Default: 500.
+    #[pyo3(
+        signature = (layer, chunk_size=500),
+        text_signature = "($self, layer, chunk_size=500)",
+    )]
+    pub fn densify_x(&self, layer: &str, chunk_size: usize) -> Result<()> {
+        self.0.densify_x(layer, chunk_size)
+    }
+
+    /// Split the observations into at most `n_chunks` contiguous row ranges.
+    ///
+    /// If X is a CSR matrix, the ranges are balanced by the number of non-zero
+    /// entries, otherwise by the number of rows. Together with opening the file
+    /// read-only in each process, this allows the chunks to be processed in
+    /// parallel, see `map_chunks`.
+    ///
+    /// Parameters
+    /// ----------
+    /// n_chunks : int
+    ///     Maximum number of chunks.
+    ///
+    /// Returns
+    /// -------
+    /// list[tuple[int, int]]
+    ///     The start and end (exclusive) of each chunk.
+    #[pyo3(text_signature = "($self, n_chunks)")]
+    pub fn chunk_ranges(&self, n_chunks: usize) -> Result<Vec<(usize, usize)>> {
+        self.0.chunk_ranges(n_chunks)
+    }
+
+    /// Append observations, given as rows of X together with their records in obs.
+    ///
+    /// X is grown in place, so that data can be written in a streaming fashion.
+    /// Each call is atomic: if it fails, X and obs are left unchanged.
+    ///
+    /// Parameters
+    /// ----------
+    /// x : np.ndarray | scipy.sparse.csr_matrix
+    ///     The new rows of X.
+    /// obs : pd.DataFrame | pl.DataFrame | dict | None
+    ///     The new records of obs. The columns must match the existing ones.
+    /// obs_names : list[str] | None
+    ///     The names of the new observations. Can be omitted if the existing
+    ///     obs_names are the default ones.
+    #[pyo3(
+        signature = (x, obs=None, obs_names=None),
+        text_signature = "($self, x, obs=None, obs_names=None)",
+    )]
+    pub fn append(
+        &self,
+        x: PyArrayData,
+        obs: Option<Bound<'_, PyAny>>,
+        obs_names: Option<Vec<String>>,
+    ) -> Result<()> {
+        self.0.append(x, obs, obs_names)
+    }
+
     /// Filename of the backing .h5ad file.
     ///
+    /// Assigning a new filename moves the object to the new file: the data is
+    /// written to it, the object is re-opened from it in read-write mode, and
+    /// the old file is removed. The new file must not exist.
+    ///
     /// Returns
     /// -------
     /// Path
@@ -413,6 +1091,10 @@ impl AnnData {
     pub fn filename(&self) -> PathBuf {
         self.0.filename()
     }
+    #[setter(filename)]
+    pub fn set_filename(&self, filename: PathBuf) -> Result<()> {
+        self.0.save_as(filename, true, false)
+    }
 
     #[getter]
     pub fn backend(&self) -> String {
@@ -439,6 +1121,94 @@ impl AnnData {
         self.0.is_closed()
     }
 
+    /// Whether the AnnData object has been modified since it was opened or
+    /// last flushed.
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    #[getter]
+    pub fn is_dirty(&self) -> bool {
+        self.0.is_dirty()
+    }
+
+    /// Time of the last modification of the file, in seconds since the epoch.
+    ///
+    /// This is stored in the `last_modified` attribute of the file root and is
+    /// visible to other readers of the file.
+    ///
+    /// Returns
+    /// -------
+    /// float | None
+    #[getter]
+    pub fn last_modified(&self) -> Option<f64> {
+        self.0.last_modified()
+    }
+
+    /// Check that the file complies with the AnnData on-disk specification.
+    ///
+    /// Every element must have the `encoding-type` and `encoding-version`
+    /// attributes, dataframes must have the `_index` and `column-order`
+    /// attributes, the components of sparse matrices must agree with their
+    /// `shape`, and the elements of `obsm`, `obsp`, `varm`, `varp` and `layers`
+    /// must agree with `n_obs` and `n_vars`. Elements whose encoding is not
+    /// supported, which cannot be read but are kept when the object is written
+    /// or copied, are reported as well. Only the metadata is read.
+    ///
+    /// Returns
+    /// -------
+    /// list[str]
+    ///     A description of each violation, prefixed by the path of the element.
+    ///     Empty if the file is valid.
+    #[pyo3(text_signature = "($self)")]
+    pub fn validate(&self) -> Result<Vec<String>> {
+        self.0.validate()
+    }
+
+    /// Report the storage usage of the elements in the file.
+    ///
+    /// Only the metadata of the elements is read. Strings are counted by
+    /// their storage size, as their length is unknown without reading them.
+    ///
+    /// Parameters
+    /// ----------
+    /// summary: bool
+    ///     If True, return a pretty-printed summary grouped by slot instead.
+    ///
+    /// Returns
+    /// -------
+    /// polars.DataFrame | str
+    ///     A dataframe with one row per element and the columns "slot", "path",
+    ///     "encoding", "dtype", "storage_size", "logical_size" (in bytes) and
+    ///     "compression_ratio".
+    #[pyo3(
+        signature = (summary=false),
+        text_signature = "($self, summary=False)",
+    )]
+    pub fn usage(&self, py: Python<'_>, summary: bool) -> Result<PyObject> {
+        let report = self.0.usage()?;
+        if summary {
+            return Ok(report.to_string().into_py(py));
+        }
+        let elems = &report.elements;
+        let df = DataFrame::new(vec![
+            Series::new("slot", elems.iter().map(|x| x.slot.as_str()).collect::<Vec<_>>()),
+            Series::new("path", elems.iter().map(|x| x.path.as_str()).collect::<Vec<_>>()),
+            Series::new("encoding", elems.iter().map(|x| x.encoding.as_str()).collect::<Vec<_>>()),
+            Series::new("dtype", elems.iter().map(|x| x.dtype.map(|t| t.to_string())).collect::<Vec<_>>()),
+            Series::new("storage_size", elems.iter().map(|x| x.storage_size).collect::<Vec<_>>()),
+            Series::new("logical_size", elems.iter().map(|x| x.logical_size).collect::<Vec<_>>()),
+            Series::new("compression_ratio", elems.iter().map(|x| x.compression_ratio()).collect::<Vec<_>>()),
+        ])?;
+        Ok(PyDataFrame(df).into_py(py))
+    }
+
+    /// Flush the buffered data to disk without closing the file.
+    #[pyo3(text_signature = "($self)")]
+    pub fn flush(&self) -> Result<()> {
+        self.0.flush()
+    }
+
     /// Close the AnnData object.
     #[pyo3(text_signature = "($self)")]
     pub fn close(&self) -> Result<()> {
@@ -446,6 +1216,11 @@ impl AnnData {
     }
 
     /// Reopen a closed AnnData object.
+    ///
+    /// Parameters
+    /// ----------
+    /// mode: Literal['r', 'r+', 'swmr', 'swmr+']
+    ///     See the `backed` parameter of `read`.
     #[pyo3(
         signature = (mode="r"),
         text_signature = "($self, mode='r')",
@@ -456,14 +1231,116 @@ impl AnnData {
 
     /// Write .h5ad-formatted hdf5 file.
     ///
+    /// The data is written to a temporary file first, which replaces `filename`
+    /// only when the writing is complete.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename: Path
+    ///     File name of the output `.h5ad` file.
+    /// backend: str | None
+    /// overwrite: bool
+    ///     Whether to replace `filename` if it already exists.
+    /// obs_columns: list[str] | None
+    ///     Only write these obs columns, in this order. All columns are
+    ///     written if None. Other elements are written unchanged.
+    /// var_columns: list[str] | None
+    ///     Only write these var columns, see `obs_columns`.
+    #[pyo3(
+        signature = (filename, backend=None, overwrite=true, obs_columns=None, var_columns=None),
+        text_signature = "($self, filename, backend=None, overwrite=True, obs_columns=None, var_columns=None)",
+    )]
+    pub fn write(
+        &self,
+        filename: PathBuf,
+        backend: Option<&str>,
+        overwrite: bool,
+        obs_columns: Option<Vec<String>>,
+        var_columns: Option<Vec<String>>,
+    ) -> Result<()> {
+        self.0.write(filename, backend, [obs_columns.as_deref(), var_columns.as_deref()], overwrite)
+    }
+
+    /// Write the AnnData object to a new file and continue with that file.
+    ///
+    /// Unlike `write`, which leaves the object backed by the current file,
+    /// the object is re-opened from `filename` in read-write mode, so that
+    /// subsequent changes are saved to the new file. The current file is
+    /// closed but kept.
+    ///
     /// Parameters
     /// ----------
     /// filename: Path
     ///     File name of the output `.h5ad` file.
+    /// overwrite: bool
+    ///     Whether to replace `filename` if it already exists.
+    #[pyo3(
+        signature = (filename, overwrite=true),
+        text_signature = "($self, filename, overwrite=True)",
+    )]
+    pub fn save_as(&self, filename: PathBuf, overwrite: bool) -> Result<()> {
+        self.0.save_as(filename, false, overwrite)
+    }
+
+    /// Rewrite the backing file to reclaim the space of deleted elements.
+    ///
+    /// HDF5 files do not shrink when data is deleted, e.g., with
+    /// `del adata.obsm[["a", "b"]]`. The file is replaced by a compacted copy,
+    /// which the object is then backed by.
+    #[pyo3(text_signature = "($self)")]
+    pub fn repack(&self) -> Result<()> {
+        self.0.repack()
+    }
+
+    /// Write a subset of the AnnData object to a new file in one call.
+    ///
+    /// Observations and variables can each be selected by a query expression
+    /// (see `obs_query`), by names, or by indices; at most one selection may be
+    /// given per axis, and an axis without a selection is kept in full. The
+    /// subset is streamed to disk without being loaded into memory.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename: Path
+    ///     File name of the output `.h5ad` file.
+    /// obs_query: str | None
+    ///     Query expression on the obs columns, e.g. `"cell_type == 'T'"`.
+    /// var_query: str | None
+    ///     Query expression on the var columns.
+    /// obs_names: list[str] | None
+    /// var_names: list[str] | None
+    /// obs_indices: Index1D | None
+    /// var_indices: Index1D | None
     /// backend: str | None
-    #[pyo3(text_signature = "($self, filename, backend)")]
-    pub fn write(&self, filename: PathBuf, backend: Option<&str>) -> Result<()> {
-        self.0.write(filename, backend)
+    /// overwrite: bool
+    ///     Whether to replace `filename` if it already exists.
+    ///
+    /// Examples
+    /// --------
+    /// >>> adata.filter_to("tcells.h5ad", obs_query="cell_type == 'T'", var_names=marker_genes)
+    #[pyo3(
+        signature = (
+            filename, *, obs_query=None, var_query=None, obs_names=None, var_names=None,
+            obs_indices=None, var_indices=None, backend=None, overwrite=true,
+        ),
+        text_signature = "($self, filename, *, obs_query=None, var_query=None, obs_names=None, var_names=None, obs_indices=None, var_indices=None, backend=None, overwrite=True)",
+    )]
+    pub fn filter_to(
+        &self,
+        filename: PathBuf,
+        obs_query: Option<String>,
+        var_query: Option<String>,
+        obs_names: Option<Vec<String>>,
+        var_names: Option<Vec<String>>,
+        obs_indices: Option<&Bound<'_, PyAny>>,
+        var_indices: Option<&Bound<'_, PyAny>>,
+        backend: Option<&str>,
+        overwrite: bool,
+    ) -> Result<()> {
+        let obs = axis_selection("obs", obs_query, obs_names, obs_indices, self.n_obs())?;
+        let var = axis_selection("var", var_query, var_names, var_indices, self.n_vars())?;
+        check_overwrite(&filename, overwrite)?;
+        self.0.filter_to(filename, obs, var, backend)
     }
 
     /// Copy the AnnData object.
@@ -473,25 +1350,159 @@ impl AnnData {
     /// filename
     ///     File name of the output `.h5ad` file.
     /// backend: str | None
+    /// overwrite: bool
+    ///     Whether to replace `filename` if it already exists.
     ///
     /// Returns
     /// -------
     /// AnnData
-    #[pyo3(text_signature = "($self, filename, backend)")]
-    fn copy(&self, filename: PathBuf, backend: Option<&str>) -> Result<Self> {
+    #[pyo3(
+        signature = (filename, backend=None, overwrite=true),
+        text_signature = "($self, filename, backend=None, overwrite=True)",
+    )]
+    fn copy(&self, filename: PathBuf, backend: Option<&str>, overwrite: bool) -> Result<Self> {
+        check_overwrite(&filename, overwrite)?;
         self.0.copy(filename, backend)
     }
 
     /// Return a new AnnData object with all backed arrays loaded into memory.
     ///
+    /// The result is an object of the pure-Python `anndata` package, which can
+    /// be passed to tools that do not support the backed AnnData.
+    ///
     /// Returns
     /// -------
-    /// AnnData
+    /// anndata.AnnData
     #[pyo3(text_signature = "($self)")]
     pub fn to_memory<'py>(&self, py: Python<'py>) -> Result<PyAnnData<'py>> {
         self.0.to_memory(py)
     }
 
+    /// Return `X`, or a layer, as a pandas DataFrame with obs_names as the index
+    /// and var_names as the columns.
+    ///
+    /// Sparse matrices are densified, which may need a lot of memory.
+    ///
+    /// Parameters
+    /// ----------
+    /// layer: str | None
+    ///     The layer to use. If None, `X` is used.
+    ///
+    /// Returns
+    /// -------
+    /// pandas.DataFrame
+    #[pyo3(
+        signature = (layer=None),
+        text_signature = "($self, layer=None)",
+    )]
+    pub fn to_df<'py>(&self, py: Python<'py>, layer: Option<&str>) -> Result<Bound<'py, PyAny>> {
+        let elem = match layer {
+            None => self.0.get_x().context("X is empty")?,
+            Some(key) => self.0.get_layers().context("layers are empty")?.el(key)?,
+        };
+        let (n_obs, n_vars) = self.0.shape();
+        if n_obs * n_vars > MAX_DENSE_SIZE {
+            bail!("refusing to create a dataframe with {} entries", n_obs * n_vars);
+        }
+        let mut data = elem.read_all(py)?;
+        if isinstance_of_csr(&data)? || isinstance_of_csc(&data)? {
+            let warning = py.get_type_bound::<pyo3::exceptions::PyUserWarning>();
+            PyErr::warn_bound(py, &warning, "densifying a sparse matrix, which may use a lot of memory", 1)?;
+            data = data.call_method0("toarray")?;
+        }
+        let kwargs = PyDict::new_bound(py);
+        kwargs.set_item("index", self.0.obs_names().into_vec())?;
+        kwargs.set_item("columns", self.0.var_names().into_vec())?;
+        Ok(py.import_bound("pandas")?.getattr("DataFrame")?.call((data,), Some(&kwargs))?)
+    }
+
+    /// Return the observation annotations as a pandas DataFrame.
+    ///
+    /// Unlike `adata.obs[:]`, which returns a polars DataFrame, the result is
+    /// indexed by the observation names and categorical columns are kept as
+    /// pandas categoricals, as expected by many downstream tools.
+    ///
+    /// Returns
+    /// -------
+    /// pandas.DataFrame
+    #[pyo3(text_signature = "($self)")]
+    pub fn obs_as_pandas<'py>(&self, py: Python<'py>) -> Result<Bound<'py, PyAny>> {
+        to_pandas(py, self.0.read_obs()?, self.0.obs_names())
+    }
+
+    /// Return the variable annotations as a pandas DataFrame indexed by the
+    /// variable names. See `obs_as_pandas`.
+    ///
+    /// Returns
+    /// -------
+    /// pandas.DataFrame
+    #[pyo3(text_signature = "($self)")]
+    pub fn var_as_pandas<'py>(&self, py: Python<'py>) -> Result<Bound<'py, PyAny>> {
+        to_pandas(py, self.0.read_var()?, self.0.var_names())
+    }
+
+    /// Return the state used for pickling: the filename, the backend and the
+    /// mode used to re-open the file.
+    ///
+    /// Unpickled objects open the file in read-only mode, so that the object
+    /// can be sent to worker processes, e.g., with `ProcessPoolExecutor`, without
+    /// several processes writing to the same file. HDF5 handles are not
+    /// fork-safe, so handles inherited by forked processes must not be used;
+    /// unpickled objects always open fresh handles instead. Note that HDF5 file
+    /// locking prevents other processes from opening a file that is open for
+    /// writing, so the parent process should open the file with `backed="r"`.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    fn __getstate__<'py>(&self, py: Python<'py>) -> Result<Bound<'py, PyDict>> {
+        if self.is_closed() {
+            bail!("cannot pickle a closed AnnData object");
+        }
+        let state = PyDict::new_bound(py);
+        state.set_item("filename", self.filename())?;
+        state.set_item("backend", self.backend())?;
+        state.set_item("mode", "r")?;
+        Ok(state)
+    }
+
+    /// Re-open the file described by `state`. See `__getstate__`.
+    fn __setstate__(&mut self, state: &Bound<'_, PyDict>) -> Result<()> {
+        *self = Self::from_state(state)?;
+        Ok(())
+    }
+
+    #[classmethod]
+    fn _from_state(_cls: &Bound<'_, PyType>, state: &Bound<'_, PyDict>) -> Result<Self> {
+        Self::from_state(state)
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> Result<(Bound<'py, PyAny>, (Bound<'py, PyDict>,))> {
+        let state = slf.borrow().__getstate__(slf.py())?;
+        Ok((slf.get_type().getattr("_from_state")?, (state,)))
+    }
+
+    /// Return a new handle to the same file. Changes made through either
+    /// object are visible to the other.
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    /// Copy the AnnData object to a new file in the temporary directory.
+    /// See `copy`. The file is deleted when the copy is closed or dropped,
+    /// unless it is saved with `save_as`.
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyDict>) -> Result<Self> {
+        let filename = std::env::temp_dir()
+            .join(format!("anndata_{:016x}.h5ad", rand::random::<u64>()));
+        let backend = Some(self.0.backend());
+        self.0.write(filename.clone(), backend, [None, None], false)?;
+        let adata = AnnData::open_temporary(filename.clone(), "r+", backend);
+        if adata.is_err() {
+            let _ = std::fs::remove_file(&filename);
+        }
+        adata
+    }
+
     fn __repr__(&self) -> String {
         self.0.show()
     }
@@ -501,14 +1512,77 @@ impl AnnData {
     }
 }
 
-trait AnnDataTrait: Send + Downcast {
+impl AnnData {
+    fn from_state<'py>(state: &Bound<'py, PyDict>) -> Result<Self> {
+        let get = |key: &str| -> Result<Bound<'py, PyAny>> {
+            state.get_item(key)?.with_context(|| format!("'{}' is missing in the state", key))
+        };
+        let filename: PathBuf = get("filename")?.extract()?;
+        let backend: String = get("backend")?.extract()?;
+        let mode: String = get("mode")?.extract()?;
+        Self::new_from(filename, &mode, Some(&backend))
+    }
+}
+
+pub(crate) fn check_overwrite(filename: &Path, overwrite: bool) -> Result<()> {
+    if !overwrite && filename.exists() {
+        bail!("file already exists: {}", filename.display());
+    }
+    Ok(())
+}
+
+fn axis_selection(
+    axis: &str,
+    query: Option<String>,
+    names: Option<Vec<String>>,
+    indices: Option<&Bound<'_, PyAny>>,
+    n: usize,
+) -> Result<Option<AxisSelection>> {
+    match (query, names, indices) {
+        (None, None, None) => Ok(None),
+        (Some(expr), None, None) => Ok(Some(AxisSelection::Query(expr))),
+        (None, Some(names), None) => Ok(Some(AxisSelection::Names(names))),
+        (None, None, Some(ix)) => Ok(Some(AxisSelection::Index(to_select_elem(ix, n)?))),
+        _ => bail!(
+            "at most one of {0}_query, {0}_names and {0}_indices can be given",
+            axis
+        ),
+    }
+}
+
+/// Convert a dataframe to pandas with `index` as the index. Categorical
+/// columns become pandas categoricals.
+pub(super) fn to_pandas<'py>(py: Python<'py>, df: DataFrame, index: DataFrameIndex) -> Result<Bound<'py, PyAny>> {
+    if df.width() == 0 {
+        let kwargs = PyDict::new_bound(py);
+        kwargs.set_item("index", index.into_vec())?;
+        return Ok(py.import_bound("pandas")?.getattr("DataFrame")?.call((), Some(&kwargs))?);
+    }
+    let df = PyDataFrame(df).into_py(py).into_bound(py).call_method0("to_pandas")?;
+    df.setattr("index", index.into_vec())?;
+    Ok(df)
+}
+
+pub(super) trait AnnDataTrait: Send + Downcast {
     fn shape(&self) -> (usize, usize);
+    fn set_n_obs(&self, n: usize) -> Result<()>;
+    fn set_n_vars(&self, n: usize) -> Result<()>;
+    fn set_coerce_bool_columns(&self, coerce: bool) -> Result<()>;
+    fn set_nan_as_null(&self, nan_as_null: bool) -> Result<()>;
+    fn set_assume_sorted_indices(&self, assume_sorted_indices: bool) -> Result<()>;
+    fn del_raw(&self) -> Result<()>;
+    fn densify_x(&self, layer: &str, chunk_size: usize) -> Result<()>;
     fn obs_names(&self) -> DataFrameIndex;
     fn set_obs_names(&self, names: Bound<'_, PyAny>) -> Result<()>;
     fn obs_ix(&self, index: Bound<'_, PyAny>) -> Result<Vec<usize>>;
+    fn obs_query(&self, expr: &str) -> Result<Vec<usize>>;
     fn var_names(&self) -> DataFrameIndex;
     fn set_var_names(&self, names: Bound<'_, PyAny>) -> Result<()>;
     fn var_ix(&self, index: Bound<'_, PyAny>) -> Result<Vec<usize>>;
+    fn read_obs(&self) -> Result<DataFrame>;
+    fn read_var(&self) -> Result<DataFrame>;
+    fn read_obs_select(&self, select: &SelectInfoElem) -> Result<DataFrame>;
+    fn read_var_select(&self, select: &SelectInfoElem) -> Result<DataFrame>;
 
     fn get_x(&self) -> Option<PyArrayElem>;
     fn get_obs(&self) -> Option<PyDataFrameElem>;
@@ -523,6 +1597,8 @@ trait AnnDataTrait: Send + Downcast {
     fn set_x(&self, data: Option<PyArrayData>) -> Result<()>;
     fn set_obs(&self, obs: Option<Bound<'_, PyAny>>) -> Result<()>;
     fn set_var(&self, var: Option<Bound<'_, PyAny>>) -> Result<()>;
+    fn set_obs_from_parquet(&self, path: &Path) -> Result<()>;
+    fn set_var_from_parquet(&self, path: &Path) -> Result<()>;
     fn set_uns(&self, uns: Option<HashMap<String, PyData>>) -> Result<()>;
     fn set_obsm(&self, obsm: Option<HashMap<String, PyArrayData>>) -> Result<()>;
     fn set_obsp(&self, obsp: Option<HashMap<String, PyArrayData>>) -> Result<()>;
@@ -530,6 +1606,14 @@ trait AnnDataTrait: Send + Downcast {
     fn set_varp(&self, varp: Option<HashMap<String, PyArrayData>>) -> Result<()>;
     fn set_layers(&self, varp: Option<HashMap<String, PyArrayData>>) -> Result<()>;
 
+    fn neighbors(&self, key: Option<&str>) -> Result<Option<Neighbors>>;
+    fn set_neighbors(&self, key: Option<&str>, neighbors: Neighbors) -> Result<()>;
+    fn spatial(&self) -> Result<Option<Spatial>>;
+    fn set_spatial(&self, spatial: Spatial) -> Result<()>;
+    fn embedding(&self, basis: &str) -> Result<Option<ArrayData>>;
+    fn calculate_qc_metrics(&self) -> Result<()>;
+    fn read_many(&self, requests: &[(ElemSlot, &str)]) -> HashMap<String, Result<Data>>;
+
     fn subset(
         &self,
         py: Python<'_>,
@@ -540,27 +1624,58 @@ trait AnnDataTrait: Send + Downcast {
     ) -> Result<Option<PyObject>>;
 
     fn chunked_x(&self, chunk_size: usize) -> PyChunkedArray;
-
-    fn write(&self, filename: PathBuf, backend: Option<&str>) -> Result<()>;
+    fn chunk_ranges(&self, n_chunks: usize) -> Result<Vec<(usize, usize)>>;
+    fn sample_obs(&self, n: usize, seed: u64) -> Result<Vec<usize>>;
+    fn append(
+        &self,
+        x: PyArrayData,
+        obs: Option<Bound<'_, PyAny>>,
+        obs_names: Option<Vec<String>>,
+    ) -> Result<()>;
+
+    /// Write the object, keeping only the given obs and var columns if any.
+    fn write(&self, filename: PathBuf, backend: Option<&str>, columns: [Option<&[String]>; 2], overwrite: bool) -> Result<()>;
+    /// Continue with a copy of the object at `filename`, removing the old file
+    /// if `remove_old` is true.
+    fn save_as(&self, filename: PathBuf, remove_old: bool, overwrite: bool) -> Result<()>;
+    /// Replace the file by a compacted copy.
+    fn repack(&self) -> Result<()>;
+    fn filter_to(
+        &self,
+        filename: PathBuf,
+        obs: Option<AxisSelection>,
+        var: Option<AxisSelection>,
+        backend: Option<&str>,
+    ) -> Result<()>;
     fn copy(&self, filename: PathBuf, backend: Option<&str>) -> Result<AnnData>;
     fn to_memory<'py>(&self, py: Python<'py>) -> Result<PyAnnData<'py>>;
 
     fn filename(&self) -> PathBuf;
     fn backend(&self) -> &str;
     fn is_closed(&self) -> bool;
+    fn is_dirty(&self) -> bool;
+    /// See [`anndata::AnnData::generation`].
+    fn generation(&self) -> Result<u64>;
+    fn last_modified(&self) -> Option<f64>;
+    fn validate(&self) -> Result<Vec<String>>;
+    fn usage(&self) -> Result<UsageReport>;
     fn show(&self) -> String;
 
     /// Reopen a closed AnnData object.
     fn open(&self, mode: &str) -> Result<()>;
+    fn flush(&self) -> Result<()>;
     fn close(&self) -> Result<()>;
     fn clone_ref(&self) -> Box<dyn AnnDataTrait>;
 }
 impl_downcast!(AnnDataTrait);
 
-/// An AnnData object with abstract backend.
+/// An AnnData object with abstract backend. The filename and whether the
+/// file is temporary are shared by the clones, as they change in `save_as`.
 struct InnerAnnData<B: Backend> {
-    filename: PathBuf,
+    filename: Arc<Mutex<PathBuf>>,
     adata: Slot<anndata::AnnData<B>>,
+    /// Whether the file is deleted when the object is closed.
+    temporary: Arc<AtomicBool>,
 }
 
 impl<B: Backend> Clone for InnerAnnData<B> {
@@ -568,6 +1683,17 @@ impl<B: Backend> Clone for InnerAnnData<B> {
         Self {
             filename: self.filename.clone(),
             adata: self.adata.clone(),
+            temporary: self.temporary.clone(),
+        }
+    }
+}
+
+impl<B: Backend> Drop for InnerAnnData<B> {
+    /// Delete a temporary file when the last reference to it is dropped
+    /// without closing it.
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.temporary) == 1 && self.temporary.load(Ordering::Relaxed) {
+            let _ = AnnDataTrait::close(self);
         }
     }
 }
@@ -578,6 +1704,37 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         (inner.n_obs(), inner.n_vars())
     }
 
+    fn set_n_obs(&self, n: usize) -> Result<()> {
+        self.adata.try_inner()?.set_n_obs(n)
+    }
+
+    fn set_n_vars(&self, n: usize) -> Result<()> {
+        self.adata.try_inner()?.set_n_vars(n)
+    }
+
+    fn set_coerce_bool_columns(&self, coerce: bool) -> Result<()> {
+        self.adata.try_inner()?.set_coerce_bool_columns(coerce);
+        Ok(())
+    }
+
+    fn set_nan_as_null(&self, nan_as_null: bool) -> Result<()> {
+        self.adata.try_inner()?.set_nan_as_null(nan_as_null);
+        Ok(())
+    }
+
+    fn set_assume_sorted_indices(&self, assume_sorted_indices: bool) -> Result<()> {
+        self.adata.try_inner()?.set_assume_sorted_indices(assume_sorted_indices);
+        Ok(())
+    }
+
+    fn del_raw(&self) -> Result<()> {
+        self.adata.try_inner()?.del_raw()
+    }
+
+    fn densify_x(&self, layer: &str, chunk_size: usize) -> Result<()> {
+        self.adata.try_inner()?.densify_x(layer, chunk_size)
+    }
+
     fn obs_names(&self) -> DataFrameIndex {
         self.adata.inner().obs_names()
     }
@@ -587,10 +1744,14 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         self.adata.inner().obs_ix(bounds.iter().map(|x| x.extract::<&str>().unwrap()))
     }
 
+    fn obs_query(&self, expr: &str) -> Result<Vec<usize>> {
+        self.adata.try_inner()?.get_obs().query(expr)
+    }
+
     fn set_obs_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
         let obs_names: Result<DataFrameIndex> =
             names.iter()?.map(|x| Ok(x?.extract::<String>()?)).collect();
-        self.adata.inner().set_obs_names(obs_names?)
+        self.adata.try_inner()?.set_obs_names(obs_names?)
     }
 
     fn var_names(&self) -> DataFrameIndex {
@@ -602,10 +1763,26 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         self.adata.inner().var_ix(bounds.iter().map(|x| x.extract::<&str>().unwrap()))
     }
 
+    fn read_obs(&self) -> Result<DataFrame> {
+        self.adata.inner().read_obs()
+    }
+
+    fn read_var(&self) -> Result<DataFrame> {
+        self.adata.inner().read_var()
+    }
+
+    fn read_obs_select(&self, select: &SelectInfoElem) -> Result<DataFrame> {
+        self.adata.inner().view(select.clone(), SelectInfoElem::full())?.read_obs()
+    }
+
+    fn read_var_select(&self, select: &SelectInfoElem) -> Result<DataFrame> {
+        self.adata.inner().view(SelectInfoElem::full(), select.clone())?.read_var()
+    }
+
     fn set_var_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
         let var_names: Result<DataFrameIndex> =
             names.iter()?.map(|x| Ok(x?.extract::<String>()?)).collect();
-        self.adata.inner().set_var_names(var_names?)
+        self.adata.try_inner()?.set_var_names(var_names?)
     }
 
     fn get_x(&self) -> Option<PyArrayElem> {
@@ -614,7 +1791,14 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         if x.is_empty() {
             None
         } else {
-            Some(x.clone().into())
+            let adata = self.adata.clone();
+            let elem = PyArrayElem::from(x.clone()).with_names(move |axis, names| {
+                let adata = adata.lock();
+                let adata = adata.as_ref().context("AnnData has been closed")?;
+                let names = names.iter().map(|x| x.as_str());
+                if axis == 0 { adata.obs_ix(names) } else { adata.var_ix(names) }
+            });
+            Some(elem)
         }
     }
     fn get_obs(&self) -> Option<PyDataFrameElem> {
@@ -692,7 +1876,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
     }
 
     fn set_x(&self, data: Option<PyArrayData>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(d) = data {
             inner.set_x::<ArrayData>(d.into())?;
         } else {
@@ -701,7 +1885,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
     fn set_obs(&self, obs: Option<Bound<'_, PyAny>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(x) = obs {
             let py = x.py();
             let ob = if isinstance_of_pandas(&x)? {
@@ -718,7 +1902,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
     fn set_var(&self, var: Option<Bound<'_, PyAny>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(x) = var {
             let py = x.py();
             let ob = if isinstance_of_pandas(&x)? {
@@ -734,8 +1918,14 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         }
         Ok(())
     }
+    fn set_obs_from_parquet(&self, path: &Path) -> Result<()> {
+        self.adata.try_inner()?.set_obs_from_parquet(path)
+    }
+    fn set_var_from_parquet(&self, path: &Path) -> Result<()> {
+        self.adata.try_inner()?.set_var_from_parquet(path)
+    }
     fn set_uns(&self, uns: Option<HashMap<String, PyData>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(u) = uns {
             inner.set_uns(u.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -744,7 +1934,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
     fn set_obsm(&self, obsm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(o) = obsm {
             inner.set_obsm(o.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -753,7 +1943,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
     fn set_obsp(&self, obsp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(o) = obsp {
             inner.set_obsp(o.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -762,7 +1952,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
     fn set_varm(&self, varm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(v) = varm {
             inner.set_varm(v.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -771,7 +1961,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
     fn set_varp(&self, varp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(v) = varp {
             inner.set_varp(v.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -780,7 +1970,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
     fn set_layers(&self, varp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.adata.inner();
+        let inner = self.adata.try_inner()?;
         if let Some(v) = varp {
             inner.set_layers(v.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -789,6 +1979,29 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
 
+    fn neighbors(&self, key: Option<&str>) -> Result<Option<Neighbors>> {
+        self.adata.inner().neighbors(key)
+    }
+    fn set_neighbors(&self, key: Option<&str>, neighbors: Neighbors) -> Result<()> {
+        self.adata.try_inner()?.set_neighbors(key, neighbors)
+    }
+    fn spatial(&self) -> Result<Option<Spatial>> {
+        self.adata.inner().spatial()
+    }
+    fn set_spatial(&self, spatial: Spatial) -> Result<()> {
+        self.adata.try_inner()?.set_spatial(spatial)
+    }
+    fn embedding(&self, basis: &str) -> Result<Option<ArrayData>> {
+        self.adata.inner().embedding(basis)
+    }
+    fn calculate_qc_metrics(&self) -> Result<()> {
+        self.adata.try_inner()?.calculate_qc_metrics()?;
+        Ok(())
+    }
+    fn read_many(&self, requests: &[(ElemSlot, &str)]) -> HashMap<String, Result<Data>> {
+        self.adata.inner().read_many(requests)
+    }
+
     fn subset(
         &self,
         py: Python<'_>,
@@ -828,12 +2041,12 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
                 let obs_idx = inner.obs_names();
                 if !obs_idx.is_empty() {
                     adata.set_obs_names(obs_idx.select(&slice[0]))?;
-                    adata.set_obs(inner.read_obs()?.select_axis(0, &slice[0]))?;
+                    adata.set_obs(inner.get_obs().inner().select_axis(0, &slice[0])?)?;
                 }
                 let var_idx = inner.var_names();
                 if !var_idx.is_empty() {
                     adata.set_var_names(var_idx.select(&slice[1]))?;
-                    adata.set_var(inner.read_var()?.select_axis(0, &slice[1]))?;
+                    adata.set_var(inner.get_var().inner().select_axis(0, &slice[1])?)?;
                 }
             }
             {
@@ -913,15 +2126,75 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         self.adata.inner().get_x().chunked(chunk_size).into()
     }
 
-    fn write(&self, filename: PathBuf, backend: Option<&str>) -> Result<()> {
+    fn chunk_ranges(&self, n_chunks: usize) -> Result<Vec<(usize, usize)>> {
+        let ranges = self.adata.inner().chunk_ranges(n_chunks)?;
+        Ok(ranges.into_iter().map(|x| (x.start, x.end)).collect())
+    }
+
+    fn sample_obs(&self, n: usize, seed: u64) -> Result<Vec<usize>> {
+        self.adata.inner().sample_obs(n, seed)
+    }
+
+    fn append(
+        &self,
+        x: PyArrayData,
+        obs: Option<Bound<'_, PyAny>>,
+        obs_names: Option<Vec<String>>,
+    ) -> Result<()> {
+        let obs = match obs {
+            Some(x) => {
+                let py = x.py();
+                let ob = if isinstance_of_pandas(&x)? {
+                    py.import_bound("polars")?.call_method1("from_pandas", (x, ))?
+                } else if x.is_instance_of::<pyo3::types::PyDict>() {
+                    py.import_bound("polars")?.call_method1("from_dict", (x, ))?
+                } else {
+                    x
+                };
+                ob.extract::<PyDataFrame>()?.0
+            }
+            None => DataFrame::empty(),
+        };
+        self.adata.inner().append::<ArrayData>(x.into(), obs, obs_names)
+    }
+
+    fn write(&self, filename: PathBuf, backend: Option<&str>, columns: [Option<&[String]>; 2], overwrite: bool) -> Result<()> {
+        match backend.unwrap_or(H5::NAME) {
+            H5::NAME => self.adata.inner().write_columns::<H5, _>(filename, columns[0], columns[1], overwrite),
+            x => bail!("Unsupported backend: {}", x),
+        }
+    }
+
+    fn save_as(&self, filename: PathBuf, remove_old: bool, overwrite: bool) -> Result<()> {
+        let old = self.filename();
+        self.adata.try_inner()?.save_as(&filename, overwrite)?;
+        *self.filename.lock() = filename;
+        let temporary = self.temporary.swap(false, Ordering::Relaxed);
+        if remove_old || temporary {
+            std::fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+
+    fn repack(&self) -> Result<()> {
+        self.adata.try_inner()?.repack()
+    }
+
+    fn filter_to(
+        &self,
+        filename: PathBuf,
+        obs: Option<AxisSelection>,
+        var: Option<AxisSelection>,
+        backend: Option<&str>,
+    ) -> Result<()> {
         match backend.unwrap_or(H5::NAME) {
-            H5::NAME => self.adata.inner().write::<H5, _>(filename),
+            H5::NAME => self.adata.inner().filter_to::<H5, _>(filename, obs, var),
             x => bail!("Unsupported backend: {}", x),
         }
     }
 
     fn copy(&self, filename: PathBuf, backend: Option<&str>) -> Result<AnnData> {
-        AnnDataTrait::write(self, filename.clone(), backend)?;
+        AnnDataTrait::write(self, filename.clone(), backend, [None, None], true)?;
         AnnData::new_from(filename, "r+", backend)
     }
 
@@ -930,7 +2203,7 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
     }
 
     fn filename(&self) -> PathBuf {
-        self.filename.clone()
+        self.filename.lock().clone()
     }
 
     fn backend(&self) -> &str {
@@ -941,6 +2214,26 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         self.adata.is_empty()
     }
 
+    fn is_dirty(&self) -> bool {
+        self.adata.inner().is_dirty()
+    }
+
+    fn generation(&self) -> Result<u64> {
+        Ok(self.adata.try_inner()?.generation())
+    }
+
+    fn last_modified(&self) -> Option<f64> {
+        self.adata.inner().last_modified()
+    }
+
+    fn validate(&self) -> Result<Vec<String>> {
+        Ok(self.adata.inner().validate()?.iter().map(|x| x.to_string()).collect())
+    }
+
+    fn usage(&self) -> Result<UsageReport> {
+        self.adata.inner().usage()
+    }
+
     fn show(&self) -> String {
         if self.is_closed() {
             "Closed AnnData object".to_string()
@@ -954,6 +2247,8 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
             let file = match mode {
                 "r" => B::open(self.filename())?,
                 "r+" => B::open_rw(self.filename())?,
+                "swmr" => B::open_swmr(self.filename(), false)?,
+                "swmr+" => B::open_swmr(self.filename(), true)?,
                 _ => bail!("Unknown mode: {}", mode),
             };
             self.adata.insert(anndata::AnnData::<B>::open(file)?);
@@ -961,9 +2256,16 @@ impl<B: Backend> AnnDataTrait for InnerAnnData<B> {
         Ok(())
     }
 
+    fn flush(&self) -> Result<()> {
+        self.adata.inner().flush()
+    }
+
     fn close(&self) -> Result<()> {
         if let Some(inner) = self.adata.extract() {
             inner.close()?;
+            if self.temporary.load(Ordering::Relaxed) {
+                std::fs::remove_file(self.filename())?;
+            }
         }
         Ok(())
     }
@@ -1,7 +1,7 @@
 use crate::container::{
     PyArrayElem, PyAxisArrays, PyChunkedArray, PyDataFrameElem, PyElemCollection,
 };
-use crate::data::{isinstance_of_pandas, to_select_elem, PyArrayData, PyData};
+use crate::data::{isinstance_of_pandas, to_select_elem, PyArrayData, PyArrayDict, PyData, PyIndex};
 use crate::{AnnData, PyAnnData};
 
 use anndata::container::Slot;
@@ -10,7 +10,7 @@ use anndata::{self, ArrayElemOp, Data, ArrayOp};
 use anndata::{AnnDataOp, Backend};
 use anndata::{AxisArraysOp, ElemCollectionOp};
 use anndata_hdf5::H5;
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use downcast_rs::{impl_downcast, Downcast};
 use pyo3::prelude::*;
 use pyo3_polars::PyDataFrame;
@@ -114,6 +114,36 @@ pub enum AnnDataFile<'py> {
     Data(Bound<'py, AnnData>),
 }
 
+/// Open the component AnnData objects of a dataset. Backed AnnData objects are
+/// taken over by the dataset.
+fn open_components<B: Backend>(adatas: Vec<(String, AnnDataFile)>) -> Result<Vec<(String, anndata::AnnData<B>)>> {
+    adatas.into_iter().map(|(key, data_file)| {
+        let adata = match data_file {
+            AnnDataFile::Data(data) => data.borrow().take_inner::<B>()
+                .with_context(|| format!("AnnData '{}' is closed", key))?,
+            AnnDataFile::Path(path) => anndata::AnnData::open(B::open(path)?)?,
+        };
+        Ok((key, adata))
+    }).collect()
+}
+
+impl AnnDataSet {
+    /// See `AnnData.concat_lazy`.
+    pub(crate) fn concat_lazy(
+        adatas: Vec<(String, AnnDataFile)>,
+        add_key: &str,
+        backend: Option<&str>,
+    ) -> Result<Self> {
+        match backend.unwrap_or(H5::NAME) {
+            H5::NAME => {
+                let anndatas = open_components::<H5>(adatas)?;
+                Ok(anndata::AnnDataSet::concat_lazy(anndatas, add_key)?.into())
+            }
+            x => bail!("Unsupported backend: {}", x),
+        }
+    }
+}
+
 #[pymethods]
 impl AnnDataSet {
     #[new]
@@ -126,15 +156,7 @@ impl AnnDataSet {
     ) -> Result<Self> {
         match backend.unwrap_or(H5::NAME) {
             H5::NAME => {
-                let anndatas = adatas.into_iter().map(|(key, data_file)| {
-                    let adata = match data_file {
-                        AnnDataFile::Data(data) => data.borrow().take_inner::<H5>().unwrap(),
-                        AnnDataFile::Path(path) => {
-                            anndata::AnnData::open(H5::open(path).unwrap()).unwrap()
-                        }
-                    };
-                    (key, adata)
-                });
+                let anndatas = open_components::<H5>(adatas)?;
                 Ok(anndata::AnnDataSet::new(anndatas, filename, add_key)?.into())
             }
             _ => todo!(),
@@ -175,10 +197,10 @@ impl AnnDataSet {
     ///
     /// Returns
     /// -------
-    /// list[str]
+    /// Index
     #[getter]
-    pub fn obs_names(&self) -> Vec<String> {
-        self.0.obs_names().into_vec()
+    pub fn obs_names(&self) -> PyIndex {
+        self.0.obs_names().into()
     }
     #[setter(obs_names)]
     pub fn set_obs_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
@@ -192,10 +214,10 @@ impl AnnDataSet {
     ///
     /// Returns
     /// -------
-    /// list[str]
+    /// Index
     #[getter]
-    pub fn var_names(&self) -> Vec<String> {
-        self.0.var_names().into_vec()
+    pub fn var_names(&self) -> PyIndex {
+        self.0.var_names().into()
     }
     #[setter(var_names)]
     pub fn set_var_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
@@ -262,8 +284,8 @@ impl AnnDataSet {
         self.0.get_obsm()
     }
     #[setter(obsm)]
-    pub fn set_obsm(&self, obsm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_obsm(obsm)
+    pub fn set_obsm(&self, obsm: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_obsm(obsm.map(Into::into))
     }
 
     #[getter(obsp)]
@@ -271,8 +293,8 @@ impl AnnDataSet {
         self.0.get_obsp()
     }
     #[setter(obsp)]
-    pub fn set_obsp(&self, obsp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_obsp(obsp)
+    pub fn set_obsp(&self, obsp: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_obsp(obsp.map(Into::into))
     }
 
     #[getter(varm)]
@@ -280,8 +302,8 @@ impl AnnDataSet {
         self.0.get_varm()
     }
     #[setter(varm)]
-    pub fn set_varm(&self, varm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_varm(varm)
+    pub fn set_varm(&self, varm: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_varm(varm.map(Into::into))
     }
 
     #[getter(varp)]
@@ -289,8 +311,8 @@ impl AnnDataSet {
         self.0.get_varp()
     }
     #[setter(varp)]
-    pub fn set_varp(&self, varp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        self.0.set_varp(varp)
+    pub fn set_varp(&self, varp: Option<PyArrayDict>) -> Result<()> {
+        self.0.set_varp(varp.map(Into::into))
     }
 
     /// Subsetting the AnnDataSet object.
@@ -507,7 +529,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
     fn set_obs_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
         let obs_names: Result<DataFrameIndex> =
             names.iter()?.map(|x| Ok(x?.extract::<String>()?)).collect();
-        self.inner().set_obs_names(obs_names?)
+        self.try_inner()?.set_obs_names(obs_names?)
     }
 
     fn obs_ix(&self, index: &Bound<'_, PyAny>) -> Result<Vec<usize>> {
@@ -522,7 +544,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
     fn set_var_names(&self, names: Bound<'_, PyAny>) -> Result<()> {
         let var_names: Result<DataFrameIndex> =
             names.iter()?.map(|x| Ok(x?.extract::<String>()?)).collect();
-        self.inner().set_var_names(var_names?)
+        self.try_inner()?.set_var_names(var_names?)
     }
 
     fn var_ix(&self, index: Bound<'_, PyAny>) -> Result<Vec<usize>> {
@@ -531,7 +553,18 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
     }
 
     fn get_x(&self) -> Option<PyArrayElem> {
-        Some(self.inner().x().into())
+        let x = self.inner().x();
+        if x.is_empty() {
+            return None;
+        }
+        let dataset = self.clone();
+        let elem = PyArrayElem::from(x).with_names(move |axis, names| {
+            let dataset = dataset.lock();
+            let dataset = dataset.as_ref().context("AnnDataSet has been closed")?;
+            let names = names.iter().map(|x| x.as_str());
+            if axis == 0 { dataset.obs_ix(names) } else { dataset.var_ix(names) }
+        });
+        Some(elem)
     }
     fn get_obs(&self) -> Option<PyDataFrameElem> {
         let inner = self.inner();
@@ -598,7 +631,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
     }
 
     fn set_obs(&self, obs: Option<Bound<'_, PyAny>>) -> Result<()> {
-        let inner = self.inner();
+        let inner = self.try_inner()?;
         if let Some(x) = obs {
             let py = x.py();
             let ob = if isinstance_of_pandas(&x)? {
@@ -615,7 +648,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
         Ok(())
     }
     fn set_var(&self, var: Option<Bound<'_, PyAny>>) -> Result<()> {
-        let inner = self.inner();
+        let inner = self.try_inner()?;
         if let Some(x) = var {
             let py = x.py();
             let ob = if isinstance_of_pandas(&x)? {
@@ -632,7 +665,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
         Ok(())
     }
     fn set_uns(&self, uns: Option<HashMap<String, PyData>>) -> Result<()> {
-        let inner = self.inner();
+        let inner = self.try_inner()?;
         if let Some(u) = uns {
             inner.set_uns(u.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -641,7 +674,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
         Ok(())
     }
     fn set_obsm(&self, obsm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.inner();
+        let inner = self.try_inner()?;
         if let Some(o) = obsm {
             inner.set_obsm(o.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -650,7 +683,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
         Ok(())
     }
     fn set_obsp(&self, obsp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.inner();
+        let inner = self.try_inner()?;
         if let Some(o) = obsp {
             inner.set_obsp(o.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -659,7 +692,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
         Ok(())
     }
     fn set_varm(&self, varm: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.inner();
+        let inner = self.try_inner()?;
         if let Some(v) = varm {
             inner.set_varm(v.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -668,7 +701,7 @@ impl<B: Backend> AnnDataSetTrait for Slot<anndata::AnnDataSet<B>> {
         Ok(())
     }
     fn set_varp(&self, varp: Option<HashMap<String, PyArrayData>>) -> Result<()> {
-        let inner = self.inner();
+        let inner = self.try_inner()?;
         if let Some(v) = varp {
             inner.set_varp(v.into_iter().map(|(k, v)| (k, v.into())))?;
         } else {
@@ -1,6 +1,7 @@
 use crate::data::{isinstance_of_pyanndata, isinstance_of_polars, PyArrayData, PyData};
 
 use std::ops::Deref;
+use std::path::Path;
 use polars::prelude::DataFrame;
 use pyo3::prelude::*;
 use pyo3::exceptions::PyTypeError;
@@ -9,7 +10,7 @@ use pyo3_polars::PyDataFrame;
 use anndata::{self, ArrayOp, ElemCollectionOp, ArrayElemOp};
 use anndata::{AnnDataOp, AxisArraysOp, ArrayData, Data, ReadArrayData, ReadData, Backend, WriteArrayData, HasShape};
 use anndata::data::{DataFrameIndex, SelectInfoElem, ArrayChunk, Shape};
-use anyhow::{Result, bail};
+use anyhow::{bail, Context, Result};
 
 pub struct PyAnnData<'py>(Bound<'py, PyAny>);
 
@@ -51,7 +52,8 @@ impl<'py> PyAnnData<'py> {
             .extract()
     }
 
-    pub fn from_anndata<B: Backend>(py: Python<'py>, inner: &anndata::AnnData<B>) -> Result<Self> {
+    /// Build a Python AnnData object by reading all elements of `inner` into memory.
+    pub fn from_anndata<A: AnnDataOp>(py: Python<'py>, inner: &A) -> Result<Self> {
         let adata = PyAnnData::new(py)?;
         {
             // Set X
@@ -73,35 +75,85 @@ impl<'py> PyAnnData<'py> {
             inner
                 .uns().keys()
                 .into_iter()
-                .try_for_each(|k| adata.uns().add(&k, inner.uns().get_item::<Data>(&k)?.unwrap()))?;
+                .try_for_each(|k| adata.uns().add(&k, inner.uns().get_item::<Data>(&k)?
+                    .with_context(|| format!("'{}' is missing from uns", k))?))?;
         }
         {
             // Set obsm
             inner
                 .obsm().keys()
                 .into_iter()
-                .try_for_each(|k| adata.obsm().add(&k, inner.obsm().get_item::<ArrayData>(&k)?.unwrap()))?;
+                .try_for_each(|k| adata.obsm().add(&k, inner.obsm().get_item::<ArrayData>(&k)?
+                    .with_context(|| format!("'{}' is missing from obsm", k))?))?;
         }
         {
             // Set obsp
             inner
                 .obsp().keys()
                 .into_iter()
-                .try_for_each(|k| adata.obsp().add(&k, inner.obsp().get_item::<ArrayData>(&k)?.unwrap()))?;
+                .try_for_each(|k| adata.obsp().add(&k, inner.obsp().get_item::<ArrayData>(&k)?
+                    .with_context(|| format!("'{}' is missing from obsp", k))?))?;
         }
         {
             // Set varm
             inner
                 .varm().keys()
                 .into_iter()
-                .try_for_each(|k| adata.varm().add(&k, inner.varm().get_item::<ArrayData>(&k)?.unwrap()))?;
+                .try_for_each(|k| adata.varm().add(&k, inner.varm().get_item::<ArrayData>(&k)?
+                    .with_context(|| format!("'{}' is missing from varm", k))?))?;
         }
         {
             // Set varp
             inner
                 .varp().keys()
                 .into_iter()
-                .try_for_each(|k| adata.varp().add(&k, inner.varp().get_item::<ArrayData>(&k)?.unwrap()))?;
+                .try_for_each(|k| adata.varp().add(&k, inner.varp().get_item::<ArrayData>(&k)?
+                    .with_context(|| format!("'{}' is missing from varp", k))?))?;
+        }
+        {
+            // Set layers
+            inner
+                .layers().keys()
+                .into_iter()
+                .try_for_each(|k| adata.layers().add(&k, inner.layers().get_item::<ArrayData>(&k)?
+                    .with_context(|| format!("'{}' is missing from layers", k))?))?;
+        }
+        Ok(adata)
+    }
+
+    /// Write the Python AnnData object into a new backed AnnData file.
+    pub fn to_anndata<B: Backend, P: AsRef<Path>>(&self, filename: P) -> Result<anndata::AnnData<B>> {
+        let adata = anndata::AnnData::<B>::new(filename)?;
+        {
+            // Set X
+            adata.set_n_obs(self.getattr("n_obs")?.extract()?)?;
+            adata.set_n_vars(self.getattr("n_vars")?.extract()?)?;
+            if let Some(x) = ArrayElem(self.getattr("X")?).get::<ArrayData>()? {
+                adata.set_x(x)?;
+            }
+        }
+        {
+            // Set obs and var
+            adata.set_obs_names(self.getattr("obs_names")?.extract::<Vec<String>>()?.into())?;
+            adata.set_var_names(self.getattr("var_names")?.extract::<Vec<String>>()?.into())?;
+            adata.set_obs(self.read_obs()?)?;
+            adata.set_var(self.read_var()?)?;
+        }
+        {
+            // Set uns
+            for item in self.getattr("uns")?.call_method0("items")?.iter()? {
+                let (key, value): (String, Bound<'_, PyAny>) = item?.extract()?;
+                let data: PyData = value.extract().with_context(|| format!("cannot convert '{}' in uns", key))?;
+                adata.uns().add::<Data>(&key, data.into())?;
+            }
+        }
+        {
+            // Set obsm, obsp, varm, varp and layers
+            copy_axis_arrays(&self.getattr("obsm")?, adata.obsm())?;
+            copy_axis_arrays(&self.getattr("obsp")?, adata.obsp())?;
+            copy_axis_arrays(&self.getattr("varm")?, adata.varm())?;
+            copy_axis_arrays(&self.getattr("varp")?, adata.varp())?;
+            copy_axis_arrays(&self.getattr("layers")?, adata.layers())?;
         }
         Ok(adata)
     }
@@ -485,7 +537,14 @@ impl<'py> AxisArraysOp for AxisArrays<'py> {
         }
         let d = PyArrayData::from(data.into()).into_py(py);
         let new_d = if isinstance_of_polars(d.bind(py))? {
-            d.call_method0(py, "to_pandas")?
+            // Data frames in obsm and varm must be indexed by the names of the aligned axis.
+            let df = d.call_method0(py, "to_pandas")?;
+            match self.axis {
+                0 => df.setattr(py, "index", self.adata.getattr("obs_names")?)?,
+                1 => df.setattr(py, "index", self.adata.getattr("var_names")?)?,
+                _ => {}
+            }
+            df
         } else {
             d
         };
@@ -563,4 +622,13 @@ impl ArrayElemOp for ArrayElem<'_> {
         let array = self.0.extract::<PyArrayData>().unwrap();
         PyArrayIterator::new(array, chunk_size).unwrap()
     }
-}
\ No newline at end of file
+}
+
+fn copy_axis_arrays<O: AxisArraysOp>(from: &Bound<'_, PyAny>, to: O) -> Result<()> {
+    for item in from.call_method0("items")?.iter()? {
+        let (key, value): (String, Bound<'_, PyAny>) = item?.extract()?;
+        let data: PyArrayData = value.extract().with_context(|| format!("cannot convert '{}'", key))?;
+        to.add::<ArrayData>(&key, data.into())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use crate::data::{to_array_index, PyIndex};
+use super::backed::{check_overwrite, to_pandas, AnnData};
+
+use anndata::data::{BoundedSelectInfoElem, DataFrameIndex, SelectInfoElem};
+use anndata::AxisSelection;
+use anyhow::{anyhow, ensure, Result};
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use std::path::PathBuf;
+
+/** A view of a subset of a backed AnnData object.
+
+    Views are created by indexing an `AnnData` object, e.g., `adata[0:100, ["GeneA", "GeneB"]]`,
+    and can be indexed again, with positions and names relative to the view.
+    No data is read when a view is created; elements are read, restricted to
+    the selected observations and variables, when they are accessed.
+    A view becomes invalid, and raises an error when used, once the underlying
+    object is subset or appended to in place.
+
+    See Also
+    --------
+    AnnData.subset
+*/
+#[pyclass(name = "AnnDataView")]
+pub struct AnnDataView {
+    adata: AnnData,
+    obs: SelectInfoElem,
+    var: SelectInfoElem,
+    generation: u64,
+}
+
+impl AnnDataView {
+    pub(crate) fn new(adata: AnnData) -> Result<Self> {
+        let generation = adata.0.generation()?;
+        Ok(Self { adata, obs: SelectInfoElem::full(), var: SelectInfoElem::full(), generation })
+    }
+
+    /// Return an error if the underlying object has been subset or appended
+    /// to since the view was created.
+    fn check(&self) -> Result<()> {
+        ensure!(
+            self.adata.0.generation()? == self.generation,
+            "the view is no longer valid as '{}' has been subset or resized",
+            self.adata.filename().display(),
+        );
+        Ok(())
+    }
+
+    /// Select from the view with a subscript such as `[obs, var]` or `[obs]`.
+    pub(crate) fn select(&self, subscript: &Bound<'_, PyAny>) -> PyResult<Self> {
+        self.check()?;
+        let obs_names = self.obs_index();
+        let var_names = self.var_index();
+        let resolve = move |axis: usize, names: &[String]| {
+            let (index, name) = if axis == 0 { (&obs_names, "obs") } else { (&var_names, "var") };
+            names.iter().map(|x| index.get_index(x).ok_or_else(||
+                anyhow!("unknown {} name: {}", name, x)
+            )).collect::<Result<Vec<_>>>()
+        };
+        let select = to_array_index(subscript, &[self.n_obs()?, self.n_vars()?], Some(&resolve))?.select;
+        let [obs, var] = select.as_ref() else { unreachable!() };
+        let (n_obs, n_vars) = self.adata.shape();
+        Ok(Self {
+            adata: self.adata.clone(),
+            obs: self.obs.compose(n_obs, obs).map_err(|e| PyKeyError::new_err(e.to_string()))?,
+            var: self.var.compose(n_vars, var).map_err(|e| PyKeyError::new_err(e.to_string()))?,
+            generation: self.generation,
+        })
+    }
+
+    fn selection(&self) -> [SelectInfoElem; 2] {
+        [self.obs.clone(), self.var.clone()]
+    }
+
+    fn obs_index(&self) -> DataFrameIndex {
+        self.adata.0.obs_names().select(&self.obs)
+    }
+
+    fn var_index(&self) -> DataFrameIndex {
+        self.adata.0.var_names().select(&self.var)
+    }
+}
+
+#[pymethods]
+impl AnnDataView {
+    /// Shape of the view (#observations, #variables).
+    #[getter]
+    fn shape(&self) -> Result<(usize, usize)> {
+        Ok((self.n_obs()?, self.n_vars()?))
+    }
+
+    /// Number of observations in the view.
+    #[getter]
+    fn n_obs(&self) -> Result<usize> {
+        self.check()?;
+        Ok(BoundedSelectInfoElem::new(&self.obs, self.adata.n_obs()).len())
+    }
+
+    /// Number of variables in the view.
+    #[getter]
+    fn n_vars(&self) -> Result<usize> {
+        self.check()?;
+        Ok(BoundedSelectInfoElem::new(&self.var, self.adata.n_vars()).len())
+    }
+
+    /// Names of the selected observations.
+    #[getter]
+    fn obs_names(&self) -> Result<PyIndex> {
+        self.check()?;
+        Ok(self.obs_index().into())
+    }
+
+    /// Names of the selected variables.
+    #[getter]
+    fn var_names(&self) -> Result<PyIndex> {
+        self.check()?;
+        Ok(self.var_index().into())
+    }
+
+    /// The selected part of the data matrix, read on access.
+    #[getter(X)]
+    fn x(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.check()?;
+        self.adata.0.get_x()
+            .map(|x| x.read(py, &self.selection()).map(Bound::unbind))
+            .transpose()
+    }
+
+    /// Observation annotations of the selected observations as a pandas
+    /// DataFrame, read on access.
+    #[getter]
+    fn obs<'py>(&self, py: Python<'py>) -> Result<Bound<'py, PyAny>> {
+        self.check()?;
+        to_pandas(py, self.adata.0.read_obs_select(&self.obs)?, self.obs_index())
+    }
+
+    /// Variable annotations of the selected variables as a pandas DataFrame,
+    /// read on access.
+    #[getter]
+    fn var<'py>(&self, py: Python<'py>) -> Result<Bound<'py, PyAny>> {
+        self.check()?;
+        to_pandas(py, self.adata.0.read_var_select(&self.var)?, self.var_index())
+    }
+
+    fn __getitem__(&self, subscript: &Bound<'_, PyAny>) -> PyResult<Self> {
+        self.select(subscript)
+    }
+
+    /// Return the subset as an in-memory AnnData object.
+    ///
+    /// Returns
+    /// -------
+    /// anndata.AnnData
+    #[pyo3(text_signature = "($self)")]
+    fn to_memory<'py>(&self, py: Python<'py>) -> Result<PyObject> {
+        self.check()?;
+        let adata = self.adata.0.subset(py, &self.selection(), None, false, None)?;
+        Ok(adata.expect("subset did not return an AnnData object"))
+    }
+
+    /// Write the subset to a new file.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename: Path
+    ///     File name of the output `.h5ad` file.
+    /// backend: str | None
+    ///     The backend to use. Currently "hdf5" is the only supported backend.
+    /// overwrite: bool
+    ///     Whether to replace `filename` if it already exists.
+    #[pyo3(
+        signature = (filename, backend=None, overwrite=true),
+        text_signature = "($self, filename, backend=None, overwrite=True)",
+    )]
+    fn write(&self, filename: PathBuf, backend: Option<&str>, overwrite: bool) -> Result<()> {
+        self.check()?;
+        check_overwrite(&filename, overwrite)?;
+        let [obs, var] = self.selection();
+        self.adata.0.filter_to(filename, Some(AxisSelection::Index(obs)), Some(AxisSelection::Index(var)), backend)
+    }
+
+    fn __repr__(&self) -> String {
+        match self.shape() {
+            Ok((n_obs, n_vars)) => format!(
+                "View of AnnData object with n_obs x n_vars = {} x {} backed at '{}'",
+                n_obs,
+                n_vars,
+                self.adata.filename().display(),
+            ),
+            Err(_) => format!("Invalid view of AnnData object backed at '{}'", self.adata.filename().display()),
+        }
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
+
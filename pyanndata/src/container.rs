@@ -1,13 +1,24 @@
 mod traits;
 
-use crate::data::{PyData, PyArrayData};
+use crate::data::{
+    isinstance_of_arr, isinstance_of_csc, isinstance_of_csr, isinstance_of_polars, numpy_dtype_name, to_array_index,
+    to_scalar_type, FromPython, IntoPython, NameResolver, PyData, PyArrayData,
+};
 
+use numpy::IntoPyArray;
 use pyo3::prelude::*;
-use pyo3_polars::PySeries;
+use pyo3::exceptions::{PyMemoryError, PyValueError};
+use pyo3::types::{PyDict, PyTuple};
+use pyo3_polars::{PyDataFrame, PySeries};
 use traits::{ElemTrait, ArrayElemTrait, DataFrameElemTrait, AxisArrayTrait};
-use anyhow::Result;
+use anndata::ArrayData;
+use anndata::backend::DataType;
+use anndata::data::{DynScalar, SelectInfoElem};
+use anndata::container::ChunkStrategy;
+use anyhow::{bail, Result};
+use std::path::PathBuf;
 
-use self::traits::{ElemCollectionTrait, ChunkedArrayTrait};
+use self::traits::{ElemCollectionTrait, ChunkedArrayTrait, ShuffledChunksTrait};
 
 
 #[pyclass]
@@ -38,6 +49,21 @@ impl PyElem {
         self.0.get(subscript)
     }
 
+    /// Read a custom attribute of the element. See `PyArrayElem.get_attr`.
+    #[pyo3(text_signature = "($self, name)")]
+    fn get_attr(&self, py: Python<'_>, name: &str) -> Result<PyObject> {
+        match self.0.get_attr(name)? {
+            Some(value) => Ok(value.into_python(py)?),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Set a custom attribute of the element. See `PyArrayElem.set_attr`.
+    #[pyo3(text_signature = "($self, name, value)")]
+    fn set_attr(&self, name: &str, value: &Bound<'_, PyAny>) -> Result<()> {
+        self.0.set_attr(name, DynScalar::from_python(value)?)
+    }
+
     fn __repr__(&self) -> String {
         self.0.show()
     }
@@ -47,15 +73,46 @@ impl PyElem {
     }
 }
 
+/// Maximum number of entries an element can have to be implicitly converted
+/// to a dense array.
+pub(crate) const MAX_DENSE_SIZE: usize = 1 << 31;
+
 /// An element that stores array objects such as dense arrays and sparse matrices.
 /// Array elements support row and column indexing.
 #[pyclass]
-#[repr(transparent)]
-pub struct PyArrayElem(Box<dyn ArrayElemTrait>);
+pub struct PyArrayElem {
+    elem: Box<dyn ArrayElemTrait>,
+    names: Option<Box<NameResolver>>,
+}
 
 impl<T: ArrayElemTrait + 'static> From<T> for PyArrayElem {
     fn from(elem: T) -> Self {
-        Self(Box::new(elem))
+        Self { elem: Box::new(elem), names: None }
+    }
+}
+
+impl PyArrayElem {
+    /// Allow indexing the element by observation and variable names.
+    pub(crate) fn with_names<F>(mut self, names: F) -> Self
+    where
+        F: Fn(usize, &[String]) -> Result<Vec<usize>> + Send + 'static,
+    {
+        self.names = Some(Box::new(names));
+        self
+    }
+
+    /// Read the whole element.
+    pub(crate) fn read_all<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let full: Vec<_> = self.elem.shape().iter().map(|_| SelectInfoElem::full()).collect();
+        self.read(py, &full)
+    }
+
+    /// Read a selection, straight into numpy arrays if possible.
+    pub(crate) fn read<'py>(&self, py: Python<'py>, slice: &[SelectInfoElem]) -> PyResult<Bound<'py, PyAny>> {
+        match self.elem.get_numpy(py, slice)? {
+            Some(data) => Ok(data.into_bound(py)),
+            None => Ok(PyArrayData::from(self.elem.get(slice)?).into_py(py).into_bound(py)),
+        }
     }
 }
 
@@ -66,23 +123,125 @@ impl PyArrayElem {
     /// the in-memory cache.
     #[pyo3(text_signature = "($self)")]
     fn enable_cache(&self) {
-        self.0.enable_cache();
+        self.elem.enable_cache();
     }
 
     /// Disable caching. In-memory cache will be cleared immediately.
     #[pyo3(text_signature = "($self)")]
     fn disable_cache(&self) {
-        self.0.disable_cache();
+        self.elem.disable_cache();
+    }
+
+    /// Set the size of the cache of decompressed chunks of a dense array.
+    ///
+    /// Reading a compressed array decompresses whole chunks. If they do not
+    /// fit in the cache, which is 1 MiB by default, reading nearby rows
+    /// decompresses the same chunks again. `chunked` already grows the cache to
+    /// hold a full row of chunks unless the size has been set with this method.
+    ///
+    /// Parameters
+    /// ----------
+    /// nbytes : int
+    ///     Size of the cache in bytes.
+    #[pyo3(text_signature = "($self, nbytes)")]
+    fn set_chunk_cache(&self, nbytes: usize) -> Result<()> {
+        self.elem.set_chunk_cache(nbytes)
     }
 
     /// Shape of array.
     #[getter]
     fn shape(&self) -> Vec<usize> {
-        self.0.shape()
+        self.elem.shape()
     }
 
-    fn __getitem__(&self, subscript: &Bound<'_, PyAny>) -> Result<PyArrayData> {
-        self.0.get(subscript)
+    /// The numpy dtype of the element, e.g., `"float32"`. For sparse matrices
+    /// this is the dtype of the stored values. None for categorical arrays
+    /// and data frames.
+    #[getter]
+    fn dtype(&self) -> Result<Option<&'static str>> {
+        match self.elem.dtype()? {
+            DataType::Array(ty) | DataType::CsrMatrix(ty) | DataType::CscMatrix(ty) =>
+                Ok(Some(numpy_dtype_name(ty))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Select a subset of the array using numpy-style indexing, e.g.,
+    /// `x[10:20, [0, 5, 7]]`, `x[mask, :]` or `x[..., 0]`. The `X` matrix can also
+    /// be indexed by observation and variable names, e.g., `adata.X[:, "GeneA"]`.
+    /// Scalar indices remove the corresponding axis from dense results.
+    fn __getitem__(&self, py: Python<'_>, subscript: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let index = to_array_index(subscript, &self.elem.shape(), self.names.as_deref())?;
+        let data = self.read(py, index.select.as_ref())?;
+        if index.squeeze.is_empty() || !isinstance_of_arr(&data)? {
+            Ok(data.unbind())
+        } else if index.squeeze.len() == index.select.as_ref().len() {
+            Ok(data.get_item(PyTuple::empty_bound(py))?.unbind())
+        } else {
+            Ok(data.call_method1("squeeze", (PyTuple::new_bound(py, &index.squeeze),))?.unbind())
+        }
+    }
+
+    /// Convert the element to a numpy array, so that it can be passed to
+    /// functions expecting array-like objects, e.g., `np.asarray(adata.X)`.
+    ///
+    /// Sparse matrices are densified. To avoid accidentally loading a huge
+    /// matrix into memory, this fails if the element has more than 2^31
+    /// entries. Use `toarray()` to bypass the check.
+    #[pyo3(signature = (dtype=None, copy=None))]
+    fn __array__<'py>(
+        &self,
+        py: Python<'py>,
+        dtype: Option<&Bound<'py, PyAny>>,
+        copy: Option<bool>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        if copy == Some(false) {
+            return Err(PyValueError::new_err("a copy is always made when reading an element"));
+        }
+        let size: usize = self.elem.shape().iter().product();
+        if size > MAX_DENSE_SIZE {
+            return Err(PyMemoryError::new_err(format!(
+                "refusing to densify an array with {} entries, use `toarray()` if this is intended",
+                size,
+            )));
+        }
+        let arr = self.toarray(py)?;
+        match dtype {
+            Some(dtype) => arr.call_method1("astype", (dtype,)),
+            None => Ok(arr),
+        }
+    }
+
+    /// Read the whole element as a dense numpy array.
+    ///
+    /// Returns
+    /// -------
+    /// np.ndarray
+    #[pyo3(text_signature = "($self)")]
+    fn toarray<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let data = self.read_all(py)?;
+        if isinstance_of_csr(&data)? || isinstance_of_csc(&data)? {
+            data.call_method0("toarray")
+        } else {
+            py.import_bound("numpy")?.call_method1("asarray", (data,))
+        }
+    }
+
+    /// Read the whole element as a CSR matrix.
+    ///
+    /// Returns
+    /// -------
+    /// scipy.sparse.csr_matrix
+    #[pyo3(text_signature = "($self)")]
+    fn tocsr<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let data = self.read_all(py)?;
+        if isinstance_of_csr(&data)? {
+            Ok(data)
+        } else if isinstance_of_csc(&data)? {
+            data.call_method0("tocsr")
+        } else {
+            py.import_bound("scipy.sparse")?.call_method1("csr_matrix", (data,))
+        }
     }
 
     /// Return a chunk of the matrix with random indices.
@@ -109,30 +268,232 @@ impl PyArrayElem {
         replace: bool,
         seed: u64,
     ) -> Result<PyArrayData> {
-        self.0.chunk(size, replace, seed).map(PyArrayData::from)
+        self.elem.chunk(size, replace, seed).map(PyArrayData::from)
     }
 
     /// Return an iterator over the rows of the matrix.
     ///
+    /// Exactly one of `chunk_size` and `target_nnz` must be given.
+    ///
     /// Parameters
     /// ----------
     /// chunk_size
     ///     Number of rows of a single chunk.
+    /// target_nnz
+    ///     Approximate number of non-zero entries of a single chunk. For CSR
+    ///     matrices, this gives chunks that take similar time to read and
+    ///     process when the number of entries per row varies a lot.
     ///
     /// Returns
     /// -------
     /// An iterator, of which the elements are matrices.
-    #[pyo3(text_signature = "($self, chunk_size)")]
-    pub fn chunked(&self, chunk_size: usize) -> PyChunkedArray {
-        self.0.chunked(chunk_size)
+    #[pyo3(
+        signature = (chunk_size=None, *, target_nnz=None),
+        text_signature = "($self, chunk_size=None, *, target_nnz=None)",
+    )]
+    pub fn chunked(&self, chunk_size: Option<usize>, target_nnz: Option<usize>) -> Result<PyChunkedArray> {
+        let strategy = match (chunk_size, target_nnz) {
+            (Some(n), None) => ChunkStrategy::ByRows(n),
+            (None, Some(n)) => ChunkStrategy::ByNnz(n),
+            _ => bail!("exactly one of 'chunk_size' and 'target_nnz' must be given"),
+        };
+        self.elem.chunked(strategy)
+    }
+
+    /// Sum of the array elements over a given axis.
+    ///
+    /// The data is read in chunks of rows, so backed matrices are never
+    /// loaded into memory at once.
+    ///
+    /// Parameters
+    /// ----------
+    /// axis: int | None
+    ///     Axis along which the sum is computed. 0 returns the column sums,
+    ///     1 returns the row sums, and None returns the sum of all elements.
+    ///
+    /// Returns
+    /// -------
+    /// np.ndarray | float
+    #[pyo3(
+        signature = (axis=None),
+        text_signature = "($self, axis=None)",
+    )]
+    pub fn sum(&self, py: Python<'_>, axis: Option<usize>) -> Result<PyObject> {
+        match axis {
+            Some(axis) => Ok(PyArrayData::from(self.elem.sum(axis)?).into_py(py)),
+            None => {
+                let sum: ndarray::Array1<f64> = self.elem.sum(0)?.try_into()?;
+                Ok(sum.sum().into_py(py))
+            }
+        }
+    }
+
+    /// Variance of the array elements over a given axis.
+    ///
+    /// The variance is computed in a single pass over chunks of rows using
+    /// Welford's algorithm, so backed matrices are never loaded into memory
+    /// at once. Zeros that are not stored in sparse matrices are included.
+    ///
+    /// Parameters
+    /// ----------
+    /// axis: int
+    ///     Axis along which the variance is computed. 0 returns the variance
+    ///     of each column, e.g., each gene, and 1 that of each row.
+    /// ddof: int
+    ///     The divisor is `n - ddof`, where `n` is the number of elements.
+    ///
+    /// Returns
+    /// -------
+    /// np.ndarray
+    #[pyo3(
+        signature = (axis, ddof=0),
+        text_signature = "($self, axis, ddof=0)",
+    )]
+    pub fn var(&self, axis: usize, ddof: usize) -> Result<PyArrayData> {
+        Ok(self.elem.var(axis, ddof)?.into())
+    }
+
+    /// Convert the element to the given dtype.
+    ///
+    /// Like `numpy.ndarray.astype`, the converted data is returned as a new
+    /// array and the element is left unchanged, so that
+    /// `adata.X = adata.X.astype("float32")` works as expected. With
+    /// `inplace=True`, the element is instead converted in the file, chunk by
+    /// chunk, so that it is never read into memory as a whole. Sparse matrices
+    /// keep their structure and only the stored values are converted. A warning
+    /// is logged if the conversion may lose precision, and an error is raised
+    /// if a value cannot be represented in `dtype`, in which case the element
+    /// is left unchanged.
+    ///
+    /// Parameters
+    /// ----------
+    /// dtype
+    ///     The target dtype, e.g., `"float32"` or `np.int64`.
+    /// inplace
+    ///     If True, convert the element in the file and return None.
+    ///
+    /// Returns
+    /// -------
+    /// np.ndarray | scipy.sparse.csr_matrix | scipy.sparse.csc_matrix | None
+    #[pyo3(
+        signature = (dtype, inplace=false),
+        text_signature = "($self, dtype, inplace=False)",
+    )]
+    pub fn astype(&self, dtype: &Bound<'_, PyAny>, inplace: bool) -> Result<Option<PyArrayData>> {
+        let ty = to_scalar_type(dtype)?;
+        if inplace {
+            self.elem.astype_in_place(ty)?;
+            Ok(None)
+        } else {
+            Ok(Some(self.elem.astype(ty)?.into()))
+        }
+    }
+
+    /// Apply a function to each chunk of rows of the element.
+    ///
+    /// Only one chunk is held in memory at a time, so this can be used for
+    /// custom preprocessing of backed matrices, e.g.,
+    /// `adata.X.map_chunks(np.log1p, inplace=True)`.
+    ///
+    /// Parameters
+    /// ----------
+    /// f: Callable
+    ///     Function called with each chunk, a numpy array or a scipy sparse
+    ///     matrix. With `inplace=True`, it must return an array of the same
+    ///     shape and format.
+    /// chunk_size: int
+    ///     Number of rows of a single chunk.
+    /// inplace: bool
+    ///     If True, replace each chunk by the result of `f`. The element is
+    ///     left unchanged if `f` fails. CSC matrices cannot be changed in place.
+    ///
+    /// Returns
+    /// -------
+    /// Iterator | None
+    ///     An iterator over the results of `f` in the order of the chunks, or
+    ///     None if `inplace=True`. The chunks are read and `f` is called as the
+    ///     iterator is consumed.
+    #[pyo3(
+        signature = (f, chunk_size=500, inplace=false),
+        text_signature = "($self, f, chunk_size=500, inplace=False)",
+    )]
+    pub fn map_chunks(&self, f: &Bound<'_, PyAny>, chunk_size: usize, inplace: bool) -> Result<Option<PyMappedChunks>> {
+        if inplace {
+            self.elem.map_chunks_in_place(chunk_size, &mut |chunk| {
+                let result = f.call1((PyArrayData::from(chunk),))?;
+                Ok(result.extract::<PyArrayData>()?.into())
+            })?;
+            Ok(None)
+        } else {
+            let chunks = self.elem.chunked(ChunkStrategy::ByRows(chunk_size))?;
+            Ok(Some(PyMappedChunks { chunks, f: f.clone().unbind() }))
+        }
+    }
+
+    /// Return an iterator over shuffled minibatches of rows.
+    ///
+    /// The iterator can be used directly as the source of a PyTorch
+    /// `IterableDataset`.
+    ///
+    /// Parameters
+    /// ----------
+    /// batch_size
+    ///     Number of rows of a single batch.
+    /// buffer_size
+    ///     If given, rows are only shuffled within a sliding buffer of this many
+    ///     rows. This keeps reads more sequential at the cost of a weaker shuffle.
+    /// seed
+    ///     Random seed.
+    ///
+    /// Returns
+    /// -------
+    /// An iterator, of which the elements are tuples of a matrix and the
+    /// global indices of its rows.
+    #[pyo3(
+        signature = (batch_size, buffer_size=None, seed=2022),
+        text_signature = "($self, batch_size, buffer_size=None, seed=2022)",
+    )]
+    pub fn shuffled_chunks(&self, batch_size: usize, buffer_size: Option<usize>, seed: u64) -> PyShuffledChunks {
+        self.elem.shuffled_chunks(batch_size, buffer_size, seed)
+    }
+
+    /// Read a custom attribute of the element, e.g., `units`.
+    ///
+    /// Parameters
+    /// ----------
+    /// name: str
+    ///
+    /// Returns
+    /// -------
+    /// str | int | float | bool | None
+    ///     The value of the attribute, or None if it does not exist.
+    #[pyo3(text_signature = "($self, name)")]
+    fn get_attr(&self, py: Python<'_>, name: &str) -> Result<PyObject> {
+        match self.elem.get_attr(name)? {
+            Some(value) => Ok(value.into_python(py)?),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Set a custom attribute of the element. The attribute is written to the
+    /// file, and attributes describing the encoding, e.g., `encoding-type`,
+    /// cannot be changed.
+    ///
+    /// Parameters
+    /// ----------
+    /// name: str
+    /// value: str | int | float | bool
+    #[pyo3(text_signature = "($self, name, value)")]
+    fn set_attr(&self, name: &str, value: &Bound<'_, PyAny>) -> Result<()> {
+        self.elem.set_attr(name, DynScalar::from_python(value)?)
     }
 
     fn __repr__(&self) -> String {
-        self.0.show()
+        self.elem.show()
     }
 
     fn __str__(&self) -> String {
-        self.0.show()
+        self.elem.show()
     }
 }
 
@@ -149,6 +510,137 @@ impl<T: DataFrameElemTrait + 'static> From<T> for PyDataFrameElem {
 
 #[pymethods]
 impl PyDataFrameElem {
+    /// Return the column names.
+    ///
+    /// Returns
+    /// -------
+    /// list[str]
+    #[pyo3(text_signature = "($self)")]
+    fn keys(&self) -> Vec<String> {
+        self.0.keys()
+    }
+
+    /// Summary statistics of the columns, similar to `pandas.DataFrame.describe`.
+    ///
+    /// Returns
+    /// -------
+    /// tuple[polars.DataFrame, dict[str, polars.DataFrame]]
+    ///     The count, mean, std, min and max of numeric columns, and the
+    ///     value counts of categorical columns.
+    #[pyo3(text_signature = "($self)")]
+    fn describe(&self, py: Python<'_>) -> Result<PyObject> {
+        self.0.describe(py)
+    }
+
+    /// Number of nulls in each column. Only the null masks of nullable columns
+    /// are read from the storage.
+    ///
+    /// Returns
+    /// -------
+    /// dict[str, int]
+    #[pyo3(text_signature = "($self)")]
+    fn null_counts<'py>(&self, py: Python<'py>) -> Result<Bound<'py, PyDict>> {
+        let counts = PyDict::new_bound(py);
+        for (name, n) in self.0.null_counts()? {
+            counts.set_item(name, n)?;
+        }
+        Ok(counts)
+    }
+
+    /// Change the order of the columns without moving their data.
+    ///
+    /// The new order is used when the dataframe is read or written. Columns
+    /// not in `order` are placed at the end, keeping their relative order.
+    ///
+    /// Parameters
+    /// ----------
+    /// order: list[str]
+    ///     Names of the columns in the new order.
+    #[pyo3(text_signature = "($self, order)")]
+    fn reorder_columns(&self, order: Vec<String>) -> Result<()> {
+        self.0.reorder_columns(&order)
+    }
+
+    /// Store a string column as a categorical array, in place.
+    ///
+    /// Only this column is rewritten in the file. The categories are the
+    /// unique values in the order they first appear. Categorical columns are
+    /// left unchanged.
+    ///
+    /// Parameters
+    /// ----------
+    /// column: str
+    ///     Name of the column.
+    #[pyo3(text_signature = "($self, column)")]
+    fn as_categorical(&self, column: &str) -> Result<()> {
+        self.0.as_categorical(column)
+    }
+
+    /// Store a categorical column as a plain string array, in place. See
+    /// `as_categorical`.
+    ///
+    /// Parameters
+    /// ----------
+    /// column: str
+    ///     Name of the column.
+    #[pyo3(text_signature = "($self, column)")]
+    fn as_string(&self, column: &str) -> Result<()> {
+        self.0.as_string(column)
+    }
+
+    /// The levels of a compound index, which some tools, e.g., R packages,
+    /// store as separate datasets. The names of the rows join the levels with
+    /// "_". None if the index has a single level.
+    ///
+    /// Returns
+    /// -------
+    /// polars.DataFrame | None
+    #[getter]
+    fn index_levels(&self) -> Result<Option<PyDataFrame>> {
+        Ok(self.0.index_levels()?.map(PyDataFrame))
+    }
+
+    /// Write the dataframe to a CSV file, with the index as the first column.
+    ///
+    /// Columns that are not cached are read from the backing file one at a
+    /// time. Categorical columns are written as their labels.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: Path
+    ///     File name of the output CSV file.
+    #[pyo3(text_signature = "($self, path)")]
+    fn to_csv(&self, path: PathBuf) -> Result<()> {
+        self.0.write_csv(&path)
+    }
+
+    /// Write the dataframe to a Parquet file, with the index as the first
+    /// column.
+    ///
+    /// Unlike `to_csv`, categorical columns keep their categories, so the file
+    /// can be read by polars, pandas or R without losing the column types.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: Path
+    ///     File name of the output Parquet file.
+    #[pyo3(text_signature = "($self, path)")]
+    fn to_parquet(&self, path: PathBuf) -> Result<()> {
+        self.0.write_parquet(&path)
+    }
+
+    /// Write the dataframe to an Arrow IPC (Feather v2) file, with the index as
+    /// the first column. See `to_parquet`.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: Path
+    ///     File name of the output Arrow IPC file.
+    #[pyo3(text_signature = "($self, path)")]
+    fn to_ipc(&self, path: PathBuf) -> Result<()> {
+        self.0.write_ipc(&path)
+    }
+
     fn __getitem__(&self, subscript: &Bound<'_, PyAny>) -> Result<PyObject> {
         self.0.get(subscript)
     }
@@ -162,6 +654,21 @@ impl PyDataFrameElem {
         self.0.contains(key)
     }
 
+    /// Read a custom attribute of the element. See `PyArrayElem.get_attr`.
+    #[pyo3(text_signature = "($self, name)")]
+    fn get_attr(&self, py: Python<'_>, name: &str) -> Result<PyObject> {
+        match self.0.get_attr(name)? {
+            Some(value) => Ok(value.into_python(py)?),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Set a custom attribute of the element. See `PyArrayElem.set_attr`.
+    #[pyo3(text_signature = "($self, name, value)")]
+    fn set_attr(&self, name: &str, value: &Bound<'_, PyAny>) -> Result<()> {
+        self.0.set_attr(name, DynScalar::from_python(value)?)
+    }
+
     fn __repr__(&self) -> String {
         self.0.show()
     }
@@ -175,6 +682,11 @@ impl PyDataFrameElem {
 /// (a two or higher-dimensional ndarray).
 /// It allows indexing and slicing along the associated axis.
 ///
+/// Values can also be pandas or polars DataFrames, dicts of columns or lists
+/// of lists. DataFrames are stored with the dataframe encoding of anndata,
+/// which keeps the column names and the dtype of each column, rather than as
+/// a 2-D array, and are read back as pandas DataFrames.
+///
 /// Examples
 /// --------
 /// >>> data.obsm
@@ -211,12 +723,29 @@ impl PyAxisArrays {
         self.0.keys()
     }
 
+    /// Return the keys of embeddings, i.e., keys starting with "X_".
+    ///
+    /// Returns
+    /// -------
+    /// List[str]
+    #[pyo3(text_signature = "($self)")]
+    pub fn embeddings(&self) -> Vec<String> {
+        self.0.embeddings()
+    }
+
     fn __contains__(&self, key: &str) -> bool {
         self.0.contains(key)
     }
 
-    fn __getitem__(&self, key: &str) -> Result<PyArrayData> {
-        self.0.get(key)
+    /// Read the element. Dataframes are returned as pandas DataFrames, as in
+    /// anndata, with a default index.
+    fn __getitem__<'py>(&self, py: Python<'py>, key: &str) -> Result<Bound<'py, PyAny>> {
+        let data = self.0.el(key)?.read_all(py)?;
+        if isinstance_of_polars(&data)? {
+            Ok(data.call_method0("to_pandas")?)
+        } else {
+            Ok(data)
+        }
     }
 
     /// Provide a lazy access to the elements.
@@ -250,7 +779,7 @@ impl PyAxisArrays {
     /// -------
     /// Optional[PyArrayElem]
     #[pyo3(text_signature = "($self, key)")]
-    fn el(&self, key: &str) -> Result<PyArrayElem> {
+    pub fn el(&self, key: &str) -> Result<PyArrayElem> {
         self.0.el(key)
     }
 
@@ -258,6 +787,66 @@ impl PyAxisArrays {
         self.0.set(key, data)
     }
 
+    /// Add a dense array stored transposed.
+    ///
+    /// The array is written with its axes reversed, so that reading a single
+    /// column, e.g., `adata.obsm.el('X_pca')[:, 0]` for the first
+    /// component, is a contiguous read. Reading the element returns the
+    /// original array.
+    ///
+    /// Parameters
+    /// ----------
+    /// key
+    ///     the name of the key.
+    /// data
+    ///     a numpy array.
+    #[pyo3(text_signature = "($self, key, data)")]
+    fn add_transposed(&self, key: &str, data: PyArrayData) -> Result<()> {
+        self.0.set_transposed(key, data)
+    }
+
+    /// Write an element chunk by chunk.
+    ///
+    /// The chunks are concatenated along the first axis and written to disk
+    /// as they are produced, so the full array never needs to be held in memory.
+    /// If the iterator raises an exception or the resulting shape does not
+    /// match the other elements, the key is removed.
+    ///
+    /// Parameters
+    /// ----------
+    /// key
+    ///     the name of the key.
+    /// data
+    ///     An iterator of arrays (numpy arrays or scipy sparse matrices).
+    #[pyo3(text_signature = "($self, key, data)")]
+    fn set_from_iter(&self, key: &str, data: &Bound<'_, PyAny>) -> Result<()> {
+        let mut err = None;
+        let iter = data.iter()?.map_while(|x| match x.and_then(|x| x.extract::<PyArrayData>()) {
+            Ok(x) => Some(Into::<ArrayData>::into(x)),
+            Err(e) => {
+                err = Some(e);
+                None
+            }
+        });
+        let result = self.0.set_from_iter(key, Box::new(iter));
+        if let Some(e) = err {
+            if result.is_ok() {
+                self.0.remove(&[key.to_string()])?;
+            }
+            return Err(e.into());
+        }
+        result
+    }
+
+    /// Delete one key, or several keys at once if a list is given.
+    fn __delitem__(&self, key: &Bound<'_, PyAny>) -> Result<()> {
+        let keys = match key.extract::<String>() {
+            Ok(k) => vec![k],
+            Err(_) => key.extract::<Vec<String>>()?,
+        };
+        self.0.remove(&keys)
+    }
+
     fn __repr__(&self) -> String {
         self.0.show()
     }
@@ -296,6 +885,44 @@ impl PyElemCollection {
         self.0.set(key, data)
     }
 
+    /// Return the data at a `/`-separated path of nested keys.
+    ///
+    /// Unlike `uns["a"]["b"]`, only the requested element is read, rather
+    /// than the whole mapping stored under `"a"`.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: str
+    ///     The path, e.g., `"rank_genes_groups/names"`.
+    #[pyo3(text_signature = "($self, path)")]
+    fn get_path(&self, path: &str) -> Result<PyData> {
+        self.0.get_path(path)
+    }
+
+    /// Append a value to the 1-dimensional array under `key`, e.g., a log of
+    /// processing steps. The array is created if `key` does not exist.
+    ///
+    /// The array is grown in place rather than rewritten, so appending many
+    /// values one by one stays cheap.
+    ///
+    /// Parameters
+    /// ----------
+    /// key: str
+    /// value: str | int | float | bool
+    #[pyo3(text_signature = "($self, key, value)")]
+    fn append_to_list(&self, key: &str, value: &Bound<'_, PyAny>) -> Result<()> {
+        self.0.append_to_list(key, DynScalar::from_python(value)?)
+    }
+
+    /// Delete one key, or several keys at once if a list is given.
+    fn __delitem__(&self, key: &Bound<'_, PyAny>) -> Result<()> {
+        let keys = match key.extract::<String>() {
+            Ok(k) => vec![k],
+            Err(_) => key.extract::<Vec<String>>()?,
+        };
+        self.0.remove(&keys)
+    }
+
     fn __repr__(&self) -> String {
         self.0.show()
     }
@@ -329,4 +956,73 @@ impl<T: ChunkedArrayTrait + 'static> From<T> for PyChunkedArray {
     fn from(elem: T) -> Self {
         Self(Box::new(elem))
     }
+}
+
+/// The results of a function applied to each chunk of an array, computed as
+/// they are iterated over. See [`PyArrayElem::map_chunks`].
+#[pyclass]
+pub struct PyMappedChunks {
+    chunks: PyChunkedArray,
+    f: PyObject,
+}
+
+#[pymethods]
+impl PyMappedChunks {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> Result<Option<PyObject>> {
+        let py = slf.py();
+        match slf.chunks.0.next() {
+            Some((chunk, _, _)) => Ok(Some(slf.f.call1(py, (PyArrayData::from(chunk),))?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Shuffled minibatches of an array. Each iteration starts a new pass over
+/// the batches, in the same order.
+#[pyclass]
+#[repr(transparent)]
+pub struct PyShuffledChunks(Box<dyn ShuffledChunksTrait>);
+
+#[pymethods]
+impl PyShuffledChunks {
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    fn __iter__(&self) -> PyShuffledChunksIter {
+        PyShuffledChunksIter(self.0.box_clone())
+    }
+}
+
+impl<T: ShuffledChunksTrait + 'static> From<T> for PyShuffledChunks {
+    fn from(elem: T) -> Self {
+        Self(Box::new(elem))
+    }
+}
+
+/// A single pass over the batches of [`PyShuffledChunks`].
+#[pyclass]
+#[repr(transparent)]
+pub struct PyShuffledChunksIter(Box<dyn ShuffledChunksTrait>);
+
+#[pymethods]
+impl PyShuffledChunksIter {
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> Result<Option<(PyArrayData, PyObject)>> {
+        let py = slf.py();
+        slf.0.next()
+            .transpose()
+            .map(|x| x.map(|(data, indices)| (data.into(), indices.into_pyarray_bound(py).into_py(py))))
+    }
 }
\ No newline at end of file
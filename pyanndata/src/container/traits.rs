@@ -1,24 +1,28 @@
 use std::ops::Deref;
+use std::path::Path;
 
 use crate::data::{
     is_none_slice, to_select_info, PyArrayData, PyData,
 };
 
-use anndata::backend::DataType;
-use anndata::data::SelectInfoElem;
+use anndata::backend::{BackendData, DataType, ScalarType};
+use anndata::data::{BoundedSelectInfo, DataFrameSummary, DynScalar, SelectInfoElem, Shape};
 use anndata::{
-    ArrayData, ArrayElem, AxisArrays, Backend, Data,
+    ArrayData, ArrayElem, ArrayElemOp, AxisArrays, Backend, Data,
     DataFrameElem, Elem, ElemCollection, StackedArrayElem, StackedDataFrame, StackedAxisArrays,
 };
-use anndata::container::{ChunkedArrayElem, StackedChunkedArrayElem};
+use anndata::container::{ChunkStrategy, ChunkedArrayElem, InnerArrayElem, ShuffledChunks, StackedChunkedArrayElem};
+use numpy::{Element, PyArray1, PyArrayDyn, PyArrayMethods};
 use anyhow::{bail, Context, Result};
+use polars::frame::DataFrame;
 use polars::series::Series;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 use pyo3_polars::{PySeries, PyDataFrame};
 use rand::Rng;
 use rand::SeedableRng;
 
-use super::{PyArrayElem, PyElem, PyChunkedArray};
+use super::{PyArrayElem, PyElem, PyChunkedArray, PyShuffledChunks};
 
 /// Trait for `Elem` to abtract over different backends.
 pub trait ElemTrait: Send {
@@ -27,6 +31,8 @@ pub trait ElemTrait: Send {
     fn is_scalar(&self) -> bool;
     fn get<'py>(&self, subscript: &Bound<'py, PyAny>) -> Result<PyData>;
     fn show(&self) -> String;
+    fn get_attr(&self, name: &str) -> Result<Option<DynScalar>>;
+    fn set_attr(&self, name: &str, value: DynScalar) -> Result<()>;
 }
 
 impl<B: Backend> ElemTrait for Elem<B> {
@@ -38,6 +44,10 @@ impl<B: Backend> ElemTrait for Elem<B> {
         self.lock().as_mut().map(|x| x.disable_cache());
     }
 
+    fn set_chunk_cache(&self, nbytes: usize) -> Result<()> {
+        self.try_inner()?.set_chunk_cache(nbytes)
+    }
+
     fn is_scalar(&self) -> bool {
         match self.inner().dtype() {
             DataType::Scalar(_) => true,
@@ -47,7 +57,8 @@ impl<B: Backend> ElemTrait for Elem<B> {
 
     fn get<'py>(&self, slice: &Bound<'py, PyAny>) -> Result<PyData> {
         if is_none_slice(slice)? {
-            Ok(self.inner().data::<Data>()?.into())
+            let data = self.get_data::<Data>()?.context("accessing an empty or closed slot")?;
+            PyData::from_supported(data)
         } else {
             bail!("Please use None slice to retrieve data.")
         }
@@ -56,13 +67,27 @@ impl<B: Backend> ElemTrait for Elem<B> {
     fn show(&self) -> String {
         format!("{}", self)
     }
+
+    fn get_attr(&self, name: &str) -> Result<Option<DynScalar>> {
+        self.try_inner()?.get_attr(name)
+    }
+
+    fn set_attr(&self, name: &str, value: DynScalar) -> Result<()> {
+        self.try_inner()?.set_attr(name, value)
+    }
 }
 
 pub trait ArrayElemTrait: Send {
     fn enable_cache(&self);
     fn disable_cache(&self);
     fn show(&self) -> String;
-    fn get(&self, subscript: &Bound<'_, PyAny>) -> Result<PyArrayData>;
+    fn get_attr(&self, name: &str) -> Result<Option<DynScalar>>;
+    fn set_attr(&self, name: &str, value: DynScalar) -> Result<()>;
+    fn get(&self, slice: &[SelectInfoElem]) -> Result<ArrayData>;
+    /// Like `get`, but read numeric data straight into newly allocated numpy
+    /// arrays. Return `None` if the element cannot be read this way.
+    fn get_numpy(&self, py: Python<'_>, slice: &[SelectInfoElem]) -> Result<Option<PyObject>>;
+    fn set_chunk_cache(&self, nbytes: usize) -> Result<()>;
     fn shape(&self) -> Vec<usize>;
     fn chunk(
         &self,
@@ -70,7 +95,83 @@ pub trait ArrayElemTrait: Send {
         replace: bool,
         seed: u64,
     ) -> Result<ArrayData>;
-    fn chunked(&self, chunk_size: usize) -> PyChunkedArray;
+    fn chunked(&self, strategy: ChunkStrategy) -> Result<PyChunkedArray>;
+    fn sum(&self, axis: usize) -> Result<ArrayData>;
+    fn var(&self, axis: usize, ddof: usize) -> Result<ArrayData>;
+    fn dtype(&self) -> Result<DataType>;
+    fn astype(&self, ty: ScalarType) -> Result<ArrayData>;
+    fn astype_in_place(&self, ty: ScalarType) -> Result<()>;
+    fn map_chunks_in_place(&self, chunk_size: usize, f: &mut dyn FnMut(ArrayData) -> Result<ArrayData>) -> Result<()>;
+    fn shuffled_chunks(&self, batch_size: usize, buffer_size: Option<usize>, seed: u64) -> PyShuffledChunks;
+}
+
+/// Read a dense array into a numpy array of the selected shape. See
+/// `ArrayElemTrait::get_numpy`.
+fn read_dense<B, T>(
+    py: Python<'_>,
+    elem: &InnerArrayElem<B, ArrayData>,
+    slice: &[SelectInfoElem],
+    shape: &Shape,
+) -> Result<Option<PyObject>>
+where
+    B: Backend,
+    T: BackendData + Element,
+{
+    let arr = PyArrayDyn::<T>::zeros_bound(py, shape.as_ref(), false);
+    let filled = elem.read_dense_into(slice, unsafe { arr.as_slice_mut()? })?;
+    Ok(filled.then(|| arr.into_any().unbind()))
+}
+
+/// Read a whole sparse matrix into numpy arrays and assemble them into a
+/// scipy matrix. See `ArrayElemTrait::get_numpy`.
+fn read_sparse<B, T>(py: Python<'_>, elem: &InnerArrayElem<B, ArrayData>) -> Result<Option<PyObject>>
+where
+    B: Backend,
+    T: BackendData + Element,
+{
+    fn read_components<B, T, I>(
+        py: Python<'_>,
+        elem: &InnerArrayElem<B, ArrayData>,
+        nnz: usize,
+        n: usize,
+    ) -> Result<Option<(PyObject, PyObject, PyObject)>>
+    where
+        B: Backend,
+        T: BackendData + Element,
+        I: BackendData + Element,
+    {
+        let data = PyArray1::<T>::zeros_bound(py, nnz, false);
+        let indices = PyArray1::<I>::zeros_bound(py, nnz, false);
+        let indptr = PyArray1::<I>::zeros_bound(py, n + 1, false);
+        let filled = unsafe {
+            elem.read_sparse_into(data.as_slice_mut()?, indices.as_slice_mut()?, indptr.as_slice_mut()?)?
+        };
+        Ok(filled.then(|| {
+            (data.into_any().unbind(), indices.into_any().unbind(), indptr.into_any().unbind())
+        }))
+    }
+
+    let Some(nnz) = elem.nnz()? else { return Ok(None) };
+    let shape = elem.shape();
+    let (class, n) = match elem.dtype() {
+        DataType::CsrMatrix(_) => ("csr_matrix", shape[0]),
+        _ => ("csc_matrix", shape[1]),
+    };
+    // scipy copies index arrays that are wider than needed.
+    let components = if nnz.max(shape[0]).max(shape[1]) <= i32::MAX as usize {
+        read_components::<B, T, i32>(py, elem, nnz, n)?
+    } else {
+        read_components::<B, T, i64>(py, elem, nnz, n)?
+    };
+    match components {
+        Some(components) => Ok(Some(
+            PyModule::import_bound(py, "scipy.sparse")?
+                .getattr(class)?
+                .call1((components, (shape[0], shape[1])))?
+                .unbind(),
+        )),
+        None => Ok(None),
+    }
 }
 
 impl<B: Backend + 'static> ArrayElemTrait for ArrayElem<B> {
@@ -82,19 +183,65 @@ impl<B: Backend + 'static> ArrayElemTrait for ArrayElem<B> {
         self.lock().as_mut().map(|x| x.disable_cache());
     }
 
-    fn get(&self, subscript: &Bound<'_, PyAny>) -> Result<PyArrayData> {
-        let slice = to_select_info(subscript, self.inner().shape())?;
-        self.inner()
-            .select::<ArrayData, _>(slice.as_ref())
-            .map(|x| x.into())
+    fn get(&self, slice: &[SelectInfoElem]) -> Result<ArrayData> {
+        ArrayElemOp::slice::<ArrayData, _>(self, slice)?.context("accessing an empty or closed slot")
+    }
+
+    fn get_numpy(&self, py: Python<'_>, slice: &[SelectInfoElem]) -> Result<Option<PyObject>> {
+        let guard = self.read();
+        let elem = guard.as_ref().context("accessing an empty or closed slot")?;
+        match elem.dtype() {
+            DataType::Array(ty) => {
+                let shape = BoundedSelectInfo::new(&slice, elem.shape()).out_shape();
+                match ty {
+                    ScalarType::I8 => read_dense::<B, i8>(py, elem, slice, &shape),
+                    ScalarType::I16 => read_dense::<B, i16>(py, elem, slice, &shape),
+                    ScalarType::I32 => read_dense::<B, i32>(py, elem, slice, &shape),
+                    ScalarType::I64 => read_dense::<B, i64>(py, elem, slice, &shape),
+                    ScalarType::U8 => read_dense::<B, u8>(py, elem, slice, &shape),
+                    ScalarType::U16 => read_dense::<B, u16>(py, elem, slice, &shape),
+                    ScalarType::U32 => read_dense::<B, u32>(py, elem, slice, &shape),
+                    ScalarType::U64 => read_dense::<B, u64>(py, elem, slice, &shape),
+                    ScalarType::Usize => read_dense::<B, usize>(py, elem, slice, &shape),
+                    ScalarType::F32 => read_dense::<B, f32>(py, elem, slice, &shape),
+                    ScalarType::F64 => read_dense::<B, f64>(py, elem, slice, &shape),
+                    _ => Ok(None),
+                }
+            }
+            DataType::CsrMatrix(ty) | DataType::CscMatrix(ty) if slice.iter().all(|x| x.is_full()) => {
+                match ty {
+                    ScalarType::I8 => read_sparse::<B, i8>(py, elem),
+                    ScalarType::I16 => read_sparse::<B, i16>(py, elem),
+                    ScalarType::I32 => read_sparse::<B, i32>(py, elem),
+                    ScalarType::I64 => read_sparse::<B, i64>(py, elem),
+                    ScalarType::U8 => read_sparse::<B, u8>(py, elem),
+                    ScalarType::U16 => read_sparse::<B, u16>(py, elem),
+                    ScalarType::U32 => read_sparse::<B, u32>(py, elem),
+                    ScalarType::U64 => read_sparse::<B, u64>(py, elem),
+                    ScalarType::Usize => read_sparse::<B, usize>(py, elem),
+                    ScalarType::F32 => read_sparse::<B, f32>(py, elem),
+                    ScalarType::F64 => read_sparse::<B, f64>(py, elem),
+                    _ => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
     }
 
     fn show(&self) -> String {
         format!("{}", self)
     }
 
+    fn get_attr(&self, name: &str) -> Result<Option<DynScalar>> {
+        self.try_inner()?.get_attr(name)
+    }
+
+    fn set_attr(&self, name: &str, value: DynScalar) -> Result<()> {
+        self.try_inner()?.set_attr(name, value)
+    }
+
     fn shape(&self) -> Vec<usize> {
-        self.inner().shape().as_ref().to_vec()
+        ArrayElemOp::shape(self).expect("accessing an empty slot").as_ref().to_vec()
     }
 
     fn chunk(
@@ -112,11 +259,45 @@ impl<B: Backend + 'static> ArrayElemTrait for ArrayElem<B> {
         } else {
             rand::seq::index::sample(&mut rng, length, size).into_vec()
         };
-        self.inner().select_axis::<ArrayData, _>(0, &SelectInfoElem::from(idx))
+        ArrayElemOp::slice_axis::<ArrayData, _>(self, 0, SelectInfoElem::from(idx))?
+            .context("accessing an empty or closed slot")
+    }
+
+    fn chunked(&self, strategy: ChunkStrategy) -> Result<PyChunkedArray> {
+        Ok(self.chunked_by::<ArrayData>(strategy)?.into())
+    }
+
+    fn sum(&self, axis: usize) -> Result<ArrayData> {
+        let sum = ArrayElemOp::sum(self, axis)?.context("cannot sum an empty element")?;
+        Ok(sum.into())
     }
 
-    fn chunked(&self, chunk_size: usize) -> PyChunkedArray {
-        self.chunked::<ArrayData>(chunk_size).into()
+    fn var(&self, axis: usize, ddof: usize) -> Result<ArrayData> {
+        let var = ArrayElemOp::var(self, axis, ddof)?.context("cannot compute the variance of an empty element")?;
+        Ok(var.into())
+    }
+
+    fn dtype(&self) -> Result<DataType> {
+        Ok(self.try_inner()?.dtype())
+    }
+
+    fn astype(&self, ty: ScalarType) -> Result<ArrayData> {
+        self.inner().data::<ArrayData>()?.astype(ty)
+    }
+
+    fn astype_in_place(&self, ty: ScalarType) -> Result<()> {
+        ArrayElem::astype(self, ty)
+    }
+
+    fn map_chunks_in_place(&self, chunk_size: usize, f: &mut dyn FnMut(ArrayData) -> Result<ArrayData>) -> Result<()> {
+        ArrayElem::map_chunks_in_place(self, chunk_size, f)
+    }
+
+    fn shuffled_chunks(&self, batch_size: usize, buffer_size: Option<usize>, seed: u64) -> PyShuffledChunks {
+        match buffer_size {
+            None => self.shuffled_chunks::<ArrayData>(batch_size, seed).into(),
+            Some(n) => self.approx_shuffled_chunks::<ArrayData>(batch_size, n, seed).into(),
+        }
     }
 }
 
@@ -129,16 +310,30 @@ impl<B: Backend + 'static> ArrayElemTrait for StackedArrayElem<B> {
         self.deref().disable_cache();
     }
 
-    fn get(&self, subscript: &Bound<'_, PyAny>) -> Result<PyArrayData> {
-        let slice = to_select_info(subscript, self.deref().shape().as_ref().unwrap())?;
-        self.select::<ArrayData, _>(slice.as_ref())
-            .map(|x| x.unwrap().into())
+    fn set_chunk_cache(&self, nbytes: usize) -> Result<()> {
+        self.deref().set_chunk_cache(nbytes)
+    }
+
+    fn get(&self, slice: &[SelectInfoElem]) -> Result<ArrayData> {
+        self.select::<ArrayData, _>(slice).map(|x| x.unwrap())
+    }
+
+    fn get_numpy(&self, _py: Python<'_>, _slice: &[SelectInfoElem]) -> Result<Option<PyObject>> {
+        Ok(None)
     }
 
     fn show(&self) -> String {
         format!("{}", self)
     }
 
+    fn get_attr(&self, _name: &str) -> Result<Option<DynScalar>> {
+        bail!("attributes are not supported for stacked elements")
+    }
+
+    fn set_attr(&self, _name: &str, _value: DynScalar) -> Result<()> {
+        bail!("attributes are not supported for stacked elements")
+    }
+
     fn shape(&self) -> Vec<usize> {
         self.deref().shape().as_ref().unwrap().as_ref().to_vec()
     }
@@ -162,16 +357,64 @@ impl<B: Backend + 'static> ArrayElemTrait for StackedArrayElem<B> {
             .map(|x| x.unwrap())
     }
 
-    fn chunked(&self, chunk_size: usize) -> PyChunkedArray {
-        self.chunked::<ArrayData>(chunk_size).into()
+    fn chunked(&self, strategy: ChunkStrategy) -> Result<PyChunkedArray> {
+        Ok(self.chunked_by::<ArrayData>(strategy)?.into())
+    }
+
+    fn sum(&self, axis: usize) -> Result<ArrayData> {
+        let sum = ArrayElemOp::sum(self, axis)?.context("cannot sum an empty element")?;
+        Ok(sum.into())
+    }
+
+    fn var(&self, axis: usize, ddof: usize) -> Result<ArrayData> {
+        let var = ArrayElemOp::var(self, axis, ddof)?.context("cannot compute the variance of an empty element")?;
+        Ok(var.into())
+    }
+
+    fn dtype(&self) -> Result<DataType> {
+        if self.deref().is_empty() {
+            bail!("accessing an empty element");
+        }
+        Ok(self.deref().dtype())
+    }
+
+    fn astype(&self, ty: ScalarType) -> Result<ArrayData> {
+        self.data::<ArrayData>()?.context("cannot convert an empty element")?.astype(ty)
+    }
+
+    fn astype_in_place(&self, _ty: ScalarType) -> Result<()> {
+        bail!("cannot convert stacked elements in place, use `copy=True` to read a converted copy")
+    }
+
+    fn map_chunks_in_place(&self, _chunk_size: usize, _f: &mut dyn FnMut(ArrayData) -> Result<ArrayData>) -> Result<()> {
+        bail!("cannot map the chunks of stacked elements in place, use `inplace=False`")
+    }
+
+    fn shuffled_chunks(&self, batch_size: usize, buffer_size: Option<usize>, seed: u64) -> PyShuffledChunks {
+        match buffer_size {
+            None => self.shuffled_chunks::<ArrayData>(batch_size, seed).into(),
+            Some(n) => self.approx_shuffled_chunks::<ArrayData>(batch_size, n, seed).into(),
+        }
     }
 }
 
 pub trait DataFrameElemTrait: Send {
     fn get(&self, subscript: &Bound<'_, PyAny>) -> Result<PyObject>;
     fn set(&self, key: &str, data: Series) -> Result<()>;
+    fn keys(&self) -> Vec<String>;
     fn contains(&self, key: &str) -> bool;
+    fn describe(&self, py: Python<'_>) -> Result<PyObject>;
+    fn null_counts(&self) -> Result<Vec<(String, usize)>>;
+    fn reorder_columns(&self, order: &[String]) -> Result<()>;
+    fn as_categorical(&self, column: &str) -> Result<()>;
+    fn as_string(&self, column: &str) -> Result<()>;
+    fn index_levels(&self) -> Result<Option<DataFrame>>;
+    fn write_csv(&self, path: &Path) -> Result<()>;
+    fn write_ipc(&self, path: &Path) -> Result<()>;
+    fn write_parquet(&self, path: &Path) -> Result<()>;
     fn show(&self) -> String;
+    fn get_attr(&self, name: &str) -> Result<Option<DynScalar>>;
+    fn set_attr(&self, name: &str, value: DynScalar) -> Result<()>;
 }
 
 impl<B: Backend> DataFrameElemTrait for DataFrameElem<B> {
@@ -182,7 +425,7 @@ impl<B: Backend> DataFrameElemTrait for DataFrameElem<B> {
         } else {
             let width = self.inner().width();
             let height = self.inner().height();
-            let shape = [width, height].as_slice().into();
+            let shape = [height, width].as_slice().into();
             let slice = to_select_info(subscript, &shape)?;
             let df = self.inner().select(slice.as_ref())?;
             Ok(PyDataFrame(df).into_py(py))
@@ -191,19 +434,70 @@ impl<B: Backend> DataFrameElemTrait for DataFrameElem<B> {
 
     fn set(&self, key: &str, mut data: Series) -> Result<()> {
         data.rename(key);
-        self.inner().set_column(key, data)
+        self.try_inner()?.set_column(key, data)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.read()
+            .as_ref()
+            .map(|x| x.get_column_names().iter().cloned().collect())
+            .unwrap_or_default()
     }
 
     fn contains(&self, key: &str) -> bool {
-        self.lock()
+        self.read()
             .as_ref()
             .map(|x| x.get_column_names().contains(key))
             .unwrap_or(false)
     }
 
+    fn describe(&self, py: Python<'_>) -> Result<PyObject> {
+        summary_to_py(py, self.inner().describe()?)
+    }
+
+    fn null_counts(&self) -> Result<Vec<(String, usize)>> {
+        Ok(self.inner().null_counts()?.into_iter().collect())
+    }
+
+    fn reorder_columns(&self, order: &[String]) -> Result<()> {
+        self.try_inner()?.reorder_columns(order)
+    }
+
+    fn as_categorical(&self, column: &str) -> Result<()> {
+        self.try_inner()?.as_categorical(column)
+    }
+
+    fn as_string(&self, column: &str) -> Result<()> {
+        self.try_inner()?.as_string(column)
+    }
+
+    fn index_levels(&self) -> Result<Option<DataFrame>> {
+        self.try_inner()?.index_levels()
+    }
+
+    fn write_csv(&self, path: &Path) -> Result<()> {
+        self.try_inner()?.write_csv(path)
+    }
+
+    fn write_ipc(&self, path: &Path) -> Result<()> {
+        self.try_inner()?.write_ipc(path)
+    }
+
+    fn write_parquet(&self, path: &Path) -> Result<()> {
+        self.try_inner()?.write_parquet(path)
+    }
+
     fn show(&self) -> String {
         format!("{}", self)
     }
+
+    fn get_attr(&self, name: &str) -> Result<Option<DynScalar>> {
+        self.try_inner()?.get_attr(name)
+    }
+
+    fn set_attr(&self, name: &str, value: DynScalar) -> Result<()> {
+        self.try_inner()?.set_attr(name, value)
+    }
 }
 
 impl<B: Backend> DataFrameElemTrait for StackedDataFrame<B> {
@@ -221,25 +515,86 @@ impl<B: Backend> DataFrameElemTrait for StackedDataFrame<B> {
         }
     }
 
-    fn set(&self, _: &str, _: Series) -> Result<()> {
-        bail!("Cannot set column in stacked dataframe")
+    fn set(&self, key: &str, data: Series) -> Result<()> {
+        self.set_column(key, data)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.column_names().into_iter().collect()
     }
 
     fn contains(&self, key: &str) -> bool {
         self.get_column_names().contains(key)
     }
 
+    fn describe(&self, py: Python<'_>) -> Result<PyObject> {
+        summary_to_py(py, StackedDataFrame::describe(self)?)
+    }
+
+    fn null_counts(&self) -> Result<Vec<(String, usize)>> {
+        Ok(StackedDataFrame::null_counts(self)?.into_iter().collect())
+    }
+
+    fn reorder_columns(&self, _order: &[String]) -> Result<()> {
+        bail!("cannot reorder the columns of stacked elements")
+    }
+
+    fn as_categorical(&self, _column: &str) -> Result<()> {
+        bail!("cannot change the encoding of the columns of stacked elements")
+    }
+
+    fn as_string(&self, _column: &str) -> Result<()> {
+        bail!("cannot change the encoding of the columns of stacked elements")
+    }
+
+    fn index_levels(&self) -> Result<Option<DataFrame>> {
+        Ok(None)
+    }
+
+    fn write_csv(&self, _path: &Path) -> Result<()> {
+        bail!("cannot export stacked elements to CSV")
+    }
+
+    fn write_ipc(&self, _path: &Path) -> Result<()> {
+        bail!("cannot export stacked elements to Arrow IPC")
+    }
+
+    fn write_parquet(&self, _path: &Path) -> Result<()> {
+        bail!("cannot export stacked elements to Parquet")
+    }
+
     fn show(&self) -> String {
         format!("{}", self)
     }
+
+    fn get_attr(&self, _name: &str) -> Result<Option<DynScalar>> {
+        bail!("attributes are not supported for stacked elements")
+    }
+
+    fn set_attr(&self, _name: &str, _value: DynScalar) -> Result<()> {
+        bail!("attributes are not supported for stacked elements")
+    }
+}
+
+/// Convert the summary to a tuple of a polars DataFrame, containing statistics
+/// of numeric columns, and a dict of value counts of categorical columns.
+fn summary_to_py(py: Python<'_>, summary: DataFrameSummary) -> Result<PyObject> {
+    let categorical = PyDict::new_bound(py);
+    for (k, v) in summary.categorical {
+        categorical.set_item(k, PyDataFrame(v).into_py(py))?;
+    }
+    Ok((PyDataFrame(summary.numeric).into_py(py), categorical).into_py(py))
 }
 
 pub trait AxisArrayTrait: Send {
     fn keys(&self) -> Vec<String>;
+    fn embeddings(&self) -> Vec<String>;
     fn contains(&self, key: &str) -> bool;
-    fn get(&self, key: &str) -> Result<PyArrayData>;
     fn el(&self, key: &str) -> Result<PyArrayElem>;
     fn set(&self, key: &str, data: PyArrayData) -> Result<()>;
+    fn set_transposed(&self, key: &str, data: PyArrayData) -> Result<()>;
+    fn set_from_iter(&self, key: &str, data: Box<dyn Iterator<Item = ArrayData> + '_>) -> Result<()>;
+    fn remove(&self, keys: &[String]) -> Result<()>;
     fn show(&self) -> String;
 }
 
@@ -248,18 +603,12 @@ impl<B: Backend + 'static> AxisArrayTrait for AxisArrays<B> {
         self.inner().keys().map(|x| x.to_string()).collect()
     }
 
-    fn contains(&self, key: &str) -> bool {
-        self.inner().contains_key(key)
+    fn embeddings(&self) -> Vec<String> {
+        anndata::AxisArraysOp::embeddings(&self)
     }
 
-    fn get(&self, key: &str) -> Result<PyArrayData> {
-        Ok(self
-            .inner()
-            .get(key)
-            .context(format!("No such key: {}", key))?
-            .inner()
-            .data::<ArrayData>()?
-            .into())
+    fn contains(&self, key: &str) -> bool {
+        self.inner().contains_key(key)
     }
 
     fn el(&self, key: &str) -> Result<PyArrayElem> {
@@ -272,7 +621,23 @@ impl<B: Backend + 'static> AxisArrayTrait for AxisArrays<B> {
     }
 
     fn set(&self, key: &str, data: PyArrayData) -> Result<()> {
-        self.inner().add_data::<ArrayData>(key, data.into())
+        self.try_inner()?.add_data::<ArrayData>(key, data.into())
+    }
+
+    fn set_transposed(&self, key: &str, data: PyArrayData) -> Result<()> {
+        match Into::<ArrayData>::into(data) {
+            ArrayData::Array(arr) => self.try_inner()?.add_data_transposed(key, arr),
+            _ => bail!("only dense arrays can be stored transposed"),
+        }
+    }
+
+    fn set_from_iter(&self, key: &str, data: Box<dyn Iterator<Item = ArrayData> + '_>) -> Result<()> {
+        self.try_inner()?.add_data_from_iter(key, data)
+    }
+
+    fn remove(&self, keys: &[String]) -> Result<()> {
+        let mut inner = self.try_inner()?;
+        keys.iter().try_for_each(|k| inner.remove_data(k))
     }
 
     fn show(&self) -> String {
@@ -285,17 +650,12 @@ impl<B: Backend + 'static> AxisArrayTrait for StackedAxisArrays<B> {
         self.deref().keys().map(|x| x.to_string()).collect()
     }
 
-    fn contains(&self, key: &str) -> bool {
-        self.deref().contains_key(key)
+    fn embeddings(&self) -> Vec<String> {
+        anndata::AxisArraysOp::embeddings(&self)
     }
 
-    fn get(&self, key: &str) -> Result<PyArrayData> {
-        Ok(self
-            .deref()
-            .get(key)
-            .context(format!("No such key: {}", key))?
-            .data::<ArrayData>()?.unwrap()
-            .into())
+    fn contains(&self, key: &str) -> bool {
+        self.deref().contains_key(key)
     }
 
     fn el(&self, key: &str) -> Result<PyArrayElem> {
@@ -311,6 +671,18 @@ impl<B: Backend + 'static> AxisArrayTrait for StackedAxisArrays<B> {
         bail!("mutations are not allowed on stacked axis arrays")
     }
 
+    fn set_transposed(&self, _: &str, _: PyArrayData) -> Result<()> {
+        bail!("mutations are not allowed on stacked axis arrays")
+    }
+
+    fn set_from_iter(&self, _: &str, _: Box<dyn Iterator<Item = ArrayData> + '_>) -> Result<()> {
+        bail!("mutations are not allowed on stacked axis arrays")
+    }
+
+    fn remove(&self, _: &[String]) -> Result<()> {
+        bail!("mutations are not allowed on stacked axis arrays")
+    }
+
     fn show(&self) -> String {
         format!("{}", self)
     }
@@ -322,8 +694,11 @@ pub trait ElemCollectionTrait: Send {
     fn keys(&self) -> Vec<String>;
     fn contains(&self, key: &str) -> bool;
     fn get(&self, key: &str) -> Result<PyData>;
+    fn get_path(&self, path: &str) -> Result<PyData>;
     fn el(&self, key: &str) -> Result<PyElem>;
     fn set(&self, key: &str, data: PyData) -> Result<()>;
+    fn append_to_list(&self, key: &str, value: DynScalar) -> Result<()>;
+    fn remove(&self, keys: &[String]) -> Result<()>;
     fn show(&self) -> String;
 }
 
@@ -337,13 +712,20 @@ impl<B: Backend + 'static> ElemCollectionTrait for ElemCollection<B> {
     }
 
     fn get(&self, key: &str) -> Result<PyData> {
-        Ok(self
+        PyData::from_supported(self
             .inner()
             .get(key)
             .context(format!("No such key: {}", key))?
             .inner()
-            .data::<Data>()?
-            .into())
+            .data::<Data>()?)
+    }
+
+    fn get_path(&self, path: &str) -> Result<PyData> {
+        PyData::from_supported(self
+            .get_path(path)?
+            .context(format!("No such path: {}", path))?
+            .inner()
+            .data::<Data>()?)
     }
 
     fn el(&self, key: &str) -> Result<PyElem> {
@@ -356,7 +738,16 @@ impl<B: Backend + 'static> ElemCollectionTrait for ElemCollection<B> {
     }
 
     fn set(&self, key: &str, data: PyData) -> Result<()> {
-        self.inner().add_data::<Data>(key, data.into())
+        self.try_inner()?.add_data::<Data>(key, data.into())
+    }
+
+    fn append_to_list(&self, key: &str, value: DynScalar) -> Result<()> {
+        self.try_inner()?.append_to_list(key, value)
+    }
+
+    fn remove(&self, keys: &[String]) -> Result<()> {
+        let mut inner = self.try_inner()?;
+        keys.iter().try_for_each(|k| inner.remove_data(k))
     }
 
     fn show(&self) -> String {
@@ -367,4 +758,20 @@ impl<B: Backend + 'static> ElemCollectionTrait for ElemCollection<B> {
 pub trait ChunkedArrayTrait: ExactSizeIterator<Item = (ArrayData, usize, usize)> + Send {}
 
 impl<B: Backend> ChunkedArrayTrait for ChunkedArrayElem<B, ArrayData> {}
-impl<B: Backend> ChunkedArrayTrait for StackedChunkedArrayElem<B, ArrayData> {}
\ No newline at end of file
+impl<B: Backend> ChunkedArrayTrait for StackedChunkedArrayElem<B, ArrayData> {}
+
+pub trait ShuffledChunksTrait: ExactSizeIterator<Item = Result<(ArrayData, Vec<usize>)>> + Send {
+    fn box_clone(&self) -> Box<dyn ShuffledChunksTrait>;
+}
+
+impl<B: Backend> ShuffledChunksTrait for ShuffledChunks<ArrayElem<B>, ArrayData> {
+    fn box_clone(&self) -> Box<dyn ShuffledChunksTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl<B: Backend> ShuffledChunksTrait for ShuffledChunks<StackedArrayElem<B>, ArrayData> {
+    fn box_clone(&self) -> Box<dyn ShuffledChunksTrait> {
+        Box::new(self.clone())
+    }
+}
\ No newline at end of file
@@ -1,14 +1,18 @@
 mod instance;
 mod slice;
 mod array;
+mod index;
 
 pub(crate) use instance::*;
 use pyo3_polars::PyDataFrame;
-pub use slice::{to_select_info, to_select_elem};
+pub use index::PyIndex;
+pub use slice::{to_select_info, to_select_elem, to_array_index, ArrayIndex, NameResolver};
 
 use std::{collections::HashMap, ops::Deref};
 use pyo3::{prelude::*, types::PyDict};
 use anndata::data::{Data, ArrayData, DynArray, DynCsrMatrix, DynCscMatrix, DynScalar, Mapping, DynCsrNonCanonical};
+use anndata::ArrayOp;
+use anndata::backend::ScalarType;
 
 pub(crate) trait FromPython<'source>: Sized {
     fn from_python(ob: &Bound<'source, PyAny>) -> PyResult<Self>;
@@ -57,14 +61,54 @@ impl<'py> FromPyObject<'py> for PyArrayData {
             Ok(ArrayData::from(ob.extract::<PyDataFrame>()?.0).into())
         } else if isinstance_of_polars(ob)? {
             Ok(ArrayData::from(ob.extract::<PyDataFrame>()?.0).into())
+        } else if ob.is_instance_of::<pyo3::types::PyDict>() {
+            // A dict of columns, stored as a dataframe.
+            let ob = ob.py().import_bound("polars")?.call_method1("DataFrame", (ob, ))?;
+            Ok(ArrayData::from(ob.extract::<PyDataFrame>()?.0).into())
+        } else if ob.is_instance_of::<pyo3::types::PyList>() || ob.is_instance_of::<pyo3::types::PyTuple>() {
+            // Nested sequences, e.g., lists of lists, are converted by numpy.
+            let arr = ob.py().import_bound("numpy")?.call_method1("asarray", (ob, ))?;
+            if arr.getattr("dtype")?.getattr("kind")?.extract::<String>()? == "O" {
+                return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                    "cannot convert a sequence with elements of mixed or non-numeric types to an array"
+                ));
+            }
+            Ok(ArrayData::from(DynArray::from_python(&arr)?).into())
         } else {
-            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-                format!("Could not convert Python type {} to Rust data", ob.get_type())
-            ))?
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                "expected a numpy array, a scipy sparse matrix, a pandas or polars DataFrame, \
+                a dict of columns or a list of lists, but received {}",
+                ob.get_type(),
+            )))
         }
     }
 }
 
+/// A dict of arrays, e.g., the value of `adata.obsm = {...}`. Unlike
+/// `HashMap<String, PyArrayData>`, conversion errors name the offending key.
+pub struct PyArrayDict(HashMap<String, PyArrayData>);
+
+impl From<PyArrayDict> for HashMap<String, PyArrayData> {
+    fn from(value: PyArrayDict) -> Self {
+        value.0
+    }
+}
+
+impl<'py> FromPyObject<'py> for PyArrayDict {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let dict = ob.downcast::<PyDict>().map_err(|_| PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            format!("expected a dict of arrays, but received {}", ob.get_type())
+        ))?;
+        dict.iter().map(|(k, v)| {
+            let key: String = k.extract()?;
+            let value = v.extract::<PyArrayData>().map_err(|e| PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                format!("cannot convert the value of '{}': {}", key, e.value_bound(ob.py()))
+            ))?;
+            Ok((key, value))
+        }).collect::<PyResult<_>>().map(PyArrayDict)
+    }
+}
+
 impl IntoPy<PyObject> for PyArrayData {
     fn into_py(self, py: Python<'_>) -> PyObject {
         match self.0 {
@@ -85,6 +129,17 @@ impl From<Data> for PyData {
     }
 }
 
+impl PyData {
+    /// Convert the data, returning an error if it contains elements with
+    /// unsupported encodings, as these cannot be represented in Python.
+    pub(crate) fn from_supported(data: Data) -> anyhow::Result<Self> {
+        if let Some(x) = data.find_unsupported() {
+            anyhow::bail!("cannot read element: {}", x);
+        }
+        Ok(PyData(data))
+    }
+}
+
 impl Into<Data> for PyData {
     fn into(self) -> Data {
         self.0
@@ -112,20 +167,88 @@ impl IntoPy<PyObject> for PyData {
             Data::ArrayData(arr) => PyArrayData(arr).into_py(py),
             Data::Scalar(s) => s.into_python(py).unwrap(),
             Data::Mapping(m) => m.into_python(py).unwrap(),
+            // Elements with unsupported encodings are converted to None. Use
+            // `PyData::from_supported` to raise an error instead.
+            Data::Unknown(_) => py.None(),
         }
     }
 }
 
+/// numpy dtypes that have a corresponding [`DynScalar`] variant.
+const SCALAR_DTYPES: [&str; 12] = [
+    "int8", "int16", "int32", "int64", "uint8", "uint16", "uint32", "uint64",
+    "float16", "float32", "float64", "bool",
+];
+
+/// Convert a numpy dtype, e.g., `"float32"` or `np.float32`, to a scalar type.
+pub(crate) fn to_scalar_type(dtype: &Bound<'_, PyAny>) -> anyhow::Result<ScalarType> {
+    let name = dtype.py().import_bound("numpy")?
+        .call_method1("dtype", (dtype,))?
+        .getattr("name")?
+        .extract::<String>()?;
+    let ty = match name.as_str() {
+        "int8" => ScalarType::I8,
+        "int16" => ScalarType::I16,
+        "int32" => ScalarType::I32,
+        "int64" => ScalarType::I64,
+        "uint8" => ScalarType::U8,
+        "uint16" => ScalarType::U16,
+        "uint32" => ScalarType::U32,
+        "uint64" => ScalarType::U64,
+        "float16" => ScalarType::F16,
+        "float32" => ScalarType::F32,
+        "float64" => ScalarType::F64,
+        "bool" => ScalarType::Bool,
+        _ => anyhow::bail!("unsupported dtype '{}'", name),
+    };
+    Ok(ty)
+}
+
+/// The name of the numpy dtype corresponding to `ty`, see `to_scalar_type`.
+pub(crate) fn numpy_dtype_name(ty: ScalarType) -> &'static str {
+    match ty {
+        ScalarType::I8 => "int8",
+        ScalarType::I16 => "int16",
+        ScalarType::I32 => "int32",
+        ScalarType::I64 => "int64",
+        ScalarType::U8 => "uint8",
+        ScalarType::U16 => "uint16",
+        ScalarType::U32 => "uint32",
+        ScalarType::U64 | ScalarType::Usize => "uint64",
+        ScalarType::F16 => "float16",
+        ScalarType::F32 => "float32",
+        ScalarType::F64 => "float64",
+        ScalarType::Bool => "bool",
+        ScalarType::String => "object",
+    }
+}
+
 impl FromPython<'_> for DynScalar {
     fn from_python(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
         if ob.is_instance_of::<pyo3::types::PyBool>() {
             ob.extract::<bool>().map(Into::into)
         } else if ob.is_instance_of::<pyo3::types::PyInt>() {
             ob.extract::<i64>().map(Into::into)
+                .or_else(|_| ob.extract::<u64>().map(Into::into))
         } else if ob.is_instance_of::<pyo3::types::PyString>() {
             ob.extract::<String>().map(Into::into)
         } else if ob.is_instance_of::<pyo3::types::PyFloat>() {
             ob.extract::<f64>().map(Into::into)
+        } else if isinstance_of_numpy_scalar(ob)?
+            || (isinstance_of_arr(ob)? && ob.getattr("ndim")?.extract::<usize>()? == 0)
+        {
+            // numpy scalars and 0-d arrays are stored as scalars of the same
+            // dtype. Other dtypes, e.g., float128, are converted by numpy.
+            let arr = ob.py().import_bound("numpy")?.call_method1("asarray", (ob,))?;
+            let kind = arr.getattr("dtype")?.getattr("kind")?.extract::<String>()?;
+            let name = arr.getattr("dtype")?.getattr("name")?.extract::<String>()?;
+            if kind == "U" || SCALAR_DTYPES.contains(&name.as_str()) {
+                DynArray::from_python(&arr)?.get(&[]).ok_or_else(||
+                    PyErr::new::<pyo3::exceptions::PyTypeError, _>("Could not convert to Scalar")
+                )
+            } else {
+                DynScalar::from_python(&ob.call_method0("item")?)
+            }
         } else {
             Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
                 "Could not convert to Scalar"
@@ -156,6 +279,7 @@ impl IntoPython for DynScalar {
             DynScalar::U32(s) => Ok(s.into_py(py)),
             DynScalar::U64(s) => Ok(s.into_py(py)),
             DynScalar::Usize(s) => Ok(s.into_py(py)),
+            DynScalar::F16(s) => Ok(s.to_f32().into_py(py)),
             DynScalar::F32(s) => Ok(s.into_py(py)),
             DynScalar::F64(s) => Ok(s.into_py(py)),
             DynScalar::Bool(s) => Ok(s.into_py(py)),
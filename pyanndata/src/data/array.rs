@@ -1,8 +1,10 @@
 use crate::data::{isinstance_of_csc, isinstance_of_csr, FromPython, IntoPython};
 
-use ndarray::ArrayD;
+use half::f16;
+use ndarray::{Array, ArrayD, Dimension};
 use nalgebra_sparse::{CsrMatrix, CscMatrix};
 use pyo3::{exceptions::PyTypeError, prelude::*};
+use pyo3::types::{IntoPyDict, PyList};
 use anndata::data::{DynArray, DynCsrMatrix, DynCscMatrix, DynCsrNonCanonical, CsrNonCanonical};
 use numpy::{PyReadonlyArrayDyn, IntoPyArray, PyArrayMethods};
 
@@ -41,6 +43,10 @@ macro_rules! proc_py_numeric {
                 let x: $ty_anno<u64> = $data;
                 x.into()
             }
+            "float16" => {
+                let x: $ty_anno<f16> = $data;
+                x.into()
+            }
             "float32" => {
                 let x: $ty_anno<f32> = $data;
                 x.into()
@@ -53,31 +59,53 @@ macro_rules! proc_py_numeric {
                 let x: $ty_anno<bool> = $data;
                 x.into()
             }
-            other => panic!("converting python type '{}' is not supported", other),
+            other => return Err(PyTypeError::new_err(format!(
+                "unsupported dtype '{}', the supported dtypes are bool, integers, floats and strings",
+                other,
+            ))),
         }
     };
 }
 
+/// Arrays are converted to C order regardless of the memory layout of the
+/// input, and keep their shape.
+fn c_order<T: Clone, D: Dimension>(arr: Array<T, D>) -> Array<T, D> {
+    if arr.is_standard_layout() {
+        arr
+    } else {
+        arr.as_standard_layout().into_owned()
+    }
+}
+
 impl FromPython<'_> for DynArray {
     fn from_python(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
         let py = ob.py();
         let dtype = ob.getattr("dtype")?.getattr("char")?;
         let dtype = dtype.extract::<&str>()?;
         let arr = if dtype == "U" || dtype == "S" {
-            ob.getattr("astype")?.call1(("object",))?
+            // Byte strings are decoded first.
+            ob.call_method1("astype", ("U",))?.call_method1("astype", ("object",))?
                 .extract::<PyReadonlyArrayDyn<PyObject>>()?
                 .as_array()
                 .map(|x| x.extract::<String>(py).unwrap())
                 .into()
         } else if dtype == "O" {
-            ob.extract::<PyReadonlyArrayDyn<PyObject>>()?
-                .as_array()
-                .map(|x| x.extract::<String>(py).unwrap())
-                .into()
+            // Object arrays are only accepted if all values are strings.
+            // Mixed values are not converted implicitly, as the result would
+            // depend on how every value happens to be formatted.
+            let arr = ob.extract::<PyReadonlyArrayDyn<PyObject>>()?;
+            let arr = arr.as_array();
+            let values = arr.iter().map(|x| x.extract::<String>(py).map_err(|_| PyTypeError::new_err(format!(
+                "cannot store an array of dtype object containing a value of type {}; \
+                the supported dtypes are bool, integers, floats and strings, \
+                use `.astype(str)` to store the values as strings",
+                x.bind(py).get_type(),
+            )))).collect::<PyResult<Vec<_>>>()?;
+            ArrayD::from_shape_vec(arr.raw_dim(), values).unwrap().into()
         } else {
             let ty = ob.getattr("dtype")?.getattr("name")?;
             let ty = ty.extract::<&str>()?;
-            proc_py_numeric!(ty, ob.extract::<PyReadonlyArrayDyn<_>>()?.to_owned_array(), ArrayD)
+            proc_py_numeric!(ty, c_order(ob.extract::<PyReadonlyArrayDyn<_>>()?.to_owned_array()), ArrayD)
         };
         Ok(arr)
     }
@@ -236,19 +264,28 @@ impl FromPython<'_> for DynCscMatrix {
 impl IntoPython for DynArray {
     fn into_python(self, py: Python<'_>) -> PyResult<PyObject> {
         let res = match self {
-            DynArray::I8(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::I16(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::I32(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::I64(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::U8(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::U16(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::U32(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::U64(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::Usize(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::F32(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::F64(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::Bool(arr) => arr.into_pyarray_bound(py).to_object(py),
-            DynArray::String(_) => todo!(),
+            DynArray::I8(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::I16(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::I32(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::I64(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::U8(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::U16(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::U32(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::U64(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::Usize(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::F16(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::F32(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::F64(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::Bool(arr) => c_order(arr).into_pyarray_bound(py).to_object(py),
+            DynArray::String(arr) => {
+                // Like anndata, strings are returned as an array of dtype object.
+                let shape = arr.shape().to_vec();
+                let values = PyList::new_bound(py, c_order(arr).into_raw_vec());
+                PyModule::import_bound(py, "numpy")?
+                    .call_method("array", (values,), Some(&[("dtype", "object")].into_py_dict_bound(py)))?
+                    .call_method1("reshape", (shape,))?
+                    .to_object(py)
+            }
             DynArray::Categorical(_) => todo!(),
         };
         Ok(res)
@@ -285,6 +322,7 @@ impl IntoPython for DynCsrMatrix {
             DynCsrMatrix::U32(csr) => helper(csr, py),
             DynCsrMatrix::U64(csr) => helper(csr, py),
             DynCsrMatrix::Usize(csr) => helper(csr, py),
+            DynCsrMatrix::F16(csr) => helper(csr, py),
             DynCsrMatrix::F32(csr) => helper(csr, py),
             DynCsrMatrix::F64(csr) => helper(csr, py),
             DynCsrMatrix::Bool(csr) => helper(csr, py),
@@ -323,6 +361,7 @@ impl IntoPython for DynCsrNonCanonical {
             DynCsrNonCanonical::U32(csr) => helper(csr, py),
             DynCsrNonCanonical::U64(csr) => helper(csr, py),
             DynCsrNonCanonical::Usize(csr) => helper(csr, py),
+            DynCsrNonCanonical::F16(csr) => helper(csr, py),
             DynCsrNonCanonical::F32(csr) => helper(csr, py),
             DynCsrNonCanonical::F64(csr) => helper(csr, py),
             DynCsrNonCanonical::Bool(csr) => helper(csr, py),
@@ -362,6 +401,7 @@ impl IntoPython for DynCscMatrix {
             DynCscMatrix::U32(csc) => helper(csc, py),
             DynCscMatrix::U64(csc) => helper(csc, py),
             DynCscMatrix::Usize(csc) => helper(csc, py),
+            DynCscMatrix::F16(csc) => helper(csc, py),
             DynCscMatrix::F32(csc) => helper(csc, py),
             DynCscMatrix::F64(csc) => helper(csc, py),
             DynCscMatrix::Bool(csc) => helper(csc, py),
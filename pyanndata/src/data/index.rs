@@ -0,0 +1,61 @@
+use anndata::data::DataFrameIndex;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::PyType;
+
+/// The Python class of [`PyIndex`]. It subclasses `list`, so that the names
+/// can be used wherever a list of strings was accepted before.
+const INDEX_CLASS: &str = r#"
+class Index(list):
+    """Names of the observations or variables.
+
+    It is a list of strings, and additionally supports looking up the
+    positions of names, like `pandas.Index`.
+    """
+
+    __slots__ = ()
+
+    def get_indexer(self, names):
+        """Return the positions of the given names.
+
+        Parameters
+        ----------
+        names : list[str]
+
+        Returns
+        -------
+        np.ndarray
+            The position of each name, or -1 if the name is not in the index.
+        """
+        import numpy as np
+        positions = {}
+        for i, name in enumerate(self):
+            positions.setdefault(name, i)
+        return np.array([positions.get(name, -1) for name in names], dtype=np.intp)
+
+    def to_list(self):
+        """Return the names as a plain list."""
+        return list(self)
+"#;
+
+static CLASS: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+/// Names of the observations or variables, converted to an `Index`, i.e., a
+/// list of strings with the `get_indexer` method of `pandas.Index`.
+pub struct PyIndex(Vec<String>);
+
+impl From<DataFrameIndex> for PyIndex {
+    fn from(index: DataFrameIndex) -> Self {
+        Self(index.into_vec())
+    }
+}
+
+impl IntoPy<PyObject> for PyIndex {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        let class = CLASS.get_or_try_init(py, || -> PyResult<_> {
+            let module = PyModule::from_code_bound(py, INDEX_CLASS, "index.py", "anndata_rs.index")?;
+            Ok(module.getattr("Index")?.downcast_into::<PyType>()?.unbind())
+        }).expect("cannot create the Index class");
+        class.bind(py).call1((self.0,)).expect("cannot create an Index").unbind()
+    }
+}
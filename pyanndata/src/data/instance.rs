@@ -27,6 +27,15 @@ pub fn isinstance_of_arr<'py>(obj: &Bound<'py, PyAny>) -> PyResult<bool> {
     )
 }
 
+pub fn isinstance_of_numpy_scalar<'py>(obj: &Bound<'py, PyAny>) -> PyResult<bool> {
+    obj.is_instance(
+        obj.py().import_bound("numpy")?
+            .getattr("generic")?
+            .downcast::<PyType>()
+            .unwrap(),
+    )
+}
+
 pub fn isinstance_of_pyanndata<'py>(obj: &Bound<'py, PyAny>) -> PyResult<bool> {
     obj.is_instance(
         obj.py().import_bound("anndata")?
@@ -1,58 +1,165 @@
 use crate::data::instance::*;
 
 use pyo3::prelude::*;
+use pyo3::exceptions::{PyIndexError, PyKeyError};
+use pyo3::types::{PyBool, PyInt, PyString, PyTuple};
 use anndata::data::{Shape, SelectInfo, SelectInfoElem};
 
 pub fn to_select_info(ob: &Bound<'_, PyAny>, shape: &Shape) -> PyResult<SelectInfo> {
-    let ndim = shape.ndim();
-    if is_none_slice(ob)? {
-        Ok(std::iter::repeat(SelectInfoElem::full()).take(ndim).collect())
-    } else if ob.is_instance_of::<pyo3::types::PyTuple>() {
-        ob.iter()?.zip(shape.as_ref())
-            .map(|(x, len)| to_select_elem(&x?, *len))
-            .collect()
+    to_array_index(ob, shape.as_ref(), None).map(|index| index.select)
+}
+
+/// Resolve names along the given axis (0 for observations, 1 for variables)
+/// to integer indices.
+pub type NameResolver = dyn Fn(usize, &[String]) -> anyhow::Result<Vec<usize>> + Send;
+
+/// The selection described by a numpy-style subscript.
+pub struct ArrayIndex {
+    pub select: SelectInfo,
+    /// Axes indexed by a scalar, which numpy removes from the result.
+    pub squeeze: Vec<usize>,
+}
+
+/// Parse a numpy-style subscript, e.g., `x[10:20, [0, 5, 7]]`, `x[mask, :]`,
+/// `x[..., 0]` or `x[:, "GeneA"]`. Names are only accepted when `names` is given.
+pub fn to_array_index(
+    ob: &Bound<'_, PyAny>,
+    shape: &[usize],
+    names: Option<&NameResolver>,
+) -> PyResult<ArrayIndex> {
+    let py = ob.py();
+    let items: Vec<_> = if ob.is_instance_of::<PyTuple>() {
+        ob.iter()?.collect::<PyResult<_>>()?
     } else {
-        todo!()
+        vec![ob.clone()]
+    };
+    let n_ellipsis = items.iter().filter(|x| x.is(&py.Ellipsis())).count();
+    if n_ellipsis > 1 {
+        return Err(PyIndexError::new_err("an index can only have a single ellipsis ('...')"));
+    }
+    let n_explicit = items.len() - n_ellipsis;
+    if n_explicit > shape.len() {
+        return Err(PyIndexError::new_err(format!(
+            "too many indices: array is {}-dimensional, but {} were indexed", shape.len(), n_explicit,
+        )));
+    }
+
+    let mut select = Vec::with_capacity(shape.len());
+    let mut squeeze = Vec::new();
+    for item in items {
+        if item.is(&py.Ellipsis()) {
+            select.extend(std::iter::repeat(SelectInfoElem::full()).take(shape.len() - n_explicit));
+        } else {
+            let axis = select.len();
+            let (elem, is_scalar) = parse_index(&item, axis, shape[axis], names)?;
+            if is_scalar {
+                squeeze.push(axis);
+            }
+            select.push(elem);
+        }
     }
+    select.resize(shape.len(), SelectInfoElem::full());
+    Ok(ArrayIndex { select: select.into_iter().collect(), squeeze })
 }
 
 pub fn to_select_elem(ob: &Bound<'_, PyAny>, length: usize) -> PyResult<SelectInfoElem> {
+    parse_index(ob, 0, length, None).map(|(select, _)| select)
+}
+
+/// Parse the index of a single axis. The returned flag indicates whether the
+/// index is a scalar.
+fn parse_index(
+    ob: &Bound<'_, PyAny>,
+    axis: usize,
+    length: usize,
+    names: Option<&NameResolver>,
+) -> PyResult<(SelectInfoElem, bool)> {
     let select = if let Ok(slice) = ob.downcast::<pyo3::types::PySlice>() {
         let s = slice.indices(length as i64)?;
-        ndarray::Slice { 
-            start: s.start,
-            end: Some(s.stop),
-            step: s.step,
-        }.into()
+        if s.step > 0 {
+            // Python may report a stop before the start for empty slices, e.g. `5:2`.
+            ndarray::Slice {
+                start: s.start,
+                end: Some(s.stop.max(s.start)),
+                step: s.step,
+            }.into()
+        } else {
+            // Negative steps iterate from `start` downwards, which differs from
+            // ndarray's semantics, so we select the explicit indices instead.
+            (0..s.slicelength as isize)
+                .map(|i| (s.start + i * s.step) as usize)
+                .collect::<Vec<_>>()
+                .into()
+        }
     } else if is_none_slice(ob)? {
         SelectInfoElem::full()
-    } else if ob.is_instance_of::<pyo3::types::PyInt>() {
-        ob.extract::<usize>()?.into()
-    } else if isinstance_of_arr(ob)? && ob.getattr("dtype")?.getattr("name")?.extract::<&str>()? == "bool" {
-        let arr = ob
-            .extract::<numpy::PyReadonlyArray1<bool>>()?;
-        if arr.len()? == length {
-            boolean_mask_to_indices(arr.as_array().into_iter().map(|x| *x)).into()
-        } else {
-            panic!("boolean mask dimension mismatched")
+    } else if let Ok(name) = ob.extract::<String>() {
+        return Ok((lookup_names(&[name], axis, names)?.into(), true));
+    } else if ob.is_instance_of::<PyInt>() || isinstance_of_numpy_scalar(ob)? {
+        return Ok((normalize_index(ob.extract()?, axis, length)?.into(), true));
+    } else if isinstance_of_arr(ob)? {
+        match ob.getattr("dtype")?.getattr("kind")?.extract::<&str>()? {
+            "b" => {
+                let arr = ob.extract::<numpy::PyReadonlyArray1<bool>>()?;
+                check_mask_len(arr.len()?, axis, length)?;
+                boolean_mask_to_indices(arr.as_array().iter().copied()).into()
+            }
+            "i" | "u" => {
+                let arr = ob.call_method1("astype", ("int64",))?;
+                let arr = arr.extract::<numpy::PyReadonlyArray1<i64>>()?;
+                arr.as_array().iter()
+                    .map(|i| normalize_index(*i as isize, axis, length))
+                    .collect::<PyResult<Vec<_>>>()?
+                    .into()
+            }
+            _ => lookup_names(&ob.extract::<Vec<String>>()?, axis, names)?.into(),
         }
     } else {
-        let boolean_mask: PyResult<Vec<bool>> =
-            ob.iter()?.map(|x| x.unwrap().extract()).collect();
-        match boolean_mask {
-            Ok(mask) => {
-                if mask.len() == length {
-                    boolean_mask_to_indices(mask.into_iter()).into()
-                } else if mask.len() == 0 {
-                    Vec::new().into()
-                } else {
-                    panic!("boolean mask dimension mismatched")
-                }
-            }
-            _ => ob.iter()?.map(|x| x.unwrap().extract()).collect::<PyResult<Vec<usize>>>()?.into(),
+        let items: Vec<_> = ob.iter()?.collect::<PyResult<_>>()?;
+        if items.is_empty() {
+            Vec::new().into()
+        } else if items.iter().all(|x| x.is_instance_of::<PyBool>()) {
+            check_mask_len(items.len(), axis, length)?;
+            boolean_mask_to_indices(items.iter().map(|x| x.extract().unwrap())).into()
+        } else if items[0].is_instance_of::<PyString>() {
+            let names_: Vec<String> = items.iter().map(|x| x.extract()).collect::<PyResult<_>>()?;
+            lookup_names(&names_, axis, names)?.into()
+        } else {
+            items.iter()
+                .map(|x| normalize_index(x.extract()?, axis, length))
+                .collect::<PyResult<Vec<_>>>()?
+                .into()
         }
     };
-    Ok(select)
+    Ok((select, false))
+}
+
+fn normalize_index(i: isize, axis: usize, length: usize) -> PyResult<usize> {
+    let j = if i < 0 { i + length as isize } else { i };
+    if j < 0 || j as usize >= length {
+        Err(PyIndexError::new_err(format!(
+            "index {} is out of bounds for axis {} with size {}", i, axis, length,
+        )))
+    } else {
+        Ok(j as usize)
+    }
+}
+
+fn check_mask_len(n: usize, axis: usize, length: usize) -> PyResult<()> {
+    if n == length {
+        Ok(())
+    } else {
+        Err(PyIndexError::new_err(format!(
+            "boolean index did not match axis {}: size is {} but the index has size {}", axis, length, n,
+        )))
+    }
+}
+
+fn lookup_names(names: &[String], axis: usize, resolver: Option<&NameResolver>) -> PyResult<Vec<usize>> {
+    match resolver {
+        Some(f) => f(axis, names).map_err(|e| PyKeyError::new_err(e.to_string())),
+        None => Err(PyIndexError::new_err("indexing by names is not supported for this element")),
+    }
 }
 
 fn boolean_mask_to_indices<I>(iter: I) -> Vec<usize>
@@ -2,8 +2,11 @@ pub mod anndata;
 pub mod data;
 pub mod container;
 
-pub use crate::anndata::{AnnData, AnnDataSet, PyAnnData, read, read_mtx, read_dataset};
+pub use crate::anndata::{
+    AnnData, AnnDataSet, AnnDataView, PyAnnData, read, read_h5ad_bundle, read_mtx, read_dataset,
+    set_categorical_threshold,
+};
 pub use crate::container::{
     PyAxisArrays, PyDataFrameElem, PyElem, PyElemCollection, PyArrayElem,
-    PyChunkedArray,
+    PyChunkedArray, PyMappedChunks, PyShuffledChunks, PyShuffledChunksIter,
 };
@@ -1 +1,2 @@
-from _anndata_rs import *
\ No newline at end of file
+from _anndata_rs import *
+from .parallel import map_chunks
@@ -0,0 +1,69 @@
+import multiprocessing
+from concurrent.futures import ProcessPoolExecutor
+import numpy as np
+import pandas as pd
+import polars as pl
+import scipy.sparse as sp
+
+from _anndata_rs import read
+
+def map_chunks(path, fn, n_workers=None, n_chunks=None):
+    """Apply a function to chunks of rows of X in multiple processes.
+
+    Each worker opens the file read-only and reads a disjoint range of rows,
+    as given by `AnnData.chunk_ranges`. The file must not be open for writing
+    while the workers are running.
+
+    Parameters
+    ----------
+    path : str | Path
+        The .h5ad file.
+    fn : Callable
+        The function applied to each chunk of X. It must be picklable, e.g.,
+        defined at the top level of a module.
+    n_workers : int | None
+        Number of worker processes. Default: the number of CPUs.
+    n_chunks : int | None
+        Number of chunks. Default: `n_workers`.
+
+    Returns
+    -------
+    The results of `fn`, concatenated along the first axis if they are numpy
+    arrays, scipy sparse matrices, pandas or polars objects, and a list otherwise.
+    """
+    n_workers = n_workers or multiprocessing.cpu_count()
+    adata = read(path, backed="r")
+    try:
+        ranges = adata.chunk_ranges(n_chunks or n_workers)
+    finally:
+        adata.close()
+    # Use spawn so that workers do not inherit the HDF5 state of the parent.
+    context = multiprocessing.get_context("spawn")
+    with ProcessPoolExecutor(n_workers, mp_context=context) as executor:
+        results = list(executor.map(
+            _run_chunk, [(str(path), fn, start, end) for start, end in ranges]
+        ))
+    return _concat(results)
+
+def _run_chunk(args):
+    path, fn, start, end = args
+    adata = read(path, backed="r")
+    try:
+        return fn(adata.X[start:end])
+    finally:
+        adata.close()
+
+def _concat(results):
+    if len(results) == 0:
+        return results
+    first = results[0]
+    if isinstance(first, np.ndarray) and first.ndim > 0:
+        return np.concatenate(results)
+    elif sp.issparse(first):
+        return sp.vstack(results, format=first.format)
+    elif isinstance(first, (pd.DataFrame, pd.Series)):
+        return pd.concat(results)
+    elif isinstance(first, (pl.DataFrame, pl.Series)):
+        return pl.concat(results)
+    else:
+        return results
@@ -0,0 +1,63 @@
+"""Round trip of obs with a large string column, from pandas and from polars.
+
+Polars data frames are passed to Rust through the Arrow C data interface,
+while pandas data frames are first converted to polars, and read back with
+`obs_as_pandas`. The difference between the two paths is the cost of the
+pandas conversions. Run with `python benchmarks/obs_strings.py`.
+"""
+
+import tempfile
+import timeit
+from pathlib import Path
+
+import pandas as pd
+import polars as pl
+
+from anndata_rs import AnnData, read
+
+
+def barcodes(n):
+    return [f"{i:016x}-1" for i in range(n)]
+
+
+def write(filename, obs):
+    adata = AnnData(filename=filename, mode="w")
+    adata.obs = obs
+    adata.close()
+
+
+def read_polars(filename):
+    adata = read(filename, backed="r")
+    obs = adata.obs[:]
+    adata.close()
+    return obs
+
+
+def read_pandas(filename):
+    adata = read(filename, backed="r")
+    obs = adata.obs_as_pandas()
+    adata.close()
+    return obs
+
+
+def bench(f, repeat=5):
+    return min(timeit.repeat(f, number=1, repeat=repeat))
+
+
+def main():
+    print(f"{'n':>9} {'frame':>7} {'write (s)':>10} {'read (s)':>10}")
+    with tempfile.TemporaryDirectory() as dir:
+        filename = str(Path(dir) / "bench.h5ad")
+        for n in [100_000, 1_000_000]:
+            frames = {
+                "polars": (pl.DataFrame({"barcode": barcodes(n)}), read_polars),
+                "pandas": (pd.DataFrame({"barcode": barcodes(n)}), read_pandas),
+            }
+            for name, (obs, reader) in frames.items():
+                t_write = bench(lambda: write(filename, obs))
+                t_read = bench(lambda: reader(filename))
+                print(f"{n:>9} {name:>7} {t_write:>10.3f} {t_read:>10.3f}")
+
+
+if __name__ == "__main__":
+    main()
@@ -5,17 +5,53 @@ use pyo3::{
     pymodule, types::PyModule, PyResult, Python,
 };
 use pyo3::prelude::*;
+use std::sync::OnceLock;
+
+static LOG_RESET: OnceLock<pyo3_log::ResetHandle> = OnceLock::new();
+
+/// Set the level of the messages logged by the "anndata" logger.
+///
+/// At the "debug" level, file opens, element reads and writes (with byte
+/// counts), cache hits and misses, and long-running operations are reported,
+/// if the module is built with the `trace` feature.
+///
+/// Parameters
+/// ----------
+/// level: str
+///     One of "debug", "info", "warning", "error" or "critical".
+#[pyfunction]
+fn set_verbosity(py: Python<'_>, level: &str) -> PyResult<()> {
+    let logger = py.import("logging")?.call_method1("getLogger", ("anndata",))?;
+    logger.call_method1("setLevel", (level.to_uppercase(),))?;
+    if !logger.call_method0("hasHandlers")?.extract::<bool>()? {
+        let handler = py.import("logging")?.call_method0("StreamHandler")?;
+        logger.call_method1("addHandler", (handler,))?;
+    }
+    // The levels are cached on the Rust side.
+    if let Some(handle) = LOG_RESET.get() {
+        handle.reset();
+    }
+    Ok(())
+}
 
 #[pymodule]
 fn _anndata_rs(_py: Python, m: &PyModule) -> PyResult<()> {
-    pyo3_log::init();
+    let _ = LOG_RESET.set(pyo3_log::init());
+    // Whether debug traces are compiled in, see `set_verbosity`.
+    m.add("_TRACE", cfg!(feature = "trace"))?;
 
     m.add_class::<AnnData>().unwrap();
     m.add_class::<AnnDataSet>().unwrap();
+    m.add_class::<AnnDataView>().unwrap();
+    // Make `AnnData` objects picklable by reference to this module.
+    m.getattr("AnnData")?.setattr("__module__", m.name()?)?;
 
     m.add_function(wrap_pyfunction!(read, m)?)?;
     m.add_function(wrap_pyfunction!(read_dataset, m)?)?;
     m.add_function(wrap_pyfunction!(read_mtx, m)?)?;
+    m.add_function(wrap_pyfunction!(read_h5ad_bundle, m)?)?;
+    m.add_function(wrap_pyfunction!(set_categorical_threshold, m)?)?;
+    m.add_function(wrap_pyfunction!(set_verbosity, m)?)?;
     /*
     m.add_class::<StackedAnnData>().unwrap();
     m.add_class::<element::PyElemCollection>().unwrap();
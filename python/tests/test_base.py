@@ -1,6 +1,8 @@
-from anndata_rs import AnnData, AnnDataSet, read
+from anndata_rs import AnnData, AnnDataSet, read, read_h5ad_bundle, map_chunks, set_categorical_threshold, set_verbosity
 
+import _anndata_rs
 import math
+import os
 import numpy as np
 import pandas as pd
 import polars as pl
@@ -80,6 +82,29 @@ def test_assign_arrays(x, tmp_path):
     x_ = adata.uns['x']
     np.testing.assert_array_equal(x_, x)
 
+@given(x=arrays(
+    integer_dtypes(endianness='=') | floating_dtypes(endianness='=', sizes=(32, 64)),
+    array_shapes(min_dims=3, max_dims=4, min_side=1, max_side=5),
+))
+@settings(deadline=None, suppress_health_check = [HealthCheck.function_scoped_fixture])
+def test_nd_arrays(x, tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename = file)
+    adata.uns['x'] = x
+    adata.uns['x_f'] = np.asfortranarray(x)
+    adata.obsm['x'] = x
+    np.testing.assert_array_equal(adata.obsm.el('x')[1:, :, :1], x[1:, :, :1])
+    adata.close()
+
+    adata = read(file)
+    for key in ['x', 'x_f']:
+        x_ = adata.uns[key]
+        assert x_.shape == x.shape
+        assert x_.flags['C_CONTIGUOUS']
+        np.testing.assert_array_equal(x_, x)
+    np.testing.assert_array_equal(adata.obsm['x'], x)
+    adata.close()
+
 @given(x=st.floats())
 @settings(deadline=None, suppress_health_check = [HealthCheck.function_scoped_fixture])
 def test_assign_floats(x, tmp_path):
@@ -88,6 +113,60 @@ def test_assign_floats(x, tmp_path):
     x_ = adata.uns['x']
     assert (x_ == x or (math.isnan(x) and math.isnan(x_)))
 
+def test_obsm_values(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)))
+    adata.obsm = {
+        "lists": [[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]],
+        "df": pd.DataFrame({"a": [1, 2, 3], "b": ["x", "y", "z"]}),
+    }
+    adata.obsm["dict"] = {"u": [1, 2, 3], "v": [0.5, 1.5, 2.5]}
+    np.testing.assert_array_equal(adata.obsm["lists"], np.array([[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]))
+    with pytest.raises(TypeError, match="'bad'.*set"):
+        adata.obsm = {"bad": {1, 2, 3}}
+    with pytest.raises(TypeError, match="mixed"):
+        adata.obsm["mixed"] = [[1.0, None], [2.0, 3.0], [4.0, 5.0]]
+    adata.close()
+
+    adata = read(file)
+    df = adata.obsm["df"]
+    assert isinstance(df, pd.DataFrame)
+    assert list(df.columns) == ["a", "b"]
+    assert df["b"].to_list() == ["x", "y", "z"]
+    assert list(adata.obsm["dict"].columns) == ["u", "v"]
+    assert isinstance(adata.obsm.el("df")[:], pl.DataFrame)
+    adata.close()
+
+def test_string_array(tmp_path):
+    file = h5ad(tmp_path)
+    seq = np.array([["A", "CG", "T"], ["GG", "A", "C"], ["T", "T", "ACG"], ["C", "G", "A"]])
+    adata = AnnData(filename=file, X=np.ones((4, 2)))
+    adata.obsm["seq"] = seq
+    adata.uns["codes"] = np.array([b"x", b"yz"])
+    np.testing.assert_array_equal(adata.obsm["seq"], seq)
+    np.testing.assert_array_equal(adata.obsm["seq"][[2, 0], 1:], seq[[2, 0], 1:])
+    adata.close()
+
+    adata = read(file)
+    assert adata.obsm["seq"].dtype == "object"
+    np.testing.assert_array_equal(adata.obsm["seq"][1:3], seq[1:3])
+    assert list(adata.uns["codes"]) == ["x", "yz"]
+    adata.close()
+
+def test_object_dtype(tmp_path):
+    adata = AnnData(filename=h5ad(tmp_path), X=np.ones((3, 2)))
+    mixed = np.array([["a", 1], [2.5, "b"], ["c", "d"]], dtype=object)
+    with pytest.raises(TypeError, match="astype"):
+        adata.obsm["mixed"] = mixed
+    with pytest.raises(TypeError, match="object"):
+        adata.X = np.array([[1, None], [2, 3], [4, 5]], dtype=object)
+    with pytest.raises(TypeError, match="complex128"):
+        adata.X = np.ones((3, 2), dtype=np.complex128)
+    np.testing.assert_array_equal(adata.X[:], np.ones((3, 2)))
+    adata.obsm["mixed"] = mixed.astype(str)
+    assert adata.obsm["mixed"][0, 1] == "1"
+    adata.close()
+
 def test_creation(tmp_path):
     adata = AnnData(filename=h5ad(tmp_path))
     assert adata.n_obs == 0
@@ -132,6 +211,1425 @@ def test_type(tmp_path):
     adata.uns["dict"] = x
     assert adata.uns["dict"] == x
 
+def test_uns_scalars(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename = file)
+    scalars = {
+        "int": (15, int, 15),
+        "float": (1.5, float, 1.5),
+        "bool": (True, bool, True),
+        "long_str": ("x" * 100000, str, "x" * 100000),
+        "np_int": (np.int32(7), int, 7),
+        "np_float": (np.float32(0.5), float, 0.5),
+        "np_bool": (np.bool_(False), bool, False),
+        "np_0d": (np.array(5), int, 5),
+    }
+    for k, (v, _, _) in scalars.items():
+        adata.uns[k] = v
+    for k, (_, ty, expected) in scalars.items():
+        assert type(adata.uns[k]) is ty
+        assert adata.uns[k] == expected
+    adata.close()
+
+    adata = read(file)
+    for k, (_, ty, expected) in scalars.items():
+        assert type(adata.uns[k]) is ty
+        assert adata.uns[k] == expected
+    adata.close()
+
+def test_delete_keys(tmp_path):
+    adata = AnnData(filename = h5ad(tmp_path), X = np.ones((10, 5)))
+    for k in ["a", "b", "c", "d"]:
+        adata.obsm[k] = np.zeros((10, 3))
+        adata.uns[k] = k
+    del adata.obsm[["b", "d"]]
+    del adata.uns["a"]
+    assert sorted(adata.obsm.keys()) == ["a", "c"]
+    assert sorted(adata.uns.keys()) == ["b", "c", "d"]
+    np.testing.assert_array_equal(adata.obsm["c"], np.zeros((10, 3)))
+
+    adata.obsm["big"] = np.random.rand(10, 10000)
+    adata.flush()
+    size = os.path.getsize(adata.filename)
+    del adata.obsm[["big"]]
+    adata.repack()
+    assert os.path.getsize(adata.filename) < size
+    assert sorted(adata.obsm.keys()) == ["a", "c"]
+    np.testing.assert_array_equal(adata.X[:], np.ones((10, 5)))
+
+def test_write_overwrite(tmp_path):
+    adata = AnnData(filename = h5ad(tmp_path), X = np.ones((10, 5)))
+    output = h5ad(tmp_path)
+    adata.write(output)
+    adata.X = np.zeros((10, 5))
+    adata.write(output)
+    with pytest.raises(Exception):
+        adata.write(output, overwrite=False)
+    with pytest.raises(Exception):
+        adata.copy(output, overwrite=False)
+    out = read(output)
+    np.testing.assert_array_equal(out.X[:], np.zeros((10, 5)))
+    out.close()
+    assert not any(p.name.endswith(".tmp") for p in tmp_path.iterdir())
+
+def test_set_from_iter(tmp_path):
+    adata = AnnData(filename = h5ad(tmp_path), X = np.ones((100, 5)))
+    chunks = [np.full((10, 3), i, dtype=np.float64) for i in range(10)]
+    adata.obsm.set_from_iter("X_pca", (c for c in chunks))
+    np.testing.assert_array_equal(adata.obsm["X_pca"], np.concatenate(chunks))
+
+    with pytest.raises(Exception):
+        adata.obsm.set_from_iter("X_bad", (c for c in chunks[:9]))
+    def failing():
+        yield chunks[0]
+        raise ValueError("stop")
+    with pytest.raises(ValueError):
+        adata.obsm.set_from_iter("X_fail", failing())
+    assert adata.obsm.keys() == ["X_pca"]
+
+def test_missing_x(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename = file)
+    adata.obs = pl.DataFrame({"a": [1, 2, 3]})
+    adata.var = pl.DataFrame({"b": ["x", "y"]})
+    adata.obsm["X_pca"] = np.zeros((3, 4))
+    adata.close()
+
+    adata = read(file)
+    assert adata.X is None
+    assert adata.shape == (3, 2)
+    np.testing.assert_array_equal(adata.obsm["X_pca"], np.zeros((3, 4)))
+    adata.close()
+
+def test_infer_shape(tmp_path):
+    adata = AnnData(filename = h5ad(tmp_path))
+    assert adata.shape == (0, 0)
+    adata.n_vars = 4
+    adata.obsm["X_pca"] = np.zeros((3, 2))
+    assert adata.shape == (3, 4)
+
+    with pytest.raises(Exception):
+        adata.n_obs = 5
+    with pytest.raises(Exception):
+        adata.obs = pl.DataFrame({"a": [1, 2]})
+    with pytest.raises(Exception):
+        adata.X = np.zeros((3, 5))
+    adata.X = np.zeros((3, 4))
+    adata.close()
+
+def test_spatial(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename = file, X = np.ones((10, 5)))
+    coords = np.random.rand(10, 2)
+    hires = np.random.randint(0, 255, (20, 20, 3), dtype=np.uint8)
+    scalefactors = {"tissue_hires_scalef": 0.5, "spot_diameter_fullres": 89.4}
+
+    assert adata.spatial() is None
+    with pytest.raises(Exception):
+        adata.set_spatial(np.zeros((10, 3)))
+    adata.set_spatial(coords, {"slide1": {"images": {"hires": hires}, "scalefactors": scalefactors}})
+    adata.close()
+
+    adata = read(file)
+    spatial = adata.spatial()
+    np.testing.assert_array_equal(spatial["coordinates"], coords)
+    np.testing.assert_array_equal(spatial["libraries"]["slide1"]["images"]["hires"], hires)
+    assert spatial["libraries"]["slide1"]["scalefactors"] == scalefactors
+    adata.close()
+
+def test_dirty(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename = file)
+    assert not adata.is_dirty
+    assert adata.last_modified is None
+
+    adata.X = np.ones((10, 3))
+    assert adata.is_dirty
+    t = adata.last_modified
+    assert t is not None
+    adata.flush()
+    assert not adata.is_dirty
+
+    adata.obsm["X_pca"] = np.zeros((10, 2))
+    assert adata.is_dirty
+    assert adata.last_modified >= t
+    t = adata.last_modified
+    adata.close()
+
+    adata = read(file)
+    assert not adata.is_dirty
+    assert adata.last_modified == t
+    adata.close()
+
+def test_sum(tmp_path):
+    X = sp.random(1234, 57, 0.1, format="csr", dtype=np.int64)
+    adata = AnnData(filename = h5ad(tmp_path), X = X)
+    dense = X.toarray()
+    np.testing.assert_array_equal(adata.X.sum(axis=0), dense.sum(axis=0))
+    np.testing.assert_array_equal(adata.X.sum(axis=1), dense.sum(axis=1))
+    assert adata.X.sum() == dense.sum()
+    adata.close()
+
+def test_fetch(tmp_path):
+    adata = AnnData(filename = h5ad(tmp_path), X = np.ones((10, 5)))
+    adata.obsm["X_pca"] = np.random.rand(10, 3)
+    adata.obsm["X_umap"] = np.random.rand(10, 2)
+    adata.uns["a"] = 1
+
+    result = adata.fetch(obsm=["X_pca", "X_umap"], uns=["a"])
+    np.testing.assert_array_equal(result["obsm"]["X_pca"], adata.obsm["X_pca"])
+    np.testing.assert_array_equal(result["obsm"]["X_umap"], adata.obsm["X_umap"])
+    assert result["uns"]["a"] == 1
+
+    with pytest.warns(UserWarning, match="obsp/missing"):
+        result = adata.fetch(obsm=["X_pca"], obsp=["missing"])
+    assert list(result.keys()) == ["obsm"]
+    adata.close()
+
+def test_array_protocol(tmp_path):
+    x = np.arange(20 * 7, dtype=np.int32).reshape(20, 7)
+    adata = AnnData(X=csr_matrix(x), filename=h5ad(tmp_path))
+    adata.obsm["X_pca"] = x[:, :3].astype(np.float64)
+
+    np.testing.assert_array_equal(np.asarray(adata.X), x)
+    assert np.asarray(adata.X, dtype=np.float32).dtype == np.float32
+    np.testing.assert_array_equal(adata.X.toarray(), x)
+    assert sp.isspmatrix_csr(adata.X.tocsr())
+    np.testing.assert_array_equal(adata.X.tocsr().todense(), x)
+
+    pca = adata.obsm.el("X_pca")
+    np.testing.assert_array_equal(np.asarray(pca), x[:, :3])
+    np.testing.assert_array_equal(pca.tocsr().todense(), x[:, :3])
+    adata.close()
+
+def test_null_counts(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file)
+    adata.obs = pl.DataFrame({
+        "a": [1, None, 3, None, 5],
+        "b": [1.0, 2.0, 3.0, 4.0, 5.0],
+    })
+    assert adata.obs.null_counts() == {"a": 2, "b": 0}
+    adata.close()
+
+    adata = read(file)
+    assert adata.obs.null_counts() == {"a": 2, "b": 0}
+    assert adata.obs["a"].to_list() == [1, None, 3, None, 5]
+    adata.close()
+
+def _sum_x(adata):
+    return adata.X[:].sum()
+
+def test_pickle(tmp_path):
+    import copy
+    import multiprocessing
+    import pickle
+    from concurrent.futures import ProcessPoolExecutor
+
+    file = h5ad(tmp_path)
+    x = np.arange(20 * 7).reshape(20, 7)
+    AnnData(X=x, filename=file).close()
+    adata = read(file, backed="r")
+
+    adata2 = pickle.loads(pickle.dumps(adata))
+    assert adata2.filename == adata.filename
+    np.testing.assert_array_equal(adata2.X[:], x)
+
+    # Worker processes re-open the file with fresh read-only handles.
+    with ProcessPoolExecutor(2, mp_context=multiprocessing.get_context("spawn")) as executor:
+        assert list(executor.map(_sum_x, [adata] * 3)) == [x.sum()] * 3
+
+    shallow = copy.copy(adata)
+    assert shallow.filename == adata.filename
+    deep = copy.deepcopy(adata)
+    assert deep.filename != adata.filename
+    np.testing.assert_array_equal(deep.X[:], x)
+    deep.close()
+    assert not Path(deep.filename).exists()
+    adata.close()
+
+    with pytest.raises(Exception):
+        pickle.dumps(adata)
+
+_inherited = None
+
+def _sum_x_fresh(adata):
+    return adata is not _inherited, adata.X[:].sum()
+
+def test_pickle_fork(tmp_path):
+    import multiprocessing
+    from concurrent.futures import ProcessPoolExecutor
+    global _inherited
+
+    if "fork" not in multiprocessing.get_all_start_methods():
+        pytest.skip("fork is not available")
+    file = h5ad(tmp_path)
+    x = np.arange(20 * 7).reshape(20, 7)
+    AnnData(X=x, filename=file).close()
+    adata = read(file, backed="r")
+
+    # Forked workers inherit `_inherited` with the handle of the parent, but
+    # receive an unpickled object with its own handle.
+    _inherited = adata
+    try:
+        with ProcessPoolExecutor(2, mp_context=multiprocessing.get_context("fork")) as executor:
+            assert list(executor.map(_sum_x_fresh, [adata] * 3)) == [(True, x.sum())] * 3
+    finally:
+        _inherited = None
+    np.testing.assert_array_equal(adata.X[:], x)
+    adata.close()
+
+def test_to_df(tmp_path):
+    x = np.arange(5 * 3, dtype=np.float64).reshape(5, 3)
+    adata = AnnData(X=x, filename=h5ad(tmp_path))
+    adata.obs_names = [f"c{i}" for i in range(5)]
+    adata.var_names = ["g0", "g1", "g2"]
+    adata.layers["counts"] = csr_matrix(x * 2)
+
+    df = adata.to_df()
+    assert df.shape == (5, 3)
+    assert list(df.index) == adata.obs_names
+    assert list(df.columns) == adata.var_names
+    np.testing.assert_array_equal(df.to_numpy(), x)
+
+    with pytest.warns(UserWarning):
+        df = adata.to_df(layer="counts")
+    np.testing.assert_array_equal(df.to_numpy(), x * 2)
+
+    with pytest.raises(Exception):
+        adata.to_df(layer="missing")
+
+def _row_sums(x):
+    return np.asarray(x.sum(axis=1)).ravel()
+
+def test_map_chunks(tmp_path):
+    file = h5ad(tmp_path)
+    # All non-zero entries are in the first ten rows.
+    x = np.zeros((40, 5), dtype=np.int64)
+    x[:10] = np.arange(50).reshape(10, 5) + 1
+    AnnData(X=csr_matrix(x), filename=file).close()
+
+    adata = read(file, backed="r")
+    ranges = adata.chunk_ranges(2)
+    assert ranges[0][0] == 0 and ranges[-1][1] == 40
+    assert ranges[0][1] <= 10
+    adata.close()
+
+    np.testing.assert_array_equal(map_chunks(file, _row_sums, n_workers=2), x.sum(axis=1))
+    np.testing.assert_array_equal(
+        map_chunks(file, _row_sums, n_workers=2, n_chunks=5), x.sum(axis=1)
+    )
+
+def test_shuffled_chunks(tmp_path):
+    x = np.arange(40, dtype=np.int64).reshape(20, 2)
+    adata = AnnData(X=x, filename=h5ad(tmp_path))
+    chunks = adata.X.shuffled_chunks(6, seed=1)
+    assert len(chunks) == 4
+
+    # Each iteration is a new pass over the same batches.
+    first = [idx.tolist() for _, idx in chunks]
+    second = [idx.tolist() for _, idx in chunks]
+    assert first == second
+    assert sorted(i for batch in first for i in batch) == list(range(20))
+    adata.close()
+
+def test_append(tmp_path):
+    x = sp.random(1000, 20, 0.1, format="csr", dtype=np.float64)
+    adata = AnnData(filename=h5ad(tmp_path))
+    for i in range(10):
+        obs = pd.DataFrame({"batch": np.repeat(i, 100)})
+        adata.append(x[i * 100:(i + 1) * 100], obs=obs)
+        assert adata.n_obs == (i + 1) * 100
+        assert adata.obs[:].shape[0] == adata.n_obs
+        assert adata.X.shape[0] == adata.n_obs
+
+    with pytest.raises(Exception):
+        adata.append(x[:100], obs=pd.DataFrame({"other": np.repeat(0, 100)}))
+    assert adata.n_obs == 1000
+    np.testing.assert_array_equal(adata.X[:].todense(), x.todense())
+    np.testing.assert_array_equal(adata.obs["batch"], np.repeat(np.arange(10), 100))
+
+def test_read_bundle(tmp_path):
+    import os
+    import tarfile
+
+    file = h5ad(tmp_path)
+    x = np.arange(6 * 4).reshape(6, 4)
+    AnnData(X=x, filename=file).close()
+    bundle = str(tmp_path / "bundle.tar.gz")
+    with tarfile.open(bundle, "w:gz") as tar:
+        tar.add(file, arcname="dataset/data.h5ad")
+
+    adata = read_h5ad_bundle(bundle)
+    extracted = adata.filename
+    assert os.path.exists(extracted)
+    np.testing.assert_array_equal(adata.X[:], x)
+    adata.close()
+    assert not os.path.exists(extracted)
+
+def test_categorical_obs(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.zeros((6, 2)))
+    adata.obs = pd.DataFrame({
+        "tissue": ["cortex", "cortex", "liver", "cortex", "liver", "cortex"],
+        "barcode": ["a", "b", "c", "d", "e", "f"],
+        "batch": pd.Categorical(["1", "2", "3", "4", "5", "6"]),
+    })
+    adata.close()
+
+    obs = read(file, backed=None).obs
+    assert obs["tissue"].dtype == "category"
+    assert obs["batch"].dtype == "category"
+    assert obs["barcode"].dtype != "category"
+    assert list(obs["tissue"]) == ["cortex", "cortex", "liver", "cortex", "liver", "cortex"]
+
+    set_categorical_threshold(None)
+    try:
+        adata = read(file)
+        adata.obs = pl.DataFrame({"tissue": ["cortex"] * 6})
+        adata.close()
+    finally:
+        set_categorical_threshold(0.5)
+    adata = read(file)
+    assert adata.obs["tissue"].dtype == pl.String
+    adata.close()
+
+def test_column_encoding(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.zeros((4, 2)))
+    adata.obs = pl.DataFrame({"name": ["a", "b", "c", "a"], "n": [1, 2, 3, 4]})
+    adata.obs.as_categorical("name")
+    assert adata.obs["name"].dtype == pl.Categorical
+    assert adata.obs["name"].to_list() == ["a", "b", "c", "a"]
+    with pytest.raises(Exception):
+        adata.obs.as_categorical("n")
+    adata.close()
+    with h5py.File(file, "r") as f:
+        assert f["obs/name"].attrs["encoding-type"] == "categorical"
+        assert list(f["obs/name/codes"][:]) == [0, 1, 2, 0]
+        assert [x.decode() for x in f["obs/name/categories"][:]] == ["a", "b", "c"]
+
+    adata = read(file)
+    adata.obs.as_string("name")
+    assert adata.obs["name"].dtype == pl.String
+    assert adata.obs["name"].to_list() == ["a", "b", "c", "a"]
+    adata.close()
+    with h5py.File(file, "r") as f:
+        assert f["obs/name"].attrs["encoding-type"] == "string-array"
+
+def test_to_memory(tmp_path):
+    import anndata
+
+    file = h5ad(tmp_path)
+    x = np.arange(12, dtype=np.float64).reshape(4, 3)
+    adata = AnnData(filename=file, X=x)
+    adata.obs_names = ["a", "b", "c", "d"]
+    adata.obs = pl.DataFrame({"n": [1, 2, 3, 4]})
+    adata.obsm["pca"] = x[:, :2]
+    adata.layers["counts"] = csr_matrix(x)
+    adata.uns["key"] = "value"
+
+    result = adata.to_memory()
+    assert isinstance(result, anndata.AnnData)
+    assert result.shape == (4, 3)
+    assert list(result.obs_names) == ["a", "b", "c", "d"]
+    assert list(result.obs["n"]) == [1, 2, 3, 4]
+    np.testing.assert_array_equal(result.X, x)
+    np.testing.assert_array_equal(result.obsm["pca"], x[:, :2])
+    np.testing.assert_array_equal(result.layers["counts"].toarray(), x)
+    assert result.uns["key"] == "value"
+    adata.close()
+
+def test_unsupported_uns(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)))
+    adata.uns["a"] = 1
+    adata.close()
+    with h5py.File(file, "a") as f:
+        group = f["uns"].create_group("awkward")
+        group.attrs["encoding-type"] = "awkward-array"
+        group.create_dataset("node0-data", data=np.arange(3))
+
+    adata = read(file)
+    assert adata.uns["a"] == 1
+    assert "awkward" in adata.uns.keys()
+    assert len(adata.validate()) == 1
+    with pytest.raises(Exception, match="awkward-array"):
+        adata.uns["awkward"]
+    output = h5ad(tmp_path)
+    adata.write(output)
+    adata.close()
+    with h5py.File(output, "r") as f:
+        assert f["uns/awkward"].attrs["encoding-type"] == "awkward-array"
+
+def test_malformed_raw(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)))
+    adata.close()
+    with h5py.File(file, "a") as f:
+        f.create_group("raw").create_dataset("X", data=np.ones((3, 4)))
+
+    adata = read(file)
+    np.testing.assert_array_equal(adata.X[:], np.ones((3, 2)))
+    assert any(v.startswith("raw") for v in map(str, adata.validate()))
+    output = h5ad(tmp_path)
+    adata.write(output)
+    adata.del_raw()
+    adata.close()
+    with h5py.File(output, "r") as f:
+        assert "raw/X" in f
+    with h5py.File(file, "r") as f:
+        assert "raw" not in f
+
+def test_densify_x(tmp_path):
+    X = sp.random(20, 7, density=0.3, format="csr", dtype=np.float64)
+    adata = AnnData(filename=h5ad(tmp_path), X=X)
+    adata.densify_x("dense", chunk_size=3)
+    np.testing.assert_array_equal(adata.layers["dense"], X.toarray())
+    assert sp.issparse(adata.X[:])
+
+    adata = AnnData(filename=h5ad(tmp_path))
+    with pytest.raises(Exception):
+        adata.densify_x("dense")
+
+def test_to_csv(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)))
+    adata.obs_names = ["c1", "c2", "c3"]
+    adata.obs = pl.DataFrame({
+        "n_counts": [100, 600, 700],
+        "cell_type": pl.Series(["T", "B", "T"], dtype=pl.Categorical),
+        "score": [0.5, None, 1.5],
+    })
+
+    output = tmp_path / "obs.csv"
+    adata.obs.to_csv(output)
+    df = pd.read_csv(output, index_col=0)
+    assert list(df.index) == ["c1", "c2", "c3"]
+    assert list(df["n_counts"]) == [100, 600, 700]
+    assert list(df["cell_type"]) == ["T", "B", "T"]
+    assert df["score"].isna().tolist() == [False, True, False]
+    adata.close()
+
+def test_compound_index(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)))
+    adata.close()
+    with h5py.File(file, "a") as f:
+        obs = f["obs"]
+        del obs[obs.attrs["_index"]]
+        str_dtype = h5py.string_dtype()
+        obs.create_dataset("sample", data=["s1", "s1", "s2"], dtype=str_dtype)
+        obs.create_dataset("barcode", data=["AAC", "GTT", "AAC"], dtype=str_dtype)
+        obs.attrs["_index"] = np.array(["sample", "barcode"], dtype=str_dtype)
+
+    adata = read(file)
+    assert list(adata.obs_names) == ["s1_AAC", "s1_GTT", "s2_AAC"]
+    levels = adata.obs.index_levels
+    assert levels.columns == ["sample", "barcode"]
+    assert levels["barcode"].to_list() == ["AAC", "GTT", "AAC"]
+    assert adata.var.index_levels is None
+    adata.close()
+
+def test_to_parquet(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)))
+    adata.obs_names = ["c1", "c2", "c3"]
+    adata.obs = pl.DataFrame({
+        "n_counts": [100, 600, 700],
+        "cell_type": pl.Series(["T", "B", "T"], dtype=pl.Categorical),
+    })
+
+    output = tmp_path / "obs.parquet"
+    adata.obs.to_parquet(output)
+    df = pl.read_parquet(output)
+    assert df.columns == ["index", "n_counts", "cell_type"]
+    assert df["index"].to_list() == ["c1", "c2", "c3"]
+    assert df["cell_type"].dtype == pl.Categorical
+    assert df["cell_type"].to_list() == ["T", "B", "T"]
+
+    other = AnnData(filename=h5ad(tmp_path), X=np.ones((3, 2)))
+    other.set_obs_from_parquet(output)
+    assert list(other.obs_names) == ["c1", "c2", "c3"]
+    assert other.obs["n_counts"].to_list() == [100, 600, 700]
+    assert other.obs["cell_type"].to_list() == ["T", "B", "T"]
+
+    with pytest.raises(Exception):
+        AnnData(filename=h5ad(tmp_path), X=np.ones((4, 2))).set_obs_from_parquet(output)
+    other.close()
+    adata.close()
+
+def test_from_anndata(tmp_path):
+    sc = pytest.importorskip("scanpy")
+
+    x = np.random.default_rng(0).poisson(1.0, (50, 20)).astype(np.float32)
+    py_adata = sc.AnnData(
+        X=csr_matrix(x),
+        obs=pd.DataFrame(
+            {"group": pd.Categorical(["a", "b"] * 25)},
+            index=[f"cell{i}" for i in range(50)],
+        ),
+        var=pd.DataFrame(index=[f"gene{i}" for i in range(20)]),
+    )
+    py_adata.layers["counts"] = x.copy()
+    py_adata.obsm["X_pca"] = x[:, :5]
+    py_adata.uns["pca"] = {"variance": np.arange(5.0)}
+    sc.pp.calculate_qc_metrics(py_adata, inplace=True)
+
+    file = h5ad(tmp_path)
+    adata = AnnData.from_anndata(py_adata, file)
+    assert adata.shape == (50, 20)
+    assert adata.obs_names == list(py_adata.obs_names)
+    assert adata.var_names == list(py_adata.var_names)
+    assert adata.obs["group"].to_list() == list(py_adata.obs["group"])
+    np.testing.assert_array_equal(adata.X[:].todense(), x)
+    np.testing.assert_array_equal(adata.layers["counts"], x)
+    np.testing.assert_array_equal(adata.obsm["X_pca"], py_adata.obsm["X_pca"])
+    np.testing.assert_array_equal(
+        adata.obs["total_counts"].to_numpy(), py_adata.obs["total_counts"].to_numpy(),
+    )
+    np.testing.assert_array_equal(
+        adata.uns["pca"]["variance"], py_adata.uns["pca"]["variance"],
+    )
+    adata.close()
+
+    with pytest.raises(Exception):
+        AnnData.from_anndata(np.ones(3), h5ad(tmp_path))
+
+def test_nested_uns(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file)
+    adata.uns["rank_genes_groups"] = {
+        "names": {"a": np.array(["x", "y"]), "b": 1},
+        "method": "t-test",
+    }
+    adata.close()
+
+    adata = read(file)
+    assert adata.uns["rank_genes_groups"]["names"]["b"] == 1
+    assert adata.uns.get_path("rank_genes_groups/names/b") == 1
+    np.testing.assert_array_equal(adata.uns.get_path("rank_genes_groups/names/a"), ["x", "y"])
+    assert adata.uns.get_path("rank_genes_groups/method") == "t-test"
+    with pytest.raises(Exception):
+        adata.uns.get_path("rank_genes_groups/missing")
+    adata.close()
+
+    adata = read(file, backed=None)
+    assert adata.uns["rank_genes_groups"]["names"]["b"] == 1
+
+def test_embedding(tmp_path):
+    umap = np.random.rand(10, 2)
+    pca = np.random.rand(10, 5)
+    adata = AnnData(
+        X=np.ones((10, 3)),
+        obsm={"X_umap": umap, "X_pca": pca, "spatial": np.zeros((10, 2))},
+        filename=h5ad(tmp_path),
+    )
+    assert sorted(adata.obsm.embeddings()) == ["X_pca", "X_umap"]
+    np.testing.assert_array_equal(adata.embedding("umap"), umap)
+    np.testing.assert_array_equal(adata.embedding("X_umap"), umap)
+    np.testing.assert_array_equal(adata.embedding("pca"), pca)
+    assert adata.embedding("tsne") is None
+
+def test_obs_select(tmp_path):
+    file = h5ad(tmp_path)
+    obs = pl.DataFrame({
+        "tissue": ["cortex", "cortex", "liver", "cortex", "liver", "cortex"],
+        "n": [1, None, 3, None, 5, 6],
+        "barcode": ["a", "b", "c", "d", "e", "f"],
+    })
+    adata = AnnData(X=np.ones((6, 2)), obs=obs, filename=file)
+    adata.obs_names = [f"cell{i}" for i in range(6)]
+    adata.close()
+
+    adata = read(file)
+    rows = [4, 1, 2]
+    df = adata.obs[rows, :]
+    assert df["tissue"].cast(pl.String).to_list() == ["liver", "cortex", "liver"]
+    assert df["n"].to_list() == [5, None, 3]
+    assert df["barcode"].to_list() == ["e", "b", "c"]
+
+    subset = adata.subset(rows, inplace=False)
+    assert list(subset.obs["barcode"]) == ["e", "b", "c"]
+    adata.close()
+
+def test_setter_errors(tmp_path):
+    adata = AnnData(X=np.ones((5, 3)), filename=h5ad(tmp_path))
+    adata.obs_names = [f"c{i}" for i in range(5)]
+
+    bad = np.ones((4, 4))
+    setters = [
+        lambda: setattr(adata, "X", bad),
+        lambda: setattr(adata, "obs", pl.DataFrame({"a": [1, 2]})),
+        lambda: setattr(adata, "var", pl.DataFrame({"a": [1, 2]})),
+        lambda: setattr(adata, "obs_names", ["a", "b"]),
+        lambda: setattr(adata, "var_names", ["a", "b"]),
+        lambda: setattr(adata, "uns", {"a": object()}),
+        lambda: setattr(adata, "obsm", {"a": bad}),
+        lambda: setattr(adata, "obsp", {"a": bad}),
+        lambda: setattr(adata, "varm", {"a": bad}),
+        lambda: setattr(adata, "varp", {"a": bad}),
+        lambda: setattr(adata, "layers", {"a": bad}),
+        lambda: adata.obsm.__setitem__("a", bad),
+        lambda: adata.uns.__setitem__("a", object()),
+    ]
+    for setter in setters:
+        with pytest.raises(Exception):
+            setter()
+    assert adata.shape == (5, 3)
+    assert adata.obs_names == [f"c{i}" for i in range(5)]
+
+    adata.close()
+    with pytest.raises(Exception):
+        adata.obsm = {"a": np.ones((5, 2))}
+    with pytest.raises(Exception):
+        adata.uns = {"a": 1}
+
+def test_usage(tmp_path):
+    adata = AnnData(
+        X=np.zeros((10, 5)),
+        obsm={"X_pca": np.ones((10, 3), dtype=np.float32)},
+        filename=h5ad(tmp_path),
+    )
+    adata.obs_names = [f"c{i}" for i in range(10)]
+    adata.uns["a"] = 1
+
+    df = adata.usage()
+    assert isinstance(df, pl.DataFrame)
+    row = df.filter(pl.col("path") == "X").row(0, named=True)
+    assert row["slot"] == "X"
+    assert row["dtype"] == "f64"
+    assert row["logical_size"] == 400
+    assert row["storage_size"] > 0
+    row = df.filter(pl.col("path") == "obsm/X_pca").row(0, named=True)
+    assert row["slot"] == "obsm" and row["logical_size"] == 120
+    assert "uns/a" in df["path"].to_list()
+
+    summary = adata.usage(summary=True)
+    assert "obsm/X_pca" in summary and "total" in summary
+
+def test_create_mode(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(X=np.zeros((10, 5)), filename=file)
+    adata.close()
+
+    with pytest.raises(Exception, match="already exists"):
+        AnnData(filename=file)
+    with pytest.raises(Exception, match="already exists"):
+        AnnData(filename=file, mode="x")
+
+    adata = AnnData(filename=file, mode="a")
+    assert adata.shape == (10, 5)
+    adata.close()
+
+    adata = AnnData(filename=file, mode="w")
+    assert adata.n_obs == 0
+    adata.close()
+
+    with pytest.raises(Exception, match="unknown mode"):
+        AnnData(filename=h5ad(tmp_path), mode="r")
+
+def test_numpy_layouts(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    x = np.asfortranarray(np.arange(12, dtype=np.float32).reshape(3, 4))
+    obsm = np.arange(9, dtype=np.int16).reshape(3, 3)
+    adata = AnnData(X=x, obsm={"c": obsm}, filename=file)
+    adata.uns["f32"] = np.float32(3.5)
+    adata.uns["u8"] = np.array(7, dtype=np.uint8)
+    adata.uns["f16"] = np.float16(0.5)
+    np.testing.assert_array_equal(adata.X[:], x)
+    assert adata.X[:].dtype == np.float32
+    np.testing.assert_array_equal(adata.obsm["c"], obsm)
+    assert adata.obsm["c"].dtype == np.int16
+    assert adata.uns["f32"] == 3.5
+    adata.close()
+
+    with h5py.File(file, "r") as f:
+        np.testing.assert_array_equal(f["X"][:], x)
+        assert f["uns/f32"].dtype == np.float32
+        assert f["uns/u8"].dtype == np.uint8
+        assert f["uns/f16"][()] == 0.5
+
+def test_qc_metrics(tmp_path):
+    x = np.array([[0, 1, 2], [3, 0, 0], [0, 0, 4], [1, 1, 1]], dtype=np.float32)
+    for data in [x, csr_matrix(x)]:
+        adata = AnnData(X=data, filename=h5ad(tmp_path))
+        adata.var_names = ["a", "b", "c"]
+        adata.calculate_qc_metrics()
+        np.testing.assert_array_equal(adata.obs["total_counts"], x.sum(axis=1))
+        np.testing.assert_array_equal(adata.obs["n_genes_by_counts"], (x != 0).sum(axis=1))
+        np.testing.assert_array_equal(adata.var["n_cells_by_counts"], (x != 0).sum(axis=0))
+        np.testing.assert_allclose(adata.var["mean_counts"], x.mean(axis=0))
+        assert adata.var_names == ["a", "b", "c"]
+        adata.close()
+
+def test_float16(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    x = np.arange(12, dtype=np.float16).reshape(3, 4) / 4
+    adata = AnnData(X=x, obsm={"half": np.asfortranarray(x[:, :2])}, filename=file)
+    adata.layers["csr"] = csr_matrix(x)
+    adata.uns["f16"] = np.float16(0.5)
+    assert adata.X[:].dtype == np.float16
+    np.testing.assert_array_equal(adata.X[:], x)
+    np.testing.assert_array_equal(adata.X[[2, 0], 1:3], x[[2, 0], 1:3])
+    np.testing.assert_array_equal(adata.obsm["half"], x[:, :2])
+    assert adata.layers["csr"].dtype == np.float16
+    np.testing.assert_array_equal(adata.layers["csr"].toarray(), x)
+    np.testing.assert_array_equal(adata.subset([0, 2], out=h5ad(tmp_path), inplace=False).X[:], x[[0, 2]])
+    assert adata.uns["f16"] == 0.5
+    adata.close()
+
+    with h5py.File(file, "r") as f:
+        assert f["X"].dtype == np.float16
+        assert f["layers/csr/data"].dtype == np.float16
+        assert f["uns/f16"].dtype == np.float16
+
+def test_transposed_obsm(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    pca = np.random.rand(20, 5)
+    adata = AnnData(X=np.ones((20, 3)), filename=file)
+    adata.obsm.add_transposed("X_pca", pca)
+    np.testing.assert_array_equal(adata.obsm["X_pca"], pca)
+    np.testing.assert_array_equal(adata.obsm.el("X_pca")[:, 1], pca[:, 1])
+    np.testing.assert_array_equal(adata.obsm.el("X_pca")[[3, 0], :], pca[[3, 0], :])
+    with pytest.raises(Exception):
+        adata.obsm.add_transposed("X_bad", np.ones((5, 20)))
+    adata.close()
+
+    with h5py.File(file, "r") as f:
+        assert f["obsm/X_pca"].shape == (5, 20)
+        np.testing.assert_array_equal(f["obsm/X_pca"][1], pca[:, 1])
+
+def test_bool_columns(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((4, 2)))
+    adata.obs = pl.DataFrame({
+        "flag": pl.Series([1, 0, 0, 1], dtype=pl.Int8),
+        "hinted": pl.Series([0, 1, 1, 1], dtype=pl.Int8),
+        "count": [0, 1, 2, 1],
+    })
+    adata.close()
+    with h5py.File(file, "a") as f:
+        f["obs/hinted"].attrs["dtype"] = "bool"
+
+    adata = read(file, backed="r")
+    assert adata.obs["flag"].dtype == pl.Int8
+    assert adata.obs["hinted"].to_list() == [False, True, True, True]
+    adata.close()
+
+    adata = read(file, backed="r", coerce_bool_columns=True)
+    assert adata.obs["flag"].to_list() == [True, False, False, True]
+    assert adata.obs["count"].dtype == pl.Int64
+    adata.close()
+    with pytest.raises(Exception, match="backed mode"):
+        read(file, backed=None, coerce_bool_columns=True)
+
+def test_nan_as_null(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)))
+    adata.obs = pd.DataFrame({"score": [1.5, np.nan, 3.0], "count": [1, 2, 3]})
+    adata.close()
+
+    adata = read(file, backed="r")
+    assert adata.obs["score"].null_count() == 0
+    assert np.isnan(adata.obs["score"][1])
+    adata.close()
+
+    adata = read(file, backed="r", nan_as_null=True)
+    assert adata.obs["score"].to_list() == [1.5, None, 3.0]
+    assert adata.obs["count"].to_list() == [1, 2, 3]
+    adata.close()
+    with pytest.raises(Exception, match="backed mode"):
+        read(file, backed=None, nan_as_null=True)
+
+def test_assume_sorted_indices(tmp_path):
+    file = h5ad(tmp_path)
+    x = csr_matrix(np.array([[1, 0, 2], [0, 3, 0]]))
+    adata = AnnData(filename=file, X=x)
+    adata.layers["raw"] = x
+    adata.close()
+
+    adata = read(file, backed="r", assume_sorted_indices=True)
+    np.testing.assert_array_equal(adata.X[:].todense(), [[1, 0, 2], [0, 3, 0]])
+    np.testing.assert_array_equal(adata.X[[1], :].todense(), [[0, 3, 0]])
+    np.testing.assert_array_equal(adata.layers["raw"][:].todense(), [[1, 0, 2], [0, 3, 0]])
+    adata.close()
+    with pytest.raises(Exception, match="backed mode"):
+        read(file, backed=None, assume_sorted_indices=True)
+
+def test_reorder_columns(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((2, 2)))
+    adata.obs = pd.DataFrame({"a": [1, 2], "b": [3, 4], "c": [5, 6]})
+    adata.obs.reorder_columns(["c", "a"])
+    assert adata.obs.keys() == ["c", "a", "b"]
+    with pytest.raises(Exception, match="does not exist"):
+        adata.obs.reorder_columns(["x"])
+    adata.close()
+
+    adata = read(file, backed="r")
+    assert adata.obs.keys() == ["c", "a", "b"]
+    assert list(adata.to_memory().obs.columns) == ["c", "a", "b"]
+    adata.close()
+
+def test_obs_query(tmp_path):
+    adata = AnnData(filename=h5ad(tmp_path), X=np.ones((5, 2)))
+    adata.obs = pd.DataFrame({
+        "n_counts": [100, 600, 700, 800, 900],
+        "cell_type": pd.Categorical(["T", "B", "NK", "T", "B"]),
+    })
+    idx = adata.obs_query("n_counts > 500 & cell_type in ['T','NK']")
+    assert idx == [2, 3]
+    assert adata.obs_query("cell_type == 'B' | n_counts <= 100") == [0, 1, 4]
+    subset = adata.subset(idx, out=h5ad(tmp_path), inplace=False)
+    assert subset.obs["n_counts"].to_list() == [700, 800]
+    subset.close()
+    with pytest.raises(Exception, match="does not exist"):
+        adata.obs_query("missing == 1")
+    adata.close()
+
+def test_x_map_chunks(tmp_path):
+    x = np.random.default_rng(0).random((23, 5))
+    adata = AnnData(filename=h5ad(tmp_path), X=x)
+    counts = csr_matrix(np.arange(115, dtype=np.float64).reshape(23, 5))
+    adata.layers["counts"] = counts
+
+    sums = list(adata.X.map_chunks(lambda c: c.sum(axis=1), chunk_size=5))
+    assert len(sums) == 5
+    np.testing.assert_allclose(np.concatenate(sums), x.sum(axis=1))
+    np.testing.assert_array_equal(adata.X[:], x)
+
+    assert adata.X.map_chunks(np.log1p, chunk_size=5, inplace=True) is None
+    np.testing.assert_allclose(adata.X[:], np.log1p(x))
+    adata.layers["counts"].map_chunks(lambda c: c.log1p(), chunk_size=5, inplace=True)
+    np.testing.assert_allclose(adata.layers["counts"][:].toarray(), np.log1p(counts.toarray()))
+
+    with pytest.raises(Exception):
+        adata.X.map_chunks(lambda c: c[:, :2], chunk_size=5, inplace=True)
+    np.testing.assert_allclose(adata.X[:], np.log1p(x))
+    adata.close()
+
+def test_astype(tmp_path):
+    x = np.random.default_rng(0).random((20, 5))
+    adata = AnnData(filename=h5ad(tmp_path), X=x)
+    adata.layers["counts"] = csr_matrix(np.arange(100, dtype=np.float64).reshape(20, 5))
+    assert adata.X.dtype == "float64"
+    adata.X = adata.X.astype("float32")
+    assert adata.X.dtype == "float32"
+    np.testing.assert_allclose(adata.X[:], x, rtol=1e-6)
+    adata.X = x
+    assert adata.X.astype("float32", inplace=True) is None
+    assert adata.X.dtype == "float32"
+    assert adata.X[:].dtype == np.float32
+    np.testing.assert_allclose(adata.X[:], x, rtol=1e-6)
+    counts = adata.layers["counts"].astype(np.int32)
+    assert isinstance(counts, csr_matrix)
+    assert counts.dtype == np.int32
+    assert counts.nnz == 99
+    assert adata.layers["counts"].dtype == "float64"
+    adata.layers["counts"].astype("uint32", inplace=True)
+    assert adata.layers["counts"].dtype == "uint32"
+    np.testing.assert_array_equal(adata.layers["counts"][:].toarray(), counts.toarray())
+    with pytest.raises(Exception):
+        adata.X.astype("U10", inplace=True)
+
+    adata.layers["signed"] = csr_matrix(-np.ones((20, 5), dtype=np.int64))
+    with pytest.raises(Exception):
+        adata.layers["signed"].astype("uint32", inplace=True)
+    assert adata.layers["signed"].dtype == "int64"
+    adata.close()
+
+def test_filter_to(tmp_path):
+    x = np.arange(15, dtype=np.float64).reshape(5, 3)
+    adata = AnnData(filename=h5ad(tmp_path), X=x)
+    adata.obs = pd.DataFrame({"cell_type": pd.Categorical(["T", "B", "T", "NK", "T"])})
+    adata.obs_names = ["c1", "c2", "c3", "c4", "c5"]
+    adata.var_names = ["g1", "g2", "g3"]
+    out = h5ad(tmp_path)
+    adata.filter_to(out, obs_query="cell_type == 'T'", var_names=["g3", "g1"])
+    subset = read(out)
+    assert subset.obs_names == ["c1", "c3", "c5"]
+    assert subset.var_names == ["g3", "g1"]
+    np.testing.assert_array_equal(subset.X[:], x[[0, 2, 4]][:, [2, 0]])
+    subset.close()
+
+    bad = tmp_path / "bad.h5ad"
+    with pytest.raises(Exception, match="does not exist"):
+        adata.filter_to(bad, obs_query="missing == 1")
+    with pytest.raises(Exception, match="at most one"):
+        adata.filter_to(bad, obs_query="cell_type == 'T'", obs_indices=[0])
+    assert not bad.exists()
+    adata.close()
+
+def test_minimal_file(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    x = np.arange(12, dtype=np.int32).reshape(4, 3)
+    with h5py.File(file, "w") as f:
+        f.create_dataset("X", data=x)
+
+    adata = read(file, backed="r")
+    assert adata.shape == (4, 3)
+    assert adata.obs_names == ["0", "1", "2", "3"]
+    assert adata.obsm.keys() == []
+    subset = adata.subset([3, 1], out=h5ad(tmp_path), inplace=False)
+    assert subset.obs_names == ["3", "1"]
+    np.testing.assert_array_equal(subset.X[:], x[[3, 1]])
+    subset.close()
+    adata.close()
+
+def test_concat_lazy(tmp_path):
+    def new(key, n):
+        file = h5ad(tmp_path)
+        adata = AnnData(filename=file, X=np.arange(n * 4).reshape(n, 4) + n)
+        adata.obs_names = [f"{key}_{i}" for i in range(n)]
+        adata.close()
+        return (key, file)
+
+    eager = AnnDataSet(
+        adatas=[new("a", 3), new("b", 5)], filename=h5ad(tmp_path), add_key="sample",
+    ).to_adata()
+    lazy = AnnData.concat_lazy([new("a", 3), new("b", 5)], add_key="sample")
+    assert lazy.shape == eager.shape
+    assert lazy.obs_names == list(eager.obs_names)
+    assert lazy.obs["sample"].to_list() == list(eager.obs["sample"])
+    np.testing.assert_array_equal(lazy.X[:], eager.X)
+    np.testing.assert_array_equal(lazy.X[[6, 1]], eager.X[[6, 1]])
+    lazy.close()
+
+def test_anndataset_set_obs_column(tmp_path):
+    a = AnnData(filename=h5ad(tmp_path), X=np.ones((2, 2)))
+    a.obs = pd.DataFrame({"n": [1, 2]})
+    b = AnnData(filename=h5ad(tmp_path), X=np.ones((3, 2)))
+    b.obs = pd.DataFrame({"n": [3, 4, 5]})
+    dataset = AnnDataSet(adatas=[("a", a), ("b", b)], filename=h5ad(tmp_path), add_key="sample")
+
+    dataset.obs["label"] = pl.Series(["x", "y", "x", "z", "y"])
+    assert "label" in dataset.obs.keys()
+    assert dataset.obs["label"].to_list() == ["x", "y", "x", "z", "y"]
+
+    # Invalid values are rejected before any component is written.
+    with pytest.raises(Exception, match="nulls"):
+        dataset.obs["n"] = pl.Series(["p", None, "q", "r", "s"])
+    assert dataset.obs["n"].to_list() == [1, 2, 3, 4, 5]
+    dataset.close()
+
+def test_concat(tmp_path):
+    a = AnnData(filename=h5ad(tmp_path), X=np.array([[1, 2], [3, 4]]))
+    a.var_names = ["g1", "g2"]
+    b = AnnData(filename=h5ad(tmp_path), X=np.array([[5, 6]]))
+    b.var_names = ["g2", "g3"]
+
+    adata = AnnData.concat([("a", a), ("b", b)], filename=h5ad(tmp_path), join="outer", fill_value=0)
+    assert adata.var_names == ["g1", "g2", "g3"]
+    assert adata.obs["sample"].to_list() == ["a", "a", "b"]
+    np.testing.assert_array_equal(adata.X[:], [[1, 2, 0], [3, 4, 0], [0, 5, 6]])
+    adata.close()
+
+    adata = AnnData.concat([("a", a), ("b", b)], filename=h5ad(tmp_path))
+    assert adata.var_names == ["g2"]
+    np.testing.assert_array_equal(adata.X[:], [[2], [4], [5]])
+    adata.close()
+
+def test_custom_attrs(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((4, 2)))
+    adata.obsm["spatial"] = np.zeros((4, 2))
+    spatial = adata.obsm.el("spatial")
+    assert spatial.get_attr("units") is None
+    spatial.set_attr("units", "um")
+    spatial.set_attr("scale", 0.5)
+    adata.obs = pd.DataFrame({"a": [1, 2, 3, 4]})
+    adata.obs.set_attr("description", "cell metadata")
+    with pytest.raises(Exception, match="reserved"):
+        spatial.set_attr("encoding-type", "csr_matrix")
+    adata.close()
+
+    adata = read(file, backed="r")
+    spatial = adata.obsm.el("spatial")
+    assert spatial.get_attr("units") == "um"
+    assert spatial.get_attr("scale") == 0.5
+    assert spatial.get_attr("encoding-type") == "array"
+    assert adata.obs.get_attr("description") == "cell metadata"
+    adata.close()
+
+def test_no_x(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((4, 2)))
+    adata.obs_names = ["a", "b", "c", "d"]
+    adata.var_names = ["g1", "g2"]
+    adata.X = None
+    assert adata.X is None
+    assert adata.shape == (4, 2)
+    adata.X = np.ones((4, 2))
+    del adata.X
+    assert adata.X is None
+    adata.X = np.ones((4, 2))
+    adata.del_x()
+    assert adata.X is None
+    adata.close()
+
+    adata = read(file)
+    assert adata.X is None
+    assert adata.shape == (4, 2)
+    subset = adata.subset(["d", "a"], out=h5ad(tmp_path), inplace=False)
+    assert subset.X is None
+    assert subset.obs_names == ["d", "a"]
+    subset.close()
+    adata.close()
+
+def test_validate(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((3, 2)), obs=pl.DataFrame({"a": [1, 2, 3]}))
+    adata.obsm["x"] = np.zeros((3, 4))
+    adata.uns["n"] = 1
+    assert adata.validate() == []
+    adata.close()
+
+    with h5py.File(file, "a") as f:
+        del f["obsm/x"].attrs["encoding-version"]
+        group = f["uns"].create_group("bad_csr")
+        group.attrs["encoding-type"] = "csr_matrix"
+        group.attrs["encoding-version"] = "0.1.0"
+        group.attrs["shape"] = [3, 2]
+        group.create_dataset("indptr", data=np.array([0, 1, 2]))
+        group.create_dataset("indices", data=np.array([0, 1]))
+        group.create_dataset("data", data=np.array([1.0, 2.0]))
+
+    adata = read(file, backed="r")
+    assert sorted(adata.validate()) == [
+        "obsm/x: missing attribute 'encoding-version'",
+        "uns/bad_csr: 'indptr' has length 3, expected 4 for shape [3, 2]",
+    ]
+    adata.close()
+
+def test_delete_slots(tmp_path):
+    import h5py
+
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file, X=np.ones((4, 3)))
+    adata.obs_names = ["a", "b", "c", "d"]
+    adata.obsm["pca"] = np.zeros((4, 2))
+    adata.obsp["distances"] = np.zeros((4, 4))
+    adata.uns["n"] = 1
+    adata.del_obsp()
+    adata.del_uns()
+    assert "obsp:" not in repr(adata) and "uns:" not in repr(adata)
+    adata.del_obs()
+    assert adata.obs_names == ["0", "1", "2", "3"]
+    adata.del_x()
+    adata.del_obsm()
+    assert adata.n_obs == 0
+    adata.close()
+
+    with h5py.File(file, "r") as f:
+        for slot in ["X", "obs", "obsm", "obsp", "uns"]:
+            assert slot not in f
+    adata = read(file, backed="r")
+    assert adata.n_obs == 0
+    assert adata.X is None
+    adata.close()
+
+def test_obs_as_pandas(tmp_path):
+    adata = AnnData(filename=h5ad(tmp_path), X=np.ones((4, 2)))
+    adata.obs_names = ["a", "b", "c", "d"]
+    adata.obs = pl.DataFrame({
+        "n": [1, 2, 3, 4],
+        "tissue": pl.Series(["x", "y", "x", "x"], dtype=pl.Categorical),
+    })
+    obs = adata.obs_as_pandas()
+    assert isinstance(obs, pd.DataFrame)
+    assert list(obs.index) == ["a", "b", "c", "d"]
+    assert isinstance(obs["tissue"].dtype, pd.CategoricalDtype)
+    assert list(obs["n"]) == [1, 2, 3, 4]
+
+    var = adata.var_as_pandas()
+    assert isinstance(var, pd.DataFrame)
+    assert list(var.index) == ["0", "1"]
+    assert var.shape == (2, 0)
+    adata.close()
+
+def test_save_as(tmp_path):
+    old = h5ad(tmp_path)
+    new = h5ad(tmp_path)
+    adata = AnnData(filename=old, X=np.ones((3, 2)))
+    adata.save_as(new)
+    adata.uns["x"] = 1
+    assert str(adata.filename) == new
+    adata.close()
+    for file, saved in [(new, True), (old, False)]:
+        adata = read(file, backed="r")
+        assert ("x" in adata.uns.keys()) == saved
+        adata.close()
+
+    adata = read(new, backed="r+")
+    with pytest.raises(Exception):
+        adata.filename = old
+    moved = h5ad(tmp_path)
+    adata.filename = moved
+    assert not Path(new).exists()
+    assert adata.X[:].shape == (3, 2)
+    adata.close()
+
+def test_swmr(tmp_path):
+    file = h5ad(tmp_path)
+    x = np.random.rand(5, 3)
+    adata = AnnData(filename=file, X=x, swmr=True)
+    adata.uns.append_to_list("log", 1.0)
+    modified = adata.last_modified
+    adata.close()
+
+    writer = read(file, backed="swmr+")
+    reader = read(file, backed="swmr")
+    np.testing.assert_array_equal(reader.X[:], x)
+    writer.uns.append_to_list("log", 2.0)
+    writer.flush()
+    assert writer.last_modified == modified
+    reader.close()
+    reader.open("swmr")
+    assert reader.shape == (5, 3)
+    np.testing.assert_array_equal(reader.uns["log"], [1.0, 2.0])
+    reader.close()
+    writer.close()
+
+def test_sample_obs(tmp_path):
+    x = np.arange(200).reshape(100, 2)
+    adata = AnnData(filename=h5ad(tmp_path), X=x)
+    adata.obs_names = [str(i) for i in range(100)]
+
+    indices = adata.sample_obs(10, seed=1, return_indices=True)
+    assert len(indices) == 10 and len(set(indices)) == 10
+    assert indices == adata.sample_obs(10, seed=1, return_indices=True)
+    sample = adata.sample_obs(10, seed=1)
+    assert sample.n_obs == 10
+    assert list(sample.obs_names) == [str(i) for i in indices]
+    np.testing.assert_array_equal(sample.X, x[indices])
+    with pytest.raises(Exception):
+        adata.sample_obs(101)
+
+@pytest.mark.skipif(not _anndata_rs._TRACE, reason="built without the trace feature")
+def test_verbosity(tmp_path, caplog):
+    set_verbosity("debug")
+    try:
+        adata = AnnData(X=np.ones((3, 2)), filename=h5ad(tmp_path))
+        adata.X[:]
+        assert any(r.name.startswith("anndata") for r in caplog.records)
+    finally:
+        set_verbosity("warning")
+
+def test_get_indexer(tmp_path):
+    adata = AnnData(X=np.ones((4, 3)), filename=h5ad(tmp_path))
+    adata.obs_names = ["a", "b", "c", "d"]
+    adata.var_names = ["g1", "g2", "g3"]
+
+    np.testing.assert_array_equal(adata.obs_names.get_indexer(["d", "x", "a"]), [3, -1, 0])
+    np.testing.assert_array_equal(adata.var_names.get_indexer(["g2", "g4"]), [1, -1])
+    assert adata.obs_names == ["a", "b", "c", "d"]
+    assert adata.obs_names != ["a", "b"]
+    assert len(adata.obs_names) == 4 and adata.obs_names[-1] == "d"
+    assert adata.obs_names[1:3] == ["b", "c"]
+    assert "c" in adata.obs_names and "x" not in adata.obs_names
+    assert isinstance(adata.obs_names, list)
+    assert adata.obs_names + ["e"] == ["a", "b", "c", "d", "e"]
+    assert adata.obs_names.index("c") == 2
+
+def test_append_to_list(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename=file)
+    for i in range(100):
+        adata.uns.append_to_list("log", f"step {i}")
+    with pytest.raises(Exception):
+        adata.uns.append_to_list("log", 1.5)
+    adata.close()
+
+    adata = read(file)
+    assert list(adata.uns["log"]) == [f"step {i}" for i in range(100)]
+    adata.close()
+
+def test_obsm_dataframe(tmp_path):
+    import anndata
+
+    obs_names = [f"cell{i}" for i in range(6)]
+    df = pd.DataFrame(
+        {
+            "score": np.arange(6, dtype=np.float64),
+            "label": pd.Categorical(["a", "b", "a", "b", "c", "c"]),
+        },
+        index=obs_names,
+    )
+    py_adata = anndata.AnnData(X=np.ones((6, 2)), obs=pd.DataFrame(index=obs_names))
+    py_adata.obsm["meta"] = df
+    file = h5ad(tmp_path)
+    py_adata.write_h5ad(file)
+
+    adata = read(file)
+    assert adata.obsm.keys() == ["meta"]
+    meta = adata.obsm["meta"]
+    assert isinstance(meta, pl.DataFrame)
+    assert meta.columns == ["score", "label"]
+    assert meta["score"].to_list() == list(df["score"])
+    assert meta["label"].to_list() == list(df["label"])
+    assert adata.obsm.el("meta")[2:4, :]["score"].to_list() == [2.0, 3.0]
+
+    result = adata.to_memory()
+    assert list(result.obsm["meta"].columns) == ["score", "label"]
+    assert list(result.obsm["meta"].index) == obs_names
+    adata.close()
+
+def test_var(tmp_path):
+    X = sp.random(1234, 57, 0.1, format="csr", dtype=np.float64)
+    adata = AnnData(filename = h5ad(tmp_path), X = X)
+    dense = X.toarray()
+    np.testing.assert_allclose(adata.X.var(axis=0), dense.var(axis=0))
+    np.testing.assert_allclose(adata.X.var(axis=1), dense.var(axis=1))
+    np.testing.assert_allclose(adata.X.var(0, ddof=1), dense.var(axis=0, ddof=1))
+    adata.X = dense
+    np.testing.assert_allclose(adata.X.var(axis=0), dense.var(axis=0))
+    adata.close()
+
+def test_chunk_cache(tmp_path):
+    import h5py
+    import time
+
+    # Each chunk holds 2 MiB, more than HDF5's default cache of 1 MiB.
+    x = np.random.default_rng(0).random((2000, 512))
+    file = h5ad(tmp_path)
+    AnnData(filename=file, X=x).close()
+    with h5py.File(file, "a") as f:
+        del f["X"]
+        f.create_dataset("X", data=x, chunks=(512, 512), compression="gzip")
+        f["X"].attrs["encoding-type"] = "array"
+        f["X"].attrs["encoding-version"] = "0.2.0"
+
+    def row_reads(adata):
+        start = time.perf_counter()
+        for i in range(200):
+            np.testing.assert_array_equal(adata.X[i:i + 1, :], x[i:i + 1, :])
+        adata.close()
+        return time.perf_counter() - start
+
+    uncached = row_reads(read(file, backed="r"))
+    cached = row_reads(read(file, backed="r", chunk_cache_bytes=64 * 1024 * 1024))
+    # Timings are noisy, only catch a cache that makes reads much slower.
+    assert cached < uncached * 2
+
+    with pytest.raises(Exception):
+        read(file, backed=None, chunk_cache_bytes=1024)
+
+def test_read_into_numpy(tmp_path):
+    obsm = np.random.default_rng(0).random((100, 8)).astype(np.float32)
+    x = csr_matrix(np.random.default_rng(1).integers(0, 3, (100, 20)), dtype=np.float64)
+    adata = AnnData(filename=h5ad(tmp_path), X=x, obsm={"X_pca": obsm})
+
+    result = adata.obsm["X_pca"]
+    assert result.dtype == np.float32
+    np.testing.assert_array_equal(result, obsm)
+    np.testing.assert_array_equal(adata.obsm.el("X_pca")[10:20, 2:5], obsm[10:20, 2:5])
+
+    result = adata.X[:]
+    assert result.indices.dtype == np.int32
+    np.testing.assert_array_equal(result.toarray(), x.toarray())
+    np.testing.assert_array_equal(adata.X[[3, 1], :].toarray(), x[[3, 1], :].toarray())
+
+    adata.obsm.el("X_pca").enable_cache()
+    np.testing.assert_array_equal(adata.obsm["X_pca"], obsm)
+
+def test_set_chunk_cache(tmp_path):
+    x = np.random.default_rng(0).random((3000, 200))
+    adata = AnnData(filename=h5ad(tmp_path), X=x, obsm={"sparse": csr_matrix(x)})
+
+    adata.X.set_chunk_cache(8 * 1024 * 1024)
+    np.testing.assert_array_equal(adata.X[100:200, :], x[100:200, :])
+    chunks = [c for c, _, _ in adata.X.chunked(700)]
+    np.testing.assert_array_equal(np.concatenate(chunks), x)
+
+    with pytest.raises(Exception):
+        adata.obsm.el("sparse").set_chunk_cache(1024)
+
+def test_write_obs_columns(tmp_path):
+    obs = pd.DataFrame({c: np.arange(4) * i for i, c in enumerate("abcde")}, index=list("wxyz"))
+    adata = AnnData(filename=h5ad(tmp_path), X=np.ones((4, 3)), obs=obs)
+    output = h5ad(tmp_path)
+    adata.write(output, obs_columns=["d", "b"])
+
+    slim = read(output, backed="r")
+    assert slim.obs.keys() == ["d", "b"]
+    assert list(slim.obs_names) == list("wxyz")
+    np.testing.assert_array_equal(slim.obs["d"], obs["d"])
+    np.testing.assert_array_equal(slim.X[:], np.ones((4, 3)))
+    slim.close()
+
+    with pytest.raises(Exception, match="no column named 'f'"):
+        adata.write(h5ad(tmp_path), obs_columns=["a", "f"])
+
+def test_view(tmp_path):
+    x = np.arange(60).reshape(10, 6)
+    obs = pd.DataFrame({"a": np.arange(10)}, index=[f"c{i}" for i in range(10)])
+    adata = AnnData(filename=h5ad(tmp_path), X=x, obs=obs)
+
+    view = adata[2:9, [5, 1, 3]]
+    chained = view[[6, 0, 2], 1:3]
+    assert chained.shape == (3, 2)
+    np.testing.assert_array_equal(chained.X, x[2:9][:, [5, 1, 3]][[6, 0, 2]][:, 1:3])
+    assert list(chained.obs_names) == ["c8", "c2", "c4"]
+    assert list(chained.obs["a"]) == [8, 2, 4]
+    assert list(view[["c4", "c2"]].obs_names) == ["c4", "c2"]
+    assert adata.shape == (10, 6)
+
+    output = h5ad(tmp_path)
+    chained.write(output)
+    subset = read(output, backed="r")
+    np.testing.assert_array_equal(subset.X[:], chained.X)
+    subset.close()
+
+    adata.subset(slice(0, 5))
+    with pytest.raises(Exception, match="no longer valid"):
+        chained.X
+    with pytest.raises(Exception, match="no longer valid"):
+        view[0:2]
+    assert adata[1:3].shape == (2, 6)
+
+def test_neighbors(tmp_path):
+    file = h5ad(tmp_path)
+    adata = AnnData(filename = file, X = np.ones((20, 5)))
+    conn = sp.random(20, 20, 0.2, format="csr", dtype=np.float64)
+    dist = sp.random(20, 20, 0.2, format="csr", dtype=np.float64)
+    params = {"n_neighbors": 15, "metric": "euclidean", "method": "umap"}
+
+    assert adata.neighbors() is None
+    adata.set_neighbors(conn, dist, params)
+    adata.set_neighbors(conn, dist, key="knn")
+    assert set(adata.obsp.keys()) == {"connectivities", "distances", "knn_connectivities", "knn_distances"}
+    assert adata.uns["neighbors"]["connectivities_key"] == "connectivities"
+    adata.close()
+
+    adata = read(file)
+    neighbors = adata.neighbors()
+    np.testing.assert_array_equal(neighbors["connectivities"].todense(), conn.todense())
+    np.testing.assert_array_equal(neighbors["distances"].todense(), dist.todense())
+    assert neighbors["params"] == params
+    assert adata.neighbors("knn")["params"] == {}
+    adata.close()
+
 @given(
     x1 = arrays(np.int64, (7, 13)),
     x2 = arrays(np.int64, (9, 13)),
@@ -74,6 +74,59 @@ def test_subset(x, obs, obsm, obsp, varm, varp, indices, indices2, tmp_path):
     np.testing.assert_array_equal(adata.obsm["y"].todense(), obsm[indices, :])
     np.testing.assert_array_equal(adata_subset.layers["raw"], x[indices, :])
 
+@pytest.mark.parametrize("s", [
+    slice(None, None, -1),
+    slice(10, 0, -2),
+    slice(5, 5),
+    slice(5, 2),
+    slice(None, None, 3),
+    slice(-3, None),
+])
+def test_subset_slice(s, tmp_path):
+    x = np.arange(20 * 7).reshape(20, 7)
+    adata = AnnData(X=x, filename=h5ad(tmp_path))
+    adata.obs_names = [str(i) for i in range(20)]
+
+    np.testing.assert_array_equal(adata.X[s], x[s])
+    np.testing.assert_array_equal(adata.X[s, s], x[s, s])
+
+    adata_subset = adata.subset(s, inplace=False)
+    assert adata_subset.n_obs == len(range(20)[s])
+    np.testing.assert_array_equal(adata_subset.X[:], x[s])
+    assert adata_subset.obs_names == [str(i) for i in range(20)[s]]
+
+def test_element_indexing(tmp_path):
+    x = np.arange(20 * 7).reshape(20, 7)
+    adata = AnnData(X=x, filename=h5ad(tmp_path))
+    adata.obs_names = [str(i) for i in range(20)]
+    adata.var_names = ["Gene" + str(i) for i in range(7)]
+    mask = np.arange(20) % 3 == 0
+
+    np.testing.assert_array_equal(adata.X[10:20, [0, 5, 6]], x[10:20, [0, 5, 6]])
+    np.testing.assert_array_equal(adata.X[mask, :], x[mask, :])
+    np.testing.assert_array_equal(adata.X[list(mask)], x[mask])
+    np.testing.assert_array_equal(adata.X[..., 2:], x[..., 2:])
+    np.testing.assert_array_equal(adata.X[[-1, 0]], x[[-1, 0]])
+    np.testing.assert_array_equal(adata.X[:, "Gene3"], x[:, 3])
+    np.testing.assert_array_equal(adata.X[["1", "5"], ["Gene0", "Gene6"]], x[np.ix_([1, 5], [0, 6])])
+    np.testing.assert_array_equal(adata.X[3], x[3])
+    np.testing.assert_array_equal(adata.X[..., -1], x[..., -1])
+    assert adata.X[3, 4] == x[3, 4]
+
+    with pytest.raises(IndexError):
+        adata.X[20]
+    with pytest.raises(IndexError):
+        adata.X[0, 0, 0]
+    with pytest.raises(IndexError):
+        adata.X[mask[1:]]
+    with pytest.raises(KeyError):
+        adata.X[:, "missing"]
+
+    adata.layers["sparse"] = csr_matrix(x)
+    np.testing.assert_array_equal(adata.layers.el("sparse")[mask, 1:3].todense(), x[mask, 1:3])
+    with pytest.raises(IndexError):
+        adata.layers.el("sparse")[:, "Gene3"]
+
 def test_chunk(tmp_path):
     X = random(5000, 50, 0.1, format="csr", dtype=np.int64)
     adata = AnnData(
@@ -113,6 +166,26 @@ def test_chunk(tmp_path):
         s_ += m.sum(axis = 0)
     np.testing.assert_array_equal(s, s_)
 
+def test_chunk_by_nnz(tmp_path):
+    # Dense rows interleaved with nearly empty ones.
+    x = random(1000, 50, 0.01, format="lil", dtype=np.int64)
+    x[::100] = 1
+    x = x.tocsr()
+    adata = AnnData(X=x, filename=h5ad(tmp_path))
+
+    rows = []
+    for m, start, end in adata.X.chunked(target_nnz=200):
+        assert m.shape[0] == end - start
+        assert m.nnz - m[-1].nnz < 200
+        rows.extend(range(start, end))
+        np.testing.assert_array_equal(m.todense(), x[start:end].todense())
+    assert rows == list(range(1000))
+
+    with pytest.raises(Exception):
+        adata.X.chunked()
+    with pytest.raises(Exception):
+        adata.X.chunked(10, target_nnz=200)
+
 @given(
     x1 = arrays(np.int64, (15, 179)),
     x2 = arrays(np.int64, (47, 179)),